@@ -6,40 +6,114 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::{ToTokens, quote};
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
 use syn::{Attribute, Data, DeriveInput, Fields, Ident, Lit, Type, parse_macro_input};
 
 // Container / field attribute models -----------------------------------------------------------
 #[derive(Debug, Clone, Default)]
 struct ContainerAttributes {
-    rename_all: Option<RenameRule>,
+    rename_all_serialize: Option<RenameRule>,
+    rename_all_deserialize: Option<RenameRule>,
     deny_unknown_fields: bool,
+    /// Only implement `FromLlsd`/`IntoLlsd`, without the `TryFrom`/`From` bridges.
+    traits_only: bool,
+    /// Read through this type, then convert with `From`.
+    from: Option<Type>,
+    /// Convert a clone with `Into` to this type, then write it.
+    into: Option<Type>,
+    /// Encode as an array in field declaration order instead of a map.
+    as_array: bool,
+    /// Also generate a `<Name>Builder` (emitted by `LlsdInto` / `LlsdFromTo`).
+    builder: bool,
 }
 
 #[derive(Debug, Clone)]
 struct FieldAttributes {
-    rename: Option<String>,
+    rename_serialize: Option<String>,
+    rename_deserialize: Option<String>,
     skip: bool,
     skip_serializing: bool,
     skip_deserializing: bool,
+    /// Written but never read; unlike `skip_deserializing` the key is not a
+    /// known input key, so `deny_unknown_fields` rejects it.
+    serialize_only: bool,
+    /// Read but never written.
+    deserialize_only: bool,
     default: DefaultType,
     flatten: bool,
-    with: Option<syn::Path>,
+    /// `path = "/a/b"`: map keys leading to a nested location.
+    path: Option<Vec<String>>,
+    with: Option<With>,
+    /// `Some(false)` opts a byte container out of the default `Binary` encoding.
+    binary: Option<bool>,
+    validators: Vec<Validator>,
+    /// Read empty strings, nil UUIDs and empty URIs as `None`.
+    empty_as_none: bool,
 }
 impl Default for FieldAttributes {
     fn default() -> Self {
         Self {
-            rename: None,
+            rename_serialize: None,
+            rename_deserialize: None,
             skip: false,
             skip_serializing: false,
             skip_deserializing: false,
+            serialize_only: false,
+            deserialize_only: false,
             default: DefaultType::None,
             flatten: false,
+            path: None,
             with: None,
+            binary: None,
+            validators: Vec::new(),
+            empty_as_none: false,
         }
     }
 }
 
+/// Custom per-field conversion replacing `FromLlsd` / `IntoLlsd`.
+#[derive(Debug, Clone)]
+enum With {
+    /// A module with `serialize(&T) -> Llsd` and `deserialize(&Llsd) -> Result<T>`.
+    Module(syn::Path),
+    /// `date_format = "%Y-%m-%d"`: a chrono format string.
+    DateFormat(syn::LitStr),
+}
+impl With {
+    /// Call producing an `Llsd` from `value` (a reference to the field).
+    fn serialize(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            With::Module(path) => quote! { #path::serialize(#value) },
+            With::DateFormat(format) => {
+                quote! { llsd_rs::derive::date_format::custom::serialize(#value, #format) }
+            }
+        }
+    }
+
+    /// Call converting `llsd` (an `&Llsd`), returning `Result<T, LlsdError>`.
+    fn deserialize(&self, llsd: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let call = match self {
+            With::Module(path) => quote! { #path::deserialize(#llsd) },
+            With::DateFormat(format) => {
+                quote! { llsd_rs::derive::date_format::custom::deserialize(#llsd, #format) }
+            }
+        };
+        quote! { #call.map_err(|e| llsd_rs::LlsdError::from_anyhow(e.into())) }
+    }
+}
+
+/// Checks applied to a field value right after it is converted from LLSD.
+#[derive(Debug, Clone)]
+enum Validator {
+    /// `validate(range = "1..=100")`: the original text is kept for the message.
+    Range(Box<syn::Expr>, String),
+    MinLen(syn::LitInt),
+    NonEmpty,
+    Func(syn::Path),
+}
+
 #[derive(Debug, Clone, Default)]
 enum DefaultType {
     #[default]
@@ -59,6 +133,27 @@ enum RenameRule {
     Upper,
 }
 impl RenameRule {
+    fn parse(lit: &syn::LitStr) -> syn::Result<Self> {
+        Ok(match lit.value().as_str() {
+            "snake_case" => RenameRule::Snake,
+            "kebab-case" => RenameRule::Kebab,
+            "camelCase" => RenameRule::Camel,
+            "PascalCase" => RenameRule::Pascal,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            other => {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "unknown rename rule `{other}`; expected one of snake_case, kebab-case, \
+                         camelCase, PascalCase, SCREAMING_SNAKE_CASE, lowercase, UPPERCASE"
+                    ),
+                ));
+            }
+        })
+    }
+
     fn apply(&self, name: &str) -> String {
         match self {
             RenameRule::Snake => to_snake_case(name),
@@ -73,6 +168,104 @@ impl RenameRule {
 }
 
 // Parsing -------------------------------------------------------------------------------------
+const CONTAINER_ATTRIBUTES: &[&str] = &[
+    "rename_all",
+    "deny_unknown_fields",
+    "traits_only",
+    "builder",
+    "as_array",
+    "from",
+    "into",
+];
+const FIELD_ATTRIBUTES: &[&str] = &[
+    "rename",
+    "skip",
+    "skip_serializing",
+    "skip_deserializing",
+    "serialize_only",
+    "deserialize_only",
+    "default",
+    "empty_as_none",
+    "flatten",
+    "path",
+    "with",
+    "int_as",
+    "date_format",
+    "compact_date",
+    "uuid_as_binary",
+    "binary",
+    "validate",
+];
+
+/// Field attributes that each replace the field's conversion, so at most one
+/// of them may be given.
+const CONVERSION_ATTRIBUTES: &[&str] = &[
+    "with",
+    "int_as",
+    "date_format",
+    "compact_date",
+    "uuid_as_binary",
+];
+
+fn conversion_conflict(meta: &syn::meta::ParseNestedMeta, key: &str) -> syn::Error {
+    let others: Vec<String> = CONVERSION_ATTRIBUTES
+        .iter()
+        .filter(|other| **other != key)
+        .map(|other| format!("`{other}`"))
+        .collect();
+    meta.error(format!(
+        "`{key}` cannot be combined with {}",
+        others.join(", ")
+    ))
+}
+
+/// Error for an unrecognized `#[llsd(...)]` key, pointing at the key. Keys that
+/// belong on the other level (container vs field) or differ only by a typo get
+/// a suggestion; anything else lists the accepted keys.
+fn unknown_attribute(
+    meta: &syn::meta::ParseNestedMeta,
+    expected: &[&str],
+    other: &[&str],
+    other_level: &str,
+) -> syn::Error {
+    let key = meta
+        .path
+        .get_ident()
+        .map(|i| i.unraw().to_string())
+        .unwrap_or_else(|| meta.path.to_token_stream().to_string().replace(' ', ""));
+    let message = if other.contains(&key.as_str()) {
+        format!("`{key}` is a {other_level} attribute and is not accepted here")
+    } else if let Some(close) = expected.iter().find(|e| is_typo_of(&key, e)) {
+        format!("unknown llsd attribute `{key}`; did you mean `{close}`?")
+    } else {
+        format!(
+            "unknown llsd attribute `{key}`; expected one of {}",
+            expected.join(", ")
+        )
+    };
+    syn::Error::new_spanned(&meta.path, message)
+}
+
+/// Edit distance of at most one (a single insertion, deletion or substitution)
+/// or a single transposition; enough to catch `rename_al` or `defualt`.
+fn is_typo_of(key: &str, candidate: &str) -> bool {
+    let a: Vec<char> = key.chars().collect();
+    let b: Vec<char> = candidate.chars().collect();
+    if a == b || a.len().abs_diff(b.len()) > 1 {
+        return false;
+    }
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let (ra, rb) = (&a[prefix..], &b[prefix..]);
+    match (ra.len(), rb.len()) {
+        (la, lb) if la == lb => {
+            ra[1..] == rb[1..]
+                || (la >= 2 && ra[0] == rb[1] && ra[1] == rb[0] && ra[2..] == rb[2..])
+        }
+        (la, lb) if la > lb => ra[1..] == *rb,
+        _ => *ra == rb[1..],
+    }
+}
+
 fn parse_container_attributes(attrs: &[Attribute]) -> syn::Result<ContainerAttributes> {
     let mut out = ContainerAttributes::default();
     for attr in attrs {
@@ -81,28 +274,39 @@ fn parse_container_attributes(attrs: &[Attribute]) -> syn::Result<ContainerAttri
         }
         attr.parse_nested_meta(|meta| {
             if meta.path.is_ident("rename_all") {
-                let value = meta.value()?;
-                let lit: Lit = value.parse()?;
-                if let Lit::Str(s) = lit {
-                    out.rename_all = Some(match s.value().as_str() {
-                        "snake_case" => RenameRule::Snake,
-                        "kebab-case" => RenameRule::Kebab,
-                        "camelCase" => RenameRule::Camel,
-                        "PascalCase" => RenameRule::Pascal,
-                        "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
-                        "lowercase" => RenameRule::Lower,
-                        "UPPERCASE" => RenameRule::Upper,
-                        _ => return Err(syn::Error::new(s.span(), "Invalid rename_all value")),
-                    });
-                    Ok(())
-                } else {
-                    Err(syn::Error::new(lit.span(), "Expected string literal"))
+                let (ser, de) = parse_rename_pair(&meta)?;
+                if let Some(lit) = ser {
+                    out.rename_all_serialize = Some(RenameRule::parse(&lit)?);
+                }
+                if let Some(lit) = de {
+                    out.rename_all_deserialize = Some(RenameRule::parse(&lit)?);
                 }
+                Ok(())
             } else if meta.path.is_ident("deny_unknown_fields") {
                 out.deny_unknown_fields = true;
                 Ok(())
+            } else if meta.path.is_ident("traits_only") {
+                out.traits_only = true;
+                Ok(())
+            } else if meta.path.is_ident("builder") {
+                out.builder = true;
+                Ok(())
+            } else if meta.path.is_ident("as_array") {
+                out.as_array = true;
+                Ok(())
+            } else if meta.path.is_ident("from") {
+                out.from = Some(parse_type_value(meta.value()?)?);
+                Ok(())
+            } else if meta.path.is_ident("into") {
+                out.into = Some(parse_type_value(meta.value()?)?);
+                Ok(())
             } else {
-                Err(meta.error("Unknown container attribute"))
+                Err(unknown_attribute(
+                    &meta,
+                    CONTAINER_ATTRIBUTES,
+                    FIELD_ATTRIBUTES,
+                    "field",
+                ))
             }
         })?;
     }
@@ -117,14 +321,14 @@ fn parse_field_attributes(attrs: &[Attribute]) -> syn::Result<FieldAttributes> {
         }
         attr.parse_nested_meta(|meta| {
             if meta.path.is_ident("rename") {
-                let value = meta.value()?;
-                let lit: Lit = value.parse()?;
-                if let Lit::Str(s) = lit {
-                    out.rename = Some(s.value());
-                    Ok(())
-                } else {
-                    Err(syn::Error::new(lit.span(), "Expected string literal"))
+                let (ser, de) = parse_rename_pair(&meta)?;
+                if let Some(lit) = ser {
+                    out.rename_serialize = Some(lit.value());
                 }
+                if let Some(lit) = de {
+                    out.rename_deserialize = Some(lit.value());
+                }
+                Ok(())
             } else if meta.path.is_ident("skip") {
                 out.skip = true;
                 Ok(())
@@ -134,6 +338,14 @@ fn parse_field_attributes(attrs: &[Attribute]) -> syn::Result<FieldAttributes> {
             } else if meta.path.is_ident("skip_deserializing") {
                 out.skip_deserializing = true;
                 Ok(())
+            } else if meta.path.is_ident("serialize_only") {
+                out.serialize_only = true;
+                out.skip_deserializing = true;
+                Ok(())
+            } else if meta.path.is_ident("deserialize_only") {
+                out.deserialize_only = true;
+                out.skip_serializing = true;
+                Ok(())
             } else if meta.path.is_ident("default") {
                 if meta.input.peek(syn::token::Eq) {
                     let value = meta.value()?;
@@ -143,22 +355,211 @@ fn parse_field_attributes(attrs: &[Attribute]) -> syn::Result<FieldAttributes> {
                     out.default = DefaultType::Default;
                 }
                 Ok(())
+            } else if meta.path.is_ident("empty_as_none") {
+                out.empty_as_none = true;
+                Ok(())
             } else if meta.path.is_ident("flatten") {
                 out.flatten = true;
                 Ok(())
+            } else if meta.path.is_ident("path") {
+                let lit = parse_lit_str(meta.value()?)?;
+                out.path = Some(parse_pointer(&lit)?);
+                Ok(())
             } else if meta.path.is_ident("with") {
                 let value = meta.value()?;
                 let path: syn::Path = value.parse()?;
-                out.with = Some(path);
+                if out.with.is_some() {
+                    return Err(conversion_conflict(&meta, "with"));
+                }
+                out.with = Some(With::Module(path));
+                Ok(())
+            } else if meta.path.is_ident("int_as") {
+                let lit = parse_lit_str(meta.value()?)?;
+                let encoding = match lit.value().as_str() {
+                    "string" => quote! { string },
+                    "real" => quote! { real },
+                    "binary" => quote! { binary },
+                    _ => {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "Expected \"string\", \"real\" or \"binary\"",
+                        ));
+                    }
+                };
+                if out.with.is_some() {
+                    return Err(conversion_conflict(&meta, "int_as"));
+                }
+                out.with = Some(With::Module(syn::parse_quote!(
+                    llsd_rs::derive::int_as::#encoding
+                )));
+                out.binary = Some(false);
+                Ok(())
+            } else if meta.path.is_ident("date_format") {
+                let lit = parse_lit_str(meta.value()?)?;
+                let with = match lit.value().as_str() {
+                    "epoch_seconds" | "seconds" => With::Module(syn::parse_quote!(
+                        llsd_rs::derive::date_format::epoch_seconds
+                    )),
+                    "epoch_millis" | "millis" => With::Module(syn::parse_quote!(
+                        llsd_rs::derive::date_format::epoch_millis
+                    )),
+                    "rfc3339" => With::Module(syn::parse_quote!(
+                        llsd_rs::derive::date_format::rfc3339
+                    )),
+                    format if format.contains('%') => With::DateFormat(lit.clone()),
+                    _ => {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "Expected \"epoch_seconds\", \"epoch_millis\", \"rfc3339\" or a chrono format string",
+                        ));
+                    }
+                };
+                if out.with.is_some() {
+                    return Err(conversion_conflict(&meta, "date_format"));
+                }
+                out.with = Some(with);
+                Ok(())
+            } else if meta.path.is_ident("compact_date") {
+                if out.with.is_some() {
+                    return Err(conversion_conflict(&meta, "compact_date"));
+                }
+                out.with = Some(With::Module(syn::parse_quote!(
+                    llsd_rs::derive::date_format::epoch_real
+                )));
+                Ok(())
+            } else if meta.path.is_ident("uuid_as_binary") {
+                if out.with.is_some() {
+                    return Err(conversion_conflict(&meta, "uuid_as_binary"));
+                }
+                out.with = Some(With::Module(syn::parse_quote!(
+                    llsd_rs::derive::uuid_as_binary
+                )));
                 Ok(())
+            } else if meta.path.is_ident("binary") {
+                if meta.input.peek(syn::token::Eq) {
+                    let lit: syn::LitBool = meta.value()?.parse()?;
+                    out.binary = Some(lit.value);
+                } else {
+                    out.binary = Some(true);
+                }
+                Ok(())
+            } else if meta.path.is_ident("validate") {
+                if meta.input.peek(syn::token::Eq) {
+                    out.validators
+                        .push(Validator::Func(parse_path_value(meta.value()?)?));
+                    return Ok(());
+                }
+                meta.parse_nested_meta(|inner| {
+                    if inner.path.is_ident("range") {
+                        let lit: syn::LitStr = inner.value()?.parse()?;
+                        let expr: syn::Expr = lit.parse()?;
+                        if !matches!(expr, syn::Expr::Range(_)) {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "Expected a range like \"1..=100\"",
+                            ));
+                        }
+                        out.validators
+                            .push(Validator::Range(Box::new(expr), lit.value()));
+                        Ok(())
+                    } else if inner.path.is_ident("min_len") {
+                        out.validators
+                            .push(Validator::MinLen(inner.value()?.parse()?));
+                        Ok(())
+                    } else if inner.path.is_ident("non_empty") {
+                        out.validators.push(Validator::NonEmpty);
+                        Ok(())
+                    } else {
+                        Err(inner.error("Unknown validator"))
+                    }
+                })
             } else {
-                Err(meta.error("Unknown field attribute"))
+                Err(unknown_attribute(&meta, FIELD_ATTRIBUTES, CONTAINER_ATTRIBUTES, "container"))
             }
         })?;
     }
     Ok(out)
 }
 
+/// Parse `name = "x"` (both directions) or `name(serialize = "a", deserialize = "b")`.
+fn parse_rename_pair(
+    meta: &syn::meta::ParseNestedMeta,
+) -> syn::Result<(Option<syn::LitStr>, Option<syn::LitStr>)> {
+    if meta.input.peek(syn::token::Eq) {
+        let lit = parse_lit_str(meta.value()?)?;
+        return Ok((Some(lit.clone()), Some(lit)));
+    }
+    let mut ser = None;
+    let mut de = None;
+    meta.parse_nested_meta(|inner| {
+        if inner.path.is_ident("serialize") {
+            ser = Some(parse_lit_str(inner.value()?)?);
+            Ok(())
+        } else if inner.path.is_ident("deserialize") {
+            de = Some(parse_lit_str(inner.value()?)?);
+            Ok(())
+        } else {
+            Err(inner.error("Expected `serialize` or `deserialize`"))
+        }
+    })?;
+    Ok((ser, de))
+}
+
+fn parse_lit_str(input: syn::parse::ParseStream) -> syn::Result<syn::LitStr> {
+    let lit: Lit = input.parse()?;
+    if let Lit::Str(s) = lit {
+        Ok(s)
+    } else {
+        Err(syn::Error::new(lit.span(), "Expected string literal"))
+    }
+}
+
+/// Split a `/a/b` pointer into map keys, decoding `~1` and `~0` like `Llsd::pointer`.
+fn parse_pointer(lit: &syn::LitStr) -> syn::Result<Vec<String>> {
+    let value = lit.value();
+    let segments: Vec<String> = match value.strip_prefix('/') {
+        Some(rest) => rest
+            .split('/')
+            .map(|x| x.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+        None => {
+            return Err(syn::Error::new(
+                lit.span(),
+                "Expected a path like \"/agent/id\"",
+            ));
+        }
+    };
+    if segments.iter().any(String::is_empty) {
+        return Err(syn::Error::new(
+            lit.span(),
+            "Path segments must not be empty",
+        ));
+    }
+    Ok(segments)
+}
+
+/// Accept either a bare path (`validate = check_fn`) or a string literal
+/// containing one (`validate = "path::to_fn"`).
+fn parse_path_value(input: syn::parse::ParseStream) -> syn::Result<syn::Path> {
+    if input.peek(syn::LitStr) {
+        let lit: syn::LitStr = input.parse()?;
+        lit.parse()
+    } else {
+        input.parse()
+    }
+}
+
+/// Accept either a bare type (`from = Wire`) or a string literal containing
+/// one (`from = "Wire"`).
+fn parse_type_value(input: syn::parse::ParseStream) -> syn::Result<Type> {
+    if input.peek(syn::LitStr) {
+        let lit: syn::LitStr = input.parse()?;
+        lit.parse()
+    } else {
+        input.parse()
+    }
+}
+
 // Trait impl generation -----------------------------------------------------------------------
 #[proc_macro_derive(LlsdFrom, attributes(llsd))]
 pub fn derive_llsd_from(input: TokenStream) -> TokenStream {
@@ -191,77 +592,266 @@ fn expand(input: TokenStream, mode: Mode) -> TokenStream {
 // Internal representation of a parsed field
 struct FieldInfo {
     ident: Ident,
+    ty: Type,
     attrs: FieldAttributes,
-    llsd_name: String,
+    /// Key written by `LlsdInto` (the first segment for `path` fields).
+    ser_name: String,
+    /// Key read by `LlsdFrom` (the first segment for `path` fields).
+    de_name: String,
     is_option: bool,
 }
 
 fn impl_expand(ast: DeriveInput, mode: Mode) -> syn::Result<proc_macro2::TokenStream> {
     let name = &ast.ident;
     let container_attrs = parse_container_attributes(&ast.attrs)?;
-    let data = match ast.data {
-        Data::Struct(s) => s,
-        _ => return Err(syn::Error::new_spanned(name, "Only structs supported")),
-    };
-    let fields_named = match data.fields {
-        Fields::Named(f) => f.named,
-        _ => return Err(syn::Error::new_spanned(name, "Only named fields supported")),
-    };
-
-    // Collect field info
-    let mut known_keys_tokens: Vec<String> = Vec::new();
-    let mut field_infos: Vec<FieldInfo> = Vec::new();
+    let wants_from = matches!(mode, Mode::From | Mode::Both);
+    let wants_into = matches!(mode, Mode::Into | Mode::Both);
+    let wants_builder = wants_into && container_attrs.builder;
 
-    for field in fields_named.iter() {
-        let ident = field.ident.clone().unwrap();
-        let ty = field.ty.clone();
-        let attrs = parse_field_attributes(&field.attrs)?;
-        let llsd_name = field_llsd_name(&ident, &attrs, &container_attrs);
-        let is_option = is_type_option(&ty);
-        if !attrs.skip && !attrs.flatten {
-            known_keys_tokens.push(llsd_name.clone());
-        }
-        field_infos.push(FieldInfo {
-            ident,
-            attrs,
-            llsd_name,
-            is_option,
-        });
-    }
+    // Types converted entirely through `from`/`into` need no field info, and
+    // may be enums or tuple structs.
+    let field_infos = if (wants_from && container_attrs.from.is_none())
+        || (wants_into && container_attrs.into.is_none())
+        || wants_builder
+    {
+        collect_fields(ast.data.clone(), name, &container_attrs)?
+    } else {
+        Vec::new()
+    };
 
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
-    let from_impl = match mode {
-        Mode::From | Mode::Both => Some(gen_from(
+    let from_impl = wants_from.then(|| {
+        gen_from(
             &field_infos,
             name,
             &impl_generics,
             &ty_generics,
             where_clause,
             &container_attrs,
-        )),
-        _ => None,
-    };
-    let into_impl = match mode {
-        Mode::Into | Mode::Both => Some(gen_into(
+        )
+    });
+    let into_impl = wants_into.then(|| {
+        gen_into(
             &field_infos,
             name,
             &impl_generics,
             &ty_generics,
             where_clause,
             &container_attrs,
-        )),
-        _ => None,
-    };
+        )
+    });
+
+    let builder = wants_builder.then(|| gen_builder(&field_infos, &ast));
 
-    let from_tokens = from_impl.map(|body| {
-        quote! { #body }
+    Ok(quote! { #from_impl #into_impl #builder })
+}
+
+/// `<Name>Builder` with one setter per field. Fields that are not `Option`,
+/// `default` or skipped are required, and `build` reports the first one
+/// missing as a `missing field` error.
+fn gen_builder(fields: &[FieldInfo], ast: &DeriveInput) -> proc_macro2::TokenStream {
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let builder = format_ident!("{}Builder", name);
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let params = &ast.generics.params;
+
+    let idents: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+    let types: Vec<&Type> = fields.iter().map(|f| &f.ty).collect();
+    let setters = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let doc = format!("Set `{}`.", ident.unraw());
+        match option_inner_type(&f.ty) {
+            Some(inner) => quote! {
+                #[doc = #doc]
+                pub fn #ident(mut self, value: impl ::core::convert::Into<#inner>) -> Self {
+                    self.#ident = Some(Some(value.into()));
+                    self
+                }
+            },
+            None => {
+                let ty = &f.ty;
+                quote! {
+                    #[doc = #doc]
+                    pub fn #ident(mut self, value: impl ::core::convert::Into<#ty>) -> Self {
+                        self.#ident = Some(value.into());
+                        self
+                    }
+                }
+            }
+        }
     });
-    let into_tokens = into_impl.map(|body| {
-        quote! { #body }
+    let finish = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let fallback = match (&f.attrs.default, f.is_option || f.attrs.skip) {
+            (DefaultType::Path(p), _) => quote! { .unwrap_or_else(#p) },
+            (DefaultType::Default, _) | (DefaultType::None, true) => {
+                quote! { .unwrap_or_default() }
+            }
+            (DefaultType::None, false) => {
+                let field = ident.unraw().to_string();
+                quote! { .ok_or_else(|| llsd_rs::LlsdError::missing_field(#field))? }
+            }
+        };
+        quote! { #ident: self.#ident #fallback }
     });
+    let builder_doc = format!("Builder for [`{name}`].");
+    let builder_fn_doc = format!("Start a [`{builder}`].");
 
-    Ok(quote! { #from_tokens #into_tokens })
+    quote! {
+        #[doc = #builder_doc]
+        #vis struct #builder <#params> #where_clause {
+            #( #idents: ::core::option::Option<#types>, )*
+        }
+        impl #impl_generics ::core::default::Default for #builder #ty_generics #where_clause {
+            fn default() -> Self {
+                Self { #( #idents: None, )* }
+            }
+        }
+        impl #impl_generics #builder #ty_generics #where_clause {
+            /// Builder with no fields set.
+            pub fn new() -> Self {
+                ::core::default::Default::default()
+            }
+            #( #setters )*
+            /// Build the value, failing if a required field was not set.
+            pub fn build(self) -> ::core::result::Result<#name #ty_generics, llsd_rs::LlsdError> {
+                Ok(#name { #( #finish, )* })
+            }
+        }
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc = #builder_fn_doc]
+            pub fn builder() -> #builder #ty_generics {
+                #builder::new()
+            }
+        }
+    }
+}
+
+fn collect_fields(
+    data: Data,
+    name: &Ident,
+    container_attrs: &ContainerAttributes,
+) -> syn::Result<Vec<FieldInfo>> {
+    let hint = "convert through a struct with `#[llsd(from = \"Wire\", into = \"Wire\")]`, \
+                or implement `FromLlsd`/`IntoLlsd` by hand";
+    let data = match data {
+        Data::Struct(s) => s,
+        Data::Enum(e) => {
+            return Err(syn::Error::new_spanned(
+                e.enum_token,
+                format!(
+                    "field-based llsd derives need a struct with named fields; for `{name}`, {hint}"
+                ),
+            ));
+        }
+        Data::Union(u) => {
+            return Err(syn::Error::new_spanned(
+                u.union_token,
+                format!(
+                    "field-based llsd derives need a struct with named fields; for `{name}`, {hint}"
+                ),
+            ));
+        }
+    };
+    let fields_named = match data.fields {
+        Fields::Named(f) => f.named,
+        fields => {
+            return Err(syn::Error::new_spanned(
+                fields,
+                format!(
+                    "field-based llsd derives need named fields; give `{name}` named fields, or {hint}"
+                ),
+            ));
+        }
+    };
+
+    let mut field_infos: Vec<FieldInfo> = Vec::new();
+    let mut errors: Option<syn::Error> = None;
+    let mut report = |error: syn::Error| match &mut errors {
+        Some(errors) => errors.combine(error),
+        None => errors = Some(error),
+    };
+
+    for field in fields_named.iter() {
+        let ident = field.ident.clone().unwrap();
+        let ty = field.ty.clone();
+        let mut attrs = match parse_field_attributes(&field.attrs) {
+            Ok(attrs) => attrs,
+            Err(error) => {
+                report(error);
+                continue;
+            }
+        };
+        let conflict = |message: &str| {
+            syn::Error::new_spanned(&ident, format!("field `{}`: {message}", ident.unraw()))
+        };
+        if attrs.serialize_only && attrs.deserialize_only {
+            report(conflict(
+                "`serialize_only` cannot be combined with `deserialize_only`; use `skip` to leave the field out entirely",
+            ));
+        }
+        if attrs.path.is_some()
+            && (attrs.flatten
+                || attrs.rename_serialize.is_some()
+                || attrs.rename_deserialize.is_some())
+        {
+            report(conflict(
+                "`path` cannot be combined with `flatten` or `rename`; the path's last segment is the key",
+            ));
+        }
+        if attrs.empty_as_none && (!is_type_option(&ty) || attrs.flatten) {
+            let message = if attrs.flatten {
+                format!(
+                    "field `{}`: `empty_as_none` cannot be used on a flattened field",
+                    ident.unraw()
+                )
+            } else {
+                format!(
+                    "field `{}`: `empty_as_none` requires an `Option` field; wrap the type in `Option<...>`",
+                    ident.unraw()
+                )
+            };
+            report(syn::Error::new_spanned(&field.ty, message));
+        }
+        if attrs.binary == Some(true) && attrs.with.is_some() {
+            report(conflict("`binary` cannot be combined with `with`"));
+        }
+        let byte_container = is_byte_container(option_inner_type(&ty).unwrap_or(&ty));
+        if attrs.with.is_none() && !attrs.flatten && attrs.binary.unwrap_or(byte_container) {
+            attrs.with = Some(With::Module(syn::parse_quote!(llsd_rs::derive::binary)));
+        }
+        let (ser_name, de_name) = match &attrs.path {
+            Some(path) => (path[0].clone(), path[0].clone()),
+            None => (
+                field_llsd_name(
+                    &ident,
+                    attrs.rename_serialize.as_ref(),
+                    container_attrs.rename_all_serialize,
+                ),
+                field_llsd_name(
+                    &ident,
+                    attrs.rename_deserialize.as_ref(),
+                    container_attrs.rename_all_deserialize,
+                ),
+            ),
+        };
+        let is_option = is_type_option(&ty);
+        field_infos.push(FieldInfo {
+            ident,
+            ty,
+            attrs,
+            ser_name,
+            de_name,
+            is_option,
+        });
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(field_infos),
+    }
 }
 
 fn gen_from(
@@ -272,23 +862,313 @@ fn gen_from(
     where_clause: Option<&syn::WhereClause>,
     container_attrs: &ContainerAttributes,
 ) -> proc_macro2::TokenStream {
-    let deny_unknown = container_attrs.deny_unknown_fields;
+    let bridges = (!container_attrs.traits_only).then(|| {
+        quote! {
+            impl #impl_generics ::core::convert::TryFrom<&llsd_rs::Llsd> for #name #ty_generics #where_clause {
+                type Error = anyhow::Error;
+                fn try_from(llsd: &llsd_rs::Llsd) -> ::core::result::Result<Self, Self::Error> {
+                    <Self as llsd_rs::FromLlsd>::from_llsd(llsd).map_err(::core::convert::Into::into)
+                }
+            }
+            impl #impl_generics ::core::convert::TryFrom<llsd_rs::Llsd> for #name #ty_generics #where_clause {
+                type Error = anyhow::Error;
+                fn try_from(llsd: llsd_rs::Llsd) -> ::core::result::Result<Self, Self::Error> {
+                    <Self as llsd_rs::FromLlsd>::from_llsd_owned(llsd).map_err(::core::convert::Into::into)
+                }
+            }
+        }
+    });
 
-    // Keys we consider known (exclude skip + flatten)
-    let known_key_literals: Vec<proc_macro2::TokenStream> = fields
+    if let Some(wire) = &container_attrs.from {
+        return quote! {
+            impl #impl_generics llsd_rs::FromLlsd for #name #ty_generics #where_clause {
+                fn from_llsd(llsd: &llsd_rs::Llsd) -> ::core::result::Result<Self, llsd_rs::LlsdError> {
+                    <#wire as llsd_rs::FromLlsd>::from_llsd(llsd).map(::core::convert::From::from)
+                }
+                fn from_llsd_owned(llsd: llsd_rs::Llsd) -> ::core::result::Result<Self, llsd_rs::LlsdError> {
+                    <#wire as llsd_rs::FromLlsd>::from_llsd_owned(llsd).map(::core::convert::From::from)
+                }
+                fn known_keys(keys: &mut ::std::vec::Vec<&'static str>) {
+                    <#wire as llsd_rs::FromLlsd>::known_keys(keys);
+                }
+            }
+            #bridges
+        };
+    }
+
+    if container_attrs.as_array {
+        let body = gen_array_from(fields);
+        return quote! {
+            impl #impl_generics llsd_rs::FromLlsd for #name #ty_generics #where_clause {
+                #body
+            }
+            #bridges
+        };
+    }
+
+    // Keys we consider known; flattened fields contribute their own keys
+    let known_keys: Vec<proc_macro2::TokenStream> = fields
         .iter()
-        .filter(|f| !f.attrs.skip && !f.attrs.flatten)
-        .map(|f| {
-            let k = &f.llsd_name;
-            quote! { #k }
+        .filter(|f| !f.attrs.skip && !f.attrs.serialize_only)
+        .filter_map(|f| {
+            if !f.attrs.flatten {
+                let k = &f.de_name;
+                Some(quote! { keys.push(#k); })
+            } else if !f.attrs.skip_deserializing {
+                let ty = &f.ty;
+                Some(quote! { <#ty as llsd_rs::FromLlsd>::known_keys(keys); })
+            } else {
+                None
+            }
         })
         .collect();
+    let deny_unknown = container_attrs
+        .deny_unknown_fields
+        .then(|| gen_deny_unknown(fields));
+
+    // Without flatten and path fields every read field owns exactly one key,
+    // so the map is scanned once and each key is dispatched by a `match`.
+    let single_pass = gen_key_scan(fields, container_attrs.deny_unknown_fields);
+    let map_deny = single_pass.is_none().then_some(&deny_unknown);
+
+    let (borrowed_lets, field_inits) = gen_field_reads(fields, false, single_pass.is_some());
+    let (owned_lets, _) = gen_field_reads(fields, true, single_pass.is_some());
+    let updates = gen_field_updates(fields);
+
+    quote! {
+        impl #impl_generics llsd_rs::FromLlsd for #name #ty_generics #where_clause {
+            fn from_llsd(llsd: &llsd_rs::Llsd) -> ::core::result::Result<Self, llsd_rs::LlsdError> {
+                let map = match llsd.as_map() {
+                    Some(map) => map,
+                    None => return Err(llsd_rs::LlsdError::invalid_type("map", llsd)),
+                };
+                #map_deny
+                #single_pass
+                #( #borrowed_lets )*
+                Ok(Self { #( #field_inits ),* })
+            }
+            fn from_llsd_owned(llsd: llsd_rs::Llsd) -> ::core::result::Result<Self, llsd_rs::LlsdError> {
+                #[allow(unused_mut, unused_variables)]
+                let mut map = match llsd {
+                    llsd_rs::Llsd::Map(map) => map,
+                    other => return Err(llsd_rs::LlsdError::invalid_type("map", &other)),
+                };
+                #map_deny
+                #single_pass
+                #( #owned_lets )*
+                Ok(Self { #( #field_inits ),* })
+            }
+            fn update_from_llsd(&mut self, llsd: &llsd_rs::Llsd) -> ::core::result::Result<(), llsd_rs::LlsdError> {
+                let map = match llsd.as_map() {
+                    Some(map) => map,
+                    None => return Err(llsd_rs::LlsdError::invalid_type("map", llsd)),
+                };
+                #deny_unknown
+                #( #updates )*
+                Ok(())
+            }
+            fn known_keys(keys: &mut ::std::vec::Vec<&'static str>) {
+                #( #known_keys )*
+            }
+        }
+        #bridges
+    }
+}
+
+/// Keys the struct itself accepts (flattened fields excluded), in field order.
+fn own_known_keys(fields: &[FieldInfo]) -> Vec<&str> {
+    let mut keys: Vec<&str> = Vec::new();
+    for f in fields {
+        if !f.attrs.skip
+            && !f.attrs.serialize_only
+            && !f.attrs.flatten
+            && !keys.contains(&f.de_name.as_str())
+        {
+            keys.push(&f.de_name);
+        }
+    }
+    keys
+}
+
+/// `deny_unknown_fields` check against the struct's own keys as a `match` on
+/// the key string; only keys it does not know consult the flattened types'
+/// `known_keys`, which are collected at most once.
+fn gen_deny_unknown(fields: &[FieldInfo]) -> proc_macro2::TokenStream {
+    let own = own_known_keys(fields);
+    let flattened: Vec<&Type> = fields
+        .iter()
+        .filter(|f| f.attrs.flatten && !f.attrs.skip && !f.attrs.skip_deserializing)
+        .map(|f| &f.ty)
+        .collect();
+    let unknown = if flattened.is_empty() {
+        quote! { return Err(llsd_rs::LlsdError::unknown_field(key)) }
+    } else {
+        quote! {{
+            let known = flattened.get_or_insert_with(|| {
+                let mut keys = ::std::vec::Vec::new();
+                #( <#flattened as llsd_rs::FromLlsd>::known_keys(&mut keys); )*
+                keys
+            });
+            if !known.contains(&key.as_str()) {
+                return Err(llsd_rs::LlsdError::unknown_field(key));
+            }
+        }}
+    };
+    let known_arm = (!own.is_empty()).then(|| quote! { #( #own )|* => {} });
+    let lazy = (!flattened.is_empty())
+        .then(|| quote! { let mut flattened: ::core::option::Option<::std::vec::Vec<&'static str>> = None; });
+    quote! {
+        #lazy
+        for key in map.keys() {
+            match key.as_str() {
+                #known_arm
+                _ => #unknown,
+            }
+        }
+    }
+}
 
-    // Build per-field initialization expressions
-    let mut field_inits: Vec<proc_macro2::TokenStream> = Vec::new();
+/// Single-pass key dispatch for structs without `flatten` or `path` fields:
+/// one `__slot_<field>` per read field, filled by a `match` over the map's
+/// keys (folding in `deny_unknown_fields`). `None` when a field needs the map
+/// itself.
+fn gen_key_scan(fields: &[FieldInfo], deny_unknown: bool) -> Option<proc_macro2::TokenStream> {
+    let read: Vec<&FieldInfo> = fields
+        .iter()
+        .filter(|f| !f.attrs.skip && !f.attrs.skip_deserializing)
+        .collect();
+    if read
+        .iter()
+        .any(|f| f.attrs.flatten || f.attrs.path.is_some())
+    {
+        return None;
+    }
+    let keys: Vec<&str> = read.iter().map(|f| f.de_name.as_str()).collect();
+    if keys.iter().enumerate().any(|(i, k)| keys[..i].contains(k)) {
+        return None;
+    }
+    if read.is_empty() && !deny_unknown {
+        return Some(quote! {});
+    }
+    let slots: Vec<Ident> = read
+        .iter()
+        .map(|f| format_ident!("__slot_{}", f.ident))
+        .collect();
+    let other_known: Vec<&str> = own_known_keys(fields)
+        .into_iter()
+        .filter(|k| !keys.contains(k))
+        .collect();
+    let other_arm = (!other_known.is_empty()).then(|| quote! { #( #other_known )|* => {} });
+    let unknown = if deny_unknown {
+        quote! { return Err(llsd_rs::LlsdError::unknown_field(&key)) }
+    } else {
+        quote! { {} }
+    };
+    // `map` is a `&LlsdMap` in `from_llsd` and an owned one (moved from) in
+    // `from_llsd_owned`; the same tokens serve both.
+    Some(if read.is_empty() {
+        quote! {
+            for key in map.keys() {
+                match key.as_str() {
+                    #other_arm
+                    _ => #unknown,
+                }
+            }
+        }
+    } else {
+        quote! {
+            #( let mut #slots = ::core::option::Option::None; )*
+            for (key, value) in map {
+                match key.as_str() {
+                    #( #keys => #slots = ::core::option::Option::Some(value), )*
+                    #other_arm
+                    _ => #unknown,
+                }
+            }
+        }
+    })
+}
 
+/// `FromLlsd` methods for `as_array` structs: element `i` is the `i`-th field
+/// that is not skipped, and the array length must match exactly.
+fn gen_array_from(fields: &[FieldInfo]) -> proc_macro2::TokenStream {
+    let mut index = 0usize;
+    let mut borrowed = Vec::new();
+    let mut owned = Vec::new();
     for f in fields {
         let ident = &f.ident;
+        let from_llsd = from_llsd_for(&f.ty);
+        if f.attrs.skip || f.attrs.skip_deserializing {
+            let default_expr = match &f.attrs.default {
+                DefaultType::Path(p) => quote! { #p() },
+                _ => quote! { ::core::default::Default::default() },
+            };
+            borrowed.push(quote! { #ident: #default_expr });
+            owned.push(quote! { #ident: #default_expr });
+            continue;
+        }
+        let (by_ref, by_value) = match f.attrs.with.as_ref() {
+            Some(with) => (
+                with.deserialize(quote! { &items[#index] }),
+                with.deserialize(quote! { &items.next().expect("length checked") }),
+            ),
+            None => (
+                quote! { #from_llsd::from_llsd(&items[#index]) },
+                quote! { #from_llsd::from_llsd_owned(items.next().expect("length checked")) },
+            ),
+        };
+        borrowed.push(quote! { #ident: #by_ref.map_err(|e| e.with_index(#index))? });
+        owned.push(quote! { #ident: #by_value.map_err(|e| e.with_index(#index))? });
+        index += 1;
+    }
+    let len = index;
+    quote! {
+        fn from_llsd(llsd: &llsd_rs::Llsd) -> ::core::result::Result<Self, llsd_rs::LlsdError> {
+            let items = match llsd {
+                llsd_rs::Llsd::Array(items) if items.len() == #len => items,
+                llsd_rs::Llsd::Array(items) => return Err(llsd_rs::LlsdError::invalid_length(#len, items.len())),
+                _ => return Err(llsd_rs::LlsdError::invalid_type("array", llsd)),
+            };
+            Ok(Self { #( #borrowed ),* })
+        }
+        fn from_llsd_owned(llsd: llsd_rs::Llsd) -> ::core::result::Result<Self, llsd_rs::LlsdError> {
+            #[allow(unused_mut)]
+            let mut items = match llsd {
+                llsd_rs::Llsd::Array(items) if items.len() == #len => items.into_iter(),
+                llsd_rs::Llsd::Array(items) => return Err(llsd_rs::LlsdError::invalid_length(#len, items.len())),
+                other => return Err(llsd_rs::LlsdError::invalid_type("array", &other)),
+            };
+            Ok(Self { #( #owned ),* })
+        }
+    }
+}
+
+/// Build one `let` binding per field plus the matching struct initializers.
+///
+/// The borrowed variant reads from `map: &LlsdMap` (and `llsd` for flatten);
+/// the owned variant moves values out of `map: LlsdMap` with `remove`, so large
+/// strings and binaries are never cloned. Flattened fields are read last from
+/// whatever keys remain; keys a flattened type also knows are cloned rather
+/// than removed, so both variants hand it the same keys.
+fn gen_field_reads(
+    fields: &[FieldInfo],
+    owned: bool,
+    single_pass: bool,
+) -> (Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>) {
+    let mut lets = Vec::new();
+    let mut flatten_lets = Vec::new();
+    let mut inits = Vec::new();
+    let flatten_count = fields
+        .iter()
+        .filter(|f| f.attrs.flatten && !f.attrs.skip && !f.attrs.skip_deserializing)
+        .count();
+    let mut flatten_seen = 0;
+
+    for f in fields {
+        let ident = &f.ident;
+        let from_llsd = from_llsd_for(option_inner_type(&f.ty).unwrap_or(&f.ty));
+        let local = format_ident!("__field_{}", ident);
+        inits.push(quote! { #ident: #local });
 
         // Skip or skip_deserializing => just supply default
         if f.attrs.skip || f.attrs.skip_deserializing {
@@ -298,170 +1178,440 @@ fn gen_from(
                 }
                 DefaultType::Path(p) => quote! { #p() },
             };
-            field_inits.push(quote! { #ident: #default_expr });
+            lets.push(quote! { let #local = #default_expr; });
             continue;
         }
 
-        // Flatten just delegates a full conversion from the whole value
+        // Flatten delegates a full conversion from the (remaining) map. An
+        // `Option` is only `Some` when one of the inner type's keys is present.
         if f.attrs.flatten {
-            field_inits.push(quote! { #ident: ::core::convert::TryFrom::try_from(llsd)? });
+            flatten_seen += 1;
+            let convert = if !owned {
+                quote! { #from_llsd::from_llsd(llsd) }
+            } else if flatten_seen == flatten_count {
+                quote! { #from_llsd::from_llsd_owned(llsd_rs::Llsd::Map(::core::mem::take(&mut map))) }
+            } else {
+                quote! { #from_llsd::from_llsd_owned(llsd_rs::Llsd::Map(map.clone())) }
+            };
+            let init = match option_inner_type(&f.ty) {
+                Some(inner) => quote! {{
+                    let mut keys = ::std::vec::Vec::new();
+                    <#inner as llsd_rs::FromLlsd>::known_keys(&mut keys);
+                    if keys.iter().any(|k| map.contains_key(*k)) {
+                        Some(#convert?)
+                    } else {
+                        None
+                    }
+                }},
+                None => quote! { #convert? },
+            };
+            flatten_lets.push(quote! { let #local = #init; });
             continue;
         }
 
-        let key = &f.llsd_name;
-        let with_path = f.attrs.with.as_ref();
-
-        let init_expr = if f.is_option {
-            // Option fields
-            match &f.attrs.default {
-                DefaultType::None => {
-                    if let Some(p) = with_path {
-                        quote! { map.get(#key).map(|v| #p::deserialize(v)).transpose()? }
-                    } else {
-                        quote! { map.get(#key).map(|v| ::core::convert::TryFrom::try_from(v)).transpose()? }
-                    }
-                }
-                DefaultType::Default => {
-                    if let Some(p) = with_path {
-                        quote! { map.get(#key).map(|v| #p::deserialize(v)).transpose()? }
+        let key = &f.de_name;
+        let qualify = error_path(f);
+        let slot = format_ident!("__slot_{}", ident);
+        let first = f
+            .attrs
+            .path
+            .as_ref()
+            .and_then(|path| path.first())
+            .unwrap_or(key);
+        let (fetch, value_ref) = match (&f.attrs.path, owned) {
+            (Some(path), true) if flatten_count > 0 => (
+                quote! {
+                    if __flatten_keys.contains(&#first) {
+                        llsd_rs::derive::path::get(&map, &[ #( #path ),* ]).cloned()
                     } else {
-                        quote! { map.get(#key).map(|v| ::core::convert::TryFrom::try_from(v)).transpose()? }
+                        llsd_rs::derive::path::take(&mut map, &[ #( #path ),* ])
                     }
-                }
-                DefaultType::Path(func) => {
-                    if let Some(p) = with_path {
-                        quote! { map.get(#key).map(|v| #p::deserialize(v)).transpose()?.or_else(|| Some(#func())) }
+                },
+                quote! { &v },
+            ),
+            (Some(path), true) => (
+                quote! { llsd_rs::derive::path::take(&mut map, &[ #( #path ),* ]) },
+                quote! { &v },
+            ),
+            (Some(path), false) => (
+                quote! { llsd_rs::derive::path::get(map, &[ #( #path ),* ]) },
+                quote! { v },
+            ),
+            (None, true) if single_pass => (quote! { #slot }, quote! { &v }),
+            (None, false) if single_pass => (quote! { #slot }, quote! { v }),
+            (None, true) if flatten_count > 0 => (
+                quote! {
+                    if __flatten_keys.contains(&#key) {
+                        map.get(#key).cloned()
                     } else {
-                        quote! { map.get(#key).map(|v| ::core::convert::TryFrom::try_from(v)).transpose()?.or_else(|| Some(#func())) }
+                        map.remove(#key)
                     }
-                }
+                },
+                quote! { &v },
+            ),
+            (None, true) => (quote! { map.remove(#key) }, quote! { &v }),
+            (None, false) => (quote! { map.get(#key) }, quote! { v }),
+        };
+        let convert = match (f.attrs.with.as_ref(), owned) {
+            (Some(with), _) => {
+                let call = with.deserialize(value_ref);
+                quote! { #call.map_err(|e| e #qualify) }
+            }
+            (None, false) => {
+                quote! { #from_llsd::from_llsd(v).map_err(|e| e #qualify) }
+            }
+            (None, true) => {
+                quote! { #from_llsd::from_llsd_owned(v).map_err(|e| e #qualify) }
             }
+        };
+        let validate = if f.attrs.validators.is_empty() {
+            None
         } else {
-            // Non-option fields
-            match &f.attrs.default {
-                DefaultType::None => {
-                    if let Some(p) = with_path {
-                        quote! {{
-                            let raw = map.get(#key).ok_or_else(|| anyhow::Error::msg(format!("Missing required field: {}", #key)))?;
-                            #p::deserialize(raw)?
-                        }}
-                    } else {
-                        quote! { map.get(#key).ok_or_else(|| anyhow::Error::msg(format!("Missing required field: {}", #key)))?.try_into()? }
+            let checks = gen_validation(f);
+            let value_ty = option_inner_type(&f.ty).unwrap_or(&f.ty);
+            Some(quote! { .and_then(|value: #value_ty| { #checks Ok(value) }) })
+        };
+        let (blank_input, empty_output) = if f.attrs.empty_as_none {
+            (
+                quote! { .filter(|v| !llsd_rs::derive::empty_as_none::is_blank(v)) },
+                quote! { .filter(|value| !llsd_rs::derive::empty_as_none::EmptyValue::is_empty_value(value)) },
+            )
+        } else {
+            (quote! {}, quote! {})
+        };
+        // Conversion of a present value, with the key prepended to any error path
+        let lookup = quote! {
+            #fetch
+                #blank_input
+                .map(|v| #convert #validate)
+                .transpose()?
+                #empty_output
+        };
+
+        let init_expr = match (f.is_option, &f.attrs.default) {
+            (true, DefaultType::None | DefaultType::Default) => lookup,
+            (true, DefaultType::Path(func)) => quote! { #lookup.or_else(|| Some(#func())) },
+            (false, DefaultType::None) => quote! {
+                #lookup.ok_or_else(|| llsd_rs::LlsdError::new(llsd_rs::LlsdErrorKind::MissingField) #qualify)?
+            },
+            (false, DefaultType::Default) => quote! { #lookup.unwrap_or_default() },
+            (false, DefaultType::Path(func)) => quote! { #lookup.unwrap_or_else(#func) },
+        };
+
+        lets.push(quote! { let #local = #init_expr; });
+    }
+    if owned && flatten_count > 0 {
+        let flattened = fields
+            .iter()
+            .filter(|f| f.attrs.flatten && !f.attrs.skip && !f.attrs.skip_deserializing)
+            .map(|f| {
+                let ty = option_inner_type(&f.ty).unwrap_or(&f.ty);
+                quote! { <#ty as llsd_rs::FromLlsd>::known_keys(&mut __flatten_keys); }
+            });
+        lets.insert(
+            0,
+            quote! {
+                let mut __flatten_keys: ::std::vec::Vec<&'static str> = ::std::vec::Vec::new();
+                #( #flattened )*
+            },
+        );
+    }
+    lets.extend(flatten_lets);
+    (lets, inits)
+}
+
+/// Statements for `update_from_llsd`: each field present in `map` is updated
+/// in place (recursively for nested structs), absent fields are left alone.
+/// Fields with `with` or validators are converted in full and assigned.
+fn gen_field_updates(fields: &[FieldInfo]) -> Vec<proc_macro2::TokenStream> {
+    let mut updates = Vec::new();
+    for f in fields {
+        if f.attrs.skip || f.attrs.skip_deserializing {
+            continue;
+        }
+        let ident = &f.ident;
+        let from_llsd = from_llsd_for(option_inner_type(&f.ty).unwrap_or(&f.ty));
+        if f.attrs.flatten {
+            let update = match option_inner_type(&f.ty) {
+                Some(inner) => quote! {
+                    match &mut self.#ident {
+                        Some(value) => #from_llsd::update_from_llsd(value, llsd)?,
+                        None => {
+                            let mut keys = ::std::vec::Vec::new();
+                            <#inner as llsd_rs::FromLlsd>::known_keys(&mut keys);
+                            if keys.iter().any(|k| map.contains_key(*k)) {
+                                self.#ident = Some(#from_llsd::from_llsd(llsd)?);
+                            }
+                        }
                     }
+                },
+                None => quote! { #from_llsd::update_from_llsd(&mut self.#ident, llsd)?; },
+            };
+            updates.push(update);
+            continue;
+        }
+        let key = &f.de_name;
+        let qualify = error_path(f);
+        let fetch = match &f.attrs.path {
+            Some(path) => quote! { llsd_rs::derive::path::get(map, &[ #( #path ),* ]) },
+            None => quote! { map.get(#key) },
+        };
+        let apply = if f.attrs.with.is_some() || !f.attrs.validators.is_empty() {
+            let convert = match f.attrs.with.as_ref() {
+                Some(with) => {
+                    let call = with.deserialize(quote! { v });
+                    quote! { #call.map_err(|e| e #qualify) }
                 }
-                DefaultType::Default => {
-                    if let Some(p) = with_path {
-                        quote! { map.get(#key).map(|v| #p::deserialize(v)).transpose()?.unwrap_or_default() }
-                    } else {
-                        quote! { map.get(#key).map(|v| v.try_into()).transpose()?.unwrap_or_default() }
-                    }
+                None => quote! { #from_llsd::from_llsd(v).map_err(|e| e #qualify) },
+            };
+            let checks = gen_validation(f);
+            let value_ty = option_inner_type(&f.ty).unwrap_or(&f.ty);
+            let wrap = f.is_option.then(|| quote! { Some });
+            quote! {
+                let value: #value_ty = #convert?;
+                #checks
+                self.#ident = #wrap(value);
+            }
+        } else if f.is_option {
+            quote! {
+                match &mut self.#ident {
+                    Some(value) => #from_llsd::update_from_llsd(value, v).map_err(|e| e #qualify)?,
+                    None => self.#ident = Some(#from_llsd::from_llsd(v).map_err(|e| e #qualify)?),
                 }
-                DefaultType::Path(func) => {
-                    if let Some(p) = with_path {
-                        quote! { map.get(#key).map(|v| #p::deserialize(v)).transpose()?.unwrap_or_else(|| #func()) }
-                    } else {
-                        quote! { map.get(#key).map(|v| v.try_into()).transpose()?.unwrap_or_else(|| #func()) }
+            }
+        } else {
+            quote! { #from_llsd::update_from_llsd(&mut self.#ident, v).map_err(|e| e #qualify)?; }
+        };
+        let apply = if f.attrs.empty_as_none {
+            quote! {
+                if llsd_rs::derive::empty_as_none::is_blank(&v) {
+                    self.#ident = None;
+                } else {
+                    #apply
+                    if self.#ident.as_ref().is_some_and(llsd_rs::derive::empty_as_none::EmptyValue::is_empty_value) {
+                        self.#ident = None;
                     }
                 }
             }
+        } else {
+            apply
         };
-
-        field_inits.push(quote! { #ident: #init_expr });
+        updates.push(quote! {
+            if let Some(v) = #fetch {
+                #apply
+            }
+        });
     }
+    updates
+}
 
-    quote! {
-        impl #impl_generics ::core::convert::TryFrom<&llsd_rs::Llsd> for #name #ty_generics #where_clause {
-            type Error = anyhow::Error;
-            fn try_from(llsd: &llsd_rs::Llsd) -> ::core::result::Result<Self, Self::Error> {
-                if let Some(map) = llsd.as_map() {
-                    if #deny_unknown {
-                        for key in map.keys() {
-                            if !( #( key == #known_key_literals )||* ) {
-                                return Err(anyhow::Error::msg(format!("Unknown field: {}", key)));
-                            }
-                        }
+/// Validation statements run against `value` (the converted, non-`Option`
+/// field value); failures return a path-qualified `LlsdError`.
+fn gen_validation(f: &FieldInfo) -> proc_macro2::TokenStream {
+    let qualify = error_path(f);
+    let checks = f.attrs.validators.iter().map(|validator| {
+        let (condition, message) = match validator {
+            Validator::Range(range, text) => {
+                let message = format!("must be in range {text}");
+                (quote! { !(#range).contains(&value) }, quote! { #message })
+            }
+            Validator::MinLen(min) => (
+                quote! { value.len() < #min },
+                quote! { format!("length must be at least {}", #min) },
+            ),
+            Validator::NonEmpty => (quote! { value.is_empty() }, quote! { "must not be empty" }),
+            Validator::Func(func) => {
+                return quote! {
+                    if let Err(e) = #func(&value) {
+                        return Err(llsd_rs::LlsdError::validation(e) #qualify);
                     }
-                    Ok(Self { #( #field_inits ),* })
-                } else {
-                    Err(anyhow::Error::msg("Expected LLSD Map"))
-                }
+                };
             }
-        }
-        impl #impl_generics ::core::convert::TryFrom<llsd_rs::Llsd> for #name #ty_generics #where_clause {
-            type Error = anyhow::Error;
-            fn try_from(llsd: llsd_rs::Llsd) -> ::core::result::Result<Self, Self::Error> {
-                <Self as ::core::convert::TryFrom<&llsd_rs::Llsd>>::try_from(&llsd)
+        };
+        quote! {
+            if #condition {
+                return Err(llsd_rs::LlsdError::validation(#message) #qualify);
             }
         }
-    }
+    });
+    quote! { #( #checks )* }
 }
+
 fn gen_into(
     fields: &[FieldInfo],
     name: &Ident,
     impl_generics: &impl ToTokens,
     ty_generics: &impl ToTokens,
     where_clause: Option<&syn::WhereClause>,
-    _container_attrs: &ContainerAttributes,
+    container_attrs: &ContainerAttributes,
 ) -> proc_macro2::TokenStream {
-    let mut inserts = Vec::new();
-    let idents: Vec<Ident> = fields.iter().map(|f| f.ident.clone()).collect();
-    for f in fields {
-        if f.attrs.skip || f.attrs.skip_serializing {
-            continue;
-        }
-        let ident = &f.ident;
-        let key = &f.llsd_name;
-        let with_path = f.attrs.with.as_ref();
-        let expr = match (f.is_option, f.attrs.flatten, with_path) {
-            (true, _, Some(path)) => {
-                quote! { if let Some(field_value) = #ident { map.insert(#key.to_string(), #path::serialize(&field_value)); } }
-            }
-            (true, _, None) => {
-                quote! { if let Some(field_value) = #ident { map.insert(#key.to_string(), llsd_rs::Llsd::from(field_value)); } }
-            }
-            (false, true, Some(path)) => {
-                quote! { if let llsd_rs::Llsd::Map(inner) = #path::serialize(&#ident) { for (k,v) in inner { map.insert(k, v); } } }
+    let bridges = (!container_attrs.traits_only).then(|| {
+        quote! {
+            impl #impl_generics ::core::convert::From<#name #ty_generics> for llsd_rs::Llsd #where_clause {
+                fn from(value: #name #ty_generics) -> Self {
+                    llsd_rs::IntoLlsd::into_llsd(value)
+                }
             }
-            (false, true, None) => {
-                quote! { if let llsd_rs::Llsd::Map(inner) = llsd_rs::Llsd::from(#ident) { for (k,v) in inner { map.insert(k, v); } } }
+            impl #impl_generics ::core::convert::From<&#name #ty_generics> for llsd_rs::Llsd #where_clause {
+                fn from(value: &#name #ty_generics) -> Self {
+                    llsd_rs::IntoLlsd::to_llsd(value)
+                }
             }
-            (false, false, Some(path)) => {
-                quote! { map.insert(#key.to_string(), #path::serialize(&#ident)); }
+        }
+    });
+
+    if let Some(wire) = &container_attrs.into {
+        return quote! {
+            impl #impl_generics llsd_rs::IntoLlsd for #name #ty_generics #where_clause {
+                fn to_llsd(&self) -> llsd_rs::Llsd {
+                    let wire: #wire = ::core::convert::Into::into(::core::clone::Clone::clone(self));
+                    llsd_rs::IntoLlsd::into_llsd(wire)
+                }
+                fn into_llsd(self) -> llsd_rs::Llsd {
+                    let wire: #wire = ::core::convert::Into::into(self);
+                    llsd_rs::IntoLlsd::into_llsd(wire)
+                }
             }
-            (false, false, None) => {
-                quote! { map.insert(#key.to_string(), llsd_rs::Llsd::from(#ident)); }
+            #bridges
+        };
+    }
+
+    let emitted: Vec<&FieldInfo> = fields
+        .iter()
+        .filter(|f| !f.attrs.skip && !f.attrs.skip_serializing)
+        .collect();
+    let idents: Vec<&Ident> = emitted.iter().map(|f| &f.ident).collect();
+
+    if container_attrs.as_array {
+        let items: Vec<proc_macro2::TokenStream> = emitted
+            .iter()
+            .map(|f| {
+                let ident = &f.ident;
+                let into_llsd = into_llsd_for(f);
+                match f.attrs.with.as_ref() {
+                    Some(with) => with.serialize(quote! { &#ident }),
+                    None => quote! { #into_llsd::into_llsd(#ident) },
+                }
+            })
+            .collect();
+        return quote! {
+            impl #impl_generics llsd_rs::IntoLlsd for #name #ty_generics #where_clause {
+                fn to_llsd(&self) -> llsd_rs::Llsd {
+                    let #name { #( #idents, )* .. } = self;
+                    llsd_rs::Llsd::Array(::std::vec![ #( #items ),* ])
+                }
+                fn into_llsd(self) -> llsd_rs::Llsd {
+                    let #name { #( #idents, )* .. } = self;
+                    llsd_rs::Llsd::Array(::std::vec![ #( #items ),* ])
+                }
             }
+            #bridges
         };
-        inserts.push(expr);
     }
+
+    let inserts: Vec<proc_macro2::TokenStream> = emitted.iter().map(|f| gen_insert(f)).collect();
     quote! {
-        impl #impl_generics ::core::convert::From<#name #ty_generics> for llsd_rs::Llsd #where_clause {
-            fn from(value: #name #ty_generics) -> Self {
-                let #name { #( #idents ),* } = value;
-                let mut map = ::std::collections::HashMap::new();
+        impl #impl_generics llsd_rs::IntoLlsd for #name #ty_generics #where_clause {
+            fn to_llsd(&self) -> llsd_rs::Llsd {
+                let #name { #( #idents, )* .. } = self;
+                let mut map = llsd_rs::LlsdMap::new();
+                #(#inserts)*
+                llsd_rs::Llsd::Map(map)
+            }
+            fn into_llsd(self) -> llsd_rs::Llsd {
+                let #name { #( #idents, )* .. } = self;
+                let mut map = llsd_rs::LlsdMap::new();
                 #(#inserts)*
                 llsd_rs::Llsd::Map(map)
             }
         }
+        #bridges
+    }
+}
+
+/// Insert statement for one field. The same tokens serve both `into_llsd`
+/// (where the binding holds the value) and `to_llsd` (where it holds a
+/// reference, so nested structs and collections go through `IntoLlsd for &T`).
+fn gen_insert(f: &FieldInfo) -> proc_macro2::TokenStream {
+    let ident = &f.ident;
+    let into_llsd = into_llsd_for(f);
+    let key = &f.ser_name;
+    let source = if f.is_option {
+        quote! { field_value }
+    } else {
+        quote! { #ident }
+    };
+    let value = match f.attrs.with.as_ref() {
+        Some(with) => with.serialize(quote! { &#source }),
+        None => quote! { #into_llsd::into_llsd(#source) },
+    };
+    let store = match (f.attrs.flatten, &f.attrs.path) {
+        (true, _) => {
+            quote! { if let llsd_rs::Llsd::Map(inner) = #value { for (k,v) in inner { map.insert(k, v); } } }
+        }
+        (false, Some(path)) => {
+            quote! { llsd_rs::derive::path::insert(&mut map, &[ #( #path ),* ], #value); }
+        }
+        (false, None) => quote! { map.insert(#key.to_string(), #value); },
+    };
+    if f.is_option {
+        quote! { if let Some(field_value) = #ident { #store } }
+    } else {
+        store
+    }
+}
+
+/// `<T as FromLlsd>` with the trait path spanned at `T`, so a missing impl is
+/// reported on the offending field type rather than on the derive.
+fn from_llsd_for(ty: &Type) -> proc_macro2::TokenStream {
+    let span = ty.span();
+    quote_spanned! {span=> <#ty as llsd_rs::FromLlsd> }
+}
+
+/// `llsd_rs::IntoLlsd` spanned at the field, for the same reason.
+fn into_llsd_for(f: &FieldInfo) -> proc_macro2::TokenStream {
+    let span = f.ty.span();
+    quote_spanned! {span=> llsd_rs::IntoLlsd }
+}
+
+/// `.with_key(..)` calls that prepend the field's location to an error path.
+fn error_path(f: &FieldInfo) -> proc_macro2::TokenStream {
+    match &f.attrs.path {
+        Some(path) => {
+            let segments = path.iter().rev();
+            quote! { #( .with_key(#segments) )* }
+        }
+        None => {
+            let key = &f.de_name;
+            quote! { .with_key(#key) }
+        }
     }
 }
 
 // Utilities -----------------------------------------------------------------------------------
 fn field_llsd_name(
     ident: &Ident,
-    fattrs: &FieldAttributes,
-    cattrs: &ContainerAttributes,
+    rename: Option<&String>,
+    rename_all: Option<RenameRule>,
 ) -> String {
-    if let Some(r) = &fattrs.rename {
+    if let Some(r) = rename {
         r.clone()
-    } else if let Some(rule) = cattrs.rename_all {
-        rule.apply(&ident.to_string())
+    } else if let Some(rule) = rename_all {
+        rule.apply(&ident.unraw().to_string())
     } else {
-        ident.to_string()
+        ident.unraw().to_string()
+    }
+}
+/// A type as written, looking through the invisible groups `macro_rules!`
+/// wraps around `$ty:ty` fragments.
+fn ungroup(mut ty: &Type) -> &Type {
+    while let Type::Group(group) = ty {
+        ty = &group.elem;
     }
+    ty
 }
 fn is_type_option(ty: &Type) -> bool {
-    if let Type::Path(p) = ty
+    if let Type::Path(p) = ungroup(ty)
         && p.qself.is_none()
         && let Some(seg) = p.path.segments.first()
     {
@@ -469,6 +1619,40 @@ fn is_type_option(ty: &Type) -> bool {
     }
     false
 }
+/// `Vec<u8>`, `[u8; N]` and `Bytes`, which default to `Llsd::Binary`.
+fn is_byte_container(ty: &Type) -> bool {
+    let is_u8 = |ty: &Type| matches!(ungroup(ty), Type::Path(p) if p.qself.is_none() && p.path.is_ident("u8"));
+    match ungroup(ty) {
+        Type::Array(array) => is_u8(&array.elem),
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            let Some(last) = type_path.path.segments.last() else {
+                return false;
+            };
+            match &last.arguments {
+                syn::PathArguments::None => last.ident == "Bytes",
+                syn::PathArguments::AngleBracketed(args) if last.ident == "Vec" => {
+                    matches!(args.args.first(), Some(syn::GenericArgument::Type(inner)) if args.args.len() == 1 && is_u8(inner))
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// `T` for an `Option<T>` type.
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    if let Type::Path(p) = ungroup(ty)
+        && p.qself.is_none()
+        && let Some(seg) = p.path.segments.last()
+        && seg.ident == "Option"
+        && let syn::PathArguments::AngleBracketed(args) = &seg.arguments
+        && let Some(syn::GenericArgument::Type(inner)) = args.args.first()
+    {
+        return Some(inner);
+    }
+    None
+}
 fn to_snake_case(s: &str) -> String {
     let mut out = String::new();
     let mut prev_lower = false;
@@ -516,3 +1700,77 @@ fn to_pascal_case(s: &str) -> String {
         String::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(ast: DeriveInput) -> Vec<String> {
+        let err = impl_expand(ast, Mode::Both).unwrap_err();
+        err.into_iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn attribute_errors_suggest_fixes() {
+        assert_eq!(
+            errors(syn::parse_quote! {
+                #[llsd(rename_al = "camelCase")]
+                struct S { a: u32 }
+            }),
+            ["unknown llsd attribute `rename_al`; did you mean `rename_all`?"]
+        );
+        assert_eq!(
+            errors(syn::parse_quote! {
+                #[llsd(flatten)]
+                struct S { a: u32 }
+            }),
+            ["`flatten` is a field attribute and is not accepted here"]
+        );
+    }
+
+    #[test]
+    fn field_errors_are_reported_together() {
+        assert_eq!(
+            errors(syn::parse_quote! {
+                struct S {
+                    #[llsd(sikp)]
+                    a: u32,
+                    #[llsd(empty_as_none)]
+                    b: String,
+                    #[llsd(path = "/x/y", rename = "z")]
+                    c: u32,
+                }
+            }),
+            [
+                "unknown llsd attribute `sikp`; did you mean `skip`?",
+                "field `b`: `empty_as_none` requires an `Option` field; wrap the type in `Option<...>`",
+                "field `c`: `path` cannot be combined with `flatten` or `rename`; the path's last segment is the key",
+            ]
+        );
+    }
+
+    #[test]
+    fn typo_distance() {
+        assert!(is_typo_of("defualt", "default"));
+        assert!(is_typo_of("flaten", "flatten"));
+        assert!(is_typo_of("skipp", "skip"));
+        assert!(is_typo_of("bynary", "binary"));
+        assert!(!is_typo_of("skip", "skip"));
+        assert!(!is_typo_of("rename", "rename_all"));
+    }
+
+    #[test]
+    fn types_from_macro_fragments() {
+        // `$ty:ty` reaches the derive wrapped in an invisible group.
+        let group = |ty: Type| {
+            Type::Group(syn::TypeGroup {
+                group_token: Default::default(),
+                elem: Box::new(ty),
+            })
+        };
+        assert!(is_type_option(&group(syn::parse_quote!(Option<u32>))));
+        assert!(option_inner_type(&group(syn::parse_quote!(Option<u32>))).is_some());
+        assert!(is_byte_container(&group(syn::parse_quote!([u8; 4]))));
+        assert!(is_byte_container(&group(syn::parse_quote!(Vec<u8>))));
+    }
+}
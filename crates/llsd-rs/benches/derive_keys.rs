@@ -0,0 +1,103 @@
+//! Key lookup cost of derived `FromLlsd` on a wide struct.
+//!
+//! `derived` uses the generated single-pass `match` over the map's keys;
+//! `per_key_lookup` reproduces the previous expansion (a `known_keys` vector
+//! scanned for every input key, then one `HashMap::get` per field).
+//!
+//! Run with `cargo bench -p llsd-rs --features derive --bench derive_keys`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use llsd_rs::{FromLlsd, Llsd, LlsdError, LlsdFrom};
+
+macro_rules! wide_struct {
+    ($($field:ident),* $(,)?) => {
+        #[derive(Debug, LlsdFrom)]
+        #[llsd(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct AgentUpdate {
+            $($field: i32,)*
+        }
+
+        const KEYS: &[&str] = &[$(stringify!($field)),*];
+
+        fn per_key_lookup(llsd: &Llsd) -> Result<AgentUpdate, LlsdError> {
+            let map = llsd
+                .as_map()
+                .ok_or_else(|| LlsdError::invalid_type("map", llsd))?;
+            let known: Vec<&'static str> = KEYS.to_vec();
+            for key in map.keys() {
+                if !known.contains(&key.as_str()) {
+                    return Err(LlsdError::unknown_field(key));
+                }
+            }
+            Ok(AgentUpdate {
+                $($field: match map.get(stringify!($field)) {
+                    Some(v) => i32::from_llsd(v).map_err(|e| e.with_key(stringify!($field)))?,
+                    None => return Err(LlsdError::missing_field(stringify!($field))),
+                },)*
+            })
+        }
+    };
+}
+
+wide_struct!(
+    agent_id,
+    session_id,
+    circuit_code,
+    body_rotation,
+    head_rotation,
+    state,
+    camera_center,
+    camera_at_axis,
+    camera_left_axis,
+    camera_up_axis,
+    far,
+    control_flags,
+    flags,
+    god_level,
+    look_at,
+    region_x,
+    region_y,
+    region_z,
+    velocity_x,
+    velocity_y,
+    velocity_z,
+    parent_id,
+    group_id,
+    group_powers,
+    animation_id,
+    sound_id,
+    media_id,
+    texture_id,
+    attachment_point,
+    hover_height,
+    agent_access,
+    max_access,
+    language_public,
+);
+
+fn input() -> Llsd {
+    let mut llsd = Llsd::map();
+    for (i, key) in KEYS.iter().enumerate() {
+        llsd = llsd.insert(*key, i as i32).unwrap();
+    }
+    llsd
+}
+
+fn derive_keys(c: &mut Criterion) {
+    let llsd = input();
+    assert!(per_key_lookup(&llsd).is_ok());
+    let mut group = c.benchmark_group("derive_keys");
+    group.bench_function("per_key_lookup", |b| {
+        b.iter(|| black_box(per_key_lookup(black_box(&llsd)).unwrap()))
+    });
+    group.bench_function("derived", |b| {
+        b.iter(|| black_box(AgentUpdate::from_llsd(black_box(&llsd)).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, derive_keys);
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+//! `LlsdMap` against a plain `HashMap` for lookups and inserts, plus parsing
+//! of a typical message-sized document in each wire format.
+//!
+//! Run with `cargo bench -p llsd-rs --bench map`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use llsd_rs::{Llsd, LlsdMap, binary, notation, xml};
+
+const KEYS: &[&str] = &[
+    "agent_id",
+    "session_id",
+    "circuit_code",
+    "region_x",
+    "region_y",
+    "look_at",
+    "god_level",
+    "flags",
+    "group_id",
+    "group_powers",
+    "parent_id",
+    "state",
+    "far",
+    "velocity",
+    "position",
+    "rotation",
+];
+
+fn entries(len: usize) -> Vec<(String, Llsd)> {
+    KEYS[..len]
+        .iter()
+        .enumerate()
+        .map(|(i, k)| (k.to_string(), Llsd::Integer(i as i32)))
+        .collect()
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    for len in [4, 8, 16] {
+        let llsd_map: LlsdMap = entries(len).into_iter().collect();
+        let hash_map: HashMap<String, Llsd> = entries(len).into_iter().collect();
+        let keys = &KEYS[..len];
+        group.bench_with_input(BenchmarkId::new("LlsdMap", len), &len, |b, _| {
+            b.iter(|| {
+                for key in keys {
+                    black_box(llsd_map.get(black_box(*key)));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("HashMap", len), &len, |b, _| {
+            b.iter(|| {
+                for key in keys {
+                    black_box(hash_map.get(black_box(*key)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for len in [4, 8, 16] {
+        group.bench_with_input(BenchmarkId::new("LlsdMap", len), &len, |b, &len| {
+            b.iter_batched(
+                || entries(len),
+                |entries| black_box(entries.into_iter().collect::<LlsdMap>()),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("HashMap", len), &len, |b, &len| {
+            b.iter_batched(
+                || entries(len),
+                |entries| black_box(entries.into_iter().collect::<HashMap<_, _>>()),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// An object-update-like document: an array of small maps.
+fn document() -> Llsd {
+    let objects = (0..100)
+        .map(|i| {
+            Llsd::map()
+                .insert("local_id", i)
+                .unwrap()
+                .insert("full_id", llsd_rs::Uuid::from_u128(i as u128))
+                .unwrap()
+                .insert("name", format!("Object {i}"))
+                .unwrap()
+                .insert("position", Llsd::from((1.0f64, 2.0f64, 3.0f64)))
+                .unwrap()
+                .insert("flags", 0x10)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    Llsd::map().insert("objects", objects).unwrap()
+}
+
+fn parse(c: &mut Criterion) {
+    let llsd = document();
+    let binary = binary::to_vec(&llsd).unwrap();
+    let notation = notation::to_string(&llsd, &Default::default()).unwrap();
+    let xml = xml::to_string(&llsd).unwrap();
+
+    let mut group = c.benchmark_group("parse");
+    group.bench_function("binary", |b| {
+        b.iter(|| black_box(binary::from_slice(black_box(&binary)).unwrap()))
+    });
+    group.bench_function("notation", |b| {
+        b.iter(|| black_box(notation::from_str(black_box(&notation), 64).unwrap()))
+    });
+    group.bench_function("xml", |b| {
+        b.iter(|| black_box(xml::from_str(black_box(&xml)).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup, build, parse);
+criterion_main!(benches);
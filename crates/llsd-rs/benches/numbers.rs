@@ -0,0 +1,90 @@
+//! Numeric text output: `ryu`/`itoa` against the `Display`-based formatting
+//! the text writers used before, per value and for a numeric-heavy document.
+//!
+//! Run with `cargo bench -p llsd-rs --bench numbers`.
+
+use std::hint::black_box;
+use std::io::Write;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use llsd_rs::{Llsd, notation, xml};
+
+fn reals() -> Vec<f64> {
+    (0..1024)
+        .map(|i| (i as f64 * 0.731).sin() * 10f64.powi(i % 40 - 20))
+        .collect()
+}
+
+fn integers() -> Vec<i32> {
+    (0..1024).map(|i| i * 2_097_151 - 1_073_741_824).collect()
+}
+
+fn format_values(c: &mut Criterion) {
+    let reals = reals();
+    let integers = integers();
+    let mut out = Vec::with_capacity(64 * 1024);
+
+    let mut group = c.benchmark_group("real");
+    group.bench_function("display", |b| {
+        b.iter(|| {
+            out.clear();
+            for v in &reals {
+                write!(out, "r{}", black_box(v)).unwrap();
+            }
+        })
+    });
+    group.bench_function("ryu", |b| {
+        b.iter(|| {
+            out.clear();
+            let mut buf = ryu::Buffer::new();
+            for v in &reals {
+                out.push(b'r');
+                out.extend_from_slice(buf.format(*black_box(v)).as_bytes());
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("integer");
+    group.bench_function("display", |b| {
+        b.iter(|| {
+            out.clear();
+            for v in &integers {
+                write!(out, "i{}", black_box(v)).unwrap();
+            }
+        })
+    });
+    group.bench_function("itoa", |b| {
+        b.iter(|| {
+            out.clear();
+            let mut buf = itoa::Buffer::new();
+            for v in &integers {
+                out.push(b'i');
+                out.extend_from_slice(buf.format(*black_box(v)).as_bytes());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn write_documents(c: &mut Criterion) {
+    let llsd = Llsd::Array(
+        reals()
+            .into_iter()
+            .zip(integers())
+            .flat_map(|(r, i)| [Llsd::Real(r), Llsd::Integer(i)])
+            .collect(),
+    );
+    let context = notation::FormatterContext::new();
+    let mut group = c.benchmark_group("write_numeric_document");
+    group.bench_function("notation", |b| {
+        b.iter(|| black_box(notation::to_vec(black_box(&llsd), &context).unwrap()))
+    });
+    group.bench_function("xml", |b| {
+        b.iter(|| black_box(xml::to_string(black_box(&llsd)).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, format_values, write_documents);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! XML output for a 50k-node document: the `EventWriter` path the writer used
+//! before against the compact writer that formats straight into the buffer.
+//!
+//! Run with `cargo bench -p llsd-rs --bench xml_writer`.
+
+use std::hint::black_box;
+
+use ::xml::EventWriter;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use llsd_rs::Uuid;
+use llsd_rs::{Date, Llsd, LlsdMap, xml};
+
+/// 5000 records of 10 nodes each: the map, its `tags` array and eight
+/// scalars.
+fn document() -> Llsd {
+    Llsd::Array(
+        (0..5000)
+            .map(|i| {
+                let mut map = LlsdMap::new();
+                map.insert("name".into(), Llsd::String(format!("Item <{i}> & co")));
+                map.insert("id".into(), Llsd::Uuid(Uuid::from_u128(i as u128 * 7919)));
+                map.insert(
+                    "created".into(),
+                    Llsd::Date(Date::from_timestamp(1_600_000_000 + i, 0).unwrap()),
+                );
+                map.insert("size".into(), Llsd::Integer(i as i32 * 31));
+                map.insert("weight".into(), Llsd::Real(i as f64 / 7.0));
+                map.insert("thumbnail".into(), Llsd::Binary(vec![i as u8; 24]));
+                map.insert("owner".into(), Llsd::Undefined);
+                map.insert(
+                    "tags".into(),
+                    Llsd::Array(vec![Llsd::String("texture".into())]),
+                );
+                Llsd::Map(map)
+            })
+            .collect(),
+    )
+}
+
+fn write_document(c: &mut Criterion) {
+    let llsd = document();
+    let len = xml::to_string(&llsd).unwrap().len();
+    let mut group = c.benchmark_group("xml_write_50k_nodes");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("event_writer", |b| {
+        b.iter(|| {
+            let mut buf = Vec::new();
+            xml::write(black_box(&llsd), &mut EventWriter::new(&mut buf)).unwrap();
+            black_box(buf)
+        })
+    });
+    group.bench_function("to_string", |b| {
+        b.iter(|| black_box(xml::to_string(black_box(&llsd)).unwrap()))
+    });
+    let mut encoder = xml::XmlEncoder::new();
+    let mut out = Vec::with_capacity(len);
+    group.bench_function("encoder_reused", |b| {
+        b.iter(|| {
+            out.clear();
+            encoder.encode_into(black_box(&llsd), &mut out).unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, write_document);
+criterion_main!(benches);
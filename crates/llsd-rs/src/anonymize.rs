@@ -0,0 +1,311 @@
+//! Pseudonymizing documents for bug reports.
+//!
+//! [`Anonymizer`] replaces the identifying values in a tree with stable
+//! stand-ins derived from a secret key with HMAC-SHA-256:
+//!
+//! - UUIDs, in `Uuid` values, in strings and map keys that are a UUID, and
+//!   in the path segments of URIs (capability URLs embed their secret);
+//! - IP addresses in strings (with or without a port) and URI hosts, mapped
+//!   into `10.0.0.0/8` or `fd00::/8` so they read as obviously private;
+//! - names: the string values of the [name keys](Anonymizer::with_name_key),
+//!   such as `first_name`, and the user names of URIs.
+//!
+//! The same value always gets the same stand-in under the same key, so
+//! references between captured messages still line up, while nobody without
+//! the key can map them back. The nil UUID and loopback and unspecified
+//! addresses are kept, and URI passwords are dropped.
+//!
+//! ```
+//! use llsd_rs::{Llsd, anonymize::Anonymizer, llsd_map};
+//! use uuid::Uuid;
+//!
+//! let agent = Uuid::from_u128(0x1234);
+//! let anonymizer = Anonymizer::new(b"bug report key".to_vec());
+//! let login = anonymizer.anonymize(&llsd_map! {
+//!     "agent_id" => agent,
+//!     "first_name" => "Philip",
+//!     "sim_ip" => "203.0.113.7",
+//! });
+//! let again = anonymizer.anonymize(&llsd_map! { "owner" => agent.to_string() });
+//!
+//! assert_ne!(login["agent_id"], Llsd::Uuid(agent));
+//! assert_eq!(again["owner"], Llsd::String(login["agent_id"].as_uuid().unwrap().to_string()));
+//! assert!(login["first_name"].as_str().unwrap().starts_with("anon-"));
+//! assert!(login["sim_ip"].as_str().unwrap().starts_with("10."));
+//! ```
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{Llsd, LlsdMap, Uri};
+
+/// Keys whose string values are taken as people's names unless
+/// [`Anonymizer::without_name_keys`] clears them. `name` is left out: objects
+/// and inventory items use it far more often than people do.
+pub const DEFAULT_NAME_KEYS: &[&str] = &[
+    "first_name",
+    "last_name",
+    "legacy_first_name",
+    "legacy_last_name",
+    "display_name",
+    "username",
+    "agent_name",
+    "owner_name",
+    "from_name",
+];
+
+/// Pseudonymizes values with a secret key.
+#[derive(Clone)]
+pub struct Anonymizer {
+    key: Vec<u8>,
+    name_keys: Vec<String>,
+}
+
+impl std::fmt::Debug for Anonymizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Anonymizer")
+            .field("name_keys", &self.name_keys)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Anonymizer {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            name_keys: DEFAULT_NAME_KEYS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    /// Also treat the string values of `key` (compared ignoring case) as names.
+    pub fn with_name_key(mut self, key: impl Into<String>) -> Self {
+        self.name_keys.push(key.into());
+        self
+    }
+
+    /// Forget the [default name keys](DEFAULT_NAME_KEYS).
+    pub fn without_name_keys(mut self) -> Self {
+        self.name_keys.clear();
+        self
+    }
+
+    /// A copy of `llsd` with identifying values replaced.
+    pub fn anonymize(&self, llsd: &Llsd) -> Llsd {
+        let mut out = llsd.clone();
+        self.anonymize_in_place(&mut out);
+        out
+    }
+
+    pub fn anonymize_in_place(&self, llsd: &mut Llsd) {
+        self.value(llsd, false);
+    }
+
+    /// The stand-in for `id`: a version 4 UUID, or nil for nil.
+    pub fn uuid(&self, id: Uuid) -> Uuid {
+        if id.is_nil() {
+            return id;
+        }
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&self.mac(b"uuid", id.as_bytes())[..16]);
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+
+    /// The stand-in for `name`: `anon-` and eight hex digits.
+    pub fn name(&self, name: &str) -> String {
+        if name.is_empty() {
+            return String::new();
+        }
+        let mac = self.mac(b"name", name.as_bytes());
+        format!(
+            "anon-{:08x}",
+            u32::from_be_bytes([mac[0], mac[1], mac[2], mac[3]])
+        )
+    }
+
+    /// The stand-in for `ip`, in `10.0.0.0/8` or `fd00::/8`. Loopback and
+    /// unspecified addresses are kept.
+    pub fn ip(&self, ip: IpAddr) -> IpAddr {
+        if ip.is_loopback() || ip.is_unspecified() {
+            return ip;
+        }
+        match ip {
+            IpAddr::V4(v4) => {
+                let mac = self.mac(b"ipv4", &v4.octets());
+                Ipv4Addr::new(10, mac[0], mac[1], mac[2]).into()
+            }
+            IpAddr::V6(v6) => {
+                let mac = self.mac(b"ipv6", &v6.octets());
+                let mut octets = [0; 16];
+                octets[0] = 0xfd;
+                octets[1..].copy_from_slice(&mac[..15]);
+                Ipv6Addr::from(octets).into()
+            }
+        }
+    }
+
+    /// HMAC of `value`, separated by `domain` so equal bytes of different
+    /// kinds get unrelated stand-ins.
+    fn mac(&self, domain: &[u8], value: &[u8]) -> [u8; 32] {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(domain);
+        mac.update(b":");
+        mac.update(value);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn is_name_key(&self, key: &str) -> bool {
+        self.name_keys.iter().any(|k| k.eq_ignore_ascii_case(key))
+    }
+
+    /// `name` is set for the values of name keys (and arrays of them).
+    fn value(&self, llsd: &mut Llsd, name: bool) {
+        match llsd {
+            Llsd::Uuid(id) => *id = self.uuid(*id),
+            Llsd::String(text) => *text = self.text(text, name),
+            Llsd::Uri(uri) => *uri = self.uri(uri),
+            Llsd::Array(items) => items.iter_mut().for_each(|item| self.value(item, name)),
+            Llsd::Map(map) => self.map(map),
+            _ => {}
+        }
+    }
+
+    fn map(&self, map: &mut LlsdMap) {
+        let rekey = map.keys().any(|key| Uuid::try_parse(key).is_ok());
+        if rekey {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, value)| (self.text(&key, false), value))
+                .collect();
+        }
+        for (key, value) in map.iter_mut() {
+            self.value(value, self.is_name_key(key));
+        }
+    }
+
+    fn text(&self, text: &str, name: bool) -> String {
+        if let Ok(id) = Uuid::try_parse(text) {
+            return self.uuid(id).to_string();
+        }
+        if let Ok(ip) = text.parse::<IpAddr>() {
+            return self.ip(ip).to_string();
+        }
+        if let Ok(addr) = text.parse::<SocketAddr>() {
+            return SocketAddr::new(self.ip(addr.ip()), addr.port()).to_string();
+        }
+        if name {
+            return self.name(text);
+        }
+        text.to_string()
+    }
+
+    fn uri(&self, uri: &Uri) -> Uri {
+        match uri {
+            Uri::Url(url) => Uri::Url(self.url(url)),
+            uri => uri.clone(),
+        }
+    }
+
+    fn url(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        if !url.username().is_empty() {
+            let _ = url.set_username(&self.name(url.username()));
+        }
+        let _ = url.set_password(None);
+        let host_ip = match url.host() {
+            Some(url::Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+            Some(url::Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+            _ => None,
+        };
+        if let Some(ip) = host_ip {
+            let _ = url.set_ip_host(self.ip(ip));
+        }
+        let path = url.path_segments().map(|segments| {
+            segments
+                .map(|segment| match Uuid::try_parse(segment) {
+                    Ok(id) => self.uuid(id).to_string(),
+                    Err(_) => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/")
+        });
+        if let Some(path) = path {
+            url.set_path(&format!("/{path}"));
+        }
+        url
+    }
+}
+
+/// Pseudonymize `llsd` with `key` and the default name keys.
+pub fn anonymize(llsd: &Llsd, key: &[u8]) -> Llsd {
+    Anonymizer::new(key).anonymize(llsd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{llsd_array, llsd_map};
+
+    #[test]
+    fn stand_ins_are_consistent() {
+        let id = Uuid::from_u128(0xfeed);
+        let first = llsd_map! {
+            "agent_id" => id,
+            "owner" => id.to_string().to_uppercase(),
+            "first_name" => "Philip",
+            "names" => llsd_array!["Philip"],
+            "region" => "Ahern",
+        };
+        let anonymizer = Anonymizer::new(b"k".to_vec()).with_name_key("Names");
+        let out = anonymizer.anonymize(&first);
+        let stand_in = anonymizer.uuid(id);
+        assert_ne!(stand_in, id);
+        assert_eq!(stand_in.get_version_num(), 4);
+        assert_eq!(out["agent_id"], Llsd::Uuid(stand_in));
+        assert_eq!(out["owner"], Llsd::String(stand_in.to_string()));
+        assert_eq!(out["first_name"], Llsd::String(anonymizer.name("Philip")));
+        assert_eq!(out["names"][0], out["first_name"]);
+        assert_eq!(out["region"], Llsd::from("Ahern"));
+        assert_eq!(anonymize(&first, b"k")["agent_id"], out["agent_id"]);
+
+        let other = Anonymizer::new(b"other key".to_vec());
+        assert_ne!(other.uuid(id), stand_in);
+        assert_eq!(anonymizer.uuid(Uuid::nil()), Uuid::nil());
+    }
+
+    #[test]
+    fn addresses_and_urls() {
+        let anonymizer = Anonymizer::new(b"k".to_vec());
+        let cap = Uuid::from_u128(0xcafe);
+        let doc = llsd_map! {
+            "sim_ip" => "203.0.113.7",
+            "sim" => "203.0.113.7:13005",
+            "v6" => "2001:db8::1",
+            "local" => "127.0.0.1",
+            "seed" => Uri::parse(&format!("https://bob:pw@203.0.113.7:12043/cap/{cap}?x=1")),
+            cap.to_string() => 1,
+        };
+        let out = anonymizer.anonymize(&doc);
+        let ip = anonymizer.ip("203.0.113.7".parse().unwrap());
+        assert!(ip.to_string().starts_with("10."));
+        assert_eq!(out["sim_ip"], Llsd::String(ip.to_string()));
+        assert_eq!(out["sim"], Llsd::String(format!("{ip}:13005")));
+        assert!(out["v6"].as_str().unwrap().starts_with("fd"));
+        assert_eq!(out["local"], Llsd::from("127.0.0.1"));
+        let seed = Uri::parse(&format!(
+            "https://{}@{ip}:12043/cap/{}?x=1",
+            anonymizer.name("bob"),
+            anonymizer.uuid(cap)
+        ));
+        assert_eq!(out["seed"], Llsd::Uri(seed));
+        assert_eq!(
+            out[anonymizer.uuid(cap).to_string().as_str()],
+            Llsd::Integer(1)
+        );
+        assert!(out.get(cap.to_string().as_str()).is_none());
+    }
+}
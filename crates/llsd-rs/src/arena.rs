@@ -0,0 +1,403 @@
+//! Parsing into a caller-provided [`bumpalo`] arena.
+//!
+//! [`LlsdRef`] mirrors [`Llsd`], but arrays and maps are slices allocated in
+//! a [`Bump`], and strings and binaries borrow the input wherever the encoding
+//! allows (escaped notation strings and base16 binaries are copied into the
+//! arena instead). Nothing is freed individually: dropping or resetting the
+//! arena releases the whole document at once, which suits request-scoped
+//! handling.
+//!
+//! ```
+//! use bumpalo::Bump;
+//! use llsd_rs::{Llsd, binary};
+//!
+//! let data = binary::to_vec(&Llsd::map().insert("name", "Ahern").unwrap()).unwrap();
+//! let bump = Bump::new();
+//! let llsd = binary::from_slice_in(&data, &bump).unwrap();
+//! assert_eq!(llsd.get("name").and_then(|v| v.as_string()), Some(&"Ahern"));
+//! ```
+
+use crate::Uuid;
+use bumpalo::{Bump, collections::Vec as BumpVec};
+use enum_as_inner::EnumAsInner;
+
+use crate::{
+    Date, Llsd, LlsdMap, Uri,
+    autodetect::{self, LlsdEncoding},
+    date,
+    escape::unescape_notation,
+    lazy::Cursor,
+};
+
+/// A parsed value whose containers live in an arena and whose strings and
+/// binaries borrow the input where possible.
+#[derive(Debug, Clone, Copy, PartialEq, EnumAsInner)]
+pub enum LlsdRef<'a> {
+    Undefined,
+    Boolean(bool),
+    Integer(i32),
+    Real(f64),
+    String(&'a str),
+    Uri(&'a str),
+    Uuid(Uuid),
+    Date(Date),
+    Binary(&'a [u8]),
+    Array(&'a [LlsdRef<'a>]),
+    /// Entries in document order; a repeated key keeps every occurrence and
+    /// lookups return the last one, as decoding into [`Llsd`] would.
+    Map(&'a [(&'a str, LlsdRef<'a>)]),
+}
+
+impl<'a> LlsdRef<'a> {
+    /// Same names as [`Llsd::type_name`].
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LlsdRef::Undefined => "undef",
+            LlsdRef::Boolean(_) => "boolean",
+            LlsdRef::Integer(_) => "integer",
+            LlsdRef::Real(_) => "real",
+            LlsdRef::String(_) => "string",
+            LlsdRef::Uri(_) => "uri",
+            LlsdRef::Uuid(_) => "uuid",
+            LlsdRef::Date(_) => "date",
+            LlsdRef::Binary(_) => "binary",
+            LlsdRef::Array(_) => "array",
+            LlsdRef::Map(_) => "map",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            LlsdRef::Array(a) => a.len(),
+            LlsdRef::Map(m) => m.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a LlsdRef<'a>> {
+        match *self {
+            LlsdRef::Map(entries) => entries
+                .iter()
+                .rev()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&'a LlsdRef<'a>> {
+        match *self {
+            LlsdRef::Array(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    /// Copy into an owned [`Llsd`] that outlives the arena.
+    pub fn to_llsd(&self) -> Llsd {
+        match *self {
+            LlsdRef::Undefined => Llsd::Undefined,
+            LlsdRef::Boolean(v) => Llsd::Boolean(v),
+            LlsdRef::Integer(v) => Llsd::Integer(v),
+            LlsdRef::Real(v) => Llsd::Real(v),
+            LlsdRef::String(v) => Llsd::String(v.to_string()),
+            LlsdRef::Uri(v) => Llsd::Uri(Uri::parse(v)),
+            LlsdRef::Uuid(v) => Llsd::Uuid(v),
+            LlsdRef::Date(v) => Llsd::Date(v),
+            LlsdRef::Binary(v) => Llsd::Binary(v.to_vec()),
+            LlsdRef::Array(items) => Llsd::Array(items.iter().map(LlsdRef::to_llsd).collect()),
+            LlsdRef::Map(entries) => Llsd::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_llsd()))
+                    .collect::<LlsdMap>(),
+            ),
+        }
+    }
+}
+
+impl From<&LlsdRef<'_>> for Llsd {
+    fn from(llsd: &LlsdRef<'_>) -> Self {
+        llsd.to_llsd()
+    }
+}
+
+fn depth_exceeded() -> anyhow::Error {
+    anyhow::anyhow!("LLSD maximum recursion depth exceeded")
+}
+
+pub(crate) fn from_binary<'a>(
+    data: &'a [u8],
+    bump: &'a Bump,
+    max_depth: usize,
+) -> anyhow::Result<LlsdRef<'a>> {
+    let data = autodetect::payload_after_header(data, LlsdEncoding::Binary);
+    let mut cursor = Cursor { data, pos: 0 };
+    binary_value(&mut cursor, bump, max_depth)
+}
+
+fn binary_value<'a>(
+    cursor: &mut Cursor<'a>,
+    bump: &'a Bump,
+    depth_remaining: usize,
+) -> anyhow::Result<LlsdRef<'a>> {
+    if depth_remaining == 0 {
+        return Err(depth_exceeded());
+    }
+    Ok(match cursor.next()? {
+        b'!' => LlsdRef::Undefined,
+        b'1' => LlsdRef::Boolean(true),
+        b'0' => LlsdRef::Boolean(false),
+        b'i' => LlsdRef::Integer(i32::from_be_bytes(cursor.take(4)?.try_into()?)),
+        b'r' => LlsdRef::Real(f64::from_be_bytes(cursor.take(8)?.try_into()?)),
+        b's' => {
+            let len = cursor.binary_len()?;
+            LlsdRef::String(std::str::from_utf8(cursor.take(len)?)?)
+        }
+        b'l' => {
+            let len = cursor.binary_len()?;
+            LlsdRef::Uri(std::str::from_utf8(cursor.take(len)?)?)
+        }
+        b'u' => LlsdRef::Uuid(Uuid::from_slice(cursor.take(16)?)?),
+        b'd' => {
+            // Use little endian
+            let real = f64::from_le_bytes(cursor.take(8)?.try_into()?);
+            LlsdRef::Date(crate::binary::real_to_date(real))
+        }
+        b'b' => {
+            let len = cursor.binary_len()?;
+            LlsdRef::Binary(cursor.take(len)?)
+        }
+        b'[' => {
+            let len = cursor.binary_len()?;
+            let mut items = BumpVec::with_capacity_in(len.min(cursor.data.len()), bump);
+            for _ in 0..len {
+                items.push(binary_value(cursor, bump, depth_remaining - 1)?);
+            }
+            cursor.expect(b"]")?;
+            LlsdRef::Array(items.into_bump_slice())
+        }
+        b'{' => {
+            let len = cursor.binary_len()?;
+            let mut entries = BumpVec::with_capacity_in(len.min(cursor.data.len()), bump);
+            for _ in 0..len {
+                cursor.expect(b"k")?;
+                let key_len = cursor.binary_len()?;
+                let key = std::str::from_utf8(cursor.take(key_len)?)?;
+                entries.push((key, binary_value(cursor, bump, depth_remaining - 1)?));
+            }
+            cursor.expect(b"}")?;
+            LlsdRef::Map(entries.into_bump_slice())
+        }
+        delim @ (b'"' | b'\'') => LlsdRef::String(alloc_unescaped(cursor, bump, delim)?),
+        other => return Err(anyhow::anyhow!("Unknown LLSD type: {}", other)),
+    })
+}
+
+fn alloc_unescaped<'a>(
+    cursor: &mut Cursor<'a>,
+    bump: &'a Bump,
+    delim: u8,
+) -> anyhow::Result<&'a str> {
+    let raw = cursor.skip_escaped(delim)?;
+    Ok(match unescape_notation(raw)? {
+        std::borrow::Cow::Borrowed(s) => s,
+        std::borrow::Cow::Owned(s) => bump.alloc_str(&s),
+    })
+}
+
+pub(crate) fn from_notation<'a>(
+    data: &'a [u8],
+    bump: &'a Bump,
+    max_depth: usize,
+) -> anyhow::Result<LlsdRef<'a>> {
+    let mut cursor = Cursor { data, pos: 0 };
+    cursor.skip_ws();
+    if cursor.peek().is_none() {
+        return Ok(LlsdRef::Undefined);
+    }
+    notation_value(&mut cursor, bump, max_depth)
+}
+
+fn notation_value<'a>(
+    cursor: &mut Cursor<'a>,
+    bump: &'a Bump,
+    depth_remaining: usize,
+) -> anyhow::Result<LlsdRef<'a>> {
+    if depth_remaining == 0 {
+        return Err(depth_exceeded());
+    }
+    let take_while = |cursor: &mut Cursor<'a>, pred: fn(u8) -> bool| {
+        let start = cursor.pos;
+        cursor.skip_while(pred);
+        std::str::from_utf8(&cursor.data[start..cursor.pos])
+    };
+    Ok(match cursor.next()? {
+        b'{' => {
+            let mut entries = BumpVec::new_in(bump);
+            loop {
+                cursor.skip_ws();
+                let key = match cursor.next()? {
+                    b'}' => break,
+                    b',' => continue,
+                    b's' => std::str::from_utf8(cursor.notation_sized()?)?,
+                    delim @ (b'\'' | b'"') => alloc_unescaped(cursor, bump, delim)?,
+                    other => {
+                        return Err(anyhow::anyhow!("Invalid character in map: 0x{other:02x}"));
+                    }
+                };
+                cursor.skip_ws();
+                cursor.expect(b":")?;
+                cursor.skip_ws();
+                entries.push((key, notation_value(cursor, bump, depth_remaining - 1)?));
+            }
+            LlsdRef::Map(entries.into_bump_slice())
+        }
+        b'[' => {
+            let mut items = BumpVec::new_in(bump);
+            loop {
+                cursor.skip_ws();
+                match cursor.peek() {
+                    Some(b']') => {
+                        cursor.pos += 1;
+                        break;
+                    }
+                    Some(b',') => cursor.pos += 1,
+                    _ => items.push(notation_value(cursor, bump, depth_remaining - 1)?),
+                }
+            }
+            LlsdRef::Array(items.into_bump_slice())
+        }
+        b'!' => LlsdRef::Undefined,
+        b'0' => LlsdRef::Boolean(false),
+        b'1' => LlsdRef::Boolean(true),
+        b'i' | b'I' => LlsdRef::Integer(
+            take_while(cursor, |c| matches!(c, b'0'..=b'9' | b'+' | b'-'))?.parse()?,
+        ),
+        b'r' | b'R' => {
+            LlsdRef::Real(take_while(cursor, |c| b"+-.0123456789eEinfINFaA".contains(&c))?.parse()?)
+        }
+        b'u' | b'U' => LlsdRef::Uuid(Uuid::parse_str(take_while(cursor, |c| {
+            c.is_ascii_hexdigit() || c == b'-'
+        })?)?),
+        b't' | b'T' => {
+            cursor.keyword_tail(b"rue")?;
+            LlsdRef::Boolean(true)
+        }
+        b'f' | b'F' => {
+            cursor.keyword_tail(b"alse")?;
+            LlsdRef::Boolean(false)
+        }
+        delim @ (b'\'' | b'"') => LlsdRef::String(alloc_unescaped(cursor, bump, delim)?),
+        b's' => LlsdRef::String(std::str::from_utf8(cursor.notation_sized()?)?),
+        b'l' | b'L' => {
+            cursor.expect(b"\"")?;
+            LlsdRef::Uri(alloc_unescaped(cursor, bump, b'"')?)
+        }
+        b'd' | b'D' => {
+            cursor.expect(b"\"")?;
+            let raw = alloc_unescaped(cursor, bump, b'"')?;
+            LlsdRef::Date(date::parse_rfc3339(raw)?)
+        }
+        b'b' | b'B' => {
+            if cursor.peek() == Some(b'(') {
+                LlsdRef::Binary(cursor.notation_sized()?)
+            } else {
+                cursor.expect(b"1")?;
+                cursor.expect(b"6")?;
+                cursor.expect(b"\"")?;
+                let hex = cursor.skip_escaped(b'"')?;
+                let mut buf = BumpVec::with_capacity_in(hex.len() / 2, bump);
+                for pair in hex.chunks(2) {
+                    buf.push(u8::from_str_radix(std::str::from_utf8(pair)?, 16)?);
+                }
+                LlsdRef::Binary(buf.into_bump_slice())
+            }
+        }
+        c => return Err(anyhow::anyhow!("Invalid character: 0x{c:02x}")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{binary, notation};
+
+    fn sample() -> Llsd {
+        let mut permissions = LlsdMap::new();
+        permissions.insert("owner_mask".into(), Llsd::Integer(0x7fff_ffff));
+        permissions.insert("group_id".into(), Llsd::Uuid(Uuid::from_u128(9)));
+        let mut object = LlsdMap::new();
+        object.insert("name".into(), Llsd::String("it's \"quoted\"".into()));
+        object.insert("scale".into(), Llsd::Real(0.5));
+        object.insert("texture".into(), Llsd::Binary(vec![0, 1, 0xfe, 0xff]));
+        object.insert("permissions".into(), Llsd::Map(permissions));
+        object.insert(
+            "created".into(),
+            Llsd::Date(Date::from_timestamp(1_620_000_000, 0).unwrap()),
+        );
+        object.insert(
+            "url".into(),
+            Llsd::Uri(Uri::parse("https://example.com/a?b=c")),
+        );
+        object.insert(
+            "flags".into(),
+            Llsd::Array(vec![Llsd::Boolean(true), Llsd::Undefined]),
+        );
+        Llsd::Array(vec![Llsd::Map(object), Llsd::Map(LlsdMap::new())])
+    }
+
+    #[test]
+    fn binary_matches_owned_parse() {
+        let llsd = sample();
+        let encoded = binary::to_vec(&llsd).unwrap();
+        let bump = Bump::new();
+        let parsed = binary::from_slice_in(&encoded, &bump).unwrap();
+        assert_eq!(parsed.to_llsd(), llsd);
+
+        let name = parsed.get_index(0).and_then(|o| o.get("name")).unwrap();
+        let name = name.as_string().unwrap();
+        // Binary strings are borrowed straight from the input.
+        assert!(encoded.as_ptr_range().contains(&name.as_ptr()));
+    }
+
+    #[test]
+    fn notation_matches_owned_parse() {
+        let llsd = sample();
+        for context in [
+            notation::FormatterContext::new(),
+            notation::FormatterContext::new()
+                .with_pretty(true)
+                .with_hex(true),
+        ] {
+            let encoded = notation::to_vec(&llsd, &context).unwrap();
+            let bump = Bump::new();
+            let parsed = notation::from_bytes_in(&encoded, 64, &bump).unwrap();
+            assert_eq!(parsed.to_llsd(), llsd);
+        }
+    }
+
+    #[test]
+    fn repeated_keys_resolve_to_last() {
+        let bump = Bump::new();
+        let parsed = notation::from_bytes_in(b"{'a':i1,'a':i2}", 64, &bump).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.get("a"), Some(&LlsdRef::Integer(2)));
+        assert_eq!(parsed.get("b"), None);
+    }
+
+    #[test]
+    fn depth_is_limited() {
+        let mut llsd = Llsd::Undefined;
+        for _ in 0..64 {
+            llsd = Llsd::Array(vec![llsd]);
+        }
+        let encoded = binary::to_vec_with_depth(&llsd, 65).unwrap();
+        let bump = Bump::new();
+        assert!(binary::from_slice_in(&encoded, &bump).is_err());
+        assert!(notation::from_bytes_in("[".repeat(100).as_bytes(), 64, &bump).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::io::Read;
 
+use crate::parse::{ParseFailure, ParseOptions, ParseWarnings};
 use crate::{Llsd, binary, notation, xml};
 
 const MAX_HDR_LEN: usize = 20;
@@ -67,6 +68,46 @@ pub fn from_slice_with(data: &[u8], options: AutoDecodeOptions) -> Result<Llsd,
     }
 }
 
+/// Like [`from_slice`], reporting failures as a [`ParseFailure`] whose offset
+/// counts from the start of `data`, header included.
+pub fn from_slice_with_options(data: &[u8], options: &ParseOptions) -> Result<Llsd, anyhow::Error> {
+    from_slice_reporting(data, options, None)
+}
+
+/// Like [`from_slice_with_options`], reporting what the detected format's
+/// `from_*_with_warnings` reports to `warnings`.
+pub fn from_slice_with_warnings(
+    data: &[u8],
+    options: &ParseOptions,
+    warnings: &mut ParseWarnings<'_>,
+) -> Result<Llsd, anyhow::Error> {
+    from_slice_reporting(data, options, Some(warnings))
+}
+
+fn from_slice_reporting(
+    data: &[u8],
+    options: &ParseOptions,
+    warnings: Option<&mut ParseWarnings<'_>>,
+) -> Result<Llsd, anyhow::Error> {
+    let format = detect_format(data);
+    let payload = payload_after_header(data, format);
+    let result = match (format, warnings) {
+        (LlsdEncoding::Binary, None) => binary::from_slice_with_options(payload, options),
+        (LlsdEncoding::Binary, Some(w)) => binary::from_slice_with_warnings(payload, options, w),
+        (LlsdEncoding::Xml, None) => xml::from_slice_with_options(payload, options),
+        (LlsdEncoding::Xml, Some(w)) => xml::from_slice_with_warnings(payload, options, w),
+        (LlsdEncoding::Notation, None) => notation::from_bytes_with_options(payload, options),
+        (LlsdEncoding::Notation, Some(w)) => {
+            notation::from_bytes_with_warnings(payload, options, w)
+        }
+    };
+    let base = data.len() - payload.len();
+    result.map_err(|e| match e.downcast::<ParseFailure>() {
+        Ok(failure) => failure.rebase(data, base, options).into(),
+        Err(e) => e,
+    })
+}
+
 pub fn from_reader<R: Read>(mut reader: R) -> Result<Llsd, anyhow::Error> {
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
@@ -103,7 +144,7 @@ fn detect_header_token(data: &[u8]) -> Option<String> {
     }
 }
 
-fn payload_after_header(data: &[u8], format: LlsdEncoding) -> &[u8] {
+pub(crate) fn payload_after_header(data: &[u8], format: LlsdEncoding) -> &[u8] {
     if starts_with_ignore_ascii_case(data, LEGACY_NON_HEADER) {
         return data;
     }
@@ -1,15 +1,77 @@
-use std::io::{Read, Write};
+use std::fs::File;
+use std::io::{self, Read, Write};
 
-use chrono::{DateTime, Utc};
-use uuid::Uuid;
+use crate::Uuid;
 
-use crate::{Llsd, Uri};
+use crate::Date;
+use crate::autodetect::LlsdEncoding;
+use crate::compare::{Token, TokenSource};
+use crate::escape;
+use crate::metrics::{self, Limit, Operation};
+use crate::parse::{
+    ParseFailure, ParseOptions, ParseWarningKind, ParseWarnings, Warn, locate, unlocate,
+};
+use crate::scalar::{LlsdType, Scalar};
+use crate::{Llsd, PathSegment, UndefinedEntries, Uri};
 
 const DEFAULT_MAX_DEPTH: usize = 64;
 const MAX_UNBOUNDED_LENGTH: usize = 64 * 1024 * 1024;
 const MAX_UNBOUNDED_CONTAINER_ENTRIES: usize = 1_000_000;
 
-fn write_inner<W: Write>(llsd: &Llsd, w: &mut W) -> Result<(), anyhow::Error> {
+/// Writer settings.
+#[derive(Clone, Copy)]
+struct WriteOptions {
+    max_depth: usize,
+    sorted_keys: bool,
+    undefined: UndefinedEntries,
+    /// The profile of [`to_canonical_vec`]: normalized reals and dates.
+    canonical: bool,
+}
+
+impl WriteOptions {
+    const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            sorted_keys: false,
+            undefined: UndefinedEntries::Keep,
+            canonical: false,
+        }
+    }
+}
+
+/// The one bit pattern canonical output uses for NaN, and `+0.0` for `-0.0`.
+fn canonical_real(v: f64) -> f64 {
+    if v.is_nan() {
+        f64::NAN
+    } else if v == 0.0 {
+        0.0
+    } else {
+        v
+    }
+}
+
+/// Seconds since the epoch as the binary format stores dates. Canonical
+/// output rounds to the millisecond, well above the precision the `f64`
+/// keeps, so a canonical date reads back as the same instant and re-encodes
+/// to the same bytes.
+fn date_to_real(v: &Date, canonical: bool) -> f64 {
+    if canonical {
+        let millis = (i64::from(v.timestamp_subsec_nanos()) + 500_000) / 1_000_000;
+        canonical_real(v.timestamp() as f64 + millis as f64 / 1000.0)
+    } else {
+        v.timestamp() as f64 + (v.timestamp_subsec_nanos() as f64 / 1_000_000_000.0)
+    }
+}
+
+/// The date stored as `real` seconds since the epoch; the epoch itself when
+/// it is out of range or not a number.
+pub(crate) fn real_to_date(real: f64) -> Date {
+    // Floor, so the fraction is positive for dates before the epoch.
+    let secs = real.floor();
+    Date::from_timestamp(secs as i64, ((real - secs) * 1_000_000_000.0) as u32).unwrap_or_default()
+}
+
+fn write_scalar<W: Write>(llsd: &Llsd, w: &mut W, canonical: bool) -> Result<(), anyhow::Error> {
     match llsd {
         Llsd::Undefined => w.write_all(b"!")?,
         Llsd::Boolean(v) => w.write_all(if *v { b"1" } else { b"0" })?,
@@ -19,6 +81,7 @@ fn write_inner<W: Write>(llsd: &Llsd, w: &mut W) -> Result<(), anyhow::Error> {
         }
         Llsd::Real(v) => {
             w.write_all(b"r")?;
+            let v = if canonical { canonical_real(*v) } else { *v };
             w.write_all(&v.to_be_bytes())?;
         }
         Llsd::String(v) => {
@@ -38,8 +101,7 @@ fn write_inner<W: Write>(llsd: &Llsd, w: &mut W) -> Result<(), anyhow::Error> {
         }
         Llsd::Date(v) => {
             w.write_all(b"d")?;
-            let real: f64 =
-                v.timestamp() as f64 + (v.timestamp_subsec_nanos() as f64 / 1_000_000_000.0);
+            let real = date_to_real(v, canonical);
             // Use little endian
             w.write_all(&real.to_le_bytes())?;
         }
@@ -48,47 +110,346 @@ fn write_inner<W: Write>(llsd: &Llsd, w: &mut W) -> Result<(), anyhow::Error> {
             w.write_all(&(v.len() as u32).to_be_bytes())?;
             w.write_all(v)?;
         }
-        Llsd::Array(v) => {
-            w.write_all(b"[")?;
-            w.write_all(&(v.len() as u32).to_be_bytes())?;
-            for e in v {
-                write_inner(e, w)?;
+        Llsd::Array(_) | Llsd::Map(_) => unreachable!("containers are written by write_inner"),
+    }
+    Ok(())
+}
+
+/// Open containers while writing, innermost last.
+enum Frame<'a> {
+    Array(std::slice::Iter<'a, Llsd>),
+    Map(crate::order::Entries<'a>),
+}
+
+/// Writes with an explicit stack instead of recursion, so nesting is bounded
+/// by `max_depth` (with the parser's meaning) rather than by the call stack.
+/// Scalars `source` opens are written as binaries streamed from it.
+fn write_inner<'a, W: Write>(
+    llsd: &'a Llsd,
+    w: &mut W,
+    options: WriteOptions,
+    stack: &mut Vec<Frame<'a>>,
+    mut source: Option<&mut dyn BinarySource>,
+) -> Result<(), anyhow::Error> {
+    stack.clear();
+    let mut next = Some(llsd);
+    loop {
+        if let Some(value) = next.take() {
+            if stack.len() >= options.max_depth {
+                metrics::limit_hit(LlsdEncoding::Binary, Operation::Write, Limit::Depth);
+                return Err(anyhow::anyhow!(
+                    "LLSD binary maximum recursion depth exceeded"
+                ));
             }
-            w.write_all(b"]")?;
-        }
-        Llsd::Map(v) => {
-            w.write_all(b"{")?;
-            w.write_all(&(v.len() as u32).to_be_bytes())?;
-            for (k, e) in v {
-                w.write_all(b"k")?;
-                w.write_all(&(k.len() as u32).to_be_bytes())?;
-                w.write_all(k.as_bytes())?;
-                write_inner(e, w)?;
+            match value {
+                Llsd::Array(v) => {
+                    w.write_all(b"[")?;
+                    w.write_all(&(v.len() as u32).to_be_bytes())?;
+                    stack.push(Frame::Array(v.iter()));
+                }
+                Llsd::Map(v) => {
+                    w.write_all(b"{")?;
+                    let len = crate::order::written_len(v, options.undefined);
+                    w.write_all(&(len as u32).to_be_bytes())?;
+                    stack.push(Frame::Map(crate::order::write_entries(
+                        v,
+                        None,
+                        options.sorted_keys,
+                        options.undefined,
+                    )));
+                }
+                scalar => match source.as_deref_mut().map(|s| s.open(scalar)).transpose()? {
+                    Some(Some((len, reader))) => write_stream(w, len, reader)?,
+                    _ => write_scalar(scalar, w, options.canonical)?,
+                },
             }
-            w.write_all(b"}")?;
+        }
+        match stack.last_mut() {
+            None => return Ok(()),
+            Some(Frame::Array(items)) => match items.next() {
+                Some(item) => next = Some(item),
+                None => {
+                    w.write_all(b"]")?;
+                    stack.pop();
+                }
+            },
+            Some(Frame::Map(entries)) => match entries.next() {
+                Some((k, item, _)) => {
+                    w.write_all(b"k")?;
+                    w.write_all(&(k.len() as u32).to_be_bytes())?;
+                    w.write_all(k.as_bytes())?;
+                    next = Some(item);
+                }
+                None => {
+                    w.write_all(b"}")?;
+                    stack.pop();
+                }
+            },
         }
     }
-    Ok(())
 }
 
 pub fn write<W: Write>(llsd: &Llsd, w: &mut W) -> Result<(), anyhow::Error> {
-    write_inner(llsd, w)
+    write_with_depth(llsd, w, DEFAULT_MAX_DEPTH)
+}
+
+/// Like [`write`], failing on values nested deeper than `max_depth` levels
+/// (the limit [`from_slice_with_depth`] would reject them with).
+pub fn write_with_depth<W: Write>(
+    llsd: &Llsd,
+    w: &mut W,
+    max_depth: usize,
+) -> Result<(), anyhow::Error> {
+    let mut out = crate::output::Output::new(w);
+    write_inner(
+        llsd,
+        &mut out,
+        WriteOptions::new(max_depth),
+        &mut Vec::new(),
+        None,
+    )?;
+    Ok(out.finish()?)
+}
+
+/// Binary payloads [`write_streaming`] copies from a reader rather than
+/// from the value, so large uploads don't have to be held in memory.
+pub trait BinarySource {
+    /// The payload to write as a binary in place of the scalar `value`, with
+    /// its exact length, or `None` to write `value` as it is.
+    fn open(&mut self, value: &Llsd) -> io::Result<Option<(u64, Box<dyn Read + '_>)>>;
+}
+
+impl<F> BinarySource for F
+where
+    F: FnMut(&Llsd) -> io::Result<Option<(u64, Box<dyn Read>)>>,
+{
+    fn open(&mut self, value: &Llsd) -> io::Result<Option<(u64, Box<dyn Read + '_>)>> {
+        self(value)
+    }
+}
+
+/// Streams the file behind each `file://` URI in the value, as
+/// [`rpc::DirSpool`](crate::rpc::DirSpool) leaves them, as a binary. Other
+/// URIs are written as URIs.
+#[derive(Debug, Default)]
+pub struct FileSource;
+
+impl BinarySource for FileSource {
+    fn open(&mut self, value: &Llsd) -> io::Result<Option<(u64, Box<dyn Read + '_>)>> {
+        let Llsd::Uri(uri) = value else {
+            return Ok(None);
+        };
+        let Some(path) = url::Url::try_from(uri)
+            .ok()
+            .filter(|url| url.scheme() == "file")
+        else {
+            return Ok(None);
+        };
+        let path = path.to_file_path().map_err(|()| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path} is not a local file"),
+            )
+        })?;
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Some((len, Box::new(file))))
+    }
+}
+
+/// Like [`write`], writing the scalars `source` opens as binaries copied
+/// from its readers, never holding a payload in memory.
+///
+/// ```
+/// use std::io::{self, Read};
+/// use llsd_rs::{Llsd, binary, llsd_map};
+///
+/// let upload = llsd_map! { "name" => "terrain.raw", "data" => Llsd::Uri("stream:0".into()) };
+/// let mut source = |value: &Llsd| -> io::Result<Option<(u64, Box<dyn Read>)>> {
+///     Ok(match value {
+///         Llsd::Uri(uri) if uri.as_str() == "stream:0" => {
+///             Some((1 << 20, Box::new(io::repeat(7).take(1 << 20))))
+///         }
+///         _ => None,
+///     })
+/// };
+/// let mut out = Vec::new();
+/// binary::write_streaming(&upload, &mut out, &mut source).unwrap();
+/// let read = binary::from_slice(&out).unwrap();
+/// assert_eq!(read["data"].as_binary().unwrap().len(), 1 << 20);
+/// ```
+pub fn write_streaming<W: Write>(
+    llsd: &Llsd,
+    w: &mut W,
+    source: &mut impl BinarySource,
+) -> Result<(), anyhow::Error> {
+    let mut out = crate::output::Output::new(w);
+    write_inner(
+        llsd,
+        &mut out,
+        WriteOptions::new(DEFAULT_MAX_DEPTH),
+        &mut Vec::new(),
+        Some(source),
+    )?;
+    Ok(out.finish()?)
+}
+
+/// Write a binary of `len` bytes copied from `reader`, which must hold
+/// exactly that many.
+fn write_stream<W: Write>(
+    w: &mut W,
+    len: u64,
+    reader: Box<dyn Read + '_>,
+) -> Result<(), anyhow::Error> {
+    let Ok(len32) = u32::try_from(len) else {
+        return Err(anyhow::anyhow!(
+            "binary stream of {len} bytes is too long for LLSD"
+        ));
+    };
+    w.write_all(b"b")?;
+    w.write_all(&len32.to_be_bytes())?;
+    let copied = io::copy(&mut reader.take(len), w)?;
+    if copied != len {
+        return Err(anyhow::anyhow!(
+            "binary stream ended after {copied} of {len} bytes"
+        ));
+    }
+    Ok(())
 }
 
 pub fn to_vec(llsd: &Llsd) -> Result<Vec<u8>, anyhow::Error> {
+    to_vec_with_depth(llsd, DEFAULT_MAX_DEPTH)
+}
+
+pub fn to_vec_with_depth(llsd: &Llsd, max_depth: usize) -> Result<Vec<u8>, anyhow::Error> {
+    metrics::write(LlsdEncoding::Binary, llsd, || {
+        let mut buf = Vec::new();
+        write_inner(
+            llsd,
+            &mut buf,
+            WriteOptions::new(max_depth),
+            &mut Vec::new(),
+            None,
+        )?;
+        Ok(buf)
+    })
+}
+
+/// A reusable binary writer.
+///
+/// Keeps its container stack between calls, so a service encoding one message
+/// after another doesn't reallocate it each time; pair it with an output
+/// buffer that is cleared rather than dropped between messages.
+pub struct BinaryEncoder {
+    options: WriteOptions,
+    stack: Vec<Frame<'static>>,
+}
+
+impl BinaryEncoder {
+    pub fn new() -> Self {
+        Self {
+            options: WriteOptions::new(DEFAULT_MAX_DEPTH),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Fail instead of writing values nested deeper than `max_depth` levels.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// Write map entries sorted by key, so equal values always encode to the
+    /// same bytes.
+    pub fn with_sorted_keys(mut self, sorted_keys: bool) -> Self {
+        self.options.sorted_keys = sorted_keys;
+        self
+    }
+
+    /// Keep (the default) or leave out map entries whose value is undefined.
+    pub fn with_undefined_entries(mut self, undefined: UndefinedEntries) -> Self {
+        self.options.undefined = undefined;
+        self
+    }
+
+    /// Append the binary encoding of `llsd` to `out`.
+    ///
+    /// On error `out` may hold a partial encoding past its original length.
+    pub fn encode_into(&mut self, llsd: &Llsd, out: &mut Vec<u8>) -> Result<(), anyhow::Error> {
+        let mut stack = crate::output::recycle(std::mem::take(&mut self.stack));
+        let result = write_inner(llsd, out, self.options, &mut stack, None);
+        self.stack = crate::output::recycle(stack);
+        result
+    }
+
+    pub fn encode(&mut self, llsd: &Llsd) -> Result<Vec<u8>, anyhow::Error> {
+        let mut out = Vec::new();
+        self.encode_into(llsd, &mut out)?;
+        Ok(out)
+    }
+}
+
+impl Default for BinaryEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encode `llsd` in the canonical binary profile, so equal values always give
+/// the same bytes (for hashing or signing):
+///
+/// - no `<? LLSD/Binary ?>` header;
+/// - map keys in ascending byte order;
+/// - one NaN bit pattern, and `+0.0` in place of `-0.0`, for reals and dates;
+/// - dates rounded to the millisecond.
+///
+/// Everything else is written as [`to_vec`] writes it.
+pub fn to_canonical_vec(llsd: &Llsd) -> Result<Vec<u8>, anyhow::Error> {
+    let options = WriteOptions {
+        sorted_keys: true,
+        canonical: true,
+        ..WriteOptions::new(DEFAULT_MAX_DEPTH)
+    };
     let mut buf = Vec::new();
-    write(llsd, &mut buf)?;
+    write_inner(llsd, &mut buf, options, &mut Vec::new(), None)?;
     Ok(buf)
 }
 
+/// Decode `data`, failing unless it is exactly the canonical encoding
+/// ([`to_canonical_vec`]) of the value it holds.
+pub fn verify_canonical(data: &[u8]) -> Result<Llsd, anyhow::Error> {
+    let llsd = from_slice(data)?;
+    if to_canonical_vec(&llsd)? != data {
+        return Err(anyhow::anyhow!("LLSD binary is not in canonical form"));
+    }
+    Ok(llsd)
+}
+
 struct BinaryReader<'a, R: Read> {
     reader: &'a mut R,
     remaining: Option<usize>,
+    warnings: Option<&'a mut dyn Warn>,
 }
 
 impl<'a, R: Read> BinaryReader<'a, R> {
     fn new(reader: &'a mut R, remaining: Option<usize>) -> Self {
-        Self { reader, remaining }
+        Self {
+            reader,
+            remaining,
+            warnings: None,
+        }
+    }
+
+    fn enter(&mut self, segment: impl FnOnce() -> PathSegment) {
+        if let Some(warnings) = self.warnings.as_deref_mut() {
+            warnings.enter(segment());
+        }
+    }
+
+    fn leave(&mut self) {
+        if let Some(warnings) = self.warnings.as_deref_mut() {
+            warnings.leave();
+        }
     }
 
     fn remaining(&self) -> Option<usize> {
@@ -112,6 +473,29 @@ impl<'a, R: Read> BinaryReader<'a, R> {
         Ok(())
     }
 
+    fn skip(&mut self, len: usize) -> Result<(), anyhow::Error> {
+        if let Some(remaining) = self.remaining
+            && len > remaining
+        {
+            return Err(anyhow::anyhow!(
+                "LLSD binary length {} exceeds remaining input {}",
+                len,
+                remaining
+            ));
+        }
+        let skipped = std::io::copy(
+            &mut (&mut *self.reader).take(len as u64),
+            &mut std::io::sink(),
+        )?;
+        if skipped != len as u64 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= len;
+        }
+        Ok(())
+    }
+
     fn read_optional_u8(&mut self) -> Result<Option<u8>, anyhow::Error> {
         if self.remaining == Some(0) {
             return Ok(None);
@@ -167,6 +551,7 @@ fn read_len<R: Read>(
             ));
         }
     } else if len > MAX_UNBOUNDED_LENGTH {
+        metrics::limit_hit(LlsdEncoding::Binary, Operation::Parse, Limit::Length);
         return Err(anyhow::anyhow!(
             "LLSD binary {context} length {len} exceeds max {MAX_UNBOUNDED_LENGTH}"
         ));
@@ -180,6 +565,7 @@ fn read_container_len<R: Read>(
 ) -> Result<usize, anyhow::Error> {
     let len = read_len(reader, context)?;
     if reader.remaining().is_none() && len > MAX_UNBOUNDED_CONTAINER_ENTRIES {
+        metrics::limit_hit(LlsdEncoding::Binary, Operation::Parse, Limit::Entries);
         return Err(anyhow::anyhow!(
             "LLSD binary {context} entry count {len} exceeds max {MAX_UNBOUNDED_CONTAINER_ENTRIES}"
         ));
@@ -187,14 +573,9 @@ fn read_container_len<R: Read>(
     Ok(len)
 }
 
+/// A hex digit of a `\x` escape; like the viewer, anything else reads as 0.
 fn hex<R: Read>(r: &mut BinaryReader<'_, R>) -> Result<u8, anyhow::Error> {
-    let c = read_u8(r)?;
-    match c {
-        b'0'..=b'9' => Ok(c - b'0'),
-        b'a'..=b'f' => Ok(c - b'a' + 10),
-        b'A'..=b'F' => Ok(c - b'A' + 10),
-        _ => Ok(0),
-    }
+    Ok(escape::hex_value(read_u8(r)?).unwrap_or(0))
 }
 
 fn unescape<R: Read>(r: &mut BinaryReader<'_, R>, delim: u8) -> Result<String, anyhow::Error> {
@@ -203,18 +584,8 @@ fn unescape<R: Read>(r: &mut BinaryReader<'_, R>, delim: u8) -> Result<String, a
         match read_u8(r)? {
             c if c == delim => break,
             b'\\' => match read_u8(r)? {
-                b'a' => buf.push(0x07),
-                b'b' => buf.push(0x08),
-                b'f' => buf.push(0x0c),
-                b'n' => buf.push(b'\n'),
-                b'r' => buf.push(b'\r'),
-                b't' => buf.push(b'\t'),
-                b'v' => buf.push(0x0b),
-                b'\\' => buf.push(b'\\'),
-                b'\'' => buf.push(b'\''),
-                b'"' => buf.push(b'"'),
                 b'x' => buf.push((hex(r)? << 4) | hex(r)?),
-                other => buf.push(other),
+                other => buf.push(escape::unescape_char(other)),
             },
             other => buf.push(other),
         }
@@ -228,6 +599,7 @@ fn from_reader_inner_with_tag<R: Read>(
     depth_remaining: usize,
 ) -> Result<Llsd, anyhow::Error> {
     if depth_remaining == 0 {
+        metrics::limit_hit(LlsdEncoding::Binary, Operation::Parse, Limit::Depth);
         return Err(anyhow::anyhow!(
             "LLSD binary maximum recursion depth exceeded"
         ));
@@ -259,12 +631,7 @@ fn from_reader_inner_with_tag<R: Read>(
             let mut buf = [0_u8; 8];
             r.read_exact(&mut buf)?;
             // Use little endian
-            let real = f64::from_le_bytes(buf);
-            let date = DateTime::<Utc>::from_timestamp(
-                real.trunc() as i64,
-                (real.fract() * 1_000_000_000.0) as u32,
-            );
-            Ok(Llsd::Date(date.unwrap_or_default()))
+            Ok(Llsd::Date(real_to_date(f64::from_le_bytes(buf))))
         }
         b'b' => {
             let len = read_len(r, "binary")?;
@@ -276,7 +643,13 @@ fn from_reader_inner_with_tag<R: Read>(
             let len = read_container_len(r, "array")?;
             let mut buf = Vec::with_capacity(len);
             for _ in 0..len {
-                buf.push(read_inner(r, depth_remaining - 1)?);
+                let index = buf.len();
+                r.enter(|| PathSegment::Index(index));
+                buf.push(
+                    read_inner(r, depth_remaining - 1)
+                        .map_err(|e| locate(e, PathSegment::Index(index)))?,
+                );
+                r.leave();
             }
             if read_u8(r)? != b']' {
                 return Err(anyhow::anyhow!("Expected ']'"));
@@ -285,7 +658,7 @@ fn from_reader_inner_with_tag<R: Read>(
         }
         b'{' => {
             let len = read_container_len(r, "map")?;
-            let mut buf = std::collections::HashMap::with_capacity(len);
+            let mut buf = crate::LlsdMap::with_capacity(len);
             for _ in 0..len {
                 if read_u8(r)? != b'k' {
                     return Err(anyhow::anyhow!("Expected 'k'"));
@@ -294,7 +667,17 @@ fn from_reader_inner_with_tag<R: Read>(
                 let mut key_buf = vec![0; key_len];
                 r.read_exact(&mut key_buf)?;
                 let key = String::from_utf8(key_buf)?;
-                let value = read_inner(r, depth_remaining - 1)?;
+                if let Some(warnings) = r.warnings.as_deref_mut()
+                    && buf.contains_key(key.as_str())
+                {
+                    warnings.warn(ParseWarningKind::DuplicateKey(key.clone()));
+                }
+                r.enter(|| PathSegment::Key(key.clone()));
+                let value = match read_inner(r, depth_remaining - 1) {
+                    Ok(value) => value,
+                    Err(e) => return Err(locate(e, PathSegment::Key(key))),
+                };
+                r.leave();
                 buf.insert(key, value);
             }
             if read_u8(r)? != b'}' {
@@ -318,7 +701,7 @@ fn read_inner<R: Read>(
 
 pub fn from_reader_inner<R: Read>(r: &mut R) -> Result<Llsd, anyhow::Error> {
     let mut reader = BinaryReader::new(r, None);
-    read_inner(&mut reader, DEFAULT_MAX_DEPTH)
+    read_inner(&mut reader, DEFAULT_MAX_DEPTH).map_err(unlocate)
 }
 
 fn looks_like_llsd_binary_header(header: &[u8]) -> bool {
@@ -328,14 +711,12 @@ fn looks_like_llsd_binary_header(header: &[u8]) -> bool {
         .any(|w| w.eq_ignore_ascii_case(NEEDLE))
 }
 
-fn from_binary_reader<R: Read>(
-    r: &mut BinaryReader<'_, R>,
-    max_depth: usize,
-) -> Result<Llsd, anyhow::Error> {
+/// Consume the optional `<? LLSD/Binary ?>` header and return the first tag.
+fn read_first_tag<R: Read>(r: &mut BinaryReader<'_, R>) -> Result<u8, anyhow::Error> {
     let mut first = [0u8; 1];
     r.read_exact(&mut first)?;
     if first[0] != b'<' {
-        return from_reader_inner_with_tag(r, first[0], max_depth);
+        return Ok(first[0]);
     }
 
     let mut header = vec![first[0]];
@@ -354,13 +735,11 @@ fn from_binary_reader<R: Read>(
         return Err(anyhow::anyhow!("Unexpected LLSD header"));
     }
 
-    // consume optional whitespace after header, then parse next tag
+    // consume optional whitespace after header, then return the next tag
     loop {
         match r.read_optional_u8()? {
             Some(b' ' | b'\r' | b'\n' | b'\t') => continue,
-            Some(next) => {
-                return from_reader_inner_with_tag(r, next, max_depth);
-            }
+            Some(next) => return Ok(next),
             None => {
                 return Err(anyhow::anyhow!("Unexpected EOF after LLSD header"));
             }
@@ -368,6 +747,254 @@ fn from_binary_reader<R: Read>(
     }
 }
 
+fn from_binary_reader<R: Read>(
+    r: &mut BinaryReader<'_, R>,
+    max_depth: usize,
+) -> Result<Llsd, anyhow::Error> {
+    from_binary_reader_located(r, max_depth).map_err(unlocate)
+}
+
+/// Like [`from_binary_reader`], keeping the path of the failing value on the
+/// error for [`ParseFailure`].
+fn from_binary_reader_located<R: Read>(
+    r: &mut BinaryReader<'_, R>,
+    max_depth: usize,
+) -> Result<Llsd, anyhow::Error> {
+    let tag = read_first_tag(r)?;
+    from_reader_inner_with_tag(r, tag, max_depth)
+}
+
+/// Step over the value starting with `tag` without building it.
+fn skip_value<R: Read>(r: &mut BinaryReader<'_, R>, tag: u8) -> Result<(), anyhow::Error> {
+    let mut tag = tag;
+    let mut depth = 0usize;
+    loop {
+        match tag {
+            b'!' | b'1' | b'0' => {}
+            b'i' => r.skip(4)?,
+            b'r' | b'd' => r.skip(8)?,
+            b'u' => r.skip(16)?,
+            b's' | b'l' | b'b' => {
+                let len = read_len(r, "value")?;
+                r.skip(len)?;
+            }
+            b'"' | b'\'' => loop {
+                match read_u8(r)? {
+                    b'\\' => {
+                        read_u8(r)?;
+                    }
+                    c if c == tag => break,
+                    _ => {}
+                }
+            },
+            b'[' | b'{' => {
+                read_container_len(r, "container")?;
+                depth += 1;
+            }
+            b'k' if depth > 0 => {
+                let len = read_len(r, "map key")?;
+                r.skip(len)?;
+            }
+            b']' | b'}' if depth > 0 => depth -= 1,
+            other => return Err(anyhow::anyhow!("Unknown LLSD type: {}", other)),
+        }
+        if depth == 0 {
+            return Ok(());
+        }
+        tag = read_u8(r)?;
+    }
+}
+
+/// A document as [`Token`]s, for [`crate::compare`].
+pub(crate) struct Tokens<R: Read> {
+    reader: R,
+    /// Open containers: entries left, whether it is a map, and whether that
+    /// map's next entry has had its key read.
+    stack: Vec<(usize, bool, bool)>,
+    started: bool,
+}
+
+impl<R: Read> Tokens<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            stack: Vec::new(),
+            started: false,
+        }
+    }
+}
+
+impl<R: Read> TokenSource for Tokens<R> {
+    fn next_token(&mut self) -> Result<Option<Token>, anyhow::Error> {
+        let r = &mut BinaryReader::new(&mut self.reader, None);
+        let tag = if !self.started {
+            self.started = true;
+            read_first_tag(r)?
+        } else {
+            let Some((remaining, map, keyed)) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            if *remaining == 0 {
+                let close = if *map { b'}' } else { b']' };
+                if read_u8(r)? != close {
+                    return Err(anyhow::anyhow!("Expected '{}'", close as char));
+                }
+                self.stack.pop();
+                return Ok(Some(Token::End));
+            }
+            if *map && !*keyed {
+                if read_u8(r)? != b'k' {
+                    return Err(anyhow::anyhow!("Expected 'k'"));
+                }
+                let key_len = read_len(r, "map key")?;
+                let mut key = vec![0; key_len];
+                r.read_exact(&mut key)?;
+                *keyed = true;
+                return Ok(Some(Token::Key(String::from_utf8(key)?)));
+            }
+            *remaining -= 1;
+            *keyed = false;
+            read_u8(r)?
+        };
+        Ok(Some(match tag {
+            b'[' => {
+                self.stack
+                    .push((read_container_len(r, "array")?, false, false));
+                Token::ArrayStart
+            }
+            b'{' => {
+                self.stack
+                    .push((read_container_len(r, "map")?, true, false));
+                Token::MapStart
+            }
+            tag => Token::Scalar(from_reader_inner_with_tag(r, tag, 1).map_err(unlocate)?),
+        }))
+    }
+}
+
+/// The type of the value `data` starts with, after an optional
+/// `<? LLSD/Binary ?>` header; `None` for an unknown tag or empty input.
+pub fn peek_type(data: &[u8]) -> Option<LlsdType> {
+    let data = crate::autodetect::payload_after_header(data, LlsdEncoding::Binary);
+    LlsdType::from_tag(LlsdEncoding::Binary, *data.first()?)
+}
+
+/// Read the scalar at the start of `data` (its tag byte first) without
+/// allocating, returning it with the number of bytes it took. `None` for a
+/// container, an unknown tag, or a value cut short or malformed.
+pub fn read_scalar(data: &[u8]) -> Option<(Scalar<'_>, usize)> {
+    let (&tag, rest) = data.split_first()?;
+    let (scalar, len) = match tag {
+        b'!' => (Scalar::Undefined, 0),
+        b'1' => (Scalar::Boolean(true), 0),
+        b'0' => (Scalar::Boolean(false), 0),
+        b'i' => (Scalar::Integer(i32::from_be_bytes(fixed(rest)?)), 4),
+        b'r' => (Scalar::Real(f64::from_be_bytes(fixed(rest)?)), 8),
+        b'd' => (
+            Scalar::Date(real_to_date(f64::from_le_bytes(fixed(rest)?))),
+            8,
+        ),
+        b'u' => (Scalar::Uuid(Uuid::from_bytes(fixed(rest)?)), 16),
+        b's' => {
+            let (bytes, len) = sized(rest)?;
+            (Scalar::String(std::str::from_utf8(bytes).ok()?.into()), len)
+        }
+        b'l' => {
+            let (bytes, len) = sized(rest)?;
+            (Scalar::Uri(std::str::from_utf8(bytes).ok()?), len)
+        }
+        b'b' => {
+            let (bytes, len) = sized(rest)?;
+            (Scalar::Binary(bytes), len)
+        }
+        b'"' | b'\'' => {
+            let mut escaped = false;
+            let end = rest.iter().position(|&c| {
+                let closes = c == tag && !escaped;
+                escaped = c == b'\\' && !escaped;
+                closes
+            })?;
+            let text = escape::unescape_notation(&rest[..end]).ok()?;
+            (Scalar::String(text), end + 1)
+        }
+        _ => return None,
+    };
+    Some((scalar, 1 + len))
+}
+
+/// Read the map key at the start of `data` (its `k` tag first), returning
+/// it with the number of bytes it took.
+pub fn read_key(data: &[u8]) -> Option<(&str, usize)> {
+    let rest = data.strip_prefix(b"k")?;
+    let (bytes, len) = sized(rest)?;
+    Some((std::str::from_utf8(bytes).ok()?, 1 + len))
+}
+
+fn fixed<const N: usize>(data: &[u8]) -> Option<[u8; N]> {
+    data.get(..N)?.try_into().ok()
+}
+
+/// Bytes after a big-endian `u32` length, and the length of both.
+fn sized(data: &[u8]) -> Option<(&[u8], usize)> {
+    let len = usize::try_from(u32::from_be_bytes(fixed(data)?)).ok()?;
+    Some((data.get(4..4 + len)?, 4 + len))
+}
+
+/// Decode only the value at `pointer` (the syntax of [`Llsd::pointer`]),
+/// stepping over everything before it using the length prefixes. Reading
+/// stops once that value is decoded. Returns `None` when the path does not
+/// exist; unlike a full decode, a repeated map key resolves to its first
+/// occurrence.
+pub fn extract_pointer<R: Read>(r: &mut R, pointer: &str) -> Result<Option<Llsd>, anyhow::Error> {
+    let Some(tokens) = crate::pointer_tokens(pointer) else {
+        return Ok(None);
+    };
+    let mut reader = BinaryReader::new(r, None);
+    let r = &mut reader;
+    let mut tag = read_first_tag(r)?;
+    for token in &tokens {
+        match tag {
+            b'{' => {
+                let len = read_container_len(r, "map")?;
+                let mut found = false;
+                for _ in 0..len {
+                    if read_u8(r)? != b'k' {
+                        return Err(anyhow::anyhow!("Expected 'k'"));
+                    }
+                    let key_len = read_len(r, "map key")?;
+                    let mut key = vec![0; key_len];
+                    r.read_exact(&mut key)?;
+                    tag = read_u8(r)?;
+                    if key == token.as_bytes() {
+                        found = true;
+                        break;
+                    }
+                    skip_value(r, tag)?;
+                }
+                if !found {
+                    return Ok(None);
+                }
+            }
+            b'[' => {
+                let len = read_container_len(r, "array")?;
+                let Some(index) = token.parse::<usize>().ok().filter(|&i| i < len) else {
+                    return Ok(None);
+                };
+                for _ in 0..index {
+                    let tag = read_u8(r)?;
+                    skip_value(r, tag)?;
+                }
+                tag = read_u8(r)?;
+            }
+            _ => return Ok(None),
+        }
+    }
+    let depth = DEFAULT_MAX_DEPTH.saturating_sub(tokens.len());
+    from_reader_inner_with_tag(r, tag, depth)
+        .map(Some)
+        .map_err(unlocate)
+}
+
 pub fn from_reader_with_depth<R: Read>(r: &mut R, max_depth: usize) -> Result<Llsd, anyhow::Error> {
     let mut reader = BinaryReader::new(r, None);
     from_binary_reader(&mut reader, max_depth)
@@ -378,20 +1005,70 @@ pub fn from_reader<R: Read>(r: &mut R) -> Result<Llsd, anyhow::Error> {
 }
 
 pub fn from_slice_with_depth(data: &[u8], max_depth: usize) -> Result<Llsd, anyhow::Error> {
-    let mut cursor = std::io::Cursor::new(data);
-    let mut reader = BinaryReader::new(&mut cursor, Some(data.len()));
-    from_binary_reader(&mut reader, max_depth)
+    metrics::parse(LlsdEncoding::Binary, data.len(), || {
+        let mut cursor = std::io::Cursor::new(data);
+        let mut reader = BinaryReader::new(&mut cursor, Some(data.len()));
+        from_binary_reader(&mut reader, max_depth)
+    })
 }
 
 pub fn from_slice(data: &[u8]) -> Result<Llsd, anyhow::Error> {
     from_slice_with_depth(data, DEFAULT_MAX_DEPTH)
 }
 
+/// Like [`from_slice_with_depth`], reporting failures as a [`ParseFailure`]
+/// with the offset, container path and (per `options`) surrounding bytes.
+pub fn from_slice_with_options(data: &[u8], options: &ParseOptions) -> Result<Llsd, anyhow::Error> {
+    from_slice_reporting(data, options, None)
+}
+
+/// Like [`from_slice_with_options`], reporting duplicate map keys to
+/// `warnings`.
+pub fn from_slice_with_warnings(
+    data: &[u8],
+    options: &ParseOptions,
+    warnings: &mut ParseWarnings<'_>,
+) -> Result<Llsd, anyhow::Error> {
+    from_slice_reporting(data, options, Some(warnings.begin()))
+}
+
+fn from_slice_reporting(
+    data: &[u8],
+    options: &ParseOptions,
+    warnings: Option<&mut dyn Warn>,
+) -> Result<Llsd, anyhow::Error> {
+    let mut cursor = std::io::Cursor::new(data);
+    let mut reader = BinaryReader::new(&mut cursor, Some(data.len()));
+    reader.warnings = warnings.map(|w| w as &mut dyn Warn);
+    let result = from_binary_reader_located(&mut reader, options.max_depth());
+    result.map_err(|e| {
+        let offset = cursor.position() as usize;
+        ParseFailure::new(LlsdEncoding::Binary, data, Some(offset), e, options).into()
+    })
+}
+
+/// Like [`from_slice`], decoding the elements of a top-level array, or of
+/// arrays directly under a top-level map, in parallel.
+#[cfg(feature = "rayon")]
+pub fn from_slice_par(data: &[u8]) -> Result<Llsd, anyhow::Error> {
+    let lazy = crate::LazyLlsd::from_binary(data)?;
+    crate::lazy::decode_par(&lazy, DEFAULT_MAX_DEPTH)
+}
+
+/// Like [`from_slice`], allocating arrays and maps in `bump` and borrowing
+/// strings and binaries from `data`.
+#[cfg(feature = "arena")]
+pub fn from_slice_in<'a>(
+    data: &'a [u8],
+    bump: &'a bumpalo::Bump,
+) -> Result<crate::arena::LlsdRef<'a>, anyhow::Error> {
+    crate::arena::from_binary(data, bump, DEFAULT_MAX_DEPTH)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{TimeZone, Utc};
-    use std::collections::HashMap;
+    use crate::LlsdMap;
 
     fn round_trip(llsd: Llsd) {
         let encoded = to_vec(&llsd).expect("Failed to encode");
@@ -399,6 +1076,48 @@ mod tests {
         assert_eq!(llsd, decoded);
     }
 
+    #[test]
+    fn streams_binaries_from_files() {
+        let path = std::env::temp_dir().join(format!("llsd-rs-stream-{}.bin", std::process::id()));
+        let payload: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+        std::fs::write(&path, &payload).unwrap();
+        let url = url::Url::from_file_path(&path).unwrap();
+        let llsd = Llsd::Array(vec![
+            Llsd::Uri(url.into()),
+            Llsd::Uri(Uri::parse("https://example.com/a")),
+            Llsd::Integer(1),
+        ]);
+
+        let mut out = Vec::new();
+        write_streaming(&llsd, &mut out, &mut FileSource).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            from_slice(&out).unwrap(),
+            Llsd::Array(vec![
+                Llsd::Binary(payload),
+                Llsd::Uri(Uri::parse("https://example.com/a")),
+                Llsd::Integer(1),
+            ])
+        );
+        assert!(write_streaming(&llsd, &mut Vec::new(), &mut FileSource).is_err());
+    }
+
+    #[test]
+    fn stream_must_match_its_length() {
+        let mut short = |_: &Llsd| -> io::Result<Option<(u64, Box<dyn Read>)>> {
+            Ok(Some((10, Box::new(&b"abc"[..]))))
+        };
+        let err = write_streaming(&Llsd::Undefined, &mut Vec::new(), &mut short).unwrap_err();
+        assert_eq!(err.to_string(), "binary stream ended after 3 of 10 bytes");
+
+        let mut long = |_: &Llsd| -> io::Result<Option<(u64, Box<dyn Read>)>> {
+            Ok(Some((2, Box::new(&b"abc"[..]))))
+        };
+        let mut out = Vec::new();
+        write_streaming(&Llsd::Undefined, &mut out, &mut long).unwrap();
+        assert_eq!(from_slice(&out).unwrap(), Llsd::Binary(b"ab".to_vec()));
+    }
+
     #[test]
     fn undefined() {
         round_trip(Llsd::Undefined);
@@ -438,7 +1157,7 @@ mod tests {
 
     #[test]
     fn date() {
-        let dt = Utc.timestamp_opt(1_620_000_000, 0).unwrap();
+        let dt = Date::from_timestamp(1_620_000_000, 0).unwrap();
         round_trip(Llsd::Date(dt));
     }
 
@@ -459,7 +1178,7 @@ mod tests {
 
     #[test]
     fn array_in_map_parses_closing_bracket() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert(
             "a".to_string(),
             Llsd::Array(vec![Llsd::Integer(1), Llsd::Integer(2)]),
@@ -550,7 +1269,7 @@ mod tests {
 
     #[test]
     fn from_reader_preserves_trailing_bytes() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert("answer".into(), Llsd::Integer(42));
         let value = Llsd::Map(map);
         let mut encoded = b"<? LLSD/Binary ?>\n".to_vec();
@@ -568,10 +1287,215 @@ mod tests {
 
     #[test]
     fn map() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert("answer".into(), Llsd::Integer(42));
         map.insert("pi".into(), Llsd::Real(13.14));
         map.insert("greeting".into(), Llsd::String("hello".into()));
         round_trip(Llsd::Map(map));
     }
+
+    #[test]
+    fn write_depth_matches_parser() {
+        let mut llsd = Llsd::Undefined;
+        for _ in 0..DEFAULT_MAX_DEPTH - 1 {
+            llsd = Llsd::Array(vec![llsd]);
+        }
+        let encoded = to_vec(&llsd).expect("Failed to encode");
+        assert_eq!(from_slice(&encoded).expect("Failed to decode"), llsd);
+
+        let llsd = Llsd::Array(vec![llsd]);
+        assert!(to_vec(&llsd).is_err());
+        let encoded = to_vec_with_depth(&llsd, DEFAULT_MAX_DEPTH + 1).expect("Failed to encode");
+        assert!(from_slice(&encoded).is_err());
+        assert_eq!(
+            from_slice_with_depth(&encoded, DEFAULT_MAX_DEPTH + 1).expect("Failed to decode"),
+            llsd
+        );
+    }
+
+    #[test]
+    fn extract_pointer_skips_to_subtree() {
+        let mut body = LlsdMap::new();
+        body.insert("message".into(), Llsd::String("TeleportFinish".into()));
+        body.insert("blob".into(), Llsd::Binary(vec![b'}'; 64]));
+        let mut event = LlsdMap::new();
+        event.insert("quoted".into(), Llsd::Array(vec![Llsd::Real(1.0); 3]));
+        event.insert("body".into(), Llsd::Map(body));
+        let mut root = LlsdMap::new();
+        root.insert("id".into(), Llsd::Integer(3));
+        root.insert(
+            "events".into(),
+            Llsd::Array(vec![Llsd::Undefined, Llsd::Map(event.clone())]),
+        );
+        let llsd = Llsd::Map(root);
+        let mut encoded = b"<? LLSD/Binary ?>\n".to_vec();
+        encoded.extend(to_vec(&llsd).unwrap());
+
+        for pointer in [
+            "",
+            "/id",
+            "/events",
+            "/events/1/body",
+            "/events/1/body/message",
+        ] {
+            let extracted = extract_pointer(&mut encoded.as_slice(), pointer).unwrap();
+            assert_eq!(extracted.as_ref(), llsd.pointer(pointer), "{pointer}");
+        }
+        for pointer in ["/nope", "/events/2", "/events/x", "/id/0", "events"] {
+            assert_eq!(
+                extract_pointer(&mut encoded.as_slice(), pointer).unwrap(),
+                None
+            );
+        }
+        // Input after the addressed value is never read.
+        let cut = encoded.len() - 2;
+        let extracted = extract_pointer(&mut &encoded[..cut], "/events/1/quoted").unwrap();
+        assert_eq!(extracted.as_ref(), event.get("quoted"));
+        assert!(extract_pointer(&mut &encoded[..cut], "/missing").is_err());
+    }
+
+    #[test]
+    fn encoder_appends_and_is_reusable() {
+        let mut map = LlsdMap::new();
+        map.insert("list".into(), Llsd::Array(vec![Llsd::Integer(1); 4]));
+        map.insert("name".into(), Llsd::String("region".into()));
+        let llsd = Llsd::Map(map);
+
+        let mut encoder = BinaryEncoder::new();
+        let mut out = b"prefix".to_vec();
+        encoder.encode_into(&llsd, &mut out).unwrap();
+        assert_eq!(&out[..6], b"prefix");
+        assert_eq!(out[6..], to_vec(&llsd).unwrap());
+
+        out.clear();
+        encoder.encode_into(&Llsd::Integer(7), &mut out).unwrap();
+        assert_eq!(out, to_vec(&Llsd::Integer(7)).unwrap());
+
+        let mut shallow = BinaryEncoder::new().with_max_depth(1);
+        assert!(shallow.encode(&llsd).is_err());
+        // A failed call leaves nothing behind for the next one.
+        assert_eq!(shallow.encode(&Llsd::Undefined).unwrap(), b"!");
+    }
+
+    #[test]
+    fn sorted_keys_give_one_encoding() {
+        let keys: Vec<String> = (0..30).map(|i| format!("key{i}")).collect();
+        let forward: LlsdMap = keys.iter().map(|k| (k.clone(), Llsd::Undefined)).collect();
+        let backward: LlsdMap = keys
+            .iter()
+            .rev()
+            .map(|k| (k.clone(), Llsd::Undefined))
+            .collect();
+        let mut encoder = BinaryEncoder::new().with_sorted_keys(true);
+        let encoded = encoder.encode(&Llsd::Map(forward)).unwrap();
+        assert_eq!(encoder.encode(&Llsd::Map(backward)).unwrap(), encoded);
+
+        let mut sorted = keys.clone();
+        sorted.sort();
+        let mut expected = b"{".to_vec();
+        expected.extend_from_slice(&30u32.to_be_bytes());
+        for key in &sorted {
+            expected.push(b'k');
+            expected.extend_from_slice(&(key.len() as u32).to_be_bytes());
+            expected.extend_from_slice(key.as_bytes());
+            expected.push(b'!');
+        }
+        expected.push(b'}');
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn canonical_encoding() {
+        let mut inner = LlsdMap::new();
+        inner.insert("b".into(), Llsd::Real(-0.0));
+        inner.insert(
+            "a".into(),
+            Llsd::Real(f64::from_bits(0x7ff8_dead_beef_0001)),
+        );
+        let mut map = LlsdMap::new();
+        for i in (0..12).rev() {
+            map.insert(format!("k{i:02}"), Llsd::Map(inner.clone()));
+        }
+        map.insert(
+            "expires".into(),
+            Llsd::Date(Date::from_timestamp(1_700_000_000, 123_456_789).unwrap()),
+        );
+        map.insert(
+            "before_epoch".into(),
+            Llsd::Date(Date::from_timestamp(-2, 500_000_000).unwrap()),
+        );
+        let llsd = Llsd::Map(map);
+
+        let canonical = to_canonical_vec(&llsd).unwrap();
+        let decoded = verify_canonical(&canonical).unwrap();
+        assert_eq!(to_canonical_vec(&decoded).unwrap(), canonical);
+        // Rounded to the millisecond; the `f64` read back is within a
+        // microsecond of it.
+        let expires = decoded.get("expires").and_then(Llsd::as_date).unwrap();
+        let millis = Date::from_timestamp(1_700_000_000, 123_000_000).unwrap();
+        let nanos = |d: &Date| {
+            i128::from(d.timestamp()) * 1_000_000_000 + i128::from(d.timestamp_subsec_nanos())
+        };
+        assert!((nanos(expires) - nanos(&millis)).abs() < 1_000);
+        assert_eq!(
+            decoded.get("before_epoch").and_then(Llsd::as_date),
+            Some(&Date::from_timestamp(-2, 500_000_000).unwrap())
+        );
+        let real = decoded.pointer("/k03/a").and_then(Llsd::as_real).unwrap();
+        assert_eq!(real.to_bits(), f64::NAN.to_bits());
+        let zero = decoded.pointer("/k03/b").and_then(Llsd::as_real).unwrap();
+        assert!(zero.is_sign_positive());
+
+        // Unsorted keys, a header, or trailing bytes are all rejected.
+        assert!(verify_canonical(&to_vec(&llsd).unwrap()).is_err());
+        let mut with_header = b"<? LLSD/Binary ?>\n".to_vec();
+        with_header.extend_from_slice(&canonical);
+        assert!(verify_canonical(&with_header).is_err());
+        let mut trailing = canonical.clone();
+        trailing.push(b'!');
+        assert!(verify_canonical(&trailing).is_err());
+    }
+
+    #[test]
+    fn scalar_fast_paths() {
+        let date = Date::from_timestamp(1_704_164_645, 0).unwrap();
+        for llsd in [
+            Llsd::Undefined,
+            Llsd::Boolean(false),
+            Llsd::Integer(-7),
+            Llsd::Real(1.5),
+            Llsd::String("héllo".into()),
+            Llsd::Uri(Uri::parse("https://example.com/")),
+            Llsd::Uuid(Uuid::from_u128(0x1234)),
+            Llsd::Date(date),
+            Llsd::Binary(vec![0, 1, 2]),
+        ] {
+            let mut data = to_vec(&llsd).unwrap();
+            let len = data.len();
+            data.push(b'!');
+            let (scalar, used) = read_scalar(&data).unwrap();
+            assert_eq!((Llsd::from(scalar), used), (llsd.clone(), len));
+            assert_eq!(peek_type(&data).unwrap().name(), llsd.type_name());
+            if len > 1 {
+                assert_eq!(read_scalar(&data[..len - 1]), None, "{llsd:?}");
+            }
+        }
+        assert!(matches!(
+            read_scalar(b"'it\\'s'!"),
+            Some((Scalar::String(std::borrow::Cow::Owned(s)), 7)) if s == "it's"
+        ));
+        assert!(matches!(
+            read_scalar(b"\"plain\""),
+            Some((Scalar::String(std::borrow::Cow::Borrowed("plain")), 7))
+        ));
+        assert_eq!(read_scalar(b"[\0\0\0\0]"), None);
+        assert_eq!(read_scalar(b"s\0\0\0\x01\xff"), None);
+        assert_eq!(read_scalar(b""), None);
+
+        assert_eq!(read_key(b"k\0\0\0\x02ab!"), Some(("ab", 7)));
+        assert_eq!(read_key(b"s\0\0\0\x02ab"), None);
+        assert_eq!(read_key(b"k\0\0\0\x03ab"), None);
+        assert_eq!(peek_type(b"<? LLSD/Binary ?>\n{"), Some(LlsdType::Map));
+        assert_eq!(peek_type(b"x"), None);
+    }
 }
@@ -0,0 +1,505 @@
+//! A directory of LLSD documents keyed by UUID or name.
+//!
+//! [`CacheStore`] keeps one binary LLSD file per key, spread over
+//! subdirectories named after the key's first character the way the viewer
+//! lays out its asset cache (`<dir>/a/a1b2...-....llsd`). Writes go to a
+//! temporary file renamed into place, so a crash leaves either the old or the
+//! new document, never half of one. With a size limit, the least recently
+//! used files are evicted after each write to get back under it.
+//!
+//! ```
+//! use llsd_rs::{Llsd, cache::CacheStore};
+//! use llsd_rs::Uuid;
+//!
+//! let dir = std::env::temp_dir().join(format!("llsd-rs-cache-doc-{}", std::process::id()));
+//! let mut cache = CacheStore::open(&dir).unwrap().with_max_bytes(1 << 20);
+//! let id = Uuid::from_u128(0xa1b2);
+//! cache.put(id, &Llsd::String("texture".into())).unwrap();
+//! assert_eq!(cache.get(id).unwrap(), Some(Llsd::String("texture".into())));
+//! assert_eq!(cache.get("missing").unwrap(), None);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::Uuid;
+use anyhow::Context;
+
+use crate::Llsd;
+
+const EXTENSION: &str = "llsd";
+const COMPRESSED_EXTENSION: &str = "llsdz";
+
+/// Compresses cached documents. The `deflate` feature provides [`Deflate`];
+/// implement this over `zstd` or the like for anything else.
+pub trait Compression: Send + Sync {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error>;
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error>;
+}
+
+/// zlib-wrapped DEFLATE (via `flate2`), at level 6 unless set otherwise.
+#[cfg(feature = "deflate")]
+#[derive(Debug, Clone, Copy)]
+pub struct Deflate {
+    level: u32,
+}
+
+#[cfg(feature = "deflate")]
+impl Default for Deflate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Deflate {
+    pub fn new() -> Self {
+        Self { level: 6 }
+    }
+
+    /// From 0 (store only) to 9 (smallest).
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Compression for Deflate {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::new(self.level));
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        let mut out = Vec::new();
+        std::io::Read::read_to_end(&mut flate2::read::ZlibDecoder::new(data), &mut out)
+            .context("inflating cached document")?;
+        Ok(out)
+    }
+}
+
+/// What a document is cached under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CacheKey {
+    Uuid(Uuid),
+    Name(String),
+}
+
+impl CacheKey {
+    /// The file stem: the hyphenated UUID; a name as is when it only holds
+    /// lowercase letters, digits, `-`, `_` and non-leading `.` and does not
+    /// read as a UUID, otherwise `~` and its bytes in hex. Names thus never
+    /// share a stem with a UUID key.
+    fn file_stem(&self) -> String {
+        match self {
+            CacheKey::Uuid(id) => id.to_string(),
+            CacheKey::Name(name) => {
+                let plain = !name.is_empty()
+                    && !name.starts_with('.')
+                    && name.bytes().all(|b| {
+                        b.is_ascii_lowercase() || b.is_ascii_digit() || b"-_.".contains(&b)
+                    })
+                    && Uuid::try_parse(name).is_err();
+                if plain {
+                    name.clone()
+                } else {
+                    let mut stem = String::from("~");
+                    for b in name.bytes() {
+                        stem.push_str(&format!("{b:02x}"));
+                    }
+                    stem
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheKey::Uuid(id) => id.fmt(f),
+            CacheKey::Name(name) => name.fmt(f),
+        }
+    }
+}
+
+impl From<Uuid> for CacheKey {
+    fn from(id: Uuid) -> Self {
+        CacheKey::Uuid(id)
+    }
+}
+
+impl From<&str> for CacheKey {
+    fn from(name: &str) -> Self {
+        CacheKey::Name(name.to_string())
+    }
+}
+
+impl From<String> for CacheKey {
+    fn from(name: String) -> Self {
+        CacheKey::Name(name)
+    }
+}
+
+/// A directory of cached documents.
+pub struct CacheStore {
+    dir: PathBuf,
+    max_bytes: Option<u64>,
+    compression: Option<Box<dyn Compression>>,
+    /// Bytes in cached files, as of the last scan plus changes since.
+    size: u64,
+}
+
+impl fmt::Debug for CacheStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheStore")
+            .field("dir", &self.dir)
+            .field("max_bytes", &self.max_bytes)
+            .field("compressed", &self.compression.is_some())
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl CacheStore {
+    /// Open the cache in `dir`, creating the directory if needed.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating cache directory {}", dir.display()))?;
+        let mut store = Self {
+            dir,
+            max_bytes: None,
+            compression: None,
+            size: 0,
+        };
+        store.size = store.entries()?.iter().map(|e| e.len).sum();
+        Ok(store)
+    }
+
+    /// Evict least recently used documents after a write leaves the cache
+    /// over `max_bytes`.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Store documents compressed with `compression`. Documents written
+    /// uncompressed stay readable.
+    pub fn with_compression(mut self, compression: impl Compression + 'static) -> Self {
+        self.compression = Some(Box::new(compression));
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Bytes the cached files take up.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The document under `key`, marking it recently used.
+    pub fn get(&self, key: impl Into<CacheKey>) -> Result<Option<Llsd>, anyhow::Error> {
+        let key = key.into();
+        for compressed in [self.compression.is_some(), self.compression.is_none()] {
+            let path = self.path(&key, compressed);
+            let data = match fs::read(&path) {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+            };
+            let data = match (compressed, &self.compression) {
+                (false, _) => data,
+                (true, Some(compression)) => compression.decompress(&data)?,
+                (true, None) => {
+                    return Err(anyhow::anyhow!(
+                        "cached {key} is compressed but the cache has no compression"
+                    ));
+                }
+            };
+            let llsd = crate::binary::from_slice(&data)
+                .with_context(|| format!("decoding cached {key}"))?;
+            // Recency is the modification time; failing to bump it only
+            // makes the entry an earlier eviction candidate.
+            if let Ok(file) = File::options().write(true).open(&path) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            return Ok(Some(llsd));
+        }
+        Ok(None)
+    }
+
+    pub fn contains(&self, key: impl Into<CacheKey>) -> bool {
+        let key = key.into();
+        self.path(&key, true).exists() || self.path(&key, false).exists()
+    }
+
+    /// Store `llsd` under `key`, replacing what was there.
+    pub fn put(&mut self, key: impl Into<CacheKey>, llsd: &Llsd) -> Result<(), anyhow::Error> {
+        let key = key.into();
+        let mut data = crate::binary::to_vec(llsd)?;
+        if let Some(compression) = &self.compression {
+            data = compression.compress(&data)?;
+        }
+        let compressed = self.compression.is_some();
+        let path = self.path(&key, compressed);
+        let replaced = fs::metadata(&path).map_or(0, |m| m.len());
+        let parent = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(parent)?;
+        let temp = parent.join(format!(".{}.{}.tmp", key.file_stem(), std::process::id()));
+        let written = File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(&data)?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&temp, &path));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("writing {}", path.display()));
+        }
+        self.size = self.size.saturating_sub(replaced) + data.len() as u64;
+        // A copy stored with the other compression setting is now stale.
+        let stale = self.path(&key, !compressed);
+        if let Ok(metadata) = fs::metadata(&stale) {
+            fs::remove_file(&stale).with_context(|| format!("removing {}", stale.display()))?;
+            self.size = self.size.saturating_sub(metadata.len());
+        }
+        self.evict()
+    }
+
+    /// Remove the document under `key`, returning whether there was one.
+    pub fn remove(&mut self, key: impl Into<CacheKey>) -> Result<bool, anyhow::Error> {
+        let key = key.into();
+        let mut removed = false;
+        for compressed in [false, true] {
+            let path = self.path(&key, compressed);
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+            self.size = self.size.saturating_sub(metadata.len());
+            removed = true;
+        }
+        Ok(removed)
+    }
+
+    /// Remove every cached document.
+    pub fn clear(&mut self) -> Result<(), anyhow::Error> {
+        for entry in self.entries()? {
+            fs::remove_file(&entry.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    fn path(&self, key: &CacheKey, compressed: bool) -> PathBuf {
+        let stem = key.file_stem();
+        let bucket = &stem[..stem.chars().next().map_or(0, char::len_utf8)];
+        let extension = if compressed {
+            COMPRESSED_EXTENSION
+        } else {
+            EXTENSION
+        };
+        self.dir.join(bucket).join(format!("{stem}.{extension}"))
+    }
+
+    /// Drop least recently used files until the cache fits its limit.
+    fn evict(&mut self) -> Result<(), anyhow::Error> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+        if self.size <= max_bytes {
+            return Ok(());
+        }
+        let mut entries = self.entries()?;
+        self.size = entries.iter().map(|e| e.len).sum();
+        entries.sort_by_key(|e| e.modified);
+        for entry in entries {
+            if self.size <= max_bytes {
+                break;
+            }
+            fs::remove_file(&entry.path)?;
+            self.size -= entry.len;
+        }
+        Ok(())
+    }
+
+    /// The cached files, in no particular order.
+    fn entries(&self) -> Result<Vec<Entry>, anyhow::Error> {
+        let mut entries = Vec::new();
+        for bucket in fs::read_dir(&self.dir)? {
+            let bucket = bucket?;
+            if !bucket.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(bucket.path())? {
+                let file = file?;
+                let path = file.path();
+                let cached = path
+                    .extension()
+                    .is_some_and(|e| e == EXTENSION || e == COMPRESSED_EXTENSION);
+                if !cached {
+                    continue;
+                }
+                let metadata = file.metadata()?;
+                entries.push(Entry {
+                    path,
+                    len: metadata.len(),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+struct Entry {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("llsd-rs-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Byte-wise NOT: enough to tell compressed files from plain ones.
+    struct Invert;
+
+    impl Compression for Invert {
+        fn compress(&self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+            Ok(data.iter().map(|b| !b).collect())
+        }
+
+        fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+            self.compress(data)
+        }
+    }
+
+    #[test]
+    fn put_get_remove() {
+        let dir = scratch("basic");
+        let mut cache = CacheStore::open(&dir).unwrap();
+        let id = Uuid::from_u128(0xc0ffee);
+        let llsd = Llsd::Array(vec![Llsd::Integer(1), Llsd::Binary(vec![2; 10])]);
+        cache.put(id, &llsd).unwrap();
+        cache.put("Odd/Name", &Llsd::Boolean(true)).unwrap();
+        assert!(
+            dir.join("0/00000000-0000-0000-0000-000000c0ffee.llsd")
+                .is_file()
+        );
+        assert!(dir.join("~/~4f64642f4e616d65.llsd").is_file());
+
+        assert_eq!(cache.get(id).unwrap(), Some(llsd));
+        assert_eq!(cache.get("Odd/Name").unwrap(), Some(Llsd::Boolean(true)));
+        assert!(cache.contains(id));
+        let size = cache.size();
+        assert_eq!(CacheStore::open(&dir).unwrap().size(), size);
+
+        assert!(cache.remove(id).unwrap());
+        assert!(!cache.remove(id).unwrap());
+        assert_eq!(cache.get(id).unwrap(), None);
+        cache.clear().unwrap();
+        assert_eq!(cache.size(), 0);
+        assert_eq!(cache.get("Odd/Name").unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_never_alias_uuids() {
+        let dir = scratch("alias");
+        let mut cache = CacheStore::open(&dir).unwrap();
+        let id = Uuid::parse_str("6ba7b810-9dad-11d1-80b4-00c04fd430c8").unwrap();
+        cache.put(id, &Llsd::Integer(1)).unwrap();
+        cache.put(id.to_string(), &Llsd::Integer(2)).unwrap();
+        assert_eq!(cache.get(id).unwrap(), Some(Llsd::Integer(1)));
+        assert_eq!(cache.get(id.to_string()).unwrap(), Some(Llsd::Integer(2)));
+        assert!(cache.remove(id).unwrap());
+        assert!(cache.contains(id.to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compressed_entries() {
+        let dir = scratch("compressed");
+        let mut plain = CacheStore::open(&dir).unwrap();
+        plain.put("old", &Llsd::Integer(1)).unwrap();
+
+        let mut cache = CacheStore::open(&dir).unwrap().with_compression(Invert);
+        cache.put("new", &Llsd::Integer(2)).unwrap();
+        assert!(dir.join("n/new.llsdz").is_file());
+        assert_eq!(cache.get("old").unwrap(), Some(Llsd::Integer(1)));
+        assert_eq!(cache.get("new").unwrap(), Some(Llsd::Integer(2)));
+        // Rewriting replaces the uncompressed file.
+        cache.put("old", &Llsd::Integer(3)).unwrap();
+        assert!(!dir.join("o/old.llsd").exists());
+        assert!(plain.get("old").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate_entries() {
+        let dir = scratch("deflate");
+        let mut cache = CacheStore::open(&dir)
+            .unwrap()
+            .with_compression(Deflate::new().with_level(9));
+        let llsd = Llsd::Array(vec![Llsd::String("region".repeat(100)); 10]);
+        cache.put("big", &llsd).unwrap();
+        let plain = crate::binary::to_vec(&llsd).unwrap().len() as u64;
+        assert!(cache.size() < plain / 10, "{} of {plain}", cache.size());
+        assert_eq!(cache.get("big").unwrap(), Some(llsd));
+        fs::write(dir.join("b/big.llsdz"), b"not zlib").unwrap();
+        assert!(cache.get("big").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = scratch("evict");
+        let blob = |n| Llsd::Binary(vec![n; 100]);
+        let mut cache = CacheStore::open(&dir).unwrap();
+        let mut time = SystemTime::now() - Duration::from_secs(100);
+        for name in ["a", "b", "c"] {
+            cache.put(name, &blob(1)).unwrap();
+            let path = dir.join(name).join(format!("{name}.llsd"));
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+            time += Duration::from_secs(10);
+        }
+        // Reading `a` makes `b` the oldest.
+        cache.get("a").unwrap();
+        let entry = cache.size() / 3;
+        let mut cache = cache.with_max_bytes(entry * 3);
+        cache.put("d", &blob(2)).unwrap();
+        assert!(cache.size() <= entry * 3);
+        assert!(!cache.contains("b"));
+        for name in ["a", "c", "d"] {
+            assert!(cache.contains(name), "{name}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+//! Seed capability grants.
+//!
+//! A region answers the seed capability with a map from capability names
+//! to URLs, written as strings or URIs. [`CapMap`] keeps that map, hands out
+//! parsed URLs, remembers when it was granted so callers can tell a stale
+//! grant, and reports what changed when the region grants the caps again.
+//!
+//! ```
+//! use std::time::Duration;
+//! use llsd_rs::{Llsd, caps::CapMap, llsd_map};
+//!
+//! let seed = llsd_map! {
+//!     "EventQueueGet" => "https://sim1.example.com:12043/cap/eq",
+//!     "FetchInventory2" => "https://sim1.example.com:12043/cap/fi",
+//! };
+//! let mut caps = CapMap::from_llsd(&seed).unwrap().with_lifetime(Duration::from_secs(3600));
+//! assert_eq!(caps.get_cap("EventQueueGet").unwrap().path(), "/cap/eq");
+//! assert!(!caps.is_expired());
+//!
+//! let regrant = CapMap::from_llsd(&llsd_map! {
+//!     "EventQueueGet" => "https://sim1.example.com:12043/cap/eq2",
+//!     "GetDisplayNames" => "https://sim1.example.com:12043/cap/dn",
+//! })
+//! .unwrap();
+//! let diff = caps.regrant(regrant);
+//! assert_eq!(diff.added, ["GetDisplayNames"]);
+//! assert_eq!(diff.removed, ["FetchInventory2"]);
+//! assert_eq!(diff.changed, ["EventQueueGet"]);
+//! ```
+
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::{FromLlsd, IntoLlsd, Llsd, LlsdError, LlsdMap, Uri};
+
+/// Capability names and their URLs, with the time they were granted.
+///
+/// URLs are kept as the region sent them, strings or URIs, so the map
+/// converts back to the same LLSD.
+#[derive(Debug, Clone)]
+pub struct CapMap {
+    caps: LlsdMap,
+    granted_at: Instant,
+    lifetime: Option<Duration>,
+}
+
+/// What changed between two grants, each list of names sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapDiff {
+    /// Granted now and not before.
+    pub added: Vec<String>,
+    /// Granted before and not now.
+    pub removed: Vec<String>,
+    /// Granted both times, at a different URL.
+    pub changed: Vec<String>,
+}
+
+impl CapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl CapMap {
+    /// An empty grant made now.
+    pub fn new() -> Self {
+        Self {
+            caps: LlsdMap::new(),
+            granted_at: Instant::now(),
+            lifetime: None,
+        }
+    }
+
+    /// Read a seed capability response, granted now. Every value must be a
+    /// string or a URI.
+    pub fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        let Llsd::Map(map) = llsd else {
+            return Err(LlsdError::invalid_type("map", llsd));
+        };
+        for (name, url) in map.iter() {
+            if !matches!(url, Llsd::String(_) | Llsd::Uri(_)) {
+                return Err(LlsdError::invalid_type("uri", url).with_key(name));
+            }
+        }
+        Ok(Self {
+            caps: map.clone(),
+            ..Self::new()
+        })
+    }
+
+    /// Treat the grant as expired `lifetime` after it was made. Without
+    /// one it never expires.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+
+    /// The URL of capability `name`; `None` when it wasn't granted or its
+    /// URL doesn't parse.
+    pub fn get_cap(&self, name: &str) -> Option<Url> {
+        match self.caps.get(name)? {
+            Llsd::Uri(uri) => Url::try_from(uri).ok(),
+            Llsd::String(url) => Url::parse(url.trim()).ok(),
+            _ => None,
+        }
+    }
+
+    /// The URL of capability `name` as sent, parsed or not.
+    pub fn get_uri(&self, name: &str) -> Option<Uri> {
+        match self.caps.get(name)? {
+            Llsd::Uri(uri) => Some(uri.clone()),
+            Llsd::String(url) => Some(Uri::parse(url)),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.caps.contains_key(name)
+    }
+
+    /// Grant capability `name` at `url`.
+    pub fn insert(&mut self, name: impl Into<String>, url: impl Into<Uri>) {
+        self.caps.insert(name.into(), Llsd::Uri(url.into()));
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.caps.remove(name).is_some()
+    }
+
+    /// The granted capability names, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.caps.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.caps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.caps.is_empty()
+    }
+
+    pub fn as_map(&self) -> &LlsdMap {
+        &self.caps
+    }
+
+    pub fn granted_at(&self) -> Instant {
+        self.granted_at
+    }
+
+    pub fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+
+    /// When the grant expires; `None` without a lifetime.
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.lifetime
+            .and_then(|lifetime| self.granted_at.checked_add(lifetime))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Instant::now())
+    }
+
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        self.expires_at().is_some_and(|expires| now >= expires)
+    }
+
+    /// What changes from this grant to `newer`. URLs are compared as text,
+    /// so a string and a URI of the same URL are equal.
+    pub fn diff(&self, newer: &CapMap) -> CapDiff {
+        let mut diff = CapDiff::default();
+        for (name, url) in newer.caps.iter() {
+            match self.caps.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(old) if url_text(old) != url_text(url) => diff.changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = self
+            .caps
+            .keys()
+            .filter(|name| !newer.caps.contains_key(name.as_str()))
+            .cloned()
+            .collect();
+        diff.added.sort_unstable();
+        diff.removed.sort_unstable();
+        diff.changed.sort_unstable();
+        diff
+    }
+
+    /// Replace this grant with `newer`, keeping this one's lifetime when
+    /// `newer` has none, and return what changed.
+    pub fn regrant(&mut self, mut newer: CapMap) -> CapDiff {
+        let diff = self.diff(&newer);
+        newer.lifetime = newer.lifetime.or(self.lifetime);
+        *self = newer;
+        diff
+    }
+}
+
+impl Default for CapMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Equal when they grant the same capabilities at the same URLs, whenever
+/// they were granted.
+impl PartialEq for CapMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.diff(other).is_empty()
+    }
+}
+
+fn url_text(url: &Llsd) -> &str {
+    match url {
+        Llsd::Uri(uri) => uri.as_str(),
+        Llsd::String(url) => url.trim(),
+        _ => "",
+    }
+}
+
+impl FromLlsd for CapMap {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        CapMap::from_llsd(llsd)
+    }
+}
+
+impl IntoLlsd for CapMap {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Map(self.caps.clone())
+    }
+}
+
+impl From<CapMap> for Llsd {
+    fn from(caps: CapMap) -> Self {
+        Llsd::Map(caps.caps)
+    }
+}
+
+impl TryFrom<&Llsd> for CapMap {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &Llsd) -> Result<Self, Self::Error> {
+        Ok(CapMap::from_llsd(llsd)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llsd_map;
+
+    #[test]
+    fn reads_and_converts_back() {
+        let seed = llsd_map! {
+            "EventQueueGet" => Llsd::Uri(Uri::parse("https://sim.example.com/eq")),
+            "Broken" => "not a url",
+            "Padded" => " https://sim.example.com/padded ",
+        };
+        let caps = CapMap::from_llsd(&seed).unwrap();
+        assert_eq!(caps.len(), 3);
+        assert_eq!(
+            caps.get_cap("EventQueueGet").unwrap().as_str(),
+            "https://sim.example.com/eq"
+        );
+        assert_eq!(caps.get_cap("Broken"), None);
+        assert_eq!(caps.get_uri("Broken").unwrap().as_str(), "not a url");
+        assert_eq!(
+            caps.get_cap("Padded").unwrap().as_str(),
+            "https://sim.example.com/padded"
+        );
+        assert_eq!(caps.get_cap("Missing"), None);
+        assert_eq!(Llsd::from(caps.clone()), seed);
+        assert_eq!(caps.to_llsd(), seed);
+
+        let err = CapMap::from_llsd(&llsd_map! { "A" => 1 }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type at A: expected uri, found integer"
+        );
+        assert!(CapMap::from_llsd(&Llsd::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn expiry() {
+        let caps = CapMap::new();
+        assert_eq!(caps.expires_at(), None);
+        assert!(!caps.is_expired_at(caps.granted_at() + Duration::from_secs(1 << 20)));
+
+        let caps = caps.with_lifetime(Duration::from_secs(60));
+        let granted = caps.granted_at();
+        assert!(!caps.is_expired_at(granted + Duration::from_secs(59)));
+        assert!(caps.is_expired_at(granted + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn regrants() {
+        let mut caps = CapMap::new().with_lifetime(Duration::from_secs(60));
+        caps.insert("A", Uri::parse("https://sim.example.com/a"));
+        caps.insert("B", Uri::parse("https://sim.example.com/b"));
+        caps.insert("C", Uri::parse("https://sim.example.com/c"));
+
+        let newer = CapMap::from_llsd(&llsd_map! {
+            "A" => "https://sim.example.com/a",
+            "C" => "https://sim.example.com/c2",
+            "D" => "https://sim.example.com/d",
+        })
+        .unwrap();
+        assert_eq!(newer, newer.clone());
+        assert_ne!(caps, newer);
+        let diff = caps.regrant(newer);
+        assert_eq!(
+            diff,
+            CapDiff {
+                added: vec!["D".into()],
+                removed: vec!["B".into()],
+                changed: vec!["C".into()],
+            }
+        );
+        assert!(!caps.contains("B"));
+        assert_eq!(caps.lifetime(), Some(Duration::from_secs(60)));
+        assert!(caps.diff(&caps.clone()).is_empty());
+        assert!(caps.remove("A"));
+        assert_eq!(caps.names().count(), 2);
+    }
+}
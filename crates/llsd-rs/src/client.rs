@@ -0,0 +1,1150 @@
+//! XML-RPC and capability clients over a pluggable HTTP transport.
+//!
+//! The crate does not pick an HTTP stack. [`RpcClient`] and [`CapsClient`]
+//! (and their async twins) encode the call, hand a [`Request`] to a
+//! [`Transport`] or [`AsyncTransport`] and decode the reply, retrying
+//! throttled and timed-out requests as their [`RetryPolicy`] allows.
+//! With the `reqwest` feature, reqwest's blocking and async clients are
+//! transports; implementing the traits for another stack (`hyper`, or
+//! whatever the embedding application already uses) is a few lines of
+//! mapping between request types, as those impls show.
+//! [`MockTransport`] replays canned responses, so fault handling can be
+//! tested without a network:
+//!
+//! ```
+//! use llsd_rs::{Llsd, client::{MockTransport, Response, RpcClient}};
+//! use llsd_rs::rpc::{self, XmlRpc};
+//!
+//! let reply = rpc::to_string(&XmlRpc::MethodResponse(Llsd::Integer(7))).unwrap();
+//! let transport = MockTransport::new().with_response(Response::new(200, reply));
+//! let mut client = RpcClient::new(transport, "https://login.example/cgi-bin/login.cgi");
+//! let call = XmlRpc::MethodCall("ping".into(), Llsd::Undefined);
+//! assert_eq!(client.call(&call).unwrap().llsd(), &Llsd::Integer(7));
+//! assert_eq!(client.transport().requests()[0].header("content-type"), Some("text/xml"));
+//! ```
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    hash::{BuildHasher, RandomState},
+    sync::Mutex,
+    time::Duration,
+};
+
+use crate::{
+    Llsd,
+    fingerprint::etag_listed,
+    rpc::{self, XmlRpc},
+};
+
+/// An HTTP request for a transport to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// How long the transport should wait for the response before failing
+    /// with [`TransportError::Timeout`]; its own default when `None`.
+    pub timeout: Option<Duration>,
+}
+
+impl Request {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: "GET".to_string(),
+            url: url.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    pub fn post(url: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            method: "POST".to_string(),
+            body: body.into(),
+            ..Self::get(url)
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// The first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// An HTTP response as a transport received it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// The first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Answer `request` for a capability whose current value is `llsd`: a 200
+    /// with it as LLSD XML and its [cache key](Llsd::cache_key) as the `ETag`,
+    /// or a bodyless 304 when the request's `If-None-Match` already names that
+    /// key, without serializing the value at all.
+    pub fn llsd_for(request: &Request, llsd: &Llsd) -> Result<Self, anyhow::Error> {
+        let etag = llsd.cache_key()?.etag();
+        if request
+            .header("If-None-Match")
+            .is_some_and(|header| etag_listed(header, &etag))
+        {
+            return Ok(Self::new(304, Vec::new()).with_header("ETag", etag));
+        }
+        Ok(Self::new(200, crate::xml::to_string(llsd)?)
+            .with_header("Content-Type", LLSD_XML)
+            .with_header("ETag", etag))
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Why a request got no usable response. Transports report timeouts and
+/// connection failures with these so callers can tell them from other errors
+/// by downcasting; the clients report statuses other than 2xx as
+/// [`TransportError::Status`].
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("request timed out")]
+    Timeout,
+    #[error("connection failed: {0}")]
+    Connect(String),
+    #[error("HTTP status {}", .0.status)]
+    Status(Response),
+}
+
+/// Sends requests one at a time, blocking until the response arrives.
+pub trait Transport {
+    fn send(&mut self, request: Request) -> Result<Response, anyhow::Error>;
+
+    /// Wait out a retry delay.
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Sends requests asynchronously. Takes `&self` so one transport (usually a
+/// connection pool) can serve concurrent calls.
+pub trait AsyncTransport {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response, anyhow::Error>> + Send;
+
+    /// Wait out a retry delay, on the runtime's timer (`tokio::time::sleep`
+    /// or the like).
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send(&mut self, request: Request) -> Result<Response, anyhow::Error> {
+        (**self).send(request)
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+impl<T: AsyncTransport + Sync + ?Sized> AsyncTransport for &T {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response, anyhow::Error>> + Send {
+        (**self).send(request)
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        (**self).sleep(duration)
+    }
+}
+
+/// When and how often the clients retry a request, and how long each
+/// attempt may take.
+///
+/// A response with one of the retry statuses (503 by default) or a
+/// [`TransportError::Timeout`] is retried after a delay that starts at
+/// `initial_backoff` and grows by `multiplier` per attempt up to
+/// `max_backoff`, shortened by a random fraction of up to `jitter` so that
+/// clients throttled together do not retry together. A `Retry-After` header,
+/// in seconds or as an HTTP date, replaces the computed delay, still capped
+/// at `max_backoff`. Once retries run out the last response or error is
+/// returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    retry_statuses: Vec<u16>,
+    retry_timeouts: bool,
+    timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    /// Three retries from half a second, doubling up to 30 seconds, with up
+    /// to half of each delay taken off at random.
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            retry_statuses: vec![503],
+            retry_timeouts: true,
+            timeout: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send every request once. What the clients use unless given a policy.
+    pub fn none() -> Self {
+        Self::default().with_max_retries(0)
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// The largest fraction of each backoff delay taken off at random,
+    /// clamped to `0.0..=1.0`; `0.0` makes delays deterministic.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Retry responses with these statuses instead of only 503.
+    pub fn with_retry_statuses(mut self, statuses: impl Into<Vec<u16>>) -> Self {
+        self.retry_statuses = statuses.into();
+        self
+    }
+
+    pub fn with_retry_timeouts(mut self, retry: bool) -> Self {
+        self.retry_timeouts = retry;
+        self
+    }
+
+    /// Give each attempt `timeout`, unless the request sets its own.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// How long to wait before retrying after `result` on attempt `attempt`
+    /// (0 for the first), or `None` to stop. `random` is in `0.0..1.0`.
+    pub fn retry_delay(
+        &self,
+        attempt: u32,
+        result: &Result<Response, anyhow::Error>,
+        random: f64,
+    ) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        match result {
+            Ok(response) if self.retry_statuses.contains(&response.status) => {
+                match response.header("Retry-After").and_then(retry_after) {
+                    Some(delay) => Some(delay.min(self.max_backoff)),
+                    None => Some(self.backoff(attempt, random)),
+                }
+            }
+            Err(err)
+                if self.retry_timeouts
+                    && matches!(
+                        err.downcast_ref::<TransportError>(),
+                        Some(TransportError::Timeout)
+                    ) =>
+            {
+                Some(self.backoff(attempt, random))
+            }
+            _ => None,
+        }
+    }
+
+    fn backoff(&self, attempt: u32, random: f64) -> Duration {
+        let exponent = i32::try_from(attempt).unwrap_or(i32::MAX);
+        let max = self.max_backoff.as_secs_f64();
+        let delay = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent)).min(max);
+        Duration::from_secs_f64((delay * (1.0 - self.jitter * random)).clamp(0.0, max))
+    }
+
+    fn prepare(&self, mut request: Request) -> Request {
+        if request.timeout.is_none() {
+            request.timeout = self.timeout;
+        }
+        request
+    }
+}
+
+/// A `Retry-After` value: delay seconds or, with the `chrono` feature, an
+/// HTTP date.
+fn retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    #[cfg(feature = "chrono")]
+    if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+        return Some(
+            (date.to_utc() - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+        );
+    }
+    None
+}
+
+/// Random numbers in `0.0..1.0` for jitter; xorshift seeded from the hasher
+/// keys std randomizes per process.
+struct Jitter(u64);
+
+impl Jitter {
+    fn new() -> Self {
+        Self(RandomState::new().hash_one(0u8) | 1)
+    }
+
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn send_retrying<T: Transport>(
+    transport: &mut T,
+    policy: &RetryPolicy,
+    request: Request,
+) -> Result<Response, anyhow::Error> {
+    let request = policy.prepare(request);
+    let mut jitter = Jitter::new();
+    let mut attempt = 0;
+    loop {
+        let result = transport.send(request.clone());
+        match policy.retry_delay(attempt, &result, jitter.next()) {
+            Some(delay) => transport.sleep(delay),
+            None => return result,
+        }
+        attempt += 1;
+    }
+}
+
+async fn send_retrying_async<T: AsyncTransport>(
+    transport: &T,
+    policy: &RetryPolicy,
+    request: Request,
+) -> Result<Response, anyhow::Error> {
+    let request = policy.prepare(request);
+    let mut jitter = Jitter::new();
+    let mut attempt = 0;
+    loop {
+        let result = transport.send(request.clone()).await;
+        match policy.retry_delay(attempt, &result, jitter.next()) {
+            Some(delay) => transport.sleep(delay).await,
+            None => return result,
+        }
+        attempt += 1;
+    }
+}
+
+/// A transport that answers from a queue of canned results and records the
+/// requests it was sent and the delays it was asked to sleep, without
+/// sleeping. Fails once the queue runs out.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    results: Mutex<VecDeque<Result<Response, TransportError>>>,
+    requests: Mutex<Vec<Request>>,
+    sleeps: Mutex<Vec<Duration>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `response` as the answer to the next unanswered request.
+    pub fn with_response(self, response: Response) -> Self {
+        self.push(Ok(response));
+        self
+    }
+
+    /// Queue a failure to send, such as [`TransportError::Timeout`].
+    pub fn with_error(self, error: TransportError) -> Self {
+        self.push(Err(error));
+        self
+    }
+
+    pub fn push(&self, result: Result<Response, TransportError>) {
+        lock(&self.results).push_back(result);
+    }
+
+    /// The requests sent so far, oldest first.
+    pub fn requests(&self) -> Vec<Request> {
+        lock(&self.requests).clone()
+    }
+
+    /// The retry delays slept so far, oldest first.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        lock(&self.sleeps).clone()
+    }
+
+    fn answer(&self, request: Request) -> Result<Response, anyhow::Error> {
+        lock(&self.requests).push(request);
+        match lock(&self.results).pop_front() {
+            Some(result) => Ok(result?),
+            None => Err(anyhow::anyhow!("mock transport has no response queued")),
+        }
+    }
+}
+
+/// The data behind a poisoned lock is still a valid queue.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Transport for MockTransport {
+    fn send(&mut self, request: Request) -> Result<Response, anyhow::Error> {
+        self.answer(request)
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        lock(&self.sleeps).push(duration);
+    }
+}
+
+impl AsyncTransport for MockTransport {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response, anyhow::Error>> + Send {
+        std::future::ready(self.answer(request))
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        lock(&self.sleeps).push(duration);
+        std::future::ready(())
+    }
+}
+
+/// reqwest's blocking client, with the `reqwest` feature. Timeouts and
+/// connection failures come back as [`TransportError`]s; enable one of
+/// reqwest's TLS features in the application for `https` URLs.
+#[cfg(feature = "reqwest")]
+impl Transport for reqwest::blocking::Client {
+    fn send(&mut self, request: Request) -> Result<Response, anyhow::Error> {
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())?;
+        let mut builder = self.request(method, &request.url).body(request.body);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let response = builder.send().map_err(reqwest_error)?;
+        Ok(Response {
+            status: response.status().as_u16(),
+            headers: reqwest_headers(response.headers()),
+            body: response.bytes().map_err(reqwest_error)?.to_vec(),
+        })
+    }
+}
+
+/// reqwest's async client, with the `reqwest` feature. Needs a Tokio
+/// runtime, whose timer also waits out retry delays.
+#[cfg(feature = "reqwest")]
+impl AsyncTransport for reqwest::Client {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response, anyhow::Error>> + Send {
+        let builder = reqwest::Method::from_bytes(request.method.as_bytes()).map(|method| {
+            let mut builder = self.request(method, &request.url).body(request.body);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            match request.timeout {
+                Some(timeout) => builder.timeout(timeout),
+                None => builder,
+            }
+        });
+        async move {
+            let response = builder?.send().await.map_err(reqwest_error)?;
+            Ok(Response {
+                status: response.status().as_u16(),
+                headers: reqwest_headers(response.headers()),
+                body: response.bytes().await.map_err(reqwest_error)?.to_vec(),
+            })
+        }
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+fn reqwest_error(err: reqwest::Error) -> anyhow::Error {
+    if err.is_timeout() {
+        TransportError::Timeout.into()
+    } else if err.is_connect() {
+        TransportError::Connect(err.to_string()).into()
+    } else {
+        err.into()
+    }
+}
+
+#[cfg(feature = "reqwest")]
+fn reqwest_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.as_str().to_string(), value)
+        })
+        .collect()
+}
+
+fn rpc_request(url: &str, call: &XmlRpc) -> Result<Request, anyhow::Error> {
+    Ok(Request::post(url, rpc::to_string(call)?).with_header("Content-Type", "text/xml"))
+}
+
+fn rpc_response(response: Response) -> Result<XmlRpc, anyhow::Error> {
+    if !response.is_success() {
+        return Err(TransportError::Status(response).into());
+    }
+    rpc::from_slice(&response.body)
+}
+
+/// Makes XML-RPC calls to one endpoint through a blocking [`Transport`].
+#[derive(Debug)]
+pub struct RpcClient<T> {
+    transport: T,
+    url: String,
+    policy: RetryPolicy,
+}
+
+impl<T: Transport> RpcClient<T> {
+    pub fn new(transport: T, url: impl Into<String>) -> Self {
+        Self {
+            transport,
+            url: url.into(),
+            policy: RetryPolicy::none(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Send `call` and decode the reply. Statuses other than 2xx left once
+    /// retries are spent fail with [`TransportError::Status`].
+    pub fn call(&mut self, call: &XmlRpc) -> Result<XmlRpc, anyhow::Error> {
+        let request = rpc_request(&self.url, call)?;
+        rpc_response(send_retrying(&mut self.transport, &self.policy, request)?)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+}
+
+/// Makes XML-RPC calls to one endpoint through an [`AsyncTransport`].
+#[derive(Debug)]
+pub struct AsyncRpcClient<T> {
+    transport: T,
+    url: String,
+    policy: RetryPolicy,
+}
+
+impl<T: AsyncTransport> AsyncRpcClient<T> {
+    pub fn new(transport: T, url: impl Into<String>) -> Self {
+        Self {
+            transport,
+            url: url.into(),
+            policy: RetryPolicy::none(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Send `call` and decode the reply. Statuses other than 2xx left once
+    /// retries are spent fail with [`TransportError::Status`].
+    pub async fn call(&self, call: &XmlRpc) -> Result<XmlRpc, anyhow::Error> {
+        let request = rpc_request(&self.url, call)?;
+        rpc_response(send_retrying_async(&self.transport, &self.policy, request).await?)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+}
+
+/// The content type capabilities are sent and answered in.
+const LLSD_XML: &str = "application/llsd+xml";
+
+fn caps_get(url: &str) -> Request {
+    Request::get(url).with_header("Accept", LLSD_XML)
+}
+
+fn caps_post(url: &str, body: &Llsd) -> Result<Request, anyhow::Error> {
+    Ok(Request::post(url, crate::xml::to_request(body)?)
+        .with_header("Content-Type", LLSD_XML)
+        .with_header("Accept", LLSD_XML))
+}
+
+/// Decode a capability reply in whichever LLSD format it came in; an empty
+/// body is `Undefined`.
+fn caps_response(response: Response) -> Result<Llsd, anyhow::Error> {
+    if !response.is_success() {
+        return Err(TransportError::Status(response).into());
+    }
+    if response.body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Llsd::Undefined);
+    }
+    crate::autodetect::from_slice(&response.body)
+}
+
+/// What a conditional capability GET found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conditional {
+    /// A new value, with the entity tag to send next time if there was one.
+    Modified { llsd: Llsd, etag: Option<String> },
+    /// The value the sent tag names is still current.
+    NotModified,
+}
+
+fn caps_get_if_none_match(url: &str, etag: Option<&str>) -> Request {
+    match etag {
+        Some(etag) => caps_get(url).with_header("If-None-Match", etag),
+        None => caps_get(url),
+    }
+}
+
+fn caps_conditional(response: Response) -> Result<Conditional, anyhow::Error> {
+    if response.status == 304 {
+        return Ok(Conditional::NotModified);
+    }
+    let etag = response.header("ETag").map(str::to_string);
+    Ok(Conditional::Modified {
+        llsd: caps_response(response)?,
+        etag,
+    })
+}
+
+/// Fetches and posts LLSD to capability URLs through a blocking
+/// [`Transport`]. Requests go out as LLSD XML.
+#[derive(Debug)]
+pub struct CapsClient<T> {
+    transport: T,
+    policy: RetryPolicy,
+}
+
+impl<T: Transport> CapsClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            policy: RetryPolicy::none(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub fn get(&mut self, url: &str) -> Result<Llsd, anyhow::Error> {
+        caps_response(send_retrying(
+            &mut self.transport,
+            &self.policy,
+            caps_get(url),
+        )?)
+    }
+
+    /// GET `url`, sending `etag` (from an earlier [`Conditional::Modified`])
+    /// as `If-None-Match` so an unchanged value comes back as a bodyless
+    /// [`Conditional::NotModified`].
+    pub fn get_if_none_match(
+        &mut self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<Conditional, anyhow::Error> {
+        caps_conditional(send_retrying(
+            &mut self.transport,
+            &self.policy,
+            caps_get_if_none_match(url, etag),
+        )?)
+    }
+
+    pub fn post(&mut self, url: &str, body: &Llsd) -> Result<Llsd, anyhow::Error> {
+        let request = caps_post(url, body)?;
+        caps_response(send_retrying(&mut self.transport, &self.policy, request)?)
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+}
+
+/// Fetches and posts LLSD to capability URLs through an [`AsyncTransport`].
+#[derive(Debug)]
+pub struct AsyncCapsClient<T> {
+    transport: T,
+    policy: RetryPolicy,
+}
+
+impl<T: AsyncTransport> AsyncCapsClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            policy: RetryPolicy::none(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Llsd, anyhow::Error> {
+        caps_response(send_retrying_async(&self.transport, &self.policy, caps_get(url)).await?)
+    }
+
+    /// GET `url` conditionally, as [`CapsClient::get_if_none_match`] does.
+    pub async fn get_if_none_match(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<Conditional, anyhow::Error> {
+        let request = caps_get_if_none_match(url, etag);
+        caps_conditional(send_retrying_async(&self.transport, &self.policy, request).await?)
+    }
+
+    pub async fn post(&self, url: &str, body: &Llsd) -> Result<Llsd, anyhow::Error> {
+        let request = caps_post(url, body)?;
+        caps_response(send_retrying_async(&self.transport, &self.policy, request).await?)
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Llsd;
+
+    /// Drive a future that never waits, as mock transports' do.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match future.as_mut().poll(&mut cx) {
+            std::task::Poll::Ready(output) => output,
+            std::task::Poll::Pending => panic!("future is not ready"),
+        }
+    }
+
+    fn reply(llsd: Llsd) -> Response {
+        Response::new(200, rpc::to_string(&XmlRpc::MethodResponse(llsd)).unwrap())
+    }
+
+    fn call() -> XmlRpc {
+        XmlRpc::MethodCall("echo".into(), Llsd::String("hi".into()))
+    }
+
+    #[test]
+    fn sends_encoded_call_and_decodes_reply() {
+        let mut client = RpcClient::new(
+            MockTransport::new().with_response(reply(Llsd::Boolean(true))),
+            "https://grid.example/xmlrpc",
+        );
+        let response = client.call(&call()).unwrap();
+        assert_eq!(response, XmlRpc::MethodResponse(Llsd::Boolean(true)));
+
+        let requests = client.transport().requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].url, "https://grid.example/xmlrpc");
+        assert_eq!(rpc::from_slice(&requests[0].body).unwrap(), call());
+    }
+
+    #[test]
+    fn failures_keep_their_kind() {
+        let transport = MockTransport::new()
+            .with_response(Response::new(503, "busy").with_header("Retry-After", "2"))
+            .with_error(TransportError::Timeout)
+            .with_response(Response::new(200, "<html>"));
+        let mut client = RpcClient::new(transport, "https://grid.example/xmlrpc");
+
+        let err = client.call(&call()).unwrap_err();
+        match err.downcast_ref::<TransportError>() {
+            Some(TransportError::Status(response)) => {
+                assert_eq!(response.status, 503);
+                assert_eq!(response.header("retry-after"), Some("2"));
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        let err = client.call(&call()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransportError>(),
+            Some(TransportError::Timeout)
+        ));
+        // A 200 that is not XML-RPC is a decode error, not a transport one.
+        let err = client.call(&call()).unwrap_err();
+        assert!(err.downcast_ref::<TransportError>().is_none());
+        // The queue is spent.
+        assert!(client.call(&call()).is_err());
+        assert_eq!(client.transport().requests().len(), 4);
+    }
+
+    #[test]
+    fn async_client() {
+        let transport = MockTransport::new()
+            .with_response(reply(Llsd::Integer(1)))
+            .with_response(reply(Llsd::Integer(2)));
+        let client = AsyncRpcClient::new(&transport, "https://grid.example/xmlrpc");
+        let first = block_on(client.call(&call())).unwrap();
+        let second = block_on(client.call(&call())).unwrap();
+        assert_eq!(first.llsd(), &Llsd::Integer(1));
+        assert_eq!(second.llsd(), &Llsd::Integer(2));
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy::new()
+            .with_max_retries(10)
+            .with_initial_backoff(Duration::from_millis(100))
+            .with_max_backoff(Duration::from_secs(1))
+            .with_jitter(0.0);
+        let busy = Ok(Response::new(503, ""));
+        let delays: Vec<_> = (0..5)
+            .map(|attempt| policy.retry_delay(attempt, &busy, 0.9).unwrap())
+            .collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000].map(Duration::from_millis)
+        );
+        let jittered = policy.clone().with_jitter(0.5);
+        assert_eq!(
+            jittered.retry_delay(1, &busy, 0.5),
+            Some(Duration::from_millis(150))
+        );
+        assert_eq!(policy.retry_delay(10, &busy, 0.0), None);
+        // Only the configured statuses and timeouts are retried.
+        assert_eq!(
+            policy.retry_delay(0, &Ok(Response::new(500, "")), 0.0),
+            None
+        );
+        let refused = Err(TransportError::Connect("refused".into()).into());
+        assert_eq!(policy.retry_delay(0, &refused, 0.0), None);
+        let timeout = Err(TransportError::Timeout.into());
+        assert!(policy.retry_delay(0, &timeout, 0.0).is_some());
+        let policy = policy.with_retry_timeouts(false);
+        assert_eq!(policy.retry_delay(0, &timeout, 0.0), None);
+    }
+
+    #[test]
+    fn retry_after_overrides_backoff() {
+        let policy = RetryPolicy::new().with_max_backoff(Duration::from_secs(10));
+        let after = |value: &str| {
+            let response = Response::new(503, "").with_header("Retry-After", value);
+            policy.retry_delay(0, &Ok(response), 0.0)
+        };
+        assert_eq!(after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(after("120"), Some(Duration::from_secs(10)));
+        #[cfg(feature = "chrono")]
+        {
+            assert_eq!(after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+            let soon = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+            assert!(after(&soon).unwrap() > Duration::from_secs(5));
+        }
+        // Unparseable values fall back to the backoff.
+        assert_eq!(after("soon"), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn clients_retry_until_success() {
+        let transport = MockTransport::new()
+            .with_response(Response::new(503, "").with_header("Retry-After", "1"))
+            .with_error(TransportError::Timeout)
+            .with_response(reply(Llsd::Integer(3)));
+        let policy = RetryPolicy::new()
+            .with_jitter(0.0)
+            .with_timeout(Duration::from_secs(20));
+        let mut client =
+            RpcClient::new(transport, "https://grid.example/xmlrpc").with_retry_policy(policy);
+        assert_eq!(client.call(&call()).unwrap().llsd(), &Llsd::Integer(3));
+        let transport = client.into_transport();
+        assert_eq!(
+            transport.sleeps(),
+            [Duration::from_secs(1), Duration::from_secs(1)]
+        );
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.timeout == Some(Duration::from_secs(20)))
+        );
+    }
+
+    #[test]
+    fn clients_give_up_after_max_retries() {
+        let transport = MockTransport::new();
+        for _ in 0..3 {
+            transport.push(Ok(Response::new(503, "busy")));
+        }
+        let client = AsyncCapsClient::new(&transport)
+            .with_retry_policy(RetryPolicy::new().with_max_retries(2));
+        let err = block_on(client.get("https://sim.example/cap/1")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransportError>(),
+            Some(TransportError::Status(r)) if r.status == 503
+        ));
+        assert_eq!(transport.requests().len(), 3);
+        assert_eq!(transport.sleeps().len(), 2);
+    }
+
+    #[test]
+    fn caps_client_posts_llsd() {
+        let transport = MockTransport::new()
+            .with_response(Response::new(200, "<llsd><integer>5</integer></llsd>"))
+            .with_response(Response::new(200, ""));
+        let mut client = CapsClient::new(transport);
+        let body = Llsd::Array(vec![Llsd::String("a".into())]);
+        let url = "https://sim.example/cap/2";
+        assert_eq!(client.post(url, &body).unwrap(), Llsd::Integer(5));
+        assert_eq!(client.get(url).unwrap(), Llsd::Undefined);
+
+        let requests = client.transport().requests();
+        assert_eq!(requests[0].header("Content-Type"), Some(LLSD_XML));
+        assert_eq!(crate::xml::from_slice(&requests[0].body).unwrap(), body);
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[1].timeout, None);
+    }
+
+    #[test]
+    fn conditional_gets() {
+        let value = crate::llsd_map! { "events" => 3 };
+        let get = Request::get("https://sim.example/cap/3");
+        let fresh = Response::llsd_for(&get, &value).unwrap();
+        assert_eq!(fresh.status, 200);
+        let etag = fresh.header("etag").unwrap().to_string();
+        assert_eq!(etag, value.cache_key().unwrap().etag());
+        let conditional = get.clone().with_header("If-None-Match", &etag);
+        let unchanged = Response::llsd_for(&conditional, &value).unwrap();
+        assert_eq!(unchanged.status, 304);
+        assert!(unchanged.body.is_empty());
+        let changed = Response::llsd_for(&conditional, &Llsd::Integer(4)).unwrap();
+        assert_eq!(changed.status, 200);
+
+        let transport = MockTransport::new()
+            .with_response(fresh)
+            .with_response(unchanged)
+            .with_response(changed);
+        let mut client = CapsClient::new(transport);
+        let url = "https://sim.example/cap/3";
+        assert_eq!(
+            client.get_if_none_match(url, None).unwrap(),
+            Conditional::Modified {
+                llsd: value,
+                etag: Some(etag.clone())
+            }
+        );
+        assert_eq!(
+            client.get_if_none_match(url, Some(&etag)).unwrap(),
+            Conditional::NotModified
+        );
+        assert!(matches!(
+            client.get_if_none_match(url, Some(&etag)).unwrap(),
+            Conditional::Modified {
+                llsd: Llsd::Integer(4),
+                etag: Some(_)
+            }
+        ));
+        let requests = client.transport().requests();
+        assert_eq!(requests[0].header("If-None-Match"), None);
+        assert_eq!(requests[1].header("If-None-Match"), Some(etag.as_str()));
+    }
+
+    #[cfg(all(feature = "reqwest", feature = "test-server"))]
+    #[test]
+    fn reqwest_transports_talk_http() {
+        use crate::test_server::{MockResponse, MockServer};
+
+        let server = MockServer::start().unwrap();
+        server.respond(
+            "/login",
+            MockResponse::rpc(&XmlRpc::MethodResponse(Llsd::Integer(7))).unwrap(),
+        );
+        server.respond(
+            "/cap",
+            MockResponse::status(503).with_header("Retry-After", "0"),
+        );
+        server.respond("/cap", MockResponse::llsd(&Llsd::from("ok")).unwrap());
+
+        let mut rpc = RpcClient::new(reqwest::blocking::Client::new(), server.url_for("/login"));
+        assert_eq!(rpc.call(&call()).unwrap().llsd(), &Llsd::Integer(7));
+        let policy = RetryPolicy::new().with_max_retries(1);
+        let mut caps =
+            CapsClient::new(reqwest::blocking::Client::new()).with_retry_policy(policy.clone());
+        assert_eq!(
+            caps.post(&server.url_for("/cap"), &Llsd::Integer(1))
+                .unwrap(),
+            Llsd::from("ok")
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[0].rpc, Some(call()));
+        assert_eq!(requests[0].header("content-type"), Some("text/xml"));
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].llsd, Some(Llsd::Integer(1)));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let client = AsyncCapsClient::new(reqwest::Client::new()).with_retry_policy(policy);
+        let got = runtime
+            .block_on(client.get(&server.url_for("/cap")))
+            .unwrap();
+        assert_eq!(got, Llsd::from("ok"));
+        let missing = runtime.block_on(client.get(&server.url_for("/missing")));
+        assert!(matches!(
+            missing.unwrap_err().downcast_ref(),
+            Some(TransportError::Status(Response { status: 404, .. }))
+        ));
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = CapsClient::new(reqwest::blocking::Client::new())
+            .get(&format!("http://{closed}/cap"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(TransportError::Connect(_))
+        ));
+    }
+}
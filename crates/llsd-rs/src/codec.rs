@@ -0,0 +1,238 @@
+//! Base64 and base16 encoding for binary values.
+//!
+//! With the `simd` feature, base64 goes through `base64-simd`, which picks the
+//! widest instruction set available at runtime; otherwise the `base64` crate is
+//! used. Base16 output is written two bytes at a time from a lookup table.
+
+#[cfg(not(feature = "simd"))]
+use base64::prelude::*;
+
+/// Standard (padded) base64 of `data`.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    #[cfg(feature = "simd")]
+    {
+        base64_simd::STANDARD.encode_to_string(data)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        BASE64_STANDARD.encode(data)
+    }
+}
+
+/// Standard (padded) base64 of `data`, appended to `out`.
+pub(crate) fn base64_encode_into(data: &[u8], out: &mut String) {
+    #[cfg(feature = "simd")]
+    {
+        base64_simd::STANDARD.encode_append(data, out);
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        BASE64_STANDARD.encode_string(data, out);
+    }
+}
+
+/// Decode standard (padded) base64.
+pub(crate) fn base64_decode(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    #[cfg(feature = "simd")]
+    {
+        Ok(base64_simd::STANDARD.decode_to_vec(data)?)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        Ok(BASE64_STANDARD.decode(data)?)
+    }
+}
+
+/// Decode standard (padded) base64 of `data`, appended to `out`.
+fn base64_decode_append(data: &[u8], out: &mut Vec<u8>) -> anyhow::Result<()> {
+    #[cfg(feature = "simd")]
+    {
+        Ok(base64_simd::STANDARD.decode_append(data, out)?)
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        Ok(BASE64_STANDARD.decode_vec(data, out)?)
+    }
+}
+
+/// Input characters decoded per call into the base64 backend.
+const DECODE_BLOCK: usize = 4 * 1024;
+
+/// Base64 decoder fed text in pieces, such as the character events of one
+/// XML element, appending to the output as whole quads arrive.
+///
+/// ASCII whitespace between characters is skipped. Fails once the decoded
+/// length would pass `limit`.
+#[derive(Debug)]
+pub(crate) struct Base64Decoder {
+    block: Vec<u8>,
+    decoded: usize,
+    limit: usize,
+    over_limit: bool,
+    padded: bool,
+}
+
+impl Base64Decoder {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            block: Vec::new(),
+            decoded: 0,
+            limit,
+            over_limit: false,
+            padded: false,
+        }
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8], out: &mut Vec<u8>) -> anyhow::Result<()> {
+        for &b in data {
+            if b.is_ascii_whitespace() {
+                continue;
+            }
+            if self.padded {
+                return Err(anyhow::anyhow!("base64 data after padding"));
+            }
+            self.block.push(b);
+            if self.block.len() == DECODE_BLOCK {
+                self.flush(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the whole quads buffered so far, keeping any partial one.
+    fn flush(&mut self, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        let whole = self.block.len() / 4 * 4;
+        if whole == 0 {
+            return Ok(());
+        }
+        let quads = &self.block[..whole];
+        // Exact when the input is valid: padding only shortens the last quad.
+        let len =
+            (whole / 4 * 3).saturating_sub(quads.iter().rev().take_while(|&&b| b == b'=').count());
+        if self.decoded + len > self.limit {
+            self.over_limit = true;
+            return Err(anyhow::anyhow!(
+                "base64 data exceeds max decoded length {}",
+                self.limit
+            ));
+        }
+        let before = out.len();
+        base64_decode_append(quads, out)?;
+        self.decoded += out.len() - before;
+        self.padded = self.block[whole - 1] == b'=';
+        self.block.drain(..whole);
+        Ok(())
+    }
+
+    /// Whether decoding failed for passing the limit.
+    pub(crate) fn over_limit(&self) -> bool {
+        self.over_limit
+    }
+
+    /// Decode the rest, failing on a trailing partial quad.
+    pub(crate) fn finish(&mut self, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        self.flush(out)?;
+        if !self.block.is_empty() {
+            return Err(anyhow::anyhow!(
+                "base64 data ends in a partial group of {} characters",
+                self.block.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Standard (padded) base64 of `data`, handed to `write` a chunk at a time
+/// through `buf` so no more than a few kilobytes of text exist at once.
+pub(crate) fn base64_encode_chunks<E>(
+    data: &[u8],
+    buf: &mut String,
+    mut write: impl FnMut(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    for chunk in data.chunks(DECODE_BLOCK / 4 * 3) {
+        buf.clear();
+        base64_encode_into(chunk, buf);
+        write(buf)?;
+    }
+    Ok(())
+}
+
+const HEX_UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Uppercase base16 of `data`, appended to `out`.
+pub(crate) fn base16_encode_into(data: &[u8], out: &mut Vec<u8>) {
+    out.reserve(data.len() * 2);
+    out.extend(data.iter().flat_map(|b| {
+        [
+            HEX_UPPER[usize::from(b >> 4)],
+            HEX_UPPER[usize::from(b & 0x0f)],
+        ]
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trip() {
+        for len in 0..40 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37) as u8).collect();
+            let encoded = base64_encode(&data);
+            assert_eq!(encoded.len(), data.len().div_ceil(3) * 4);
+            assert_eq!(base64_decode(encoded.as_bytes()).unwrap(), data);
+        }
+        assert_eq!(base64_encode(b"llsd"), "bGxzZA==");
+        assert!(base64_decode(b"bGxzZA=").is_err());
+    }
+
+    #[test]
+    fn base64_decoder_takes_pieces() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i * 7) as u8).collect();
+        let mut encoded = String::new();
+        base64_encode_chunks(&data, &mut String::new(), |chunk| {
+            encoded.push_str(chunk);
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        assert_eq!(encoded, base64_encode(&data));
+        // Line-wrapped, and split at awkward places.
+        let wrapped: Vec<u8> = encoded
+            .as_bytes()
+            .chunks(76)
+            .flat_map(|line| line.iter().copied().chain(*b"\n  "))
+            .collect();
+        for piece in [1, 3, 5, 4096, 100_000] {
+            let mut decoder = Base64Decoder::new(data.len());
+            let mut out = Vec::new();
+            for chunk in wrapped.chunks(piece) {
+                decoder.push(chunk, &mut out).unwrap();
+            }
+            decoder.finish(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn base64_decoder_rejects_bad_input() {
+        let decode = |text: &str, limit| {
+            let mut decoder = Base64Decoder::new(limit);
+            let mut out = Vec::new();
+            decoder.push(text.as_bytes(), &mut out)?;
+            decoder.finish(&mut out).map(|()| out)
+        };
+        assert_eq!(decode("bGxzZA==", 4).unwrap(), b"llsd");
+        assert!(decode("bGxzZA=", 4).is_err());
+        assert!(decode("bGxzZA==bGxz", 16).is_err());
+        assert!(decode("bG!zZA==", 4).is_err());
+        let err = decode("bGxzZA==", 3).unwrap_err();
+        assert!(err.to_string().contains("max decoded length 3"), "{err}");
+    }
+
+    #[test]
+    fn base16_is_uppercase() {
+        let mut out = b"b16\"".to_vec();
+        base16_encode_into(&[0x00, 0x7f, 0xab, 0xff], &mut out);
+        assert_eq!(out, b"b16\"007FABFF");
+    }
+}
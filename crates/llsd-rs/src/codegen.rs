@@ -0,0 +1,686 @@
+//! Rust type definitions inferred from sample documents.
+//!
+//! [`generate`] looks at one or more samples of a message and writes the
+//! structs that read and write it with the [`derive`](crate::derive) macros,
+//! as a starting point to edit rather than a final model:
+//!
+//! - each map becomes a struct, named after its key (singular for an array
+//!   of maps), with fields in key order;
+//! - field types come from the values seen: `i32`, `f64` (also for integers
+//!   mixed with reals), `String`, `Uuid`, `DateTime<Utc>`, `Uri`, `Vec<u8>`,
+//!   `Vec<T>` and nested structs, or `Llsd` where samples disagree;
+//! - a key missing from some samples, or `Undefined` in any, gives an
+//!   `Option` field;
+//! - keys that aren't snake_case get a container `rename_all` when one rule
+//!   covers every field, and a `rename` per field otherwise.
+//!
+//! ```
+//! use llsd_rs::{Llsd, codegen::{self, CodegenOptions}};
+//!
+//! let sample = Llsd::map()
+//!     .insert("AgentID", llsd_rs::Uuid::nil())
+//!     .unwrap()
+//!     .insert("Flags", 3)
+//!     .unwrap();
+//! let code = codegen::generate(&[sample], &CodegenOptions::new("AgentUpdate")).unwrap();
+//! assert!(code.contains("pub struct AgentUpdate {"));
+//! assert!(code.contains("#[llsd(rename = \"AgentID\")]\n    pub agent_id: Uuid,"));
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::Llsd;
+
+#[derive(Debug, Clone)]
+pub struct CodegenOptions {
+    root: String,
+    derives: Vec<String>,
+    deny_unknown_fields: bool,
+}
+
+impl CodegenOptions {
+    /// Options for a root struct named `root`.
+    pub fn new(root: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            derives: ["Debug", "Clone", "PartialEq"].map(String::from).to_vec(),
+            deny_unknown_fields: false,
+        }
+    }
+
+    /// Derives listed before `LlsdFromTo` (`Debug, Clone, PartialEq` by
+    /// default).
+    pub fn with_derives<S: Into<String>>(mut self, derives: impl IntoIterator<Item = S>) -> Self {
+        self.derives = derives.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Mark every struct `#[llsd(deny_unknown_fields)]`.
+    pub fn with_deny_unknown_fields(mut self, deny: bool) -> Self {
+        self.deny_unknown_fields = deny;
+        self
+    }
+}
+
+/// What the samples showed of one value.
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    /// Nothing but `Undefined`, or an empty array's elements.
+    Unknown,
+    Boolean,
+    Integer,
+    Real,
+    String,
+    Uuid,
+    Date,
+    Uri,
+    Binary,
+    Array(Box<Slot>),
+    Map(Fields),
+    /// Samples of different kinds.
+    Mixed,
+}
+
+/// A value that may also be `Undefined`.
+#[derive(Debug, Clone, PartialEq)]
+struct Slot {
+    shape: Shape,
+    undefined: bool,
+}
+
+/// The entries of every sample of a map, with how many samples there were.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Fields {
+    samples: usize,
+    /// By key, with the number of samples holding it.
+    entries: BTreeMap<String, (Slot, usize)>,
+}
+
+impl Slot {
+    fn of(llsd: &Llsd) -> Slot {
+        let shape = match llsd {
+            Llsd::Undefined => Shape::Unknown,
+            Llsd::Boolean(_) => Shape::Boolean,
+            Llsd::Integer(_) => Shape::Integer,
+            Llsd::Real(_) => Shape::Real,
+            Llsd::String(_) => Shape::String,
+            Llsd::Uuid(_) => Shape::Uuid,
+            Llsd::Date(_) => Shape::Date,
+            Llsd::Uri(_) => Shape::Uri,
+            Llsd::Binary(_) => Shape::Binary,
+            Llsd::Array(items) => Shape::Array(Box::new(Slot::merged(items))),
+            Llsd::Map(map) => {
+                let mut fields = Fields::default();
+                fields.add(map);
+                Shape::Map(fields)
+            }
+        };
+        Slot {
+            shape,
+            undefined: llsd.is_undefined(),
+        }
+    }
+
+    fn merged<'a>(values: impl IntoIterator<Item = &'a Llsd>) -> Slot {
+        let mut values = values.into_iter();
+        let first = values.next().map_or(
+            Slot {
+                shape: Shape::Unknown,
+                undefined: false,
+            },
+            Slot::of,
+        );
+        values.fold(first, |slot, value| slot.merge(Slot::of(value)))
+    }
+
+    fn merge(self, other: Slot) -> Slot {
+        let shape = match (self.shape, other.shape) {
+            (Shape::Unknown, shape) | (shape, Shape::Unknown) => shape,
+            (Shape::Integer, Shape::Real) | (Shape::Real, Shape::Integer) => Shape::Real,
+            (Shape::Array(a), Shape::Array(b)) => Shape::Array(Box::new(a.merge(*b))),
+            (Shape::Map(mut a), Shape::Map(b)) => {
+                a.merge(b);
+                Shape::Map(a)
+            }
+            (a, b) if a == b => a,
+            _ => Shape::Mixed,
+        };
+        Slot {
+            shape,
+            undefined: self.undefined || other.undefined,
+        }
+    }
+}
+
+impl Fields {
+    fn add(&mut self, map: &crate::LlsdMap) {
+        self.merge(Fields {
+            samples: 1,
+            entries: map
+                .iter()
+                .map(|(key, value)| (key.clone(), (Slot::of(value), 1)))
+                .collect(),
+        });
+    }
+
+    fn merge(&mut self, other: Fields) {
+        self.samples += other.samples;
+        for (key, (slot, seen)) in other.entries {
+            match self.entries.remove(&key) {
+                Some((mine, mine_seen)) => {
+                    self.entries
+                        .insert(key, (mine.merge(slot), mine_seen + seen));
+                }
+                None => {
+                    self.entries.insert(key, (slot, seen));
+                }
+            }
+        }
+    }
+}
+
+/// Rust source for the structs describing `samples`, which must all be maps
+/// or arrays of maps (described by their element struct).
+pub fn generate(samples: &[Llsd], options: &CodegenOptions) -> Result<String, anyhow::Error> {
+    let mut fields = Fields::default();
+    for sample in samples {
+        match sample {
+            Llsd::Map(map) => fields.add(map),
+            Llsd::Array(items) => {
+                for item in items {
+                    let Llsd::Map(map) = item else {
+                        return Err(anyhow::anyhow!(
+                            "codegen: expected an array of maps, found an array holding {}",
+                            item.type_name()
+                        ));
+                    };
+                    fields.add(map);
+                }
+            }
+            other => {
+                return Err(anyhow::anyhow!(
+                    "codegen: expected a map, found {}",
+                    other.type_name()
+                ));
+            }
+        }
+    }
+    if fields.samples == 0 {
+        return Err(anyhow::anyhow!("codegen: no samples"));
+    }
+
+    let mut writer = Writer {
+        options,
+        names: Vec::new(),
+        structs: Vec::new(),
+        imports: Imports::default(),
+    };
+    writer.names.push(options.root.clone());
+    writer.write_struct(&options.root, &fields);
+
+    let mut out = String::new();
+    for import in writer.imports.lines() {
+        out.push_str(import);
+        out.push('\n');
+    }
+    for code in &writer.structs {
+        out.push('\n');
+        out.push_str(code);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Default)]
+struct Imports {
+    llsd: bool,
+    uri: bool,
+    uuid: bool,
+    date: bool,
+}
+
+impl Imports {
+    fn lines(&self) -> Vec<&'static str> {
+        let mut lines = vec![match (self.llsd, self.uri) {
+            (false, false) => "use llsd_rs::LlsdFromTo;",
+            (true, false) => "use llsd_rs::{Llsd, LlsdFromTo};",
+            (false, true) => "use llsd_rs::{LlsdFromTo, Uri};",
+            (true, true) => "use llsd_rs::{Llsd, LlsdFromTo, Uri};",
+        }];
+        if self.date {
+            lines.insert(0, "use chrono::{DateTime, Utc};");
+        }
+        if self.uuid {
+            lines.push("use uuid::Uuid;");
+        }
+        lines
+    }
+}
+
+struct Writer<'a> {
+    options: &'a CodegenOptions,
+    /// Struct names taken so far.
+    names: Vec<String>,
+    /// Finished definitions, the root first.
+    structs: Vec<String>,
+    imports: Imports,
+}
+
+impl Writer<'_> {
+    /// Write the struct `name` for `fields`; nested structs follow it.
+    fn write_struct(&mut self, name: &str, fields: &Fields) {
+        let index = self.structs.len();
+        self.structs.push(String::new());
+
+        let idents: Vec<(String, &str)> = fields
+            .entries
+            .keys()
+            .map(|key| (field_ident(key), key.as_str()))
+            .collect();
+        let rename_all = rename_rule(&idents);
+        let mut body = String::new();
+        for ((ident, key), (slot, seen)) in idents.iter().zip(fields.entries.values()) {
+            let optional = *seen < fields.samples || slot.undefined;
+            let ty = self.field_type(key, slot, optional);
+            let unraw = ident.trim_start_matches("r#");
+            let renamed = match rename_all {
+                Some(rule) => rule.apply(unraw),
+                None => unraw.to_string(),
+            };
+            if renamed != *key {
+                let _ = writeln!(body, "    #[llsd(rename = {key:?})]");
+            }
+            let _ = writeln!(body, "    pub {ident}: {ty},");
+        }
+
+        let mut code = String::new();
+        let mut derives = self.options.derives.clone();
+        derives.push("LlsdFromTo".into());
+        let _ = writeln!(code, "#[derive({})]", derives.join(", "));
+        let mut attributes = Vec::new();
+        if let Some(rule) = rename_all {
+            attributes.push(format!("rename_all = {:?}", rule.name()));
+        }
+        if self.options.deny_unknown_fields {
+            attributes.push("deny_unknown_fields".into());
+        }
+        if !attributes.is_empty() {
+            let _ = writeln!(code, "#[llsd({})]", attributes.join(", "));
+        }
+        let _ = writeln!(code, "pub struct {name} {{\n{body}}}");
+        self.structs[index] = code;
+    }
+
+    fn field_type(&mut self, key: &str, slot: &Slot, optional: bool) -> String {
+        let ty = self.shape_type(key, &slot.shape, false);
+        if optional && !ty.starts_with("Option<") {
+            format!("Option<{ty}>")
+        } else {
+            ty
+        }
+    }
+
+    /// The type for `shape`, found under `key` (as an array's elements when
+    /// `element`).
+    fn shape_type(&mut self, key: &str, shape: &Shape, element: bool) -> String {
+        match shape {
+            Shape::Unknown => {
+                self.imports.llsd = true;
+                "Option<Llsd>".into()
+            }
+            Shape::Mixed => {
+                self.imports.llsd = true;
+                "Llsd".into()
+            }
+            Shape::Boolean => "bool".into(),
+            Shape::Integer => "i32".into(),
+            Shape::Real => "f64".into(),
+            Shape::String => "String".into(),
+            Shape::Uuid => {
+                self.imports.uuid = true;
+                "Uuid".into()
+            }
+            Shape::Date => {
+                self.imports.date = true;
+                "DateTime<Utc>".into()
+            }
+            Shape::Uri => {
+                self.imports.uri = true;
+                "Uri".into()
+            }
+            Shape::Binary => "Vec<u8>".into(),
+            Shape::Array(slot) => {
+                let inner = self.shape_type(key, &slot.shape, true);
+                if slot.undefined && !inner.starts_with("Option<") {
+                    format!("Vec<Option<{inner}>>")
+                } else {
+                    format!("Vec<{inner}>")
+                }
+            }
+            Shape::Map(fields) => {
+                let name = self.struct_name(key, element);
+                self.write_struct(&name, fields);
+                name
+            }
+        }
+    }
+
+    /// An unused struct name for a map under `key`.
+    fn struct_name(&mut self, key: &str, element: bool) -> String {
+        let mut base = to_pascal_case(&field_ident(key).replace("r#", ""));
+        if base.is_empty() || base.starts_with(|c: char| c.is_ascii_digit()) {
+            base.insert_str(0, "Entry");
+        }
+        if element {
+            base = singular(&base);
+        }
+        let mut name = base.clone();
+        let mut n = 2;
+        while self.names.contains(&name) {
+            name = format!("{base}{n}");
+            n += 1;
+        }
+        self.names.push(name.clone());
+        name
+    }
+}
+
+/// `folders` -> `Folder`; names that don't look plural get `Item`.
+fn singular(name: &str) -> String {
+    match name.strip_suffix('s') {
+        Some(stem) if stem.len() > 2 && !stem.ends_with('s') => stem.to_string(),
+        _ => format!("{name}Item"),
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "false", "fn",
+    "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// A snake_case field name for `key`: `AgentID` -> `agent_id`,
+/// `sim-name` -> `sim_name`, `type` -> `r#type`.
+fn field_ident(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && i > 0 && !out.ends_with('_') {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(char::is_ascii_lowercase);
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    let mut ident = out.trim_matches('_').to_string();
+    if ident.is_empty() {
+        ident = "field".into();
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    match ident.as_str() {
+        "self" | "super" | "crate" | "extern" | "Self" => ident + "_",
+        kw if KEYWORDS.contains(&kw) => format!("r#{ident}"),
+        _ => ident,
+    }
+}
+
+/// The `rename_all` rules the derive supports that codegen tries, with the
+/// derive's conversions.
+#[derive(Debug, Clone, Copy)]
+enum RenameRule {
+    Camel,
+    Pascal,
+    Kebab,
+    ScreamingSnake,
+}
+
+impl RenameRule {
+    const ALL: [RenameRule; 4] = [
+        RenameRule::Pascal,
+        RenameRule::Camel,
+        RenameRule::Kebab,
+        RenameRule::ScreamingSnake,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            RenameRule::Camel => "camelCase",
+            RenameRule::Pascal => "PascalCase",
+            RenameRule::Kebab => "kebab-case",
+            RenameRule::ScreamingSnake => "SCREAMING_SNAKE_CASE",
+        }
+    }
+
+    fn apply(self, name: &str) -> String {
+        match self {
+            RenameRule::Camel => to_camel_case(name),
+            RenameRule::Pascal => to_pascal_case(name),
+            RenameRule::Kebab => name.replace('_', "-"),
+            RenameRule::ScreamingSnake => name.to_uppercase(),
+        }
+    }
+}
+
+/// A rule mapping every field to its key, when some key isn't its field's
+/// name already.
+fn rename_rule(idents: &[(String, &str)]) -> Option<RenameRule> {
+    let unraw = |ident: &str| ident.trim_start_matches("r#").to_string();
+    if idents.iter().all(|(ident, key)| unraw(ident) == *key) {
+        return None;
+    }
+    RenameRule::ALL.into_iter().find(|rule| {
+        idents
+            .iter()
+            .all(|(ident, key)| rule.apply(&unraw(ident)) == *key)
+    })
+}
+
+fn to_camel_case(s: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for ch in s.chars() {
+        if ch == '_' || ch == '-' {
+            upper = true;
+            continue;
+        }
+        if upper {
+            out.extend(ch.to_uppercase());
+            upper = false;
+        } else {
+            out.push(ch.to_ascii_lowercase());
+        }
+    }
+    out
+}
+
+fn to_pascal_case(s: &str) -> String {
+    let camel = to_camel_case(s);
+    let mut chars = camel.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LlsdMap;
+    use crate::Uuid;
+
+    fn parcel(extra: bool) -> Llsd {
+        let folder = |name: &str, id: u128| {
+            Llsd::map()
+                .insert("name", name)
+                .unwrap()
+                .insert("folder_id", Uuid::from_u128(id))
+                .unwrap()
+                .insert(
+                    "version",
+                    if extra {
+                        Llsd::Real(1.5)
+                    } else {
+                        Llsd::Integer(1)
+                    },
+                )
+                .unwrap()
+        };
+        let mut map = LlsdMap::new();
+        map.insert("RegionID".into(), Llsd::Uuid(Uuid::from_u128(1)));
+        map.insert("Name".into(), Llsd::String("Home".into()));
+        map.insert("type".into(), Llsd::Integer(2));
+        map.insert(
+            "folders".into(),
+            Llsd::Array(vec![folder("Objects", 2), folder("Scripts", 3)]),
+        );
+        map.insert("Tags".into(), Llsd::Array(vec![]));
+        map.insert("Owner".into(), Llsd::Undefined);
+        map.insert(
+            "Snapshot".into(),
+            Llsd::map()
+                .insert("URL", Llsd::Uri("https://example.com/".into()))
+                .unwrap(),
+        );
+        if extra {
+            map.insert("MusicURL".into(), Llsd::String("http://radio/".into()));
+        }
+        Llsd::Map(map)
+    }
+
+    #[test]
+    fn infers_structs_from_samples() {
+        let code = generate(
+            &[parcel(false), parcel(true)],
+            &CodegenOptions::new("Parcel"),
+        )
+        .unwrap();
+        assert_eq!(
+            code,
+            r#"use llsd_rs::{Llsd, LlsdFromTo, Uri};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+pub struct Parcel {
+    #[llsd(rename = "MusicURL")]
+    pub music_url: Option<String>,
+    #[llsd(rename = "Name")]
+    pub name: String,
+    #[llsd(rename = "Owner")]
+    pub owner: Option<Llsd>,
+    #[llsd(rename = "RegionID")]
+    pub region_id: Uuid,
+    #[llsd(rename = "Snapshot")]
+    pub snapshot: Snapshot,
+    #[llsd(rename = "Tags")]
+    pub tags: Vec<Option<Llsd>>,
+    pub folders: Vec<Folder>,
+    pub r#type: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct Snapshot {
+    pub url: Uri,
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+pub struct Folder {
+    pub folder_id: Uuid,
+    pub name: String,
+    pub version: f64,
+}
+"#
+        );
+    }
+
+    #[test]
+    fn names_and_renames() {
+        assert_eq!(field_ident("AgentID"), "agent_id");
+        assert_eq!(field_ident("IDName"), "id_name");
+        assert_eq!(field_ident("sim-name"), "sim_name");
+        assert_eq!(field_ident("2nd place"), "_2nd_place");
+        assert_eq!(field_ident("self"), "self_");
+        assert_eq!(field_ident("fn"), "r#fn");
+        assert_eq!(field_ident(""), "field");
+        let keys = ["RegionHandle", "SimName"];
+        let idents: Vec<_> = keys.iter().map(|k| (field_ident(k), *k)).collect();
+        assert_eq!(
+            rename_rule(&idents).map(RenameRule::name),
+            Some("PascalCase")
+        );
+
+        let options = CodegenOptions::new("Event")
+            .with_derives(["Debug"])
+            .with_deny_unknown_fields(true);
+        let sample = Llsd::map().insert("messageName", "x").unwrap();
+        assert_eq!(
+            generate(&[sample], &options).unwrap(),
+            "use llsd_rs::LlsdFromTo;\n\n#[derive(Debug, LlsdFromTo)]\n\
+             #[llsd(rename_all = \"camelCase\", deny_unknown_fields)]\n\
+             pub struct Event {\n    pub message_name: String,\n}\n"
+        );
+        assert!(generate(&[Llsd::Integer(1)], &options).is_err());
+        assert!(generate(&[], &options).is_err());
+    }
+
+    /// The output of `infers_structs_from_samples`, compiled.
+    #[cfg(feature = "derive")]
+    mod generated {
+        use crate::Uuid;
+        use crate::{Llsd, LlsdFromTo, Uri};
+
+        #[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+        pub struct Parcel {
+            #[llsd(rename = "MusicURL")]
+            pub music_url: Option<String>,
+            #[llsd(rename = "Name")]
+            pub name: String,
+            #[llsd(rename = "Owner")]
+            pub owner: Option<Llsd>,
+            #[llsd(rename = "RegionID")]
+            pub region_id: Uuid,
+            #[llsd(rename = "Snapshot")]
+            pub snapshot: Snapshot,
+            #[llsd(rename = "Tags")]
+            pub tags: Vec<Option<Llsd>>,
+            pub folders: Vec<Folder>,
+            pub r#type: i32,
+        }
+
+        #[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+        #[llsd(rename_all = "SCREAMING_SNAKE_CASE")]
+        pub struct Snapshot {
+            pub url: Uri,
+        }
+
+        #[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+        pub struct Folder {
+            pub folder_id: Uuid,
+            pub name: String,
+            pub version: f64,
+        }
+
+        #[test]
+        fn reads_its_samples() {
+            for sample in [super::parcel(false), super::parcel(true)] {
+                let parcel = Parcel::try_from(&sample).unwrap();
+                assert_eq!(parcel.folders.len(), 2);
+                assert_eq!(parcel.r#type, 2);
+                assert_eq!(parcel.snapshot.url.as_str(), "https://example.com/");
+            }
+        }
+    }
+}
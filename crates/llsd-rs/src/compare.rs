@@ -0,0 +1,291 @@
+//! Semantic comparison of serialized documents without decoding them.
+//!
+//! [`streams_equal`] reads two documents, in any mix of formats, side by
+//! side as streams of tokens and stops at the first difference, holding only
+//! the open containers and the current scalar. Map entries are expected in
+//! the same order, as they come from one writer; where the keys of two maps
+//! part ways, the rest of those two maps is decoded and compared by content,
+//! so reordered maps are still equal but cost memory for what remains of
+//! them.
+//!
+//! Equality is that of the decoded values, except that a NaN real equals
+//! another NaN, so a document always equals itself.
+//!
+//! ```
+//! use llsd_rs::{Llsd, autodetect::LlsdEncoding, compare};
+//!
+//! let llsd = Llsd::map().insert("id", 7).unwrap();
+//! let binary = llsd_rs::binary::to_vec(&llsd).unwrap();
+//! let xml = llsd_rs::xml::to_string(&llsd).unwrap();
+//! assert!(compare::streams_equal(
+//!     LlsdEncoding::Binary,
+//!     &binary[..],
+//!     LlsdEncoding::Xml,
+//!     xml.as_bytes(),
+//! )
+//! .unwrap());
+//! ```
+
+use std::io::Read;
+
+use crate::{
+    Llsd, LlsdMap,
+    autodetect::LlsdEncoding,
+    debug::{ERROR_DEPTH, ERROR_ITEMS},
+};
+
+/// Deepest nesting followed, as for the decoders.
+const MAX_DEPTH: usize = 64;
+
+/// One step through a document.
+pub(crate) enum Token {
+    Scalar(Llsd),
+    ArrayStart,
+    MapStart,
+    Key(String),
+    /// Closes the innermost array or map.
+    End,
+}
+
+/// Scalars are shown cut short, as tokens end up in error messages.
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Scalar(llsd) => f
+                .debug_tuple("Scalar")
+                .field(&llsd.debug_limited(ERROR_DEPTH, ERROR_ITEMS))
+                .finish(),
+            Token::ArrayStart => f.write_str("ArrayStart"),
+            Token::MapStart => f.write_str("MapStart"),
+            Token::Key(key) => f.debug_tuple("Key").field(key).finish(),
+            Token::End => f.write_str("End"),
+        }
+    }
+}
+
+/// A format's tokenizer. Returns `None` once the top-level value is
+/// complete.
+pub(crate) trait TokenSource {
+    fn next_token(&mut self) -> Result<Option<Token>, anyhow::Error>;
+}
+
+fn tokens<'r>(format: LlsdEncoding, reader: impl Read + 'r) -> Box<dyn TokenSource + 'r> {
+    match format {
+        LlsdEncoding::Binary => Box::new(crate::binary::Tokens::new(reader)),
+        LlsdEncoding::Notation => Box::new(crate::notation::Tokens::new(reader)),
+        LlsdEncoding::Xml => Box::new(crate::xml::Tokens::new(reader)),
+    }
+}
+
+fn next(source: &mut dyn TokenSource) -> Result<Token, anyhow::Error> {
+    source
+        .next_token()?
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of document"))
+}
+
+/// Whether `a` (in `format_a`) and `b` (in `format_b`) hold equal values.
+/// Reading stops at the first difference; a malformed document is an error
+/// unless a difference came first.
+pub fn streams_equal<A: Read, B: Read>(
+    format_a: LlsdEncoding,
+    a: A,
+    format_b: LlsdEncoding,
+    b: B,
+) -> Result<bool, anyhow::Error> {
+    let mut a = tokens(format_a, a);
+    let mut b = tokens(format_b, b);
+    let mut depth = 0usize;
+    loop {
+        match (next(&mut *a)?, next(&mut *b)?) {
+            (Token::Key(ka), Token::Key(kb)) if ka != kb => {
+                let rest_a = rest_of_map(&mut *a, ka, depth)?;
+                let rest_b = rest_of_map(&mut *b, kb, depth)?;
+                if !maps_equal(&rest_a, &rest_b) {
+                    return Ok(false);
+                }
+                depth -= 1;
+            }
+            (Token::Scalar(va), Token::Scalar(vb)) => {
+                if !values_equal(&va, &vb) {
+                    return Ok(false);
+                }
+            }
+            (Token::Key(ka), Token::Key(kb)) => debug_assert_eq!(ka, kb),
+            (Token::ArrayStart, Token::ArrayStart) | (Token::MapStart, Token::MapStart) => {
+                depth += 1;
+                if depth > MAX_DEPTH {
+                    return Err(anyhow::anyhow!("maximum depth exceeded"));
+                }
+            }
+            (Token::End, Token::End) => depth -= 1,
+            _ => return Ok(false),
+        }
+        if depth == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Decode what is left of the map whose entry `key` was just read, up to
+/// and including its end.
+fn rest_of_map(
+    source: &mut dyn TokenSource,
+    mut key: String,
+    depth: usize,
+) -> Result<LlsdMap, anyhow::Error> {
+    let mut map = LlsdMap::new();
+    loop {
+        let value = build(source, depth)?;
+        map.insert(key, value);
+        match next(source)? {
+            Token::Key(next) => key = next,
+            Token::End => return Ok(map),
+            token => return Err(anyhow::anyhow!("expected a map key, found {token:?}")),
+        }
+    }
+}
+
+/// Decode the next value, inside `depth` open containers.
+fn build(source: &mut dyn TokenSource, depth: usize) -> Result<Llsd, anyhow::Error> {
+    match next(source)? {
+        Token::Scalar(value) => Ok(value),
+        Token::ArrayStart | Token::MapStart if depth >= MAX_DEPTH => {
+            Err(anyhow::anyhow!("maximum depth exceeded"))
+        }
+        Token::ArrayStart => {
+            let mut items = Vec::new();
+            loop {
+                match build(source, depth + 1) {
+                    Ok(item) => items.push(item),
+                    Err(e) if e.is::<EndOfContainer>() => return Ok(Llsd::Array(items)),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Token::MapStart => match next(source)? {
+            Token::End => Ok(Llsd::Map(LlsdMap::new())),
+            Token::Key(key) => rest_of_map(source, key, depth + 1).map(Llsd::Map),
+            token => Err(anyhow::anyhow!("expected a map key, found {token:?}")),
+        },
+        Token::End => Err(EndOfContainer.into()),
+        Token::Key(key) => Err(anyhow::anyhow!("unexpected map key {key:?}")),
+    }
+}
+
+/// [`build`] met the end of the enclosing array instead of a value.
+#[derive(Debug, thiserror::Error)]
+#[error("unexpected end of container")]
+struct EndOfContainer;
+
+fn maps_equal(a: &LlsdMap, b: &LlsdMap) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|(key, va)| b.get(key.as_str()).is_some_and(|vb| values_equal(va, vb)))
+}
+
+/// `==`, with NaN equal to NaN.
+fn values_equal(a: &Llsd, b: &Llsd) -> bool {
+    match (a, b) {
+        (Llsd::Real(a), Llsd::Real(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (Llsd::Array(a), Llsd::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
+        }
+        (Llsd::Map(a), Llsd::Map(b)) => maps_equal(a, b),
+        (a, b) => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::FormatterContext;
+    use LlsdEncoding::*;
+
+    fn encode(format: LlsdEncoding, llsd: &Llsd) -> Vec<u8> {
+        match format {
+            Binary => crate::binary::to_vec(llsd).unwrap(),
+            Notation => crate::notation::to_vec(llsd, &FormatterContext::new()).unwrap(),
+            Xml => crate::xml::to_string(llsd).unwrap().into_bytes(),
+        }
+    }
+
+    fn equal(a: &Llsd, b: &Llsd) -> bool {
+        let mut results = Vec::new();
+        for fa in [Binary, Notation, Xml] {
+            for fb in [Binary, Notation, Xml] {
+                let (ea, eb) = (encode(fa, a), encode(fb, b));
+                results.push(streams_equal(fa, &ea[..], fb, &eb[..]).unwrap());
+            }
+        }
+        assert!(results.iter().all(|&r| r == results[0]), "{results:?}");
+        results[0]
+    }
+
+    #[test]
+    fn compares_across_formats() {
+        let options = crate::corpus::CorpusOptions::new(1444).with_max_depth(4);
+        let values: Vec<Llsd> = crate::corpus::Generator::new(options).take(30).collect();
+        for (i, value) in values.iter().enumerate() {
+            assert!(equal(value, value), "{i}");
+        }
+        for pair in values.windows(2) {
+            assert_eq!(equal(&pair[0], &pair[1]), values_equal(&pair[0], &pair[1]));
+        }
+    }
+
+    #[test]
+    fn finds_differences() {
+        let map = |entries: &[(&str, Llsd)]| {
+            Llsd::Map(
+                entries
+                    .iter()
+                    .cloned()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            )
+        };
+        let a = map(&[("a", Llsd::Integer(1)), ("b", Llsd::Array(vec![]))]);
+        assert!(!equal(&a, &map(&[("a", Llsd::Integer(1))])));
+        assert!(!equal(
+            &a,
+            &map(&[("a", Llsd::Integer(2)), ("b", Llsd::Array(vec![]))])
+        ));
+        assert!(!equal(
+            &a,
+            &map(&[("a", Llsd::Integer(1)), ("c", Llsd::Array(vec![]))])
+        ));
+        assert!(!equal(
+            &a,
+            &map(&[
+                ("a", Llsd::Integer(1)),
+                ("b", Llsd::Array(vec![Llsd::Undefined]))
+            ])
+        ));
+        assert!(!equal(&Llsd::Array(vec![]), &Llsd::map()));
+        assert!(equal(&Llsd::Real(f64::NAN), &Llsd::Real(f64::NAN)));
+        assert!(!equal(&Llsd::Integer(1), &Llsd::Real(1.0)));
+    }
+
+    #[test]
+    fn reordered_maps_are_equal() {
+        let nested = Llsd::Array(vec![Llsd::Integer(1), Llsd::map().insert("x", 1).unwrap()]);
+        let a = "{'k':i0,'a':[i1,{'x':i1}],'b':'two','c':[]}";
+        let b = "{'k':i0,'c':[],'b':'two','a':[i1,{'x':i1}]}";
+        let c = "{'k':i0,'c':[],'b':'two','a':[i1,{'x':i2}]}";
+        let notation = |s: &'static str| s.as_bytes();
+        assert!(streams_equal(Notation, notation(a), Notation, notation(b)).unwrap());
+        assert!(!streams_equal(Notation, notation(a), Notation, notation(c)).unwrap());
+        let xml = crate::xml::to_string(&crate::notation::from_str(b, 64).unwrap()).unwrap();
+        assert!(streams_equal(Notation, notation(a), Xml, xml.as_bytes()).unwrap());
+        assert_eq!(crate::notation::from_str(a, 64).unwrap()["a"], nested);
+    }
+
+    #[test]
+    fn stops_at_the_first_difference() {
+        // Garbage after the difference is never read.
+        let a = b"[i1,i2,i3]";
+        let b = b"[i1,i9,\xff\xff";
+        assert!(!streams_equal(Notation, &a[..], Notation, &b[..]).unwrap());
+        assert!(streams_equal(Notation, &a[..], Notation, &b"[i1,\xff"[..]).is_err());
+    }
+}
@@ -0,0 +1,554 @@
+//! `FromLlsd` / `IntoLlsd`: the conversion traits targeted by the derive macros.
+//!
+//! The derives implement these traits and, unless `#[llsd(traits_only)]` is
+//! set, thin `TryFrom<&Llsd>` / `TryFrom<Llsd>` / `From<T> for Llsd` bridges on
+//! top. Field values are converted through the traits as well, so a type that
+//! only has hand-written `TryFrom`/`From` impls can be used as a field after
+//! bridging it with [`from_llsd_via_try_from!`](crate::from_llsd_via_try_from)
+//! and [`into_llsd_via_from!`](crate::into_llsd_via_from).
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::Uuid;
+use url::Url;
+
+use crate::{Date, Llsd, LlsdError, Uri};
+
+/// Conversion from LLSD with a path-carrying [`LlsdError`].
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has no conversion from LLSD",
+    label = "`FromLlsd` is not implemented for `{Self}`",
+    note = "for a derived field, consider `#[llsd(with = module)]`, or bridge an existing `TryFrom<&Llsd>` impl with `llsd_rs::from_llsd_via_try_from!`"
+)]
+pub trait FromLlsd: Sized {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError>;
+
+    /// Consuming conversion; override it to move heap data out of `llsd`.
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        Self::from_llsd(&llsd)
+    }
+
+    /// Overwrite `self` with the values present in `llsd`. Derived structs only
+    /// touch the fields whose keys are present (recursing into nested structs),
+    /// so sparse deltas keep unspecified fields; other types replace `self`.
+    /// On error, fields updated before the failing one keep their new values.
+    fn update_from_llsd(&mut self, llsd: &Llsd) -> Result<(), LlsdError> {
+        *self = Self::from_llsd(llsd)?;
+        Ok(())
+    }
+
+    /// Map keys read by this type, including those of flattened fields.
+    /// Derived structs fill this in; `flatten` and `deny_unknown_fields` use it.
+    fn known_keys(_keys: &mut Vec<&'static str>) {}
+}
+
+/// Conversion into LLSD.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has no conversion into LLSD",
+    label = "`IntoLlsd` is not implemented for `{Self}`",
+    note = "for a derived field, consider `#[llsd(with = module)]`, or bridge an existing `From<T> for Llsd` impl with `llsd_rs::into_llsd_via_from!`"
+)]
+pub trait IntoLlsd {
+    fn to_llsd(&self) -> Llsd;
+
+    /// Consuming conversion; override it to avoid cloning heap data.
+    fn into_llsd(self) -> Llsd
+    where
+        Self: Sized,
+    {
+        self.to_llsd()
+    }
+}
+
+/// Implement [`FromLlsd`] for types that already convert through
+/// `TryFrom<&Llsd>` and `TryFrom<Llsd>` (with errors convertible to `anyhow::Error`).
+#[macro_export]
+macro_rules! from_llsd_via_try_from {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl $crate::FromLlsd for $t {
+                fn from_llsd(llsd: &$crate::Llsd) -> ::core::result::Result<Self, $crate::LlsdError> {
+                    <$t as ::core::convert::TryFrom<&$crate::Llsd>>::try_from(llsd)
+                        .map_err(|e| $crate::LlsdError::from_anyhow(e.into()))
+                }
+
+                fn from_llsd_owned(llsd: $crate::Llsd) -> ::core::result::Result<Self, $crate::LlsdError> {
+                    <$t as ::core::convert::TryFrom<$crate::Llsd>>::try_from(llsd)
+                        .map_err(|e| $crate::LlsdError::from_anyhow(e.into()))
+                }
+            }
+        )*
+    };
+}
+
+/// Implement [`IntoLlsd`] for types with `From<T>` and `From<&T>` impls for `Llsd`.
+#[macro_export]
+macro_rules! into_llsd_via_from {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl $crate::IntoLlsd for $t {
+                fn to_llsd(&self) -> $crate::Llsd {
+                    $crate::Llsd::from(self)
+                }
+
+                fn into_llsd(self) -> $crate::Llsd {
+                    $crate::Llsd::from(self)
+                }
+            }
+        )*
+    };
+}
+
+from_llsd_via_try_from!(f32, f64, bool, String, Uuid, Url, Uri, Date);
+
+/// Integers read through the checked `Llsd::to_*` conversions, failing with
+/// `OutOfRange` where the `TryFrom` impls would wrap or truncate.
+macro_rules! from_llsd_checked {
+    ($($t:ty => $to:ident),* $(,)?) => {
+        $(
+            impl FromLlsd for $t {
+                fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+                    llsd.$to()
+                }
+            }
+        )*
+    };
+}
+
+from_llsd_checked!(
+    u8 => to_u8,
+    u16 => to_u16,
+    u32 => to_u32,
+    u64 => to_u64,
+    i8 => to_i8,
+    i16 => to_i16,
+    i32 => to_i32,
+    i64 => to_i64,
+);
+
+into_llsd_via_from!(
+    u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool, String, Uuid, Url, Uri, Date
+);
+
+#[cfg(feature = "chrono")]
+into_llsd_via_from!(chrono::DateTime<chrono::FixedOffset>);
+#[cfg(feature = "time")]
+from_llsd_via_try_from!(time::OffsetDateTime);
+#[cfg(feature = "time")]
+into_llsd_via_from!(time::OffsetDateTime);
+
+#[cfg(feature = "bytes")]
+from_llsd_via_try_from!(bytes::Bytes);
+#[cfg(feature = "bytes")]
+into_llsd_via_from!(bytes::Bytes);
+
+impl IntoLlsd for str {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::String(self.to_string())
+    }
+}
+
+impl<const N: usize> IntoLlsd for [u8; N] {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Binary(self.to_vec())
+    }
+}
+
+impl FromLlsd for Llsd {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        Ok(llsd.clone())
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        Ok(llsd)
+    }
+}
+
+impl IntoLlsd for Llsd {
+    fn to_llsd(&self) -> Llsd {
+        self.clone()
+    }
+
+    fn into_llsd(self) -> Llsd {
+        self
+    }
+}
+
+impl<T: IntoLlsd + ?Sized> IntoLlsd for &T {
+    fn to_llsd(&self) -> Llsd {
+        (**self).to_llsd()
+    }
+}
+
+impl<T: FromLlsd> FromLlsd for Box<T> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        T::from_llsd(llsd).map(Box::new)
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        T::from_llsd_owned(llsd).map(Box::new)
+    }
+
+    fn update_from_llsd(&mut self, llsd: &Llsd) -> Result<(), LlsdError> {
+        (**self).update_from_llsd(llsd)
+    }
+
+    fn known_keys(keys: &mut Vec<&'static str>) {
+        T::known_keys(keys);
+    }
+}
+
+impl<T: IntoLlsd + ?Sized> IntoLlsd for Box<T> {
+    fn to_llsd(&self) -> Llsd {
+        (**self).to_llsd()
+    }
+}
+
+impl<T: FromLlsd> FromLlsd for Rc<T> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        T::from_llsd(llsd).map(Rc::new)
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        T::from_llsd_owned(llsd).map(Rc::new)
+    }
+
+    fn known_keys(keys: &mut Vec<&'static str>) {
+        T::known_keys(keys);
+    }
+}
+
+impl<T: IntoLlsd + ?Sized> IntoLlsd for Rc<T> {
+    fn to_llsd(&self) -> Llsd {
+        (**self).to_llsd()
+    }
+}
+
+impl<T: FromLlsd> FromLlsd for Arc<T> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        T::from_llsd(llsd).map(Arc::new)
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        T::from_llsd_owned(llsd).map(Arc::new)
+    }
+
+    fn known_keys(keys: &mut Vec<&'static str>) {
+        T::known_keys(keys);
+    }
+}
+
+impl<T: IntoLlsd + ?Sized> IntoLlsd for Arc<T> {
+    fn to_llsd(&self) -> Llsd {
+        (**self).to_llsd()
+    }
+}
+
+impl FromLlsd for Box<str> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        String::from_llsd(llsd).map(String::into_boxed_str)
+    }
+}
+
+impl FromLlsd for Rc<str> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        String::from_llsd(llsd).map(Rc::from)
+    }
+}
+
+impl FromLlsd for Arc<str> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        String::from_llsd(llsd).map(Arc::from)
+    }
+}
+
+/// Always reads into `Cow::Owned`, so any lifetime works (`Cow<'static, str>`).
+impl<T: ToOwned + ?Sized> FromLlsd for Cow<'_, T>
+where
+    T::Owned: FromLlsd,
+{
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        T::Owned::from_llsd(llsd).map(Cow::Owned)
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        T::Owned::from_llsd_owned(llsd).map(Cow::Owned)
+    }
+
+    fn known_keys(keys: &mut Vec<&'static str>) {
+        T::Owned::known_keys(keys);
+    }
+}
+
+impl<T: IntoLlsd + ToOwned + ?Sized> IntoLlsd for Cow<'_, T> {
+    fn to_llsd(&self) -> Llsd {
+        (**self).to_llsd()
+    }
+}
+
+/// `Undefined` reads as `None`; anything else must convert to `T`.
+impl<T: FromLlsd> FromLlsd for Option<T> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Undefined => Ok(None),
+            other => T::from_llsd(other).map(Some),
+        }
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Undefined => Ok(None),
+            other => T::from_llsd_owned(other).map(Some),
+        }
+    }
+
+    fn known_keys(keys: &mut Vec<&'static str>) {
+        T::known_keys(keys);
+    }
+}
+
+impl<T: IntoLlsd> IntoLlsd for Option<T> {
+    fn to_llsd(&self) -> Llsd {
+        self.as_ref().map_or(Llsd::Undefined, T::to_llsd)
+    }
+
+    fn into_llsd(self) -> Llsd {
+        self.map_or(Llsd::Undefined, T::into_llsd)
+    }
+}
+
+impl<T: FromLlsd> FromLlsd for Vec<T> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Array(array) => array
+                .iter()
+                .enumerate()
+                .map(|(index, item)| T::from_llsd(item).map_err(|e| e.with_index(index)))
+                .collect(),
+            _ => Err(LlsdError::invalid_type("array", llsd)),
+        }
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Array(array) => array
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| T::from_llsd_owned(item).map_err(|e| e.with_index(index)))
+                .collect(),
+            other => Err(LlsdError::invalid_type("array", &other)),
+        }
+    }
+}
+
+/// Convert every element of an LLSD array, like `Vec::<T>::from_llsd`, but
+/// report the error of every element that fails rather than only the first.
+/// Each error's path starts with its element's index.
+///
+/// ```
+/// use llsd_rs::{Llsd, convert::from_llsd_each, llsd_array};
+///
+/// let records = llsd_array![1, Llsd::Undefined, 3, llsd_array![4]];
+/// let errors = from_llsd_each::<i32>(records.as_array().unwrap()).unwrap_err();
+/// let indices: Vec<usize> = errors.iter().map(|(index, _)| *index).collect();
+/// assert_eq!(indices, [1, 3]);
+/// assert_eq!(errors[0].1.to_string(), "invalid type at [1]: expected integer, found undef");
+/// ```
+pub fn from_llsd_each<T: FromLlsd>(items: &[Llsd]) -> Result<Vec<T>, Vec<(usize, LlsdError)>> {
+    collect_each(
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (index, T::from_llsd(item))),
+    )
+}
+
+/// Consuming [`from_llsd_each`].
+pub fn from_llsd_each_owned<T: FromLlsd>(
+    items: Vec<Llsd>,
+) -> Result<Vec<T>, Vec<(usize, LlsdError)>> {
+    collect_each(
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| (index, T::from_llsd_owned(item))),
+    )
+}
+
+fn collect_each<T>(
+    results: impl Iterator<Item = (usize, Result<T, LlsdError>)>,
+) -> Result<Vec<T>, Vec<(usize, LlsdError)>> {
+    let mut values = Vec::with_capacity(results.size_hint().0);
+    let mut errors = Vec::new();
+    for (index, result) in results {
+        match result {
+            Ok(value) if errors.is_empty() => values.push(value),
+            Ok(_) => {}
+            Err(e) => errors.push((index, e.with_index(index))),
+        }
+    }
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(errors)
+    }
+}
+
+impl<T: IntoLlsd> IntoLlsd for [T] {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Array(self.iter().map(T::to_llsd).collect())
+    }
+}
+
+impl<T: IntoLlsd> IntoLlsd for Vec<T> {
+    fn to_llsd(&self) -> Llsd {
+        self.as_slice().to_llsd()
+    }
+
+    fn into_llsd(self) -> Llsd {
+        Llsd::Array(self.into_iter().map(T::into_llsd).collect())
+    }
+}
+
+impl<V: FromLlsd> FromLlsd for HashMap<String, V> {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Map(map) => map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), V::from_llsd(v).map_err(|e| e.with_key(k))?)))
+                .collect(),
+            _ => Err(LlsdError::invalid_type("map", llsd)),
+        }
+    }
+
+    fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Map(map) => map
+                .into_iter()
+                .map(|(k, v)| {
+                    let value = V::from_llsd_owned(v).map_err(|e| e.with_key(&k))?;
+                    Ok((k, value))
+                })
+                .collect(),
+            other => Err(LlsdError::invalid_type("map", &other)),
+        }
+    }
+}
+
+impl<V: IntoLlsd> IntoLlsd for HashMap<String, V> {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Map(self.iter().map(|(k, v)| (k.clone(), v.to_llsd())).collect())
+    }
+
+    fn into_llsd(self) -> Llsd {
+        Llsd::Map(self.into_iter().map(|(k, v)| (k, v.into_llsd())).collect())
+    }
+}
+
+macro_rules! impl_tuple {
+    ($len:expr => $($name:ident $index:tt),+) => {
+        impl<$($name: FromLlsd),+> FromLlsd for ($($name,)+) {
+            fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+                match llsd {
+                    Llsd::Array(a) if a.len() == $len => Ok(($(
+                        $name::from_llsd(&a[$index]).map_err(|e| e.with_index($index))?,
+                    )+)),
+                    Llsd::Array(a) => Err(LlsdError::invalid_length($len, a.len())),
+                    _ => Err(LlsdError::invalid_type("array", llsd)),
+                }
+            }
+
+            fn from_llsd_owned(llsd: Llsd) -> Result<Self, LlsdError> {
+                match llsd {
+                    Llsd::Array(a) if a.len() == $len => {
+                        let mut items = a.into_iter();
+                        Ok(($({
+                            let item = items.next().expect("length checked");
+                            $name::from_llsd_owned(item).map_err(|e| e.with_index($index))?
+                        },)+))
+                    }
+                    Llsd::Array(a) => Err(LlsdError::invalid_length($len, a.len())),
+                    other => Err(LlsdError::invalid_type("array", &other)),
+                }
+            }
+        }
+
+        impl<$($name: IntoLlsd),+> IntoLlsd for ($($name,)+) {
+            fn to_llsd(&self) -> Llsd {
+                Llsd::Array(vec![$(self.$index.to_llsd()),+])
+            }
+
+            fn into_llsd(self) -> Llsd {
+                Llsd::Array(vec![$(self.$index.into_llsd()),+])
+            }
+        }
+    };
+}
+
+impl_tuple!(2 => A 0, B 1);
+impl_tuple!(3 => A 0, B 1, C 2);
+impl_tuple!(4 => A 0, B 1, C 2, D 3);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LlsdMap;
+
+    #[test]
+    fn containers_report_nested_paths() {
+        let llsd = Llsd::Map(LlsdMap::from([(
+            "items".to_string(),
+            Llsd::Array(vec![Llsd::Integer(1), Llsd::Map(LlsdMap::new())]),
+        )]));
+        let err = HashMap::<String, Vec<i32>>::from_llsd(&llsd).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type at items[1]: expected integer, found map"
+        );
+    }
+
+    #[test]
+    fn converts_each_element() {
+        let items = vec![
+            Llsd::Integer(1),
+            Llsd::Undefined,
+            Llsd::Integer(3),
+            Llsd::Array(vec![Llsd::Integer(4)]),
+        ];
+        let errors = from_llsd_each::<i32>(&items).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, 1);
+        assert_eq!(errors[1].0, 3);
+        assert_eq!(errors[1].1.path_string(), "[3]");
+
+        let errors = from_llsd_each_owned::<Vec<String>>(vec![
+            Llsd::Array(vec![Llsd::String("a".into())]),
+            Llsd::Array(vec![Llsd::String("b".into()), Llsd::Map(LlsdMap::new())]),
+        ])
+        .unwrap_err();
+        assert_eq!(errors[0].0, 1);
+        assert_eq!(errors[0].1.path_string(), "[1][1]");
+
+        assert_eq!(from_llsd_each::<i32>(&items[..1]).unwrap(), [1]);
+        assert_eq!(
+            from_llsd_each_owned::<i32>(vec![Llsd::Integer(1), Llsd::Integer(2)]).unwrap(),
+            [1, 2]
+        );
+        assert!(from_llsd_each::<i32>(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn round_trips_through_both_traits() {
+        let value = (1i32, vec!["a".to_string()], Some(2.5f64));
+        let llsd = value.to_llsd();
+        assert_eq!(
+            <(i32, Vec<String>, Option<f64>)>::from_llsd_owned(llsd).unwrap(),
+            value
+        );
+        assert_eq!(None::<i32>.into_llsd(), Llsd::Undefined);
+        assert_eq!(Option::<i32>::from_llsd(&Llsd::Undefined).unwrap(), None);
+    }
+}
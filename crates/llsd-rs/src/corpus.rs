@@ -0,0 +1,513 @@
+//! Seed corpora of valid documents for fuzzing and load testing.
+//!
+//! Random bytes rarely get past a parser's first few checks. [`Generator`]
+//! instead builds random but well-formed values, nested and sized per
+//! [`CorpusOptions`], with the edge cases parsers trip on (extreme integers,
+//! non-finite reals, escapes and non-ASCII text in strings and keys, empty
+//! containers) mixed in, and [`generate`] encodes each in every format.
+//! Output is a function of the seed, so a corpus can be regenerated rather
+//! than checked in.
+//!
+//! ```
+//! use llsd_rs::corpus::{self, CorpusFormat, CorpusOptions};
+//!
+//! let options = CorpusOptions::new(7).with_max_depth(4);
+//! for doc in corpus::generate(&options, 10).unwrap() {
+//!     // Headerless binary is the one encoding autodetection can't tell.
+//!     if doc.format != CorpusFormat::Binary {
+//!         llsd_rs::autodetect::from_slice(&doc.bytes).unwrap();
+//!     }
+//! }
+//! ```
+
+use std::path::Path;
+
+use crate::Uuid;
+
+use crate::{Date, Llsd, LlsdMap, Uri, notation::FormatterContext};
+
+/// How often each kind of value is picked, relative to the others. Zero
+/// weights leave a kind out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeWeights {
+    pub undefined: u32,
+    pub boolean: u32,
+    pub integer: u32,
+    pub real: u32,
+    pub string: u32,
+    pub uuid: u32,
+    pub uri: u32,
+    pub date: u32,
+    pub binary: u32,
+    pub array: u32,
+    pub map: u32,
+}
+
+impl Default for TypeWeights {
+    /// Every kind; containers and strings a little more often, as in real
+    /// traffic.
+    fn default() -> Self {
+        Self {
+            undefined: 1,
+            boolean: 2,
+            integer: 3,
+            real: 2,
+            string: 4,
+            uuid: 2,
+            uri: 1,
+            date: 1,
+            binary: 2,
+            array: 3,
+            map: 4,
+        }
+    }
+}
+
+/// What [`Generator`] builds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusOptions {
+    seed: u64,
+    max_depth: usize,
+    max_items: usize,
+    max_text_len: usize,
+    edge_cases: bool,
+    weights: TypeWeights,
+}
+
+impl CorpusOptions {
+    /// Depth 6, up to 8 items per container, strings and binaries up to 32
+    /// bytes, edge cases on.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            max_depth: 6,
+            max_items: 8,
+            max_text_len: 32,
+            edge_cases: true,
+            weights: TypeWeights::default(),
+        }
+    }
+
+    /// Nest containers at most `max_depth` levels; the top-level value
+    /// counts as one.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// The longest string, key or binary, in bytes.
+    pub fn with_max_text_len(mut self, max_text_len: usize) -> Self {
+        self.max_text_len = max_text_len;
+        self
+    }
+
+    /// Mix in boundary values (`i32::MIN`, NaN, escapes...) among the
+    /// random ones.
+    pub fn with_edge_cases(mut self, edge_cases: bool) -> Self {
+        self.edge_cases = edge_cases;
+        self
+    }
+
+    pub fn with_weights(mut self, weights: TypeWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+}
+
+impl Default for CorpusOptions {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// A seeded source of random values.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    state: u64,
+    options: CorpusOptions,
+}
+
+/// Text pieces that exercise escaping and decoding paths. Only what XML
+/// can carry unchanged: no NUL or other C0 controls, no bare carriage return.
+const EDGE_TEXT: &[&str] = &[
+    "",
+    "'",
+    "\"",
+    "\\",
+    "\n\t",
+    "<&>",
+    "]]>",
+    "é",
+    "日本語",
+    "🦀",
+    "\u{7f}",
+];
+
+impl Generator {
+    pub fn new(options: CorpusOptions) -> Self {
+        Self {
+            state: options.seed,
+            options,
+        }
+    }
+
+    /// splitmix64.
+    pub(crate) fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`, or 0 when `n` is 0.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// Whether to use an edge case this time (one in eight).
+    fn edge(&mut self) -> bool {
+        self.options.edge_cases && self.below(8) == 0
+    }
+
+    /// A random value.
+    pub fn value(&mut self) -> Llsd {
+        self.value_at(self.options.max_depth)
+    }
+
+    fn value_at(&mut self, depth: usize) -> Llsd {
+        let w = self.options.weights;
+        let containers = depth > 1;
+        let kinds = [
+            w.undefined,
+            w.boolean,
+            w.integer,
+            w.real,
+            w.string,
+            w.uuid,
+            w.uri,
+            w.date,
+            w.binary,
+            if containers { w.array } else { 0 },
+            if containers { w.map } else { 0 },
+        ];
+        let total: u32 = kinds.iter().sum();
+        if total == 0 {
+            return Llsd::Undefined;
+        }
+        let mut pick = self.below(total as usize) as u32;
+        let kind = kinds
+            .iter()
+            .position(|&weight| {
+                if pick < weight {
+                    true
+                } else {
+                    pick -= weight;
+                    false
+                }
+            })
+            .unwrap_or(0);
+        match kind {
+            0 => Llsd::Undefined,
+            1 => Llsd::Boolean(self.below(2) == 1),
+            2 => Llsd::Integer(self.integer()),
+            3 => Llsd::Real(self.real()),
+            4 => Llsd::String(self.text()),
+            5 => Llsd::Uuid(self.uuid()),
+            6 => Llsd::Uri(self.uri()),
+            7 => Llsd::Date(self.date()),
+            8 => Llsd::Binary(self.binary()),
+            9 => {
+                let len = self.items();
+                Llsd::Array((0..len).map(|_| self.value_at(depth - 1)).collect())
+            }
+            _ => {
+                let len = self.items();
+                let mut map = LlsdMap::new();
+                for _ in 0..len {
+                    let key = self.text();
+                    let value = self.value_at(depth - 1);
+                    map.insert(key, value);
+                }
+                Llsd::Map(map)
+            }
+        }
+    }
+
+    /// A container length.
+    pub(crate) fn items(&mut self) -> usize {
+        self.below(self.options.max_items + 1)
+    }
+
+    pub(crate) fn integer(&mut self) -> i32 {
+        if self.edge() {
+            [0, 1, -1, i32::MIN, i32::MAX][self.below(5)]
+        } else {
+            self.next() as i32 >> self.below(31)
+        }
+    }
+
+    pub(crate) fn real(&mut self) -> f64 {
+        if self.edge() {
+            let edges = [
+                0.0,
+                -0.0,
+                f64::NAN,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::MIN_POSITIVE,
+                f64::MAX,
+                5e-324,
+            ];
+            edges[self.below(edges.len())]
+        } else {
+            (self.next() as i64 as f64) / (1u64 << self.below(60)) as f64
+        }
+    }
+
+    pub(crate) fn text(&mut self) -> String {
+        let max = self.options.max_text_len;
+        if self.edge() {
+            let edge = EDGE_TEXT[self.below(EDGE_TEXT.len())];
+            if edge.len() <= max {
+                return edge.to_string();
+            }
+        }
+        let len = self.below(max + 1);
+        let mut text = String::with_capacity(len);
+        while text.len() < len {
+            let c = match self.below(16) {
+                0 => EDGE_TEXT[self.below(EDGE_TEXT.len())].chars().next(),
+                1 => char::from_u32(0xa0 + self.below(0x2000) as u32),
+                _ => Some(char::from(b' ' + self.below(95) as u8)),
+            };
+            if let Some(c) = c
+                && text.len() + c.len_utf8() <= len
+            {
+                text.push(c);
+            } else {
+                break;
+            }
+        }
+        text
+    }
+
+    /// Whole seconds between 1970 and 2100: every format keeps those exactly.
+    pub(crate) fn date(&mut self) -> Date {
+        let seconds = if self.edge() {
+            0
+        } else {
+            self.below(4_102_444_800) as i64
+        };
+        Date::from_timestamp(seconds, 0).unwrap_or_default()
+    }
+
+    pub(crate) fn uuid(&mut self) -> Uuid {
+        if self.edge() {
+            Uuid::nil()
+        } else {
+            Uuid::from_u64_pair(self.next(), self.next())
+        }
+    }
+
+    pub(crate) fn uri(&mut self) -> Uri {
+        Uri::parse(&format!(
+            "https://grid.example/cap/{}?q={}",
+            self.below(1000),
+            self.below(10)
+        ))
+    }
+
+    pub(crate) fn binary(&mut self) -> Vec<u8> {
+        let len = self.below(self.options.max_text_len + 1);
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Llsd;
+
+    fn next(&mut self) -> Option<Llsd> {
+        Some(self.value())
+    }
+}
+
+/// An encoding a corpus document is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CorpusFormat {
+    /// Binary without the `<? LLSD/Binary ?>` header.
+    Binary,
+    /// Binary after the header, as autodetection sees it.
+    BinaryWithHeader,
+    Notation,
+    /// Notation with the viewer's pretty-printing.
+    NotationPretty,
+    Xml,
+}
+
+impl CorpusFormat {
+    pub const ALL: [CorpusFormat; 5] = [
+        CorpusFormat::Binary,
+        CorpusFormat::BinaryWithHeader,
+        CorpusFormat::Notation,
+        CorpusFormat::NotationPretty,
+        CorpusFormat::Xml,
+    ];
+
+    /// The directory name [`write_dir`] uses.
+    pub fn name(self) -> &'static str {
+        match self {
+            CorpusFormat::Binary => "binary",
+            CorpusFormat::BinaryWithHeader => "binary-header",
+            CorpusFormat::Notation => "notation",
+            CorpusFormat::NotationPretty => "notation-pretty",
+            CorpusFormat::Xml => "xml",
+        }
+    }
+
+    pub fn encode(self, llsd: &Llsd) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(match self {
+            CorpusFormat::Binary => crate::binary::to_vec(llsd)?,
+            CorpusFormat::BinaryWithHeader => {
+                let mut out = b"<? LLSD/Binary ?>\n".to_vec();
+                crate::binary::write(llsd, &mut out)?;
+                out
+            }
+            CorpusFormat::Notation => crate::notation::to_vec(llsd, &FormatterContext::new())?,
+            CorpusFormat::NotationPretty => {
+                crate::notation::to_vec(llsd, &FormatterContext::new().with_pretty(true))?
+            }
+            CorpusFormat::Xml => crate::xml::to_string(llsd)?.into_bytes(),
+        })
+    }
+}
+
+/// One encoded document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusDoc {
+    /// Which generated value (0-based) this encodes.
+    pub index: usize,
+    pub format: CorpusFormat,
+    pub bytes: Vec<u8>,
+}
+
+/// `count` values from `options`, each encoded in every [`CorpusFormat`].
+pub fn generate(options: &CorpusOptions, count: usize) -> Result<Vec<CorpusDoc>, anyhow::Error> {
+    let mut docs = Vec::with_capacity(count * CorpusFormat::ALL.len());
+    for (index, llsd) in Generator::new(options.clone()).take(count).enumerate() {
+        for format in CorpusFormat::ALL {
+            docs.push(CorpusDoc {
+                index,
+                format,
+                bytes: format.encode(&llsd)?,
+            });
+        }
+    }
+    Ok(docs)
+}
+
+/// Write [`generate`]'s documents under `dir`, one subdirectory per format
+/// (`dir/xml/00001`...), the layout fuzzers take seed directories in.
+pub fn write_dir(
+    dir: impl AsRef<Path>,
+    options: &CorpusOptions,
+    count: usize,
+) -> Result<(), anyhow::Error> {
+    let dir = dir.as_ref();
+    for doc in generate(options, count)? {
+        let format_dir = dir.join(doc.format.name());
+        std::fs::create_dir_all(&format_dir)?;
+        std::fs::write(format_dir.join(format!("{:05}", doc.index)), &doc.bytes)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(llsd: &Llsd) -> usize {
+        match llsd {
+            Llsd::Array(items) => 1 + items.iter().map(depth).max().unwrap_or(0),
+            Llsd::Map(map) => 1 + map.values().map(depth).max().unwrap_or(0),
+            _ => 1,
+        }
+    }
+
+    #[test]
+    fn deterministic_and_bounded() {
+        let options = CorpusOptions::new(42).with_max_depth(3).with_max_items(4);
+        let first: Vec<_> = Generator::new(options.clone()).take(50).collect();
+        let again: Vec<_> = Generator::new(options.clone()).take(50).collect();
+        assert_eq!(
+            crate::binary::to_vec(&Llsd::Array(first.clone())).unwrap(),
+            crate::binary::to_vec(&Llsd::Array(again)).unwrap()
+        );
+        assert!(first.iter().all(|llsd| depth(llsd) <= 3));
+        assert!(first.iter().any(|llsd| depth(llsd) == 3));
+
+        let only_maps = TypeWeights {
+            map: 1,
+            integer: 1,
+            ..TypeWeights {
+                undefined: 0,
+                boolean: 0,
+                integer: 0,
+                real: 0,
+                string: 0,
+                uuid: 0,
+                uri: 0,
+                date: 0,
+                binary: 0,
+                array: 0,
+                map: 0,
+            }
+        };
+        let mut generator = Generator::new(CorpusOptions::new(1).with_weights(only_maps));
+        for _ in 0..20 {
+            assert!(matches!(generator.value(), Llsd::Map(_) | Llsd::Integer(_)));
+        }
+    }
+
+    #[test]
+    fn every_document_parses_back() {
+        let options = CorpusOptions::new(1437);
+        let values: Vec<_> = Generator::new(options.clone()).take(40).collect();
+        for doc in generate(&options, values.len()).unwrap() {
+            let parsed = match doc.format {
+                CorpusFormat::Binary => crate::binary::from_slice(&doc.bytes),
+                _ => crate::autodetect::from_slice(&doc.bytes),
+            }
+            .unwrap_or_else(|e| panic!("{:?} {}: {e}", doc.format, doc.index));
+            // Compared as binary so NaN matches itself.
+            assert_eq!(
+                crate::binary::to_vec(&parsed).unwrap(),
+                crate::binary::to_vec(&values[doc.index]).unwrap(),
+                "{:?} {}",
+                doc.format,
+                doc.index
+            );
+        }
+    }
+
+    #[test]
+    fn writes_one_directory_per_format() {
+        let dir = std::env::temp_dir().join(format!("llsd-rs-corpus-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        write_dir(&dir, &CorpusOptions::new(3), 2).unwrap();
+        for format in CorpusFormat::ALL {
+            let files = std::fs::read_dir(dir.join(format.name())).unwrap().count();
+            assert_eq!(files, 2, "{}", format.name());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,450 @@
+//! The representation behind [`Llsd::Date`](crate::Llsd::Date).
+//!
+//! With the default `chrono` feature, [`Date`] is chrono's `DateTime<Utc>`.
+//! Without it, it is [`Timestamp`]: seconds and nanoseconds since the Unix
+//! epoch, with the subset of chrono's methods the codecs use
+//! (`from_timestamp`, `timestamp`, `timestamp_subsec_nanos`, `to_rfc3339`...),
+//! so code written against that subset builds either way. Both cover
+//! chrono's range of years, -262143 to 262142.
+//!
+//! ```
+//! use llsd_rs::{Llsd, date::Date};
+//!
+//! let date = Date::from_timestamp(1_000_000_000, 500_000_000).unwrap();
+//! assert_eq!(date.to_rfc3339(), "2001-09-09T01:46:40.500+00:00");
+//! assert_eq!(Llsd::from(date).as_date().unwrap().timestamp(), 1_000_000_000);
+//! ```
+//!
+//! The `time` feature converts `time::OffsetDateTime` to and from LLSD dates
+//! with either representation.
+
+use std::{fmt, str::FromStr};
+
+/// The value of an LLSD date.
+#[cfg(feature = "chrono")]
+pub type Date = chrono::DateTime<chrono::Utc>;
+/// The value of an LLSD date.
+#[cfg(not(feature = "chrono"))]
+pub type Date = Timestamp;
+
+/// Why a date string didn't parse.
+#[cfg(feature = "chrono")]
+pub type ParseError = chrono::ParseError;
+/// Why a date string didn't parse.
+#[cfg(not(feature = "chrono"))]
+pub type ParseError = InvalidDate;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+/// -262143-01-01T00:00:00Z, chrono's earliest date.
+const MIN_SECS: i64 = -8_334_601_228_800;
+/// +262142-12-31T23:59:59Z, chrono's latest.
+const MAX_SECS: i64 = 8_210_266_876_799;
+
+/// A UTC instant as seconds and nanoseconds since the Unix epoch; the
+/// [`Date`] of builds without chrono. Displays (and parses) as RFC 3339.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timestamp {
+    secs: i64,
+    nanos: u32,
+}
+
+impl Timestamp {
+    pub const UNIX_EPOCH: Self = Self { secs: 0, nanos: 0 };
+
+    /// `None` if `nsecs` is a second or more or the instant is out of range.
+    pub const fn from_timestamp(secs: i64, nsecs: u32) -> Option<Self> {
+        if nsecs >= NANOS_PER_SEC || secs < MIN_SECS || secs > MAX_SECS {
+            return None;
+        }
+        Some(Self { secs, nanos: nsecs })
+    }
+
+    pub const fn from_timestamp_millis(millis: i64) -> Option<Self> {
+        Self::from_timestamp(
+            millis.div_euclid(1_000),
+            millis.rem_euclid(1_000) as u32 * 1_000_000,
+        )
+    }
+
+    pub const fn from_timestamp_micros(micros: i64) -> Option<Self> {
+        Self::from_timestamp(
+            micros.div_euclid(1_000_000),
+            micros.rem_euclid(1_000_000) as u32 * 1_000,
+        )
+    }
+
+    /// Whole seconds since the epoch, rounded down.
+    pub const fn timestamp(&self) -> i64 {
+        self.secs
+    }
+
+    pub const fn timestamp_subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    pub const fn timestamp_millis(&self) -> i64 {
+        self.secs * 1_000 + (self.nanos / 1_000_000) as i64
+    }
+
+    pub const fn timestamp_micros(&self) -> i64 {
+        self.secs * 1_000_000 + (self.nanos / 1_000) as i64
+    }
+
+    /// Like chrono's: `+00:00` for the offset, and as many groups of three
+    /// fractional digits as the nanoseconds need.
+    pub fn to_rfc3339(&self) -> String {
+        self.to_string()
+    }
+
+    /// Read an RFC 3339 date, with `T`, `t` or a space between date and time
+    /// and any offset.
+    pub fn parse_from_rfc3339(text: &str) -> Result<Self, InvalidDate> {
+        Rfc3339Parser {
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+        .parse()
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.secs.div_euclid(86_400));
+        let seconds = self.secs.rem_euclid(86_400);
+        if (0..=9999).contains(&year) {
+            write!(f, "{year:04}")?;
+        } else {
+            write!(f, "{year:+05}")?;
+        }
+        write!(
+            f,
+            "-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+        match self.nanos {
+            0 => {}
+            n if n % 1_000_000 == 0 => write!(f, ".{:03}", n / 1_000_000)?,
+            n if n % 1_000 == 0 => write!(f, ".{:06}", n / 1_000)?,
+            n => write!(f, ".{n:09}")?,
+        }
+        f.write_str("+00:00")
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = InvalidDate;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse_from_rfc3339(text)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(t: Timestamp) -> Self {
+        Self::from_timestamp(t.secs, t.nanos).expect("timestamps stay in chrono's range")
+    }
+}
+
+/// Leap seconds fold into the second before.
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn from(d: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            secs: d.timestamp(),
+            nanos: d.timestamp_subsec_nanos().min(NANOS_PER_SEC - 1),
+        }
+    }
+}
+
+/// A date string [`Timestamp`] couldn't read; the messages match chrono's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidDate(&'static str);
+
+impl InvalidDate {
+    const TOO_SHORT: Self = Self("premature end of input");
+    const INVALID: Self = Self("input contains invalid characters");
+    const TOO_LONG: Self = Self("trailing input");
+    const OUT_OF_RANGE: Self = Self("input is out of range");
+}
+
+struct Rfc3339Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Rfc3339Parser<'_> {
+    fn parse(mut self) -> Result<Timestamp, InvalidDate> {
+        let year = self.digits(4)?;
+        self.expect(b"-")?;
+        let month = self.digits(2)?;
+        self.expect(b"-")?;
+        let day = self.digits(2)?;
+        self.expect(b"Tt ")?;
+        let hour = self.digits(2)?;
+        self.expect(b":")?;
+        let minute = self.digits(2)?;
+        self.expect(b":")?;
+        let second = self.digits(2)?;
+        let mut nanos = 0;
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            let start = self.pos;
+            while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                let digit = u32::from(self.bytes[self.pos] - b'0');
+                if self.pos - start < 9 {
+                    nanos += digit * 10u32.pow(8 - (self.pos - start) as u32);
+                }
+                self.pos += 1;
+            }
+            if self.pos == start {
+                return Err(self.missing());
+            }
+        }
+        let offset = match self.bytes.get(self.pos) {
+            Some(b'Z' | b'z') => {
+                self.pos += 1;
+                0
+            }
+            Some(&sign @ (b'+' | b'-')) => {
+                self.pos += 1;
+                let hours = self.digits(2)?;
+                self.expect(b":")?;
+                let minutes = self.digits(2)?;
+                if hours > 23 || minutes > 59 {
+                    return Err(InvalidDate::OUT_OF_RANGE);
+                }
+                let offset = (hours * 3600 + minutes * 60) as i64;
+                if sign == b'-' { -offset } else { offset }
+            }
+            _ => return Err(self.missing()),
+        };
+        if self.pos < self.bytes.len() {
+            return Err(InvalidDate::TOO_LONG);
+        }
+        if !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(InvalidDate::OUT_OF_RANGE);
+        }
+        let days = days_from_civil(year, month, day);
+        let secs = days * 86_400 + (hour * 3600 + minute * 60 + second) as i64 - offset;
+        Timestamp::from_timestamp(secs, nanos).ok_or(InvalidDate::OUT_OF_RANGE)
+    }
+
+    fn missing(&self) -> InvalidDate {
+        if self.pos < self.bytes.len() {
+            InvalidDate::INVALID
+        } else {
+            InvalidDate::TOO_SHORT
+        }
+    }
+
+    fn digits(&mut self, count: usize) -> Result<u32, InvalidDate> {
+        let mut value = 0;
+        for _ in 0..count {
+            match self.bytes.get(self.pos) {
+                Some(b) if b.is_ascii_digit() => value = value * 10 + u32::from(b - b'0'),
+                _ => return Err(self.missing()),
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn expect(&mut self, any_of: &[u8]) -> Result<(), InvalidDate> {
+        match self.bytes.get(self.pos) {
+            Some(b) if any_of.contains(b) => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.missing()),
+        }
+    }
+}
+
+fn is_leap_year(year: u32) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: u32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of [`days_from_civil`], for any year.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parse an RFC 3339 date into a [`Date`], whatever its offset.
+pub(crate) fn parse_rfc3339(text: &str) -> Result<Date, ParseError> {
+    #[cfg(feature = "chrono")]
+    return chrono::DateTime::parse_from_rfc3339(text).map(|date| date.to_utc());
+    #[cfg(not(feature = "chrono"))]
+    return Timestamp::parse_from_rfc3339(text);
+}
+
+/// Displays a [`Date`] as RFC 3339, without the allocation of `to_rfc3339`.
+pub(crate) struct Rfc3339<'a>(pub &'a Date);
+
+impl fmt::Display for Rfc3339<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `%+` is `to_rfc3339`'s format.
+        #[cfg(feature = "chrono")]
+        return write!(f, "{}", self.0.format("%+"));
+        #[cfg(not(feature = "chrono"))]
+        return write!(f, "{}", self.0);
+    }
+}
+
+/// `time`'s default range, years -9999 to 9999, is inside [`Date`]'s.
+#[cfg(feature = "time")]
+pub(crate) fn from_offset_date_time(date: time::OffsetDateTime) -> Date {
+    Date::from_timestamp(date.unix_timestamp(), date.nanosecond())
+        .expect("time's range is inside chrono's")
+}
+
+#[cfg(feature = "time")]
+pub(crate) fn to_offset_date_time(date: &Date) -> Option<time::OffsetDateTime> {
+    let nanos = i128::from(date.timestamp()) * i128::from(NANOS_PER_SEC)
+        + i128::from(date.timestamp_subsec_nanos().min(NANOS_PER_SEC - 1));
+    time::OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Timestamp, InvalidDate> {
+        Timestamp::parse_from_rfc3339(text)
+    }
+
+    #[test]
+    fn formats_like_chrono() {
+        let at = |secs, nanos| Timestamp::from_timestamp(secs, nanos).unwrap().to_rfc3339();
+        assert_eq!(at(0, 0), "1970-01-01T00:00:00+00:00");
+        assert_eq!(at(1_620_000_000, 0), "2021-05-03T00:00:00+00:00");
+        assert_eq!(
+            at(1_700_000_000, 123_000_000),
+            "2023-11-14T22:13:20.123+00:00"
+        );
+        assert_eq!(
+            at(1_700_000_000, 123_456_000),
+            "2023-11-14T22:13:20.123456+00:00"
+        );
+        assert_eq!(at(-2, 500_000_001), "1969-12-31T23:59:58.500000001+00:00");
+        assert_eq!(at(951_782_400, 0), "2000-02-29T00:00:00+00:00");
+        assert_eq!(at(MAX_SECS, 0), "+262142-12-31T23:59:59+00:00");
+        assert_eq!(at(MIN_SECS, 0), "-262143-01-01T00:00:00+00:00");
+        assert!(Timestamp::from_timestamp(MAX_SECS + 1, 0).is_none());
+        assert!(Timestamp::from_timestamp(0, NANOS_PER_SEC).is_none());
+        assert_eq!(
+            Timestamp::from_timestamp_micros(-1).unwrap(),
+            Timestamp::from_timestamp(-1, 999_999_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_offsets_and_fractions() {
+        let expected = Timestamp::from_timestamp(1_000_000_000, 500_000_000).unwrap();
+        assert_eq!(parse("2001-09-09T01:46:40.5Z"), Ok(expected));
+        assert_eq!(parse("2001-09-09t03:46:40.500+02:00"), Ok(expected));
+        assert_eq!(parse("2001-09-08 19:46:40.5000000009-06:00"), Ok(expected));
+        assert_eq!(parse(&expected.to_rfc3339()), Ok(expected));
+        assert_eq!(parse("2001-09-09"), Err(InvalidDate::TOO_SHORT));
+        assert_eq!(parse("2001-09-09T01:46:40"), Err(InvalidDate::TOO_SHORT));
+        assert_eq!(parse("2001-09-09X01:46:40Z"), Err(InvalidDate::INVALID));
+        assert_eq!(
+            parse("2001-09-09T01:46:40Zjunk"),
+            Err(InvalidDate::TOO_LONG)
+        );
+        assert_eq!(
+            parse("2001-02-29T00:00:00Z"),
+            Err(InvalidDate::OUT_OF_RANGE)
+        );
+        assert_eq!(
+            parse("2001-09-09T24:00:00Z"),
+            Err(InvalidDate::OUT_OF_RANGE)
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn converts_time() {
+        use crate::{FromLlsd, IntoLlsd, Llsd};
+
+        let time = time::OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_789)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap());
+        let llsd = time.to_llsd();
+        assert_eq!(
+            llsd,
+            Llsd::Date(Date::from_timestamp(1_700_000_000, 123_456_789).unwrap())
+        );
+        assert_eq!(time::OffsetDateTime::from_llsd(&llsd).unwrap(), time);
+        let text = Llsd::from("2023-11-14T22:13:20Z");
+        assert_eq!(
+            time::OffsetDateTime::try_from(&text)
+                .unwrap()
+                .unix_timestamp(),
+            1_700_000_000
+        );
+        let far = Llsd::Date(Date::from_timestamp(MAX_SECS, 0).unwrap());
+        assert!(time::OffsetDateTime::try_from(far).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn agrees_with_chrono() {
+        for (secs, nanos) in [
+            (0, 0),
+            (1_700_000_000, 123_456_789),
+            (-86_401, 1),
+            (MIN_SECS, 0),
+            (MAX_SECS, 999_999_999),
+        ] {
+            let timestamp = Timestamp::from_timestamp(secs, nanos).unwrap();
+            let date = chrono::DateTime::from_timestamp(secs, nanos).unwrap();
+            assert_eq!(timestamp.to_rfc3339(), date.to_rfc3339());
+            assert_eq!(Timestamp::from(date), timestamp);
+            assert_eq!(Date::from(timestamp), date);
+        }
+        // chrono reads only four-digit years.
+        let date = chrono::DateTime::from_timestamp(-86_401, 1).unwrap();
+        assert_eq!(parse_rfc3339(&date.to_rfc3339()).unwrap(), date);
+    }
+}
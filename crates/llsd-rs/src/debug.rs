@@ -0,0 +1,172 @@
+//! Bounded `Debug` output for values that may be huge.
+
+use std::fmt;
+
+use crate::Llsd;
+
+/// Characters of a string shown before it is cut.
+const TEXT_LIMIT: usize = 128;
+
+/// Container levels of a value quoted in an error message.
+pub(crate) const ERROR_DEPTH: usize = 3;
+/// Items per container of a value quoted in an error message.
+pub(crate) const ERROR_ITEMS: usize = 8;
+
+/// `Debug` and `Display` of an [`Llsd`] that stop at a nesting depth and a
+/// number of items per container (bytes, for binaries), marking what was
+/// left out with `…(+N more)`; strings are cut after 128 characters.
+/// Returned by [`Llsd::debug_limited`].
+///
+/// ```
+/// use llsd_rs::Llsd;
+///
+/// let llsd = Llsd::Array((0..1000).map(Llsd::Integer).collect());
+/// assert_eq!(
+///     llsd.debug_limited(2, 2).to_string(),
+///     "Array([Integer(0), Integer(1), …(+998 more)])"
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct DebugLimited<'a> {
+    llsd: &'a Llsd,
+    depth: usize,
+    max_items: usize,
+}
+
+impl<'a> DebugLimited<'a> {
+    pub(crate) fn new(llsd: &'a Llsd, depth: usize, max_items: usize) -> Self {
+        Self {
+            llsd,
+            depth,
+            max_items,
+        }
+    }
+
+    fn child(&self, llsd: &'a Llsd) -> Self {
+        Self::new(llsd, self.depth.saturating_sub(1), self.max_items)
+    }
+}
+
+/// The marker for items left out.
+struct More(usize);
+
+impl fmt::Debug for More {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "…(+{} more)", self.0)
+    }
+}
+
+/// A map entry, as `debug_map` writes one.
+struct Entry<'a>(&'a str, DebugLimited<'a>);
+
+impl fmt::Debug for Entry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: ", self.0)?;
+        fmt::Debug::fmt(&self.1, f)
+    }
+}
+
+impl fmt::Debug for DebugLimited<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = |len: usize| {
+            if self.depth == 0 {
+                0
+            } else {
+                len.min(self.max_items)
+            }
+        };
+        match self.llsd {
+            Llsd::String(s) => match s.char_indices().nth(TEXT_LIMIT) {
+                Some((cut, _)) => {
+                    let rest = s[cut..].chars().count();
+                    write!(f, "String({:?}{:?})", &s[..cut], More(rest))
+                }
+                None => fmt::Debug::fmt(self.llsd, f),
+            },
+            Llsd::Binary(b) => {
+                let n = b.len().min(self.max_items);
+                f.write_str("Binary(")?;
+                let mut list = f.debug_list();
+                list.entries(&b[..n]);
+                if n < b.len() {
+                    list.entry(&More(b.len() - n));
+                }
+                list.finish()?;
+                f.write_str(")")
+            }
+            Llsd::Array(items) => {
+                let n = shown(items.len());
+                f.write_str("Array(")?;
+                let mut list = f.debug_list();
+                list.entries(items[..n].iter().map(|item| self.child(item)));
+                if n < items.len() {
+                    list.entry(&More(items.len() - n));
+                }
+                list.finish()?;
+                f.write_str(")")
+            }
+            Llsd::Map(map) => {
+                let n = shown(map.len());
+                f.write_str("Map(")?;
+                let mut entries = f.debug_set();
+                for (key, value) in map.iter().take(n) {
+                    entries.entry(&Entry(key, self.child(value)));
+                }
+                if n < map.len() {
+                    entries.entry(&More(map.len() - n));
+                }
+                entries.finish()?;
+                f.write_str(")")
+            }
+            scalar => fmt::Debug::fmt(scalar, f),
+        }
+    }
+}
+
+impl fmt::Display for DebugLimited<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_depth_items_and_text() {
+        let nested = Llsd::Array(vec![Llsd::Array(vec![Llsd::Array(vec![Llsd::Integer(1)])])]);
+        assert_eq!(
+            nested.debug_limited(2, 4).to_string(),
+            "Array([Array([Array([…(+1 more)])])])"
+        );
+        assert_eq!(
+            nested.debug_limited(3, 4).to_string(),
+            format!("{nested:?}")
+        );
+
+        let map = Llsd::map()
+            .insert("blob", Llsd::Binary(vec![7; 100]))
+            .unwrap()
+            .insert("text", "x".repeat(TEXT_LIMIT + 5))
+            .unwrap();
+        let shown = map.debug_limited(1, 3).to_string();
+        assert!(
+            shown.contains("\"blob\": Binary([7, 7, 7, …(+97 more)])"),
+            "{shown}"
+        );
+        assert!(
+            shown.contains(&format!(
+                "\"text\": String(\"{}\"…(+5 more))",
+                "x".repeat(TEXT_LIMIT)
+            )),
+            "{shown}"
+        );
+        assert_eq!(map.debug_limited(0, 3).to_string(), "Map({…(+2 more)})");
+
+        let big = Llsd::Map((0..10).map(|i| (i.to_string(), Llsd::Integer(i))).collect());
+        let pretty = format!("{:#?}", big.debug_limited(1, 1));
+        assert!(pretty.starts_with("Map({\n    \""), "{pretty}");
+        assert!(pretty.ends_with(",\n    …(+9 more),\n})"), "{pretty}");
+    }
+}
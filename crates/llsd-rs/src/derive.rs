@@ -31,21 +31,733 @@
 //! ```
 //!
 //! Supported (currently implemented) attributes:
-//! - `#[llsd(rename = "fieldName")]`
+//! - `#[llsd(rename = "fieldName")]`; any string works as a key. Raw identifier fields (`r#type`)
+//!   use the name without the `r#` prefix
 //! - `#[llsd(rename_all = "case")]` on the container: snake_case | kebab-case | camelCase | PascalCase | SCREAMING_SNAKE_CASE
+//! - `#[llsd(rename(serialize = "a", deserialize = "b"))]` and `#[llsd(rename_all(serialize = "..", deserialize = ".."))]`
+//!   set the key written by `LlsdInto` and read by `LlsdFrom` independently; either side may be omitted
 //! - `#[llsd(default)]` or `#[llsd(default = "path::to_fn")]`
+//! - `#[llsd(with = module)]` converts the field with `module::serialize(&T) -> Llsd` and
+//!   `module::deserialize(&Llsd) -> anyhow::Result<T>`, as `int_as` and `date_format` do
 //! - `#[llsd(skip)]`, `#[llsd(skip_serializing)]`, `#[llsd(skip_deserializing)]`
-//! - `#[llsd(flatten)]` (experimental; simple merge of nested map fields)
+//! - `#[llsd(serialize_only)]` (written, never read; its key counts as unknown input under
+//!   `deny_unknown_fields`) and `#[llsd(deserialize_only)]` (read, never written), e.g. for
+//!   server-assigned fields like `id` or `created_at`
+//! - `#[llsd(path = "/agent/id")]` reads a field from (and writes it to) a nested map location,
+//!   creating intermediate maps on output; errors name the full path (`agent.id`)
+//! - `#[llsd(flatten)]` merges a nested struct's keys into the parent map; works through
+//!   several levels, and on `Option<T>` (nothing is written for `None`; reading yields `Some`
+//!   only when at least one of `T`'s keys is present)
 //! - `#[llsd(deny_unknown_fields)]`
+//! - `#[llsd(from = "Wire")]` / `#[llsd(into = "Wire")]` on the container: convert through an
+//!   intermediate type with its own derive (`Self: From<Wire>` for reading, `Self: Clone + Into<Wire>`
+//!   for writing), keeping wire-format quirks out of domain types; the container may then be any type
+//! - `#[llsd(as_array)]` on the container: encode as an array in field declaration order
+//!   (`[x, y, z]`), as the viewer does for vectors, quaternions and colors; skipped fields are
+//!   left out and the array length must match on input
+//! - `#[llsd(traits_only)]` on the container: implement only [`FromLlsd`](crate::FromLlsd) /
+//!   [`IntoLlsd`](crate::IntoLlsd), leaving `TryFrom<&Llsd>` / `From<T> for Llsd` free for
+//!   hand-written impls
+//! - `#[llsd(builder)]` on the container: also generate `<Name>Builder` (and `Name::builder()`)
+//!   with one `impl Into<T>` setter per field; `build()` fills `Option` and `default` fields
+//!   and returns a `missing field` error for any other field left unset
+//! - `#[llsd(binary)]` stores a byte container as `Llsd::Binary`; this is the default for
+//!   `Vec<u8>`, `[u8; N]` and `bytes::Bytes` (with the `bytes` feature), including inside
+//!   `Option`. Use `#[llsd(binary = false)]` to keep the array-of-integers form. Reading
+//!   accepts either form
+//! - `#[llsd(int_as = "string" | "real" | "binary")]` on `u64`/`i64` (and other integer)
+//!   fields encodes values that do not fit the 32-bit LLSD Integer without truncation:
+//!   a decimal string, a Real (exact up to 2^53), or 8 big-endian bytes. Reading accepts
+//!   any of these encodings as well as a plain Integer
+//! - `#[llsd(date_format = "epoch_seconds" | "epoch_millis" | "rfc3339" | "%Y-%m-%d ...")]` on
+//!   `DateTime<Utc>`, `DateTime<FixedOffset>` and `NaiveDateTime` (taken as UTC) fields; the numeric
+//!   forms (also spelled `"seconds"` / `"millis"`) work on `chrono::TimeDelta` and
+//!   `std::time::Duration` too. Numbers are written as Integer when they fit, otherwise Real
+//! - `#[llsd(compact_date)]` on the same types writes seconds since the epoch (or the span's
+//!   length) as a Real, 9 bytes in binary LLSD against a text date's length elsewhere, keeping
+//!   the microseconds; reading also accepts an Integer, a numeric string or an LLSD `Date`
+//! - `#[llsd(uuid_as_binary)]` on `Uuid` fields (or `Option<Uuid>`) writes the 16 raw bytes as
+//!   a Binary instead of a UUID, for peers and formats without a compact UUID type (notation and
+//!   XML spell one out in 36 characters, base64 in 24); reading accepts either form or a string
+//! - `#[llsd(empty_as_none)]` on `Option<String>`, `Option<Uuid>` and `Option<Uri>` fields reads
+//!   empty strings, the nil UUID and empty URIs as `None`, as the viewer uses them for "absent"
+//! - `#[llsd(validate(range = "1..=100", min_len = 3, non_empty))]` and
+//!   `#[llsd(validate = "path::to_fn")]` (`fn(&T) -> Result<(), impl Display>`); checks run
+//!   on converted input values (the inner value for `Option<T>`) and fail with a
+//!   path-qualified validation error
+//!
+//! `LlsdFrom` also implements [`FromLlsd::update_from_llsd`](crate::FromLlsd::update_from_llsd),
+//! which applies a sparse map in place: only fields whose keys are present are overwritten,
+//! nested structs are updated recursively, and everything else is left untouched.
 //!
 //! Notes / Limitations:
-//! - `with = "path"` attribute is parsed but not yet applied.
 //! - `flatten` currently only works for fields whose LLSD form is a Map.
 //! - Generic structs: bounds are not auto-inferred; add them manually if needed.
-//! - Error messages are basic; future improvement will add per-field context.
+//! - Attribute mistakes are reported on the attribute or field they concern (all fields at
+//!   once), with a suggestion for typos and misplaced container/field attributes. A field type
+//!   without a conversion fails on that field with a note pointing at `with` and the bridge macros.
+//! - The derives implement [`FromLlsd`](crate::FromLlsd) and [`IntoLlsd`](crate::IntoLlsd)
+//!   and bridge them to `TryFrom<&Llsd>`, `TryFrom<Llsd>`, `From<T>` and `From<&T>`.
+//!   Field types are converted through the traits; a type that only has `TryFrom`/`From`
+//!   impls can be bridged with [`from_llsd_via_try_from!`](crate::from_llsd_via_try_from)
+//!   and [`into_llsd_via_from!`](crate::into_llsd_via_from).
+//! - `FromLlsd` errors are [`LlsdError`](crate::LlsdError)s (wrapped in `anyhow::Error` by
+//!   the `TryFrom` bridges) and carry the field path, e.g.
+//!   `invalid type at inventory[3].folder_id: expected uuid, found string`.
+//!
+//! Structs without `flatten` or `path` fields are read in a single pass over the input map,
+//! dispatching each key with a `match` on the key string (which also performs the
+//! `deny_unknown_fields` check) instead of one hash lookup per field;
+//! `benches/derive_keys.rs` compares the two.
 //!
-//! All macro expansion code lives in the `llsd-rs-derive` crate so this
-//! module is intentionally minimal.
+//! All macro expansion code lives in the `llsd-rs-derive` crate; this module
+//! only holds the runtime helpers generated code calls into.
 
 #[allow(dead_code)]
 pub struct _DeriveDocs;
+
+/// Nested-location access for `#[llsd(path = "/a/b")]` fields.
+pub mod path {
+    use crate::{Llsd, LlsdMap};
+
+    pub fn get<'a>(map: &'a LlsdMap, path: &[&str]) -> Option<&'a Llsd> {
+        let (first, rest) = path.split_first()?;
+        rest.iter()
+            .try_fold(map.get(*first)?, |target, key| target.as_map()?.get(*key))
+    }
+
+    pub fn take(map: &mut LlsdMap, path: &[&str]) -> Option<Llsd> {
+        let (last, parents) = path.split_last()?;
+        let mut target = map;
+        for key in parents {
+            target = target.get_mut(*key)?.as_map_mut()?;
+        }
+        target.remove(*last)
+    }
+
+    /// Insert `value`, creating (or replacing non-map) intermediate values with maps.
+    pub fn insert(map: &mut LlsdMap, path: &[&str], value: Llsd) {
+        let Some((last, parents)) = path.split_last() else {
+            return;
+        };
+        let mut target = map;
+        for key in parents {
+            let entry = target.get_or_insert_with(key.to_string(), Llsd::map);
+            if !entry.is_map() {
+                *entry = Llsd::map();
+            }
+            target = entry.as_map_mut().expect("entry is a map");
+        }
+        target.insert(last.to_string(), value);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn insert_creates_intermediate_maps() {
+            let mut map = LlsdMap::new();
+            insert(&mut map, &["agent", "id"], Llsd::Integer(1));
+            insert(&mut map, &["agent", "name"], Llsd::from("a"));
+            assert_eq!(get(&map, &["agent", "id"]), Some(&Llsd::Integer(1)));
+            assert_eq!(take(&mut map, &["agent", "name"]), Some(Llsd::from("a")));
+            assert_eq!(get(&map, &["agent", "name"]), None);
+            assert_eq!(get(&map, &["agent", "id", "x"]), None);
+        }
+    }
+}
+
+/// Helpers for `#[llsd(empty_as_none)]` fields.
+pub mod empty_as_none {
+    use std::borrow::Borrow;
+
+    use crate::{Llsd, Uri};
+
+    /// Input treated as absent before conversion: `Undefined` and `""`.
+    pub fn is_blank<L: Borrow<Llsd>>(llsd: &L) -> bool {
+        match llsd.borrow() {
+            Llsd::Undefined => true,
+            Llsd::String(value) => value.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Converted values that mean "absent", such as the nil UUID.
+    pub trait EmptyValue {
+        fn is_empty_value(&self) -> bool;
+    }
+
+    impl EmptyValue for String {
+        fn is_empty_value(&self) -> bool {
+            self.is_empty()
+        }
+    }
+
+    impl EmptyValue for crate::Uuid {
+        fn is_empty_value(&self) -> bool {
+            self.is_nil()
+        }
+    }
+
+    impl EmptyValue for Uri {
+        fn is_empty_value(&self) -> bool {
+            self.is_empty()
+        }
+    }
+
+    impl EmptyValue for url::Url {
+        fn is_empty_value(&self) -> bool {
+            false
+        }
+    }
+}
+
+/// `with`-style helpers used for `#[llsd(binary)]` fields.
+pub mod binary {
+    use crate::{Llsd, LlsdError};
+
+    /// Byte containers that can be rebuilt from an `Llsd::Binary` payload.
+    pub trait BinaryField: Sized {
+        fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self>;
+    }
+
+    impl BinaryField for Vec<u8> {
+        fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+            Ok(bytes)
+        }
+    }
+
+    impl<const N: usize> BinaryField for [u8; N] {
+        fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+            let found = bytes.len();
+            bytes
+                .try_into()
+                .map_err(|_| LlsdError::invalid_length(N, found).into())
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    impl BinaryField for bytes::Bytes {
+        fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+            Ok(bytes.into())
+        }
+    }
+
+    pub fn serialize<T: AsRef<[u8]> + ?Sized>(value: &T) -> Llsd {
+        Llsd::Binary(value.as_ref().to_vec())
+    }
+
+    /// Accepts `Binary`, and arrays of integers as written before byte
+    /// containers defaulted to `Binary`.
+    pub fn deserialize<T: BinaryField>(llsd: &Llsd) -> anyhow::Result<T> {
+        let bytes = match llsd {
+            Llsd::Binary(bytes) => bytes.clone(),
+            Llsd::Array(_) => Vec::<u8>::try_from(llsd)?,
+            _ => return Err(LlsdError::invalid_type("binary", llsd).into()),
+        };
+        T::from_bytes(bytes)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn fixed_arrays_check_length() {
+            let llsd = serialize(&[1u8, 2, 3]);
+            assert_eq!(llsd, Llsd::Binary(vec![1, 2, 3]));
+            assert_eq!(deserialize::<[u8; 3]>(&llsd).unwrap(), [1, 2, 3]);
+            let err = deserialize::<[u8; 4]>(&llsd).unwrap_err();
+            assert_eq!(err.to_string(), "invalid length: expected 4, found 3");
+        }
+
+        #[test]
+        fn integer_arrays_are_accepted() {
+            let llsd = Llsd::from(vec![1u8, 2]);
+            assert_eq!(deserialize::<Vec<u8>>(&llsd).unwrap(), vec![1, 2]);
+        }
+    }
+}
+
+/// `with`-style helpers used for `#[llsd(int_as = "...")]` fields.
+pub mod int_as {
+    use crate::{Llsd, LlsdError};
+
+    /// Integers that may not fit the 32-bit LLSD Integer.
+    pub trait WideInt: Copy {
+        fn to_i128(self) -> i128;
+        fn from_i128(value: i128) -> Option<Self>;
+        /// Two's complement bits, used for the 8-byte binary encoding.
+        fn to_bits(self) -> u64;
+        fn from_bits(bits: u64) -> Option<Self>;
+    }
+
+    macro_rules! impl_wide_int {
+        ($($t:ty),*) => {
+            $(
+            impl AsWideInt for $t {
+                type Int = $t;
+                fn get(&self) -> $t {
+                    *self
+                }
+            }
+
+            impl AsWideInt for &$t {
+                type Int = $t;
+                fn get(&self) -> $t {
+                    **self
+                }
+            }
+
+            impl WideInt for $t {
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                fn from_i128(value: i128) -> Option<Self> {
+                    <$t>::try_from(value).ok()
+                }
+
+                fn to_bits(self) -> u64 {
+                    self as i64 as u64
+                }
+
+                fn from_bits(bits: u64) -> Option<Self> {
+                    if <$t>::MIN == 0 {
+                        Self::from_i128(bits as i128)
+                    } else {
+                        Self::from_i128(bits as i64 as i128)
+                    }
+                }
+            }
+            )*
+        };
+    }
+
+    impl_wide_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+    /// Lets generated code serialize through one extra reference.
+    pub trait AsWideInt {
+        type Int: WideInt;
+        fn get(&self) -> Self::Int;
+    }
+
+    fn decode<T: WideInt>(llsd: &Llsd) -> anyhow::Result<T> {
+        let out_of_range = || LlsdError::custom("integer out of range").into();
+        match llsd {
+            Llsd::Integer(value) => T::from_i128(*value as i128).ok_or_else(out_of_range),
+            Llsd::Real(value) if value.fract() == 0.0 => {
+                T::from_i128(*value as i128).ok_or_else(out_of_range)
+            }
+            Llsd::String(value) => match value.trim().parse::<i128>() {
+                Ok(value) => T::from_i128(value).ok_or_else(out_of_range),
+                Err(_) => Err(LlsdError::invalid_type("integer", llsd).into()),
+            },
+            Llsd::Binary(bytes) => match <[u8; 8]>::try_from(bytes.as_slice()) {
+                Ok(bytes) => T::from_bits(u64::from_be_bytes(bytes)).ok_or_else(out_of_range),
+                Err(_) => Err(LlsdError::invalid_length(8, bytes.len()).into()),
+            },
+            _ => Err(LlsdError::invalid_type("integer", llsd).into()),
+        }
+    }
+
+    pub mod string {
+        use super::*;
+
+        pub fn serialize<T: AsWideInt>(value: &T) -> Llsd {
+            Llsd::String(value.get().to_i128().to_string())
+        }
+
+        pub fn deserialize<T: WideInt>(llsd: &Llsd) -> anyhow::Result<T> {
+            decode(llsd)
+        }
+    }
+
+    pub mod real {
+        use super::*;
+
+        pub fn serialize<T: AsWideInt>(value: &T) -> Llsd {
+            Llsd::Real(value.get().to_i128() as f64)
+        }
+
+        pub fn deserialize<T: WideInt>(llsd: &Llsd) -> anyhow::Result<T> {
+            decode(llsd)
+        }
+    }
+
+    pub mod binary {
+        use super::*;
+
+        pub fn serialize<T: AsWideInt>(value: &T) -> Llsd {
+            Llsd::Binary(value.get().to_bits().to_be_bytes().to_vec())
+        }
+
+        pub fn deserialize<T: WideInt>(llsd: &Llsd) -> anyhow::Result<T> {
+            decode(llsd)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn encodings_round_trip_wide_values() {
+            let value = u64::MAX - 1;
+            assert_eq!(
+                string::serialize(&value),
+                Llsd::String("18446744073709551614".into())
+            );
+            assert_eq!(
+                binary::serialize(&&value),
+                Llsd::Binary(value.to_be_bytes().to_vec())
+            );
+            for llsd in [string::serialize(&value), binary::serialize(&value)] {
+                assert_eq!(string::deserialize::<u64>(&llsd).unwrap(), value);
+            }
+            let negative = -5_000_000_000i64;
+            assert_eq!(
+                binary::deserialize::<i64>(&binary::serialize(&negative)).unwrap(),
+                negative
+            );
+            assert_eq!(
+                real::deserialize::<i64>(&real::serialize(&negative)).unwrap(),
+                negative
+            );
+        }
+
+        #[test]
+        fn out_of_range_values_are_rejected() {
+            let err = string::deserialize::<u32>(&Llsd::String("-1".into())).unwrap_err();
+            assert_eq!(err.to_string(), "invalid value: integer out of range");
+            assert!(real::deserialize::<i64>(&Llsd::Real(1.5)).is_err());
+        }
+    }
+}
+
+/// `with`-style helpers used for `#[llsd(uuid_as_binary)]` fields.
+pub mod uuid_as_binary {
+    use std::borrow::Borrow;
+
+    use crate::Uuid;
+
+    use crate::{Llsd, LlsdError};
+
+    pub fn serialize<T: Borrow<Uuid>>(value: &T) -> Llsd {
+        Llsd::Binary(value.borrow().as_bytes().to_vec())
+    }
+
+    /// Accepts 16 bytes of `Binary`, as well as a `Uuid` or its text.
+    pub fn deserialize(llsd: &Llsd) -> anyhow::Result<Uuid> {
+        match llsd {
+            Llsd::Binary(bytes) => Uuid::from_slice(bytes)
+                .map_err(|_| LlsdError::invalid_length(16, bytes.len()).into()),
+            _ => Ok(Uuid::try_from(llsd)?),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn round_trips_and_accepts_uuids() {
+            let id = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+            let llsd = serialize(&&id);
+            assert_eq!(llsd, Llsd::Binary(id.as_bytes().to_vec()));
+            assert_eq!(deserialize(&llsd).unwrap(), id);
+            assert_eq!(deserialize(&Llsd::Uuid(id)).unwrap(), id);
+            assert_eq!(deserialize(&Llsd::from(id.to_string())).unwrap(), id);
+            let err = deserialize(&Llsd::Binary(vec![0; 4])).unwrap_err();
+            assert_eq!(err.to_string(), "invalid length: expected 16, found 4");
+        }
+    }
+}
+
+/// `with`-style helpers used for `#[llsd(date_format = "...")]` fields.
+pub mod date_format {
+    use crate::{Date, Llsd, LlsdError, date};
+
+    /// Values with a numeric form: seconds since the epoch for dates, the
+    /// length of the span for durations.
+    pub trait ToSeconds {
+        fn to_seconds(&self) -> f64;
+    }
+
+    pub trait FromSeconds: Sized {
+        fn from_seconds(seconds: f64) -> Option<Self>;
+    }
+
+    /// Points in time, which also have a text form.
+    pub trait ToDateTime {
+        fn to_datetime(&self) -> Date;
+    }
+
+    pub trait FromDateTime: Sized {
+        fn from_datetime(date: Date) -> Self;
+    }
+
+    impl<T: ToSeconds + ?Sized> ToSeconds for &T {
+        fn to_seconds(&self) -> f64 {
+            (**self).to_seconds()
+        }
+    }
+
+    impl<T: ToDateTime + ?Sized> ToDateTime for &T {
+        fn to_datetime(&self) -> Date {
+            (**self).to_datetime()
+        }
+    }
+
+    macro_rules! impl_date {
+        ($($t:ty => $to:expr, $from:expr;)*) => {
+            $(
+            impl ToDateTime for $t {
+                fn to_datetime(&self) -> Date {
+                    $to(self)
+                }
+            }
+
+            impl FromDateTime for $t {
+                fn from_datetime(date: Date) -> Self {
+                    $from(date)
+                }
+            }
+
+            impl ToSeconds for $t {
+                fn to_seconds(&self) -> f64 {
+                    self.to_datetime().timestamp_micros() as f64 / 1e6
+                }
+            }
+
+            impl FromSeconds for $t {
+                fn from_seconds(seconds: f64) -> Option<Self> {
+                    let micros = (seconds * 1e6).round();
+                    if !micros.is_finite() || micros.abs() >= i64::MAX as f64 {
+                        return None;
+                    }
+                    Date::from_timestamp_micros(micros as i64).map(Self::from_datetime)
+                }
+            }
+            )*
+        };
+    }
+
+    impl_date! {
+        Date => |d: &Date| *d, |d| d;
+    }
+
+    #[cfg(feature = "chrono")]
+    impl_date! {
+        chrono::DateTime<chrono::FixedOffset> => |d: &chrono::DateTime<chrono::FixedOffset>| d.to_utc(), |d: Date| d.fixed_offset();
+        chrono::NaiveDateTime => |d: &chrono::NaiveDateTime| d.and_utc(), |d: Date| d.naive_utc();
+    }
+
+    #[cfg(feature = "chrono")]
+    impl ToSeconds for chrono::TimeDelta {
+        fn to_seconds(&self) -> f64 {
+            match self.num_microseconds() {
+                Some(micros) => micros as f64 / 1e6,
+                None => self.num_milliseconds() as f64 / 1e3,
+            }
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    impl FromSeconds for chrono::TimeDelta {
+        fn from_seconds(seconds: f64) -> Option<Self> {
+            let millis = seconds * 1e3;
+            if !millis.is_finite() || millis.abs() >= i64::MAX as f64 {
+                return None;
+            }
+            let micros = (seconds * 1e6).round();
+            if micros.abs() < i64::MAX as f64 {
+                Some(Self::microseconds(micros as i64))
+            } else {
+                Self::try_milliseconds(millis.round() as i64)
+            }
+        }
+    }
+
+    impl ToSeconds for std::time::Duration {
+        fn to_seconds(&self) -> f64 {
+            self.as_secs_f64()
+        }
+    }
+
+    impl FromSeconds for std::time::Duration {
+        fn from_seconds(seconds: f64) -> Option<Self> {
+            std::time::Duration::try_from_secs_f64(seconds).ok()
+        }
+    }
+
+    /// Whole values that fit are written as `Integer`, anything else as `Real`.
+    fn number(value: f64) -> Llsd {
+        if value.fract() == 0.0 && value >= i32::MIN as f64 && value <= i32::MAX as f64 {
+            Llsd::Integer(value as i32)
+        } else {
+            Llsd::Real(value)
+        }
+    }
+
+    fn read_number(llsd: &Llsd) -> anyhow::Result<f64> {
+        match llsd {
+            Llsd::Integer(value) => Ok(*value as f64),
+            Llsd::Real(value) => Ok(*value),
+            Llsd::String(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| LlsdError::invalid_type("number", llsd).into()),
+            _ => Err(LlsdError::invalid_type("number", llsd).into()),
+        }
+    }
+
+    fn out_of_range() -> anyhow::Error {
+        LlsdError::custom("time value out of range").into()
+    }
+
+    pub mod epoch_seconds {
+        use super::*;
+
+        pub fn serialize<T: ToSeconds>(value: &T) -> Llsd {
+            number(value.to_seconds())
+        }
+
+        pub fn deserialize<T: FromSeconds>(llsd: &Llsd) -> anyhow::Result<T> {
+            T::from_seconds(read_number(llsd)?).ok_or_else(out_of_range)
+        }
+    }
+
+    pub mod epoch_millis {
+        use super::*;
+
+        pub fn serialize<T: ToSeconds>(value: &T) -> Llsd {
+            number((value.to_seconds() * 1e3).round())
+        }
+
+        pub fn deserialize<T: FromSeconds>(llsd: &Llsd) -> anyhow::Result<T> {
+            T::from_seconds(read_number(llsd)? / 1e3).ok_or_else(out_of_range)
+        }
+    }
+
+    /// `#[llsd(compact_date)]`: always a `Real`, so binary LLSD spends a
+    /// fixed 9 bytes and sub-second precision survives.
+    pub mod epoch_real {
+        use super::*;
+
+        pub fn serialize<T: ToSeconds>(value: &T) -> Llsd {
+            Llsd::Real(value.to_seconds())
+        }
+
+        /// Also accepts an LLSD `Date`.
+        pub fn deserialize<T: FromSeconds>(llsd: &Llsd) -> anyhow::Result<T> {
+            let seconds = match llsd {
+                Llsd::Date(date) => date.timestamp_micros() as f64 / 1e6,
+                _ => read_number(llsd)?,
+            };
+            T::from_seconds(seconds).ok_or_else(out_of_range)
+        }
+    }
+
+    pub mod rfc3339 {
+        use super::*;
+
+        pub fn serialize<T: ToDateTime>(value: &T) -> Llsd {
+            Llsd::String(value.to_datetime().to_rfc3339())
+        }
+
+        /// Also accepts an LLSD `Date`.
+        pub fn deserialize<T: FromDateTime>(llsd: &Llsd) -> anyhow::Result<T> {
+            match llsd {
+                Llsd::Date(date) => Ok(T::from_datetime(*date)),
+                Llsd::String(value) => date::parse_rfc3339(value.trim())
+                    .map(T::from_datetime)
+                    .map_err(|e| LlsdError::custom(e).into()),
+                _ => Err(LlsdError::invalid_type("date", llsd).into()),
+            }
+        }
+    }
+
+    /// Chrono `strftime`-style formats, with the `chrono` feature. Input
+    /// without an offset is taken as UTC, and a date-only format reads as
+    /// midnight.
+    #[cfg(feature = "chrono")]
+    pub mod custom {
+        use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+        use super::*;
+
+        pub fn serialize<T: ToDateTime>(value: &T, format: &str) -> Llsd {
+            Llsd::String(value.to_datetime().format(format).to_string())
+        }
+
+        pub fn deserialize<T: FromDateTime>(llsd: &Llsd, format: &str) -> anyhow::Result<T> {
+            let value = match llsd {
+                Llsd::Date(date) => return Ok(T::from_datetime(*date)),
+                Llsd::String(value) => value.trim(),
+                _ => return Err(LlsdError::invalid_type("date", llsd).into()),
+            };
+            let date = DateTime::parse_from_str(value, format)
+                .map(|date| date.to_utc())
+                .or_else(|_| NaiveDateTime::parse_from_str(value, format).map(|d| d.and_utc()))
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(value, format)
+                        .map(|d| d.and_time(Default::default()).and_utc())
+                })
+                .map_err(LlsdError::custom)?;
+            Ok(T::from_datetime(date))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn numeric_formats_round_trip() {
+            let date = Date::from_timestamp(1_700_000_000, 250_000_000).unwrap();
+            assert_eq!(
+                epoch_seconds::serialize(&date),
+                Llsd::Real(1_700_000_000.25)
+            );
+            assert_eq!(
+                epoch_millis::serialize(&&date),
+                Llsd::Real(1_700_000_000_250.0)
+            );
+            for llsd in [epoch_millis::serialize(&date), Llsd::from("1700000000250")] {
+                assert_eq!(epoch_millis::deserialize::<Date>(&llsd).unwrap(), date);
+            }
+            let span = std::time::Duration::from_millis(1500);
+            assert_eq!(epoch_seconds::serialize(&span), Llsd::Real(1.5));
+            assert_eq!(epoch_millis::serialize(&span), Llsd::Integer(1500));
+            #[cfg(feature = "chrono")]
+            assert_eq!(
+                epoch_seconds::deserialize::<chrono::TimeDelta>(&Llsd::Integer(-2)).unwrap(),
+                chrono::TimeDelta::seconds(-2)
+            );
+            assert!(epoch_seconds::deserialize::<std::time::Duration>(&Llsd::Integer(-2)).is_err());
+        }
+
+        #[cfg(feature = "chrono")]
+        #[test]
+        fn text_formats_round_trip() {
+            use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+
+            let date = DateTime::from_timestamp(1_699_999_980, 0)
+                .unwrap()
+                .naive_utc();
+            let llsd = custom::serialize(&date, "%Y-%m-%d %H:%M");
+            assert_eq!(llsd, Llsd::from("2023-11-14 22:13"));
+            assert_eq!(
+                custom::deserialize::<NaiveDateTime>(&llsd, "%Y-%m-%d %H:%M").unwrap(),
+                date
+            );
+            let day = custom::deserialize::<DateTime<Utc>>(&Llsd::from("2023-11-14"), "%Y-%m-%d");
+            assert_eq!(day.unwrap().to_rfc3339(), "2023-11-14T00:00:00+00:00");
+            let parsed: DateTime<FixedOffset> =
+                rfc3339::deserialize(&Llsd::from("2023-11-14T22:13:20+02:00")).unwrap();
+            assert_eq!(parsed.to_datetime().timestamp(), 1_699_992_800);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt;
+
+use crate::Llsd;
+
+/// A single step in the path from the document root to a failing value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Render `path` as `a.b[3].c`.
+pub(crate) fn path_string(path: &[PathSegment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(key);
+            }
+            PathSegment::Index(index) => {
+                out.push('[');
+                out.push_str(&index.to_string());
+                out.push(']');
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LlsdErrorKind {
+    InvalidType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+    /// A number that doesn't fit the requested type.
+    OutOfRange {
+        target: &'static str,
+        value: String,
+    },
+    MissingField,
+    UnknownField,
+    Validation(String),
+    Custom(String),
+}
+
+/// Conversion error carrying the location of the failing value.
+///
+/// Conversions between `Llsd` and Rust types report failures through
+/// `anyhow::Error`; when the underlying error is an `LlsdError`, nested
+/// conversions (derived structs, `Vec`, `HashMap`, tuples) prepend their own
+/// key or index so the final message names the full path, e.g.
+/// `invalid type at login_response.inventory[3].folder_id: expected uuid, found string`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlsdError {
+    kind: LlsdErrorKind,
+    path: Vec<PathSegment>,
+}
+
+impl LlsdError {
+    pub fn new(kind: LlsdErrorKind) -> Self {
+        Self {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn invalid_type(expected: &'static str, found: &Llsd) -> Self {
+        Self::new(LlsdErrorKind::InvalidType {
+            expected,
+            found: found.type_name(),
+        })
+    }
+
+    pub fn invalid_length(expected: usize, found: usize) -> Self {
+        Self::new(LlsdErrorKind::InvalidLength { expected, found })
+    }
+
+    pub fn out_of_range(target: &'static str, value: impl fmt::Display) -> Self {
+        Self::new(LlsdErrorKind::OutOfRange {
+            target,
+            value: value.to_string(),
+        })
+    }
+
+    pub fn missing_field(key: &str) -> Self {
+        Self::new(LlsdErrorKind::MissingField).with_key(key)
+    }
+
+    pub fn unknown_field(key: &str) -> Self {
+        Self::new(LlsdErrorKind::UnknownField).with_key(key)
+    }
+
+    pub fn validation(message: impl fmt::Display) -> Self {
+        Self::new(LlsdErrorKind::Validation(message.to_string()))
+    }
+
+    pub fn custom(message: impl fmt::Display) -> Self {
+        Self::new(LlsdErrorKind::Custom(message.to_string()))
+    }
+
+    pub fn kind(&self) -> &LlsdErrorKind {
+        &self.kind
+    }
+
+    /// Path segments from the document root to the failing value.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// The path rendered as `a.b[3].c`; empty for errors at the root.
+    pub fn path_string(&self) -> String {
+        path_string(&self.path)
+    }
+
+    /// Prepend a map key to the path.
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.path.insert(0, PathSegment::Key(key.to_string()));
+        self
+    }
+
+    /// Prepend an array index to the path.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.path.insert(0, PathSegment::Index(index));
+        self
+    }
+
+    /// Prepend the steps of `prefix` to the path.
+    pub(crate) fn with_prefix(mut self, prefix: &[PathSegment]) -> Self {
+        self.path.splice(0..0, prefix.iter().cloned());
+        self
+    }
+
+    /// Recover an `LlsdError` from an `anyhow::Error`, wrapping foreign errors
+    /// as [`LlsdErrorKind::Custom`].
+    pub fn from_anyhow(err: anyhow::Error) -> Self {
+        match err.downcast::<LlsdError>() {
+            Ok(err) => err,
+            Err(err) => Self::custom(err),
+        }
+    }
+
+    /// Prepend `key` to the path of a conversion error.
+    pub fn at_key(err: impl Into<anyhow::Error>, key: &str) -> anyhow::Error {
+        Self::from_anyhow(err.into()).with_key(key).into()
+    }
+
+    /// Prepend `index` to the path of a conversion error.
+    pub fn at_index(err: impl Into<anyhow::Error>, index: usize) -> anyhow::Error {
+        Self::from_anyhow(err.into()).with_index(index).into()
+    }
+}
+
+impl fmt::Display for LlsdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match &self.kind {
+            LlsdErrorKind::InvalidType { .. } => "invalid type",
+            LlsdErrorKind::InvalidLength { .. } => "invalid length",
+            LlsdErrorKind::OutOfRange { .. } => "out of range",
+            LlsdErrorKind::MissingField => "missing field",
+            LlsdErrorKind::UnknownField => "unknown field",
+            LlsdErrorKind::Validation(_) => "validation failed",
+            LlsdErrorKind::Custom(_) => "invalid value",
+        };
+        f.write_str(label)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path_string())?;
+        }
+        match &self.kind {
+            LlsdErrorKind::InvalidType { expected, found } => {
+                write!(f, ": expected {expected}, found {found}")
+            }
+            LlsdErrorKind::InvalidLength { expected, found } => {
+                write!(f, ": expected {expected}, found {found}")
+            }
+            LlsdErrorKind::OutOfRange { target, value } => {
+                write!(f, ": {value} does not fit in {target}")
+            }
+            LlsdErrorKind::MissingField | LlsdErrorKind::UnknownField => Ok(()),
+            LlsdErrorKind::Validation(message) | LlsdErrorKind::Custom(message) => {
+                write!(f, ": {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LlsdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_includes_nested_path() {
+        let err = LlsdError::invalid_type("uuid", &Llsd::String("x".into()))
+            .with_key("folder_id")
+            .with_index(3)
+            .with_key("inventory")
+            .with_key("login_response");
+        assert_eq!(
+            err.to_string(),
+            "invalid type at login_response.inventory[3].folder_id: expected uuid, found string"
+        );
+    }
+
+    #[test]
+    fn at_key_wraps_foreign_errors() {
+        let err = LlsdError::at_key(anyhow::anyhow!("boom"), "field");
+        let err = LlsdError::from_anyhow(err);
+        assert_eq!(err.kind(), &LlsdErrorKind::Custom("boom".into()));
+        assert_eq!(err.path(), &[PathSegment::Key("field".into())]);
+        assert_eq!(err.to_string(), "invalid value at field: boom");
+    }
+
+    #[test]
+    fn root_errors_have_no_path() {
+        let err = LlsdError::invalid_type("map", &Llsd::Integer(1));
+        assert_eq!(err.to_string(), "invalid type: expected map, found integer");
+    }
+}
@@ -0,0 +1,134 @@
+//! Structural digests of values, for HTTP ETags and memoization keys.
+//!
+//! [`Llsd::cache_key`] hashes the canonical binary encoding
+//! ([`binary::to_canonical_vec`](crate::binary::to_canonical_vec)) with
+//! SHA-256 (the `sha2` crate) and keeps the first 128 bits, so values that are equal as LLSD get
+//! the same key whatever their map order, and different values collide only
+//! with cryptographically small odds.
+//!
+//! ```
+//! use llsd_rs::llsd_map;
+//!
+//! let caps = llsd_map! { "EventQueueGet" => "https://sim.example.com/eq", "Seed" => 1 };
+//! let key = caps.cache_key().unwrap();
+//! let etag = key.etag();
+//! assert_eq!(etag.len(), 34);
+//! assert!(key.matches_etag(&format!("W/{etag}, \"other\"")));
+//! assert_ne!(key, llsd_map! { "Seed" => 2 }.cache_key().unwrap());
+//! ```
+
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::{Llsd, binary};
+
+/// A 128-bit digest of a value; shown as 32 lowercase hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint([u8; 16]);
+
+impl Fingerprint {
+    pub fn of(llsd: &Llsd) -> Result<Self, anyhow::Error> {
+        let digest = Sha256::digest(binary::to_canonical_vec(llsd)?);
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        Ok(Self(bytes))
+    }
+
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// A strong entity tag: the hex digest in double quotes.
+    pub fn etag(&self) -> String {
+        format!("\"{self}\"")
+    }
+
+    /// Whether an `If-None-Match` header value names this fingerprint: `*`,
+    /// or a comma-separated list of entity tags, weak (`W/`) or strong,
+    /// compared weakly as that header requires.
+    pub fn matches_etag(&self, header: &str) -> bool {
+        etag_listed(header, &self.etag())
+    }
+
+    /// Whether an `If-Match` header value names this fingerprint, comparing
+    /// strongly (RFC 9110 §13.1.1): `*`, or a listed strong tag; weak tags
+    /// never match.
+    pub fn matches_etag_strong(&self, header: &str) -> bool {
+        let etag = self.etag();
+        header
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == etag)
+    }
+}
+
+/// Whether a conditional header lists `etag` (given quoted, as sent in an
+/// `ETag` header), comparing weakly.
+pub(crate) fn etag_listed(header: &str, etag: &str) -> bool {
+    let etag = etag.trim().trim_start_matches("W/");
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LlsdMap, llsd_array};
+
+    #[test]
+    fn equal_values_share_a_key() {
+        let mut a = LlsdMap::new();
+        let mut b = LlsdMap::new();
+        for i in 0..20 {
+            a.insert(format!("k{i}"), Llsd::Integer(i));
+            b.insert(format!("k{}", 19 - i), Llsd::Integer(19 - i));
+        }
+        let key = Llsd::Map(a.clone()).cache_key().unwrap();
+        assert_eq!(key, Llsd::Map(b).cache_key().unwrap());
+        assert_eq!(
+            Llsd::Real(-0.0).cache_key().unwrap(),
+            Llsd::Real(0.0).cache_key().unwrap()
+        );
+
+        a.insert("k0".into(), Llsd::Integer(100));
+        assert_ne!(key, Llsd::Map(a).cache_key().unwrap());
+        assert_ne!(
+            llsd_array![1, 2].cache_key().unwrap(),
+            llsd_array![2, 1].cache_key().unwrap()
+        );
+        assert_ne!(
+            Llsd::Integer(1).cache_key().unwrap(),
+            Llsd::Boolean(true).cache_key().unwrap()
+        );
+    }
+
+    #[test]
+    fn formats_and_matches_etags() {
+        // SHA-256 of the canonical encoding of undef, `!`.
+        let key = Llsd::Undefined.cache_key().unwrap();
+        assert_eq!(key.to_string(), "bb7208bc9b5d7c04f1236a82a0093a5e");
+        assert_eq!(Fingerprint::from_bytes(*key.as_bytes()), key);
+        assert!(key.matches_etag(&key.etag()));
+        assert!(key.matches_etag("*"));
+        assert!(key.matches_etag("\"x\" , W/\"bb7208bc9b5d7c04f1236a82a0093a5e\""));
+        assert!(!key.matches_etag("bb7208bc9b5d7c04f1236a82a0093a5e"));
+        assert!(!key.matches_etag("\"x\""));
+
+        assert!(key.matches_etag_strong(&format!("\"x\", {}", key.etag())));
+        assert!(key.matches_etag_strong("*"));
+        assert!(!key.matches_etag_strong(&format!("W/{}", key.etag())));
+    }
+}
@@ -0,0 +1,360 @@
+//! Typed bitmasks for the permission masks and object flags Second Life
+//! messages store as LLSD integers.
+//!
+//! LLSD integers are signed 32-bit, so a mask with the top bit set, such as
+//! a full `0xffff_ffff` mask, arrives as a negative number. Widening that
+//! with `as u64`, or narrowing it through `u32::try_from(i64)`, gives the
+//! wrong mask or an error. The helpers here reinterpret the 32 bits instead,
+//! in both directions:
+//!
+//! ```
+//! use llsd_rs::{FromLlsd, IntoLlsd, Llsd, flags::{self, PermissionMask}};
+//!
+//! assert_eq!(flags::u32_from_llsd(&Llsd::Integer(-1)).unwrap(), u32::MAX);
+//! assert_eq!(flags::u32_to_llsd(0x8000_0000), Llsd::Integer(i32::MIN));
+//!
+//! let perms = PermissionMask::from_llsd(&Llsd::Integer(0xe000)).unwrap();
+//! assert!(perms.contains(PermissionMask::MODIFY | PermissionMask::COPY));
+//! assert_eq!((perms - PermissionMask::TRANSFER).to_llsd(), Llsd::Integer(0xc000));
+//! ```
+
+use std::fmt;
+use std::ops;
+
+use crate::{FromLlsd, IntoLlsd, Llsd, LlsdError};
+
+/// The bits of an LLSD integer as unsigned.
+pub const fn u32_from_i32(v: i32) -> u32 {
+    v as u32
+}
+
+/// The bits of `v` as an LLSD integer; masks with the top bit set become
+/// negative.
+pub const fn i32_from_u32(v: u32) -> i32 {
+    v as i32
+}
+
+/// Read a 32-bit mask: an integer's bits, or a 4-byte big-endian binary as
+/// the viewer's `ll_U32_from_sd` accepts. Reals and strings are rejected
+/// rather than coerced, since a rounded mask is a different mask.
+pub fn u32_from_llsd(llsd: &Llsd) -> Result<u32, LlsdError> {
+    match llsd {
+        Llsd::Integer(v) => Ok(u32_from_i32(*v)),
+        Llsd::Binary(bytes) => <[u8; 4]>::try_from(bytes.as_slice())
+            .map(u32::from_be_bytes)
+            .map_err(|_| LlsdError::invalid_length(4, bytes.len())),
+        _ => Err(LlsdError::invalid_type("integer", llsd)),
+    }
+}
+
+/// Write a 32-bit mask as an LLSD integer with the same bits.
+pub fn u32_to_llsd(v: u32) -> Llsd {
+    Llsd::Integer(i32_from_u32(v))
+}
+
+macro_rules! flags {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $($(#[$flag_meta:meta])* const $flag:ident = $bits:expr;)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+        pub struct $name(pub u32);
+
+        impl $name {
+            $($(#[$flag_meta])* pub const $flag: Self = Self($bits);)*
+
+            /// Names and values of the known flags, in declaration order.
+            pub const NAMED: &'static [(&'static str, Self)] =
+                &[$((stringify!($flag), Self::$flag)),*];
+
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            pub const fn from_bits(bits: u32) -> Self {
+                Self(bits)
+            }
+
+            pub const fn bits(self) -> u32 {
+                self.0
+            }
+
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Whether every flag of `other` is set.
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Whether any flag of `other` is set.
+            pub const fn intersects(self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            pub fn set(&mut self, other: Self, on: bool) {
+                if on {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(bits: u32) -> Self {
+                Self(bits)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(v: $name) -> Self {
+                v.0
+            }
+        }
+
+        impl ops::BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl ops::BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, rhs: Self) {
+                self.0 &= rhs.0;
+            }
+        }
+
+        impl ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 & !rhs.0)
+            }
+        }
+
+        impl ops::Not for $name {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                Self(!self.0)
+            }
+        }
+
+        /// Lists the known flags set, then any other bits in hex:
+        /// `PermissionMask(MODIFY | COPY | 0x1)`.
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}(", stringify!($name))?;
+                write_names(f, self.0, Self::NAMED.iter().map(|(name, v)| (*name, v.0)))?;
+                f.write_str(")")
+            }
+        }
+
+        impl FromLlsd for $name {
+            fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+                u32_from_llsd(llsd).map(Self)
+            }
+        }
+
+        impl IntoLlsd for $name {
+            fn to_llsd(&self) -> Llsd {
+                u32_to_llsd(self.0)
+            }
+        }
+
+        impl From<$name> for Llsd {
+            fn from(v: $name) -> Self {
+                v.to_llsd()
+            }
+        }
+
+        impl From<&$name> for Llsd {
+            fn from(v: &$name) -> Self {
+                v.to_llsd()
+            }
+        }
+
+        impl TryFrom<&Llsd> for $name {
+            type Error = anyhow::Error;
+
+            fn try_from(llsd: &Llsd) -> Result<Self, Self::Error> {
+                Ok(<$name>::from_llsd(llsd)?)
+            }
+        }
+
+        impl TryFrom<Llsd> for $name {
+            type Error = anyhow::Error;
+
+            fn try_from(llsd: Llsd) -> Result<Self, Self::Error> {
+                Ok(<$name>::from_llsd(&llsd)?)
+            }
+        }
+    };
+}
+
+/// Write the names of the flags in `bits`, joined by `|`, then any bits
+/// without a name in hex; `0` when nothing is set.
+fn write_names(
+    f: &mut fmt::Formatter<'_>,
+    bits: u32,
+    named: impl Iterator<Item = (&'static str, u32)>,
+) -> fmt::Result {
+    let mut rest = bits;
+    let mut first = true;
+    let mut sep = |f: &mut fmt::Formatter<'_>| {
+        if std::mem::take(&mut first) {
+            Ok(())
+        } else {
+            f.write_str(" | ")
+        }
+    };
+    for (name, flag) in named {
+        if flag != 0 && bits & flag == flag && rest & flag != 0 {
+            sep(f)?;
+            f.write_str(name)?;
+            rest &= !flag;
+        }
+    }
+    if rest != 0 || bits == 0 {
+        sep(f)?;
+        write!(f, "{rest:#x}")?;
+    }
+    Ok(())
+}
+
+flags! {
+    /// Inventory and object permissions (`llpermissionsflags.h`): the base,
+    /// owner, group, everyone and next-owner masks.
+    pub struct PermissionMask {
+        const TRANSFER = 1 << 13;
+        const MODIFY = 1 << 14;
+        const COPY = 1 << 15;
+        /// OpenSimulator's export permission.
+        const EXPORT = 1 << 16;
+        const MOVE = 1 << 19;
+        /// What the viewer calls `PERM_ITEM_UNRESTRICTED`.
+        const UNRESTRICTED = (1 << 13) | (1 << 14) | (1 << 15);
+        /// `PERM_ALL`: every bit but the sign bit.
+        const ALL = 0x7fff_ffff;
+    }
+}
+
+flags! {
+    /// Object flags (`llprimitive/object_flags.h`), as in `ObjectFlags` of
+    /// object updates and properties.
+    pub struct ObjectFlags {
+        const USE_PHYSICS = 1 << 0;
+        const CREATE_SELECTED = 1 << 1;
+        const OBJECT_MODIFY = 1 << 2;
+        const OBJECT_COPY = 1 << 3;
+        const OBJECT_ANY_OWNER = 1 << 4;
+        const OBJECT_YOU_OWNER = 1 << 5;
+        const SCRIPTED = 1 << 6;
+        const HANDLE_TOUCH = 1 << 7;
+        const OBJECT_MOVE = 1 << 8;
+        const TAKES_MONEY = 1 << 9;
+        const PHANTOM = 1 << 10;
+        const INVENTORY_EMPTY = 1 << 11;
+        const AFFECTS_NAVMESH = 1 << 12;
+        const CHARACTER = 1 << 13;
+        const VOLUME_DETECT = 1 << 14;
+        const INCLUDE_IN_SEARCH = 1 << 15;
+        const ALLOW_INVENTORY_DROP = 1 << 16;
+        const OBJECT_TRANSFER = 1 << 17;
+        const OBJECT_GROUP_OWNED = 1 << 18;
+        const CAMERA_DECOUPLED = 1 << 20;
+        const ANIM_SOURCE = 1 << 21;
+        const CAMERA_SOURCE = 1 << 22;
+        const TEMPORARY_ON_REZ = 1 << 29;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reinterprets_the_sign_bit() {
+        for bits in [0, 1, 0x7fff_ffff, 0x8000_0000, 0xffff_fffe, u32::MAX] {
+            assert_eq!(u32_from_i32(i32_from_u32(bits)), bits);
+            assert_eq!(u32_from_llsd(&u32_to_llsd(bits)).unwrap(), bits);
+        }
+        assert_eq!(u32_to_llsd(u32::MAX), Llsd::Integer(-1));
+        assert_eq!(
+            u32_from_llsd(&Llsd::Binary(vec![0x80, 0, 0, 1])).unwrap(),
+            0x8000_0001
+        );
+        assert!(u32_from_llsd(&Llsd::Binary(vec![0; 8])).is_err());
+        assert!(u32_from_llsd(&Llsd::Real(1.0)).is_err());
+        assert!(u32_from_llsd(&Llsd::String("1".into())).is_err());
+    }
+
+    #[test]
+    fn mask_operations_and_conversions() {
+        let mut perms = PermissionMask::UNRESTRICTED | PermissionMask::MOVE;
+        assert!(perms.contains(PermissionMask::COPY));
+        perms.remove(PermissionMask::COPY);
+        assert!(!perms.contains(PermissionMask::UNRESTRICTED));
+        assert!(perms.intersects(PermissionMask::UNRESTRICTED));
+        perms.set(PermissionMask::COPY, true);
+        assert_eq!(perms.bits(), 0x8e000);
+
+        let everything = PermissionMask::from_bits(u32::MAX);
+        assert_eq!(everything.to_llsd(), Llsd::Integer(-1));
+        assert_eq!(
+            PermissionMask::try_from(Llsd::Integer(-1)).unwrap(),
+            everything
+        );
+        assert!(everything.contains(PermissionMask::ALL));
+        assert_eq!((!PermissionMask::ALL).bits(), 0x8000_0000);
+
+        let flags = ObjectFlags::from_llsd(&Llsd::Integer(i32::MIN | (1 << 6) | 1)).unwrap();
+        assert!(flags.contains(ObjectFlags::SCRIPTED | ObjectFlags::USE_PHYSICS));
+        assert_eq!(Llsd::from(flags), Llsd::Integer(i32::MIN | 65));
+    }
+
+    #[test]
+    fn debug_names_flags() {
+        assert_eq!(
+            format!("{:?}", PermissionMask::UNRESTRICTED),
+            "PermissionMask(TRANSFER | MODIFY | COPY)"
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                ObjectFlags::PHANTOM | ObjectFlags::from_bits(1 << 31)
+            ),
+            "ObjectFlags(PHANTOM | 0x80000000)"
+        );
+        assert_eq!(format!("{:?}", ObjectFlags::empty()), "ObjectFlags(0x0)");
+    }
+}
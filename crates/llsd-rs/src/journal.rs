@@ -0,0 +1,369 @@
+//! An append-only log of LLSD records.
+//!
+//! A journal starts with an 8-byte magic, followed by records of a
+//! little-endian `u32` payload length, the CRC-32 of the payload and the
+//! payload itself, one value in the binary format (without its header).
+//! Unlike newline-separated notation, payloads may hold any bytes.
+//!
+//! A crash mid-append leaves a partial record at the end of the file.
+//! [`JournalReader`] stops before it without an error and reports where the
+//! intact records end; [`JournalWriter::open`] cuts the file back to there
+//! before appending. A record whose checksum does not match is corruption
+//! rather than a torn write and is reported as an error.
+//!
+//! ```
+//! use llsd_rs::{Llsd, journal::{JournalReader, JournalWriter}};
+//!
+//! let mut writer = JournalWriter::new(Vec::new()).unwrap();
+//! writer.append(&Llsd::String("line\nbreak".into())).unwrap();
+//! writer.append(&Llsd::Integer(2)).unwrap();
+//! let bytes = writer.into_inner();
+//!
+//! let records: Vec<Llsd> = JournalReader::new(&bytes[..])
+//!     .unwrap()
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(records, [Llsd::String("line\nbreak".into()), Llsd::Integer(2)]);
+//! ```
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::Llsd;
+
+/// The first bytes of every journal.
+pub const MAGIC: &[u8; 8] = b"LLSDJRN1";
+
+/// Bytes before each payload: its length and checksum.
+const RECORD_HEADER: usize = 8;
+
+/// Payloads past this are rejected as corrupt lengths, as for unbounded
+/// lengths in the binary format.
+const MAX_RECORD_LENGTH: usize = 64 * 1024 * 1024;
+
+/// CRC-32 (IEEE 802.3, as zlib computes it) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &b| {
+        TABLE[usize::from((crc as u8) ^ b)] ^ (crc >> 8)
+    })
+}
+
+/// Appends records to a journal.
+#[derive(Debug)]
+pub struct JournalWriter<W: Write> {
+    inner: W,
+    payload: Vec<u8>,
+}
+
+impl<W: Write> JournalWriter<W> {
+    /// Start a new journal in `inner`, writing the magic.
+    pub fn new(mut inner: W) -> Result<Self, anyhow::Error> {
+        inner.write_all(MAGIC)?;
+        Ok(Self::resume(inner))
+    }
+
+    /// Append to `inner`, which is positioned after the last intact record
+    /// of an existing journal.
+    pub fn resume(inner: W) -> Self {
+        Self {
+            inner,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Write `llsd` as one record.
+    pub fn append(&mut self, llsd: &Llsd) -> Result<(), anyhow::Error> {
+        self.payload.clear();
+        crate::binary::write(llsd, &mut self.payload)?;
+        if self.payload.len() > MAX_RECORD_LENGTH {
+            return Err(anyhow::anyhow!(
+                "journal record length {} exceeds max {MAX_RECORD_LENGTH}",
+                self.payload.len()
+            ));
+        }
+        let mut header = [0u8; RECORD_HEADER];
+        header[..4].copy_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        header[4..].copy_from_slice(&crc32(&self.payload).to_le_bytes());
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&self.payload)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), anyhow::Error> {
+        Ok(self.inner.flush()?)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl JournalWriter<File> {
+    /// Open the journal at `path` for appending, creating it if missing and
+    /// cutting off a partial record left by an interrupted append.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("opening journal {}", path.display()))?;
+        if file.metadata()?.len() == 0 {
+            return Self::new(file);
+        }
+        let mut reader = JournalReader::new(BufReader::new(&mut file))?;
+        for record in reader.by_ref() {
+            record.with_context(|| format!("recovering journal {}", path.display()))?;
+        }
+        let end = reader.valid_len();
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+        Ok(Self::resume(file))
+    }
+
+    /// Flush and have the OS write the records to disk.
+    pub fn sync(&mut self) -> Result<(), anyhow::Error> {
+        self.inner.flush()?;
+        Ok(self.inner.sync_data()?)
+    }
+}
+
+/// Reads the records of a journal, oldest first.
+///
+/// Yields an error for a record that fails its checksum or does not decode,
+/// then stops. A partial record at the end stops iteration quietly, with
+/// [`is_truncated`](Self::is_truncated) set.
+#[derive(Debug)]
+pub struct JournalReader<R: Read> {
+    inner: R,
+    valid_len: u64,
+    truncated: bool,
+    done: bool,
+    payload: Vec<u8>,
+}
+
+impl<R: Read> JournalReader<R> {
+    /// Check the magic at the start of `inner`.
+    pub fn new(mut inner: R) -> Result<Self, anyhow::Error> {
+        let mut magic = [0u8; MAGIC.len()];
+        inner
+            .read_exact(&mut magic)
+            .context("reading journal magic")?;
+        if &magic != MAGIC {
+            return Err(anyhow::anyhow!("not an LLSD journal: bad magic"));
+        }
+        Ok(Self {
+            inner,
+            valid_len: MAGIC.len() as u64,
+            truncated: false,
+            done: false,
+            payload: Vec::new(),
+        })
+    }
+
+    /// Bytes from the start of the journal to the end of the last record
+    /// read intact: where appending should resume.
+    pub fn valid_len(&self) -> u64 {
+        self.valid_len
+    }
+
+    /// Whether reading stopped at a partial record.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Fill `buf`, returning `false` on end of input (partway or not).
+    fn fill(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..]) {
+                Ok(0) => return Ok(false),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn next_record(&mut self) -> Result<Option<Llsd>, anyhow::Error> {
+        let mut header = [0u8; RECORD_HEADER];
+        let mut first = [0u8; 1];
+        // A clean end falls between records.
+        if !self.fill(&mut first)? {
+            return Ok(None);
+        }
+        header[0] = first[0];
+        let mut payload = std::mem::take(&mut self.payload);
+        let complete = self.fill(&mut header[1..])? && {
+            let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            if len > MAX_RECORD_LENGTH {
+                return Err(anyhow::anyhow!(
+                    "journal record at byte {} has length {len}, over max {MAX_RECORD_LENGTH}",
+                    self.valid_len
+                ));
+            }
+            payload.resize(len, 0);
+            self.fill(&mut payload)?
+        };
+        if !complete {
+            self.truncated = true;
+            return Ok(None);
+        }
+        let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if crc32(&payload) != crc {
+            return Err(anyhow::anyhow!(
+                "journal record at byte {} fails its checksum",
+                self.valid_len
+            ));
+        }
+        let llsd = crate::binary::from_slice(&payload)
+            .with_context(|| format!("decoding journal record at byte {}", self.valid_len))?;
+        self.valid_len += (RECORD_HEADER + payload.len()) as u64;
+        self.payload = payload;
+        Ok(Some(llsd))
+    }
+}
+
+impl<R: Read> Iterator for JournalReader<R> {
+    type Item = Result<Llsd, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.next_record().transpose();
+        self.done = !matches!(record, Some(Ok(_)));
+        record
+    }
+}
+
+/// Replay the journal at `path`.
+pub fn open(path: impl AsRef<Path>) -> Result<JournalReader<BufReader<File>>, anyhow::Error> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("opening journal {}", path.display()))?;
+    JournalReader::new(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<Llsd> {
+        vec![
+            Llsd::String("multi\nline".into()),
+            Llsd::Binary(vec![0, b'\n', 0xff]),
+            Llsd::Array(vec![Llsd::Integer(3), Llsd::Undefined]),
+        ]
+    }
+
+    fn journal() -> Vec<u8> {
+        let mut writer = JournalWriter::new(Vec::new()).unwrap();
+        for record in records() {
+            writer.append(&record).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn crc32_matches_zlib() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn partial_record_at_end_is_dropped() {
+        let bytes = journal();
+        let mut reader = JournalReader::new(&bytes[..]).unwrap();
+        assert_eq!(
+            reader.by_ref().map(Result::unwrap).collect::<Vec<_>>(),
+            records()
+        );
+        assert!(!reader.is_truncated());
+        assert_eq!(reader.valid_len(), bytes.len() as u64);
+
+        for cut in 1..12 {
+            let mut reader = JournalReader::new(&bytes[..bytes.len() - cut]).unwrap();
+            let read: Vec<_> = reader.by_ref().map(Result::unwrap).collect();
+            assert_eq!(read, records()[..2], "cut {cut}");
+            assert!(reader.is_truncated());
+            assert!(reader.valid_len() < (bytes.len() - cut) as u64);
+        }
+    }
+
+    #[test]
+    fn corruption_is_an_error() {
+        let mut bytes = journal();
+        let first_len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let second = MAGIC.len() + RECORD_HEADER + first_len;
+        bytes[second + RECORD_HEADER] ^= 0x40;
+        let mut reader = JournalReader::new(&bytes[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{err}");
+        assert!(reader.next().is_none());
+
+        assert!(JournalReader::new(&b"LLSDJRN0"[..]).is_err());
+    }
+
+    #[test]
+    fn reopened_file_resumes_after_last_intact_record() {
+        let path = std::env::temp_dir().join(format!("llsd-rs-journal-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut writer = JournalWriter::open(&path).unwrap();
+        for record in records() {
+            writer.append(&record).unwrap();
+        }
+        writer.sync().unwrap();
+        drop(writer);
+
+        // Tear the last record, as a crash mid-append would.
+        let len = std::fs::metadata(&path).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+        let mut writer = JournalWriter::open(&path).unwrap();
+        writer.append(&Llsd::Boolean(true)).unwrap();
+        writer.sync().unwrap();
+        drop(writer);
+
+        let replayed: Vec<_> = open(&path).unwrap().map(Result::unwrap).collect();
+        let mut expected = records()[..2].to_vec();
+        expected.push(Llsd::Boolean(true));
+        assert_eq!(replayed, expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
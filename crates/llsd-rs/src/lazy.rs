@@ -0,0 +1,691 @@
+//! Deferred decoding of binary and notation documents.
+//!
+//! [`LazyLlsd`] borrows an encoded buffer and only records where values start
+//! and end. A container indexes its direct children the first time one of
+//! them is asked for, stepping over nested values without building them; the
+//! real parser only runs for the subtrees passed to [`LazyLlsd::decode`].
+//!
+//! ```
+//! use llsd_rs::{Llsd, LlsdMap, binary, lazy::LazyLlsd};
+//!
+//! let mut map = LlsdMap::new();
+//! map.insert("message".into(), Llsd::String("ChatterBoxEvents".into()));
+//! map.insert("events".into(), Llsd::Array(vec![Llsd::Integer(1); 1000]));
+//! let data = binary::to_vec(&Llsd::Map(map)).unwrap();
+//!
+//! let lazy = LazyLlsd::from_binary(&data).unwrap();
+//! let message: String = lazy.get("message").unwrap().decode_as().unwrap();
+//! assert_eq!(message, "ChatterBoxEvents");
+//! assert_eq!(lazy.get("events").unwrap().len().unwrap(), 1000);
+//! ```
+
+use std::{borrow::Cow, sync::OnceLock};
+
+use crate::{
+    FromLlsd, Llsd, LlsdError, LlsdErrorKind,
+    autodetect::{self, LlsdEncoding},
+    binary, escape, notation,
+    scalar::LlsdType,
+};
+
+const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Arrays shorter than this are not worth handing to the thread pool.
+#[cfg(feature = "rayon")]
+const PAR_MIN_LEN: usize = 64;
+
+/// An encoded value whose subtrees are decoded on demand.
+pub struct LazyLlsd<'a> {
+    encoding: LlsdEncoding,
+    /// Starts at this value; for the root it also covers anything after it.
+    data: &'a [u8],
+    children: OnceLock<Result<Children<'a>, String>>,
+}
+
+enum Children<'a> {
+    Array(Vec<LazyLlsd<'a>>),
+    Map(Vec<(Cow<'a, str>, LazyLlsd<'a>)>),
+    Scalar,
+}
+
+impl<'a> LazyLlsd<'a> {
+    /// Wrap a binary or notation document, skipping its `<? ... ?>` header if
+    /// it has one. XML is rejected.
+    pub fn new(data: &'a [u8], encoding: LlsdEncoding) -> anyhow::Result<Self> {
+        let mut data = autodetect::payload_after_header(data, encoding);
+        match encoding {
+            LlsdEncoding::Binary => {}
+            LlsdEncoding::Notation => data = data.trim_ascii_start(),
+            LlsdEncoding::Xml => {
+                return Err(anyhow::anyhow!(
+                    "LazyLlsd supports binary and notation, not XML"
+                ));
+            }
+        }
+        let Some(&tag) = data.first() else {
+            return Err(anyhow::anyhow!("LazyLlsd: empty input"));
+        };
+        if type_name(encoding, tag).is_none() {
+            return Err(anyhow::anyhow!("LazyLlsd: unknown LLSD type: 0x{tag:02x}"));
+        }
+        Ok(Self::at(encoding, data))
+    }
+
+    pub fn from_binary(data: &'a [u8]) -> anyhow::Result<Self> {
+        Self::new(data, LlsdEncoding::Binary)
+    }
+
+    pub fn from_notation(data: &'a [u8]) -> anyhow::Result<Self> {
+        Self::new(data, LlsdEncoding::Notation)
+    }
+
+    /// Like [`new`](Self::new), detecting the encoding the way
+    /// [`autodetect::from_slice`] does.
+    pub fn from_slice(data: &'a [u8]) -> anyhow::Result<Self> {
+        Self::new(data, autodetect::detect_format(data))
+    }
+
+    fn at(encoding: LlsdEncoding, data: &'a [u8]) -> Self {
+        Self {
+            encoding,
+            data,
+            children: OnceLock::new(),
+        }
+    }
+
+    pub fn encoding(&self) -> LlsdEncoding {
+        self.encoding
+    }
+
+    /// The encoded bytes this value starts at.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Same names as [`Llsd::type_name`], read from the value's leading byte.
+    pub fn type_name(&self) -> &'static str {
+        type_name(self.encoding, self.data[0]).unwrap_or("unknown")
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.type_name() == "array"
+    }
+
+    pub fn is_map(&self) -> bool {
+        self.type_name() == "map"
+    }
+
+    /// Number of entries in an array or map; 0 for anything else.
+    pub fn len(&self) -> anyhow::Result<usize> {
+        Ok(match self.children()? {
+            Children::Array(items) => items.len(),
+            Children::Map(entries) => entries.len(),
+            Children::Scalar => 0,
+        })
+    }
+
+    pub fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The value under `key`, indexing this map on first use. If the key
+    /// repeats, the last one wins, as when decoding.
+    pub fn get(&self, key: &str) -> anyhow::Result<&LazyLlsd<'a>> {
+        match self.children()? {
+            Children::Map(entries) => entries
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value)
+                .ok_or_else(|| LlsdError::missing_field(key).into()),
+            _ => Err(self.invalid_type("map").into()),
+        }
+    }
+
+    /// The array element at `index`, indexing this array on first use.
+    pub fn get_index(&self, index: usize) -> anyhow::Result<&LazyLlsd<'a>> {
+        match self.children()? {
+            Children::Array(items) => items.get(index).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "LazyLlsd: index {index} out of bounds for array of length {}",
+                    items.len()
+                )
+            }),
+            _ => Err(self.invalid_type("array").into()),
+        }
+    }
+
+    /// Keys of this map in document order.
+    pub fn keys(&self) -> anyhow::Result<impl Iterator<Item = &str>> {
+        match self.children()? {
+            Children::Map(entries) => Ok(entries.iter().map(|(k, _)| k.as_ref())),
+            _ => Err(self.invalid_type("map").into()),
+        }
+    }
+
+    /// Parse this value and everything under it.
+    pub fn decode(&self) -> anyhow::Result<Llsd> {
+        self.decode_with_depth(DEFAULT_MAX_DEPTH)
+    }
+
+    /// The limit to decode a child with so the whole value gets `max_depth`.
+    /// `notation::from_bytes` does not lower its limit per level, so
+    /// notation children get the same one.
+    #[cfg(feature = "rayon")]
+    fn child_depth(&self, max_depth: usize) -> usize {
+        match self.encoding {
+            LlsdEncoding::Binary => max_depth.saturating_sub(1),
+            _ => max_depth,
+        }
+    }
+
+    fn decode_with_depth(&self, max_depth: usize) -> anyhow::Result<Llsd> {
+        match self.encoding {
+            LlsdEncoding::Binary => binary::from_slice_with_depth(self.data, max_depth),
+            LlsdEncoding::Notation => Ok(notation::from_bytes(self.data, max_depth)?),
+            LlsdEncoding::Xml => unreachable!("rejected by LazyLlsd::new"),
+        }
+    }
+
+    /// Parse this value and convert it with [`FromLlsd`].
+    pub fn decode_as<T: FromLlsd>(&self) -> anyhow::Result<T> {
+        Ok(T::from_llsd_owned(self.decode()?)?)
+    }
+
+    fn invalid_type(&self, expected: &'static str) -> LlsdError {
+        LlsdError::new(LlsdErrorKind::InvalidType {
+            expected,
+            found: self.type_name(),
+        })
+    }
+
+    fn children(&self) -> anyhow::Result<&Children<'a>> {
+        self.children
+            .get_or_init(|| {
+                let mut cursor = Cursor {
+                    data: self.data,
+                    pos: 0,
+                };
+                match self.encoding {
+                    LlsdEncoding::Binary => index_binary(&mut cursor),
+                    _ => index_notation(&mut cursor),
+                }
+                .map_err(|err| err.to_string())
+            })
+            .as_ref()
+            .map_err(|err| anyhow::anyhow!("{err}"))
+    }
+}
+
+/// Decode `lazy`, spreading the elements of a top-level array, or of arrays
+/// directly under a top-level map, across the rayon thread pool.
+#[cfg(feature = "rayon")]
+pub(crate) fn decode_par(lazy: &LazyLlsd<'_>, max_depth: usize) -> anyhow::Result<Llsd> {
+    if max_depth == 0 {
+        return lazy.decode_with_depth(max_depth);
+    }
+    let child_depth = lazy.child_depth(max_depth);
+    match lazy.children()? {
+        Children::Array(_) => decode_array_par(lazy, max_depth),
+        Children::Map(entries) => {
+            let mut map = crate::LlsdMap::with_capacity(entries.len());
+            for (key, value) in entries {
+                let value = if value.is_array() {
+                    decode_array_par(value, child_depth)?
+                } else {
+                    value.decode_with_depth(child_depth)?
+                };
+                map.insert(key.to_string(), value);
+            }
+            Ok(Llsd::Map(map))
+        }
+        Children::Scalar => lazy.decode_with_depth(max_depth),
+    }
+}
+
+#[cfg(feature = "rayon")]
+fn decode_array_par(lazy: &LazyLlsd<'_>, max_depth: usize) -> anyhow::Result<Llsd> {
+    use rayon::prelude::*;
+
+    let Children::Array(items) = lazy.children()? else {
+        return lazy.decode_with_depth(max_depth);
+    };
+    if max_depth == 0 || items.len() < PAR_MIN_LEN {
+        return lazy.decode_with_depth(max_depth);
+    }
+    let child_depth = lazy.child_depth(max_depth);
+    let items = items
+        .par_iter()
+        .with_min_len(PAR_MIN_LEN)
+        .map(|item| item.decode_with_depth(child_depth))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Llsd::Array(items))
+}
+
+impl std::fmt::Debug for LazyLlsd<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyLlsd")
+            .field("encoding", &self.encoding)
+            .field("type", &self.type_name())
+            .finish_non_exhaustive()
+    }
+}
+
+fn type_name(encoding: LlsdEncoding, tag: u8) -> Option<&'static str> {
+    LlsdType::from_tag(encoding, tag).map(LlsdType::name)
+}
+
+pub(crate) struct Cursor<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    pub(crate) fn next(&mut self) -> anyhow::Result<u8> {
+        let byte = self
+            .peek()
+            .ok_or_else(|| anyhow::anyhow!("LLSD: unexpected end of input"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| anyhow::anyhow!("LLSD: length {len} exceeds remaining input"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn expect(&mut self, expected: &[u8]) -> anyhow::Result<u8> {
+        let byte = self.next()?;
+        if !expected.contains(&byte) {
+            return Err(anyhow::anyhow!(
+                "LLSD: expected one of {:?} at byte {}, found 0x{byte:02x}",
+                String::from_utf8_lossy(expected),
+                self.pos - 1
+            ));
+        }
+        Ok(byte)
+    }
+
+    /// Consume the rest of `true` or `false` (`tail`, in any case) unless the
+    /// value was written as the bare letter.
+    pub(crate) fn keyword_tail(&mut self, tail: &[u8]) -> anyhow::Result<()> {
+        if self
+            .peek()
+            .is_some_and(|c| c.eq_ignore_ascii_case(&tail[0]))
+        {
+            for &c in tail {
+                self.expect(&[c, c.to_ascii_uppercase()])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn skip_while(&mut self, pred: impl Fn(u8) -> bool) {
+        while self.peek().is_some_and(&pred) {
+            self.pos += 1;
+        }
+    }
+
+    pub(crate) fn skip_ws(&mut self) {
+        self.skip_while(|c| c.is_ascii_whitespace());
+    }
+
+    /// Step over a quoted string whose opening delimiter was consumed,
+    /// returning its raw (still escaped) contents.
+    pub(crate) fn skip_escaped(&mut self, delim: u8) -> anyhow::Result<&'a [u8]> {
+        let start = self.pos;
+        loop {
+            match self.next()? {
+                c if c == delim => return Ok(&self.data[start..self.pos - 1]),
+                b'\\' => {
+                    self.next()?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn binary_len(&mut self) -> anyhow::Result<usize> {
+        let len = i32::from_be_bytes(self.take(4)?.try_into()?);
+        usize::try_from(len).map_err(|_| anyhow::anyhow!("LLSD: negative length {len}"))
+    }
+
+    /// `(N)"..."` in notation, after the leading `s` or `b`.
+    pub(crate) fn notation_sized(&mut self) -> anyhow::Result<&'a [u8]> {
+        self.expect(b"(")?;
+        let start = self.pos;
+        self.skip_while(|c| c != b')');
+        let len: usize = std::str::from_utf8(&self.data[start..self.pos])?.parse()?;
+        self.expect(b")")?;
+        self.expect(b"\"'")?;
+        let bytes = self.take(len)?;
+        self.expect(b"\"'")?;
+        Ok(bytes)
+    }
+}
+
+fn index_binary<'a>(cursor: &mut Cursor<'a>) -> anyhow::Result<Children<'a>> {
+    match cursor.next()? {
+        b'[' => {
+            let len = cursor.binary_len()?;
+            let mut items = Vec::with_capacity(len.min(cursor.data.len()));
+            for _ in 0..len {
+                items.push(LazyLlsd::at(
+                    LlsdEncoding::Binary,
+                    &cursor.data[cursor.pos..],
+                ));
+                skip_binary(cursor)?;
+            }
+            cursor.expect(b"]")?;
+            Ok(Children::Array(items))
+        }
+        b'{' => {
+            let len = cursor.binary_len()?;
+            let mut entries = Vec::with_capacity(len.min(cursor.data.len()));
+            for _ in 0..len {
+                cursor.expect(b"k")?;
+                let key_len = cursor.binary_len()?;
+                let key = std::str::from_utf8(cursor.take(key_len)?)?;
+                let value = LazyLlsd::at(LlsdEncoding::Binary, &cursor.data[cursor.pos..]);
+                entries.push((Cow::Borrowed(key), value));
+                skip_binary(cursor)?;
+            }
+            cursor.expect(b"}")?;
+            Ok(Children::Map(entries))
+        }
+        _ => Ok(Children::Scalar),
+    }
+}
+
+/// Step over one binary value without decoding it.
+fn skip_binary(cursor: &mut Cursor<'_>) -> anyhow::Result<()> {
+    let mut depth = 0usize;
+    loop {
+        match cursor.next()? {
+            b'!' | b'0' | b'1' => {}
+            b'i' => {
+                cursor.take(4)?;
+            }
+            b'r' | b'd' => {
+                cursor.take(8)?;
+            }
+            b'u' => {
+                cursor.take(16)?;
+            }
+            b's' | b'l' | b'b' => {
+                let len = cursor.binary_len()?;
+                cursor.take(len)?;
+            }
+            delim @ (b'"' | b'\'') => {
+                cursor.skip_escaped(delim)?;
+            }
+            b'[' | b'{' => {
+                cursor.take(4)?;
+                depth += 1;
+                continue;
+            }
+            b'k' if depth > 0 => {
+                let len = cursor.binary_len()?;
+                cursor.take(len)?;
+                continue;
+            }
+            b']' | b'}' if depth > 0 => depth -= 1,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "LazyLlsd: unknown LLSD type: 0x{other:02x}"
+                ));
+            }
+        }
+        if depth == 0 {
+            return Ok(());
+        }
+    }
+}
+
+fn index_notation<'a>(cursor: &mut Cursor<'a>) -> anyhow::Result<Children<'a>> {
+    match cursor.next()? {
+        b'[' => {
+            let mut items = Vec::new();
+            loop {
+                cursor.skip_ws();
+                match cursor.peek() {
+                    Some(b']') => break,
+                    Some(b',') => cursor.pos += 1,
+                    _ => {
+                        items.push(LazyLlsd::at(
+                            LlsdEncoding::Notation,
+                            &cursor.data[cursor.pos..],
+                        ));
+                        skip_notation(cursor)?;
+                    }
+                }
+            }
+            Ok(Children::Array(items))
+        }
+        b'{' => {
+            let mut entries = Vec::new();
+            loop {
+                cursor.skip_ws();
+                let key = match cursor.next()? {
+                    b'}' => break,
+                    b',' => continue,
+                    b's' => Cow::Borrowed(std::str::from_utf8(cursor.notation_sized()?)?),
+                    delim @ (b'\'' | b'"') => {
+                        escape::unescape_notation(cursor.skip_escaped(delim)?)?
+                    }
+                    other => {
+                        return Err(anyhow::anyhow!(
+                            "LazyLlsd: invalid character in map: 0x{other:02x}"
+                        ));
+                    }
+                };
+                cursor.skip_ws();
+                cursor.expect(b":")?;
+                cursor.skip_ws();
+                let value = LazyLlsd::at(LlsdEncoding::Notation, &cursor.data[cursor.pos..]);
+                entries.push((key, value));
+                skip_notation(cursor)?;
+            }
+            Ok(Children::Map(entries))
+        }
+        _ => Ok(Children::Scalar),
+    }
+}
+
+/// Step over one notation value without decoding it. Only the structure is
+/// checked; [`LazyLlsd::decode`] reports malformed scalars.
+fn skip_notation(cursor: &mut Cursor<'_>) -> anyhow::Result<()> {
+    let mut depth = 0usize;
+    loop {
+        cursor.skip_ws();
+        match cursor.next()? {
+            b'[' | b'{' => {
+                depth += 1;
+                continue;
+            }
+            b',' | b':' if depth > 0 => continue,
+            b']' | b'}' if depth > 0 => depth -= 1,
+            b'!' | b'0' | b'1' => {}
+            b'i' | b'I' => cursor.skip_while(|c| matches!(c, b'0'..=b'9' | b'+' | b'-')),
+            b'r' | b'R' => cursor.skip_while(|c| b"+-.0123456789eEinfINFaA".contains(&c)),
+            b'u' | b'U' => cursor.skip_while(|c| c.is_ascii_hexdigit() || c == b'-'),
+            b't' | b'T' => cursor.keyword_tail(b"rue")?,
+            b'f' | b'F' => cursor.keyword_tail(b"alse")?,
+            delim @ (b'\'' | b'"') => {
+                cursor.skip_escaped(delim)?;
+            }
+            b's' => {
+                cursor.notation_sized()?;
+            }
+            b'l' | b'L' | b'd' | b'D' => {
+                cursor.expect(b"\"")?;
+                cursor.skip_escaped(b'"')?;
+            }
+            b'b' | b'B' => {
+                if cursor.peek() == Some(b'(') {
+                    cursor.notation_sized()?;
+                } else {
+                    cursor.expect(b"1")?;
+                    cursor.expect(b"6")?;
+                    cursor.expect(b"\"")?;
+                    cursor.skip_while(|c| c != b'"');
+                    cursor.expect(b"\"")?;
+                }
+            }
+            other => {
+                return Err(anyhow::anyhow!(
+                    "LazyLlsd: invalid character: 0x{other:02x}"
+                ));
+            }
+        }
+        if depth == 0 {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uuid;
+    use crate::{Date, LlsdMap};
+
+    fn sample() -> Llsd {
+        let mut body = LlsdMap::new();
+        body.insert("agent_id".into(), Llsd::Uuid(Uuid::from_u128(7)));
+        body.insert("region".into(), Llsd::String("Ahern \"west\"".into()));
+        body.insert("blob".into(), Llsd::Binary(b"]}{[".to_vec()));
+        body.insert(
+            "when".into(),
+            Llsd::Date(Date::from_timestamp(1_620_000_000, 0).unwrap()),
+        );
+        body.insert("empty".into(), Llsd::Array(vec![]));
+        let mut root = LlsdMap::new();
+        root.insert("message".into(), Llsd::String("TeleportFinish".into()));
+        root.insert("quoted 'key'".into(), Llsd::Boolean(true));
+        root.insert(
+            "events".into(),
+            Llsd::Array(vec![Llsd::Map(body), Llsd::Real(1.5), Llsd::Undefined]),
+        );
+        Llsd::Map(root)
+    }
+
+    fn check(lazy: &LazyLlsd<'_>, expected: &Llsd) {
+        assert!(lazy.is_map());
+        assert_eq!(lazy.len().unwrap(), 3);
+        assert_eq!(&lazy.decode().unwrap(), expected);
+        let message: String = lazy.get("message").unwrap().decode_as().unwrap();
+        assert_eq!(message, "TeleportFinish");
+        assert!(
+            lazy.get("quoted 'key'")
+                .unwrap()
+                .decode_as::<bool>()
+                .unwrap()
+        );
+
+        let events = lazy.get("events").unwrap();
+        assert_eq!(events.type_name(), "array");
+        assert_eq!(events.len().unwrap(), 3);
+        assert_eq!(&events.decode().unwrap(), expected.get("events").unwrap());
+        let body = events.get_index(0).unwrap();
+        assert_eq!(
+            body.get("agent_id").unwrap().decode().unwrap(),
+            Llsd::Uuid(Uuid::from_u128(7))
+        );
+        let mut keys: Vec<_> = body.keys().unwrap().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["agent_id", "blob", "empty", "region", "when"]);
+        assert_eq!(events.get_index(1).unwrap().type_name(), "real");
+        assert_eq!(
+            events.get_index(2).unwrap().decode().unwrap(),
+            Llsd::Undefined
+        );
+        assert!(events.get_index(3).is_err());
+        assert!(events.get("message").is_err());
+
+        let err = LlsdError::from_anyhow(lazy.get("missing").unwrap_err());
+        assert_eq!(err.kind(), &LlsdErrorKind::MissingField);
+    }
+
+    #[test]
+    fn binary_subtrees() {
+        let llsd = sample();
+        let mut data = b"<? LLSD/Binary ?>\n".to_vec();
+        data.extend(binary::to_vec(&llsd).unwrap());
+        check(&LazyLlsd::from_slice(&data).unwrap(), &llsd);
+    }
+
+    #[test]
+    fn notation_subtrees() {
+        let llsd = sample();
+        for context in [
+            notation::FormatterContext::new(),
+            notation::FormatterContext::new()
+                .with_pretty(true)
+                .with_hex(true),
+        ] {
+            let data = notation::to_vec(&llsd, &context).unwrap();
+            check(&LazyLlsd::from_notation(&data).unwrap(), &llsd);
+        }
+    }
+
+    #[test]
+    fn scalars_have_no_children() {
+        let data = binary::to_vec(&Llsd::Integer(5)).unwrap();
+        let lazy = LazyLlsd::from_binary(&data).unwrap();
+        assert_eq!(lazy.len().unwrap(), 0);
+        assert_eq!(lazy.decode_as::<i32>().unwrap(), 5);
+        assert!(lazy.get("x").is_err());
+
+        assert!(LazyLlsd::from_notation(b"  ").is_err());
+        assert!(LazyLlsd::from_slice(b"<llsd><undef/></llsd>").is_err());
+    }
+
+    #[test]
+    fn truncated_input_fails_on_access() {
+        let data = binary::to_vec(&sample()).unwrap();
+        let lazy = LazyLlsd::from_binary(&data[..data.len() / 2]).unwrap();
+        assert!(lazy.get("events").is_err());
+        assert!(lazy.len().is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_decode_matches_sequential() {
+        let objects: Vec<Llsd> = (0..5000)
+            .map(|i| {
+                let mut object = LlsdMap::new();
+                object.insert("local_id".into(), Llsd::Integer(i));
+                object.insert("name".into(), Llsd::String(format!("prim {i}")));
+                Llsd::Map(object)
+            })
+            .collect();
+        let mut snapshot = LlsdMap::new();
+        snapshot.insert("region".into(), Llsd::String("Ahern".into()));
+        snapshot.insert("objects".into(), Llsd::Array(objects.clone()));
+        let context = notation::FormatterContext::new();
+        for llsd in [Llsd::Array(objects), Llsd::Map(snapshot)] {
+            let encoded = binary::to_vec(&llsd).unwrap();
+            assert_eq!(binary::from_slice_par(&encoded).unwrap(), llsd);
+            let encoded = notation::to_vec(&llsd, &context).unwrap();
+            assert_eq!(notation::from_bytes_par(&encoded, 1).unwrap(), llsd);
+        }
+
+        let broken = notation::to_vec(&Llsd::Array(vec![Llsd::Integer(1); 100]), &context)
+            .unwrap()
+            .iter()
+            .map(|&c| if c == b'1' { b'x' } else { c })
+            .collect::<Vec<_>>();
+        assert!(notation::from_bytes_par(&broken, 64).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! The Linden legacy "keyword-value block" text format, used by wearables,
+//! task inventories and serialized objects.
+//!
+//! Each line holds a keyword and, after a tab or spaces, a value running to
+//! the end of the line. A line with `{` opens a block of further lines that
+//! belongs to the entry before it, and `}` closes it:
+//!
+//! ```text
+//!     inv_item    0
+//!     {
+//!         item_id    d2a8a5f1-7a44-4e24-a2e2-1f4b2c0b59b4
+//!         permissions 0
+//!         {
+//!             base_mask    7fffffff
+//!         }
+//!         name    Box|
+//!     }
+//! ```
+//!
+//! A block reads as an [`Llsd::Array`] of entries in their order (keywords
+//! can repeat), each entry an array of the keyword and the value as strings,
+//! with the entry's block as a third element when it has one. Values are
+//! the raw rest of the line, `|` terminators included, since their type
+//! depends on the keyword; embedded LLSD fragments can be decoded with
+//! [`autodetect::from_slice`](crate::autodetect::from_slice).
+//!
+//! ```
+//! use llsd_rs::{legacy, llsd_array};
+//!
+//! let text = "permissions 0\n{\n\tbase_mask\t7fffffff\n}\nname\tBox|\n";
+//! let doc = legacy::from_str(text, 8).unwrap();
+//! assert_eq!(doc, llsd_array![
+//!     llsd_array!["permissions", "0", llsd_array![llsd_array!["base_mask", "7fffffff"]]],
+//!     llsd_array!["name", "Box|"],
+//! ]);
+//! assert_eq!(legacy::from_str(&legacy::to_string(&doc).unwrap(), 8).unwrap(), doc);
+//! ```
+
+use std::io::{self, Read, Write};
+
+use crate::{
+    Llsd,
+    notation::{ParseErrorKind, ParseResult, Stream},
+};
+
+pub fn from_reader<R: Read>(reader: R, max_depth: usize) -> ParseResult<Llsd> {
+    let mut stream = Stream::new(reader);
+    read_block(&mut stream, 0, max_depth).map(Llsd::Array)
+}
+
+pub fn from_str(s: &str, max_depth: usize) -> ParseResult<Llsd> {
+    from_reader(s.as_bytes(), max_depth)
+}
+
+pub fn from_bytes(bytes: &[u8], max_depth: usize) -> ParseResult<Llsd> {
+    from_reader(bytes, max_depth)
+}
+
+/// Read entries up to the end of input, at the top level, or up to the
+/// closing `}` of a block.
+fn read_block<R: Read>(
+    stream: &mut Stream<'_, R>,
+    depth: usize,
+    max_depth: usize,
+) -> ParseResult<Vec<Llsd>> {
+    let mut entries: Vec<Llsd> = Vec::new();
+    loop {
+        stream.skip_while(|c| c.is_ascii_whitespace())?;
+        match stream.peek()? {
+            None if depth == 0 => return Ok(entries),
+            None => return Err(stream.error(ParseErrorKind::Eof)),
+            Some(b'}') if depth > 0 => {
+                stream.next()?;
+                return Ok(entries);
+            }
+            Some(b'}') => {
+                return Err(stream.error(ParseErrorKind::Expected(
+                    "a keyword, found '}' outside a block".into(),
+                )));
+            }
+            Some(b'{') => {
+                let entry = match entries.last_mut() {
+                    Some(Llsd::Array(entry)) if entry.len() == 2 => entry,
+                    _ => {
+                        return Err(stream
+                            .error(ParseErrorKind::Expected("a keyword line before '{'".into())));
+                    }
+                };
+                if depth >= max_depth {
+                    return Err(stream.error(ParseErrorKind::MaxDepth));
+                }
+                stream.next()?;
+                entry.push(Llsd::Array(read_block(stream, depth + 1, max_depth)?));
+            }
+            Some(_) => {
+                let keyword = stream.take_while(|c| !c.is_ascii_whitespace())?;
+                let keyword = stream.parse_utf8(keyword)?;
+                stream.skip_while(|c| c == b' ' || c == b'\t')?;
+                let mut value = stream.take_while(|c| c != b'\n')?;
+                if value.last() == Some(&b'\r') {
+                    value.pop();
+                }
+                let value = stream.parse_utf8(value)?;
+                entries.push(Llsd::Array(vec![
+                    Llsd::String(keyword),
+                    Llsd::String(value),
+                ]));
+            }
+        }
+    }
+}
+
+/// Write `llsd`, shaped as [`from_reader`] returns it, with one tab between
+/// keyword and value and blocks indented by tabs.
+pub fn write<W: Write>(llsd: &Llsd, w: &mut W) -> Result<(), io::Error> {
+    write_block(llsd, w, 0)
+}
+
+pub fn to_vec(llsd: &Llsd) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+    write(llsd, &mut buffer)?;
+    Ok(buffer)
+}
+
+pub fn to_string(llsd: &Llsd) -> Result<String, io::Error> {
+    let buffer = to_vec(llsd)?;
+    String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_block<W: Write>(block: &Llsd, w: &mut W, depth: usize) -> Result<(), io::Error> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let Llsd::Array(entries) = block else {
+        return Err(invalid(format!(
+            "a legacy block is an array of entries, found {}",
+            block.type_name()
+        )));
+    };
+    for entry in entries {
+        let (keyword, value, children) = match entry {
+            Llsd::Array(entry) => match entry.as_slice() {
+                [Llsd::String(keyword), Llsd::String(value)] => (keyword, value, None),
+                [Llsd::String(keyword), Llsd::String(value), children] => {
+                    (keyword, value, Some(children))
+                }
+                _ => {
+                    return Err(invalid(
+                        "a legacy entry is [keyword, value] or [keyword, value, block], \
+                         with a string keyword and value"
+                            .into(),
+                    ));
+                }
+            },
+            _ => {
+                return Err(invalid(format!(
+                    "a legacy entry is an array, found {}",
+                    entry.type_name()
+                )));
+            }
+        };
+        if keyword.is_empty()
+            || keyword.starts_with(['{', '}'])
+            || keyword.bytes().any(|c| c.is_ascii_whitespace())
+        {
+            return Err(invalid(format!("invalid legacy keyword {keyword:?}")));
+        }
+        if value.contains('\n') || value.ends_with('\r') {
+            return Err(invalid(format!("legacy value of {keyword} spans lines")));
+        }
+        write_indent(w, depth)?;
+        w.write_all(keyword.as_bytes())?;
+        if !value.is_empty() {
+            w.write_all(b"\t")?;
+            w.write_all(value.as_bytes())?;
+        }
+        w.write_all(b"\n")?;
+        if let Some(children) = children {
+            write_indent(w, depth)?;
+            w.write_all(b"{\n")?;
+            write_block(children, w, depth + 1)?;
+            write_indent(w, depth)?;
+            w.write_all(b"}\n")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_indent<W: Write>(w: &mut W, depth: usize) -> Result<(), io::Error> {
+    for _ in 0..depth {
+        w.write_all(b"\t")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llsd_array;
+
+    const TASK_INVENTORY: &str = "\tinv_object\t0\r
+\t{\r
+\t\tobj_id\t5e7bd9a4-0b7c-4f5e-9a55-1b2f9d3c7e10\r
+\t\tname\tContents|\r
+\t}\r
+\tinv_item\t0
+\t{
+\t\tpermissions 0
+\t\t{
+\t\t\tbase_mask\t7fffffff
+\t\t\tnext_owner_mask\t00082000
+\t\t}
+\t\tname\tBox   |
+\t\tdesc\t|
+\t\tflag
+\t}
+";
+
+    #[test]
+    fn reads_and_writes_blocks() {
+        let doc = from_str(TASK_INVENTORY, 8).unwrap();
+        assert_eq!(
+            doc,
+            llsd_array![
+                llsd_array![
+                    "inv_object",
+                    "0",
+                    llsd_array![
+                        llsd_array!["obj_id", "5e7bd9a4-0b7c-4f5e-9a55-1b2f9d3c7e10"],
+                        llsd_array!["name", "Contents|"],
+                    ],
+                ],
+                llsd_array![
+                    "inv_item",
+                    "0",
+                    llsd_array![
+                        llsd_array![
+                            "permissions",
+                            "0",
+                            llsd_array![
+                                llsd_array!["base_mask", "7fffffff"],
+                                llsd_array!["next_owner_mask", "00082000"],
+                            ],
+                        ],
+                        llsd_array!["name", "Box   |"],
+                        llsd_array!["desc", "|"],
+                        llsd_array!["flag", ""],
+                    ],
+                ],
+            ]
+        );
+        let written = to_string(&doc).unwrap();
+        assert!(written.starts_with("inv_object\t0\n{\n\tobj_id\t"));
+        assert!(written.contains("\t{\n\t\tbase_mask\t7fffffff\n"));
+        assert!(written.ends_with("\tflag\n}\n"));
+        assert_eq!(from_str(&written, 8).unwrap(), doc);
+        assert_eq!(from_str(" \n", 8).unwrap(), llsd_array![]);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let err = from_str("a 1\n{\nb 2\n", 8).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Eof);
+        let err = from_str("a 1\n}\n", 8).unwrap_err();
+        assert_eq!((err.pos.line, err.pos.column), (2, 1));
+        assert!(from_str("{\n}\n", 8).is_err());
+        assert!(from_str("a 1\n{\n}\n{\n}\n", 8).is_err());
+        let err = from_str("a 1\n{\nb 2\n{\n}\n}\n", 1).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MaxDepth);
+        assert!(from_bytes(b"a \xff\n", 8).is_err());
+
+        for doc in [
+            Llsd::Integer(1),
+            llsd_array![1],
+            llsd_array![llsd_array!["a"]],
+            llsd_array![llsd_array!["a b", "1"]],
+            llsd_array![llsd_array!["{", "1"]],
+            llsd_array![llsd_array!["a", "1\n2"]],
+            llsd_array![llsd_array!["a", "1", 2]],
+        ] {
+            let err = to_vec(&doc).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{doc:?}");
+        }
+    }
+}
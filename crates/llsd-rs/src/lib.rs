@@ -1,20 +1,102 @@
 use std::{collections::HashMap, ops};
 
 use anyhow::Result;
-use chrono::{DateTime, FixedOffset, Utc};
 use enum_as_inner::EnumAsInner;
 use url::Url;
-use uuid::Uuid;
 
+// Lets the derives, which name `llsd_rs::...`, be used inside this crate.
+#[cfg(feature = "derive")]
+extern crate self as llsd_rs;
+
+#[cfg(feature = "anonymize")]
+pub mod anonymize;
+#[cfg(feature = "arena")]
+pub mod arena;
 pub mod autodetect;
 pub mod binary;
+pub mod cache;
+pub mod caps;
+#[cfg(feature = "client")]
+pub mod client;
+mod codec;
+pub mod codegen;
+pub mod compare;
+pub mod convert;
+pub mod corpus;
+pub mod date;
+mod debug;
 pub mod derive;
+pub mod error;
+pub mod escape;
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
+pub mod flags;
+pub mod journal;
+pub mod lazy;
+pub mod legacy;
+#[cfg(feature = "login")]
+pub mod login;
+mod macros;
+pub mod map;
+#[cfg(feature = "messages")]
+pub mod messages;
+pub mod metrics;
 pub mod notation;
+pub mod order;
+mod output;
+pub mod overlay;
+pub mod parse;
+pub mod pool;
 pub mod rpc;
+pub mod scalar;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod settings;
+pub mod shared;
+#[cfg(feature = "signed")]
+pub mod signed;
+pub mod spanned;
+mod template;
+#[cfg(feature = "test-server")]
+pub mod test_server;
+pub mod types;
+pub mod versioned;
+pub mod view;
 pub mod xml;
-
+pub mod xml_schema;
+
+#[cfg(feature = "arena")]
+pub use arena::LlsdRef;
+pub use convert::{FromLlsd, IntoLlsd};
+pub use date::Date;
+pub use debug::DebugLimited;
+pub use error::{LlsdError, LlsdErrorKind, PathSegment};
+pub use lazy::LazyLlsd;
 #[cfg(feature = "derive")]
 pub use llsd_rs_derive::{LlsdFrom, LlsdFromTo, LlsdInto};
+pub use map::LlsdMap;
+pub use shared::SharedLlsd;
+pub use types::Uuid;
+pub use view::LlsdView;
+
+/// Unescaped reference tokens of a JSON-pointer-style path, or `None` if it
+/// is neither empty nor starts with `/`.
+pub(crate) fn pointer_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return None;
+    }
+    Some(
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|x| x.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
 
 pub(crate) fn parse_i32_decimal_wrapping(input: &str) -> Result<i32> {
     let trimmed = input.trim();
@@ -46,6 +128,28 @@ pub(crate) fn parse_i32_decimal_wrapping(input: &str) -> Result<i32> {
     Ok(acc as i32)
 }
 
+/// An MSVC special real such as `1.#INF`, `-1.#QNAN` or `1.#IND00`, as
+/// printed by older Windows viewers.
+pub(crate) fn parse_msvc_real(input: &str) -> Option<f64> {
+    let (negative, rest) = match input.as_bytes().first()? {
+        b'-' => (true, &input[1..]),
+        b'+' => (false, &input[1..]),
+        _ => (false, input),
+    };
+    let word = rest.strip_prefix("1.#")?.trim_end_matches('0');
+    let value = if word.eq_ignore_ascii_case("inf") {
+        f64::INFINITY
+    } else if ["qnan", "snan", "ind"]
+        .iter()
+        .any(|nan| word.eq_ignore_ascii_case(nan))
+    {
+        f64::NAN
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
 fn coerce_string_to_i32(input: &str) -> i32 {
     if let Ok(v) = parse_i32_decimal_wrapping(input) {
         return v;
@@ -103,6 +207,83 @@ impl Uri {
             _ => None,
         }
     }
+
+    /// Try to repair text that didn't parse: surrounding whitespace is
+    /// dropped and spaces, non-ASCII characters and other bytes a URL can't
+    /// hold are percent-encoded (existing `%XX` escapes are kept) before
+    /// parsing again. The result is still a `Uri::String` when that isn't
+    /// enough, e.g. for a relative reference. URLs and empty URIs are
+    /// returned as they are.
+    pub fn sanitize(&self) -> Uri {
+        match self {
+            Uri::String(s, _) => Uri::parse(&percent_encode_uri(s.trim())),
+            uri => uri.clone(),
+        }
+    }
+
+    /// The text writers emit for this URI under `policy`; `None` when the
+    /// policy rejects it.
+    pub(crate) fn text_for(&self, policy: UriPolicy) -> Option<std::borrow::Cow<'_, str>> {
+        match (self, policy) {
+            (Uri::String(_, _), UriPolicy::Reject) => None,
+            (Uri::String(s, _), UriPolicy::PercentEncode) => {
+                Some(percent_encode_uri(s.trim()).into())
+            }
+            (uri, _) => Some(uri.as_str().into()),
+        }
+    }
+}
+
+/// How the notation and XML writers treat a [`Uri::String`], text that
+/// didn't parse as a URL and may not parse as one on the other end either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UriPolicy {
+    /// Write the text as it is.
+    #[default]
+    Verbatim,
+    /// Write it percent-encoded, as [`Uri::sanitize`] does.
+    PercentEncode,
+    /// Fail the write.
+    Reject,
+}
+
+/// Whether the writers emit map entries whose value is [`Llsd::Undefined`].
+///
+/// Every format carries such an entry (`!`, `<undef />`, `<nil />`) and
+/// reads it back as an entry, which a [`FromLlsd`] conversion or a peer may
+/// treat differently from a missing key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UndefinedEntries {
+    /// Write them, so the document keeps its keys.
+    #[default]
+    Keep,
+    /// Leave them out, as if the keys were missing.
+    Drop,
+}
+
+/// `uri` with every byte outside the URL unreserved and reserved sets
+/// percent-encoded, except `%` starting a valid escape.
+fn percent_encode_uri(uri: &str) -> String {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let bytes = uri.as_bytes();
+    let mut out = String::with_capacity(uri.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        let keep = match b {
+            b'%' => bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit)),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => true,
+            _ => b"-._~:/?#[]@!$&'()*+,;=".contains(&b),
+        };
+        if keep {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[usize::from(b >> 4)] as char);
+            out.push(HEX[usize::from(b & 0x0f)] as char);
+        }
+    }
+    out
 }
 
 impl From<Url> for Uri {
@@ -165,10 +346,12 @@ pub enum Llsd {
     String(String),
     Uri(Uri),
     Uuid(Uuid),
-    Date(DateTime<Utc>),
+    Date(Date),
     Binary(Vec<u8>),
+    // Not a `SmallVec`: inline `Llsd` storage inside `Llsd` would make the type
+    // infinitely sized, and boxing the elements costs more than it saves.
     Array(Vec<Llsd>),
-    Map(HashMap<String, Llsd>),
+    Map(LlsdMap),
 }
 
 impl Llsd {
@@ -181,7 +364,7 @@ impl Llsd {
     }
 
     pub fn map() -> Self {
-        Llsd::Map(HashMap::new())
+        Llsd::Map(LlsdMap::new())
     }
 
     pub fn clear(&mut self) {
@@ -205,7 +388,7 @@ impl Llsd {
                 map.insert(key.into(), llsd.into());
             }
             Llsd::Undefined => {
-                let mut map = HashMap::new();
+                let mut map = LlsdMap::new();
                 map.insert(key.into(), llsd.into());
                 self = Llsd::Map(map);
             }
@@ -222,10 +405,43 @@ impl Llsd {
         index.index_into_mut(self)
     }
 
+    /// What `&mut self[index]` gives (inserting missing map keys and
+    /// turning `Undefined` into a map for a key) without its panics: a
+    /// step into the wrong type or past the end of an array is an error
+    /// naming the path, so documents of untrusted shape can be edited.
+    pub fn try_index_mut(&mut self, index: impl Index) -> Result<&mut Llsd, LlsdError> {
+        index.try_index_or_insert(self, &IndexOptions::new())
+    }
+
+    /// [`try_index_mut`](Self::try_index_mut) with the given options, such
+    /// as letting arrays grow to reach an index.
+    pub fn try_index_mut_with(
+        &mut self,
+        index: impl Index,
+        options: &IndexOptions,
+    ) -> Result<&mut Llsd, LlsdError> {
+        index.try_index_or_insert(self, options)
+    }
+
     pub fn contains(&self, index: impl Index) -> bool {
         self.get(index).is_some()
     }
 
+    /// Like [`get`](Self::get), treating an explicit `Undefined` like a
+    /// missing key or index, as the viewer does.
+    pub fn get_defined(&self, index: impl Index) -> Option<&Llsd> {
+        self.get(index).and_then(Llsd::as_defined)
+    }
+
+    pub fn contains_defined(&self, index: impl Index) -> bool {
+        self.get_defined(index).is_some()
+    }
+
+    /// A [`LlsdView`] for path-annotated typed reads.
+    pub fn view(&self) -> LlsdView<'_> {
+        LlsdView::new(self)
+    }
+
     pub fn get_any<'a>(&'a self, keys: &[&str]) -> Option<&'a Llsd> {
         let Llsd::Map(map) = self else {
             return None;
@@ -233,6 +449,37 @@ impl Llsd {
         keys.iter().find_map(|key| map.get(*key))
     }
 
+    pub fn is_defined(&self) -> bool {
+        !self.is_undefined()
+    }
+
+    /// `None` for `Undefined`, so that `llsd.get(key).and_then(Llsd::as_defined)`
+    /// folds both kinds of absence together.
+    pub fn as_defined(&self) -> Option<&Llsd> {
+        self.is_defined().then_some(self)
+    }
+
+    /// The text of a string, borrowed.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Llsd::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a binary, borrowed.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Llsd::Binary(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// This value, or `default` if it is `Undefined`.
+    pub fn defined_or<'a>(&'a self, default: &'a Llsd) -> &'a Llsd {
+        self.as_defined().unwrap_or(default)
+    }
+
     pub fn try_coerce_i32(&self) -> Option<i32> {
         match self {
             Llsd::Integer(v) => Some(*v),
@@ -284,6 +531,47 @@ impl Llsd {
         }
     }
 
+    /// The value as a whole number, for the checked `to_*` conversions:
+    /// integers, booleans as 0 or 1, whole finite reals and strings holding
+    /// a decimal integer.
+    fn checked_integer(&self, target: &'static str) -> Result<i64, LlsdError> {
+        match self {
+            Llsd::Integer(v) => Ok(i64::from(*v)),
+            Llsd::Boolean(v) => Ok(i64::from(*v)),
+            Llsd::Real(v) if v.fract() == 0.0 && v.abs() < 2f64.powi(63) => Ok(*v as i64),
+            Llsd::Real(v) => Err(LlsdError::out_of_range(target, v)),
+            Llsd::String(v) => v
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| LlsdError::invalid_type("integer", self)),
+            _ => Err(LlsdError::invalid_type("integer", self)),
+        }
+    }
+
+    /// A `Debug`/`Display` adapter showing at most `depth` levels of
+    /// containers and `max_items` items of each, for logging values that
+    /// may be huge.
+    pub fn debug_limited(&self, depth: usize, max_items: usize) -> DebugLimited<'_> {
+        DebugLimited::new(self, depth, max_items)
+    }
+
+    /// Lower-case LLSD type name as used by the XML element names.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Llsd::Undefined => "undef",
+            Llsd::Boolean(_) => "boolean",
+            Llsd::Integer(_) => "integer",
+            Llsd::Real(_) => "real",
+            Llsd::String(_) => "string",
+            Llsd::Uri(_) => "uri",
+            Llsd::Uuid(_) => "uuid",
+            Llsd::Date(_) => "date",
+            Llsd::Binary(_) => "binary",
+            Llsd::Array(_) => "array",
+            Llsd::Map(_) => "map",
+        }
+    }
+
     pub fn len(&self) -> usize {
         match self {
             Llsd::Array(a) => a.len(),
@@ -335,6 +623,105 @@ impl Llsd {
     pub fn take(&mut self) -> Self {
         std::mem::replace(self, Llsd::Undefined)
     }
+
+    /// Reshape an array of maps into a map keyed by each element's
+    /// `key_field`, which is removed from the element:
+    /// `[{id: "a", n: 1}]` becomes `{a: {n: 1}}`. String, UUID, integer and
+    /// URI keys are accepted and become their text, so integer and UUID keys
+    /// come back from [`array_from_map`](Self::array_from_map) as strings. A
+    /// missing or repeated key is an error naming the element, e.g.
+    /// `missing field at [2].id`.
+    pub fn map_from_array(self, key_field: &str) -> Result<Self, LlsdError> {
+        let items = match self {
+            Llsd::Array(items) => items,
+            other => return Err(LlsdError::invalid_type("array", &other)),
+        };
+        let mut out = LlsdMap::with_capacity(items.len());
+        for (i, item) in items.into_iter().enumerate() {
+            let mut entry = match item {
+                Llsd::Map(entry) => entry,
+                other => return Err(LlsdError::invalid_type("map", &other).with_index(i)),
+            };
+            let key = match entry.remove(key_field) {
+                Some(Llsd::String(key)) => key,
+                Some(Llsd::Uuid(key)) => key.to_string(),
+                Some(Llsd::Integer(key)) => key.to_string(),
+                Some(Llsd::Uri(key)) => key.as_str().to_string(),
+                Some(other) => {
+                    return Err(LlsdError::invalid_type("string", &other)
+                        .with_key(key_field)
+                        .with_index(i));
+                }
+                None => return Err(LlsdError::missing_field(key_field).with_index(i)),
+            };
+            if out.contains_key(&key) {
+                return Err(LlsdError::custom(format!("repeated key {key:?}"))
+                    .with_key(key_field)
+                    .with_index(i));
+            }
+            out.insert(key, Llsd::Map(entry));
+        }
+        Ok(Llsd::Map(out))
+    }
+
+    /// The inverse of [`map_from_array`](Self::map_from_array): reshape a
+    /// map of maps into an array, storing each key as a string under
+    /// `key_into_field`. Elements come out sorted by key, since maps keep
+    /// no order, and keys that were integers or UUIDs stay strings.
+    pub fn array_from_map(self, key_into_field: &str) -> Result<Self, LlsdError> {
+        let map = match self {
+            Llsd::Map(map) => map,
+            other => return Err(LlsdError::invalid_type("map", &other)),
+        };
+        let mut entries: Vec<_> = map.into_iter().collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        entries
+            .into_iter()
+            .map(|(key, value)| {
+                let mut entry = match value {
+                    Llsd::Map(entry) => entry,
+                    other => return Err(LlsdError::invalid_type("map", &other).with_key(&key)),
+                };
+                if entry.contains_key(key_into_field) {
+                    return Err(LlsdError::custom("field already present")
+                        .with_key(key_into_field)
+                        .with_key(&key));
+                }
+                entry.insert(key_into_field.to_string(), Llsd::String(key));
+                Ok(Llsd::Map(entry))
+            })
+            .collect::<Result<_, _>>()
+            .map(Llsd::Array)
+    }
+
+    /// Fill in `${name}` placeholders from `vars`, at any depth. A string
+    /// that is exactly one placeholder becomes the variable's value, of any
+    /// type; placeholders inside longer strings and URIs are replaced by the
+    /// text of a scalar variable. `$${` stands for a literal `${`, and map
+    /// keys are left alone. An undefined variable, or a container or binary
+    /// spliced into text, is an error naming the path.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use llsd_rs::{Llsd, llsd_map};
+    ///
+    /// let template = llsd_map! { "region" => "${region}", "label" => "in ${region}" };
+    /// let vars = HashMap::from([("region".to_string(), Llsd::Integer(7))]);
+    /// assert_eq!(
+    ///     template.substitute(&vars).unwrap(),
+    ///     llsd_map! { "region" => 7, "label" => "in 7" }
+    /// );
+    /// ```
+    pub fn substitute(&self, vars: &HashMap<String, Llsd>) -> Result<Llsd, LlsdError> {
+        template::substitute(self, vars)
+    }
+
+    /// A 128-bit structural digest of this value, equal for values with the
+    /// same canonical binary encoding; see [`fingerprint`].
+    #[cfg(feature = "fingerprint")]
+    pub fn cache_key(&self) -> Result<fingerprint::Fingerprint, anyhow::Error> {
+        fingerprint::Fingerprint::of(self)
+    }
 }
 
 impl From<bool> for Llsd {
@@ -349,6 +736,37 @@ impl From<&bool> for Llsd {
     }
 }
 
+macro_rules! impl_checked_int {
+    ($($name:ident => $t:ty),* $(,)?) => {
+        impl Llsd {
+            $(
+                #[doc = concat!(
+                    "Convert to `", stringify!($t), "`, failing with ",
+                    "[`LlsdErrorKind::OutOfRange`] rather than wrapping or ",
+                    "truncating. Accepts integers, booleans, whole reals and ",
+                    "decimal strings.",
+                )]
+                pub fn $name(&self) -> Result<$t, LlsdError> {
+                    let value = self.checked_integer(stringify!($t))?;
+                    <$t>::try_from(value)
+                        .map_err(|_| LlsdError::out_of_range(stringify!($t), value))
+                }
+            )*
+        }
+    };
+}
+
+impl_checked_int!(
+    to_u8 => u8,
+    to_u16 => u16,
+    to_u32 => u32,
+    to_u64 => u64,
+    to_i8 => i8,
+    to_i16 => i16,
+    to_i32 => i32,
+    to_i64 => i64,
+);
+
 macro_rules! impl_from_int {
     ($($t:ty),*) => {
             $(
@@ -357,6 +775,14 @@ macro_rules! impl_from_int {
                     Llsd::Integer(llsd as i32)
                 }
             }
+            impl From<&$t> for Llsd {
+                fn from(llsd: &$t) -> Self {
+                    Llsd::Integer(*llsd as i32)
+                }
+            }
+            /// Values outside the target range wrap (`-1` becomes `u32::MAX`)
+            /// and reals truncate, as with `as`. [`FromLlsd`], and so derived
+            /// fields, uses the checked `Llsd::to_*` conversions instead.
             impl TryFrom<&Llsd> for $t {
                 type Error = anyhow::Error;
 
@@ -366,7 +792,7 @@ macro_rules! impl_from_int {
                         Llsd::Real(value) => Ok(*value as $t),
                         Llsd::Boolean(value) => Ok(if *value { 1 } else { 0 } as $t),
                         Llsd::String(value) => Ok(coerce_string_to_i32(value) as $t),
-                        _ => Err(anyhow::Error::msg("Expected LLSD Integer")),
+                        _ => Err(LlsdError::invalid_type("integer", llsd).into()),
                     }
                 }
             }
@@ -398,9 +824,9 @@ macro_rules! impl_from_real {
                         Llsd::Integer(value) => Ok(*value as $t),
                         Llsd::Boolean(value) => Ok(if *value { 1.0 } else { 0.0 } as $t),
                         Llsd::String(value) => {
-                            value.parse::<$t>().map_err(|_| anyhow::Error::msg("Invalid real"))
+                            value.parse::<$t>().map_err(|_| LlsdError::invalid_type("real", llsd).into())
                         }
-                        _ => Err(anyhow::Error::msg("Expected LLSD Real")),
+                        _ => Err(LlsdError::invalid_type("real", llsd).into()),
                     }
                 }
             }
@@ -410,6 +836,26 @@ macro_rules! impl_from_real {
 
 impl_from_real!(f32, f64);
 
+/// Owned conversions for scalar types that hold no heap data; these simply
+/// delegate to the borrowed impl.
+macro_rules! impl_try_from_owned_via_ref {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<Llsd> for $t {
+                type Error = anyhow::Error;
+
+                fn try_from(llsd: Llsd) -> Result<Self> {
+                    <$t>::try_from(&llsd)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_owned_via_ref!(
+    u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool, Uuid, Url, Date
+);
+
 impl From<&str> for Llsd {
     fn from(llsd: &str) -> Self {
         Llsd::String(llsd.to_string())
@@ -452,27 +898,55 @@ impl From<&Url> for Llsd {
     }
 }
 
-impl From<DateTime<Utc>> for Llsd {
-    fn from(llsd: DateTime<Utc>) -> Self {
+impl From<Uri> for Llsd {
+    fn from(llsd: Uri) -> Self {
+        Llsd::Uri(llsd)
+    }
+}
+
+impl From<&Uri> for Llsd {
+    fn from(v: &Uri) -> Self {
+        Llsd::Uri(v.clone())
+    }
+}
+
+impl From<Date> for Llsd {
+    fn from(llsd: Date) -> Self {
         Llsd::Date(llsd)
     }
 }
 
-impl From<&DateTime<Utc>> for Llsd {
-    fn from(v: &DateTime<Utc>) -> Self {
+impl From<&Date> for Llsd {
+    fn from(v: &Date) -> Self {
         Llsd::Date(*v)
     }
 }
 
-impl From<DateTime<FixedOffset>> for Llsd {
-    fn from(llsd: DateTime<FixedOffset>) -> Self {
-        Llsd::Date(llsd.with_timezone(&Utc))
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::FixedOffset>> for Llsd {
+    fn from(llsd: chrono::DateTime<chrono::FixedOffset>) -> Self {
+        Llsd::Date(llsd.to_utc())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<&chrono::DateTime<chrono::FixedOffset>> for Llsd {
+    fn from(v: &chrono::DateTime<chrono::FixedOffset>) -> Self {
+        Llsd::Date(v.to_utc())
     }
 }
 
-impl From<&DateTime<FixedOffset>> for Llsd {
-    fn from(v: &DateTime<FixedOffset>) -> Self {
-        Llsd::Date(v.with_timezone(&Utc))
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Llsd {
+    fn from(llsd: time::OffsetDateTime) -> Self {
+        Llsd::Date(date::from_offset_date_time(llsd))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<&time::OffsetDateTime> for Llsd {
+    fn from(v: &time::OffsetDateTime) -> Self {
+        Llsd::Date(date::from_offset_date_time(*v))
     }
 }
 
@@ -488,12 +962,32 @@ impl<const N: usize> From<[u8; N]> for Llsd {
     }
 }
 
+#[cfg(feature = "bytes")]
+impl From<bytes::Bytes> for Llsd {
+    fn from(llsd: bytes::Bytes) -> Self {
+        Llsd::Binary(llsd.into())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<&bytes::Bytes> for Llsd {
+    fn from(llsd: &bytes::Bytes) -> Self {
+        Llsd::Binary(llsd.to_vec())
+    }
+}
+
 impl<T: Into<Llsd>> From<Vec<T>> for Llsd {
     fn from(llsd: Vec<T>) -> Self {
         Llsd::Array(llsd.into_iter().map(Into::into).collect())
     }
 }
 
+impl<T: Clone + Into<Llsd>> From<&Vec<T>> for Llsd {
+    fn from(llsd: &Vec<T>) -> Self {
+        Llsd::Array(llsd.iter().cloned().map(Into::into).collect())
+    }
+}
+
 impl<K: Into<String>, V: Into<Llsd>> From<HashMap<K, V>> for Llsd {
     fn from(llsd: HashMap<K, V>) -> Self {
         Llsd::Map(
@@ -504,13 +998,52 @@ impl<K: Into<String>, V: Into<Llsd>> From<HashMap<K, V>> for Llsd {
     }
 }
 
+impl<K: AsRef<str>, V: Clone + Into<Llsd>> From<&HashMap<K, V>> for Llsd {
+    fn from(llsd: &HashMap<K, V>) -> Self {
+        Llsd::Map(
+            llsd.iter()
+                .map(|(k, v)| (k.as_ref().to_string(), v.clone().into()))
+                .collect(),
+        )
+    }
+}
+
+impl From<LlsdMap> for Llsd {
+    fn from(map: LlsdMap) -> Self {
+        Llsd::Map(map)
+    }
+}
+
+impl From<&Llsd> for Llsd {
+    fn from(v: &Llsd) -> Self {
+        v.clone()
+    }
+}
+
 // Tuple support (2..=4) explicit implementations -------------------------------------------
+fn tuple_item<T>(llsd: &Llsd, index: usize) -> Result<T>
+where
+    for<'x> T: TryFrom<&'x Llsd, Error = anyhow::Error>,
+{
+    T::try_from(llsd).map_err(|e| LlsdError::at_index(e, index))
+}
+
 impl<A: Into<Llsd>, B: Into<Llsd>> From<(A, B)> for Llsd {
     fn from(t: (A, B)) -> Self {
         let (a, b) = t;
         Llsd::Array(vec![a.into(), b.into()])
     }
 }
+impl<A, B> From<&(A, B)> for Llsd
+where
+    A: Clone + Into<Llsd>,
+    B: Clone + Into<Llsd>,
+{
+    fn from(t: &(A, B)) -> Self {
+        let (a, b) = t;
+        Llsd::Array(vec![a.clone().into(), b.clone().into()])
+    }
+}
 impl<A, B> TryFrom<&Llsd> for (A, B)
 where
     for<'x> A: TryFrom<&'x Llsd, Error = anyhow::Error>,
@@ -520,12 +1053,12 @@ where
     fn try_from(v: &Llsd) -> Result<Self> {
         if let Llsd::Array(a) = v {
             if a.len() == 2 {
-                Ok((A::try_from(&a[0])?, B::try_from(&a[1])?))
+                Ok((tuple_item(&a[0], 0)?, tuple_item(&a[1], 1)?))
             } else {
-                Err(anyhow::Error::msg("Expected array of length 2"))
+                Err(LlsdError::invalid_length(2, a.len()).into())
             }
         } else {
-            Err(anyhow::Error::msg("Expected LLSD Array"))
+            Err(LlsdError::invalid_type("array", v).into())
         }
     }
 }
@@ -536,6 +1069,17 @@ impl<A: Into<Llsd>, B: Into<Llsd>, C: Into<Llsd>> From<(A, B, C)> for Llsd {
         Llsd::Array(vec![a.into(), b.into(), c.into()])
     }
 }
+impl<A, B, C> From<&(A, B, C)> for Llsd
+where
+    A: Clone + Into<Llsd>,
+    B: Clone + Into<Llsd>,
+    C: Clone + Into<Llsd>,
+{
+    fn from(t: &(A, B, C)) -> Self {
+        let (a, b, c) = t;
+        Llsd::Array(vec![a.clone().into(), b.clone().into(), c.clone().into()])
+    }
+}
 impl<A, B, C> TryFrom<&Llsd> for (A, B, C)
 where
     for<'x> A: TryFrom<&'x Llsd, Error = anyhow::Error>,
@@ -547,15 +1091,15 @@ where
         if let Llsd::Array(a) = v {
             if a.len() == 3 {
                 Ok((
-                    A::try_from(&a[0])?,
-                    B::try_from(&a[1])?,
-                    C::try_from(&a[2])?,
+                    tuple_item(&a[0], 0)?,
+                    tuple_item(&a[1], 1)?,
+                    tuple_item(&a[2], 2)?,
                 ))
             } else {
-                Err(anyhow::Error::msg("Expected array of length 3"))
+                Err(LlsdError::invalid_length(3, a.len()).into())
             }
         } else {
-            Err(anyhow::Error::msg("Expected LLSD Array"))
+            Err(LlsdError::invalid_type("array", v).into())
         }
     }
 }
@@ -566,6 +1110,23 @@ impl<A: Into<Llsd>, B: Into<Llsd>, C: Into<Llsd>, D: Into<Llsd>> From<(A, B, C,
         Llsd::Array(vec![a.into(), b.into(), c.into(), d.into()])
     }
 }
+impl<A, B, C, D> From<&(A, B, C, D)> for Llsd
+where
+    A: Clone + Into<Llsd>,
+    B: Clone + Into<Llsd>,
+    C: Clone + Into<Llsd>,
+    D: Clone + Into<Llsd>,
+{
+    fn from(t: &(A, B, C, D)) -> Self {
+        let (a, b, c, d) = t;
+        Llsd::Array(vec![
+            a.clone().into(),
+            b.clone().into(),
+            c.clone().into(),
+            d.clone().into(),
+        ])
+    }
+}
 impl<A, B, C, D> TryFrom<&Llsd> for (A, B, C, D)
 where
     for<'x> A: TryFrom<&'x Llsd, Error = anyhow::Error>,
@@ -578,20 +1139,48 @@ where
         if let Llsd::Array(a) = v {
             if a.len() == 4 {
                 Ok((
-                    A::try_from(&a[0])?,
-                    B::try_from(&a[1])?,
-                    C::try_from(&a[2])?,
-                    D::try_from(&a[3])?,
+                    tuple_item(&a[0], 0)?,
+                    tuple_item(&a[1], 1)?,
+                    tuple_item(&a[2], 2)?,
+                    tuple_item(&a[3], 3)?,
                 ))
             } else {
-                Err(anyhow::Error::msg("Expected array of length 4"))
+                Err(LlsdError::invalid_length(4, a.len()).into())
             }
         } else {
-            Err(anyhow::Error::msg("Expected LLSD Array"))
+            Err(LlsdError::invalid_type("array", v).into())
         }
     }
 }
 
+macro_rules! impl_tuple_try_from_owned {
+    ($len:expr => $($name:ident),+) => {
+        impl<$($name),+> TryFrom<Llsd> for ($($name,)+)
+        where
+            $($name: TryFrom<Llsd, Error = anyhow::Error>,)+
+        {
+            type Error = anyhow::Error;
+            fn try_from(v: Llsd) -> Result<Self> {
+                match v {
+                    Llsd::Array(a) if a.len() == $len => {
+                        let mut items = a.into_iter().enumerate();
+                        Ok(($({
+                            let (index, item) = items.next().expect("length checked");
+                            $name::try_from(item).map_err(|e| LlsdError::at_index(e, index))?
+                        },)+))
+                    }
+                    Llsd::Array(a) => Err(LlsdError::invalid_length($len, a.len()).into()),
+                    other => Err(LlsdError::invalid_type("array", &other).into()),
+                }
+            }
+        }
+    };
+}
+
+impl_tuple_try_from_owned!(2 => A, B);
+impl_tuple_try_from_owned!(3 => A, B, C);
+impl_tuple_try_from_owned!(4 => A, B, C, D);
+
 impl<K: Into<String>, V: Into<Llsd>> FromIterator<(K, V)> for Llsd {
     fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
         Llsd::Map(
@@ -608,8 +1197,9 @@ impl TryFrom<&Llsd> for Uuid {
     fn try_from(llsd: &Llsd) -> Result<Self> {
         match llsd {
             Llsd::Uuid(llsd) => Ok(*llsd),
-            Llsd::String(llsd) => Ok(Uuid::parse_str(llsd.as_str())?),
-            _ => Err(anyhow::Error::msg("not a UUID")),
+            Llsd::String(value) => Uuid::parse_str(value.as_str())
+                .map_err(|_| LlsdError::invalid_type("uuid", llsd).into()),
+            _ => Err(LlsdError::invalid_type("uuid", llsd).into()),
         }
     }
 }
@@ -619,9 +1209,88 @@ impl TryFrom<&Llsd> for Url {
 
     fn try_from(llsd: &Llsd) -> Result<Self> {
         match llsd {
-            Llsd::Uri(uri) => Ok(uri.try_into()?),
-            Llsd::String(llsd) => Ok(Url::parse(llsd.as_str())?),
-            _ => Err(anyhow::Error::msg("not a URL")),
+            Llsd::Uri(uri) => Url::try_from(uri).map_err(|e| LlsdError::custom(e).into()),
+            Llsd::String(value) => {
+                Url::parse(value.as_str()).map_err(|e| LlsdError::custom(e).into())
+            }
+            _ => Err(LlsdError::invalid_type("uri", llsd).into()),
+        }
+    }
+}
+
+impl TryFrom<&Llsd> for Uri {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &Llsd) -> Result<Self> {
+        match llsd {
+            Llsd::Uri(uri) => Ok(uri.clone()),
+            Llsd::String(value) => Ok(Uri::parse(value)),
+            _ => Err(LlsdError::invalid_type("uri", llsd).into()),
+        }
+    }
+}
+
+impl TryFrom<Llsd> for Uri {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: Llsd) -> Result<Self> {
+        match llsd {
+            Llsd::Uri(uri) => Ok(uri),
+            Llsd::String(value) => Ok(Uri::parse(&value)),
+            other => Err(LlsdError::invalid_type("uri", &other).into()),
+        }
+    }
+}
+
+impl TryFrom<&Llsd> for Date {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &Llsd) -> Result<Self> {
+        match llsd {
+            Llsd::Date(date) => Ok(*date),
+            Llsd::String(value) => {
+                date::parse_rfc3339(value).map_err(|_| LlsdError::invalid_type("date", llsd).into())
+            }
+            _ => Err(LlsdError::invalid_type("date", llsd).into()),
+        }
+    }
+}
+
+/// Dates outside `time`'s years -9999 to 9999 fail.
+#[cfg(feature = "time")]
+impl TryFrom<&Llsd> for time::OffsetDateTime {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &Llsd) -> Result<Self> {
+        let date = Date::try_from(llsd)?;
+        date::to_offset_date_time(&date)
+            .ok_or_else(|| LlsdError::custom("date out of range").into())
+    }
+}
+
+#[cfg(feature = "time")]
+impl_try_from_owned_via_ref!(time::OffsetDateTime);
+
+#[cfg(feature = "bytes")]
+impl TryFrom<&Llsd> for bytes::Bytes {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &Llsd) -> anyhow::Result<Self> {
+        match llsd {
+            Llsd::Binary(value) => Ok(bytes::Bytes::copy_from_slice(value)),
+            _ => Err(LlsdError::invalid_type("binary", llsd).into()),
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl TryFrom<Llsd> for bytes::Bytes {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: Llsd) -> anyhow::Result<Self> {
+        match llsd {
+            Llsd::Binary(value) => Ok(value.into()),
+            other => Err(LlsdError::invalid_type("binary", &other).into()),
         }
     }
 }
@@ -631,6 +1300,7 @@ mod private {
     impl Sealed for usize {}
     impl Sealed for str {}
     impl Sealed for String {}
+    impl Sealed for super::Pointer<'_> {}
     impl<T> Sealed for &T where T: ?Sized + Sealed {}
 }
 
@@ -638,6 +1308,34 @@ pub trait Index: private::Sealed {
     fn index_into<'v>(&self, v: &'v Llsd) -> Option<&'v Llsd>;
     fn index_into_mut<'v>(&self, v: &'v mut Llsd) -> Option<&'v mut Llsd>;
     fn index_or_insert<'v>(&self, v: &'v mut Llsd) -> &'v mut Llsd;
+    fn try_index_or_insert<'v>(
+        &self,
+        v: &'v mut Llsd,
+        options: &IndexOptions,
+    ) -> Result<&'v mut Llsd, LlsdError>;
+}
+
+/// How [`Llsd::try_index_mut_with`] treats positions past the end of an
+/// array.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexOptions {
+    max_array_len: usize,
+}
+
+impl IndexOptions {
+    /// Arrays never grow: indexing past the end is an error.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let an index past the end extend the array with `Undefined` up to
+    /// `max_len` elements, and an `Undefined` value indexed by position
+    /// become an array, so `doc["a"][3] = x` works on a fresh document.
+    /// The bound keeps an untrusted index from allocating without limit.
+    pub fn with_array_growth(mut self, max_len: usize) -> Self {
+        self.max_array_len = max_len;
+        self
+    }
 }
 
 impl<I> ops::Index<I> for Llsd
@@ -651,21 +1349,26 @@ where
     }
 }
 
+/// Indexes arrays by position and maps by the decimal key, since caps
+/// responses often send logical arrays as maps keyed `"0"`, `"1"`...
 impl Index for usize {
     fn index_into<'v>(&self, v: &'v Llsd) -> Option<&'v Llsd> {
         match v {
             Llsd::Array(vec) => vec.get(*self),
+            Llsd::Map(map) => map.get(itoa::Buffer::new().format(*self)),
             _ => None,
         }
     }
     fn index_into_mut<'v>(&self, v: &'v mut Llsd) -> Option<&'v mut Llsd> {
         match v {
             Llsd::Array(vec) => vec.get_mut(*self),
+            Llsd::Map(map) => map.get_mut(itoa::Buffer::new().format(*self)),
             _ => None,
         }
     }
     fn index_or_insert<'v>(&self, v: &'v mut Llsd) -> &'v mut Llsd {
         match v {
+            Llsd::Map(_) => self.to_string().index_or_insert(v),
             Llsd::Array(vec) => {
                 let len = vec.len();
                 vec.get_mut(*self).unwrap_or_else(|| {
@@ -678,6 +1381,34 @@ impl Index for usize {
             _ => panic!("cannot access index {}", self),
         }
     }
+    fn try_index_or_insert<'v>(
+        &self,
+        v: &'v mut Llsd,
+        options: &IndexOptions,
+    ) -> Result<&'v mut Llsd, LlsdError> {
+        if v.is_undefined() && *self < options.max_array_len {
+            *v = Llsd::Array(Vec::new());
+        }
+        match v {
+            Llsd::Map(_) => itoa::Buffer::new()
+                .format(*self)
+                .try_index_or_insert(v, options),
+            Llsd::Array(vec) => {
+                if *self >= vec.len() {
+                    if *self >= options.max_array_len {
+                        return Err(LlsdError::custom(format_args!(
+                            "index {} is past the end of an array of length {}",
+                            self,
+                            vec.len()
+                        )));
+                    }
+                    vec.resize(*self + 1, Llsd::Undefined);
+                }
+                Ok(&mut vec[*self])
+            }
+            _ => Err(LlsdError::invalid_type("array", v)),
+        }
+    }
 }
 
 impl Index for str {
@@ -695,13 +1426,26 @@ impl Index for str {
     }
     fn index_or_insert<'v>(&self, v: &'v mut Llsd) -> &'v mut Llsd {
         if let Llsd::Undefined = v {
-            *v = Llsd::Map(HashMap::new());
+            *v = Llsd::Map(LlsdMap::new());
         }
         match v {
-            Llsd::Map(map) => map.entry(self.to_owned()).or_insert(Llsd::Undefined),
+            Llsd::Map(map) => map.get_or_insert_with(self.to_owned(), || Llsd::Undefined),
             _ => panic!("cannot access key {:?}", self),
         }
     }
+    fn try_index_or_insert<'v>(
+        &self,
+        v: &'v mut Llsd,
+        _options: &IndexOptions,
+    ) -> Result<&'v mut Llsd, LlsdError> {
+        if let Llsd::Undefined = v {
+            *v = Llsd::Map(LlsdMap::new());
+        }
+        match v {
+            Llsd::Map(map) => Ok(map.get_or_insert_with(self.to_owned(), || Llsd::Undefined)),
+            _ => Err(LlsdError::invalid_type("map", v)),
+        }
+    }
 }
 
 impl<T> Index for &T
@@ -717,6 +1461,13 @@ where
     fn index_or_insert<'v>(&self, v: &'v mut Llsd) -> &'v mut Llsd {
         (**self).index_or_insert(v)
     }
+    fn try_index_or_insert<'v>(
+        &self,
+        v: &'v mut Llsd,
+        options: &IndexOptions,
+    ) -> Result<&'v mut Llsd, LlsdError> {
+        (**self).try_index_or_insert(v, options)
+    }
 }
 
 impl Index for String {
@@ -729,6 +1480,81 @@ impl Index for String {
     fn index_or_insert<'v>(&self, v: &'v mut Llsd) -> &'v mut Llsd {
         self[..].index_or_insert(v)
     }
+    fn try_index_or_insert<'v>(
+        &self,
+        v: &'v mut Llsd,
+        options: &IndexOptions,
+    ) -> Result<&'v mut Llsd, LlsdError> {
+        self[..].try_index_or_insert(v, options)
+    }
+}
+
+/// A JSON-pointer-style path (`"/a/b/0"`, with `~1` for `/` and `~0` for
+/// `~` in keys) used as an index, reaching a deep value in one step:
+/// `llsd[Pointer("/agent/inventory/0")]` is `Undefined` when any level is
+/// missing or of the wrong type, like [`Llsd::pointer`]. Array steps are
+/// positions; steps through maps are keys, numeric or not.
+///
+/// Through `IndexMut`, missing map keys (and an `Undefined` root or step)
+/// are created as with chained `[]`, which panics on a step that is neither
+/// a map nor an in-range array index, or on a pointer not starting with `/`.
+///
+/// ```
+/// use llsd_rs::{Llsd, Pointer, llsd_array, llsd_map};
+///
+/// let mut doc = llsd_map! { "agent" => llsd_map! { "groups" => llsd_array!["a", "b"] } };
+/// assert_eq!(doc[Pointer("/agent/groups/1")], Llsd::from("b"));
+/// assert_eq!(doc[Pointer("/agent/name/first")], Llsd::Undefined);
+/// doc[Pointer("/agent/name/first")] = "Ruth".into();
+/// assert_eq!(doc["agent"]["name"]["first"], Llsd::from("Ruth"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pointer<'a>(pub &'a str);
+
+impl Index for Pointer<'_> {
+    fn index_into<'v>(&self, v: &'v Llsd) -> Option<&'v Llsd> {
+        v.pointer(self.0)
+    }
+    fn index_into_mut<'v>(&self, v: &'v mut Llsd) -> Option<&'v mut Llsd> {
+        v.pointer_mut(self.0)
+    }
+    fn index_or_insert<'v>(&self, v: &'v mut Llsd) -> &'v mut Llsd {
+        let tokens =
+            pointer_tokens(self.0).unwrap_or_else(|| panic!("invalid pointer {:?}", self.0));
+        tokens.iter().fold(v, |target, token| match target {
+            Llsd::Array(_) => match token.parse::<usize>() {
+                Ok(index) => index.index_or_insert(target),
+                Err(_) => panic!("cannot access array with key {token:?}"),
+            },
+            _ => token.index_or_insert(target),
+        })
+    }
+    fn try_index_or_insert<'v>(
+        &self,
+        v: &'v mut Llsd,
+        options: &IndexOptions,
+    ) -> Result<&'v mut Llsd, LlsdError> {
+        let tokens = pointer_tokens(self.0)
+            .ok_or_else(|| LlsdError::custom(format_args!("invalid pointer {:?}", self.0)))?;
+        let mut path = Vec::with_capacity(tokens.len());
+        let mut target = v;
+        for token in tokens {
+            let step = match (&*target, token.parse::<usize>()) {
+                (Llsd::Array(_), Ok(index)) => PathSegment::Index(index),
+                (Llsd::Undefined, Ok(index)) if index < options.max_array_len => {
+                    PathSegment::Index(index)
+                }
+                _ => PathSegment::Key(token),
+            };
+            let result = match &step {
+                PathSegment::Index(index) => index.try_index_or_insert(target, options),
+                PathSegment::Key(key) => key.try_index_or_insert(target, options),
+            };
+            target = result.map_err(|e| e.with_prefix(&path))?;
+            path.push(step);
+        }
+        Ok(target)
+    }
 }
 
 impl<I> ops::IndexMut<I> for Llsd
@@ -747,11 +1573,29 @@ impl TryFrom<&Llsd> for bool {
         if let Some(value) = llsd.as_boolean() {
             Ok(*value)
         } else {
-            Err(anyhow::Error::msg("Expected LLSD Boolean"))
+            Err(LlsdError::invalid_type("boolean", llsd).into())
         }
     }
 }
 
+impl<'a> TryFrom<&'a Llsd> for &'a str {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &'a Llsd) -> anyhow::Result<Self> {
+        llsd.as_str()
+            .ok_or_else(|| LlsdError::invalid_type("string", llsd).into())
+    }
+}
+
+impl<'a> TryFrom<&'a Llsd> for &'a [u8] {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &'a Llsd) -> anyhow::Result<Self> {
+        llsd.as_bytes()
+            .ok_or_else(|| LlsdError::invalid_type("binary", llsd).into())
+    }
+}
+
 impl TryFrom<&Llsd> for String {
     type Error = anyhow::Error;
 
@@ -759,7 +1603,7 @@ impl TryFrom<&Llsd> for String {
         if let Some(value) = llsd.as_string() {
             Ok(value.clone())
         } else {
-            Err(anyhow::Error::msg("Expected LLSD String"))
+            Err(LlsdError::invalid_type("string", llsd).into())
         }
     }
 }
@@ -772,9 +1616,13 @@ where
 
     fn try_from(llsd: &Llsd) -> anyhow::Result<Self> {
         if let Some(array) = llsd.as_array() {
-            array.iter().map(|item| T::try_from(item)).collect()
+            array
+                .iter()
+                .enumerate()
+                .map(|(index, item)| T::try_from(item).map_err(|e| LlsdError::at_index(e, index)))
+                .collect()
         } else {
-            Err(anyhow::Error::msg("Expected LLSD Array"))
+            Err(LlsdError::invalid_type("array", llsd).into())
         }
     }
 }
@@ -788,20 +1636,72 @@ where
     fn try_from(llsd: &Llsd) -> anyhow::Result<Self> {
         if let Some(map) = llsd.as_map() {
             map.iter()
-                .map(|(k, v)| Ok((k.clone(), V::try_from(v)?)))
+                .map(|(k, v)| {
+                    let value = V::try_from(v).map_err(|e| LlsdError::at_key(e, k))?;
+                    Ok((k.clone(), value))
+                })
                 .collect()
         } else {
-            Err(anyhow::Error::msg("Expected LLSD Map"))
+            Err(LlsdError::invalid_type("map", llsd).into())
+        }
+    }
+}
+
+impl TryFrom<Llsd> for String {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: Llsd) -> anyhow::Result<Self> {
+        match llsd {
+            Llsd::String(value) => Ok(value),
+            other => Err(LlsdError::invalid_type("string", &other).into()),
+        }
+    }
+}
+
+impl<T> TryFrom<Llsd> for Vec<T>
+where
+    T: TryFrom<Llsd, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: Llsd) -> anyhow::Result<Self> {
+        match llsd {
+            Llsd::Array(array) => array
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| T::try_from(item).map_err(|e| LlsdError::at_index(e, index)))
+                .collect(),
+            other => Err(LlsdError::invalid_type("array", &other).into()),
+        }
+    }
+}
+
+impl<V> TryFrom<Llsd> for HashMap<String, V>
+where
+    V: TryFrom<Llsd, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: Llsd) -> anyhow::Result<Self> {
+        match llsd {
+            Llsd::Map(map) => map
+                .into_iter()
+                .map(|(k, v)| {
+                    let value = V::try_from(v).map_err(|e| LlsdError::at_key(e, &k))?;
+                    Ok((k, value))
+                })
+                .collect(),
+            other => Err(LlsdError::invalid_type("map", &other).into()),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use crate::{FromLlsd, LlsdErrorKind, LlsdMap};
 
     use super::Llsd;
-    use uuid::Uuid;
+    use crate::Uuid;
 
     #[test]
     fn string_to_integer_try_from_coerces_like_viewer() {
@@ -822,7 +1722,7 @@ mod tests {
 
     #[test]
     fn get_any_picks_first_present_key() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert("legacy".to_string(), Llsd::Integer(7));
         map.insert("new".to_string(), Llsd::Integer(42));
         let llsd = Llsd::Map(map);
@@ -836,6 +1736,120 @@ mod tests {
         assert!(Llsd::Integer(1).get_any(&["new"]).is_none());
     }
 
+    #[test]
+    fn integer_index_reads_numeric_map_keys() {
+        let mut map = LlsdMap::new();
+        map.insert("0".to_string(), Llsd::Integer(10));
+        map.insert("1".to_string(), Llsd::Integer(11));
+        let mut llsd = Llsd::Map(map);
+
+        assert_eq!(llsd[1], Llsd::Integer(11));
+        assert_eq!(llsd.get(0), Some(&Llsd::Integer(10)));
+        assert_eq!(llsd.get(2), None);
+        *llsd.get_mut(0).unwrap() = Llsd::Integer(20);
+        llsd[2] = Llsd::Integer(12);
+        assert_eq!(llsd["0"], Llsd::Integer(20));
+        assert_eq!(llsd["2"], Llsd::Integer(12));
+        assert_eq!(Llsd::Array(vec![Llsd::Integer(1)])[0], Llsd::Integer(1));
+    }
+
+    #[test]
+    fn reshapes_between_arrays_and_maps() {
+        let item = |id: &str, n: i32| {
+            Llsd::map()
+                .insert("id", id)
+                .unwrap()
+                .insert("n", n)
+                .unwrap()
+        };
+        let array = Llsd::Array(vec![item("b", 2), item("a", 1)]);
+
+        let map = array.clone().map_from_array("id").unwrap();
+        assert_eq!(map["a"], Llsd::map().insert("n", 1).unwrap());
+        assert_eq!(map["b"]["n"], Llsd::Integer(2));
+        assert!(map["a"].get("id").is_none());
+
+        let back = map.array_from_map("id").unwrap();
+        assert_eq!(back, Llsd::Array(vec![item("a", 1), item("b", 2)]));
+
+        let repeated = Llsd::Array(vec![item("a", 1), item("a", 2)]);
+        assert_eq!(
+            repeated.map_from_array("id").unwrap_err().to_string(),
+            "invalid value at [1].id: repeated key \"a\""
+        );
+        let err = Llsd::Array(vec![item("a", 1)])
+            .map_from_array("x")
+            .unwrap_err();
+        assert_eq!(err.path_string(), "[0].x");
+        assert_eq!(err.kind(), &LlsdErrorKind::MissingField);
+        assert_eq!(
+            Llsd::Array(vec![Llsd::Integer(1)])
+                .map_from_array("id")
+                .unwrap_err()
+                .to_string(),
+            "invalid type at [0]: expected map, found integer"
+        );
+        let uuid = Uuid::from_u128(1);
+        let keyed = Llsd::Array(vec![Llsd::map().insert("id", uuid).unwrap()]);
+        assert!(
+            keyed
+                .map_from_array("id")
+                .unwrap()
+                .contains(uuid.to_string())
+        );
+        assert!(Llsd::Integer(1).array_from_map("id").is_err());
+        let clash = Llsd::map().insert("a", item("x", 1)).unwrap();
+        assert_eq!(
+            clash.array_from_map("id").unwrap_err().path_string(),
+            "a.id"
+        );
+        // Integer keys come back as strings.
+        let numbered = Llsd::Array(vec![Llsd::map().insert("id", 7).unwrap()]);
+        let back = numbered.map_from_array("id").unwrap().array_from_map("id");
+        assert_eq!(back.unwrap()[0]["id"], Llsd::from("7"));
+    }
+
+    #[test]
+    fn borrowed_string_and_binary() {
+        let text = Llsd::String("hello".into());
+        let binary = Llsd::Binary(vec![1, 2]);
+        assert_eq!(text.as_str(), Some("hello"));
+        assert_eq!(binary.as_bytes(), Some(&[1u8, 2][..]));
+        assert_eq!(binary.as_str(), None);
+        assert_eq!(text.as_bytes(), None);
+
+        let s: &str = (&text).try_into().unwrap();
+        assert!(std::ptr::eq(s, text.as_string().unwrap().as_str()));
+        let b: &[u8] = (&binary).try_into().unwrap();
+        assert_eq!(b, [1, 2]);
+        let err = <&str>::try_from(&binary).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type: expected string, found binary"
+        );
+        assert!(<&[u8]>::try_from(&text).is_err());
+    }
+
+    #[test]
+    fn undefined_reads_as_missing() {
+        let mut map = LlsdMap::new();
+        map.insert("undef".to_string(), Llsd::Undefined);
+        map.insert("zero".to_string(), Llsd::Integer(0));
+        let llsd = Llsd::Map(map);
+
+        assert!(llsd.contains("undef"));
+        assert!(!llsd.contains_defined("undef"));
+        assert_eq!(llsd.get_defined("undef"), None);
+        assert_eq!(llsd.get_defined("missing"), None);
+        assert_eq!(llsd.get_defined("zero"), Some(&Llsd::Integer(0)));
+
+        let fallback = Llsd::Integer(5);
+        assert_eq!(llsd["undef"].defined_or(&fallback), &fallback);
+        assert_eq!(llsd["zero"].defined_or(&fallback), &Llsd::Integer(0));
+        assert!(llsd.is_defined() && !Llsd::Undefined.is_defined());
+        assert_eq!(Llsd::Undefined.as_defined(), None);
+    }
+
     #[test]
     fn coerce_helpers_cover_common_shapes() {
         assert_eq!(Llsd::String("4294967296".to_string()).coerce_i32(), 0);
@@ -858,4 +1872,259 @@ mod tests {
         );
         assert!(Llsd::Binary(vec![1, 2, 3]).try_coerce_uuid().is_none());
     }
+
+    #[test]
+    fn owned_conversions_move_heap_values() {
+        let text = "x".repeat(64);
+        let ptr = text.as_ptr();
+        let moved = String::try_from(Llsd::String(text)).unwrap();
+        assert_eq!(moved.as_ptr(), ptr);
+
+        let array = Llsd::Array(vec![Llsd::String("a".into()), Llsd::Integer(2)]);
+        let err = Vec::<String>::try_from(array).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type at [1]: expected string, found integer"
+        );
+
+        let tuple = Llsd::Array(vec![Llsd::Integer(1), Llsd::String("b".into())]);
+        assert_eq!(
+            <(i32, String)>::try_from(tuple).unwrap(),
+            (1, "b".to_string())
+        );
+    }
+
+    #[test]
+    fn uri_sanitize_and_write_policies() {
+        // As a peer might have stored it, never having parsed it.
+        let raw = super::Uri::String(
+            "  http://example.com/a b/ü?q=\"x\"%41%zz ".into(),
+            url::ParseError::InvalidDomainCharacter,
+        );
+        let sanitized = raw.sanitize();
+        assert!(sanitized.is_url());
+        assert_eq!(
+            sanitized.as_str(),
+            "http://example.com/a%20b/%C3%BC?q=%22x%22%41%25zz"
+        );
+        let relative = super::Uri::parse("folder/my item.txt");
+        assert!(relative.error().is_some());
+        assert_eq!(relative.sanitize().as_str(), "folder/my%20item.txt");
+        let url = super::Uri::parse("https://example.com/");
+        assert_eq!(url.sanitize(), url);
+
+        use super::UriPolicy::*;
+        let write = |uris| {
+            let context = crate::notation::FormatterContext::new().with_uri_policy(uris);
+            let notation = crate::notation::to_string(&Llsd::Uri(relative.clone()), &context);
+            let mut encoder = crate::xml::XmlEncoder::new().with_uri_policy(uris);
+            let xml = encoder.encode(&Llsd::Uri(relative.clone()));
+            (
+                notation.ok(),
+                xml.ok().map(|x| String::from_utf8(x).unwrap()),
+            )
+        };
+        let (notation, xml) = write(Verbatim);
+        assert_eq!(notation.as_deref(), Some("l\"folder/my item.txt\""));
+        assert!(xml.unwrap().contains("<uri>folder/my item.txt</uri>"));
+        let (notation, xml) = write(PercentEncode);
+        assert_eq!(notation.as_deref(), Some("l\"folder/my%20item.txt\""));
+        assert!(xml.unwrap().contains("<uri>folder/my%20item.txt</uri>"));
+        assert_eq!(write(Reject), (None, None));
+    }
+
+    #[test]
+    fn pointer_index() {
+        use crate::Pointer;
+
+        let mut doc = Llsd::map()
+            .insert("a/b", Llsd::map().insert("7", 1).unwrap())
+            .unwrap()
+            .insert("list", Llsd::Array(vec![Llsd::from("x")]))
+            .unwrap()
+            .insert("n", 5)
+            .unwrap();
+        assert_eq!(doc[Pointer("/a~1b/7")], Llsd::Integer(1));
+        assert_eq!(doc[Pointer("/list/0")], Llsd::from("x"));
+        assert_eq!(doc[Pointer("")], doc.clone());
+        // Wrong types and malformed pointers read as undefined.
+        assert_eq!(doc[Pointer("/n/deeper/still")], Llsd::Undefined);
+        assert_eq!(doc[Pointer("/list/first")], Llsd::Undefined);
+        assert_eq!(doc[Pointer("list")], Llsd::Undefined);
+
+        doc[Pointer("/a~1b/8")] = Llsd::Integer(2);
+        doc[Pointer("/list/0")] = Llsd::from("y");
+        doc[Pointer("/new/0")] = Llsd::Boolean(true);
+        assert_eq!(doc["a/b"]["8"], Llsd::Integer(2));
+        assert_eq!(doc["list"][0], Llsd::from("y"));
+        assert_eq!(doc["new"]["0"], Llsd::Boolean(true));
+
+        let result = std::panic::catch_unwind(|| {
+            let mut doc = Llsd::Array(vec![]);
+            doc[Pointer("/key")] = Llsd::Undefined;
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn try_index_mut() {
+        use crate::{IndexOptions, Pointer};
+
+        let mut doc = Llsd::map()
+            .insert("n", 5)
+            .unwrap()
+            .insert("list", Llsd::Array(vec![Llsd::Undefined]))
+            .unwrap();
+        *doc.try_index_mut("a").unwrap().try_index_mut("b").unwrap() = Llsd::Integer(1);
+        assert_eq!(doc["a"]["b"], Llsd::Integer(1));
+        *doc.try_index_mut("list").unwrap().try_index_mut(0).unwrap() = Llsd::from("x");
+        assert_eq!(doc["list"][0], Llsd::from("x"));
+
+        let err = doc
+            .try_index_mut("n")
+            .unwrap()
+            .try_index_mut("b")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "invalid type: expected map, found integer");
+        let err = doc.try_index_mut(Pointer("/n/b")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type at n: expected map, found integer"
+        );
+        let err = doc.try_index_mut(Pointer("/list/3")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value at list: index 3 is past the end of an array of length 1"
+        );
+        assert!(doc.try_index_mut(Pointer("no slash")).is_err());
+        assert!(doc.try_index_mut(0).is_ok(), "maps take decimal keys");
+
+        let grow = IndexOptions::new().with_array_growth(4);
+        *doc.try_index_mut_with(Pointer("/list/3"), &grow).unwrap() = Llsd::Integer(3);
+        assert_eq!(doc["list"].len(), 4);
+        assert_eq!(doc["list"][2], Llsd::Undefined);
+        assert!(doc.try_index_mut_with(Pointer("/list/4"), &grow).is_err());
+        *doc.try_index_mut_with(Pointer("/fresh/1/k"), &grow)
+            .unwrap() = Llsd::Boolean(true);
+        assert_eq!(
+            doc["fresh"],
+            Llsd::Array(vec![
+                Llsd::Undefined,
+                Llsd::map().insert("k", true).unwrap()
+            ])
+        );
+        // Without growth, an undefined value indexed by position is an error.
+        let mut undefined = Llsd::Undefined;
+        assert!(undefined.try_index_mut(0).is_err());
+        assert!(undefined.try_index_mut_with(0, &grow).is_ok());
+    }
+
+    #[test]
+    fn checked_narrowing() {
+        assert_eq!(Llsd::Integer(255).to_u8().unwrap(), 255);
+        assert_eq!(Llsd::Real(-3.0).to_i16().unwrap(), -3);
+        assert_eq!(Llsd::Boolean(true).to_u32().unwrap(), 1);
+        assert_eq!(Llsd::String(" 70000 ".into()).to_u16().ok(), None);
+        assert_eq!(Llsd::String("70000".into()).to_u32().unwrap(), 70000);
+
+        let err = Llsd::Integer(-1).to_u32().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &LlsdErrorKind::OutOfRange {
+                target: "u32",
+                value: "-1".into()
+            }
+        );
+        assert_eq!(err.to_string(), "out of range: -1 does not fit in u32");
+        assert_eq!(u32::try_from(&Llsd::Integer(-1)).unwrap(), u32::MAX);
+        assert_eq!(
+            u32::from_llsd(&Llsd::Integer(-1)).unwrap_err().kind(),
+            err.kind()
+        );
+        assert_eq!(u8::from_llsd(&Llsd::Integer(255)).unwrap(), 255);
+
+        assert!(Llsd::Real(1.5).to_i32().is_err());
+        assert!(Llsd::Real(f64::NAN).to_i64().is_err());
+        assert!(Llsd::Integer(300).to_i8().is_err());
+        assert!(Llsd::Integer(-1).to_u64().is_err());
+        assert_eq!(
+            Llsd::Uuid(Uuid::nil()).to_i32().unwrap_err().kind(),
+            &LlsdErrorKind::InvalidType {
+                expected: "integer",
+                found: "uuid"
+            }
+        );
+    }
+
+    #[test]
+    fn undefined_map_entries() {
+        use crate::{UndefinedEntries::*, binary, notation, rpc, xml};
+        let doc = Llsd::map()
+            .insert("gone", Llsd::Undefined)
+            .unwrap()
+            .insert("id", 1)
+            .unwrap()
+            .insert(
+                "inner",
+                Llsd::Array(vec![
+                    Llsd::Undefined,
+                    Llsd::map().insert("x", Llsd::Undefined).unwrap(),
+                ]),
+            )
+            .unwrap();
+        let dropped = Llsd::map()
+            .insert("id", 1)
+            .unwrap()
+            .insert("inner", Llsd::Array(vec![Llsd::Undefined, Llsd::map()]))
+            .unwrap();
+        assert_ne!(doc, dropped);
+
+        let all = |undefined| {
+            let context = notation::FormatterContext::new()
+                .with_sorted_keys(true)
+                .with_undefined_entries(undefined);
+            let notation = notation::to_string(&doc, &context).unwrap();
+            let options = rpc::WriteOptions::new().with_undefined_entries(undefined);
+            let call = rpc::XmlRpc::MethodResponse(doc.clone());
+            let rpc = rpc::to_string_with_options(&call, &options).unwrap();
+            let rpc = match rpc::from_str(&rpc).unwrap() {
+                rpc::XmlRpc::MethodResponse(llsd) => llsd,
+                call => panic!("{call:?}"),
+            };
+            let binary = binary::BinaryEncoder::new()
+                .with_undefined_entries(undefined)
+                .encode(&doc)
+                .unwrap();
+            let xml = xml::XmlEncoder::new()
+                .with_undefined_entries(undefined)
+                .encode(&doc)
+                .unwrap();
+            let ordered = crate::order::OrderedLlsd::new(doc.clone(), Default::default());
+            let ordered = notation::to_string_ordered(
+                &ordered,
+                &notation::FormatterContext::new().with_undefined_entries(undefined),
+            )
+            .unwrap();
+            [
+                binary::from_slice(&binary).unwrap(),
+                notation::from_str(&notation, 8).unwrap(),
+                xml::from_slice(&xml).unwrap(),
+                rpc,
+                notation::from_str(&ordered, 8).unwrap(),
+            ]
+        };
+        for decoded in all(Keep) {
+            assert_eq!(decoded, doc);
+        }
+        for decoded in all(Drop) {
+            assert_eq!(decoded, dropped);
+        }
+        let context = notation::FormatterContext::new()
+            .with_sorted_keys(true)
+            .with_undefined_entries(Drop);
+        assert_eq!(
+            notation::to_string(&doc, &context).unwrap(),
+            "{'id':i1,'inner':[!,{}]}"
+        );
+    }
 }
@@ -0,0 +1,384 @@
+//! Typed requests and responses for the XML-RPC grid login
+//! (`login_to_simulator`).
+//!
+//! The login call is a single XML-RPC struct whose field names and value
+//! conventions (a `$1$`-prefixed MD5 password, MD5-hashed hardware ids, the
+//! option list naming which extra blocks to return) are easy to get subtly
+//! wrong. [`LoginRequest`] fills them in, [`LoginRequest::to_rpc`] produces the
+//! [`XmlRpc`] call to send, and [`LoginResponse::from_rpc`] reads the reply,
+//! turning a refused login into a [`LoginFailure`] error:
+//!
+//! ```
+//! use llsd_rs::login::LoginRequest;
+//!
+//! let request = LoginRequest::new("Avatar", "Resident", "secret")
+//!     .with_channel("Example Viewer", "1.2.3.4")
+//!     .with_start("home");
+//! let body = llsd_rs::rpc::to_string(&request.to_rpc()).unwrap();
+//! assert!(body.contains("<methodName>login_to_simulator</methodName>"));
+//! assert_eq!(request.passwd, "$1$5ebe2294ecd0e0f08eab7690d2a6ee69");
+//! ```
+
+use crate::Uuid;
+
+use crate::rpc::XmlRpc;
+use crate::{FromLlsd, IntoLlsd, LlsdError, LlsdFromTo};
+
+/// XML-RPC method name of the login call.
+pub const LOGIN_METHOD: &str = "login_to_simulator";
+
+/// The `options` the viewer requests: the extra blocks (inventory skeleton,
+/// buddy list, UI config...) included in a successful response.
+pub const DEFAULT_OPTIONS: &[&str] = &[
+    "inventory-root",
+    "inventory-skeleton",
+    "inventory-lib-root",
+    "inventory-lib-owner",
+    "inventory-skel-lib",
+    "initial-outfit",
+    "gestures",
+    "display_names",
+    "event_categories",
+    "event_notifications",
+    "classified_categories",
+    "adult_compliant",
+    "buddy-list",
+    "newuser-config",
+    "ui-config",
+    "max-agent-groups",
+    "map-server-url",
+    "voice-config",
+    "tutorial_setting",
+    "login-flags",
+    "global-textures",
+];
+
+/// Placeholder for `mac` / `id0` when no hardware id is available: an MD5 hex
+/// digest's shape with no information in it.
+pub const NULL_HARDWARE_ID: &str = "00000000000000000000000000000000";
+
+/// The `passwd` value for `password`: `$1$` followed by the MD5 hex digest.
+/// A value that already has the prefix (a saved credential) is returned as is.
+///
+/// The password is hashed as given. Second Life only considers the first 16
+/// characters, and its viewer truncates before hashing; OpenSim grids do not.
+pub fn hash_password(password: &str) -> String {
+    if password.starts_with("$1$") {
+        return password.to_string();
+    }
+    format!("$1${:x}", md5::compute(password))
+}
+
+/// The `mac` / `id0` value for a hardware identifier (a MAC address, a disk
+/// serial): its MD5 hex digest, so the raw id never leaves the machine.
+pub fn hash_hardware_id(id: impl AsRef<[u8]>) -> String {
+    format!("{:x}", md5::compute(id))
+}
+
+/// The `platform` value the viewer sends for the current target.
+pub fn default_platform() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "win"
+    } else if cfg!(target_os = "macos") {
+        "mac"
+    } else {
+        "lnx"
+    }
+}
+
+/// Parameters of a `login_to_simulator` call.
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+pub struct LoginRequest {
+    pub first: String,
+    pub last: String,
+    /// Password as produced by [`hash_password`].
+    pub passwd: String,
+    /// `"last"`, `"home"`, or a `uri:Region&x&y&z` location.
+    pub start: String,
+    /// Viewer channel, e.g. `"Second Life Release"`.
+    pub channel: String,
+    /// Viewer version as `major.minor.patch.build`.
+    pub version: String,
+    pub platform: String,
+    #[llsd(default)]
+    pub platform_version: String,
+    /// Hashed network hardware id (see [`hash_hardware_id`]).
+    pub mac: String,
+    /// Hashed machine serial (see [`hash_hardware_id`]).
+    pub id0: String,
+    /// Digest identifying the viewer build; omitted when `None`.
+    #[llsd(default)]
+    pub viewer_digest: Option<String>,
+    #[llsd(default)]
+    pub agree_to_tos: bool,
+    #[llsd(default)]
+    pub read_critical: bool,
+    /// Pointer width of the client, 32 or 64.
+    #[llsd(default)]
+    pub address_size: i32,
+    /// Ask for failure reasons beyond the legacy set (`mfa_challenge`...).
+    #[llsd(default)]
+    pub extended_errors: bool,
+    /// Second-factor token, empty when not answering an MFA challenge.
+    #[llsd(default)]
+    pub token: String,
+    /// Hash returned by an earlier successful MFA login, to skip the challenge.
+    #[llsd(default)]
+    pub mfa_hash: String,
+    #[llsd(default)]
+    pub options: Vec<String>,
+}
+
+impl LoginRequest {
+    /// A request with the viewer's defaults: start at the last location,
+    /// [`DEFAULT_OPTIONS`], the current platform and [`NULL_HARDWARE_ID`]s.
+    pub fn new(first: impl Into<String>, last: impl Into<String>, password: &str) -> Self {
+        Self {
+            first: first.into(),
+            last: last.into(),
+            passwd: hash_password(password),
+            start: "last".to_string(),
+            channel: "llsd-rs".to_string(),
+            version: concat!(env!("CARGO_PKG_VERSION"), ".0").to_string(),
+            platform: default_platform().to_string(),
+            platform_version: String::new(),
+            mac: NULL_HARDWARE_ID.to_string(),
+            id0: NULL_HARDWARE_ID.to_string(),
+            viewer_digest: None,
+            agree_to_tos: false,
+            read_critical: false,
+            address_size: 64,
+            extended_errors: true,
+            token: String::new(),
+            mfa_hash: String::new(),
+            options: DEFAULT_OPTIONS.iter().map(|o| o.to_string()).collect(),
+        }
+    }
+
+    pub fn with_channel(mut self, channel: impl Into<String>, version: impl Into<String>) -> Self {
+        self.channel = channel.into();
+        self.version = version.into();
+        self
+    }
+
+    pub fn with_start(mut self, start: impl Into<String>) -> Self {
+        self.start = start.into();
+        self
+    }
+
+    /// Hash `mac` and `id0` into the corresponding fields.
+    pub fn with_hardware_ids(mut self, mac: impl AsRef<[u8]>, id0: impl AsRef<[u8]>) -> Self {
+        self.mac = hash_hardware_id(mac);
+        self.id0 = hash_hardware_id(id0);
+        self
+    }
+
+    pub fn with_mfa(mut self, token: impl Into<String>, mfa_hash: impl Into<String>) -> Self {
+        self.token = token.into();
+        self.mfa_hash = mfa_hash.into();
+        self
+    }
+
+    pub fn with_options<I, S>(mut self, options: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options = options.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The `login_to_simulator` method call carrying this request.
+    pub fn to_rpc(&self) -> XmlRpc {
+        XmlRpc::new_method_call(LOGIN_METHOD.to_string(), self.to_llsd())
+    }
+}
+
+/// An entry of the `inventory-root` block.
+#[derive(Debug, Clone, Copy, PartialEq, LlsdFromTo)]
+pub struct FolderId {
+    pub folder_id: Uuid,
+}
+
+/// The fields of a successful login response needed to connect to the
+/// region. Blocks requested through `options` other than `inventory-root`
+/// are left in the raw response.
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+pub struct LoginResponse {
+    pub agent_id: Uuid,
+    pub session_id: Uuid,
+    pub secure_session_id: Uuid,
+    /// The grid sends names wrapped in double quotes; they are removed.
+    #[llsd(with = quoted)]
+    pub first_name: String,
+    #[llsd(with = quoted)]
+    pub last_name: String,
+    pub circuit_code: u32,
+    pub sim_ip: String,
+    pub sim_port: u16,
+    pub region_x: u32,
+    pub region_y: u32,
+    pub seed_capability: String,
+    #[llsd(default)]
+    pub agent_access: Option<String>,
+    #[llsd(default)]
+    pub agent_access_max: Option<String>,
+    #[llsd(default)]
+    pub start_location: Option<String>,
+    /// Initial camera direction, as LLSD notation (`[r1,r0,r0]`).
+    #[llsd(default)]
+    pub look_at: Option<String>,
+    #[llsd(default)]
+    pub seconds_since_epoch: Option<i64>,
+    #[llsd(default)]
+    pub message: Option<String>,
+    /// Hash to send as [`LoginRequest::mfa_hash`] next time.
+    #[llsd(default)]
+    pub mfa_hash: Option<String>,
+    #[llsd(rename = "inventory-root", default)]
+    pub inventory_root: Option<Vec<FolderId>>,
+}
+
+impl LoginResponse {
+    /// Read a login reply. A reply whose `login` field is not `"true"` fails
+    /// with a [`LoginFailure`], which callers can downcast to to inspect the
+    /// reason (`"key"`, `"tos"`, `"mfa_challenge"`...).
+    pub fn from_rpc(rpc: &XmlRpc) -> Result<Self, anyhow::Error> {
+        let llsd = rpc.llsd();
+        let status = llsd
+            .get("login")
+            .ok_or_else(|| LlsdError::missing_field("login"))?;
+        if status.as_string().map(String::as_str) == Some("true") {
+            Ok(Self::from_llsd(llsd)?)
+        } else {
+            Err(LoginFailure::from_llsd(llsd)?.into())
+        }
+    }
+
+    /// The 64-bit region handle: the region's global x and y in meters,
+    /// packed high and low.
+    pub fn region_handle(&self) -> u64 {
+        (u64::from(self.region_x) << 32) | u64::from(self.region_y)
+    }
+}
+
+/// A login the grid refused, or redirected (`login` is `"indeterminate"`,
+/// with `next_url` naming where to retry).
+#[derive(Debug, Clone, PartialEq, LlsdFromTo, thiserror::Error)]
+#[error("login failed ({reason}): {message}")]
+pub struct LoginFailure {
+    #[llsd(default)]
+    pub login: String,
+    #[llsd(default)]
+    pub reason: String,
+    #[llsd(default)]
+    pub message: String,
+    #[llsd(default)]
+    pub next_url: Option<String>,
+    #[llsd(default)]
+    pub next_method: Option<String>,
+}
+
+impl LoginFailure {
+    pub fn is_indeterminate(&self) -> bool {
+        self.login == "indeterminate"
+    }
+}
+
+/// Names travel as `"\"Resident\""`; strip the quotes and surrounding space.
+mod quoted {
+    use crate::Llsd;
+
+    pub fn serialize(value: &String) -> Llsd {
+        Llsd::String(format!("\"{value}\""))
+    }
+
+    pub fn deserialize(llsd: &Llsd) -> anyhow::Result<String> {
+        let value = String::try_from(llsd)?;
+        Ok(value.trim().trim_matches('"').trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Llsd;
+
+    const SUCCESS: &str = r#"<?xml version="1.0"?>
+<methodResponse><params><param><value><struct>
+<member><name>login</name><value><string>true</string></value></member>
+<member><name>agent_id</name><value><string>11111111-2222-3333-4444-555555555555</string></value></member>
+<member><name>session_id</name><value><string>66666666-7777-8888-9999-aaaaaaaaaaaa</string></value></member>
+<member><name>secure_session_id</name><value><string>bbbbbbbb-cccc-dddd-eeee-ffffffffffff</string></value></member>
+<member><name>first_name</name><value><string>"Avatar"</string></value></member>
+<member><name>last_name</name><value><string>Resident</string></value></member>
+<member><name>circuit_code</name><value><int>123456</int></value></member>
+<member><name>sim_ip</name><value><string>192.0.2.10</string></value></member>
+<member><name>sim_port</name><value><int>13005</int></value></member>
+<member><name>region_x</name><value><int>256000</int></value></member>
+<member><name>region_y</name><value><int>256256</int></value></member>
+<member><name>seed_capability</name><value><string>https://sim.example/cap/seed</string></value></member>
+<member><name>look_at</name><value><string>[r0.9,r0.4,r0]</string></value></member>
+<member><name>inventory-root</name><value><array><data><value><struct>
+<member><name>folder_id</name><value><string>01234567-89ab-cdef-0123-456789abcdef</string></value></member>
+</struct></value></data></array></value></member>
+</struct></value></param></params></methodResponse>"#;
+
+    #[test]
+    fn password_and_hardware_hashes() {
+        assert_eq!(
+            hash_password("password"),
+            "$1$5f4dcc3b5aa765d61d8327deb882cf99"
+        );
+        let saved = hash_password("password");
+        assert_eq!(hash_password(&saved), saved);
+        assert_eq!(hash_hardware_id(""), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[test]
+    fn request_fields() {
+        let request = LoginRequest::new("Avatar", "Resident", "password")
+            .with_hardware_ids("00:11:22:33:44:55", "serial")
+            .with_options(["inventory-root"]);
+        let rpc = crate::rpc::from_str(&crate::rpc::to_string(&request.to_rpc()).unwrap()).unwrap();
+        assert_eq!(rpc.method(), Some(LOGIN_METHOD));
+        let params = rpc.llsd();
+        assert_eq!(
+            params["passwd"],
+            Llsd::from("$1$5f4dcc3b5aa765d61d8327deb882cf99")
+        );
+        assert_eq!(params["address_size"], Llsd::Integer(64));
+        assert_eq!(params["mac"].as_string().unwrap().len(), 32);
+        assert!(params.get("viewer_digest").is_none());
+        assert_eq!(LoginRequest::from_llsd(params).unwrap(), request);
+    }
+
+    #[test]
+    fn success_response() {
+        let response = LoginResponse::from_rpc(&crate::rpc::from_str(SUCCESS).unwrap()).unwrap();
+        assert_eq!(response.first_name, "Avatar");
+        assert_eq!(response.last_name, "Resident");
+        assert_eq!(response.sim_port, 13005);
+        assert_eq!(response.region_handle(), (256000 << 32) | 256256);
+        assert_eq!(response.look_at.as_deref(), Some("[r0.9,r0.4,r0]"));
+        assert_eq!(response.inventory_root.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn failure_response() {
+        let reply = Llsd::map()
+            .insert("login", "false")
+            .unwrap()
+            .insert("reason", "key")
+            .unwrap()
+            .insert("message", "Sorry! We couldn't log you in.")
+            .unwrap();
+        let err = LoginResponse::from_rpc(&XmlRpc::new_method_response(reply)).unwrap_err();
+        let failure = err.downcast_ref::<LoginFailure>().unwrap();
+        assert_eq!(failure.reason, "key");
+        assert!(!failure.is_indeterminate());
+
+        let err = LoginResponse::from_rpc(&XmlRpc::new_method_response(Llsd::map())).unwrap_err();
+        assert!(err.downcast_ref::<LlsdError>().is_some());
+    }
+}
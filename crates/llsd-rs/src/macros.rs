@@ -0,0 +1,87 @@
+//! Shorthand constructors for fixtures and small literal documents.
+
+/// Build an [`Llsd::Map`](crate::Llsd::Map) from `key => value` pairs.
+/// Keys are anything `Into<String>` and values anything `Into<Llsd>`,
+/// including nested `llsd_map!` and `llsd_array!`; a repeated key keeps the
+/// last value.
+///
+/// ```
+/// use llsd_rs::{Llsd, llsd_array, llsd_map};
+///
+/// let id = llsd_rs::Uuid::nil();
+/// let message = llsd_map! {
+///     "message" => "TeleportFinish",
+///     "body" => llsd_map! { "AgentID" => id, "Flags" => 16, "Info" => llsd_array![] },
+/// };
+/// assert_eq!(message["body"]["Flags"], Llsd::Integer(16));
+/// ```
+#[macro_export]
+macro_rules! llsd_map {
+    () => {
+        $crate::Llsd::Map($crate::LlsdMap::new())
+    };
+    ($($key:expr => $value:expr),+ $(,)?) => {{
+        let mut map = $crate::LlsdMap::with_capacity([$(stringify!($key)),+].len());
+        $(
+            map.insert(
+                ::core::convert::Into::<::std::string::String>::into($key),
+                $crate::Llsd::from($value),
+            );
+        )+
+        $crate::Llsd::Map(map)
+    }};
+}
+
+/// Build an [`Llsd::Array`](crate::Llsd::Array) from values that are
+/// `Into<Llsd>`, which needn't share a type.
+///
+/// ```
+/// use llsd_rs::{Llsd, llsd_array};
+///
+/// assert_eq!(
+///     llsd_array![1, "two", 3.0, llsd_array![]],
+///     Llsd::Array(vec![
+///         Llsd::Integer(1),
+///         Llsd::String("two".into()),
+///         Llsd::Real(3.0),
+///         Llsd::Array(vec![]),
+///     ])
+/// );
+/// ```
+#[macro_export]
+macro_rules! llsd_array {
+    ($($value:expr),* $(,)?) => {
+        $crate::Llsd::Array(::std::vec![$($crate::Llsd::from($value)),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Llsd, LlsdMap};
+
+    #[test]
+    fn builds_nested_values() {
+        let key = String::from("dynamic");
+        let built = llsd_map! {
+            "a" => 1,
+            key.clone() => llsd_array![true, "x", llsd_map! {}],
+            "a" => 2,
+        };
+        let mut expected = LlsdMap::new();
+        expected.insert("a".into(), Llsd::Integer(2));
+        expected.insert(
+            key,
+            Llsd::Array(vec![
+                Llsd::Boolean(true),
+                Llsd::String("x".into()),
+                Llsd::Map(LlsdMap::new()),
+            ]),
+        );
+        assert_eq!(built, Llsd::Map(expected));
+        assert_eq!(llsd_array![], Llsd::Array(vec![]));
+        assert_eq!(
+            llsd_array![Llsd::Undefined,],
+            Llsd::Array(vec![Llsd::Undefined])
+        );
+    }
+}
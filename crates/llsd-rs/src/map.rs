@@ -0,0 +1,724 @@
+//! [`LlsdMap`], the storage behind [`Llsd::Map`].
+//!
+//! Most LLSD maps seen on the wire have a handful of keys, where hashing every
+//! key on insert and lookup costs more than comparing a few strings. Maps with
+//! up to [`SMALL_MAP_MAX`] entries are kept as a vector of pairs in insertion
+//! order and searched linearly; inserting past that switches to a `HashMap`.
+//! Iteration order is unspecified either way, and equality ignores it.
+//!
+//! With the `sorted-map` feature the small vector is kept sorted by key and
+//! the large representation is a `BTreeMap`, so every map iterates (and
+//! every writer emits) keys in order, and [`LlsdMap::range`] selects keys
+//! by range. The API is otherwise the same, so code and derived types
+//! compile unchanged with either backend.
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+#[cfg(feature = "sorted-map")]
+use std::ops::{Bound, RangeBounds};
+use std::{fmt, ops, slice, vec};
+
+#[cfg(feature = "sorted-map")]
+use std::collections::btree_map as large;
+#[cfg(not(feature = "sorted-map"))]
+use std::collections::hash_map as large;
+
+use crate::Llsd;
+
+/// The representation past [`SMALL_MAP_MAX`] entries.
+#[cfg(not(feature = "sorted-map"))]
+type LargeMap = HashMap<String, Llsd>;
+#[cfg(feature = "sorted-map")]
+type LargeMap = BTreeMap<String, Llsd>;
+
+#[cfg(not(feature = "sorted-map"))]
+fn large_with_capacity(capacity: usize) -> LargeMap {
+    HashMap::with_capacity(capacity)
+}
+
+#[cfg(feature = "sorted-map")]
+fn large_with_capacity(_capacity: usize) -> LargeMap {
+    BTreeMap::new()
+}
+
+/// Largest map kept in the linear (vector) representation.
+pub const SMALL_MAP_MAX: usize = 8;
+
+/// A `String` → [`Llsd`] map with a `HashMap`-like API.
+#[derive(Clone)]
+pub struct LlsdMap {
+    repr: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    Small(Vec<(String, Llsd)>),
+    Large(LargeMap),
+}
+
+impl LlsdMap {
+    pub const fn new() -> Self {
+        LlsdMap {
+            repr: Repr::Small(Vec::new()),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let repr = if capacity <= SMALL_MAP_MAX {
+            Repr::Small(Vec::with_capacity(capacity))
+        } else {
+            Repr::Large(large_with_capacity(capacity))
+        };
+        LlsdMap { repr }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Small(entries) => entries.len(),
+            Repr::Large(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.repr = Repr::Small(Vec::new());
+    }
+
+    /// Remove every entry, handing each value to `f`, and keep the
+    /// allocation for reuse.
+    pub(crate) fn drain_values(&mut self, mut f: impl FnMut(Llsd)) {
+        match &mut self.repr {
+            Repr::Small(entries) => entries.drain(..).for_each(|(_, v)| f(v)),
+            #[cfg(not(feature = "sorted-map"))]
+            Repr::Large(map) => map.drain().for_each(|(_, v)| f(v)),
+            #[cfg(feature = "sorted-map")]
+            Repr::Large(map) => std::mem::take(map).into_values().for_each(f),
+        }
+    }
+
+    /// Entries the map can hold without allocating; a sorted large map
+    /// always allocates.
+    pub(crate) fn capacity(&self) -> usize {
+        match &self.repr {
+            Repr::Small(entries) => entries.capacity(),
+            #[cfg(not(feature = "sorted-map"))]
+            Repr::Large(map) => map.capacity(),
+            #[cfg(feature = "sorted-map")]
+            Repr::Large(_) => 0,
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&Llsd>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        match &self.repr {
+            Repr::Small(entries) => entries
+                .iter()
+                .find(|(k, _)| k.borrow() == key)
+                .map(|(_, v)| v),
+            Repr::Large(map) => map.get(key),
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut Llsd>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Small(entries) => entries
+                .iter_mut()
+                .find(|(k, _)| (*k).borrow() == key)
+                .map(|(_, v)| v),
+            Repr::Large(map) => map.get_mut(key),
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        String: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Insert a value, returning the previous value for the key.
+    pub fn insert(&mut self, key: String, value: Llsd) -> Option<Llsd> {
+        if let Some(slot) = self.get_mut(key.as_str()) {
+            return Some(std::mem::replace(slot, value));
+        }
+        self.push_new(key, value);
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Llsd>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Small(entries) => {
+                let index = entries.iter().position(|(k, _)| k.borrow() == key)?;
+                if cfg!(feature = "sorted-map") {
+                    Some(entries.remove(index).1)
+                } else {
+                    Some(entries.swap_remove(index).1)
+                }
+            }
+            Repr::Large(map) => map.remove(key),
+        }
+    }
+
+    /// The value for `key`, inserting one from `default` if it is absent.
+    pub fn get_or_insert_with(&mut self, key: String, default: impl FnOnce() -> Llsd) -> &mut Llsd {
+        let position = match &self.repr {
+            Repr::Small(entries) => entries.iter().position(|(k, _)| *k == key),
+            Repr::Large(_) => None,
+        };
+        if position.is_none() && matches!(self.repr, Repr::Small(_)) {
+            return self.push_new(key, default());
+        }
+        match &mut self.repr {
+            Repr::Small(entries) => &mut entries[position.expect("found above")].1,
+            Repr::Large(map) => map.entry(key).or_insert_with(default),
+        }
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&String, &mut Llsd) -> bool) {
+        match &mut self.repr {
+            Repr::Small(entries) => entries.retain_mut(|(k, v)| keep(k, v)),
+            Repr::Large(map) => map.retain(|k, v| keep(k, v)),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(match &self.repr {
+            Repr::Small(entries) => IterRepr::Small(entries.iter()),
+            Repr::Large(map) => IterRepr::Large(map.iter()),
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut(match &mut self.repr {
+            Repr::Small(entries) => IterMutRepr::Small(entries.iter_mut()),
+            Repr::Large(map) => IterMutRepr::Large(map.iter_mut()),
+        })
+    }
+
+    pub fn keys(&self) -> Keys<'_> {
+        Keys(self.iter())
+    }
+
+    pub fn values(&self) -> Values<'_> {
+        Values(self.iter())
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// The entries with keys in `range`, in key order.
+    ///
+    /// ```
+    /// use llsd_rs::{Llsd, LlsdMap};
+    ///
+    /// let map: LlsdMap = ["a", "b", "c", "d"]
+    ///     .map(|k| (k.to_string(), Llsd::from(k)))
+    ///     .into();
+    /// let keys: Vec<_> = map.range("b".to_string().."d".to_string()).map(|(k, _)| k).collect();
+    /// assert_eq!(keys, ["b", "c"]);
+    ///
+    /// // `str` bounds go through a tuple, as with `BTreeMap`.
+    /// use std::ops::Bound::{Excluded, Unbounded};
+    /// assert_eq!(map.range::<str, _>((Excluded("c"), Unbounded)).count(), 1);
+    /// ```
+    #[cfg(feature = "sorted-map")]
+    pub fn range<Q, R>(&self, range: R) -> Range<'_>
+    where
+        String: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range(match &self.repr {
+            Repr::Small(entries) => {
+                let after_start = |k: &String| match range.start_bound() {
+                    Bound::Included(start) => k.borrow() >= start,
+                    Bound::Excluded(start) => k.borrow() > start,
+                    Bound::Unbounded => true,
+                };
+                let before_end = |k: &String| match range.end_bound() {
+                    Bound::Included(end) => k.borrow() <= end,
+                    Bound::Excluded(end) => k.borrow() < end,
+                    Bound::Unbounded => true,
+                };
+                let start = entries.partition_point(|(k, _)| !after_start(k));
+                let end = start + entries[start..].partition_point(|(k, _)| before_end(k));
+                RangeRepr::Small(entries[start..end].iter())
+            }
+            Repr::Large(map) => RangeRepr::Large(map.range(range)),
+        })
+    }
+
+    /// Append a key known to be absent (in key order, with `sorted-map`),
+    /// switching to the large representation once the linear one is full.
+    fn push_new(&mut self, key: String, value: Llsd) -> &mut Llsd {
+        if let Repr::Small(entries) = &mut self.repr
+            && entries.len() >= SMALL_MAP_MAX
+        {
+            let mut map = large_with_capacity(entries.len() * 2);
+            map.extend(entries.drain(..));
+            self.repr = Repr::Large(map);
+        }
+        match &mut self.repr {
+            Repr::Small(entries) => {
+                let index = if cfg!(feature = "sorted-map") {
+                    entries.partition_point(|(k, _)| *k < key)
+                } else {
+                    entries.len()
+                };
+                entries.insert(index, (key, value));
+                &mut entries[index].1
+            }
+            Repr::Large(map) => match map.entry(key) {
+                large::Entry::Vacant(entry) => entry.insert(value),
+                large::Entry::Occupied(entry) => {
+                    let slot = entry.into_mut();
+                    *slot = value;
+                    slot
+                }
+            },
+        }
+    }
+}
+
+impl Default for LlsdMap {
+    fn default() -> Self {
+        LlsdMap::new()
+    }
+}
+
+impl PartialEq for LlsdMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k.as_str()) == Some(v))
+    }
+}
+
+impl fmt::Debug for LlsdMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<Q> ops::Index<&Q> for LlsdMap
+where
+    String: Borrow<Q>,
+    Q: Hash + Ord + ?Sized,
+{
+    type Output = Llsd;
+
+    fn index(&self, key: &Q) -> &Llsd {
+        self.get(key).expect("key not found in LlsdMap")
+    }
+}
+
+impl From<HashMap<String, Llsd>> for LlsdMap {
+    #[cfg(not(feature = "sorted-map"))]
+    fn from(map: HashMap<String, Llsd>) -> Self {
+        if map.len() <= SMALL_MAP_MAX {
+            LlsdMap {
+                repr: Repr::Small(map.into_iter().collect()),
+            }
+        } else {
+            LlsdMap {
+                repr: Repr::Large(map),
+            }
+        }
+    }
+
+    #[cfg(feature = "sorted-map")]
+    fn from(map: HashMap<String, Llsd>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<LlsdMap> for HashMap<String, Llsd> {
+    fn from(map: LlsdMap) -> Self {
+        match map.repr {
+            Repr::Small(entries) => entries.into_iter().collect(),
+            #[cfg(not(feature = "sorted-map"))]
+            Repr::Large(map) => map,
+            #[cfg(feature = "sorted-map")]
+            Repr::Large(map) => map.into_iter().collect(),
+        }
+    }
+}
+
+impl From<BTreeMap<String, Llsd>> for LlsdMap {
+    #[cfg(not(feature = "sorted-map"))]
+    fn from(map: BTreeMap<String, Llsd>) -> Self {
+        map.into_iter().collect()
+    }
+
+    #[cfg(feature = "sorted-map")]
+    fn from(map: BTreeMap<String, Llsd>) -> Self {
+        if map.len() <= SMALL_MAP_MAX {
+            LlsdMap {
+                repr: Repr::Small(map.into_iter().collect()),
+            }
+        } else {
+            LlsdMap {
+                repr: Repr::Large(map),
+            }
+        }
+    }
+}
+
+impl From<LlsdMap> for BTreeMap<String, Llsd> {
+    fn from(map: LlsdMap) -> Self {
+        match map.repr {
+            Repr::Small(entries) => entries.into_iter().collect(),
+            #[cfg(not(feature = "sorted-map"))]
+            Repr::Large(map) => map.into_iter().collect(),
+            #[cfg(feature = "sorted-map")]
+            Repr::Large(map) => map,
+        }
+    }
+}
+
+impl<const N: usize> From<[(String, Llsd); N]> for LlsdMap {
+    fn from(entries: [(String, Llsd); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl FromIterator<(String, Llsd)> for LlsdMap {
+    fn from_iter<I: IntoIterator<Item = (String, Llsd)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut map = LlsdMap::with_capacity(iter.size_hint().0);
+        map.extend(iter);
+        map
+    }
+}
+
+impl Extend<(String, Llsd)> for LlsdMap {
+    fn extend<I: IntoIterator<Item = (String, Llsd)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl IntoIterator for LlsdMap {
+    type Item = (String, Llsd);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter(match self.repr {
+            Repr::Small(entries) => IntoIterRepr::Small(entries.into_iter()),
+            Repr::Large(map) => IntoIterRepr::Large(map.into_iter()),
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a LlsdMap {
+    type Item = (&'a String, &'a Llsd);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut LlsdMap {
+    type Item = (&'a String, &'a mut Llsd);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> IterMut<'a> {
+        self.iter_mut()
+    }
+}
+
+// Iterators -------------------------------------------------------------------------------------
+
+/// Borrowing iterator over the entries of an [`LlsdMap`].
+#[derive(Clone)]
+pub struct Iter<'a>(IterRepr<'a>);
+
+#[derive(Clone)]
+enum IterRepr<'a> {
+    Small(slice::Iter<'a, (String, Llsd)>),
+    Large(large::Iter<'a, String, Llsd>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a Llsd);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterRepr::Small(iter) => iter.next().map(|(k, v)| (k, v)),
+            IterRepr::Large(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IterRepr::Small(iter) => iter.size_hint(),
+            IterRepr::Large(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+/// Mutable iterator over the entries of an [`LlsdMap`].
+pub struct IterMut<'a>(IterMutRepr<'a>);
+
+enum IterMutRepr<'a> {
+    Small(slice::IterMut<'a, (String, Llsd)>),
+    Large(large::IterMut<'a, String, Llsd>),
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a String, &'a mut Llsd);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterMutRepr::Small(iter) => iter.next().map(|(k, v)| (&*k, v)),
+            IterMutRepr::Large(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IterMutRepr::Small(iter) => iter.size_hint(),
+            IterMutRepr::Large(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for IterMut<'_> {}
+
+/// Owning iterator over the entries of an [`LlsdMap`].
+pub struct IntoIter(IntoIterRepr);
+
+enum IntoIterRepr {
+    Small(vec::IntoIter<(String, Llsd)>),
+    Large(large::IntoIter<String, Llsd>),
+}
+
+impl Iterator for IntoIter {
+    type Item = (String, Llsd);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IntoIterRepr::Small(iter) => iter.next(),
+            IntoIterRepr::Large(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IntoIterRepr::Small(iter) => iter.size_hint(),
+            IntoIterRepr::Large(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+/// Iterator over a key range of an [`LlsdMap`], from [`LlsdMap::range`].
+#[cfg(feature = "sorted-map")]
+#[derive(Clone)]
+pub struct Range<'a>(RangeRepr<'a>);
+
+#[cfg(feature = "sorted-map")]
+#[derive(Clone)]
+enum RangeRepr<'a> {
+    Small(slice::Iter<'a, (String, Llsd)>),
+    Large(large::Range<'a, String, Llsd>),
+}
+
+#[cfg(feature = "sorted-map")]
+impl<'a> Iterator for Range<'a> {
+    type Item = (&'a String, &'a Llsd);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            RangeRepr::Small(iter) => iter.next().map(|(k, v)| (k, v)),
+            RangeRepr::Large(iter) => iter.next(),
+        }
+    }
+}
+
+#[cfg(feature = "sorted-map")]
+impl DoubleEndedIterator for Range<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            RangeRepr::Small(iter) => iter.next_back().map(|(k, v)| (k, v)),
+            RangeRepr::Large(iter) => iter.next_back(),
+        }
+    }
+}
+
+/// Iterator over the keys of an [`LlsdMap`].
+#[derive(Clone)]
+pub struct Keys<'a>(Iter<'a>);
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = &'a String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Keys<'_> {}
+
+/// Iterator over the values of an [`LlsdMap`].
+#[derive(Clone)]
+pub struct Values<'a>(Iter<'a>);
+
+impl<'a> Iterator for Values<'a> {
+    type Item = &'a Llsd;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Values<'_> {}
+
+/// Mutable iterator over the values of an [`LlsdMap`].
+pub struct ValuesMut<'a>(IterMut<'a>);
+
+impl<'a> Iterator for ValuesMut<'a> {
+    type Item = &'a mut Llsd;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ValuesMut<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_small(map: &LlsdMap) -> bool {
+        matches!(map.repr, Repr::Small(_))
+    }
+
+    #[test]
+    fn grows_from_linear_to_hashed() {
+        let mut map = LlsdMap::new();
+        for i in 0..SMALL_MAP_MAX {
+            assert_eq!(map.insert(format!("k{i}"), Llsd::Integer(i as i32)), None);
+        }
+        assert!(is_small(&map));
+        assert_eq!(
+            map.insert("k0".into(), Llsd::Integer(-1)),
+            Some(Llsd::Integer(0))
+        );
+        assert!(is_small(&map));
+
+        map.insert("extra".into(), Llsd::Boolean(true));
+        assert!(!is_small(&map));
+        assert_eq!(map.len(), SMALL_MAP_MAX + 1);
+        assert_eq!(map.get("k0"), Some(&Llsd::Integer(-1)));
+        assert_eq!(map["extra"], Llsd::Boolean(true));
+        assert_eq!(map.remove("k1"), Some(Llsd::Integer(1)));
+        assert!(!map.contains_key("k1"));
+    }
+
+    #[test]
+    fn equality_ignores_representation_and_order() {
+        let small: LlsdMap = [
+            ("a".to_string(), Llsd::Integer(1)),
+            ("b".to_string(), Llsd::Integer(2)),
+        ]
+        .into();
+        let large = LlsdMap::from(HashMap::<String, Llsd>::from_iter(small.clone()));
+        let mut reversed = LlsdMap::with_capacity(SMALL_MAP_MAX + 1);
+        reversed.insert("b".into(), Llsd::Integer(2));
+        reversed.insert("a".into(), Llsd::Integer(1));
+        assert!(!is_small(&reversed));
+        assert_eq!(small, large);
+        assert_eq!(small, reversed);
+        reversed.insert("c".into(), Llsd::Undefined);
+        assert_ne!(small, reversed);
+    }
+
+    #[test]
+    fn get_or_insert_with_and_retain() {
+        let mut map = LlsdMap::new();
+        *map.get_or_insert_with("a".into(), || Llsd::Integer(0)) = Llsd::Integer(5);
+        assert_eq!(
+            *map.get_or_insert_with("a".into(), || Llsd::Integer(0)),
+            Llsd::Integer(5)
+        );
+        map.insert("b".into(), Llsd::Undefined);
+        map.retain(|_, v| !v.is_undefined());
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["a"]);
+    }
+
+    #[test]
+    fn converts_to_and_from_btree_map() {
+        let sorted: BTreeMap<String, Llsd> = (0..20)
+            .map(|i| (format!("k{i:02}"), Llsd::Integer(i)))
+            .collect();
+        let map = LlsdMap::from(sorted.clone());
+        assert_eq!(map.len(), 20);
+        assert_eq!(map["k07"], Llsd::Integer(7));
+        assert_eq!(BTreeMap::from(map), sorted);
+    }
+
+    #[cfg(feature = "sorted-map")]
+    #[test]
+    fn sorted_iteration_and_ranges() {
+        for len in [3, SMALL_MAP_MAX + 5] {
+            let mut map = LlsdMap::new();
+            for i in (0..len).rev() {
+                map.insert(format!("k{i:02}"), Llsd::Integer(i as i32));
+            }
+            map.remove("k01");
+            assert_eq!(is_small(&map), len <= SMALL_MAP_MAX);
+            let keys: Vec<_> = map.keys().cloned().collect();
+            let mut sorted = keys.clone();
+            sorted.sort();
+            assert_eq!(keys, sorted);
+            assert_eq!(keys[0], "k00");
+
+            let range: Vec<_> = map
+                .range("k01".to_string()..="k02".to_string())
+                .map(|(k, _)| k.as_str())
+                .collect();
+            assert_eq!(range, ["k02"]);
+            assert_eq!(map.range::<String, _>(..).count(), len - 1);
+            assert_eq!(
+                map.range::<String, _>(..)
+                    .next_back()
+                    .map(|(k, _)| k.clone()),
+                Some(format!("k{:02}", len - 1))
+            );
+        }
+    }
+}
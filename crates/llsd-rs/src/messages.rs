@@ -0,0 +1,251 @@
+//! Typed bodies for common capability and event-queue messages.
+//!
+//! Each struct is declared in the table below and derives its conversions, so
+//! applications can work with fields instead of indexing maps by string. A
+//! struct naming a capability or event implements [`Message`]; the nested
+//! records they contain do not.
+//!
+//! ```
+//! use llsd_rs::messages::{EstablishAgentCommunication, EventQueueResponse};
+//! use llsd_rs::{FromLlsd, Llsd};
+//!
+//! let reply = llsd_rs::notation::from_str(
+//!     "{'id':i3,'events':[{'message':'EstablishAgentCommunication','body':\
+//!      {'agent-id':u11111111-2222-3333-4444-555555555555,\
+//!       'sim-ip-and-port':'192.0.2.10:13005','seed-capability':'https://sim.example/seed'}}]}",
+//!     8,
+//! )
+//! .unwrap();
+//! let reply = EventQueueResponse::from_llsd(&reply).unwrap();
+//! let event = reply.events[0].decode::<EstablishAgentCommunication>().unwrap().unwrap();
+//! assert_eq!(event.sim_ip_and_port, "192.0.2.10:13005");
+//! ```
+
+use crate::Uuid;
+
+use crate::{Date, FromLlsd, IntoLlsd, Llsd, LlsdError, LlsdFromTo};
+
+/// A message body with a wire name: the capability it is sent to or returned
+/// from, or the event-queue `message` that carries it.
+pub trait Message: FromLlsd + IntoLlsd {
+    const NAME: &'static str;
+}
+
+/// Declares the structs. `Type = "Name"` implements [`Message`] with that
+/// name; attributes (docs, `#[llsd(...)]`) pass through to the struct and its
+/// fields.
+macro_rules! messages {
+    ($(
+        $(#[$meta:meta])*
+        $name:ident $(= $wire:literal)? {
+            $( $(#[$field_meta:meta])* $field:ident : $ty:ty ),* $(,)?
+        }
+    )*) => {
+        $(
+            #[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+            $(#[$meta])*
+            pub struct $name {
+                $( $(#[$field_meta])* pub $field: $ty, )*
+            }
+
+            $(
+                impl Message for $name {
+                    const NAME: &'static str = $wire;
+                }
+            )?
+        )*
+
+        /// `(name, type)` for every [`Message`] in this module.
+        pub const MESSAGES: &[(&str, &str)] = &[
+            $( $( ($wire, stringify!($name)), )? )*
+        ];
+    };
+}
+
+messages! {
+    /// `GET` the media of an object's faces, or `UPDATE` them with
+    /// `object_media_data`.
+    ObjectMediaRequest = "ObjectMedia" {
+        verb: String,
+        object_id: Uuid,
+        #[llsd(default)]
+        object_media_data: Option<Vec<Option<MediaEntry>>>,
+    }
+
+    /// Media of an object, one entry per face; faces without media are `None`.
+    ObjectMediaResponse = "ObjectMedia" {
+        object_id: Uuid,
+        #[llsd(default)]
+        object_media_version: Option<String>,
+        #[llsd(default)]
+        object_media_data: Vec<Option<MediaEntry>>,
+    }
+
+    /// The media settings of one face (`LLMediaEntry`).
+    MediaEntry {
+        alt_image_enable: bool,
+        auto_loop: bool,
+        auto_play: bool,
+        auto_scale: bool,
+        auto_zoom: bool,
+        controls: i32,
+        current_url: String,
+        first_click_interact: bool,
+        height_pixels: i32,
+        home_url: String,
+        perms_control: i32,
+        perms_interact: i32,
+        #[llsd(default)]
+        whitelist: Vec<String>,
+        #[llsd(default)]
+        whitelist_enable: bool,
+        width_pixels: i32,
+    }
+
+    /// Reply to `GET GetDisplayNames?ids=...`.
+    DisplayNamesResponse = "GetDisplayNames" {
+        agents: Vec<AgentDisplayName>,
+        #[llsd(default)]
+        bad_ids: Vec<Uuid>,
+        #[llsd(default)]
+        bad_usernames: Vec<String>,
+    }
+
+    AgentDisplayName {
+        id: Uuid,
+        username: String,
+        display_name: String,
+        legacy_first_name: String,
+        legacy_last_name: String,
+        #[llsd(default)]
+        is_display_name_default: bool,
+        #[llsd(default)]
+        display_name_next_update: Option<Date>,
+        #[llsd(default)]
+        display_name_expires: Option<Date>,
+    }
+
+    FetchInventoryRequest = "FetchInventory2" {
+        agent_id: Uuid,
+        items: Vec<InventoryItemRef>,
+    }
+
+    InventoryItemRef {
+        owner_id: Uuid,
+        item_id: Uuid,
+    }
+
+    FetchInventoryResponse = "FetchInventory2" {
+        agent_id: Uuid,
+        items: Vec<InventoryItem>,
+    }
+
+    /// An inventory item (`LLViewerInventoryItem::asLLSD`).
+    InventoryItem {
+        item_id: Uuid,
+        parent_id: Uuid,
+        /// Absent for items whose asset id is hidden (`shadow_id` instead).
+        #[llsd(default)]
+        asset_id: Option<Uuid>,
+        name: String,
+        #[llsd(default)]
+        desc: String,
+        r#type: i32,
+        inv_type: i32,
+        #[llsd(default)]
+        flags: i32,
+        /// Seconds since the epoch.
+        #[llsd(default)]
+        created_at: i64,
+        sale_info: SaleInfo,
+        permissions: Permissions,
+    }
+
+    SaleInfo {
+        sale_price: i32,
+        sale_type: i32,
+    }
+
+    Permissions {
+        creator_id: Uuid,
+        owner_id: Uuid,
+        last_owner_id: Uuid,
+        group_id: Uuid,
+        #[llsd(default)]
+        is_owner_group: bool,
+        base_mask: i32,
+        owner_mask: i32,
+        group_mask: i32,
+        everyone_mask: i32,
+        next_owner_mask: i32,
+    }
+
+    /// A long poll of the event queue; `ack` is the `id` of the last reply
+    /// received, `None` on the first poll.
+    EventQueueRequest = "EventQueueGet" {
+        #[llsd(default)]
+        ack: Option<i32>,
+        #[llsd(default)]
+        done: bool,
+    }
+
+    EventQueueResponse = "EventQueueGet" {
+        id: i32,
+        events: Vec<Event>,
+    }
+
+    /// One queued event: its message name and undecoded body.
+    Event {
+        message: String,
+        body: Llsd,
+    }
+
+    /// Sent when a neighbouring or destination region becomes reachable.
+    #[llsd(rename_all = "kebab-case")]
+    EstablishAgentCommunication = "EstablishAgentCommunication" {
+        agent_id: Uuid,
+        sim_ip_and_port: String,
+        seed_capability: String,
+    }
+
+    TeleportFinish = "TeleportFinish" {
+        #[llsd(rename = "Info")]
+        info: Vec<TeleportFinishInfo>,
+    }
+
+    TeleportFinishInfo {
+        #[llsd(rename = "AgentID")]
+        agent_id: Uuid,
+        #[llsd(rename = "LocationID")]
+        location_id: i32,
+        /// IPv4 address in network byte order.
+        #[llsd(rename = "SimIP")]
+        sim_ip: [u8; 4],
+        #[llsd(rename = "SimPort")]
+        sim_port: i32,
+        #[llsd(rename = "RegionHandle", int_as = "binary")]
+        region_handle: u64,
+        #[llsd(rename = "SeedCapability")]
+        seed_capability: String,
+        #[llsd(rename = "SimAccess")]
+        sim_access: i32,
+        /// Big-endian `U32` of teleport flags.
+        #[llsd(rename = "TeleportFlags")]
+        teleport_flags: [u8; 4],
+    }
+}
+
+impl Event {
+    /// A new event carrying `message`.
+    pub fn new<T: Message>(message: &T) -> Self {
+        Self {
+            message: T::NAME.to_string(),
+            body: message.to_llsd(),
+        }
+    }
+
+    /// The body as `T`, or `None` when the event is a different message.
+    pub fn decode<T: Message>(&self) -> Option<Result<T, LlsdError>> {
+        (self.message == T::NAME).then(|| T::from_llsd(&self.body).map_err(|e| e.with_key("body")))
+    }
+}
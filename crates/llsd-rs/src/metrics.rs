@@ -0,0 +1,257 @@
+//! Hooks for exporting statistics about LLSD traffic.
+//!
+//! Install a [`Metrics`] implementation with [`set_metrics`] and the
+//! slice- and string-based parse and write functions of [`crate::binary`],
+//! [`crate::notation`] and [`crate::xml`] report a [`Sample`] per call (the
+//! format, direction, byte and value counts, time taken and outcome), and
+//! every parser and writer reports the [`Limit`]s it enforces when input or
+//! a value hits one. With nothing installed the hooks cost one atomic load.
+//!
+//! ```
+//! use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+//! use llsd_rs::metrics::{self, Metrics, Operation, Sample};
+//!
+//! #[derive(Default)]
+//! struct BytesIn(AtomicUsize);
+//!
+//! impl Metrics for BytesIn {
+//!     fn record(&self, sample: &Sample) {
+//!         if sample.operation == Operation::Parse {
+//!             self.0.fetch_add(sample.bytes, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let counter = Arc::new(BytesIn::default());
+//! metrics::set_metrics(counter.clone());
+//! llsd_rs::binary::from_slice(b"i\0\0\0\x01").unwrap();
+//! metrics::clear_metrics();
+//! assert!(counter.0.load(Ordering::Relaxed) >= 5);
+//! ```
+
+use std::{
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{Llsd, autodetect::LlsdEncoding};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Parse,
+    Write,
+}
+
+/// One parse or write call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub format: LlsdEncoding,
+    pub operation: Operation,
+    /// Bytes read, or written (0 when the write failed).
+    pub bytes: usize,
+    /// Values decoded or encoded, containers and their elements alike
+    /// (0 when a parse failed).
+    pub values: usize,
+    pub duration: Duration,
+    pub success: bool,
+}
+
+/// A limit a parser or writer enforces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// Containers nested past the maximum depth.
+    Depth,
+    /// A string or binary longer than the decoder accepts.
+    Length,
+    /// A container with more entries than the decoder accepts.
+    Entries,
+}
+
+/// Receives statistics. Called on the parsing or writing thread, so
+/// implementations should be quick (atomic counters, a channel).
+pub trait Metrics: Send + Sync {
+    fn record(&self, sample: &Sample);
+
+    fn limit_hit(&self, format: LlsdEncoding, operation: Operation, limit: Limit) {
+        let _ = (format, operation, limit);
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static METRICS: RwLock<Option<Arc<dyn Metrics>>> = RwLock::new(None);
+
+/// Report to `metrics` from now on, replacing whatever was installed.
+pub fn set_metrics(metrics: Arc<dyn Metrics>) {
+    *METRICS.write().unwrap_or_else(|e| e.into_inner()) = Some(metrics);
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Stop reporting.
+pub fn clear_metrics() {
+    ENABLED.store(false, Ordering::Release);
+    *METRICS.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn installed() -> Option<Arc<dyn Metrics>> {
+    if !ENABLED.load(Ordering::Acquire) {
+        return None;
+    }
+    METRICS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// The number of values in `llsd`, itself included.
+fn count(llsd: &Llsd) -> usize {
+    let mut stack = vec![llsd];
+    let mut values = 0;
+    while let Some(llsd) = stack.pop() {
+        values += 1;
+        match llsd {
+            Llsd::Array(items) => stack.extend(items),
+            Llsd::Map(map) => stack.extend(map.values()),
+            _ => {}
+        }
+    }
+    values
+}
+
+/// Run the parse `f` of `bytes` bytes, reporting it.
+pub(crate) fn parse<E>(
+    format: LlsdEncoding,
+    bytes: usize,
+    f: impl FnOnce() -> Result<Llsd, E>,
+) -> Result<Llsd, E> {
+    let Some(metrics) = installed() else {
+        return f();
+    };
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+    metrics.record(&Sample {
+        format,
+        operation: Operation::Parse,
+        bytes,
+        values: result.as_ref().map_or(0, count),
+        duration,
+        success: result.is_ok(),
+    });
+    result
+}
+
+/// Run the write `f` of `llsd`, reporting it.
+pub(crate) fn write<T: AsRef<[u8]>, E>(
+    format: LlsdEncoding,
+    llsd: &Llsd,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let Some(metrics) = installed() else {
+        return f();
+    };
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+    metrics.record(&Sample {
+        format,
+        operation: Operation::Write,
+        bytes: result.as_ref().map_or(0, |out| out.as_ref().len()),
+        values: count(llsd),
+        duration,
+        success: result.is_ok(),
+    });
+    result
+}
+
+/// Report that a parse or write ran into `limit`.
+pub(crate) fn limit_hit(format: LlsdEncoding, operation: Operation, limit: Limit) {
+    if let Some(metrics) = installed() {
+        metrics.limit_hit(format, operation, limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, thread::ThreadId};
+
+    use super::*;
+    use crate::notation::FormatterContext;
+
+    /// Keeps what the current thread reports; other tests run in parallel
+    /// with the same global hook.
+    struct Recorder {
+        thread: ThreadId,
+        samples: Mutex<Vec<Sample>>,
+        limits: Mutex<Vec<(LlsdEncoding, Operation, Limit)>>,
+    }
+
+    impl Metrics for Recorder {
+        fn record(&self, sample: &Sample) {
+            if std::thread::current().id() == self.thread {
+                self.samples.lock().unwrap().push(sample.clone());
+            }
+        }
+
+        fn limit_hit(&self, format: LlsdEncoding, operation: Operation, limit: Limit) {
+            if std::thread::current().id() == self.thread {
+                self.limits.lock().unwrap().push((format, operation, limit));
+            }
+        }
+    }
+
+    #[test]
+    fn parsers_and_writers_report() {
+        let recorder = Arc::new(Recorder {
+            thread: std::thread::current().id(),
+            samples: Mutex::default(),
+            limits: Mutex::default(),
+        });
+        set_metrics(recorder.clone());
+
+        let llsd = Llsd::Array(vec![Llsd::Integer(1), Llsd::String("two".into())]);
+        let binary = crate::binary::to_vec(&llsd).unwrap();
+        crate::binary::from_slice(&binary).unwrap();
+        let text = crate::notation::to_string(&llsd, &FormatterContext::new()).unwrap();
+        crate::notation::from_str(&text, 8).unwrap();
+        let xml = crate::xml::to_string(&llsd).unwrap();
+        crate::xml::from_str(&xml).unwrap();
+        assert!(crate::notation::from_str("[i1]", 0).is_err());
+        assert!(crate::binary::from_slice(b"[\0\0\0\x01[\0\0\0\x01i\0\0\0\x01]]").is_ok());
+        assert!(crate::binary::from_slice_with_depth(b"[\0\0\0\x01[\0\0\0\x00]]", 1).is_err());
+        clear_metrics();
+        crate::binary::from_slice(&binary).unwrap();
+
+        let samples = recorder.samples.lock().unwrap();
+        let summary: Vec<_> = samples
+            .iter()
+            .map(|s| (s.format, s.operation, s.values, s.success))
+            .collect();
+        use LlsdEncoding::*;
+        use Operation::*;
+        assert_eq!(
+            summary,
+            [
+                (Binary, Write, 3, true),
+                (Binary, Parse, 3, true),
+                (Notation, Write, 3, true),
+                (Notation, Parse, 3, true),
+                (Xml, Write, 3, true),
+                (Xml, Parse, 3, true),
+                (Notation, Parse, 0, false),
+                (Binary, Parse, 3, true),
+                (Binary, Parse, 0, false),
+            ]
+        );
+        assert_eq!(samples[0].bytes, binary.len());
+        assert_eq!(samples[1].bytes, binary.len());
+        assert_eq!(samples[2].bytes, text.len());
+        assert_eq!(samples[5].bytes, xml.len());
+        assert_eq!(
+            *recorder.limits.lock().unwrap(),
+            [
+                (Notation, Parse, Limit::Depth),
+                (Binary, Parse, Limit::Depth)
+            ]
+        );
+    }
+}
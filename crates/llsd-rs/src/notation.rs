@@ -1,22 +1,79 @@
 use std::{
-    collections::HashMap,
     io::{self, BufRead, BufReader, Read, Write},
     vec,
 };
 
-use chrono::DateTime;
+use crate::Uuid;
 use thiserror::Error;
-use uuid::Uuid;
 
-use crate::{Llsd, Uri};
+use crate::{
+    Llsd, LlsdMap, PathSegment, UndefinedEntries, Uri, UriPolicy,
+    autodetect::LlsdEncoding,
+    compare::{Token, TokenSource},
+    date, escape,
+    metrics::{self, Limit, Operation},
+    order::{Entries, KeyOrder, OrderedLlsd},
+    parse::{ParseFailure, ParseOptions, ParseWarningKind, ParseWarnings, Warn},
+    scalar::LlsdType,
+    spanned::{Span, SpanTree, Spanned},
+};
+
+const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// How [`FormatterContext`] writes booleans. The parser reads all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BooleanLexeme {
+    /// `true` / `false`
+    #[default]
+    Words,
+    /// `1` / `0`
+    Digits,
+    /// `t` / `f`
+    Letters,
+    /// `T` / `F`
+    UpperLetters,
+}
+
+impl BooleanLexeme {
+    fn as_bytes(self, value: bool) -> &'static [u8] {
+        match (self, value) {
+            (BooleanLexeme::Words, true) => b"true",
+            (BooleanLexeme::Words, false) => b"false",
+            (BooleanLexeme::Digits, true) => b"1",
+            (BooleanLexeme::Digits, false) => b"0",
+            (BooleanLexeme::Letters, true) => b"t",
+            (BooleanLexeme::Letters, false) => b"f",
+            (BooleanLexeme::UpperLetters, true) => b"T",
+            (BooleanLexeme::UpperLetters, false) => b"F",
+        }
+    }
+}
+
+/// How [`FormatterContext`] quotes map keys. The parser reads all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyQuote {
+    /// `'key'`
+    #[default]
+    Single,
+    /// `"key"`
+    Double,
+    /// `s(3)"key"`, the raw bytes with their length.
+    Sized,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct FormatterContext {
     indent: &'static str,
     pretty: bool,
-    boolean: bool,
+    boolean: BooleanLexeme,
+    key_quote: KeyQuote,
+    escape_non_ascii_keys: bool,
     hex: bool,
     level: usize,
+    max_depth: usize,
+    sorted_keys: bool,
+    uris: UriPolicy,
+    undefined: UndefinedEntries,
 }
 
 impl FormatterContext {
@@ -24,9 +81,15 @@ impl FormatterContext {
         Self {
             indent: "  ",
             pretty: false,
-            boolean: false,
+            boolean: BooleanLexeme::Words,
+            key_quote: KeyQuote::Single,
+            escape_non_ascii_keys: true,
             hex: false,
             level: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            sorted_keys: false,
+            uris: UriPolicy::Verbatim,
+            undefined: UndefinedEntries::Keep,
         }
     }
 
@@ -40,28 +103,76 @@ impl FormatterContext {
         self
     }
 
-    pub fn with_boolean(mut self, boolean: bool) -> Self {
+    /// Write booleans as `1`/`0` instead of `true`/`false`.
+    pub fn with_boolean(self, boolean: bool) -> Self {
+        self.with_boolean_lexeme(if boolean {
+            BooleanLexeme::Digits
+        } else {
+            BooleanLexeme::Words
+        })
+    }
+
+    pub fn with_boolean_lexeme(mut self, boolean: BooleanLexeme) -> Self {
         self.boolean = boolean;
         self
     }
 
+    pub fn with_key_quote(mut self, key_quote: KeyQuote) -> Self {
+        self.key_quote = key_quote;
+        self
+    }
+
+    /// Write bytes outside ASCII in quoted keys as `\xNN` (the default, as
+    /// the viewer does) rather than as UTF-8.
+    pub fn with_escape_non_ascii_keys(mut self, escape: bool) -> Self {
+        self.escape_non_ascii_keys = escape;
+        self
+    }
+
     pub fn with_hex(mut self, hex: bool) -> Self {
         self.hex = hex;
         self
     }
 
-    fn indent(&self) -> (String, &str) {
+    /// Fail instead of writing values nested deeper than `max_depth` levels.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Write map entries sorted by key instead of in the map's iteration
+    /// order (or the recorded order, for the `*_ordered` writers).
+    pub fn with_sorted_keys(mut self, sorted_keys: bool) -> Self {
+        self.sorted_keys = sorted_keys;
+        self
+    }
+
+    /// How to write URIs that didn't parse as URLs.
+    pub fn with_uri_policy(mut self, uris: UriPolicy) -> Self {
+        self.uris = uris;
+        self
+    }
+
+    /// Keep (the default) or leave out map entries whose value is undefined.
+    pub fn with_undefined_entries(mut self, undefined: UndefinedEntries) -> Self {
+        self.undefined = undefined;
+        self
+    }
+
+    fn write_indent<W: Write>(&self, w: &mut W, level: usize) -> Result<(), io::Error> {
         if self.pretty {
-            (self.indent.repeat(self.level), "\n")
-        } else {
-            (String::new(), "")
+            for _ in 0..level {
+                w.write_all(self.indent.as_bytes())?;
+            }
         }
+        Ok(())
     }
 
-    fn increment(&self) -> Self {
-        let mut context = *self;
-        context.level += 1;
-        context
+    fn write_newline<W: Write>(&self, w: &mut W) -> Result<(), io::Error> {
+        if self.pretty {
+            w.write_all(b"\n")?;
+        }
+        Ok(())
     }
 }
 
@@ -71,369 +182,258 @@ impl Default for FormatterContext {
     }
 }
 
-const STRING_CHARACTERS: [&[u8]; 256] = [
-    b"\\x00", // 0
-    b"\\x01", // 1
-    b"\\x02", // 2
-    b"\\x03", // 3
-    b"\\x04", // 4
-    b"\\x05", // 5
-    b"\\x06", // 6
-    b"\\a",   // 7
-    b"\\b",   // 8
-    b"\\t",   // 9
-    b"\\n",   // 10
-    b"\\v",   // 11
-    b"\\f",   // 12
-    b"\\r",   // 13
-    b"\\x0e", // 14
-    b"\\x0f", // 15
-    b"\\x10", // 16
-    b"\\x11", // 17
-    b"\\x12", // 18
-    b"\\x13", // 19
-    b"\\x14", // 20
-    b"\\x15", // 21
-    b"\\x16", // 22
-    b"\\x17", // 23
-    b"\\x18", // 24
-    b"\\x19", // 25
-    b"\\x1a", // 26
-    b"\\x1b", // 27
-    b"\\x1c", // 28
-    b"\\x1d", // 29
-    b"\\x1e", // 30
-    b"\\x1f", // 31
-    b" ",     // 32
-    b"!",     // 33
-    b"\"",    // 34
-    b"#",     // 35
-    b"$",     // 36
-    b"%",     // 37
-    b"&",     // 38
-    b"\\'",   // 39
-    b"(",     // 40
-    b")",     // 41
-    b"*",     // 42
-    b"+",     // 43
-    b",",     // 44
-    b"-",     // 45
-    b".",     // 46
-    b"/",     // 47
-    b"0",     // 48
-    b"1",     // 49
-    b"2",     // 50
-    b"3",     // 51
-    b"4",     // 52
-    b"5",     // 53
-    b"6",     // 54
-    b"7",     // 55
-    b"8",     // 56
-    b"9",     // 57
-    b":",     // 58
-    b";",     // 59
-    b"<",     // 60
-    b"=",     // 61
-    b">",     // 62
-    b"?",     // 63
-    b"@",     // 64
-    b"A",     // 65
-    b"B",     // 66
-    b"C",     // 67
-    b"D",     // 68
-    b"E",     // 69
-    b"F",     // 70
-    b"G",     // 71
-    b"H",     // 72
-    b"I",     // 73
-    b"J",     // 74
-    b"K",     // 75
-    b"L",     // 76
-    b"M",     // 77
-    b"N",     // 78
-    b"O",     // 79
-    b"P",     // 80
-    b"Q",     // 81
-    b"R",     // 82
-    b"S",     // 83
-    b"T",     // 84
-    b"U",     // 85
-    b"V",     // 86
-    b"W",     // 87
-    b"X",     // 88
-    b"Y",     // 89
-    b"Z",     // 90
-    b"[",     // 91
-    b"\\\\",  // 92
-    b"]",     // 93
-    b"^",     // 94
-    b"_",     // 95
-    b"`",     // 96
-    b"a",     // 97
-    b"b",     // 98
-    b"c",     // 99
-    b"d",     // 100
-    b"e",     // 101
-    b"f",     // 102
-    b"g",     // 103
-    b"h",     // 104
-    b"i",     // 105
-    b"j",     // 106
-    b"k",     // 107
-    b"l",     // 108
-    b"m",     // 109
-    b"n",     // 110
-    b"o",     // 111
-    b"p",     // 112
-    b"q",     // 113
-    b"r",     // 114
-    b"s",     // 115
-    b"t",     // 116
-    b"u",     // 117
-    b"v",     // 118
-    b"w",     // 119
-    b"x",     // 120
-    b"y",     // 121
-    b"z",     // 122
-    b"{",     // 123
-    b"|",     // 124
-    b"}",     // 125
-    b"~",     // 126
-    b"\\x7f", // 127
-    b"\\x80", // 128
-    b"\\x81", // 129
-    b"\\x82", // 130
-    b"\\x83", // 131
-    b"\\x84", // 132
-    b"\\x85", // 133
-    b"\\x86", // 134
-    b"\\x87", // 135
-    b"\\x88", // 136
-    b"\\x89", // 137
-    b"\\x8a", // 138
-    b"\\x8b", // 139
-    b"\\x8c", // 140
-    b"\\x8d", // 141
-    b"\\x8e", // 142
-    b"\\x8f", // 143
-    b"\\x90", // 144
-    b"\\x91", // 145
-    b"\\x92", // 146
-    b"\\x93", // 147
-    b"\\x94", // 148
-    b"\\x95", // 149
-    b"\\x96", // 150
-    b"\\x97", // 151
-    b"\\x98", // 152
-    b"\\x99", // 153
-    b"\\x9a", // 154
-    b"\\x9b", // 155
-    b"\\x9c", // 156
-    b"\\x9d", // 157
-    b"\\x9e", // 158
-    b"\\x9f", // 159
-    b"\\xa0", // 160
-    b"\\xa1", // 161
-    b"\\xa2", // 162
-    b"\\xa3", // 163
-    b"\\xa4", // 164
-    b"\\xa5", // 165
-    b"\\xa6", // 166
-    b"\\xa7", // 167
-    b"\\xa8", // 168
-    b"\\xa9", // 169
-    b"\\xaa", // 170
-    b"\\xab", // 171
-    b"\\xac", // 172
-    b"\\xad", // 173
-    b"\\xae", // 174
-    b"\\xaf", // 175
-    b"\\xb0", // 176
-    b"\\xb1", // 177
-    b"\\xb2", // 178
-    b"\\xb3", // 179
-    b"\\xb4", // 180
-    b"\\xb5", // 181
-    b"\\xb6", // 182
-    b"\\xb7", // 183
-    b"\\xb8", // 184
-    b"\\xb9", // 185
-    b"\\xba", // 186
-    b"\\xbb", // 187
-    b"\\xbc", // 188
-    b"\\xbd", // 189
-    b"\\xbe", // 190
-    b"\\xbf", // 191
-    b"\\xc0", // 192
-    b"\\xc1", // 193
-    b"\\xc2", // 194
-    b"\\xc3", // 195
-    b"\\xc4", // 196
-    b"\\xc5", // 197
-    b"\\xc6", // 198
-    b"\\xc7", // 199
-    b"\\xc8", // 200
-    b"\\xc9", // 201
-    b"\\xca", // 202
-    b"\\xcb", // 203
-    b"\\xcc", // 204
-    b"\\xcd", // 205
-    b"\\xce", // 206
-    b"\\xcf", // 207
-    b"\\xd0", // 208
-    b"\\xd1", // 209
-    b"\\xd2", // 210
-    b"\\xd3", // 211
-    b"\\xd4", // 212
-    b"\\xd5", // 213
-    b"\\xd6", // 214
-    b"\\xd7", // 215
-    b"\\xd8", // 216
-    b"\\xd9", // 217
-    b"\\xda", // 218
-    b"\\xdb", // 219
-    b"\\xdc", // 220
-    b"\\xdd", // 221
-    b"\\xde", // 222
-    b"\\xdf", // 223
-    b"\\xe0", // 224
-    b"\\xe1", // 225
-    b"\\xe2", // 226
-    b"\\xe3", // 227
-    b"\\xe4", // 228
-    b"\\xe5", // 229
-    b"\\xe6", // 230
-    b"\\xe7", // 231
-    b"\\xe8", // 232
-    b"\\xe9", // 233
-    b"\\xea", // 234
-    b"\\xeb", // 235
-    b"\\xec", // 236
-    b"\\xed", // 237
-    b"\\xee", // 238
-    b"\\xef", // 239
-    b"\\xf0", // 240
-    b"\\xf1", // 241
-    b"\\xf2", // 242
-    b"\\xf3", // 243
-    b"\\xf4", // 244
-    b"\\xf5", // 245
-    b"\\xf6", // 246
-    b"\\xf7", // 247
-    b"\\xf8", // 248
-    b"\\xf9", // 249
-    b"\\xfa", // 250
-    b"\\xfb", // 251
-    b"\\xfc", // 252
-    b"\\xfd", // 253
-    b"\\xfe", // 254
-    b"\\xff", // 255
-];
-
-fn write_string<W: Write>(s: &str, w: &mut W) -> Result<(), io::Error> {
-    for c in s.bytes() {
-        w.write_all(STRING_CHARACTERS[c as usize])?;
+fn write_key<W: Write>(key: &str, w: &mut W, context: &FormatterContext) -> Result<(), io::Error> {
+    let quote = match context.key_quote {
+        KeyQuote::Single => b'\'',
+        KeyQuote::Double => b'"',
+        KeyQuote::Sized => {
+            write!(w, "s({})\"", key.len())?;
+            w.write_all(key.as_bytes())?;
+            return w.write_all(b"\"");
+        }
+    };
+    w.write_all(&[quote])?;
+    for c in key.bytes() {
+        match c {
+            b'\'' | b'"' if c == quote => w.write_all(&[b'\\', c])?,
+            b'\'' | b'"' => w.write_all(&[c])?,
+            0x80.. if !context.escape_non_ascii_keys => w.write_all(&[c])?,
+            _ => w.write_all(escape::notation_byte(c))?,
+        }
     }
-    Ok(())
+    w.write_all(&[quote])
 }
 
-fn write_inner<W: Write>(
+fn write_scalar<W: Write>(
     llsd: &Llsd,
     w: &mut W,
     context: &FormatterContext,
+    hex: &mut Vec<u8>,
 ) -> Result<(), io::Error> {
-    let (indent, newline) = context.indent();
     match llsd {
-        Llsd::Map(v) => {
-            w.write_all(indent.as_bytes())?;
-            w.write_all(b"{")?;
-            let context = context.increment();
-            let inner_indent = context.indent().0;
-            let mut comma = false;
-            for (k, e) in v {
-                if comma {
-                    w.write_all(b",")?;
-                }
-                comma = true;
-
-                w.write_all(newline.as_bytes())?;
-                w.write_all(inner_indent.as_bytes())?;
-                w.write_all(b"'")?;
-                write_string(k, w)?;
-                w.write_all(b"':")?;
-
-                write_inner(e, w, &context)?;
-            }
-            w.write_all(newline.as_bytes())?;
-            w.write_all(indent.as_bytes())?;
-            w.write_all(b"}")?;
-        }
-        Llsd::Array(v) => {
-            w.write_all(newline.as_bytes())?;
-            w.write_all(indent.as_bytes())?;
-            w.write_all(b"[")?;
-            let context = context.increment();
-            let mut comma = false;
-            for e in v {
-                if comma {
-                    w.write_all(b",")?;
-                }
-                comma = true;
-
-                write_inner(e, w, &context)?;
-            }
-            w.write_all(b"]")?;
-        }
         Llsd::Undefined => w.write_all(b"!")?,
-        Llsd::Boolean(v) => {
-            if context.boolean {
-                w.write_all(if *v { b"1" } else { b"0" })?;
-            } else {
-                w.write_all(if *v { b"true" } else { b"false" })?;
-            }
+        Llsd::Boolean(v) => w.write_all(context.boolean.as_bytes(*v))?,
+        Llsd::Integer(v) => {
+            w.write_all(b"i")?;
+            w.write_all(itoa::Buffer::new().format(*v).as_bytes())?;
         }
-        Llsd::Integer(v) => w.write_all(format!("i{}", v).as_bytes())?,
-        Llsd::Real(v) => w.write_all(format!("r{}", v).as_bytes())?,
-        Llsd::Uuid(v) => w.write_all(format!("u{}", v).as_bytes())?,
+        Llsd::Real(v) => {
+            w.write_all(b"r")?;
+            w.write_all(ryu::Buffer::new().format(*v).as_bytes())?;
+        }
+        Llsd::Uuid(v) => write!(w, "u{}", v)?,
         Llsd::String(v) => {
             w.write_all(b"'")?;
-            write_string(v, w)?;
+            escape::write_notation(v.as_bytes(), w)?;
             w.write_all(b"'")?;
         }
-        Llsd::Date(v) => w.write_all(format!("d\"{}\"", v.to_rfc3339()).as_bytes())?,
+        Llsd::Date(v) => write!(w, "d\"{}\"", v.to_rfc3339())?,
         Llsd::Uri(v) => {
+            let text = v.text_for(context.uris).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid URI {:?}", v.as_str()),
+                )
+            })?;
             w.write_all(b"l\"")?;
-            write_string(v.as_str(), w)?;
+            escape::write_notation(text.as_bytes(), w)?;
             w.write_all(b"\"")?;
         }
         Llsd::Binary(v) => {
             if context.hex {
-                w.write_all(b"b16\"")?;
-                for byte in v {
-                    write!(w, "{:02X}", byte)?;
-                }
+                hex.clear();
+                hex.extend_from_slice(b"b16\"");
+                crate::codec::base16_encode_into(v, hex);
+                w.write_all(hex)?;
             } else {
-                w.write_all(format!("b({})\"", v.len()).as_bytes())?;
+                write!(w, "b({})\"", v.len())?;
                 w.write_all(v.as_slice())?;
             }
             w.write_all(b"\"")?;
         }
+        Llsd::Array(_) | Llsd::Map(_) => unreachable!("containers are written by write_inner"),
     }
     Ok(())
 }
 
+/// Open containers while writing, innermost last.
+enum Frame<'a> {
+    Array {
+        items: std::slice::Iter<'a, Llsd>,
+        orders: std::slice::Iter<'a, KeyOrder>,
+        first: bool,
+    },
+    Map {
+        entries: Entries<'a>,
+        first: bool,
+    },
+}
+
+fn write_inner<'a, W: Write>(
+    llsd: &'a Llsd,
+    w: &mut W,
+    context: &FormatterContext,
+    stack: &mut Vec<Frame<'a>>,
+    hex: &mut Vec<u8>,
+    order: Option<&'a KeyOrder>,
+) -> Result<(), io::Error> {
+    stack.clear();
+    let mut next = Some((llsd, order));
+    loop {
+        let level = context.level + stack.len();
+        if let Some((value, order)) = next.take() {
+            if stack.len() >= context.max_depth {
+                metrics::limit_hit(LlsdEncoding::Notation, Operation::Write, Limit::Depth);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "LLSD notation maximum depth exceeded",
+                ));
+            }
+            match value {
+                Llsd::Map(v) => {
+                    context.write_indent(w, level)?;
+                    w.write_all(b"{")?;
+                    stack.push(Frame::Map {
+                        entries: crate::order::write_entries(
+                            v,
+                            order,
+                            context.sorted_keys,
+                            context.undefined,
+                        ),
+                        first: true,
+                    });
+                }
+                Llsd::Array(v) => {
+                    context.write_newline(w)?;
+                    context.write_indent(w, level)?;
+                    w.write_all(b"[")?;
+                    stack.push(Frame::Array {
+                        items: v.iter(),
+                        orders: crate::order::items(order),
+                        first: true,
+                    });
+                }
+                scalar => write_scalar(scalar, w, context, hex)?,
+            }
+            continue;
+        }
+        match stack.last_mut() {
+            None => return Ok(()),
+            Some(Frame::Map { entries, first }) => match entries.next() {
+                Some((k, e, order)) => {
+                    if !std::mem::take(first) {
+                        w.write_all(b",")?;
+                    }
+                    context.write_newline(w)?;
+                    context.write_indent(w, level)?;
+                    write_key(k, w, context)?;
+                    w.write_all(b":")?;
+                    next = Some((e, order));
+                }
+                None => {
+                    context.write_newline(w)?;
+                    context.write_indent(w, level - 1)?;
+                    w.write_all(b"}")?;
+                    stack.pop();
+                }
+            },
+            Some(Frame::Array {
+                items,
+                orders,
+                first,
+            }) => match items.next() {
+                Some(e) => {
+                    if !std::mem::take(first) {
+                        w.write_all(b",")?;
+                    }
+                    next = Some((e, orders.next()));
+                }
+                None => {
+                    w.write_all(b"]")?;
+                    stack.pop();
+                }
+            },
+        }
+    }
+}
+
 pub fn write<W: Write>(
     llsd: &Llsd,
     w: &mut W,
     context: &FormatterContext,
 ) -> Result<(), io::Error> {
-    write_inner(llsd, w, context)
+    let mut out = crate::output::Output::new(w);
+    write_inner(
+        llsd,
+        &mut out,
+        context,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        None,
+    )?;
+    out.finish()
 }
 
 pub fn to_vec(llsd: &Llsd, context: &FormatterContext) -> Result<Vec<u8>, io::Error> {
-    let mut buffer = Vec::new();
-    write(llsd, &mut buffer, context)?;
-    Ok(buffer)
+    metrics::write(LlsdEncoding::Notation, llsd, || {
+        let mut buffer = Vec::new();
+        write_inner(
+            llsd,
+            &mut buffer,
+            context,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            None,
+        )?;
+        Ok(buffer)
+    })
+}
+
+/// A reusable notation writer.
+///
+/// Holds a [`FormatterContext`] together with the container stack and the
+/// base16 buffer, which are kept between calls instead of being allocated
+/// for every message.
+pub struct NotationEncoder {
+    context: FormatterContext,
+    stack: Vec<Frame<'static>>,
+    hex: Vec<u8>,
+}
+
+impl NotationEncoder {
+    pub fn new(context: FormatterContext) -> Self {
+        Self {
+            context,
+            stack: Vec::new(),
+            hex: Vec::new(),
+        }
+    }
+
+    pub fn context(&self) -> &FormatterContext {
+        &self.context
+    }
+
+    /// Append the notation encoding of `llsd` to `out`.
+    ///
+    /// On error `out` may hold a partial encoding past its original length.
+    pub fn encode_into(&mut self, llsd: &Llsd, out: &mut Vec<u8>) -> Result<(), io::Error> {
+        let mut stack = crate::output::recycle(std::mem::take(&mut self.stack));
+        let result = write_inner(llsd, out, &self.context, &mut stack, &mut self.hex, None);
+        self.stack = crate::output::recycle(stack);
+        result
+    }
+
+    pub fn encode(&mut self, llsd: &Llsd) -> Result<Vec<u8>, io::Error> {
+        let mut out = Vec::new();
+        self.encode_into(llsd, &mut out)?;
+        Ok(out)
+    }
+}
+
+impl Default for NotationEncoder {
+    fn default() -> Self {
+        Self::new(FormatterContext::new())
+    }
 }
 
 pub fn to_string(llsd: &Llsd, context: &FormatterContext) -> Result<String, io::Error> {
@@ -441,6 +441,292 @@ pub fn to_string(llsd: &Llsd, context: &FormatterContext) -> Result<String, io::
     String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
+/// Like [`write`], listing map keys in the order recorded in `doc`.
+pub fn write_ordered<W: Write>(
+    doc: &OrderedLlsd,
+    w: &mut W,
+    context: &FormatterContext,
+) -> Result<(), io::Error> {
+    let mut out = crate::output::Output::new(w);
+    let order = Some(&doc.order);
+    write_inner(
+        &doc.llsd,
+        &mut out,
+        context,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        order,
+    )?;
+    out.finish()
+}
+
+pub fn to_vec_ordered(doc: &OrderedLlsd, context: &FormatterContext) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+    let order = Some(&doc.order);
+    write_inner(
+        &doc.llsd,
+        &mut buffer,
+        context,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        order,
+    )?;
+    Ok(buffer)
+}
+
+pub fn to_string_ordered(
+    doc: &OrderedLlsd,
+    context: &FormatterContext,
+) -> Result<String, io::Error> {
+    let buffer = to_vec_ordered(doc, context)?;
+    String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write `llsd` like [`to_vec`], in at most `max_bytes`, for logging
+/// documents that may be huge. Values are never cut; once the next array
+/// element or map entry doesn't fit, the rest of each open container is
+/// left out and a `'...(+N more)'` element (a key with an undefined value, in
+/// maps) says how many, when there is room for it, so the output still
+/// parses. Also returns whether anything was left out. When not even an
+/// empty container fits, the output is `!`, which is the only way it can
+/// exceed `max_bytes` (of 0).
+pub fn to_vec_truncated(
+    llsd: &Llsd,
+    context: &FormatterContext,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, bool), io::Error> {
+    let mut writer = Truncating {
+        context,
+        limit: max_bytes,
+        out: Vec::new(),
+        stack: Vec::new(),
+        hex: Vec::new(),
+        truncated: false,
+    };
+    if !writer.value(llsd, context.level, 0, 0)? {
+        writer.out = b"!".to_vec();
+        writer.truncated = true;
+    }
+    Ok((writer.out, writer.truncated))
+}
+
+pub fn to_string_truncated(
+    llsd: &Llsd,
+    context: &FormatterContext,
+    max_bytes: usize,
+) -> Result<(String, bool), io::Error> {
+    let (buffer, truncated) = to_vec_truncated(llsd, context, max_bytes)?;
+    String::from_utf8(buffer)
+        .map(|text| (text, truncated))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// A `Vec` writer that fails rather than grow past `cap`.
+struct Capped<'v> {
+    out: &'v mut Vec<u8>,
+    cap: usize,
+    full: bool,
+}
+
+impl Write for Capped<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.out.len() + buf.len() > self.cap {
+            self.full = true;
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "byte budget reached",
+            ));
+        }
+        self.out.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The state of [`to_vec_truncated`]. Each method keeps `reserve` bytes of
+/// the limit free for what the enclosing containers still have to write.
+struct Truncating<'a> {
+    context: &'a FormatterContext,
+    limit: usize,
+    out: Vec<u8>,
+    stack: Vec<Frame<'a>>,
+    hex: Vec<u8>,
+    truncated: bool,
+}
+
+impl<'a> Truncating<'a> {
+    /// Write `value` whole, cutting containers short when it doesn't fit;
+    /// false, writing nothing, when not even that fits.
+    fn value(
+        &mut self,
+        value: &'a Llsd,
+        level: usize,
+        depth: usize,
+        reserve: usize,
+    ) -> Result<bool, io::Error> {
+        Ok(self.whole(value, level, depth, reserve)? || self.cut(value, level, depth, reserve)?)
+    }
+
+    /// Write as much of a container as fits; false, writing nothing, for
+    /// scalars and when not even an empty container fits.
+    fn cut(
+        &mut self,
+        value: &'a Llsd,
+        level: usize,
+        depth: usize,
+        reserve: usize,
+    ) -> Result<bool, io::Error> {
+        match value {
+            Llsd::Array(items) => {
+                let items = items.iter().map(|item| (None, item)).collect();
+                self.container(items, false, level, depth, reserve)
+            }
+            Llsd::Map(map) => {
+                let context = self.context;
+                let entries =
+                    crate::order::write_entries(map, None, context.sorted_keys, context.undefined)
+                        .map(|(key, value, _)| (Some(key), value))
+                        .collect();
+                self.container(entries, true, level, depth, reserve)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Write `value` whole if it fits, or nothing.
+    fn whole(
+        &mut self,
+        value: &'a Llsd,
+        level: usize,
+        depth: usize,
+        reserve: usize,
+    ) -> Result<bool, io::Error> {
+        let start = self.out.len();
+        let context = FormatterContext {
+            level,
+            max_depth: self.context.max_depth.saturating_sub(depth),
+            ..*self.context
+        };
+        let mut capped = Capped {
+            out: &mut self.out,
+            cap: self.limit.saturating_sub(reserve),
+            full: false,
+        };
+        match write_inner(
+            value,
+            &mut capped,
+            &context,
+            &mut self.stack,
+            &mut self.hex,
+            None,
+        ) {
+            Ok(()) => Ok(true),
+            Err(_) if capped.full => {
+                self.out.truncate(start);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write as many of a container's `entries` as fit, then the marker for
+    /// the rest.
+    fn container(
+        &mut self,
+        entries: Vec<(Option<&'a str>, &'a Llsd)>,
+        is_map: bool,
+        level: usize,
+        depth: usize,
+        reserve: usize,
+    ) -> Result<bool, io::Error> {
+        let context = self.context;
+        let (mut open, mut close) = (Vec::new(), Vec::new());
+        if is_map {
+            context.write_indent(&mut open, level)?;
+            open.push(b'{');
+            context.write_newline(&mut close)?;
+            context.write_indent(&mut close, level)?;
+            close.push(b'}');
+        } else {
+            context.write_newline(&mut open)?;
+            context.write_indent(&mut open, level)?;
+            open.push(b'[');
+            close.push(b']');
+        }
+        if self.out.len() + open.len() + close.len() + reserve > self.limit {
+            return Ok(false);
+        }
+        self.out.extend_from_slice(&open);
+        let first = self.out.len();
+        let reserve = reserve + close.len();
+        let count = entries.len();
+        for (i, (key, value)) in entries.into_iter().enumerate() {
+            let mark = self.out.len();
+            let rest = count - i - 1;
+            let after = match rest {
+                0 => reserve,
+                rest => reserve + self.marker(is_map, level + 1, rest, true)?.len(),
+            };
+            if i > 0 {
+                self.out.push(b',');
+            }
+            if let Some(key) = key {
+                context.write_newline(&mut self.out)?;
+                context.write_indent(&mut self.out, level + 1)?;
+                write_key(key, &mut self.out, context)?;
+                self.out.push(b':');
+            }
+            if self.whole(value, level + 1, depth + 1, after)? {
+                continue;
+            }
+            let omitted = if self.cut(value, level + 1, depth + 1, after)? {
+                rest
+            } else {
+                self.out.truncate(mark);
+                rest + 1
+            };
+            self.truncated = true;
+            if omitted > 0 {
+                let marker = self.marker(is_map, level + 1, omitted, self.out.len() > first)?;
+                if self.out.len() + marker.len() + reserve <= self.limit {
+                    self.out.extend_from_slice(&marker);
+                }
+            }
+            break;
+        }
+        self.out.extend_from_slice(&close);
+        Ok(true)
+    }
+
+    /// The element standing for `omitted` left-out entries.
+    fn marker(
+        &self,
+        is_map: bool,
+        level: usize,
+        omitted: usize,
+        comma: bool,
+    ) -> Result<Vec<u8>, io::Error> {
+        let context = self.context;
+        let text = format!("...(+{omitted} more)");
+        let mut marker = Vec::new();
+        if comma {
+            marker.push(b',');
+        }
+        if is_map {
+            context.write_newline(&mut marker)?;
+            context.write_indent(&mut marker, level)?;
+            write_key(&text, &mut marker, context)?;
+            marker.extend_from_slice(b":!");
+        } else {
+            write_scalar(&Llsd::String(text), &mut marker, context, &mut Vec::new())?;
+        }
+        Ok(marker)
+    }
+}
+
 pub fn from_reader<R: Read>(reader: R, max_depth: usize) -> ParseResult<Llsd> {
     let mut stream = Stream::new(reader);
     let Some(c) = stream.skip_ws()? else {
@@ -450,19 +736,123 @@ pub fn from_reader<R: Read>(reader: R, max_depth: usize) -> ParseResult<Llsd> {
 }
 
 pub fn from_str(s: &str, max_depth: usize) -> ParseResult<Llsd> {
-    let reader = s.as_bytes();
-    from_reader(reader, max_depth)
+    from_bytes(s.as_bytes(), max_depth)
 }
 
 pub fn from_bytes(bytes: &[u8], max_depth: usize) -> ParseResult<Llsd> {
-    let reader = bytes;
-    from_reader(reader, max_depth)
+    metrics::parse(LlsdEncoding::Notation, bytes.len(), || {
+        from_reader(bytes, max_depth)
+    })
+}
+
+/// Like [`from_bytes`], reporting failures as a [`ParseFailure`] with the
+/// offset, container path and (per `options`) surrounding bytes.
+pub fn from_bytes_with_options(
+    bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<Llsd, anyhow::Error> {
+    from_bytes_reporting(bytes, options, None)
+}
+
+/// Like [`from_bytes_with_options`], reporting duplicate map keys and
+/// clamped integers to `warnings`.
+pub fn from_bytes_with_warnings(
+    bytes: &[u8],
+    options: &ParseOptions,
+    warnings: &mut ParseWarnings<'_>,
+) -> Result<Llsd, anyhow::Error> {
+    from_bytes_reporting(bytes, options, Some(warnings.begin()))
+}
+
+fn from_bytes_reporting(
+    bytes: &[u8],
+    options: &ParseOptions,
+    warnings: Option<&mut dyn Warn>,
+) -> Result<Llsd, anyhow::Error> {
+    let mut stream = Stream::new(bytes);
+    stream.warnings = warnings.map(|w| w as &mut dyn Warn);
+    stream.clamp_integers = options.clamp_integers();
+    stream.lenient = options.lenient();
+    let result = match stream.skip_ws() {
+        Ok(Some(c)) => from_reader_char(&mut stream, c, options.max_depth()),
+        Ok(None) => Ok(Llsd::Undefined),
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| {
+        ParseFailure::new(
+            LlsdEncoding::Notation,
+            bytes,
+            Some(e.pos.offset),
+            e.kind,
+            options,
+        )
+        .with_path(e.path)
+        .into()
+    })
+}
+
+/// Like [`from_reader`], also recording the order of every map's keys for
+/// [`write_ordered`].
+pub fn from_reader_ordered<R: Read>(reader: R, max_depth: usize) -> ParseResult<OrderedLlsd> {
+    let mut stream = Stream::new(reader);
+    let mut order = KeyOrder::new();
+    let Some(c) = stream.skip_ws()? else {
+        return Ok(OrderedLlsd::default());
+    };
+    let llsd = from_reader_char_inner(&mut stream, c, max_depth, Some(&mut order), None)?;
+    Ok(OrderedLlsd::new(llsd, order))
+}
+
+/// Like [`from_reader`], also recording where each value was read from.
+pub fn from_reader_spanned<R: Read>(reader: R, max_depth: usize) -> ParseResult<Spanned<Llsd>> {
+    let mut stream = Stream::new(reader);
+    let mut spans = SpanTree::default();
+    let Some(c) = stream.skip_ws()? else {
+        return Ok(Spanned::default());
+    };
+    let llsd = from_reader_char_inner(&mut stream, c, max_depth, None, Some(&mut spans))?;
+    Ok(Spanned::new(llsd, spans))
+}
+
+pub fn from_str_spanned(s: &str, max_depth: usize) -> ParseResult<Spanned<Llsd>> {
+    from_reader_spanned(s.as_bytes(), max_depth)
+}
+
+pub fn from_bytes_spanned(bytes: &[u8], max_depth: usize) -> ParseResult<Spanned<Llsd>> {
+    from_reader_spanned(bytes, max_depth)
+}
+
+pub fn from_str_ordered(s: &str, max_depth: usize) -> ParseResult<OrderedLlsd> {
+    from_reader_ordered(s.as_bytes(), max_depth)
+}
+
+pub fn from_bytes_ordered(bytes: &[u8], max_depth: usize) -> ParseResult<OrderedLlsd> {
+    from_reader_ordered(bytes, max_depth)
+}
+
+/// Like [`from_bytes`], decoding the elements of a top-level array, or of
+/// arrays directly under a top-level map, in parallel.
+#[cfg(feature = "rayon")]
+pub fn from_bytes_par(bytes: &[u8], max_depth: usize) -> Result<Llsd, anyhow::Error> {
+    let lazy = crate::LazyLlsd::from_notation(bytes)?;
+    crate::lazy::decode_par(&lazy, max_depth)
+}
+
+/// Like [`from_bytes`], allocating arrays, maps and escaped strings in
+/// `bump` and borrowing everything else from `bytes`.
+#[cfg(feature = "arena")]
+pub fn from_bytes_in<'a>(
+    bytes: &'a [u8],
+    max_depth: usize,
+    bump: &'a bumpalo::Bump,
+) -> Result<crate::arena::LlsdRef<'a>, anyhow::Error> {
+    crate::arena::from_notation(bytes, bump, max_depth)
 }
 
 macro_rules! bail {
     ($stream:expr, $kind:expr $(,)?) => {{
         let pos = $stream.pos();
-        return Err(ParseError { kind: $kind, pos });
+        return Err(ParseError::new($kind, pos));
     }};
 }
 
@@ -475,48 +865,326 @@ macro_rules! map {
     }};
 }
 
+/// Read a map key opened by `quote` and the `:` after it, returning the key
+/// and the first byte of its value.
+fn read_map_key<R: Read>(stream: &mut Stream<'_, R>, quote: u8) -> ParseResult<(String, u8)> {
+    let key = if quote == b's' {
+        let buf = stream.read_sized()?;
+        stream.parse_utf8(buf)?
+    } else {
+        stream.unescape(quote)?
+    };
+    match stream.skip_ws()? {
+        Some(b':') => {}
+        Some(other) => {
+            bail!(
+                stream,
+                ParseErrorKind::Expected(format!("':' or '}}' after key, found: 0x{:02x}", other))
+            );
+        }
+        None => bail!(stream, ParseErrorKind::Eof),
+    }
+    match stream.skip_ws()? {
+        Some(c) => Ok((key, c)),
+        None => bail!(stream, ParseErrorKind::Eof),
+    }
+}
+
+/// Step over the value starting with `first` without building it.
+fn skip_value<R: Read>(stream: &mut Stream<'_, R>, first: u8) -> ParseResult<()> {
+    let mut c = first;
+    let mut depth = 0usize;
+    loop {
+        match c {
+            b'[' | b'{' => depth += 1,
+            b',' | b':' if depth > 0 => {}
+            b']' | b'}' if depth > 0 => depth -= 1,
+            b'!' | b'0' | b'1' => {}
+            b'i' | b'I' => drop(stream.integer_text()?),
+            b'r' | b'R' => drop(stream.real_text()?),
+            b'u' | b'U' => stream.skip_while(|c| c.is_ascii_hexdigit() || c == b'-')?,
+            b't' | b'T' => stream.keyword_tail(b"rue")?,
+            b'f' | b'F' => stream.keyword_tail(b"alse")?,
+            b'\'' | b'"' => stream.skip_quoted(c)?,
+            b's' => stream.skip_sized()?,
+            b'l' | b'L' | b'd' | b'D' => {
+                stream.expect(b"\"")?;
+                stream.skip_quoted(b'"')?;
+            }
+            b'b' | b'B' => match stream.peek()? {
+                Some(b'(') => stream.skip_sized()?,
+                Some(b'1') => {
+                    stream.next()?;
+                    stream.expect(b"6")?;
+                    stream.expect(b"\"")?;
+                    stream.skip_while(|c| c != b'"')?;
+                    stream.expect(b"\"")?;
+                }
+                Some(_) => bail!(
+                    stream,
+                    ParseErrorKind::Expected("Invalid binary format".to_string())
+                ),
+                None => bail!(stream, ParseErrorKind::Eof),
+            },
+            c => bail!(
+                stream,
+                ParseErrorKind::Expected(format!("Invalid character: 0x{:02x}", c))
+            ),
+        }
+        if depth == 0 {
+            return Ok(());
+        }
+        c = match stream.skip_ws()? {
+            Some(c) => c,
+            None => bail!(stream, ParseErrorKind::Eof),
+        };
+    }
+}
+
+/// The type of the value `data` starts with, after optional whitespace and
+/// `<? LLSD/Notation ?>` header; `None` for an unexpected byte or empty
+/// input. The value itself isn't checked.
+pub fn peek_type(data: &[u8]) -> Option<LlsdType> {
+    let data = crate::autodetect::payload_after_header(data, LlsdEncoding::Notation);
+    LlsdType::from_tag(LlsdEncoding::Notation, *data.trim_ascii_start().first()?)
+}
+
+/// Decode only the value at `pointer` (the syntax of [`Llsd::pointer`]),
+/// stepping over everything before it without building it. Reading stops
+/// once that value is decoded. Returns `None` when the path does not exist;
+/// unlike a full decode, a repeated map key resolves to its first occurrence.
+pub fn extract_pointer<R: Read>(
+    reader: R,
+    pointer: &str,
+    max_depth: usize,
+) -> ParseResult<Option<Llsd>> {
+    let Some(tokens) = crate::pointer_tokens(pointer) else {
+        return Ok(None);
+    };
+    let mut stream = Stream::new(reader);
+    let Some(mut c) = stream.skip_ws()? else {
+        return Ok(tokens.is_empty().then_some(Llsd::Undefined));
+    };
+    for token in &tokens {
+        match c {
+            b'{' => loop {
+                match stream.skip_ws()? {
+                    Some(b'}') => return Ok(None),
+                    Some(b',') => continue,
+                    Some(quote @ (b'\'' | b'"' | b's')) => {
+                        let (key, value_first) = read_map_key(&mut stream, quote)?;
+                        if key == *token {
+                            c = value_first;
+                            break;
+                        }
+                        skip_value(&mut stream, value_first)?;
+                    }
+                    Some(other) => bail!(
+                        stream,
+                        ParseErrorKind::Expected(format!(
+                            "Invalid character in map: 0x{:02x}",
+                            other
+                        ))
+                    ),
+                    None => bail!(stream, ParseErrorKind::Eof),
+                }
+            },
+            b'[' => {
+                let Ok(index) = token.parse::<usize>() else {
+                    return Ok(None);
+                };
+                let mut i = 0;
+                loop {
+                    match stream.skip_ws()? {
+                        Some(b']') => return Ok(None),
+                        Some(b',') => continue,
+                        Some(first) if i == index => {
+                            c = first;
+                            break;
+                        }
+                        Some(first) => {
+                            skip_value(&mut stream, first)?;
+                            i += 1;
+                        }
+                        None => bail!(stream, ParseErrorKind::Eof),
+                    }
+                }
+            }
+            _ => return Ok(None),
+        }
+    }
+    from_reader_char(&mut stream, c, max_depth).map(Some)
+}
+
+/// A document as [`Token`]s, for [`crate::compare`].
+pub(crate) struct Tokens<R: Read> {
+    stream: Stream<'static, R>,
+    /// Open containers, `true` for maps.
+    stack: Vec<bool>,
+    /// First byte of a map value, read along with its key.
+    pending: Option<u8>,
+    started: bool,
+}
+
+impl<R: Read> Tokens<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            stream: Stream::new(reader),
+            stack: Vec::new(),
+            pending: None,
+            started: false,
+        }
+    }
+
+    fn next(&mut self) -> ParseResult<Option<Token>> {
+        let c = if let Some(c) = self.pending.take() {
+            c
+        } else if !self.started {
+            self.started = true;
+            match self.stream.skip_ws()? {
+                Some(c) => c,
+                None => return Ok(Some(Token::Scalar(Llsd::Undefined))),
+            }
+        } else {
+            let Some(&map) = self.stack.last() else {
+                return Ok(None);
+            };
+            let stream = &mut self.stream;
+            loop {
+                match stream.skip_ws()? {
+                    Some(b',') => continue,
+                    Some(b']') if !map => {
+                        self.stack.pop();
+                        return Ok(Some(Token::End));
+                    }
+                    Some(b'}') if map => {
+                        self.stack.pop();
+                        return Ok(Some(Token::End));
+                    }
+                    Some(quote @ (b'\'' | b'"' | b's')) if map => {
+                        let (key, first) = read_map_key(stream, quote)?;
+                        self.pending = Some(first);
+                        return Ok(Some(Token::Key(key)));
+                    }
+                    Some(other) if map => bail!(
+                        stream,
+                        ParseErrorKind::Expected(format!(
+                            "Invalid character in map: 0x{:02x}",
+                            other
+                        ))
+                    ),
+                    Some(c) => break c,
+                    None => bail!(stream, ParseErrorKind::Eof),
+                }
+            }
+        };
+        Ok(Some(match c {
+            b'[' => {
+                self.stack.push(false);
+                Token::ArrayStart
+            }
+            b'{' => {
+                self.stack.push(true);
+                Token::MapStart
+            }
+            c => Token::Scalar(read_value(&mut self.stream, c, 1, None, None)?),
+        }))
+    }
+}
+
+impl<R: Read> TokenSource for Tokens<R> {
+    fn next_token(&mut self) -> Result<Option<Token>, anyhow::Error> {
+        Ok(self.next()?)
+    }
+}
+
 fn from_reader_char<R: Read>(
-    stream: &mut Stream<R>,
+    stream: &mut Stream<'_, R>,
+    char: u8,
+    max_depth: usize,
+) -> ParseResult<Llsd> {
+    read_value(stream, char, max_depth, None, None)
+}
+
+/// Parse one value, recording its key order into `order` and where it and
+/// its contents were read from into `spans` when given.
+fn from_reader_char_inner<R: Read>(
+    stream: &mut Stream<'_, R>,
+    char: u8,
+    max_depth: usize,
+    order: Option<&mut KeyOrder>,
+    mut spans: Option<&mut SpanTree>,
+) -> ParseResult<Llsd> {
+    // `char` is already consumed, and is never a newline.
+    let start = stream.pos();
+    let value = read_value(stream, char, max_depth, order, spans.as_deref_mut())?;
+    if let Some(spans) = spans {
+        spans.set_span(Span {
+            start: start.offset - 1,
+            end: stream.pos().offset,
+            line: start.line,
+            column: start.column - 1,
+        });
+    }
+    Ok(value)
+}
+
+fn read_value<R: Read>(
+    stream: &mut Stream<'_, R>,
     char: u8,
     max_depth: usize,
+    mut order: Option<&mut KeyOrder>,
+    mut spans: Option<&mut SpanTree>,
 ) -> ParseResult<Llsd> {
     if max_depth == 0 {
+        metrics::limit_hit(LlsdEncoding::Notation, Operation::Parse, Limit::Depth);
         bail!(stream, ParseErrorKind::MaxDepth);
     }
     match char {
         b'{' => {
-            let mut map = HashMap::new();
+            let mut map = LlsdMap::new();
             loop {
                 match stream.skip_ws()? {
                     Some(b'}') => break,
                     Some(b',') => continue,
                     Some(quote @ (b'\'' | b'"' | b's')) => {
-                        let key = if quote == b's' {
-                            let buf = stream.read_sized()?;
-                            stream.parse_utf8(buf)?
+                        let (key, value_first) = read_map_key(stream, quote)?;
+                        if let Some(warnings) = stream.warnings.as_deref_mut()
+                            && map.contains_key(key.as_str())
+                        {
+                            warnings.warn(ParseWarningKind::DuplicateKey(key.clone()));
+                        }
+                        stream.enter(|| PathSegment::Key(key.clone()));
+                        if order.is_none() && spans.is_none() {
+                            let value = match from_reader_char(stream, value_first, max_depth + 1) {
+                                Ok(value) => value,
+                                Err(e) => return Err(e.at(PathSegment::Key(key))),
+                            };
+                            map.insert(key, value);
                         } else {
-                            stream.unescape(quote)?
-                        };
-                        match stream.skip_ws()? {
-                            Some(b':') => {}
-                            Some(other) => {
-                                bail!(
-                                    stream,
-                                    ParseErrorKind::Expected(format!(
-                                        "':' or '}}' after key, found: 0x{:02x}",
-                                        other
-                                    ))
-                                );
+                            let mut child_order = order.is_some().then(KeyOrder::new);
+                            let mut child_spans = spans.is_some().then(SpanTree::default);
+                            let value = match from_reader_char_inner(
+                                stream,
+                                value_first,
+                                max_depth + 1,
+                                child_order.as_mut(),
+                                child_spans.as_mut(),
+                            ) {
+                                Ok(value) => value,
+                                Err(e) => return Err(e.at(PathSegment::Key(key))),
+                            };
+                            if let (Some(order), Some(child)) = (order.as_deref_mut(), child_order)
+                            {
+                                order.push_entry(key.clone(), child);
                             }
-                            None => bail!(stream, ParseErrorKind::Eof),
-                        }
-                        let value_first = match stream.skip_ws()? {
-                            Some(c) => c,
-                            None => {
-                                bail!(stream, ParseErrorKind::Eof);
+                            if let (Some(spans), Some(child)) = (spans.as_deref_mut(), child_spans)
+                            {
+                                spans.push_entry(key.clone(), child);
                             }
-                        };
-                        map.insert(key, from_reader_char(stream, value_first, max_depth + 1)?);
+                            map.insert(key, value);
+                        }
+                        stream.leave();
                     }
                     Some(other) => {
                         bail!(
@@ -538,7 +1206,36 @@ fn from_reader_char<R: Read>(
                 match stream.skip_ws()? {
                     Some(b']') => break,
                     Some(b',') => continue,
-                    Some(c) => array.push(from_reader_char(stream, c, max_depth + 1)?),
+                    Some(c) => {
+                        stream.enter(|| PathSegment::Index(array.len()));
+                        if order.is_none() && spans.is_none() {
+                            array.push(
+                                from_reader_char(stream, c, max_depth + 1)
+                                    .map_err(|e| e.at(PathSegment::Index(array.len())))?,
+                            );
+                        } else {
+                            let mut child_order = order.is_some().then(KeyOrder::new);
+                            let mut child_spans = spans.is_some().then(SpanTree::default);
+                            let value = from_reader_char_inner(
+                                stream,
+                                c,
+                                max_depth + 1,
+                                child_order.as_mut(),
+                                child_spans.as_mut(),
+                            )
+                            .map_err(|e| e.at(PathSegment::Index(array.len())))?;
+                            if let (Some(order), Some(child)) = (order.as_deref_mut(), child_order)
+                            {
+                                order.push_item(child);
+                            }
+                            if let (Some(spans), Some(child)) = (spans.as_deref_mut(), child_spans)
+                            {
+                                spans.push_item(child);
+                            }
+                            array.push(value);
+                        }
+                        stream.leave();
+                    }
                     None => bail!(stream, ParseErrorKind::Eof),
                 }
             }
@@ -547,14 +1244,14 @@ fn from_reader_char<R: Read>(
         b'!' => Ok(Llsd::Undefined),
         b'0' => Ok(Llsd::Boolean(false)),
         b'1' => Ok(Llsd::Boolean(true)),
-        b'i' | b'I' => {
-            let buf = stream.take_while(|c| matches!(c, b'0'..=b'9' | b'+' | b'-'))?;
-            let i = map!(stream, stream.parse_utf8(buf)?.parse::<i32>())?;
-            Ok(Llsd::Integer(i))
-        }
+        b'i' | b'I' => Ok(Llsd::Integer(stream.integer()?)),
         b'r' | b'R' => {
-            let buf = stream.take_while(|c| b"-.0123456789eEinfINFaA".contains(&c))?;
-            let f = map!(stream, stream.parse_utf8(buf)?.parse::<f64>())?;
+            let text = stream.real_text()?;
+            let msvc = stream.lenient.then(|| crate::parse_msvc_real(&text));
+            let f = match msvc.flatten() {
+                Some(f) => f,
+                None => map!(stream, text.parse::<f64>())?,
+            };
             Ok(Llsd::Real(f))
         }
         b'u' | b'U' => {
@@ -564,16 +1261,11 @@ fn from_reader_char<R: Read>(
             Ok(Llsd::Uuid(uuid))
         }
         b't' | b'T' => {
-            stream.expect(b"rR")?;
-            stream.expect(b"uU")?;
-            stream.expect(b"eE")?;
+            stream.keyword_tail(b"rue")?;
             Ok(Llsd::Boolean(true))
         }
         b'f' | b'F' => {
-            stream.expect(b"aA")?;
-            stream.expect(b"lL")?;
-            stream.expect(b"sS")?;
-            stream.expect(b"eE")?;
+            stream.keyword_tail(b"alse")?;
             Ok(Llsd::Boolean(false))
         }
         b'\'' => Ok(Llsd::String(stream.unescape(b'\'')?)),
@@ -590,8 +1282,8 @@ fn from_reader_char<R: Read>(
         b'd' | b'D' => {
             stream.expect(b"\"")?;
             let str = stream.unescape(b'"')?;
-            let time = map!(stream, DateTime::parse_from_rfc3339(&str))?;
-            Ok(Llsd::Date(time.into()))
+            let time = map!(stream, date::parse_rfc3339(&str))?;
+            Ok(Llsd::Date(time))
         }
         b'b' | b'B' => {
             if let Some(c) = stream.peek()? {
@@ -667,9 +1359,9 @@ pub enum ParseErrorKind {
     #[error("utf8 error: {0}")]
     Utf8(#[from] std::string::FromUtf8Error),
     #[error("uuid error: {0}")]
-    Uuid(#[from] uuid::Error),
-    #[error("chrono error: {0}")]
-    Chrono(#[from] chrono::ParseError),
+    Uuid(#[from] crate::types::UuidError),
+    #[error("date error: {0}")]
+    Date(#[from] date::ParseError),
     #[error("int error: {0}")]
     Int(#[from] std::num::ParseIntError),
     #[error("float error: {0}")]
@@ -688,9 +1380,7 @@ impl PartialEq for ParseErrorKind {
             }
             (ParseErrorKind::Utf8(a), ParseErrorKind::Utf8(b)) => a.to_string() == b.to_string(),
             (ParseErrorKind::Uuid(a), ParseErrorKind::Uuid(b)) => a.to_string() == b.to_string(),
-            (ParseErrorKind::Chrono(a), ParseErrorKind::Chrono(b)) => {
-                a.to_string() == b.to_string()
-            }
+            (ParseErrorKind::Date(a), ParseErrorKind::Date(b)) => a.to_string() == b.to_string(),
             (ParseErrorKind::Int(a), ParseErrorKind::Int(b)) => a.to_string() == b.to_string(),
             (ParseErrorKind::Float(a), ParseErrorKind::Float(b)) => a.to_string() == b.to_string(),
             _ => false,
@@ -705,28 +1395,107 @@ impl Eq for ParseErrorKind {}
 pub struct ParseError {
     pub kind: ParseErrorKind,
     pub pos: Position,
+    /// Path of the map keys and array indices being read; not part of the
+    /// message.
+    pub path: Vec<PathSegment>,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, pos: Position) -> Self {
+        Self {
+            kind,
+            pos,
+            path: Vec::new(),
+        }
+    }
+
+    /// The path rendered as `a.b[3].c`; empty at the root.
+    pub fn path_string(&self) -> String {
+        crate::error::path_string(&self.path)
+    }
+
+    fn at(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
 }
 
-type ParseResult<T> = Result<T, ParseError>;
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// `byte` for error messages.
+fn found(byte: Option<u8>) -> String {
+    match byte {
+        Some(byte) => format!("0x{byte:02x}"),
+        None => "end of input".into(),
+    }
+}
 
-struct Stream<R: Read> {
+/// The byte reader under the notation parser, tracking the [`Position`] of
+/// everything it consumes so errors can point at it. It is public for
+/// parsers of neighbouring Linden text formats (legacy inventory and asset
+/// files, wearables) that want the same tokenizing helpers and
+/// [`ParseError`]s.
+///
+/// ```
+/// use llsd_rs::notation::{ParseErrorKind, Stream};
+///
+/// let mut stream = Stream::new(&b"name\t\"Box\\\"s\"\nflags\tx10"[..]);
+/// let key = stream.take_while(|c| c.is_ascii_alphanumeric()).unwrap();
+/// assert_eq!(key, b"name");
+/// assert_eq!(stream.skip_ws().unwrap(), Some(b'"'));
+/// assert_eq!(stream.unescape(b'"').unwrap(), "Box\"s");
+///
+/// assert_eq!(stream.skip_ws().unwrap(), Some(b'f'));
+/// stream.skip_while(|c| c != b'\t').unwrap();
+/// assert_eq!(stream.skip_ws().unwrap(), Some(b'x'));
+/// let err = stream.error(ParseErrorKind::InvalidChar(b'x'));
+/// assert_eq!((err.pos.line, err.pos.column), (2, 8));
+/// assert_eq!(err.to_string(), "invalid character: 0x78 at byte 21 (line 2, col 8)");
+/// ```
+pub struct Stream<'a, R: Read> {
     inner: BufReader<R>,
     pos: Position,
+    warnings: Option<&'a mut dyn Warn>,
+    /// Read integers outside the `i32` range as the nearest bound.
+    clamp_integers: bool,
+    /// Accept MSVC special reals such as `1.#INF`.
+    lenient: bool,
 }
 
-impl<R: Read> Stream<R> {
-    fn new(read: R) -> Self {
+impl<'a, R: Read> Stream<'a, R> {
+    pub fn new(read: R) -> Self {
         Self {
             inner: BufReader::new(read),
             pos: Position::default(),
+            warnings: None,
+            clamp_integers: false,
+            lenient: false,
+        }
+    }
+
+    fn enter(&mut self, segment: impl FnOnce() -> PathSegment) {
+        if let Some(warnings) = self.warnings.as_deref_mut() {
+            warnings.enter(segment());
+        }
+    }
+
+    fn leave(&mut self) {
+        if let Some(warnings) = self.warnings.as_deref_mut() {
+            warnings.leave();
         }
     }
 
+    /// Where the next byte will be read from.
     #[inline]
     pub fn pos(&self) -> Position {
         self.pos
     }
 
+    /// A [`ParseError`] of `kind` at the current position.
+    pub fn error(&self, kind: impl Into<ParseErrorKind>) -> ParseError {
+        ParseError::new(kind.into(), self.pos)
+    }
+
     #[inline]
     fn advance(&mut self, byte: u8) {
         self.pos.offset += 1;
@@ -739,24 +1508,18 @@ impl<R: Read> Stream<R> {
     }
 
     /// Return the next byte **without** consuming it.
-    fn peek(&mut self) -> ParseResult<Option<u8>> {
+    pub fn peek(&mut self) -> ParseResult<Option<u8>> {
         match self.inner.fill_buf() {
             Ok([]) => Ok(None),
-            Ok(buf) => {
-                let byte = buf[0];
-                self.pos.offset += 1;
-                self.pos.column += 1;
-                Ok(Some(byte))
-            }
-            Err(e) => Err(ParseError {
-                kind: ParseErrorKind::Io(e),
-                pos: self.pos,
-            }),
+            Ok(buf) => Ok(Some(buf[0])),
+            Err(e) => Err(ParseError::new(ParseErrorKind::Io(e), self.pos)),
         }
     }
 
-    /// Consume one byte and return it.
-    fn next(&mut self) -> ParseResult<Option<u8>> {
+    /// Consume one byte and return it. (Not [`Iterator::next`]: reading can
+    /// fail partway.)
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> ParseResult<Option<u8>> {
         if let Some(b) = self.peek()? {
             self.advance(b);
             self.inner.consume(1);
@@ -765,8 +1528,8 @@ impl<R: Read> Stream<R> {
         Ok(None)
     }
 
-    /// Skip ASCII whitespace and return the first non-WS byte, consuming it
-    fn skip_ws(&mut self) -> ParseResult<Option<u8>> {
+    /// Skip ASCII whitespace and return the first non-WS byte, consuming it.
+    pub fn skip_ws(&mut self) -> ParseResult<Option<u8>> {
         loop {
             match self.next()? {
                 Some(b' ' | b'\t' | b'\r' | b'\n') => continue,
@@ -777,26 +1540,37 @@ impl<R: Read> Stream<R> {
     }
 
     /// Consume one of the expected bytes.
-    fn expect(&mut self, expected: &[u8]) -> ParseResult<()> {
+    pub fn expect(&mut self, expected: &[u8]) -> ParseResult<()> {
         match self.next()? {
             Some(b) if expected.contains(&b) => Ok(()),
-            Some(b) => Err(ParseError {
-                kind: ParseErrorKind::Expected(format!(
+            Some(b) => Err(ParseError::new(
+                ParseErrorKind::Expected(format!(
                     "expected one of {:?}, found: 0x{:02x}",
                     expected, b
                 )),
-                pos: self.pos,
-            }),
-            None => Err(ParseError {
-                kind: ParseErrorKind::Eof,
-                pos: self.pos,
-            }),
+                self.pos,
+            )),
+            None => Err(ParseError::new(ParseErrorKind::Eof, self.pos)),
+        }
+    }
+
+    /// Consume the rest of `true` or `false` (`tail`, in any case) unless the
+    /// value was written as the bare letter.
+    fn keyword_tail(&mut self, tail: &[u8]) -> ParseResult<()> {
+        if self
+            .peek()?
+            .is_some_and(|c| c.eq_ignore_ascii_case(&tail[0]))
+        {
+            for &c in tail {
+                self.expect(&[c, c.to_ascii_uppercase()])?;
+            }
         }
+        Ok(())
     }
 
     /// Read a sequence that satisfies `pred` (stop *before* the first byte
     /// that fails the predicate).
-    fn take_while<F>(&mut self, mut pred: F) -> ParseResult<Vec<u8>>
+    pub fn take_while<F>(&mut self, mut pred: F) -> ParseResult<Vec<u8>>
     where
         F: FnMut(u8) -> bool,
     {
@@ -813,31 +1587,51 @@ impl<R: Read> Stream<R> {
         Ok(out)
     }
 
-    /// Unescape a string until the delimiter is reached.
-    fn unescape(&mut self, delim: u8) -> ParseResult<String> {
+    /// Like [`take_while`](Self::take_while), discarding the bytes.
+    pub fn skip_while<F>(&mut self, mut pred: F) -> ParseResult<()>
+    where
+        F: FnMut(u8) -> bool,
+    {
+        while let Some(b) = self.peek()? {
+            if !pred(b) {
+                break;
+            }
+            self.inner.consume(1);
+            self.advance(b);
+        }
+        Ok(())
+    }
+
+    /// Like [`unescape`](Self::unescape), discarding the string.
+    fn skip_quoted(&mut self, delim: u8) -> ParseResult<()> {
+        loop {
+            match self.next()? {
+                Some(c) if c == delim => return Ok(()),
+                Some(b'\\') => {
+                    if self.next()?.is_none() {
+                        bail!(self, ParseErrorKind::Eof);
+                    }
+                }
+                Some(_) => {}
+                None => bail!(self, ParseErrorKind::Eof),
+            }
+        }
+    }
+
+    /// Unescape a string until the delimiter is reached, consuming it. The
+    /// escapes are notation's: `\xNN`, and C-style single characters.
+    pub fn unescape(&mut self, delim: u8) -> ParseResult<String> {
         let mut buf = Vec::new();
         loop {
             match self.next()? {
                 Some(c) if c == delim => break,
                 Some(b'\\') => match self.next()? {
-                    Some(c) => match c {
-                        b'a' => buf.push(0x07),
-                        b'b' => buf.push(0x08),
-                        b'f' => buf.push(0x0c),
-                        b'n' => buf.push(b'\n'),
-                        b'r' => buf.push(b'\r'),
-                        b't' => buf.push(b'\t'),
-                        b'v' => buf.push(0x0b),
-                        b'\\' => buf.push(b'\\'),
-                        b'\'' => buf.push(b'\''),
-                        b'"' => buf.push(b'"'),
-                        b'x' => {
-                            let high = self.hex()?;
-                            let low = self.hex()?;
-                            buf.push((high << 4) | low);
-                        }
-                        other => buf.push(other),
-                    },
+                    Some(b'x') => {
+                        let high = self.hex()?;
+                        let low = self.hex()?;
+                        buf.push((high << 4) | low);
+                    }
+                    Some(c) => buf.push(escape::unescape_char(c)),
                     None => bail!(self, ParseErrorKind::Eof),
                 },
                 Some(other) => buf.push(other),
@@ -847,28 +1641,146 @@ impl<R: Read> Stream<R> {
         self.parse_utf8(buf)
     }
 
+    /// Read the text of an integer after its `i`: an optional sign and
+    /// decimal digits, not followed by anything that would continue a
+    /// number.
+    fn integer_text(&mut self) -> ParseResult<String> {
+        let mut text = Vec::new();
+        if let Some(sign @ (b'+' | b'-')) = self.peek()? {
+            self.next()?;
+            text.push(sign);
+        }
+        self.digits(&mut text, "integer")?;
+        self.number_end(text)
+    }
+
+    /// Read an integer after its `i`. One outside the `i32` range is an
+    /// error, or clamped and reported as a warning with `clamp_integers`.
+    fn integer(&mut self) -> ParseResult<i32> {
+        let start = self.pos;
+        let text = self.integer_text()?;
+        let (negative, digits) = match text.as_bytes()[0] {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, &text[..]),
+        };
+        let magnitude = digits.bytes().fold(0_i64, |acc, d| {
+            acc.saturating_mul(10).saturating_add(i64::from(d - b'0'))
+        });
+        let value = if negative { -magnitude } else { magnitude };
+        if let Ok(value) = i32::try_from(value) {
+            return Ok(value);
+        }
+        if !self.clamp_integers {
+            return Err(ParseError::new(
+                ParseErrorKind::Expected(format!("an integer in the i32 range, found {text}")),
+                start,
+            ));
+        }
+        let value = value.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+        if let Some(warnings) = self.warnings.as_deref_mut() {
+            warnings.warn(ParseWarningKind::OutOfRange { text, value });
+        }
+        Ok(value)
+    }
+
+    /// Read the text of a real after its `r`: an optional sign, then `nan`
+    /// or `inf` in any case, or digits with an optional fraction and
+    /// exponent; when lenient, an MSVC `#` suffix instead of the exponent.
+    fn real_text(&mut self) -> ParseResult<String> {
+        let mut text = Vec::new();
+        if let Some(sign @ (b'+' | b'-')) = self.peek()? {
+            self.next()?;
+            text.push(sign);
+        }
+        if let Some(b'n' | b'N' | b'i' | b'I') = self.peek()? {
+            text.extend(self.take_while(|c| b"infINFaA".contains(&c))?);
+            return self.number_end(text);
+        }
+        let whole = self.take_while(|c| c.is_ascii_digit())?;
+        let has_whole = !whole.is_empty();
+        text.extend(whole);
+        if self.peek()? == Some(b'.') {
+            self.next()?;
+            text.push(b'.');
+            if has_whole {
+                text.extend(self.take_while(|c| c.is_ascii_digit())?);
+            } else {
+                self.digits(&mut text, "real")?;
+            }
+        } else if !has_whole {
+            self.digits(&mut text, "real")?;
+        }
+        if self.lenient && self.peek()? == Some(b'#') {
+            text.extend(self.take_while(|c| c == b'#' || c.is_ascii_alphanumeric())?);
+            return self.number_end(text);
+        }
+        if let Some(e @ (b'e' | b'E')) = self.peek()? {
+            self.next()?;
+            text.push(e);
+            if let Some(sign @ (b'+' | b'-')) = self.peek()? {
+                self.next()?;
+                text.push(sign);
+            }
+            self.digits(&mut text, "exponent")?;
+        }
+        self.number_end(text)
+    }
+
+    /// Append one or more decimal digits to `text`.
+    fn digits(&mut self, text: &mut Vec<u8>, what: &str) -> ParseResult<()> {
+        let digits = self.take_while(|c| c.is_ascii_digit())?;
+        if digits.is_empty() {
+            bail!(
+                self,
+                ParseErrorKind::Expected(format!(
+                    "digits in {what}, found {}",
+                    found(self.peek()?)
+                ))
+            );
+        }
+        text.extend(digits);
+        Ok(())
+    }
+
+    /// Fail when the number in `text` runs on into another sign, point or
+    /// exponent, as in `i1-2`; otherwise return it.
+    fn number_end(&mut self, text: Vec<u8>) -> ParseResult<String> {
+        let text = self.parse_utf8(text)?;
+        if let Some(c @ (b'+' | b'-' | b'.' | b'e' | b'E' | b'0'..=b'9')) = self.peek()? {
+            bail!(
+                self,
+                ParseErrorKind::Expected(format!(
+                    "the end of number {text}, found {}",
+                    found(Some(c))
+                ))
+            );
+        }
+        Ok(text)
+    }
+
     /// Read a hex character and return its value.
     fn hex(&mut self) -> ParseResult<u8> {
         let c = self.next()?;
-        match c {
-            Some(b'0'..=b'9') => Ok(c.unwrap() - b'0'),
-            Some(b'a'..=b'f') => Ok(c.unwrap() - b'a' + 10),
-            Some(b'A'..=b'F') => Ok(c.unwrap() - b'A' + 10),
-            _ => bail!(self, ParseErrorKind::InvalidChar(c.unwrap_or(0))),
+        match c.and_then(escape::hex_value) {
+            Some(value) => Ok(value),
+            None => bail!(self, ParseErrorKind::InvalidChar(c.unwrap_or(0))),
         }
     }
 
     /// Read exactly `n` bytes into the buffer.
-    fn read_exact(&mut self, buf: &mut [u8]) -> ParseResult<()> {
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> ParseResult<()> {
         match self.inner.read_exact(buf) {
-            Err(e) => Err(ParseError {
-                kind: ParseErrorKind::Io(e),
-                pos: self.pos,
-            }),
+            Err(e) => Err(ParseError::new(ParseErrorKind::Io(e), self.pos)),
             _ => {
                 self.pos.offset += buf.len();
-                self.pos.line += buf.iter().filter(|&&b| b == b'\n').count();
-                self.pos.column = buf.iter().rev().take_while(|&&b| b != b'\n').count();
+                match buf.iter().rposition(|&b| b == b'\n') {
+                    Some(last) => {
+                        self.pos.line += buf.iter().filter(|&&b| b == b'\n').count();
+                        self.pos.column = buf.len() - last;
+                    }
+                    None => self.pos.column += buf.len(),
+                }
                 Ok(())
             }
         }
@@ -886,20 +1798,33 @@ impl<R: Read> Stream<R> {
         Ok(buf)
     }
 
+    /// Like [`read_sized`](Self::read_sized), discarding the bytes.
+    fn skip_sized(&mut self) -> ParseResult<()> {
+        self.expect(b"(")?;
+        let buf = self.take_while(|c| c != b')')?;
+        self.expect(b")")?;
+        let mut size = map!(self, self.parse_utf8(buf)?.parse::<usize>())?;
+        self.expect(b"\"'")?;
+        let mut chunk = [0_u8; 256];
+        while size > 0 {
+            let n = size.min(chunk.len());
+            self.read_exact(&mut chunk[..n])?;
+            size -= n;
+        }
+        self.expect(b"\"'")?;
+        Ok(())
+    }
+
     /// Read a UTF-8 string from the buffer.
     pub fn parse_utf8(&self, buf: Vec<u8>) -> ParseResult<String> {
-        String::from_utf8(buf).map_err(|e| ParseError {
-            kind: ParseErrorKind::Utf8(e),
-            pos: self.pos,
-        })
+        String::from_utf8(buf).map_err(|e| ParseError::new(ParseErrorKind::Utf8(e), self.pos))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{TimeZone, Utc};
-    use std::collections::HashMap;
+    use crate::{Date, LlsdMap};
 
     fn round_trip(llsd: Llsd, formatter: FormatterContext) {
         let encoded = to_vec(&llsd, &formatter).expect("Failed to encode");
@@ -922,6 +1847,61 @@ mod tests {
         round_trip_default(Llsd::Boolean(false));
     }
 
+    #[test]
+    fn boolean_lexemes() {
+        let llsd = Llsd::Array(vec![Llsd::Boolean(true), Llsd::Boolean(false)]);
+        for (lexeme, expected) in [
+            (BooleanLexeme::Words, "[true,false]"),
+            (BooleanLexeme::Digits, "[1,0]"),
+            (BooleanLexeme::Letters, "[t,f]"),
+            (BooleanLexeme::UpperLetters, "[T,F]"),
+        ] {
+            let context = FormatterContext::new().with_boolean_lexeme(lexeme);
+            assert_eq!(to_string(&llsd, &context).unwrap(), expected);
+            round_trip(llsd.clone(), context);
+        }
+        assert_eq!(
+            from_str("[t, F, TRUE, False, 1, 0]", 2).unwrap(),
+            Llsd::Array(
+                [true, false, true, false, true, false]
+                    .map(Llsd::Boolean)
+                    .to_vec()
+            )
+        );
+        assert!(from_str("tru", 1).is_err());
+        assert_eq!(
+            extract_pointer(&b"[t,'x']"[..], "/1", 4).unwrap(),
+            Some(Llsd::from("x"))
+        );
+    }
+
+    #[test]
+    fn key_quoting() {
+        let mut map = LlsdMap::new();
+        map.insert("it's \"é\"".to_string(), Llsd::Integer(1));
+        let llsd = Llsd::Map(map);
+        for (context, expected) in [
+            (FormatterContext::new(), r#"{'it\'s "\xc3\xa9"':i1}"#),
+            (
+                FormatterContext::new().with_key_quote(KeyQuote::Double),
+                r#"{"it's \"\xc3\xa9\"":i1}"#,
+            ),
+            (
+                FormatterContext::new()
+                    .with_key_quote(KeyQuote::Double)
+                    .with_escape_non_ascii_keys(false),
+                r#"{"it's \"é\"":i1}"#,
+            ),
+            (
+                FormatterContext::new().with_key_quote(KeyQuote::Sized),
+                r#"{s(9)"it's "é"":i1}"#,
+            ),
+        ] {
+            assert_eq!(to_string(&llsd, &context).unwrap(), expected);
+            round_trip(llsd.clone(), context);
+        }
+    }
+
     #[test]
     fn integer() {
         round_trip_default(Llsd::Integer(42));
@@ -946,6 +1926,85 @@ mod tests {
         round_trip_default(Llsd::Real(13.1415));
     }
 
+    #[test]
+    fn strict_numbers() {
+        let message = |input: &str| from_str(input, 1).unwrap_err().to_string();
+        assert!(message("i-1-2").contains("end of number -1"));
+        assert!(message("i--1").contains("digits in integer, found 0x2d"));
+        assert!(message("i+").contains("digits in integer, found end of input"));
+        assert!(message("i1.5").contains("end of number 1"));
+        assert!(message("i4294967295").contains("integer in the i32 range"));
+        assert!(message("r1e").contains("digits in exponent"));
+        assert!(message("r1.2.3").contains("end of number 1.2"));
+        assert!(message("r.").contains("digits in real"));
+        assert!(from_str("[i1-2]", 2).is_err());
+        assert!(from_str("[i1,r2]", 2).is_ok());
+
+        assert_eq!(from_str("i+7", 1).unwrap(), Llsd::Integer(7));
+        assert_eq!(from_str("r1e+5", 1).unwrap(), Llsd::Real(1e5));
+        assert_eq!(from_str("r-.5", 1).unwrap(), Llsd::Real(-0.5));
+        assert_eq!(from_str("r2.", 1).unwrap(), Llsd::Real(2.0));
+        assert!(
+            from_str("r-inf", 1)
+                .unwrap()
+                .as_real()
+                .unwrap()
+                .is_infinite()
+        );
+        assert!(from_str("rNaN", 1).unwrap().as_real().unwrap().is_nan());
+    }
+
+    #[test]
+    fn lenient_reals() {
+        let lenient = ParseOptions::new().with_lenient(true);
+        let read = |input: &[u8]| from_bytes_with_options(input, &lenient).unwrap();
+        assert_eq!(
+            read(b"[r1.#INF,r-1.#INF,r1.#INF00]"),
+            Llsd::Array(vec![
+                Llsd::Real(f64::INFINITY),
+                Llsd::Real(f64::NEG_INFINITY),
+                Llsd::Real(f64::INFINITY),
+            ])
+        );
+        for nan in [&b"r1.#QNAN"[..], b"r-1.#IND", b"r1.#snan", b"rNaN", b"rnan"] {
+            assert!(read(nan).as_real().unwrap().is_nan());
+        }
+        assert_eq!(read(b"rINF"), Llsd::Real(f64::INFINITY));
+        assert!(from_bytes_with_options(b"[r1.#INFO]", &lenient).is_err());
+        assert!(from_bytes_with_options(b"[r1.#INF]", &ParseOptions::new()).is_err());
+        assert!(from_str("[r1.#QNAN]", 2).is_err());
+    }
+
+    #[test]
+    fn clamped_integers() {
+        let options = ParseOptions::new().with_clamped_integers(true);
+        let mut warnings = ParseWarnings::new();
+        let llsd = from_bytes_with_warnings(
+            b"[i4294967295,i-99999999999999999999999,i-2147483648]",
+            &options,
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(
+            llsd,
+            Llsd::Array(vec![
+                Llsd::Integer(i32::MAX),
+                Llsd::Integer(i32::MIN),
+                Llsd::Integer(i32::MIN),
+            ])
+        );
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings.warnings()[0].kind,
+            ParseWarningKind::OutOfRange {
+                text: "4294967295".into(),
+                value: i32::MAX,
+            }
+        );
+        assert_eq!(warnings.warnings()[1].path_string(), "[1]");
+        assert!(from_bytes_with_options(b"i4294967295", &ParseOptions::new()).is_err());
+    }
+
     #[test]
     fn string() {
         round_trip_default(Llsd::String("Hello, LLSD!".to_owned()));
@@ -964,7 +2023,7 @@ mod tests {
 
     #[test]
     fn date() {
-        let dt = Utc.timestamp_opt(1_620_000_000, 0).unwrap();
+        let dt = Date::from_timestamp(1_620_000_000, 0).unwrap();
         round_trip_default(Llsd::Date(dt));
     }
 
@@ -991,11 +2050,261 @@ mod tests {
 
     #[test]
     fn map() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert("answer".into(), Llsd::Integer(42));
         map.insert("pi".into(), Llsd::Real(13.14));
         map.insert("greeting".into(), Llsd::String("hello".into()));
         round_trip_default(Llsd::Map(map.clone()));
         round_trip(Llsd::Map(map), FormatterContext::new().with_pretty(true));
     }
+
+    #[test]
+    fn write_rejects_excessive_depth() {
+        let mut llsd = Llsd::Undefined;
+        for _ in 0..10_000 {
+            llsd = Llsd::Array(vec![llsd]);
+        }
+        let err = to_vec(&llsd, &FormatterContext::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mut nested = Llsd::Integer(1);
+        for _ in 0..3 {
+            let mut map = LlsdMap::new();
+            map.insert("k".into(), nested);
+            nested = Llsd::Map(map);
+        }
+        let context = FormatterContext::new().with_max_depth(3);
+        assert!(to_vec(&nested, &context).is_err());
+        let context = FormatterContext::new().with_max_depth(4);
+        assert_eq!(
+            to_string(&nested, &context).unwrap(),
+            "{'k':{'k':{'k':i1}}}"
+        );
+    }
+
+    #[test]
+    fn extract_pointer_skips_to_subtree() {
+        let mut body = LlsdMap::new();
+        body.insert("message".into(), Llsd::String("Teleport\\Finish".into()));
+        body.insert("blob".into(), Llsd::Binary(b"}]'\"".to_vec()));
+        body.insert("flag".into(), Llsd::Boolean(false));
+        let mut event = LlsdMap::new();
+        event.insert(
+            "skip".into(),
+            Llsd::Array(vec![Llsd::Real(1.0), Llsd::Map(LlsdMap::new())]),
+        );
+        event.insert("body".into(), Llsd::Map(body));
+        let mut root = LlsdMap::new();
+        root.insert("it's".into(), Llsd::Integer(3));
+        root.insert(
+            "events".into(),
+            Llsd::Array(vec![Llsd::Undefined, Llsd::Map(event)]),
+        );
+        let llsd = Llsd::Map(root);
+
+        for context in [
+            FormatterContext::new(),
+            FormatterContext::new().with_pretty(true).with_hex(true),
+        ] {
+            let encoded = to_vec(&llsd, &context).unwrap();
+            for pointer in [
+                "",
+                "/it's",
+                "/events/1/skip",
+                "/events/1/body",
+                "/events/1/body/flag",
+            ] {
+                let extracted = extract_pointer(encoded.as_slice(), pointer, 64).unwrap();
+                assert_eq!(extracted.as_ref(), llsd.pointer(pointer), "{pointer}");
+            }
+            for pointer in ["/nope", "/events/2", "/events/-1", "/events/0/x", "it's"] {
+                assert_eq!(
+                    extract_pointer(encoded.as_slice(), pointer, 64).unwrap(),
+                    None
+                );
+            }
+            let cut = &encoded[..encoded.len() - 1];
+            assert!(
+                extract_pointer(cut, "/events/1/body", 64)
+                    .unwrap()
+                    .is_some()
+            );
+            assert!(extract_pointer(cut, "/missing", 64).is_err());
+        }
+    }
+
+    #[test]
+    fn reals_round_trip_exactly() {
+        for v in [
+            0.1,
+            -0.0,
+            1.0 / 3.0,
+            1e21,
+            1e-7,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let encoded = to_vec(&Llsd::Real(v), &FormatterContext::default()).unwrap();
+            let decoded = from_bytes(&encoded, 1).unwrap();
+            assert_eq!(decoded.as_real().map(|r| r.to_bits()), Some(v.to_bits()));
+        }
+        let encoded = to_string(
+            &Llsd::Array(vec![Llsd::Real(1.0), Llsd::Integer(-7)]),
+            &FormatterContext::default(),
+        );
+        assert_eq!(encoded.unwrap(), "[r1.0,i-7]");
+    }
+
+    #[test]
+    fn encoder_matches_to_vec_across_calls() {
+        let mut map = LlsdMap::new();
+        map.insert("blob".into(), Llsd::Binary(vec![0xab; 40]));
+        map.insert(
+            "list".into(),
+            Llsd::Array(vec![Llsd::Integer(1), Llsd::Undefined]),
+        );
+        let docs = [
+            Llsd::Map(map),
+            Llsd::Binary(vec![1, 2, 3]),
+            Llsd::String("x".into()),
+        ];
+        for context in [
+            FormatterContext::new().with_hex(true),
+            FormatterContext::new().with_pretty(true).with_indent("\t"),
+        ] {
+            let mut encoder = NotationEncoder::new(context);
+            let mut out = Vec::new();
+            for llsd in &docs {
+                out.clear();
+                encoder.encode_into(llsd, &mut out).unwrap();
+                assert_eq!(out, to_vec(llsd, &context).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_round_trip_keeps_key_order() {
+        let mut text = String::from("{");
+        for i in 0..20 {
+            let key = format!("k{:02}", (i * 7) % 20);
+            text.push_str(&format!("'{key}':[{{'z':!,'a':i{i}}},i1],"));
+        }
+        text.push_str("'dup':i1,'dup':{'y':!,'x':!}}");
+
+        let doc = from_str_ordered(&text, 8).unwrap();
+        assert_eq!(doc.llsd, from_str(&text, 8).unwrap());
+        // The repeated key keeps its first position and the last value's order.
+        let written = to_string_ordered(&doc, &FormatterContext::new()).unwrap();
+        assert_eq!(written, text.replace("'dup':i1,'dup':", "'dup':"));
+        assert_eq!(doc.order.get("dup").unwrap().keys(), ["y", "x"]);
+
+        let pretty = FormatterContext::new().with_pretty(true);
+        let written = to_vec_ordered(&doc, &pretty).unwrap();
+        let reparsed = from_bytes_ordered(&written, 8).unwrap();
+        assert_eq!(reparsed.llsd, doc.llsd);
+        assert_eq!(reparsed.order.keys(), &doc.order.keys()[..21]);
+    }
+
+    #[test]
+    fn sorted_keys_ignore_map_and_recorded_order() {
+        let text = "{'b':{'z':!,'y':!},'a':[{'d':!,'c':!}]}";
+        let context = FormatterContext::new().with_sorted_keys(true);
+        let sorted = "{'a':[{'c':!,'d':!}],'b':{'y':!,'z':!}}";
+        assert_eq!(
+            to_string(&from_str(text, 8).unwrap(), &context).unwrap(),
+            sorted
+        );
+        let doc = from_str_ordered(text, 8).unwrap();
+        assert_eq!(to_string_ordered(&doc, &context).unwrap(), sorted);
+    }
+
+    #[test]
+    fn public_stream() {
+        let mut stream = Stream::new(&b"a\n\tb 'x\\x41' \xff"[..]);
+        assert_eq!(stream.next().unwrap(), Some(b'a'));
+        assert_eq!(stream.skip_ws().unwrap(), Some(b'b'));
+        let mut buf = [0; 2];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b" '");
+        assert_eq!(stream.unescape(b'\'').unwrap(), "xA");
+        stream.expect(b" ").unwrap();
+        let byte = stream.peek().unwrap().unwrap();
+        let err = stream.parse_utf8(vec![byte]).unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Utf8(_)));
+        assert_eq!(
+            err.pos,
+            Position {
+                offset: 13,
+                line: 2,
+                column: 12,
+            }
+        );
+        stream.next().unwrap();
+        assert_eq!(stream.peek().unwrap(), None);
+        assert_eq!(stream.expect(b" ").unwrap_err().kind, ParseErrorKind::Eof);
+    }
+
+    #[test]
+    fn truncated_output() {
+        let llsd = crate::llsd_map! {
+            "a" => crate::llsd_array![1, 2, crate::llsd_array!["x", "y"], 4],
+            "b" => "a long string value",
+        };
+        let context = FormatterContext::new().with_sorted_keys(true);
+        let full = to_vec(&llsd, &context).unwrap();
+        assert_eq!(
+            to_vec_truncated(&llsd, &context, full.len()).unwrap(),
+            (full.clone(), false)
+        );
+        assert_eq!(
+            to_string_truncated(&llsd, &context, 42).unwrap(),
+            ("{'a':[i1,'...(+3 more)'],'...(+1 more)':!}".into(), true)
+        );
+        assert_eq!(
+            to_vec_truncated(&llsd, &context, 1).unwrap(),
+            (b"!".to_vec(), true)
+        );
+
+        for context in [context, context.with_pretty(true)] {
+            let full = to_vec(&llsd, &context).unwrap();
+            for max_bytes in 2..full.len() {
+                let (out, truncated) = to_vec_truncated(&llsd, &context, max_bytes).unwrap();
+                assert!(truncated);
+                assert!(out.len() <= max_bytes, "{max_bytes}");
+                from_bytes(&out, 8)
+                    .unwrap_or_else(|e| panic!("{e}: {}", String::from_utf8_lossy(&out)));
+            }
+        }
+
+        use crate::corpus::{CorpusOptions, Generator};
+        for llsd in Generator::new(CorpusOptions::new(1470)).take(20) {
+            let full = to_vec(&llsd, &FormatterContext::new()).unwrap();
+            for max_bytes in [2, 16, full.len() / 2, full.len()] {
+                let (out, truncated) =
+                    to_vec_truncated(&llsd, &FormatterContext::new(), max_bytes).unwrap();
+                assert_eq!(truncated, max_bytes < full.len());
+                assert!(out.len() <= max_bytes.max(1));
+                from_bytes(&out, 64).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn peeks_types() {
+        for (data, ty) in [
+            (&b"  {'a':1}"[..], Some(LlsdType::Map)),
+            (b"<? LLSD/Notation ?>\n[", Some(LlsdType::Array)),
+            (b"i42", Some(LlsdType::Integer)),
+            (b"T", Some(LlsdType::Boolean)),
+            (b"s(3)\"abc\"", Some(LlsdType::String)),
+            (b"b64\"AA==\"", Some(LlsdType::Binary)),
+            (b"x", None),
+            (b" \n", None),
+        ] {
+            assert_eq!(peek_type(data), ty, "{}", String::from_utf8_lossy(data));
+        }
+    }
 }
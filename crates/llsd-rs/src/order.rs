@@ -0,0 +1,234 @@
+//! Recording and replaying the key order of parsed maps.
+//!
+//! [`LlsdMap`] does not keep insertion order, so re-serializing a document
+//! generally lists map keys in a different order than the original, which
+//! makes diffs of re-encoded captures noisy. The `*_ordered` parse functions
+//! in [`crate::xml`] and [`crate::notation`] return an [`OrderedLlsd`] that
+//! carries the key order alongside the value, and the matching
+//! `*_ordered` writers emit keys in that order.
+//!
+//! The XML reader also records the attributes of every element that has
+//! any, including `encoding` and namespaced vendor extensions, and the XML
+//! writer puts them back, so a document read and written again keeps them.
+//!
+//! ```
+//! use llsd_rs::notation::{self, FormatterContext};
+//!
+//! let text = "{'z':i1,'y':{'b':!,'a':!},'x':[{'q':false,'p':true}]}";
+//! let doc = notation::from_str_ordered(text, 8).unwrap();
+//! let out = notation::to_string_ordered(&doc, &FormatterContext::new()).unwrap();
+//! assert_eq!(out, text);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{Llsd, LlsdMap, UndefinedEntries};
+
+/// Attributes of an XML element as `(qualified name, value)` pairs in
+/// document order, namespace declarations (`xmlns:ext`) first.
+pub type XmlAttributes = Vec<(String, String)>;
+
+/// Key order of the maps in a document, as a tree shaped like the value.
+///
+/// For a map, the keys in the order they were read, each with the order of
+/// its value; for an array, the order of each element. Empty for scalars.
+/// Read from XML, each node also keeps the attributes of its value's element
+/// and, in a map, of the `<key>` element before it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyOrder {
+    keys: Vec<String>,
+    children: Vec<KeyOrder>,
+    attributes: XmlAttributes,
+    key_attributes: XmlAttributes,
+}
+
+impl KeyOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map keys in document order. A key repeated in the document appears
+    /// once per occurrence.
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// The order recorded for the value of `key` (its last occurrence).
+    pub fn get(&self, key: &str) -> Option<&KeyOrder> {
+        self.keys
+            .iter()
+            .rposition(|k| k == key)
+            .map(|i| &self.children[i])
+    }
+
+    /// The order recorded for array element `index`.
+    pub fn get_index(&self, index: usize) -> Option<&KeyOrder> {
+        self.keys
+            .is_empty()
+            .then(|| self.children.get(index))
+            .flatten()
+    }
+
+    /// Attributes of the value's XML element. When there are any, the XML
+    /// writer writes these instead of its own (`encoding="base64"`).
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// Attributes of the `<key>` element naming the value.
+    pub fn key_attributes(&self) -> &[(String, String)] {
+        &self.key_attributes
+    }
+
+    pub(crate) fn set_attributes(&mut self, attributes: XmlAttributes) {
+        self.attributes = attributes;
+    }
+
+    pub(crate) fn set_key_attributes(&mut self, attributes: XmlAttributes) {
+        self.key_attributes = attributes;
+    }
+
+    pub(crate) fn push_entry(&mut self, key: String, child: KeyOrder) {
+        self.keys.push(key);
+        self.children.push(child);
+    }
+
+    pub(crate) fn push_item(&mut self, child: KeyOrder) {
+        self.children.push(child);
+    }
+}
+
+/// A decoded value together with the key order it was read in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderedLlsd {
+    pub llsd: Llsd,
+    pub order: KeyOrder,
+    /// Attributes of the `<llsd>` root element, when read from XML.
+    pub root_attributes: XmlAttributes,
+}
+
+impl OrderedLlsd {
+    pub fn new(llsd: Llsd, order: KeyOrder) -> Self {
+        Self {
+            llsd,
+            order,
+            root_attributes: XmlAttributes::new(),
+        }
+    }
+}
+
+impl From<OrderedLlsd> for Llsd {
+    fn from(doc: OrderedLlsd) -> Self {
+        doc.llsd
+    }
+}
+
+/// Map entries for the writers, each with the order recorded for its value.
+pub(crate) struct Entries<'a> {
+    source: Source<'a>,
+    drop_undefined: bool,
+}
+
+enum Source<'a> {
+    Plain(crate::map::Iter<'a>),
+    Ordered(std::vec::IntoIter<(&'a str, &'a Llsd, Option<&'a KeyOrder>)>),
+}
+
+impl<'a> Entries<'a> {
+    fn new(source: Source<'a>) -> Self {
+        Self {
+            source,
+            drop_undefined: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = (&'a str, &'a Llsd, Option<&'a KeyOrder>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match &mut self.source {
+                Source::Plain(iter) => iter.next().map(|(k, v)| (k.as_str(), v, None)),
+                Source::Ordered(iter) => iter.next(),
+            };
+            match entry {
+                Some((_, Llsd::Undefined, _)) if self.drop_undefined => {}
+                entry => return entry,
+            }
+        }
+    }
+}
+
+/// Entries of `map`, in the recorded order when there is one. Keys the order
+/// does not know (added after parsing) follow in the map's own order; recorded
+/// keys no longer in the map are skipped.
+pub(crate) fn entries<'a>(map: &'a LlsdMap, order: Option<&'a KeyOrder>) -> Entries<'a> {
+    let Some(order) = order.filter(|o| !o.keys.is_empty()) else {
+        return Entries::new(Source::Plain(map.iter()));
+    };
+    // Position of the first occurrence, order of the last one: the value a
+    // repeated key decodes to is the last.
+    let mut recorded: HashMap<&str, Option<&KeyOrder>> = HashMap::new();
+    for (key, child) in order.keys.iter().zip(&order.children) {
+        recorded.insert(key, Some(child));
+    }
+    let mut out = Vec::with_capacity(map.len());
+    for key in &order.keys {
+        let Some(child) = recorded.get_mut(key.as_str()).and_then(Option::take) else {
+            continue;
+        };
+        if let Some(value) = map.get(key.as_str()) {
+            out.push((key.as_str(), value, Some(child)));
+        }
+    }
+    if out.len() < map.len() {
+        out.extend(
+            map.iter()
+                .filter(|(k, _)| !recorded.contains_key(k.as_str()))
+                .map(|(k, v)| (k.as_str(), v, None)),
+        );
+    }
+    Entries::new(Source::Ordered(out.into_iter()))
+}
+
+/// Entries of `map` sorted by key, for output that does not depend on the
+/// map's iteration order.
+pub(crate) fn sorted_entries(map: &LlsdMap) -> Entries<'_> {
+    let mut out: Vec<_> = map.iter().map(|(k, v)| (k.as_str(), v, None)).collect();
+    out.sort_unstable_by_key(|(k, ..)| *k);
+    Entries::new(Source::Ordered(out.into_iter()))
+}
+
+/// Entries of `map` as a writer emits them: sorted or in the recorded order,
+/// leaving out undefined values when `undefined` drops them.
+pub(crate) fn write_entries<'a>(
+    map: &'a LlsdMap,
+    order: Option<&'a KeyOrder>,
+    sorted: bool,
+    undefined: UndefinedEntries,
+) -> Entries<'a> {
+    let mut entries = if sorted {
+        sorted_entries(map)
+    } else {
+        entries(map, order)
+    };
+    entries.drop_undefined = undefined == UndefinedEntries::Drop;
+    entries
+}
+
+/// How many entries of `map` a writer emits under `undefined`.
+pub(crate) fn written_len(map: &LlsdMap, undefined: UndefinedEntries) -> usize {
+    match undefined {
+        UndefinedEntries::Keep => map.len(),
+        UndefinedEntries::Drop => map.values().filter(|v| !v.is_undefined()).count(),
+    }
+}
+
+/// Orders of the elements of an array recorded as `order`.
+pub(crate) fn items(order: Option<&KeyOrder>) -> std::slice::Iter<'_, KeyOrder> {
+    match order {
+        Some(order) if order.keys.is_empty() => order.children.iter(),
+        _ => [].iter(),
+    }
+}
@@ -0,0 +1,169 @@
+//! Buffered output for the writers.
+//!
+//! The writers emit a lot of very small pieces (tags, punctuation, length
+//! prefixes). [`Output`] collects them in a fixed-size buffer and hands the
+//! writer one chunk at a time; payloads at least as large as the buffer are
+//! sent alongside whatever is pending with a single `write_vectored` call
+//! instead of being copied.
+
+use std::io::{self, IoSlice, Write};
+
+const CAPACITY: usize = 8 * 1024;
+
+pub(crate) struct Output<'w, W: Write> {
+    inner: &'w mut W,
+    buf: Vec<u8>,
+}
+
+impl<'w, W: Write> Output<'w, W> {
+    pub(crate) fn new(inner: &'w mut W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(CAPACITY),
+        }
+    }
+
+    /// Write out anything still buffered. Does not flush the inner writer.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.flush_buf()
+    }
+
+    fn flush_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    fn write_large(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut slices = [IoSlice::new(&self.buf), IoSlice::new(data)];
+        let mut slices = &mut slices[..];
+        while !slices.is_empty() {
+            match self.inner.write_vectored(slices) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => IoSlice::advance_slices(&mut slices, n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Output<'_, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.write_all(data)?;
+        Ok(data.len())
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() >= CAPACITY {
+            return self.write_large(data);
+        }
+        if self.buf.len() + data.len() > CAPACITY {
+            self.flush_buf()?;
+        }
+        self.buf.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+/// Reuse the allocation of `v` for a vector of another element type.
+///
+/// The encoders keep their container stacks between calls, but the frames
+/// borrow from the value being written, so the stack is stored with a
+/// `'static` element type and re-typed for each call. `v` is cleared first;
+/// collecting an empty iterator over an element of the same layout reuses the
+/// allocation in place.
+pub(crate) fn recycle<T, U>(mut v: Vec<T>) -> Vec<U> {
+    v.clear();
+    v.into_iter().map(|_| unreachable!()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records every call that reaches the underlying writer.
+    #[derive(Default)]
+    struct Calls {
+        data: Vec<u8>,
+        writes: usize,
+        vectored: usize,
+    }
+
+    impl Write for Calls {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            // Accept at most 1000 bytes per call to exercise partial writes.
+            let n = data.len().min(1000);
+            self.data.extend_from_slice(&data[..n]);
+            Ok(n)
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.vectored += 1;
+            let mut total = 0;
+            for buf in bufs {
+                let n = buf.len().min(1000 - total);
+                self.data.extend_from_slice(&buf[..n]);
+                total += n;
+                if total == 1000 {
+                    break;
+                }
+            }
+            Ok(total)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn recycle_keeps_the_allocation() {
+        let v: Vec<&'static str> = Vec::with_capacity(32);
+        let ptr = v.as_ptr() as usize;
+        let text = String::from("borrowed");
+        let mut w: Vec<&str> = recycle(v);
+        w.push(&text);
+        assert_eq!(w.as_ptr() as usize, ptr);
+        assert!(w.capacity() >= 32);
+    }
+
+    #[test]
+    fn small_writes_are_batched() {
+        let mut calls = Calls::default();
+        let mut out = Output::new(&mut calls);
+        for _ in 0..100 {
+            out.write_all(b"{").unwrap();
+        }
+        out.finish().unwrap();
+        assert_eq!(calls.data, [b'{'; 100]);
+        assert_eq!(calls.writes, 1);
+    }
+
+    #[test]
+    fn large_payloads_go_out_vectored() {
+        let payload: Vec<u8> = (0..CAPACITY * 2).map(|i| i as u8).collect();
+        let mut calls = Calls::default();
+        let mut out = Output::new(&mut calls);
+        out.write_all(b"b").unwrap();
+        out.write_all(&payload).unwrap();
+        out.write_all(b"]").unwrap();
+        out.finish().unwrap();
+
+        let mut expected = b"b".to_vec();
+        expected.extend_from_slice(&payload);
+        expected.push(b']');
+        assert_eq!(calls.data, expected);
+        assert!(calls.vectored > 0);
+    }
+}
@@ -0,0 +1,456 @@
+//! Copy-on-write edits over a shared document.
+//!
+//! An [`Overlay`] records changes to a base document held in an `Arc`
+//! without touching it: each edit copies only the values on its path, and
+//! everything else is read from the base. Many overlays can share one large
+//! template (a default settings or capability document, say) and each apply
+//! its own per-connection tweaks, then [`materialize`](Overlay::materialize)
+//! the merged result when it is sent.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use llsd_rs::{Llsd, llsd_array, llsd_map, overlay::Overlay};
+//!
+//! let template = Arc::new(llsd_map! {
+//!     "region" => llsd_map! { "name" => "Ahern", "flags" => 0 },
+//!     "seeds" => llsd_array!["a", "b"],
+//! });
+//! let mut overlay = Overlay::new(template.clone());
+//! overlay.set("/region/flags", 16).unwrap();
+//! overlay.remove("/seeds").unwrap();
+//!
+//! assert_eq!(*overlay.get("/region/flags").unwrap(), Llsd::Integer(16));
+//! assert_eq!(overlay.materialize(), llsd_map! {
+//!     "region" => llsd_map! { "name" => "Ahern", "flags" => 16 },
+//! });
+//! assert_eq!(template["region"]["flags"], Llsd::Integer(0));
+//! ```
+
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use crate::{Llsd, LlsdError, LlsdMap, PathSegment, pointer_tokens};
+
+/// The edits at one path of the merged document.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// Changes below a map or array of the base; children without an entry
+    /// are the base's.
+    Edits(HashMap<String, Node>),
+    /// A value replacing the base's (or added where the base has none).
+    Value(Llsd),
+    /// A map entry of the base that is left out.
+    Removed,
+}
+
+/// A value of the merged document.
+enum Found<'a> {
+    /// Held whole by the base or an edit.
+    Value(&'a Llsd),
+    /// A base container with edits below it.
+    Edited(&'a Node, &'a Llsd),
+}
+
+/// Where an edit lands: a node of the overlay, with the base value at its
+/// path, or a value inside one that was already replaced.
+enum Place<'n, 'b> {
+    Node(&'n mut Node, Option<&'b Llsd>),
+    Value(&'n mut Llsd),
+}
+
+/// A shared base document with copy-on-write edits addressed by
+/// JSON-pointer-style paths (`"/a/b/0"`).
+///
+/// Map entries can be set, added and removed at any depth. Array elements
+/// of the base can be replaced in place; adding or removing them would
+/// shift the positions of the rest, so for that set the whole array.
+/// Within a value that an edit has replaced or added, paths behave like
+/// [`Llsd::try_index_mut`]: missing map keys are created, and removing an
+/// array element shifts the rest.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    base: Arc<Llsd>,
+    edits: Node,
+}
+
+impl Overlay {
+    pub fn new(base: Arc<Llsd>) -> Self {
+        Self {
+            base,
+            edits: Node::Edits(HashMap::new()),
+        }
+    }
+
+    pub fn base(&self) -> &Arc<Llsd> {
+        &self.base
+    }
+
+    /// Whether any edit was made since creation or the last
+    /// [`reset`](Self::reset).
+    pub fn is_modified(&self) -> bool {
+        !matches!(&self.edits, Node::Edits(children) if children.is_empty())
+    }
+
+    /// Drop every edit, showing the base again.
+    pub fn reset(&mut self) {
+        self.edits = Node::Edits(HashMap::new());
+    }
+
+    /// The value at `pointer` in the merged document: borrowed from the
+    /// base or from an edit when the path leads into either alone, built
+    /// when it is a container holding both.
+    pub fn get(&self, pointer: &str) -> Option<Cow<'_, Llsd>> {
+        match self.find(&pointer_tokens(pointer)?)? {
+            Found::Value(value) => Some(Cow::Borrowed(value)),
+            Found::Edited(edits, base) => merged(edits, Some(base)).map(Cow::Owned),
+        }
+    }
+
+    pub fn contains(&self, pointer: &str) -> bool {
+        self.get(pointer).is_some()
+    }
+
+    /// What is at `tokens` in the merged document, if anything.
+    fn find(&self, tokens: &[String]) -> Option<Found<'_>> {
+        let mut node = Some(&self.edits);
+        let mut base = Some(&*self.base);
+        for (i, token) in tokens.iter().enumerate() {
+            match node {
+                Some(Node::Removed) => return None,
+                Some(Node::Value(value)) => {
+                    let value = tokens[i..]
+                        .iter()
+                        .try_fold(value, |value, token| step(value, token))?;
+                    return Some(Found::Value(value));
+                }
+                Some(Node::Edits(children)) => node = children.get(token),
+                None => {}
+            }
+            base = base.and_then(|base| step(base, token));
+        }
+        match node {
+            Some(Node::Removed) => None,
+            Some(Node::Value(value)) => Some(Found::Value(value)),
+            Some(edits) => base.map(|base| Found::Edited(edits, base)),
+            None => base.map(Found::Value),
+        }
+    }
+
+    /// Set the value at `pointer`, creating missing map entries on the way.
+    /// Fails, naming the path, when a step leads into a scalar or past the
+    /// end of a base array.
+    pub fn set(&mut self, pointer: &str, value: impl Into<Llsd>) -> Result<(), LlsdError> {
+        let tokens = parse_pointer(pointer)?;
+        match locate(&mut self.edits, Some(&*self.base), &tokens)? {
+            Place::Node(node, _) => *node = Node::Value(value.into()),
+            Place::Value(slot) => *slot = value.into(),
+        }
+        Ok(())
+    }
+
+    /// Remove the entry at `pointer`; a missing entry is not an error.
+    /// Removing the root leaves `Undefined`.
+    pub fn remove(&mut self, pointer: &str) -> Result<(), LlsdError> {
+        let tokens = parse_pointer(pointer)?;
+        let Some((last, parent)) = tokens.split_last() else {
+            self.edits = Node::Value(Llsd::Undefined);
+            return Ok(());
+        };
+        if self.find(&tokens).is_none() {
+            return Ok(());
+        }
+        let path = || segments(&self.base, parent);
+        match locate(&mut self.edits, Some(&*self.base), parent)? {
+            Place::Node(Node::Edits(children), Some(Llsd::Map(map))) => {
+                if map.contains_key(last.as_str()) {
+                    children.insert(last.clone(), Node::Removed);
+                } else {
+                    children.remove(last);
+                }
+                Ok(())
+            }
+            Place::Node(Node::Edits(_), Some(_)) => Err(LlsdError::custom(
+                "elements of a base array can't be removed; set the whole array",
+            )
+            .with_prefix(&path())),
+            Place::Node(Node::Removed, _) => Ok(()),
+            Place::Node(Node::Value(value), _) | Place::Value(value) => {
+                remove_from(value, last).map_err(|e| e.with_prefix(&path()))
+            }
+            Place::Node(Node::Edits(_), None) => {
+                unreachable!("edits are only kept over containers")
+            }
+        }
+    }
+
+    /// The merged document.
+    pub fn materialize(&self) -> Llsd {
+        merged(&self.edits, Some(&*self.base)).unwrap_or_default()
+    }
+}
+
+impl From<Arc<Llsd>> for Overlay {
+    fn from(base: Arc<Llsd>) -> Self {
+        Self::new(base)
+    }
+}
+
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, LlsdError> {
+    pointer_tokens(pointer)
+        .ok_or_else(|| LlsdError::custom(format_args!("invalid pointer {pointer:?}")))
+}
+
+/// One step of a pointer, as [`Llsd::pointer`] takes it.
+fn step<'v>(value: &'v Llsd, token: &str) -> Option<&'v Llsd> {
+    match value {
+        Llsd::Array(array) => token.parse::<usize>().ok().and_then(|i| array.get(i)),
+        Llsd::Map(map) => map.get(token),
+        _ => None,
+    }
+}
+
+/// The path of `tokens` into `base`, for error messages.
+fn segments(base: &Llsd, tokens: &[String]) -> Vec<PathSegment> {
+    let mut value = Some(base);
+    tokens
+        .iter()
+        .map(|token| {
+            let segment = match (value, token.parse::<usize>()) {
+                (Some(Llsd::Array(_)), Ok(index)) => PathSegment::Index(index),
+                _ => PathSegment::Key(token.clone()),
+            };
+            value = value.and_then(|value| step(value, token));
+            segment
+        })
+        .collect()
+}
+
+/// Turn an edit node that can't hold child edits (over a scalar or nothing
+/// in the base) into a copy of the base value.
+fn normalize(node: &mut Node, base: Option<&Llsd>) {
+    match node {
+        Node::Edits(_) if !matches!(base, Some(Llsd::Map(_) | Llsd::Array(_))) => {
+            *node = Node::Value(base.cloned().unwrap_or_default());
+        }
+        Node::Removed => *node = Node::Value(Llsd::Undefined),
+        _ => {}
+    }
+}
+
+/// Walk `tokens` from `node`, adding edit nodes for the steps through base
+/// containers.
+fn locate<'n, 'b>(
+    mut node: &'n mut Node,
+    mut base: Option<&'b Llsd>,
+    tokens: &[String],
+) -> Result<Place<'n, 'b>, LlsdError> {
+    let mut path = Vec::with_capacity(tokens.len());
+    for (i, token) in tokens.iter().enumerate() {
+        normalize(node, base);
+        match node {
+            Node::Value(value) => {
+                let mut value = value;
+                for token in &tokens[i..] {
+                    let segment = match (&*value, token.parse::<usize>()) {
+                        (Llsd::Array(_), Ok(index)) => PathSegment::Index(index),
+                        _ => PathSegment::Key(token.clone()),
+                    };
+                    let next = match &segment {
+                        PathSegment::Index(index) => value.try_index_mut(*index),
+                        PathSegment::Key(key) => value.try_index_mut(key),
+                    };
+                    value = next.map_err(|e| e.with_prefix(&path))?;
+                    path.push(segment);
+                }
+                return Ok(Place::Value(value));
+            }
+            Node::Edits(children) => {
+                let (child, segment) = match base {
+                    Some(Llsd::Map(map)) => {
+                        (map.get(token.as_str()), PathSegment::Key(token.clone()))
+                    }
+                    Some(array @ Llsd::Array(items)) => match token.parse::<usize>() {
+                        Ok(index) if index < items.len() => {
+                            (items.get(index), PathSegment::Index(index))
+                        }
+                        Ok(index) => {
+                            return Err(LlsdError::custom(format_args!(
+                                "index {index} is past the end of an array of length {}",
+                                items.len()
+                            ))
+                            .with_prefix(&path));
+                        }
+                        Err(_) => {
+                            return Err(LlsdError::invalid_type("map", array).with_prefix(&path));
+                        }
+                    },
+                    _ => unreachable!("normalized above"),
+                };
+                node = children
+                    .entry(token.clone())
+                    .or_insert_with(|| Node::Edits(HashMap::new()));
+                base = child;
+                path.push(segment);
+            }
+            Node::Removed => unreachable!("normalized above"),
+        }
+    }
+    normalize(node, base);
+    Ok(Place::Node(node, base))
+}
+
+fn remove_from(value: &mut Llsd, token: &str) -> Result<(), LlsdError> {
+    match value {
+        Llsd::Map(map) => {
+            map.remove(token);
+            Ok(())
+        }
+        Llsd::Array(items) => match token.parse::<usize>() {
+            Ok(index) if index < items.len() => {
+                items.remove(index);
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            Err(_) => Err(LlsdError::invalid_type("map", value)),
+        },
+        _ => Err(LlsdError::invalid_type("map", value)),
+    }
+}
+
+/// The merged value of `node` over `base`; `None` when it was removed.
+fn merged(node: &Node, base: Option<&Llsd>) -> Option<Llsd> {
+    match (node, base) {
+        (Node::Removed, _) => None,
+        (Node::Value(value), _) => Some(value.clone()),
+        (Node::Edits(children), Some(Llsd::Map(map))) => {
+            let mut out = LlsdMap::with_capacity(map.len());
+            for (key, value) in map.iter() {
+                let value = match children.get(key) {
+                    Some(child) => merged(child, Some(value)),
+                    None => Some(value.clone()),
+                };
+                if let Some(value) = value {
+                    out.insert(key.clone(), value);
+                }
+            }
+            for (key, child) in children {
+                if !map.contains_key(key.as_str())
+                    && let Some(value) = merged(child, None)
+                {
+                    out.insert(key.clone(), value);
+                }
+            }
+            Some(Llsd::Map(out))
+        }
+        (Node::Edits(children), Some(Llsd::Array(items))) => Some(Llsd::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, item)| match children.get(itoa::Buffer::new().format(i)) {
+                        Some(child) => merged(child, Some(item)).unwrap_or_default(),
+                        None => item.clone(),
+                    },
+                )
+                .collect(),
+        )),
+        (Node::Edits(_), base) => base.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{llsd_array, llsd_map};
+
+    fn base() -> Arc<Llsd> {
+        Arc::new(llsd_map! {
+            "name" => "template",
+            "region" => llsd_map! { "id" => 7, "flags" => 0 },
+            "items" => llsd_array![llsd_map! { "n" => 1 }, 2, 3],
+        })
+    }
+
+    #[test]
+    fn edits_leave_the_base_alone() {
+        let base = base();
+        let mut overlay = Overlay::new(base.clone());
+        assert!(!overlay.is_modified());
+        assert_eq!(overlay.materialize(), *base);
+
+        overlay.set("/region/flags", 16).unwrap();
+        overlay.set("/items/0/n", 10).unwrap();
+        overlay.set("/new/deep/key", true).unwrap();
+        overlay.set("/new/deep/list", llsd_array![1]).unwrap();
+        overlay.set("/new/deep/list/0", 2).unwrap();
+        assert!(overlay.is_modified());
+
+        assert!(matches!(overlay.get("/name"), Some(Cow::Borrowed(_))));
+        assert!(matches!(
+            overlay.get("/region/flags"),
+            Some(Cow::Borrowed(Llsd::Integer(16)))
+        ));
+        assert_eq!(
+            *overlay.get("/region").unwrap(),
+            llsd_map! { "id" => 7, "flags" => 16 }
+        );
+        assert_eq!(
+            overlay.materialize(),
+            llsd_map! {
+                "name" => "template",
+                "region" => llsd_map! { "id" => 7, "flags" => 16 },
+                "items" => llsd_array![llsd_map! { "n" => 10 }, 2, 3],
+                "new" => llsd_map! {
+                    "deep" => llsd_map! { "key" => true, "list" => llsd_array![2] },
+                },
+            }
+        );
+        assert_eq!(base["region"]["flags"], Llsd::Integer(0));
+        assert_eq!(Arc::strong_count(&base), 2);
+
+        overlay.reset();
+        assert_eq!(overlay.materialize(), *base);
+    }
+
+    #[test]
+    fn removals() {
+        let mut overlay = Overlay::new(base());
+        overlay.remove("/region/id").unwrap();
+        overlay.remove("/missing/key").unwrap();
+        overlay.remove("/name/inner").unwrap();
+        assert_eq!(overlay.get("/region/id"), None);
+        assert!(!overlay.contains("/missing"));
+        assert_eq!(*overlay.get("/region").unwrap(), llsd_map! { "flags" => 0 });
+
+        overlay.set("/region/id", 8).unwrap();
+        assert_eq!(*overlay.get("/region/id").unwrap(), Llsd::Integer(8));
+        overlay.set("/added", 1).unwrap();
+        overlay.remove("/added").unwrap();
+        assert!(!overlay.contains("/added"));
+
+        overlay.set("/list", llsd_array![1, 2, 3]).unwrap();
+        overlay.remove("/list/1").unwrap();
+        assert_eq!(*overlay.get("/list").unwrap(), llsd_array![1, 3]);
+
+        overlay.remove("").unwrap();
+        assert_eq!(overlay.materialize(), Llsd::Undefined);
+    }
+
+    #[test]
+    fn errors_name_the_path() {
+        let mut overlay = Overlay::new(base());
+        let err = overlay.set("/items/5", 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value at items: index 5 is past the end of an array of length 3"
+        );
+        let err = overlay.set("/region/id/x", 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type at region.id: expected map, found integer"
+        );
+        let err = overlay.remove("/items/1").unwrap_err();
+        assert_eq!(err.path_string(), "items");
+        assert!(overlay.set("no slash", 1).is_err());
+        assert_eq!(overlay.materialize(), *base());
+    }
+}
@@ -0,0 +1,687 @@
+//! Parse options and located parse failures.
+//!
+//! The `*_with_options` parse functions ([`crate::binary::from_slice_with_options`],
+//! [`crate::notation::from_bytes_with_options`],
+//! [`crate::xml::from_slice_with_options`] and
+//! [`crate::autodetect::from_slice_with_options`]) report failures as a
+//! [`ParseFailure`]: the underlying error together with the byte offset where
+//! parsing stopped, the path of the container being read, and a short
+//! snippet of the input around the offset.
+//!
+//! ```
+//! use llsd_rs::parse::{ParseFailure, ParseOptions};
+//!
+//! let err = llsd_rs::notation::from_bytes_with_options(
+//!     b"{'agents':[{'name':'a'},{'name':ix}]}",
+//!     &ParseOptions::new(),
+//! )
+//! .unwrap_err();
+//! let failure = err.downcast_ref::<ParseFailure>().unwrap();
+//! assert_eq!(failure.path_string(), "agents[1].name");
+//! assert!(failure.snippet().is_some());
+//! ```
+//!
+//! Snippets copy input bytes into the error; use
+//! [`ParseOptions::without_snippet`] where payloads may hold secrets.
+//!
+//! The `*_with_warnings` variants also report input the decoders accept but
+//! a strict producer would not have written (duplicate keys, unknown XML
+//! attributes, padded or out-of-range numbers) to a [`ParseWarnings`] sink.
+//!
+//! ```
+//! use llsd_rs::parse::{ParseOptions, ParseWarningKind, ParseWarnings};
+//!
+//! let mut warnings = ParseWarnings::new();
+//! let llsd = llsd_rs::notation::from_bytes_with_warnings(
+//!     b"{'a':i1,'a':i2}",
+//!     &ParseOptions::new(),
+//!     &mut warnings,
+//! )
+//! .unwrap();
+//! assert_eq!(llsd["a"], llsd_rs::Llsd::Integer(2));
+//! assert_eq!(warnings.warnings()[0].kind, ParseWarningKind::DuplicateKey("a".into()));
+//! ```
+
+use std::fmt;
+
+use crate::PathSegment;
+use crate::autodetect::LlsdEncoding;
+
+const DEFAULT_MAX_DEPTH: usize = 64;
+const DEFAULT_CONTEXT_BYTES: usize = 16;
+
+/// Options for the `*_with_options` parse functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    max_depth: usize,
+    context_bytes: usize,
+    clamp_integers: bool,
+    lenient: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            context_bytes: DEFAULT_CONTEXT_BYTES,
+            clamp_integers: false,
+            lenient: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum container nesting (binary and notation).
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Bytes of input to include on each side of the failure offset.
+    pub fn with_context_bytes(mut self, context_bytes: usize) -> Self {
+        self.context_bytes = context_bytes;
+        self
+    }
+
+    /// Leave input bytes out of failures entirely.
+    pub fn without_snippet(self) -> Self {
+        self.with_context_bytes(0)
+    }
+
+    /// Read notation integers outside the `i32` range as the nearest bound,
+    /// as the reference parser does, instead of failing; the `*_with_warnings`
+    /// functions report each one.
+    pub fn with_clamped_integers(mut self, clamp: bool) -> Self {
+        self.clamp_integers = clamp;
+        self
+    }
+
+    /// Accept spellings older writers emit that the formats don't allow:
+    /// the MSVC reals `1.#INF`, `-1.#INF`, `1.#QNAN`, `1.#SNAN` and
+    /// `1.#IND` of older Windows viewers, in notation and XML, read as the
+    /// matching `f64` infinity or NaN. `nan` and `inf` are accepted in any
+    /// case either way.
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn context_bytes(&self) -> usize {
+        self.context_bytes
+    }
+
+    pub fn clamp_integers(&self) -> bool {
+        self.clamp_integers
+    }
+
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+}
+
+/// Input bytes around a failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Offset of the first byte of `bytes` in the input.
+    pub start: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for Snippet {
+    /// `bytes 10..18: 7b 27 61 27 3a 69 31 7d  |{'a':i1}|`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bytes {}..{}:",
+            self.start,
+            self.start + self.bytes.len()
+        )?;
+        for b in &self.bytes {
+            write!(f, " {b:02x}")?;
+        }
+        f.write_str("  |")?;
+        for &b in &self.bytes {
+            let c = if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            };
+            write!(f, "{c}")?;
+        }
+        f.write_str("|")
+    }
+}
+
+/// A parse error with the location it happened at.
+#[derive(Debug)]
+pub struct ParseFailure {
+    format: LlsdEncoding,
+    offset: Option<usize>,
+    path: Vec<PathSegment>,
+    snippet: Option<Snippet>,
+    source: anyhow::Error,
+}
+
+impl ParseFailure {
+    pub(crate) fn new(
+        format: LlsdEncoding,
+        data: &[u8],
+        offset: Option<usize>,
+        err: impl Into<anyhow::Error>,
+        options: &ParseOptions,
+    ) -> Self {
+        let (path, source) = match err.into().downcast::<Located>() {
+            Ok(located) => (located.path, located.source),
+            Err(source) => (Vec::new(), source),
+        };
+        let snippet = offset.and_then(|offset| snippet(data, offset, options));
+        Self {
+            format,
+            offset,
+            path,
+            snippet,
+            source,
+        }
+    }
+
+    /// Move the failure from a parse of `data[base..]` to `data`.
+    pub(crate) fn rebase(mut self, data: &[u8], base: usize, options: &ParseOptions) -> Self {
+        if let Some(offset) = &mut self.offset {
+            *offset += base;
+            self.snippet = snippet(data, *offset, options);
+        }
+        self
+    }
+
+    pub(crate) fn with_path(mut self, path: Vec<PathSegment>) -> Self {
+        if self.path.is_empty() {
+            self.path = path;
+        }
+        self
+    }
+
+    pub fn format(&self) -> LlsdEncoding {
+        self.format
+    }
+
+    /// Byte offset in the input where parsing stopped, when known.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Path from the document root to the value being read.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// The path rendered as `a.b[3].c`; empty at the root.
+    pub fn path_string(&self) -> String {
+        crate::error::path_string(&self.path)
+    }
+
+    /// Input around the offset; `None` when disabled in [`ParseOptions`].
+    pub fn snippet(&self) -> Option<&Snippet> {
+        self.snippet.as_ref()
+    }
+
+    /// The error the parser reported.
+    pub fn error(&self) -> &anyhow::Error {
+        &self.source
+    }
+}
+
+impl fmt::Display for ParseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self.format {
+            LlsdEncoding::Binary => "binary",
+            LlsdEncoding::Xml => "XML",
+            LlsdEncoding::Notation => "notation",
+        };
+        write!(f, "{format} parse failed")?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {offset}")?;
+        }
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path_string())?;
+        }
+        write!(f, ": {}", self.source)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, " (near {snippet})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
+fn snippet(data: &[u8], offset: usize, options: &ParseOptions) -> Option<Snippet> {
+    if options.context_bytes == 0 {
+        return None;
+    }
+    let offset = offset.min(data.len());
+    let start = offset.saturating_sub(options.context_bytes);
+    let end = offset.saturating_add(options.context_bytes).min(data.len());
+    Some(Snippet {
+        start,
+        bytes: data[start..end].to_vec(),
+    })
+}
+
+/// Something a decoder accepted but a strict producer would not have written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarningKind {
+    /// A map key seen again; the later value replaced the earlier one.
+    DuplicateKey(String),
+    /// An XML attribute the decoder does not interpret.
+    IgnoredAttribute { element: String, attribute: String },
+    /// Whitespace around a scalar's text was dropped before decoding it.
+    TrimmedWhitespace,
+    /// An integer outside the `i32` range, read as `value`: wrapped in XML,
+    /// as the viewer does, or clamped in notation with
+    /// [`ParseOptions::with_clamped_integers`].
+    OutOfRange { text: String, value: i32 },
+}
+
+impl fmt::Display for ParseWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarningKind::DuplicateKey(key) => write!(f, "duplicate key '{key}'"),
+            ParseWarningKind::IgnoredAttribute { element, attribute } => {
+                write!(f, "ignored attribute '{attribute}' on <{element}>")
+            }
+            ParseWarningKind::TrimmedWhitespace => f.write_str("trimmed whitespace"),
+            ParseWarningKind::OutOfRange { text, value } => {
+                write!(f, "integer {text} out of range, read as {value}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub kind: ParseWarningKind,
+    /// Path of the value (for duplicate keys, of the map) it applies to.
+    pub path: Vec<PathSegment>,
+}
+
+impl ParseWarning {
+    /// The path rendered as `a.b[3].c`; empty at the root.
+    pub fn path_string(&self) -> String {
+        crate::error::path_string(&self.path)
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            self.kind.fmt(f)
+        } else {
+            write!(f, "{} at {}", self.kind, self.path_string())
+        }
+    }
+}
+
+/// Where the `*_with_warnings` parse functions report warnings: collected
+/// into a list, or handed to a callback as they happen.
+#[derive(Default)]
+pub struct ParseWarnings<'a> {
+    warnings: Vec<ParseWarning>,
+    callback: Option<Box<dyn FnMut(ParseWarning) + 'a>>,
+    path: Vec<PathSegment>,
+}
+
+impl<'a> ParseWarnings<'a> {
+    /// A sink collecting every warning.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink passing each warning to `callback` instead of collecting it.
+    pub fn with_callback(callback: impl FnMut(ParseWarning) + 'a) -> Self {
+        Self {
+            callback: Some(Box::new(callback)),
+            ..Self::default()
+        }
+    }
+
+    /// The collected warnings; always empty with a callback.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn into_vec(self) -> Vec<ParseWarning> {
+        self.warnings
+    }
+
+    /// The sink for a new parse.
+    pub(crate) fn begin(&mut self) -> &mut (dyn Warn + 'a) {
+        self.path.clear();
+        self
+    }
+}
+
+impl fmt::Debug for ParseWarnings<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseWarnings")
+            .field("warnings", &self.warnings)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+/// The sink as the decoders see it, so they need only one lifetime.
+pub(crate) trait Warn {
+    /// Descend into `segment` of the current container.
+    fn enter(&mut self, segment: PathSegment);
+    fn leave(&mut self);
+    /// Report `kind` at the current path.
+    fn warn(&mut self, kind: ParseWarningKind);
+    /// Report `kind` at `path`, for decoders that track the path themselves.
+    fn warn_at(&mut self, path: Vec<PathSegment>, kind: ParseWarningKind);
+}
+
+impl Warn for ParseWarnings<'_> {
+    fn enter(&mut self, segment: PathSegment) {
+        self.path.push(segment);
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    fn warn(&mut self, kind: ParseWarningKind) {
+        self.warn_at(self.path.clone(), kind);
+    }
+
+    fn warn_at(&mut self, path: Vec<PathSegment>, kind: ParseWarningKind) {
+        let warning = ParseWarning { kind, path };
+        match &mut self.callback {
+            Some(callback) => callback(warning),
+            None => self.warnings.push(warning),
+        }
+    }
+}
+
+/// The path of the value a parser was reading, attached to its error as the
+/// error leaves each container. Displays as the underlying error, so it is
+/// invisible to callers that do not look for it.
+#[derive(Debug)]
+pub(crate) struct Located {
+    path: Vec<PathSegment>,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for Located {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl std::error::Error for Located {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
+/// Prepend `segment` to the path carried by `err`.
+pub(crate) fn locate(err: anyhow::Error, segment: PathSegment) -> anyhow::Error {
+    match err.downcast::<Located>() {
+        Ok(mut located) => {
+            located.path.insert(0, segment);
+            located.into()
+        }
+        Err(source) => Located {
+            path: vec![segment],
+            source,
+        }
+        .into(),
+    }
+}
+
+/// Drop the path carried by `err`, for entry points that report plain errors.
+pub(crate) fn unlocate(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast::<Located>() {
+        Ok(located) => located.source,
+        Err(err) => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Llsd;
+
+    #[test]
+    fn snippets_are_bounded() {
+        let data: Vec<u8> = (0..100).collect();
+        let options = ParseOptions::new().with_context_bytes(4);
+        let failure = ParseFailure::new(
+            LlsdEncoding::Binary,
+            &data,
+            Some(50),
+            anyhow::anyhow!("bad"),
+            &options,
+        );
+        let snippet = failure.snippet().unwrap();
+        assert_eq!(snippet.start, 46);
+        assert_eq!(snippet.bytes, (46..54).collect::<Vec<u8>>());
+
+        let failure = ParseFailure::new(
+            LlsdEncoding::Binary,
+            &data,
+            Some(98),
+            anyhow::anyhow!("bad"),
+            &options,
+        );
+        assert_eq!(failure.snippet().unwrap().bytes.len(), 6);
+
+        let failure = ParseFailure::new(
+            LlsdEncoding::Binary,
+            &data,
+            Some(50),
+            anyhow::anyhow!("bad"),
+            &options.without_snippet(),
+        );
+        assert!(failure.snippet().is_none());
+        assert_eq!(failure.to_string(), "binary parse failed at byte 50: bad");
+    }
+
+    #[test]
+    fn located_errors_are_transparent() {
+        let err = locate(anyhow::anyhow!("inner"), PathSegment::Index(2));
+        let err = locate(err, PathSegment::Key("items".into()));
+        assert_eq!(err.to_string(), "inner");
+        let failure = ParseFailure::new(
+            LlsdEncoding::Notation,
+            b"{'items':[1,2,x]}",
+            Some(15),
+            err,
+            &ParseOptions::new().with_context_bytes(3),
+        );
+        assert_eq!(failure.path_string(), "items[2]");
+        assert_eq!(
+            failure.to_string(),
+            "notation parse failed at byte 15 in items[2]: inner (near bytes 12..17: 32 2c 78 5d 7d  |2,x]}|)"
+        );
+    }
+
+    fn failure(err: anyhow::Error) -> ParseFailure {
+        err.downcast::<ParseFailure>().unwrap()
+    }
+
+    #[test]
+    fn binary_failures_name_the_container() {
+        let mut map = crate::LlsdMap::new();
+        map.insert(
+            "items".to_string(),
+            Llsd::Array(vec![Llsd::Integer(1), Llsd::String("two".into())]),
+        );
+        let mut data = crate::binary::to_vec(&Llsd::Map(map)).unwrap();
+        let tag = data.iter().rposition(|&b| b == b's').unwrap();
+        data[tag] = b'?';
+
+        let options = ParseOptions::new().with_context_bytes(2);
+        let err = crate::binary::from_slice_with_options(&data, &options).unwrap_err();
+        let failure = failure(err);
+        assert_eq!(failure.format(), LlsdEncoding::Binary);
+        assert_eq!(failure.path_string(), "items[1]");
+        assert_eq!(failure.offset(), Some(tag + 1));
+        assert_eq!(failure.snippet().unwrap().bytes, data[tag - 1..tag + 3]);
+        assert_eq!(failure.error().to_string(), "Unknown LLSD type: 63");
+
+        // The plain entry points report the same error without a location.
+        let err = crate::binary::from_slice(&data).unwrap_err();
+        assert!(err.downcast_ref::<Located>().is_none());
+        assert_eq!(err.to_string(), "Unknown LLSD type: 63");
+    }
+
+    #[test]
+    fn notation_failures_carry_offset_and_path() {
+        let data = b"{'a':[i1,\n  {'b':ix}]}";
+        let err = crate::notation::from_bytes(data, 64).unwrap_err();
+        assert_eq!(err.path_string(), "a[1].b");
+        assert_eq!(err.pos.offset, 18);
+        assert_eq!((err.pos.line, err.pos.column), (2, 9));
+
+        let failure = failure(
+            crate::notation::from_bytes_with_options(data, &ParseOptions::new()).unwrap_err(),
+        );
+        assert_eq!(failure.offset(), Some(18));
+        assert_eq!(failure.path_string(), "a[1].b");
+    }
+
+    #[test]
+    fn xml_failures_name_the_element() {
+        let data = b"<llsd><map><key>list</key><array><integer>1</integer>\n<integer>x</integer></array></map></llsd>";
+        let options = ParseOptions::new();
+        let failure = failure(crate::xml::from_slice_with_options(data, &options).unwrap_err());
+        assert_eq!(failure.format(), LlsdEncoding::Xml);
+        assert_eq!(failure.path_string(), "list[1]");
+        let offset = failure.offset().unwrap();
+        assert!(data[..offset].ends_with(b"<integer>"), "{failure}");
+
+        let err = crate::xml::from_slice(data).unwrap_err();
+        assert!(err.downcast_ref::<Located>().is_none());
+        assert!(
+            crate::xml::from_slice_with_options(b"<llsd><integer>1</integer></llsd>", &options)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn autodetect_offsets_include_the_header() {
+        let data = b"<? llsd/notation ?>\n[i1,i2,!x]";
+        let options = ParseOptions::new().without_snippet();
+        let failure =
+            failure(crate::autodetect::from_slice_with_options(data, &options).unwrap_err());
+        assert_eq!(failure.format(), LlsdEncoding::Notation);
+        assert_eq!(failure.path_string(), "[3]");
+        assert_eq!(&data[failure.offset().unwrap() - 1..][..1], b"x");
+        assert!(failure.snippet().is_none());
+    }
+
+    #[test]
+    fn duplicate_keys_in_every_format() {
+        let binary = {
+            let mut data = crate::binary::to_vec(&Llsd::Array(vec![Llsd::map()])).unwrap();
+            // [ {k"a" i1 k"a" i2} ] spliced in place of the empty map.
+            let map = data.iter().position(|&b| b == b'{').unwrap();
+            let mut body = vec![b'{', 0, 0, 0, 2];
+            for value in [1_i32, 2] {
+                body.extend([b'k', 0, 0, 0, 1, b'a', b'i']);
+                body.extend(value.to_be_bytes());
+            }
+            body.push(b'}');
+            data.splice(map..map + 6, body);
+            data
+        };
+        let options = ParseOptions::new();
+        let mut warnings = ParseWarnings::new();
+        let llsd =
+            crate::binary::from_slice_with_warnings(&binary, &options, &mut warnings).unwrap();
+        assert_eq!(llsd[0]["a"], Llsd::Integer(2));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings.warnings()[0].to_string(),
+            "duplicate key 'a' at [0]"
+        );
+
+        let mut warnings = ParseWarnings::new();
+        crate::notation::from_bytes_with_warnings(
+            b"[{'x':[i0,{'a':i1,'a':i2}]}]",
+            &options,
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(warnings.warnings()[0].path_string(), "[0].x[1]");
+
+        let mut warnings = ParseWarnings::new();
+        crate::xml::from_slice_with_warnings(
+            b"<llsd><map><key>m</key><map><key>a</key><integer>1</integer><key>a</key><integer>2</integer></map></map></llsd>",
+            &options,
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(
+            warnings.warnings()[0].kind,
+            ParseWarningKind::DuplicateKey("a".into())
+        );
+        assert_eq!(warnings.warnings()[0].path_string(), "m");
+    }
+
+    #[test]
+    fn xml_leniency_is_reported() {
+        let data = br#"<llsd version="1"><map>
+            <key>big</key><integer>3000000000</integer>
+            <key>padded</key><real> 1.5 </real>
+            <key>id</key><uuid>
+                11111111-2222-3333-4444-555555555555
+            </uuid>
+            <key>text</key><string> kept </string>
+            <key>blob</key><binary encoding="base64">AQI=</binary>
+            <key>list</key><array><boolean extra="x">1</boolean></array>
+        </map></llsd>"#;
+        let mut seen = Vec::new();
+        let mut warnings = ParseWarnings::with_callback(|w| seen.push(w.to_string()));
+        let llsd = crate::xml::from_slice_with_warnings(data, &ParseOptions::new(), &mut warnings)
+            .unwrap();
+        assert!(warnings.is_empty());
+        drop(warnings);
+        assert_eq!(llsd["big"], Llsd::Integer(-1294967296));
+        assert_eq!(llsd["padded"], Llsd::Real(1.5));
+        assert_eq!(llsd["text"], Llsd::String(" kept ".into()));
+        assert_eq!(
+            seen,
+            [
+                "ignored attribute 'version' on <llsd>",
+                "integer 3000000000 out of range, read as -1294967296 at big",
+                "trimmed whitespace at padded",
+                "trimmed whitespace at id",
+                "ignored attribute 'extra' on <boolean> at list[0]",
+            ]
+        );
+
+        // The plain parser accepts the same input silently.
+        assert_eq!(crate::xml::from_slice(data).unwrap(), llsd);
+    }
+}
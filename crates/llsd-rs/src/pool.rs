@@ -0,0 +1,170 @@
+//! Reuse of container allocations across messages.
+//!
+//! Decoding a message and dropping it frees every map and array it holds,
+//! and the next message allocates them again. An [`LlsdPool`] keeps the
+//! emptied containers instead: build values from [`LlsdPool::take_map`] and
+//! [`LlsdPool::take_array`], and hand finished values back with
+//! [`LlsdPool::recycle`], which empties them, nested containers included,
+//! and keeps their capacity for the next take. The pool is a plain `&mut`
+//! structure; keep one per worker thread.
+//!
+//! ```
+//! use llsd_rs::{Llsd, pool::LlsdPool};
+//!
+//! let mut pool = LlsdPool::new();
+//! for sequence in 0..3 {
+//!     let mut body = pool.take_map();
+//!     let mut ids = pool.take_array();
+//!     ids.extend((0..16).map(Llsd::Integer));
+//!     body.insert("ids".into(), Llsd::Array(ids));
+//!     body.insert("sequence".into(), Llsd::Integer(sequence));
+//!     // ... process the message ...
+//!     pool.recycle(Llsd::Map(body));
+//! }
+//! assert_eq!((pool.pooled_maps(), pool.pooled_arrays()), (1, 1));
+//! assert!(pool.take_array().capacity() >= 16);
+//! ```
+
+use crate::{Llsd, LlsdMap};
+
+/// Default for [`LlsdPool::with_max_pooled`].
+pub const DEFAULT_MAX_POOLED: usize = 64;
+
+/// Emptied maps and arrays waiting to be reused.
+#[derive(Debug, Clone)]
+pub struct LlsdPool {
+    maps: Vec<LlsdMap>,
+    arrays: Vec<Vec<Llsd>>,
+    max_pooled: usize,
+    max_capacity: usize,
+}
+
+impl LlsdPool {
+    pub fn new() -> Self {
+        Self {
+            maps: Vec::new(),
+            arrays: Vec::new(),
+            max_pooled: DEFAULT_MAX_POOLED,
+            max_capacity: usize::MAX,
+        }
+    }
+
+    /// Keep at most `max` maps and `max` arrays; containers returned past
+    /// that are freed.
+    pub fn with_max_pooled(mut self, max: usize) -> Self {
+        self.max_pooled = max;
+        self
+    }
+
+    /// Free returned containers with room for more than `max` elements
+    /// rather than keeping them, so one outsized message doesn't pin its
+    /// memory.
+    pub fn with_max_capacity(mut self, max: usize) -> Self {
+        self.max_capacity = max;
+        self
+    }
+
+    /// An empty map, reusing a returned one when there is one.
+    pub fn take_map(&mut self) -> LlsdMap {
+        self.maps.pop().unwrap_or_default()
+    }
+
+    /// An empty array, reusing a returned one when there is one.
+    pub fn take_array(&mut self) -> Vec<Llsd> {
+        self.arrays.pop().unwrap_or_default()
+    }
+
+    /// Empty `map`, recycling the containers among its values, and keep it.
+    pub fn put_map(&mut self, mut map: LlsdMap) {
+        map.drain_values(|value| self.recycle(value));
+        if self.maps.len() < self.max_pooled && map.capacity() <= self.max_capacity {
+            self.maps.push(map);
+        }
+    }
+
+    /// Empty `array`, recycling the containers among its items, and keep it.
+    pub fn put_array(&mut self, mut array: Vec<Llsd>) {
+        array.drain(..).for_each(|item| self.recycle(item));
+        if self.arrays.len() < self.max_pooled && array.capacity() <= self.max_capacity {
+            self.arrays.push(array);
+        }
+    }
+
+    /// Return the containers of a finished value; scalars are dropped.
+    pub fn recycle(&mut self, llsd: Llsd) {
+        match llsd {
+            Llsd::Map(map) => self.put_map(map),
+            Llsd::Array(array) => self.put_array(array),
+            _ => {}
+        }
+    }
+
+    pub fn pooled_maps(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn pooled_arrays(&self) -> usize {
+        self.arrays.len()
+    }
+
+    /// Free every pooled container.
+    pub fn clear(&mut self) {
+        self.maps.clear();
+        self.arrays.clear();
+    }
+}
+
+impl Default for LlsdPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{llsd_array, llsd_map};
+
+    #[test]
+    fn recycles_nested_containers() {
+        let mut pool = LlsdPool::new();
+        let doc = llsd_map! {
+            "a" => llsd_array![1, llsd_array![2, 3], llsd_map! { "b" => 4 }],
+            "c" => "text",
+        };
+        pool.recycle(doc);
+        assert_eq!((pool.pooled_maps(), pool.pooled_arrays()), (2, 2));
+
+        let map = pool.take_map();
+        assert!(map.is_empty() && map.capacity() > 0);
+        let array = pool.take_array();
+        assert!(array.is_empty() && array.capacity() >= 2);
+        pool.take_array();
+        assert_eq!(pool.take_array().capacity(), 0);
+
+        pool.recycle(Llsd::Integer(1));
+        assert_eq!(pool.pooled_arrays(), 0);
+    }
+
+    #[test]
+    fn bounds_what_it_keeps() {
+        let mut pool = LlsdPool::new().with_max_pooled(1).with_max_capacity(4);
+        pool.put_array(Vec::with_capacity(2));
+        pool.put_array(Vec::with_capacity(2));
+        assert_eq!(pool.pooled_arrays(), 1);
+        pool.clear();
+        pool.put_array(Vec::with_capacity(100));
+        assert_eq!(pool.pooled_arrays(), 0);
+
+        let mut large = LlsdMap::new();
+        for i in 0..20 {
+            large.insert(i.to_string(), Llsd::Array(vec![Llsd::Integer(i)]));
+        }
+        let mut pool = LlsdPool::new().with_max_capacity(4);
+        pool.put_map(large);
+        assert_eq!(pool.pooled_arrays(), 20);
+        // A sorted large map holds no spare capacity, so it is always kept.
+        let kept = usize::from(cfg!(feature = "sorted-map"));
+        assert_eq!(pool.pooled_maps(), kept);
+    }
+}
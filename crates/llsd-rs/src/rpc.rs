@@ -1,8 +1,14 @@
-use base64::prelude::*;
-use chrono::DateTime;
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
 use xml::{EventReader, EventWriter};
 
-use super::Llsd;
+use super::{Llsd, LlsdMap, UndefinedEntries};
+use crate::{codec::Base64Decoder, date};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum XmlRpc {
@@ -95,11 +101,76 @@ fn is_xmlrpc_int_tag(tag: &str) -> bool {
     matches!(tag, "int")
 }
 
+/// What the reader does with a member name repeated within one struct,
+/// which XML-RPC allows and some legacy grid services send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateMembers {
+    /// Keep the last value.
+    #[default]
+    Last,
+    /// Keep the first value.
+    First,
+    /// Keep every value: a repeated name maps to an array of its values in
+    /// document order, while a name that appears once keeps its value as is.
+    Collect,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    pub duplicate_members: DuplicateMembers,
+}
+
+impl ReadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_duplicate_members(mut self, duplicate_members: DuplicateMembers) -> Self {
+        self.duplicate_members = duplicate_members;
+        self
+    }
+}
+
 pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, anyhow::Error> {
+    from_parser_with_options(parser, &ReadOptions::new())
+}
+
+/// Like [`from_parser`], reading as `options` say.
+///
+/// ```
+/// use llsd_rs::{Llsd, llsd_array, rpc::{self, DuplicateMembers, ReadOptions}};
+///
+/// let xml = "<methodResponse><params><param><value><struct>\
+///     <member><name>region</name><value><string>Ahern</string></value></member>\
+///     <member><name>region</name><value><string>Morris</string></value></member>\
+///     </struct></value></param></params></methodResponse>";
+/// assert_eq!(rpc::from_str(xml).unwrap().llsd()["region"], Llsd::from("Morris"));
+///
+/// let collect = ReadOptions::new().with_duplicate_members(DuplicateMembers::Collect);
+/// let read = rpc::from_str_with_options(xml, &collect).unwrap();
+/// assert_eq!(read.llsd()["region"], llsd_array!["Ahern", "Morris"]);
+/// ```
+pub fn from_parser_with_options<R: std::io::Read>(
+    parser: EventReader<R>,
+    options: &ReadOptions,
+) -> Result<XmlRpc, anyhow::Error> {
+    from_parser_spooled(parser, options, None)
+}
+
+/// Decode from `parser`, replacing `<base64>` values that [`SpoolingReader`]
+/// marked `#n` with the `n`th of `spooled`.
+fn from_parser_spooled<R: std::io::Read>(
+    parser: EventReader<R>,
+    options: &ReadOptions,
+    spooled: Option<&RefCell<Vec<Llsd>>>,
+) -> Result<XmlRpc, anyhow::Error> {
     use xml::reader::XmlEvent;
     let mut stack: Vec<Llsd> = Vec::new();
     let mut name_stack: Vec<String> = Vec::new();
     let mut key_stack: Vec<String> = Vec::new();
+    // Per open struct, the names collected into arrays under
+    // `DuplicateMembers::Collect`.
+    let mut collected: Vec<Vec<String>> = Vec::new();
 
     let mut expect_value = Expected::XmlRpcHeader;
     let mut method = None;
@@ -135,6 +206,7 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
                     }
                     (Expected::None, "struct") => {
                         stack.push(Llsd::Map(Default::default()));
+                        collected.push(Vec::new());
                         expect_value = Expected::Member;
                     }
                     _ => {
@@ -147,11 +219,24 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
             }
             Ok(XmlEvent::Characters(data)) => {
                 let data = data.trim();
-                if expect_value == Expected::MethodCallName {
+                if name_stack.last().map(|s| s.as_str()) == Some("methodName") {
                     method = Some(data.to_string());
                 } else if name_stack.last().map(|s| s.as_str()) == Some("name") {
                     key_stack.push(data.to_string());
                 } else if let Some(llsd) = stack.last_mut() {
+                    if llsd.is_binary()
+                        && let Some(spooled) = spooled
+                        && let Some(index) = data.strip_prefix('#')
+                    {
+                        *llsd = index
+                            .parse()
+                            .ok()
+                            .and_then(|i: usize| spooled.borrow().get(i).cloned())
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("Error parsing XML-RPC: bad spool mark")
+                            })?;
+                        continue;
+                    }
                     match llsd {
                         Llsd::Boolean(_) => match data {
                             "true" => *llsd = Llsd::Boolean(true),
@@ -166,11 +251,9 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
                             }
                         },
                         &mut Llsd::String(ref mut s) => s.push_str(data),
-                        &mut Llsd::Date(ref mut d) => {
-                            *d = DateTime::parse_from_rfc3339(data)?.into()
-                        }
+                        &mut Llsd::Date(ref mut d) => *d = date::parse_rfc3339(data)?,
                         &mut Llsd::Binary(ref mut b) => {
-                            *b = BASE64_STANDARD.decode(data.as_bytes())?
+                            *b = crate::codec::base64_decode(data.as_bytes())?
                         }
                         &mut Llsd::Integer(ref mut i) => {
                             *i = crate::parse_i32_decimal_wrapping(data)?
@@ -197,6 +280,9 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
                         name.local_name
                     ));
                 }
+                if name.local_name == "struct" {
+                    collected.pop();
+                }
                 match name.local_name.as_str() {
                     "struct" | "array" if stack.len() > 1 => {
                         if let Some(parent) = stack.get(stack.len() - 2) {
@@ -211,6 +297,8 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
                             }
                         }
                     }
+                    // A closed top-level value takes no more members or items.
+                    "struct" | "array" => expect_value = Expected::None,
                     "member" => {
                         let Some(key) = key_stack.pop() else {
                             return Err(anyhow::anyhow!("Error parsing XML-RPC: missing key"));
@@ -224,7 +312,12 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
                         let Some(Llsd::Map(parent)) = stack.last_mut() else {
                             return Err(anyhow::anyhow!("Error parsing XML-RPC: not a map"));
                         };
-                        parent.insert(key.to_string(), value);
+                        let Some(collected) = collected.last_mut() else {
+                            return Err(anyhow::anyhow!(
+                                "Error parsing XML-RPC: member outside struct"
+                            ));
+                        };
+                        insert_member(parent, key, value, options, collected);
                         expect_value = Expected::Member;
                     }
                     "value" if stack.len() > 1 => {
@@ -265,6 +358,33 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<XmlRpc, a
     }
 }
 
+fn insert_member(
+    parent: &mut LlsdMap,
+    key: String,
+    value: Llsd,
+    options: &ReadOptions,
+    collected: &mut Vec<String>,
+) {
+    match options.duplicate_members {
+        DuplicateMembers::Last => {
+            parent.insert(key, value);
+        }
+        DuplicateMembers::First => {
+            parent.get_or_insert_with(key, || value);
+        }
+        DuplicateMembers::Collect => match parent.get_mut(key.as_str()) {
+            None => {
+                parent.insert(key, value);
+            }
+            Some(Llsd::Array(values)) if collected.contains(&key) => values.push(value),
+            Some(first) => {
+                *first = Llsd::Array(vec![std::mem::take(first), value]);
+                collected.push(key);
+            }
+        },
+    }
+}
+
 pub fn from_str(data: &str) -> Result<XmlRpc, anyhow::Error> {
     from_parser(EventReader::from_str(data))
 }
@@ -277,35 +397,373 @@ pub fn from_slice(data: &[u8]) -> Result<XmlRpc, anyhow::Error> {
     from_parser(EventReader::new(std::io::Cursor::new(data)))
 }
 
+pub fn from_str_with_options(data: &str, options: &ReadOptions) -> Result<XmlRpc, anyhow::Error> {
+    from_parser_with_options(EventReader::from_str(data), options)
+}
+
+pub fn from_reader_with_options<R: std::io::Read>(
+    reader: R,
+    options: &ReadOptions,
+) -> Result<XmlRpc, anyhow::Error> {
+    from_parser_with_options(EventReader::new(reader), options)
+}
+
+pub fn from_slice_with_options(
+    data: &[u8],
+    options: &ReadOptions,
+) -> Result<XmlRpc, anyhow::Error> {
+    from_parser_with_options(EventReader::new(std::io::Cursor::new(data)), options)
+}
+
+/// Storage for the `<base64>` values [`from_reader_spooled`] keeps out of
+/// memory.
+pub trait Spool {
+    /// Open storage for the next value, returning where its decoded bytes go
+    /// and the value to leave in the document in its place.
+    fn create(&mut self) -> io::Result<(Box<dyn Write + '_>, Llsd)>;
+}
+
+/// Spools each value to a new file in a directory, leaving its `file://`
+/// URI in the document. The files are the caller's to move or delete.
+#[derive(Debug)]
+pub struct DirSpool {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl DirSpool {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            files: Vec::new(),
+        }
+    }
+
+    /// The files written so far, in document order.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+impl Spool for DirSpool {
+    fn create(&mut self) -> io::Result<(Box<dyn Write + '_>, Llsd)> {
+        let path = std::path::absolute(self.dir.join(format!(
+            "rpc-{}-{:x}-{}.bin",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            self.files.len()
+        )))?;
+        let url = url::Url::from_file_path(&path).map_err(|()| {
+            io::Error::new(io::ErrorKind::InvalidInput, "spool path is not a file URL")
+        })?;
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        self.files.push(path);
+        Ok((Box::new(BufWriter::new(file)), Llsd::Uri(url.into())))
+    }
+}
+
+/// Like [`from_reader`], writing `<base64>` values of more than `threshold`
+/// characters to `spool` as they are read, so a large upload response is
+/// never held in memory whole; smaller values are decoded as usual.
+///
+/// Values are found by their literal `<base64>` tag ahead of the XML parser,
+/// so one spelled otherwise (`<base64 >`, a namespace prefix) is decoded in
+/// memory.
+///
+/// ```
+/// use llsd_rs::{Llsd, rpc::{self, Spool, XmlRpc}};
+///
+/// #[derive(Default)]
+/// struct Chunks(Vec<Vec<u8>>);
+///
+/// impl Spool for Chunks {
+///     fn create(&mut self) -> std::io::Result<(Box<dyn std::io::Write + '_>, Llsd)> {
+///         self.0.push(Vec::new());
+///         let index = self.0.len() as i32 - 1;
+///         Ok((Box::new(self.0.last_mut().unwrap()), Llsd::Integer(index)))
+///     }
+/// }
+///
+/// let response = XmlRpc::new_method_response(Llsd::Binary(vec![7; 1000]));
+/// let xml = rpc::to_string(&response).unwrap();
+/// let mut chunks = Chunks::default();
+/// let read = rpc::from_reader_spooled(xml.as_bytes(), 64, &mut chunks).unwrap();
+/// assert_eq!(read.llsd(), &Llsd::Integer(0));
+/// assert_eq!(chunks.0[0], vec![7; 1000]);
+/// ```
+pub fn from_reader_spooled<R: Read>(
+    reader: R,
+    threshold: usize,
+    spool: &mut impl Spool,
+) -> Result<XmlRpc, anyhow::Error> {
+    let spooled = RefCell::new(Vec::new());
+    let reader = SpoolingReader {
+        inner: BufReader::new(reader),
+        spool,
+        threshold,
+        spooled: &spooled,
+        out: Vec::new(),
+        pos: 0,
+        matched: 0,
+    };
+    from_parser_spooled(
+        EventReader::new(reader),
+        &ReadOptions::new(),
+        Some(&spooled),
+    )
+}
+
+const BASE64_TAG: &[u8] = b"<base64>";
+
+/// Passes a document through to the XML parser, decoding the contents of
+/// long `<base64>` elements into a [`Spool`] on the way and putting a `#n`
+/// mark, which can't be base64, in their place.
+struct SpoolingReader<'a, R> {
+    inner: BufReader<R>,
+    spool: &'a mut dyn Spool,
+    threshold: usize,
+    spooled: &'a RefCell<Vec<Llsd>>,
+    /// Bytes for the parser, read up to `pos`.
+    out: Vec<u8>,
+    pos: usize,
+    /// How much of [`BASE64_TAG`] the input last ended with.
+    matched: usize,
+}
+
+impl<R: Read> SpoolingReader<'_, R> {
+    /// Move input to `out` up to the end of the next `<base64>` tag and
+    /// handle its contents, or all buffered input when there is none.
+    fn fill(&mut self) -> io::Result<()> {
+        let available = self.inner.fill_buf()?;
+        let mut end = None;
+        for (i, &b) in available.iter().enumerate() {
+            self.matched = if b == BASE64_TAG[self.matched] {
+                self.matched + 1
+            } else {
+                usize::from(b == b'<')
+            };
+            if self.matched == BASE64_TAG.len() {
+                end = Some(i + 1);
+                break;
+            }
+        }
+        let consumed = end.unwrap_or(available.len());
+        self.out.extend_from_slice(&available[..consumed]);
+        self.inner.consume(consumed);
+        if end.is_some() {
+            self.matched = 0;
+            self.contents()?;
+        }
+        Ok(())
+    }
+
+    /// Read the text of the `<base64>` element just opened, up to its `<`.
+    fn contents(&mut self) -> io::Result<()> {
+        let invalid = |e: anyhow::Error| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+        // Held until it turns out longer than the threshold.
+        let mut text = Vec::new();
+        loop {
+            let available = self.inner.fill_buf()?;
+            let eof = available.is_empty();
+            let room = self.threshold.saturating_add(1) - text.len();
+            let end = available.iter().take(room).position(|&b| b == b'<');
+            let take = end.unwrap_or(available.len().min(room));
+            text.extend_from_slice(&available[..take]);
+            self.inner.consume(take);
+            if end.is_some() || eof {
+                if text.contains(&b'#') {
+                    return Err(invalid(anyhow::anyhow!("invalid base64 data")));
+                }
+                self.out.extend_from_slice(&text);
+                return Ok(());
+            }
+            if text.len() > self.threshold {
+                break;
+            }
+        }
+
+        let (mut writer, placeholder) = self.spool.create()?;
+        let mut decoder = Base64Decoder::new(usize::MAX);
+        let mut decoded = Vec::new();
+        decoder.push(&text, &mut decoded).map_err(invalid)?;
+        loop {
+            writer.write_all(&decoded)?;
+            decoded.clear();
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let end = available.iter().position(|&b| b == b'<');
+            let take = end.unwrap_or(available.len());
+            decoder
+                .push(&available[..take], &mut decoded)
+                .map_err(invalid)?;
+            self.inner.consume(take);
+            if end.is_some() {
+                break;
+            }
+        }
+        decoder.finish(&mut decoded).map_err(invalid)?;
+        writer.write_all(&decoded)?;
+        writer.flush()?;
+        drop(writer);
+        let mut spooled = self.spooled.borrow_mut();
+        write!(self.out, "#{}", spooled.len())?;
+        spooled.push(placeholder);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for SpoolingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.out.len() {
+            self.out.clear();
+            self.pos = 0;
+            self.fill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// What the writer does with text XML can't carry as is: control characters
+/// other than tab, newline and carriage return, and U+FFFE and U+FFFF.
+///
+/// Carriage returns are always written as `&#xD;`, since parsers turn bare
+/// ones into newlines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IllegalChars {
+    /// Fail the write.
+    #[default]
+    Reject,
+    /// Write a string holding any as `<base64>` of its UTF-8 bytes, so it
+    /// reads back as a binary. Method names and member names are still
+    /// rejected.
+    Base64,
+    /// Write them as numeric character references (`&#x1;`) in an XML 1.1
+    /// document. NUL, U+FFFE and U+FFFF have no reference and are still
+    /// rejected.
+    CharRef,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    pub illegal_chars: IllegalChars,
+    /// Whether struct members whose value is undefined are written as
+    /// `<nil />` or left out, for servers without the `nil` extension.
+    pub undefined: UndefinedEntries,
+}
+
+impl WriteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_illegal_chars(mut self, illegal_chars: IllegalChars) -> Self {
+        self.illegal_chars = illegal_chars;
+        self
+    }
+
+    pub fn with_undefined_entries(mut self, undefined: UndefinedEntries) -> Self {
+        self.undefined = undefined;
+        self
+    }
+}
+
+/// Characters written as references under [`IllegalChars::CharRef`]: those
+/// an XML 1.1 document may only hold as references, and its line ends.
+fn needs_reference(c: char) -> bool {
+    !xml::common::is_xml11_char_not_restricted(c) || matches!(c, '\r' | '\u{85}' | '\u{2028}')
+}
+
+/// The first character of `text` that needs more than PCDATA escaping.
+fn special_char(text: &str, chars: IllegalChars) -> Option<(usize, char)> {
+    text.char_indices().find(|&(_, c)| {
+        c == '\r'
+            || !xml::common::is_xml10_char(c)
+            || (chars == IllegalChars::CharRef && needs_reference(c))
+    })
+}
+
+fn can_write(text: &str, chars: IllegalChars) -> bool {
+    special_char(text, chars).is_none_or(|(_, c)| {
+        c == '\r' || (chars == IllegalChars::CharRef && xml::common::is_xml11_char(c))
+    })
+}
+
+/// Write `text` as character data, with references for what
+/// [`special_char`] finds.
+fn text<W: std::io::Write>(
+    w: &mut EventWriter<W>,
+    mut text: &str,
+    chars: IllegalChars,
+) -> Result<(), anyhow::Error> {
+    use xml::writer::XmlEvent;
+    if !can_write(text, chars) {
+        let (_, c) = special_char(text, chars).unwrap_or_default();
+        return Err(anyhow::anyhow!(
+            "Error writing XML-RPC: U+{:04X} can't be written in XML",
+            c as u32
+        ));
+    }
+    while let Some((i, c)) = special_char(text, chars) {
+        if i > 0 {
+            w.write(XmlEvent::characters(&text[..i]))?;
+        }
+        w.write(XmlEvent::raw_characters(&format!("&#x{:X};", c as u32)))?;
+        text = &text[i + c.len_utf8()..];
+    }
+    if !text.is_empty() {
+        w.write(XmlEvent::characters(text))?;
+    }
+    Ok(())
+}
+
+fn tag<W: std::io::Write>(
+    w: &mut EventWriter<W>,
+    tag: &str,
+    content: &str,
+    chars: IllegalChars,
+) -> Result<(), anyhow::Error> {
+    use xml::writer::XmlEvent;
+    w.write(XmlEvent::start_element(tag))?;
+    text(w, content, chars)?;
+    w.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
 fn write_inner<W: std::io::Write>(
     llsd: &Llsd,
     w: &mut EventWriter<W>,
+    options: &WriteOptions,
 ) -> Result<(), anyhow::Error> {
     use xml::writer::XmlEvent;
-    let tag = |w: &mut EventWriter<W>, tag, text: &str| -> Result<(), anyhow::Error> {
-        w.write(XmlEvent::start_element(tag))?;
-        if !text.is_empty() {
-            w.write(XmlEvent::characters(text))?;
-        }
-        w.write(XmlEvent::end_element())?;
-        Ok(())
-    };
+    let chars = options.illegal_chars;
+    let tag = |w: &mut EventWriter<W>, name, content: &str| tag(w, name, content, chars);
     match llsd {
         Llsd::Undefined => tag(w, "nil", ""),
         Llsd::Boolean(b) => tag(w, "boolean", if *b { "1" } else { "0" }),
-        Llsd::Integer(i) => tag(w, "int", &i.to_string()),
-        Llsd::Real(r) => tag(w, "double", &r.to_string()),
+        Llsd::Integer(i) => tag(w, "int", itoa::Buffer::new().format(*i)),
+        Llsd::Real(r) => tag(w, "double", ryu::Buffer::new().format(*r)),
+        Llsd::String(s) if chars == IllegalChars::Base64 && !can_write(s, chars) => {
+            tag(w, "base64", &crate::codec::base64_encode(s.as_bytes()))
+        }
         Llsd::String(s) => tag(w, "string", s),
         Llsd::Uri(u) => tag(w, "string", u.as_str()),
         Llsd::Uuid(u) => tag(w, "string", &u.to_string()),
         Llsd::Date(d) => tag(w, "dateTime.iso8601", &d.to_rfc3339()),
-        Llsd::Binary(b) => tag(w, "base64", &BASE64_STANDARD.encode(b)),
+        Llsd::Binary(b) => tag(w, "base64", &crate::codec::base64_encode(b)),
         Llsd::Array(a) => {
             w.write(XmlEvent::start_element("array"))?;
             w.write(XmlEvent::start_element("data"))?;
             for llsd in a {
                 w.write(XmlEvent::start_element("value"))?;
-                write_inner(llsd, w)?;
+                write_inner(llsd, w, options)?;
                 w.write(XmlEvent::end_element())?;
             }
             w.write(XmlEvent::end_element())?;
@@ -315,10 +773,13 @@ fn write_inner<W: std::io::Write>(
         Llsd::Map(m) => {
             w.write(XmlEvent::start_element("struct"))?;
             for (k, v) in m {
+                if v.is_undefined() && options.undefined == UndefinedEntries::Drop {
+                    continue;
+                }
                 w.write(XmlEvent::start_element("member"))?;
                 tag(w, "name", k)?;
                 w.write(XmlEvent::start_element("value"))?;
-                write_inner(v, w)?;
+                write_inner(v, w, options)?;
                 w.write(XmlEvent::end_element())?;
                 w.write(XmlEvent::end_element())?;
             }
@@ -328,14 +789,48 @@ fn write_inner<W: std::io::Write>(
     }
 }
 
+/// Whether writing `rpc` with [`IllegalChars::CharRef`] references
+/// characters only XML 1.1 allows.
+fn needs_xml11(rpc: &XmlRpc) -> bool {
+    let outside_xml10 = |s: &str| s.chars().any(|c| !xml::common::is_xml10_char(c));
+    let mut stack = vec![rpc.llsd()];
+    let mut found = rpc.method().is_some_and(outside_xml10);
+    while let Some(llsd) = stack.pop()
+        && !found
+    {
+        match llsd {
+            Llsd::String(s) => found = outside_xml10(s),
+            Llsd::Array(a) => stack.extend(a),
+            Llsd::Map(m) => {
+                found = m.keys().any(|k| outside_xml10(k));
+                stack.extend(m.values());
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
 pub fn write<W: std::io::Write>(rpc: &XmlRpc, w: &mut EventWriter<W>) -> Result<(), anyhow::Error> {
+    write_with_options(rpc, w, &WriteOptions::new())
+}
+
+/// Like [`write`], handling characters XML can't carry as `options` say.
+///
+/// References written for [`IllegalChars::CharRef`] to control characters
+/// are only well-formed in an XML 1.1 document, which `w` must have been
+/// started as; [`to_string_with_options`] does that when needed.
+pub fn write_with_options<W: std::io::Write>(
+    rpc: &XmlRpc,
+    w: &mut EventWriter<W>,
+    options: &WriteOptions,
+) -> Result<(), anyhow::Error> {
     use xml::writer::XmlEvent;
+    let chars = options.illegal_chars;
     match rpc {
         XmlRpc::MethodCall(method, _) => {
             w.write(XmlEvent::start_element("methodCall"))?;
-            w.write(XmlEvent::start_element("methodName"))?;
-            w.write(XmlEvent::characters(method))?;
-            w.write(XmlEvent::end_element())?;
+            tag(w, "methodName", method, chars)?;
         }
         XmlRpc::MethodResponse(_) => {
             w.write(XmlEvent::start_element("methodResponse"))?;
@@ -344,7 +839,7 @@ pub fn write<W: std::io::Write>(rpc: &XmlRpc, w: &mut EventWriter<W>) -> Result<
     w.write(XmlEvent::start_element("params"))?;
     w.write(XmlEvent::start_element("param"))?;
     w.write(XmlEvent::start_element("value"))?;
-    write_inner(rpc.as_ref(), w)?;
+    write_inner(rpc.as_ref(), w, options)?;
     w.write(XmlEvent::end_element())?;
     w.write(XmlEvent::end_element())?;
     w.write(XmlEvent::end_element())?;
@@ -353,8 +848,23 @@ pub fn write<W: std::io::Write>(rpc: &XmlRpc, w: &mut EventWriter<W>) -> Result<
 }
 
 pub fn to_string(rpc: &XmlRpc) -> Result<String, anyhow::Error> {
+    to_string_with_options(rpc, &WriteOptions::new())
+}
+
+pub fn to_string_with_options(
+    rpc: &XmlRpc,
+    options: &WriteOptions,
+) -> Result<String, anyhow::Error> {
     let mut buf = Vec::new();
-    write(rpc, &mut EventWriter::new(&mut buf))?;
+    let mut w = EventWriter::new(&mut buf);
+    if options.illegal_chars == IllegalChars::CharRef && needs_xml11(rpc) {
+        w.write(xml::writer::XmlEvent::StartDocument {
+            version: xml::common::XmlVersion::Version11,
+            encoding: Some("UTF-8"),
+            standalone: None,
+        })?;
+    }
+    write_with_options(rpc, &mut w, options)?;
     Ok(String::from_utf8(buf)?)
 }
 
@@ -373,10 +883,9 @@ pub fn to_pretty_string(rpc: &XmlRpc) -> Result<String, anyhow::Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{TimeZone, Utc};
-    use std::collections::HashMap;
+    use crate::Date;
+    use crate::Uuid;
     use url::Url;
-    use uuid::Uuid;
 
     fn round_trip(llsd: Llsd) {
         trip(llsd.clone(), llsd);
@@ -457,6 +966,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rejects_members_after_a_closed_struct() {
+        let xml = "<methodResponse><params><param><value><struct></struct>\
+            <member><name>k</name><value><int>1</int></value></member>\
+            </value></param></params></methodResponse>";
+        let err = from_str(xml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error parsing XML-RPC: unexpected element member"
+        );
+    }
+
     #[test]
     fn real() {
         round_trip(Llsd::Real(13.1415));
@@ -481,7 +1002,7 @@ mod tests {
 
     #[test]
     fn date() {
-        let dt = Utc.timestamp_opt(1_620_000_000, 0).unwrap();
+        let dt = Date::from_timestamp(1_620_000_000, 0).unwrap();
         round_trip(Llsd::Date(dt));
     }
 
@@ -502,10 +1023,179 @@ mod tests {
 
     #[test]
     fn map() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert("answer".into(), Llsd::Integer(42));
         map.insert("pi".into(), Llsd::Real(13.14));
         map.insert("greeting".into(), Llsd::String("hello".into()));
         round_trip(Llsd::Map(map));
     }
+
+    #[test]
+    fn reals_round_trip_exactly() {
+        for v in [
+            0.1,
+            -0.0,
+            1.0 / 3.0,
+            1e21,
+            1e-7,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let encoded = to_string(&XmlRpc::new_method_response(Llsd::Real(v))).unwrap();
+            let decoded = from_str(&encoded).unwrap();
+            assert_eq!(
+                decoded.llsd().as_real().map(|r| r.to_bits()),
+                Some(v.to_bits())
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_markup_and_carriage_returns() {
+        let mut map = LlsdMap::new();
+        map.insert("<&>".into(), Llsd::String("a]]>&<b\r\nc".into()));
+        let call = XmlRpc::new_method_call("x<y&z".into(), Llsd::Map(map));
+        let encoded = to_string(&call).unwrap();
+        assert!(encoded.contains("<methodName>x&lt;y&amp;z</methodName>"));
+        assert!(encoded.contains("&#xD;"));
+        assert_eq!(from_str(&encoded).unwrap(), call);
+    }
+
+    #[test]
+    fn illegal_chars() {
+        let text = Llsd::String("a\u{1}b\u{85}c".into());
+        let response = XmlRpc::new_method_response(text.clone());
+        assert!(to_string(&response).is_err());
+        assert!(to_string(&XmlRpc::new_method_call("a\u{0}".into(), Llsd::Undefined)).is_err());
+
+        let base64 = WriteOptions::new().with_illegal_chars(IllegalChars::Base64);
+        let encoded = to_string_with_options(&response, &base64).unwrap();
+        assert_eq!(
+            from_str(&encoded).unwrap().llsd(),
+            &Llsd::Binary("a\u{1}b\u{85}c".into())
+        );
+        let key = Llsd::map().insert("\u{1}", 1).unwrap();
+        assert!(to_string_with_options(&XmlRpc::new_method_response(key), &base64).is_err());
+
+        let refs = WriteOptions::new().with_illegal_chars(IllegalChars::CharRef);
+        let encoded = to_string_with_options(&response, &refs).unwrap();
+        assert!(encoded.starts_with("<?xml version=\"1.1\""));
+        assert!(encoded.contains("a&#x1;b&#x85;c"));
+        assert_eq!(from_str(&encoded).unwrap(), response);
+        let plain = XmlRpc::new_method_response(Llsd::String("\u{85}".into()));
+        assert!(
+            to_string_with_options(&plain, &refs)
+                .unwrap()
+                .starts_with("<?xml version=\"1.0\"")
+        );
+        let nul = XmlRpc::new_method_response(Llsd::String("\u{0}".into()));
+        assert!(to_string_with_options(&nul, &refs).is_err());
+    }
+
+    /// Hands out a few bytes per read, to split tags and text everywhere.
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.1).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn spools_large_base64_to_files() {
+        let large: Vec<u8> = (0..50_000).map(|i| (i * 31 % 251) as u8).collect();
+        let mut map = LlsdMap::new();
+        map.insert("asset".into(), Llsd::Binary(large.clone()));
+        map.insert("icon".into(), Llsd::Binary(vec![1, 2, 3]));
+        map.insert("empty".into(), Llsd::Binary(Vec::new()));
+        map.insert("name".into(), Llsd::String("<base64>".into()));
+        let response = XmlRpc::new_method_response(Llsd::Map(map.clone()));
+        let xml = to_pretty_string(&response).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("llsd-rs-rpc-spool-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for piece in [1, 7, 8192] {
+            let mut spool = DirSpool::new(&dir);
+            let read = from_reader_spooled(Trickle(xml.as_bytes(), piece), 64, &mut spool).unwrap();
+            let [file] = spool.files() else {
+                panic!("{:?}", spool.files());
+            };
+            assert_eq!(std::fs::read(file).unwrap(), large);
+            let mut expected = map.clone();
+            expected.insert(
+                "asset".into(),
+                Llsd::Uri(url::Url::from_file_path(file).unwrap().into()),
+            );
+            assert_eq!(read.llsd(), &Llsd::Map(expected));
+            std::fs::remove_file(file).unwrap();
+        }
+
+        let mut spool = DirSpool::new(&dir);
+        let broken = xml.replacen("<base64>", "<base64>!", 1);
+        assert!(from_reader_spooled(broken.as_bytes(), 64, &mut spool).is_err());
+        let forged = "<methodResponse><params><param><value><base64>#0</base64></value></param></params></methodResponse>";
+        assert!(from_reader_spooled(forged.as_bytes(), 64, &mut spool).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_members() {
+        let member = |name: &str, value: &str| {
+            format!("<member><name>{name}</name><value>{value}</value></member>")
+        };
+        let xml = format!(
+            "<methodResponse><params><param><value><struct>{}{}{}{}{}{}</struct></value>\
+             </param></params></methodResponse>",
+            member("a", "<int>1</int>"),
+            member(
+                "list",
+                "<array><data><value><int>1</int></value></data></array>"
+            ),
+            member("a", "<int>2</int>"),
+            member(
+                "inner",
+                &format!(
+                    "<struct>{}{}</struct>",
+                    member("b", "<int>3</int>"),
+                    member("b", "<int>4</int>")
+                )
+            ),
+            member("a", "<int>5</int>"),
+            member("list", "<array><data></data></array>"),
+        );
+        let read = |duplicates| {
+            let options = ReadOptions::new().with_duplicate_members(duplicates);
+            Llsd::from(from_str_with_options(&xml, &options).unwrap())
+        };
+
+        let last = read(DuplicateMembers::Last);
+        assert_eq!(last, Llsd::from(from_str(&xml).unwrap()));
+        assert_eq!(last["a"], Llsd::Integer(5));
+        assert_eq!(last["inner"]["b"], Llsd::Integer(4));
+        assert_eq!(last["list"], Llsd::Array(vec![]));
+
+        let first = read(DuplicateMembers::First);
+        assert_eq!(first["a"], Llsd::Integer(1));
+        assert_eq!(first["inner"]["b"], Llsd::Integer(3));
+
+        let all = read(DuplicateMembers::Collect);
+        let ints =
+            |values: &[i32]| Llsd::Array(values.iter().copied().map(Llsd::Integer).collect());
+        assert_eq!(all["a"], ints(&[1, 2, 5]));
+        assert_eq!(all["inner"]["b"], ints(&[3, 4]));
+        assert_eq!(all["list"], Llsd::Array(vec![ints(&[1]), ints(&[])]));
+    }
+
+    #[test]
+    fn method_call_keeps_its_name() {
+        let call = XmlRpc::new_method_call("login_to_simulator".into(), Llsd::Integer(1));
+        let decoded = from_str(&to_string(&call).unwrap()).unwrap();
+        assert_eq!(decoded, call);
+    }
 }
@@ -0,0 +1,198 @@
+//! Borrowed scalars and value types, for hot paths that look at the start
+//! of an encoded message without decoding it: see
+//! [`binary::read_scalar`](crate::binary::read_scalar),
+//! [`binary::read_key`](crate::binary::read_key) and the `peek_type`
+//! functions of [`binary`](crate::binary::peek_type) and
+//! [`notation`](crate::notation::peek_type).
+//!
+//! ```
+//! use llsd_rs::{Llsd, LlsdMap, binary, scalar::{LlsdType, Scalar}};
+//!
+//! let mut body = LlsdMap::new();
+//! body.insert("message".into(), Llsd::String("TeleportFinish".into()));
+//! let data = binary::to_vec(&Llsd::Map(body)).unwrap();
+//!
+//! assert_eq!(binary::peek_type(&data), Some(LlsdType::Map));
+//! let (key, used) = binary::read_key(&data[5..]).unwrap();
+//! assert_eq!(key, "message");
+//! let (value, _) = binary::read_scalar(&data[5 + used..]).unwrap();
+//! assert_eq!(value.as_str(), Some("TeleportFinish"));
+//! ```
+
+use std::borrow::Cow;
+
+use crate::Uuid;
+
+use crate::{Date, Llsd, Uri, autodetect::LlsdEncoding};
+
+/// The type of an LLSD value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LlsdType {
+    Undefined,
+    Boolean,
+    Integer,
+    Real,
+    String,
+    Uri,
+    Uuid,
+    Date,
+    Binary,
+    Array,
+    Map,
+}
+
+impl LlsdType {
+    /// Same names as [`Llsd::type_name`].
+    pub fn name(self) -> &'static str {
+        match self {
+            LlsdType::Undefined => "undef",
+            LlsdType::Boolean => "boolean",
+            LlsdType::Integer => "integer",
+            LlsdType::Real => "real",
+            LlsdType::String => "string",
+            LlsdType::Uri => "uri",
+            LlsdType::Uuid => "uuid",
+            LlsdType::Date => "date",
+            LlsdType::Binary => "binary",
+            LlsdType::Array => "array",
+            LlsdType::Map => "map",
+        }
+    }
+
+    pub fn of(llsd: &Llsd) -> Self {
+        match llsd {
+            Llsd::Undefined => LlsdType::Undefined,
+            Llsd::Boolean(_) => LlsdType::Boolean,
+            Llsd::Integer(_) => LlsdType::Integer,
+            Llsd::Real(_) => LlsdType::Real,
+            Llsd::String(_) => LlsdType::String,
+            Llsd::Uri(_) => LlsdType::Uri,
+            Llsd::Uuid(_) => LlsdType::Uuid,
+            Llsd::Date(_) => LlsdType::Date,
+            Llsd::Binary(_) => LlsdType::Binary,
+            Llsd::Array(_) => LlsdType::Array,
+            Llsd::Map(_) => LlsdType::Map,
+        }
+    }
+
+    /// The type of the value that starts with `tag` in `encoding`; XML has
+    /// no tags.
+    pub(crate) fn from_tag(encoding: LlsdEncoding, tag: u8) -> Option<Self> {
+        let ty = match (encoding, tag) {
+            (LlsdEncoding::Xml, _) => return None,
+            (_, b'!') => LlsdType::Undefined,
+            (_, b'0' | b'1') => LlsdType::Boolean,
+            (_, b'[') => LlsdType::Array,
+            (_, b'{') => LlsdType::Map,
+            (_, b'\'' | b'"' | b's') => LlsdType::String,
+            (LlsdEncoding::Binary, b'i') => LlsdType::Integer,
+            (LlsdEncoding::Binary, b'r') => LlsdType::Real,
+            (LlsdEncoding::Binary, b'u') => LlsdType::Uuid,
+            (LlsdEncoding::Binary, b'l') => LlsdType::Uri,
+            (LlsdEncoding::Binary, b'd') => LlsdType::Date,
+            (LlsdEncoding::Binary, b'b') => LlsdType::Binary,
+            (LlsdEncoding::Notation, b't' | b'T' | b'f' | b'F') => LlsdType::Boolean,
+            (LlsdEncoding::Notation, b'i' | b'I') => LlsdType::Integer,
+            (LlsdEncoding::Notation, b'r' | b'R') => LlsdType::Real,
+            (LlsdEncoding::Notation, b'u' | b'U') => LlsdType::Uuid,
+            (LlsdEncoding::Notation, b'l' | b'L') => LlsdType::Uri,
+            (LlsdEncoding::Notation, b'd' | b'D') => LlsdType::Date,
+            (LlsdEncoding::Notation, b'b' | b'B') => LlsdType::Binary,
+            _ => return None,
+        };
+        Some(ty)
+    }
+}
+
+/// A scalar read in place. Strings, URIs and binaries borrow the input;
+/// only a quoted string with escapes is copied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar<'a> {
+    Undefined,
+    Boolean(bool),
+    Integer(i32),
+    Real(f64),
+    String(Cow<'a, str>),
+    Uri(&'a str),
+    Uuid(Uuid),
+    Date(Date),
+    Binary(&'a [u8]),
+}
+
+impl Scalar<'_> {
+    pub fn llsd_type(&self) -> LlsdType {
+        match self {
+            Scalar::Undefined => LlsdType::Undefined,
+            Scalar::Boolean(_) => LlsdType::Boolean,
+            Scalar::Integer(_) => LlsdType::Integer,
+            Scalar::Real(_) => LlsdType::Real,
+            Scalar::String(_) => LlsdType::String,
+            Scalar::Uri(_) => LlsdType::Uri,
+            Scalar::Uuid(_) => LlsdType::Uuid,
+            Scalar::Date(_) => LlsdType::Date,
+            Scalar::Binary(_) => LlsdType::Binary,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Scalar::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn to_llsd(&self) -> Llsd {
+        match self {
+            Scalar::Undefined => Llsd::Undefined,
+            Scalar::Boolean(v) => Llsd::Boolean(*v),
+            Scalar::Integer(v) => Llsd::Integer(*v),
+            Scalar::Real(v) => Llsd::Real(*v),
+            Scalar::String(v) => Llsd::String(v.to_string()),
+            Scalar::Uri(v) => Llsd::Uri(Uri::parse(v)),
+            Scalar::Uuid(v) => Llsd::Uuid(*v),
+            Scalar::Date(v) => Llsd::Date(*v),
+            Scalar::Binary(v) => Llsd::Binary(v.to_vec()),
+        }
+    }
+}
+
+impl From<Scalar<'_>> for Llsd {
+    fn from(scalar: Scalar<'_>) -> Self {
+        match scalar {
+            Scalar::String(v) => Llsd::String(v.into_owned()),
+            scalar => scalar.to_llsd(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn types_match_llsd() {
+        for llsd in [
+            Llsd::Undefined,
+            Llsd::Boolean(true),
+            Llsd::Integer(1),
+            Llsd::Real(1.0),
+            Llsd::String(String::new()),
+            Llsd::Uri(Uri::new()),
+            Llsd::Uuid(Uuid::nil()),
+            Llsd::Date(Date::default()),
+            Llsd::Binary(Vec::new()),
+            Llsd::Array(Vec::new()),
+            Llsd::Map(crate::LlsdMap::new()),
+        ] {
+            assert_eq!(LlsdType::of(&llsd).name(), llsd.type_name());
+        }
+        assert_eq!(
+            Llsd::from(Scalar::String("x".into())),
+            Llsd::String("x".into())
+        );
+        assert_eq!(
+            Scalar::Binary(b"ab").to_llsd(),
+            Llsd::Binary(b"ab".to_vec())
+        );
+    }
+}
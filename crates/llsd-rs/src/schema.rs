@@ -0,0 +1,311 @@
+//! Message shapes: what a document is expected to hold.
+//!
+//! A [`Schema`] describes a value by type, with maps listing their keys.
+//! [`Schema::validate`] checks a document against one, and [`sample`] makes
+//! random documents that conform, for load testing servers and
+//! property-testing handlers against realistic messages rather than
+//! arbitrary values.
+//!
+//! ```
+//! use llsd_rs::{corpus::{CorpusOptions, Generator}, schema::{self, Schema}};
+//!
+//! let update = Schema::map()
+//!     .field("AgentID", Schema::Uuid)
+//!     .field("Position", Schema::array(Schema::Real))
+//!     .optional("Flags", Schema::Integer);
+//! let mut rng = Generator::new(CorpusOptions::new(1451));
+//! for _ in 0..10 {
+//!     let doc = schema::sample(&update, &mut rng);
+//!     update.validate(&doc).unwrap();
+//! }
+//! ```
+
+use crate::{
+    Llsd, LlsdError, LlsdMap,
+    corpus::Generator,
+    debug::{ERROR_DEPTH, ERROR_ITEMS},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Any value at all.
+    Any,
+    Undefined,
+    Boolean,
+    Integer,
+    Real,
+    String,
+    Uuid,
+    Date,
+    Uri,
+    Binary,
+    /// Exactly this value, such as a message name.
+    Literal(Llsd),
+    /// An array of any length whose items all match.
+    Array(Box<Schema>),
+    /// A map with these keys; others are allowed and ignored.
+    Map(Vec<Field>),
+    /// A map with any keys whose values all match.
+    Dict(Box<Schema>),
+    /// Any one of these.
+    OneOf(Vec<Schema>),
+}
+
+/// An entry of a [`Schema::Map`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub key: String,
+    pub schema: Schema,
+    pub required: bool,
+}
+
+impl Schema {
+    /// A map without fields yet; add them with [`field`](Self::field) and
+    /// [`optional`](Self::optional).
+    pub fn map() -> Self {
+        Schema::Map(Vec::new())
+    }
+
+    pub fn array(items: Schema) -> Self {
+        Schema::Array(Box::new(items))
+    }
+
+    pub fn dict(values: Schema) -> Self {
+        Schema::Dict(Box::new(values))
+    }
+
+    /// Add a required field to a map schema.
+    ///
+    /// # Panics
+    /// If `self` isn't a [`Schema::Map`].
+    pub fn field(self, key: impl Into<String>, schema: Schema) -> Self {
+        self.with_field(key.into(), schema, true)
+    }
+
+    /// Add a field that may be missing to a map schema.
+    ///
+    /// # Panics
+    /// If `self` isn't a [`Schema::Map`].
+    pub fn optional(self, key: impl Into<String>, schema: Schema) -> Self {
+        self.with_field(key.into(), schema, false)
+    }
+
+    fn with_field(self, key: String, schema: Schema, required: bool) -> Self {
+        let Schema::Map(mut fields) = self else {
+            panic!("fields can only be added to Schema::Map");
+        };
+        fields.push(Field {
+            key,
+            schema,
+            required,
+        });
+        Schema::Map(fields)
+    }
+
+    /// Check `llsd` against this schema, reporting the first mismatch with
+    /// its path.
+    pub fn validate(&self, llsd: &Llsd) -> Result<(), LlsdError> {
+        let expect = |ok: bool, expected: &'static str| {
+            if ok {
+                Ok(())
+            } else {
+                Err(LlsdError::invalid_type(expected, llsd))
+            }
+        };
+        match self {
+            Schema::Any => Ok(()),
+            Schema::Undefined => expect(llsd.is_undefined(), "undefined"),
+            Schema::Boolean => expect(llsd.is_boolean(), "boolean"),
+            Schema::Integer => expect(llsd.is_integer(), "integer"),
+            Schema::Real => expect(llsd.is_real(), "real"),
+            Schema::String => expect(llsd.is_string(), "string"),
+            Schema::Uuid => expect(llsd.is_uuid(), "uuid"),
+            Schema::Date => expect(llsd.is_date(), "date"),
+            Schema::Uri => expect(llsd.is_uri(), "uri"),
+            Schema::Binary => expect(llsd.is_binary(), "binary"),
+            Schema::Literal(value) if value == llsd => Ok(()),
+            Schema::Literal(value) => Err(LlsdError::validation(format_args!(
+                "expected {}, found {}",
+                value.debug_limited(ERROR_DEPTH, ERROR_ITEMS),
+                llsd.debug_limited(ERROR_DEPTH, ERROR_ITEMS)
+            ))),
+            Schema::Array(items) => {
+                let Llsd::Array(array) = llsd else {
+                    return Err(LlsdError::invalid_type("array", llsd));
+                };
+                for (i, item) in array.iter().enumerate() {
+                    items.validate(item).map_err(|e| e.with_index(i))?;
+                }
+                Ok(())
+            }
+            Schema::Map(fields) => {
+                let Llsd::Map(map) = llsd else {
+                    return Err(LlsdError::invalid_type("map", llsd));
+                };
+                for field in fields {
+                    match map.get(field.key.as_str()) {
+                        Some(value) => field
+                            .schema
+                            .validate(value)
+                            .map_err(|e| e.with_key(&field.key))?,
+                        None if field.required => {
+                            return Err(LlsdError::missing_field(&field.key));
+                        }
+                        None => {}
+                    }
+                }
+                Ok(())
+            }
+            Schema::Dict(values) => {
+                let Llsd::Map(map) = llsd else {
+                    return Err(LlsdError::invalid_type("map", llsd));
+                };
+                for (key, value) in map.iter() {
+                    values.validate(value).map_err(|e| e.with_key(key))?;
+                }
+                Ok(())
+            }
+            Schema::OneOf(choices) => {
+                let mut first = None;
+                for choice in choices {
+                    match choice.validate(llsd) {
+                        Ok(()) => return Ok(()),
+                        Err(e) => {
+                            first.get_or_insert(e);
+                        }
+                    }
+                }
+                Err(first.unwrap_or_else(|| LlsdError::validation("no schema to match")))
+            }
+        }
+    }
+}
+
+/// A random document matching `schema`.
+///
+/// Lengths, text and edge cases follow the generator's
+/// [`CorpusOptions`](crate::corpus::CorpusOptions); optional fields are
+/// present half the time. [`Schema::Any`] is a random value of the
+/// generator's full depth.
+pub fn sample(schema: &Schema, rng: &mut Generator) -> Llsd {
+    match schema {
+        Schema::Any => rng.value(),
+        Schema::Undefined => Llsd::Undefined,
+        Schema::Boolean => Llsd::Boolean(rng.below(2) == 1),
+        Schema::Integer => Llsd::Integer(rng.integer()),
+        Schema::Real => Llsd::Real(rng.real()),
+        Schema::String => Llsd::String(rng.text()),
+        Schema::Uuid => Llsd::Uuid(rng.uuid()),
+        Schema::Date => Llsd::Date(rng.date()),
+        Schema::Uri => Llsd::Uri(rng.uri()),
+        Schema::Binary => Llsd::Binary(rng.binary()),
+        Schema::Literal(value) => value.clone(),
+        Schema::Array(items) => {
+            let len = rng.items();
+            Llsd::Array((0..len).map(|_| sample(items, rng)).collect())
+        }
+        Schema::Map(fields) => {
+            let mut map = LlsdMap::new();
+            for field in fields {
+                if field.required || rng.below(2) == 1 {
+                    map.insert(field.key.clone(), sample(&field.schema, rng));
+                }
+            }
+            Llsd::Map(map)
+        }
+        Schema::Dict(values) => {
+            let len = rng.items();
+            let mut map = LlsdMap::new();
+            for _ in 0..len {
+                let key = rng.text();
+                let value = sample(values, rng);
+                map.insert(key, value);
+            }
+            Llsd::Map(map)
+        }
+        Schema::OneOf(choices) if choices.is_empty() => Llsd::Undefined,
+        Schema::OneOf(choices) => sample(&choices[rng.below(choices.len())], rng),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LlsdErrorKind, corpus::CorpusOptions};
+
+    fn event() -> Schema {
+        let folder = Schema::map()
+            .field("folder_id", Schema::Uuid)
+            .field("name", Schema::String)
+            .optional("version", Schema::Integer);
+        Schema::map()
+            .field(
+                "message",
+                Schema::OneOf(vec![
+                    Schema::Literal("BulkUpdateInventory".into()),
+                    Schema::Literal("RemoveInventoryItem".into()),
+                ]),
+            )
+            .field(
+                "body",
+                Schema::map()
+                    .field("folders", Schema::array(folder))
+                    .optional("extra", Schema::dict(Schema::Any))
+                    .optional("when", Schema::OneOf(vec![Schema::Date, Schema::Undefined])),
+            )
+    }
+
+    #[test]
+    fn samples_conform() {
+        let schema = event();
+        let mut rng = Generator::new(CorpusOptions::new(1451).with_max_depth(3));
+        let mut optional_seen = [false; 2];
+        for _ in 0..200 {
+            let doc = sample(&schema, &mut rng);
+            schema.validate(&doc).unwrap();
+            let body = &doc["body"];
+            optional_seen[0] |= body.get("extra").is_some();
+            optional_seen[1] |= body.get("extra").is_none();
+        }
+        assert_eq!(optional_seen, [true, true]);
+
+        // The same seed gives the same documents.
+        let again = |seed| sample(&schema, &mut Generator::new(CorpusOptions::new(seed)));
+        assert_eq!(again(9), again(9));
+    }
+
+    #[test]
+    fn reports_mismatches_with_paths() {
+        let schema = event();
+        let doc = Llsd::map()
+            .insert("message", "BulkUpdateInventory")
+            .unwrap()
+            .insert(
+                "body",
+                Llsd::map()
+                    .insert(
+                        "folders",
+                        Llsd::Array(vec![Llsd::map().insert("folder_id", "x").unwrap()]),
+                    )
+                    .unwrap(),
+            )
+            .unwrap();
+        let err = schema.validate(&doc).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type at body.folders[0].folder_id: expected uuid, found string"
+        );
+
+        let doc = Llsd::map().insert("message", "Other").unwrap();
+        let err = schema.validate(&doc).unwrap_err();
+        assert!(matches!(err.kind(), LlsdErrorKind::Validation(_)));
+        assert_eq!(err.path_string(), "message");
+
+        let doc = Llsd::map()
+            .insert("message", "RemoveInventoryItem")
+            .unwrap();
+        let err = schema.validate(&doc).unwrap_err();
+        assert_eq!(err.kind(), &LlsdErrorKind::MissingField);
+        assert_eq!(err.path_string(), "body");
+    }
+}
@@ -0,0 +1,890 @@
+//! serde support: `&Llsd` is a [`Deserializer`](::serde::Deserializer) and
+//! [`Serializer`] builds an `Llsd`, so types deriving serde's traits convert
+//! without the [`derive`](crate::derive) macros, and
+//! `serde_transcode` can pipe a value into any other serde format.
+//!
+//! The mapping follows the crate's own conversions:
+//!
+//! - integers are written as LLSD integers, narrowed with `as` like the
+//!   `From` impls, and read with the same coercions as `TryFrom<&Llsd>`
+//!   (reals, booleans and numeric strings are accepted);
+//! - UUIDs, dates and URIs read as their text, binaries as bytes;
+//! - `None` and `()` are `Undefined`; an enum's unit variants are strings
+//!   and its other variants single-entry maps, as serde_json does;
+//! - map keys must serialize to strings, integers, UUIDs or URIs.
+//!
+//! Errors are [`LlsdError`]s naming the path to the failing value.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Teleport {
+//!     region: String,
+//!     position: [f32; 3],
+//!     flags: u32,
+//! }
+//!
+//! let teleport = Teleport { region: "Ahern".into(), position: [128.0, 64.0, 22.5], flags: 16 };
+//! let llsd = llsd_rs::serde::to_llsd(&teleport).unwrap();
+//! assert_eq!(llsd["flags"], llsd_rs::Llsd::Integer(16));
+//! assert_eq!(llsd_rs::serde::from_llsd::<Teleport>(&llsd).unwrap(), teleport);
+//! ```
+
+use std::fmt;
+
+use ::serde::{
+    Deserialize, Deserializer, Serialize, de,
+    de::{IntoDeserializer, value::BorrowedStrDeserializer},
+    ser,
+};
+
+use crate::{Llsd, LlsdError, LlsdMap, date};
+
+/// Build an `Llsd` from any serializable value.
+pub fn to_llsd<T: Serialize + ?Sized>(value: &T) -> Result<Llsd, LlsdError> {
+    value.serialize(Serializer)
+}
+
+/// Read a `T` out of `llsd`, borrowing strings and bytes where `T` does.
+pub fn from_llsd<'de, T: Deserialize<'de>>(llsd: &'de Llsd) -> Result<T, LlsdError> {
+    T::deserialize(llsd)
+}
+
+impl de::Error for LlsdError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        LlsdError::custom(msg)
+    }
+}
+
+impl ser::Error for LlsdError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        LlsdError::custom(msg)
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, LlsdError> {
+                match <$t>::try_from(self) {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for &'de Llsd {
+    type Error = LlsdError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, LlsdError> {
+        match self {
+            Llsd::Undefined => visitor.visit_unit(),
+            Llsd::Boolean(b) => visitor.visit_bool(*b),
+            Llsd::Integer(i) => visitor.visit_i32(*i),
+            Llsd::Real(r) => visitor.visit_f64(*r),
+            Llsd::String(s) => visitor.visit_borrowed_str(s),
+            Llsd::Uri(u) => visitor.visit_borrowed_str(u.as_str()),
+            Llsd::Uuid(u) => visitor.visit_string(u.to_string()),
+            Llsd::Date(d) => visitor.visit_string(d.to_rfc3339()),
+            Llsd::Binary(b) => visitor.visit_borrowed_bytes(b),
+            Llsd::Array(items) => visitor.visit_seq(Seq {
+                items: items.iter(),
+                index: 0,
+            }),
+            Llsd::Map(map) => visitor.visit_map(Map {
+                entries: map.iter(),
+                value: None,
+            }),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, LlsdError> {
+        match self {
+            Llsd::Undefined => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        match self {
+            Llsd::String(variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            Llsd::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().expect("one entry");
+                visitor
+                    .visit_enum(Enum { variant, value })
+                    .map_err(|e| e.with_key(variant))
+            }
+            _ => Err(LlsdError::invalid_type("string or single-entry map", self)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        visitor.visit_unit()
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i128 u128 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct identifier
+    }
+}
+
+struct Seq<'de> {
+    items: std::slice::Iter<'de, Llsd>,
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Seq<'de> {
+    type Error = LlsdError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, LlsdError> {
+        let Some(item) = self.items.next() else {
+            return Ok(None);
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(item)
+            .map(Some)
+            .map_err(|e| e.with_index(index))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct Map<'de> {
+    entries: crate::map::Iter<'de>,
+    value: Option<(&'de str, &'de Llsd)>,
+}
+
+impl<'de> de::MapAccess<'de> for Map<'de> {
+    type Error = LlsdError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, LlsdError> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some((key, value));
+        seed.deserialize(Key(key))
+            .map(Some)
+            .map_err(|e| e.with_key(key))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, LlsdError> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| LlsdError::custom("map value read before its key"))?;
+        seed.deserialize(value).map_err(|e| e.with_key(key))
+    }
+}
+
+/// A map key, read as a number where one is wanted, as keys written from
+/// integers are.
+struct Key<'de>(&'de str);
+
+macro_rules! deserialize_key_number {
+    ($($method:ident => $visit:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, LlsdError> {
+                match self.0.parse::<$t>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => visitor.visit_borrowed_str(self.0),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Key<'de> {
+    type Error = LlsdError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, LlsdError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    deserialize_key_number! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string bytes byte_buf option unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// A variant written as `{variant: value}`.
+struct Enum<'de> {
+    variant: &'de str,
+    value: &'de Llsd,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum<'de> {
+    type Error = LlsdError;
+    type Variant = &'de Llsd;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, &'de Llsd), LlsdError> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<LlsdError>::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &'de Llsd {
+    type Error = LlsdError;
+
+    fn unit_variant(self) -> Result<(), LlsdError> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, LlsdError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LlsdError> {
+        self.deserialize_map(visitor)
+    }
+}
+
+/// The serializer [`to_llsd`] uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Llsd;
+    type Error = LlsdError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, v: bool) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Integer(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::from(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Real(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Real(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Binary(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Undefined)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Llsd, LlsdError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Undefined)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Undefined)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Llsd, LlsdError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Llsd, LlsdError> {
+        let value = value.serialize(self).map_err(|e| e.with_key(variant))?;
+        Ok(Llsd::Map(LlsdMap::from_iter([(
+            variant.to_string(),
+            value,
+        )])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, LlsdError> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, LlsdError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, LlsdError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeArray>, LlsdError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, LlsdError> {
+        Ok(SerializeMap {
+            map: LlsdMap::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, LlsdError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeMap>, LlsdError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+pub struct SerializeArray {
+    items: Vec<Llsd>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LlsdError> {
+        let index = self.items.len();
+        self.items.push(
+            value
+                .serialize(Serializer)
+                .map_err(|e| e.with_index(index))?,
+        );
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LlsdError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Array(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LlsdError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Array(self.items))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LlsdError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Array(self.items))
+    }
+}
+
+pub struct SerializeMap {
+    map: LlsdMap,
+    key: Option<String>,
+}
+
+impl SerializeMap {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), LlsdError> {
+        let value = value.serialize(Serializer).map_err(|e| e.with_key(&key))?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), LlsdError> {
+        self.key = Some(match key.serialize(Serializer)? {
+            Llsd::String(key) => key,
+            Llsd::Integer(key) => key.to_string(),
+            Llsd::Uuid(key) => key.to_string(),
+            Llsd::Uri(key) => key.as_str().to_string(),
+            other => return Err(LlsdError::invalid_type("string map key", &other)),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LlsdError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| LlsdError::custom("map value written before its key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Map(self.map))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), LlsdError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Llsd::Map(self.map))
+    }
+}
+
+/// A tuple or struct variant, written as `{variant: [...]}` or
+/// `{variant: {...}}`.
+pub struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+impl<T> SerializeVariant<T> {
+    fn wrap(variant: &'static str, value: Llsd) -> Llsd {
+        Llsd::Map(LlsdMap::from_iter([(variant.to_string(), value)]))
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LlsdError> {
+        self.inner.push(value).map_err(|e| e.with_key(self.variant))
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Self::wrap(self.variant, Llsd::Array(self.inner.items)))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = Llsd;
+    type Error = LlsdError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), LlsdError> {
+        self.inner
+            .insert(key.to_string(), value)
+            .map_err(|e| e.with_key(self.variant))
+    }
+
+    fn end(self) -> Result<Llsd, LlsdError> {
+        Ok(Self::wrap(self.variant, Llsd::Map(self.inner.map)))
+    }
+}
+
+/// UUIDs, dates and URIs are written as strings and binaries as bytes, so
+/// they come back as strings and (for self-describing formats) byte arrays.
+impl Serialize for Llsd {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+        match self {
+            Llsd::Undefined => serializer.serialize_unit(),
+            Llsd::Boolean(b) => serializer.serialize_bool(*b),
+            Llsd::Integer(i) => serializer.serialize_i32(*i),
+            Llsd::Real(r) => serializer.serialize_f64(*r),
+            Llsd::String(s) => serializer.serialize_str(s),
+            Llsd::Uri(u) => serializer.serialize_str(u.as_str()),
+            Llsd::Uuid(u) => serializer.serialize_str(crate::types::uuid_str(u, &mut [0; 36])),
+            Llsd::Date(d) => serializer.collect_str(&date::Rfc3339(d)),
+            Llsd::Binary(b) => serializer.serialize_bytes(b),
+            Llsd::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Llsd::Map(map) => {
+                let mut out = serializer.serialize_map(Some(map.len()))?;
+                for (key, value) in map.iter() {
+                    out.serialize_entry(key, value)?;
+                }
+                out.end()
+            }
+        }
+    }
+}
+
+/// Integers outside the `i32` range become reals; unit and `None` become
+/// `Undefined`.
+impl<'de> Deserialize<'de> for Llsd {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LlsdVisitor)
+    }
+}
+
+struct LlsdVisitor;
+
+impl<'de> de::Visitor<'de> for LlsdVisitor {
+    type Value = Llsd;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any LLSD value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Llsd, E> {
+        Ok(Llsd::Boolean(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Llsd, E> {
+        Ok(i32::try_from(v).map_or(Llsd::Real(v as f64), Llsd::Integer))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Llsd, E> {
+        Ok(i32::try_from(v).map_or(Llsd::Real(v as f64), Llsd::Integer))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Llsd, E> {
+        Ok(Llsd::Real(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Llsd, E> {
+        Ok(Llsd::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Llsd, E> {
+        Ok(Llsd::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Llsd, E> {
+        Ok(Llsd::Binary(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Llsd, E> {
+        Ok(Llsd::Binary(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Llsd, E> {
+        Ok(Llsd::Undefined)
+    }
+
+    fn visit_none<E>(self) -> Result<Llsd, E> {
+        Ok(Llsd::Undefined)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Llsd, D::Error> {
+        Llsd::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Llsd, D::Error> {
+        Llsd::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Llsd, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Llsd::Array(items))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut access: A) -> Result<Llsd, A::Error> {
+        let mut map = LlsdMap::new();
+        while let Some((key, value)) = access.next_entry::<String, Llsd>()? {
+            map.insert(key, value);
+        }
+        Ok(Llsd::Map(map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Line(i32, i32),
+        Box { w: u8, h: u8 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Object<'a> {
+        name: &'a str,
+        owner: Option<String>,
+        flags: u32,
+        tags: Vec<String>,
+        shapes: Vec<Shape>,
+        #[serde(with = "bytes")]
+        data: Vec<u8>,
+        by_id: BTreeMap<u16, bool>,
+    }
+
+    mod bytes {
+        pub fn serialize<S: ::serde::Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+
+        pub fn deserialize<'de, D: ::serde::Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+            struct Bytes;
+            impl ::serde::de::Visitor<'_> for Bytes {
+                type Value = Vec<u8>;
+                fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str("bytes")
+                }
+                fn visit_bytes<E>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                    Ok(v.to_vec())
+                }
+            }
+            d.deserialize_bytes(Bytes)
+        }
+    }
+
+    #[test]
+    fn round_trips_serde_types() {
+        let object = Object {
+            name: "box",
+            owner: None,
+            flags: 0x8000_0001,
+            tags: vec!["a".into()],
+            shapes: vec![
+                Shape::Point,
+                Shape::Circle(0.5),
+                Shape::Line(1, 2),
+                Shape::Box { w: 3, h: 4 },
+            ],
+            data: vec![1, 2, 3],
+            by_id: BTreeMap::from([(7, true)]),
+        };
+        let llsd = to_llsd(&object).unwrap();
+        assert_eq!(llsd["owner"], Llsd::Undefined);
+        assert_eq!(llsd["flags"], Llsd::Integer(0x8000_0001u32 as i32));
+        assert_eq!(llsd["data"], Llsd::Binary(vec![1, 2, 3]));
+        assert_eq!(llsd["by_id"]["7"], Llsd::Boolean(true));
+        assert_eq!(llsd["shapes"][0], Llsd::String("Point".into()));
+        assert_eq!(
+            llsd["shapes"][2]["Line"],
+            Llsd::Array(vec![1.into(), 2.into()])
+        );
+        assert_eq!(llsd["shapes"][3]["Box"]["h"], Llsd::Integer(4));
+        assert_eq!(from_llsd::<Object>(&llsd).unwrap(), object);
+
+        // Llsd itself, through serde.
+        let through = Llsd::deserialize(&llsd).unwrap();
+        assert_eq!(through, llsd);
+        assert_eq!(to_llsd(&llsd).unwrap(), llsd);
+    }
+
+    #[test]
+    fn coerces_and_reports_paths() {
+        #[derive(Debug, Deserialize)]
+        struct Agent {
+            #[allow(dead_code)]
+            id: String,
+            level: u8,
+        }
+        let llsd = Llsd::map()
+            .insert("id", crate::Uuid::nil())
+            .unwrap()
+            .insert("level", "12")
+            .unwrap();
+        assert_eq!(from_llsd::<Agent>(&llsd).unwrap().level, 12);
+
+        let llsd = Llsd::Array(vec![Llsd::map().insert("level", 1).unwrap()]);
+        let err = from_llsd::<Vec<Agent>>(&llsd).unwrap_err();
+        assert_eq!(err.path_string(), "[0]");
+        assert!(err.to_string().contains("missing field `id`"), "{err}");
+
+        let llsd = Llsd::map()
+            .insert("id", "x")
+            .unwrap()
+            .insert("level", Llsd::Array(vec![]))
+            .unwrap();
+        let err = from_llsd::<Agent>(&llsd).unwrap_err();
+        assert_eq!(err.path_string(), "level");
+
+        let err = to_llsd(&BTreeMap::from([(vec![1], 2)])).unwrap_err();
+        assert!(err.to_string().contains("string map key"), "{err}");
+    }
+}
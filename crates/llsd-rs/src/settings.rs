@@ -0,0 +1,563 @@
+//! Viewer-style settings files.
+//!
+//! A settings file is an LLSD XML map from setting name to an entry:
+//!
+//! ```text
+//! { "RenderFarClip": { "Comment": "Draw distance", "Persist": 1,
+//!                      "Type": "F32", "Value": 128.0 } }
+//! ```
+//!
+//! [`Settings`] loads files as layers: the first (the defaults) declares each
+//! setting's type and comment, later ones (user overrides) replace values
+//! and may declare new settings. A top-level `"Include"` entry whose `Value`
+//! is an array of paths loads those files, relative to the including one,
+//! before the rest of its entries.
+//!
+//! ```
+//! use llsd_rs::{settings::Settings, xml};
+//!
+//! let mut settings = Settings::from_llsd(&xml::from_str(
+//!     "<llsd><map><key>RenderFarClip</key><map>\
+//!        <key>Type</key><string>F32</string><key>Value</key><real>64</real>\
+//!      </map></map></llsd>",
+//! ).unwrap()).unwrap();
+//! settings.overlay(&xml::from_str(
+//!     "<llsd><map><key>RenderFarClip</key><map><key>Value</key><real>256</real></map></map></llsd>",
+//! ).unwrap()).unwrap();
+//! assert_eq!(settings.get_f32("RenderFarClip").unwrap(), 256.0);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+
+use crate::types::{Color4, Vector3, Vector3d};
+use crate::{FromLlsd, Llsd, LlsdError, LlsdMap};
+
+/// Name of the top-level entry listing files to include.
+pub const INCLUDE: &str = "Include";
+
+/// The `Type` of a setting, which constrains its `Value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SettingType {
+    Boolean,
+    S32,
+    U32,
+    F32,
+    String,
+    Vector3,
+    Vector3D,
+    Rect,
+    Color3,
+    Color4,
+    Color4U,
+    Llsd,
+}
+
+impl SettingType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SettingType::Boolean => "Boolean",
+            SettingType::S32 => "S32",
+            SettingType::U32 => "U32",
+            SettingType::F32 => "F32",
+            SettingType::String => "String",
+            SettingType::Vector3 => "Vector3",
+            SettingType::Vector3D => "Vector3D",
+            SettingType::Rect => "Rect",
+            SettingType::Color3 => "Color3",
+            SettingType::Color4 => "Color4",
+            SettingType::Color4U => "Color4U",
+            SettingType::Llsd => "LLSD",
+        }
+    }
+
+    /// Whether `value` is a valid `Value` for this type. Booleans may be
+    /// integers, as the viewer writes them.
+    pub fn accepts(&self, value: &Llsd) -> bool {
+        let numbers = |n: usize| {
+            matches!(value, Llsd::Array(items) if items.len() == n
+                && items.iter().all(|v| matches!(v, Llsd::Integer(_) | Llsd::Real(_))))
+        };
+        match self {
+            SettingType::Boolean => matches!(value, Llsd::Boolean(_) | Llsd::Integer(_)),
+            SettingType::S32 | SettingType::U32 => matches!(value, Llsd::Integer(_)),
+            SettingType::F32 => matches!(value, Llsd::Real(_) | Llsd::Integer(_)),
+            SettingType::String => matches!(value, Llsd::String(_)),
+            SettingType::Vector3 | SettingType::Vector3D | SettingType::Color3 => numbers(3),
+            SettingType::Rect | SettingType::Color4 | SettingType::Color4U => numbers(4),
+            SettingType::Llsd => true,
+        }
+    }
+}
+
+impl fmt::Display for SettingType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SettingType {
+    type Err = LlsdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Boolean" => SettingType::Boolean,
+            "S32" => SettingType::S32,
+            "U32" => SettingType::U32,
+            "F32" => SettingType::F32,
+            "String" => SettingType::String,
+            "Vector3" => SettingType::Vector3,
+            "Vector3D" => SettingType::Vector3D,
+            "Rect" => SettingType::Rect,
+            "Color3" => SettingType::Color3,
+            "Color4" => SettingType::Color4,
+            "Color4U" => SettingType::Color4U,
+            "LLSD" => SettingType::Llsd,
+            _ => return Err(LlsdError::validation(format!("unknown setting type {s}"))),
+        })
+    }
+}
+
+/// One setting: its declared type, current value and description.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub setting_type: SettingType,
+    pub value: Llsd,
+    pub comment: String,
+    /// Whether the viewer saves the setting to the user's file.
+    pub persist: bool,
+}
+
+impl Setting {
+    fn to_llsd(&self) -> Llsd {
+        let mut map = LlsdMap::with_capacity(4);
+        map.insert("Comment".into(), Llsd::String(self.comment.clone()));
+        map.insert("Persist".into(), Llsd::Integer(self.persist as i32));
+        map.insert(
+            "Type".into(),
+            Llsd::String(self.setting_type.as_str().into()),
+        );
+        map.insert("Value".into(), self.value.clone());
+        Llsd::Map(map)
+    }
+}
+
+/// A set of settings built from one or more layered files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    entries: HashMap<String, Setting>,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Settings declared by a single layer.
+    pub fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        let mut settings = Self::new();
+        settings.overlay(llsd)?;
+        Ok(settings)
+    }
+
+    /// Load `paths` in order, each overlaying the ones before it.
+    pub fn load<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self, anyhow::Error> {
+        let mut settings = Self::new();
+        for path in paths {
+            settings.overlay_file(path)?;
+        }
+        Ok(settings)
+    }
+
+    /// Overlay the settings file at `path`, following its includes.
+    pub fn overlay_file(&mut self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+        self.overlay_file_inner(path.as_ref(), &mut Vec::new())
+    }
+
+    fn overlay_file_inner(
+        &mut self,
+        path: &Path,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<(), anyhow::Error> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if including.contains(&canonical) {
+            anyhow::bail!("settings include cycle at {}", path.display());
+        }
+        let file = std::fs::File::open(path).with_context(|| path.display().to_string())?;
+        let llsd = crate::xml::from_reader(std::io::BufReader::new(file))
+            .with_context(|| path.display().to_string())?;
+        let map = llsd
+            .as_map()
+            .ok_or_else(|| LlsdError::invalid_type("map", &llsd))?;
+        if let Some(include) = map.get(INCLUDE) {
+            let files = include.get("Value").unwrap_or(include);
+            let files = files
+                .as_array()
+                .ok_or_else(|| LlsdError::invalid_type("array", files).with_key(INCLUDE))?;
+            including.push(canonical);
+            let base = path.parent().unwrap_or(Path::new(""));
+            for (i, file) in files.iter().enumerate() {
+                let name = file.as_string().ok_or_else(|| {
+                    LlsdError::invalid_type("string", file)
+                        .with_index(i)
+                        .with_key(INCLUDE)
+                })?;
+                self.overlay_file_inner(&base.join(name), including)?;
+            }
+            including.pop();
+        }
+        self.overlay(&llsd)?;
+        Ok(())
+    }
+
+    /// Apply one layer. Entries for known settings replace the value (and the
+    /// comment or persist flag when given); a `Type` that differs from the
+    /// declared one is an error. New settings must declare a `Type`.
+    pub fn overlay(&mut self, llsd: &Llsd) -> Result<(), LlsdError> {
+        let map = llsd
+            .as_map()
+            .ok_or_else(|| LlsdError::invalid_type("map", llsd))?;
+        for (name, entry) in map {
+            if name == INCLUDE {
+                continue;
+            }
+            self.overlay_entry(name, entry)
+                .map_err(|e| e.with_key(name))?;
+        }
+        Ok(())
+    }
+
+    fn overlay_entry(&mut self, name: &str, entry: &Llsd) -> Result<(), LlsdError> {
+        let map = entry
+            .as_map()
+            .ok_or_else(|| LlsdError::invalid_type("map", entry))?;
+        let setting_type = match map.get("Type") {
+            Some(Llsd::String(s)) => {
+                Some(s.parse::<SettingType>().map_err(|e| e.with_key("Type"))?)
+            }
+            Some(other) => return Err(LlsdError::invalid_type("string", other).with_key("Type")),
+            None => None,
+        };
+        let comment = match map.get("Comment") {
+            Some(Llsd::String(s)) => Some(s.clone()),
+            Some(other) => {
+                return Err(LlsdError::invalid_type("string", other).with_key("Comment"));
+            }
+            None => None,
+        };
+        let persist = match map.get("Persist") {
+            Some(Llsd::Integer(i)) => Some(*i != 0),
+            Some(Llsd::Boolean(b)) => Some(*b),
+            Some(other) => {
+                return Err(LlsdError::invalid_type("integer", other).with_key("Persist"));
+            }
+            None => None,
+        };
+        let value = map.get("Value");
+
+        let declared = match self.entries.get(name) {
+            Some(setting) => {
+                if let Some(redeclared) = setting_type.filter(|t| *t != setting.setting_type) {
+                    return Err(LlsdError::validation(format!(
+                        "declared as {}, redeclared as {redeclared}",
+                        setting.setting_type
+                    ))
+                    .with_key("Type"));
+                }
+                setting.setting_type
+            }
+            None if value.is_none() => return Err(LlsdError::missing_field("Value")),
+            None => setting_type.ok_or_else(|| LlsdError::missing_field("Type"))?,
+        };
+        if value.is_some_and(|v| !declared.accepts(v)) {
+            return Err(
+                LlsdError::validation(format!("value does not match type {declared}"))
+                    .with_key("Value"),
+            );
+        }
+        let setting = self
+            .entries
+            .entry(name.to_string())
+            .or_insert_with(|| Setting {
+                setting_type: declared,
+                value: Llsd::Undefined,
+                comment: String::new(),
+                persist: true,
+            });
+        if let Some(value) = value {
+            setting.value = value.clone();
+        }
+        if let Some(comment) = comment {
+            setting.comment = comment;
+        }
+        if let Some(persist) = persist {
+            setting.persist = persist;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Setting> {
+        self.entries.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Setting)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Change the value of a declared setting.
+    pub fn set(&mut self, name: &str, value: impl Into<Llsd>) -> Result<(), LlsdError> {
+        let setting = self
+            .entries
+            .get_mut(name)
+            .ok_or_else(|| LlsdError::missing_field(name))?;
+        let value = value.into();
+        if !setting.setting_type.accepts(&value) {
+            return Err(LlsdError::validation(format!(
+                "value does not match type {}",
+                setting.setting_type
+            ))
+            .with_key(name));
+        }
+        setting.value = value;
+        Ok(())
+    }
+
+    fn value(&self, name: &str) -> Result<&Llsd, LlsdError> {
+        self.entries
+            .get(name)
+            .map(|s| &s.value)
+            .ok_or_else(|| LlsdError::missing_field(name))
+    }
+
+    /// The value of `name` converted to `T`.
+    pub fn get_as<T: FromLlsd>(&self, name: &str) -> Result<T, LlsdError> {
+        T::from_llsd(self.value(name)?).map_err(|e| e.with_key(name))
+    }
+
+    pub fn get_bool(&self, name: &str) -> Result<bool, LlsdError> {
+        match self.value(name)? {
+            Llsd::Boolean(b) => Ok(*b),
+            Llsd::Integer(i) => Ok(*i != 0),
+            other => Err(LlsdError::invalid_type("boolean", other).with_key(name)),
+        }
+    }
+
+    pub fn get_i32(&self, name: &str) -> Result<i32, LlsdError> {
+        self.get_as(name)
+    }
+
+    /// A `U32` setting. The viewer stores these as (signed) LLSD integers, so
+    /// the bits are reinterpreted rather than range-checked.
+    pub fn get_u32(&self, name: &str) -> Result<u32, LlsdError> {
+        self.get_i32(name).map(|v| v as u32)
+    }
+
+    pub fn get_f32(&self, name: &str) -> Result<f32, LlsdError> {
+        self.get_as(name)
+    }
+
+    pub fn get_string(&self, name: &str) -> Result<String, LlsdError> {
+        self.get_as(name)
+    }
+
+    pub fn get_vector3(&self, name: &str) -> Result<Vector3, LlsdError> {
+        self.get_as(name)
+    }
+
+    pub fn get_vector3d(&self, name: &str) -> Result<Vector3d, LlsdError> {
+        self.get_as(name)
+    }
+
+    pub fn get_color4(&self, name: &str) -> Result<Color4, LlsdError> {
+        self.get_as(name)
+    }
+
+    /// Every setting as a settings-file map.
+    pub fn to_llsd(&self) -> Llsd {
+        Llsd::Map(
+            self.entries
+                .iter()
+                .map(|(name, setting)| (name.clone(), setting.to_llsd()))
+                .collect(),
+        )
+    }
+
+    /// The persisted settings whose value differs from `defaults` (or that
+    /// `defaults` does not declare), as a settings-file map: what to save as
+    /// the user's overrides.
+    pub fn overrides(&self, defaults: &Settings) -> Llsd {
+        Llsd::Map(
+            self.entries
+                .iter()
+                .filter(|(name, setting)| {
+                    setting.persist
+                        && defaults
+                            .entries
+                            .get(name.as_str())
+                            .is_none_or(|d| d.value != setting.value)
+                })
+                .map(|(name, setting)| (name.clone(), setting.to_llsd()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(setting_type: &str, value: Llsd) -> Llsd {
+        Llsd::map()
+            .insert("Type", setting_type)
+            .unwrap()
+            .insert("Value", value)
+            .unwrap()
+            .insert("Comment", "test")
+            .unwrap()
+            .insert("Persist", 1)
+            .unwrap()
+    }
+
+    fn defaults() -> Settings {
+        let llsd = Llsd::map()
+            .insert("ShowChat", entry("Boolean", Llsd::Integer(1)))
+            .unwrap()
+            .insert("FarClip", entry("F32", Llsd::Real(64.0)))
+            .unwrap()
+            .insert("Flags", entry("U32", Llsd::Integer(-1)))
+            .unwrap()
+            .insert(
+                "Tint",
+                entry("Color4", Llsd::from(vec![1.0, 0.5, 0.25, 1.0])),
+            )
+            .unwrap();
+        Settings::from_llsd(&llsd).unwrap()
+    }
+
+    #[test]
+    fn typed_getters() {
+        let settings = defaults();
+        assert!(settings.get_bool("ShowChat").unwrap());
+        assert_eq!(settings.get_f32("FarClip").unwrap(), 64.0);
+        assert_eq!(settings.get_u32("Flags").unwrap(), u32::MAX);
+        assert_eq!(
+            settings.get_color4("Tint").unwrap(),
+            Color4::new(1.0, 0.5, 0.25, 1.0)
+        );
+        assert_eq!(
+            settings.get_bool("Missing").unwrap_err().path_string(),
+            "Missing"
+        );
+        assert_eq!(
+            settings.get_string("FarClip").unwrap_err().path_string(),
+            "FarClip"
+        );
+    }
+
+    #[test]
+    fn overlays_replace_values_and_check_types() {
+        let mut settings = defaults();
+        let user = Llsd::map()
+            .insert("FarClip", Llsd::map().insert("Value", 256.0).unwrap())
+            .unwrap()
+            .insert("NewSetting", entry("String", "hello".into()))
+            .unwrap();
+        settings.overlay(&user).unwrap();
+        assert_eq!(settings.get_f32("FarClip").unwrap(), 256.0);
+        assert_eq!(settings.get("FarClip").unwrap().comment, "test");
+        assert_eq!(settings.get_string("NewSetting").unwrap(), "hello");
+
+        let wrong = Llsd::map()
+            .insert("FarClip", Llsd::map().insert("Value", "far").unwrap())
+            .unwrap();
+        let err = settings.overlay(&wrong).unwrap_err();
+        assert_eq!(err.path_string(), "FarClip.Value");
+
+        let undeclared = Llsd::map()
+            .insert("Other", Llsd::map().insert("Value", 1).unwrap())
+            .unwrap();
+        let err = settings.overlay(&undeclared).unwrap_err();
+        assert_eq!(err.path_string(), "Other.Type");
+
+        assert!(settings.set("FarClip", 32.0).is_ok());
+        assert!(settings.set("ShowChat", "yes").is_err());
+    }
+
+    #[test]
+    fn overrides_hold_changed_values() {
+        let defaults = defaults();
+        let mut settings = defaults.clone();
+        settings.set("FarClip", 512.0).unwrap();
+        let overrides = settings.overrides(&defaults);
+        let map = overrides.as_map().unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(overrides["FarClip"]["Value"], Llsd::Real(512.0));
+
+        let mut reloaded = defaults.clone();
+        reloaded.overlay(&overrides).unwrap();
+        assert_eq!(reloaded, settings);
+    }
+
+    #[test]
+    fn files_and_includes() {
+        let dir = std::env::temp_dir().join(format!("llsd-rs-settings-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        let write = |name: &str, llsd: &Llsd| {
+            std::fs::write(dir.join(name), crate::xml::to_string(llsd).unwrap()).unwrap();
+        };
+        write(
+            "shared/base.xml",
+            &Llsd::map()
+                .insert("FarClip", entry("F32", Llsd::Real(64.0)))
+                .unwrap(),
+        );
+        write(
+            "defaults.xml",
+            &Llsd::map()
+                .insert(
+                    INCLUDE,
+                    Llsd::map()
+                        .insert("Value", vec!["shared/base.xml"])
+                        .unwrap(),
+                )
+                .unwrap()
+                .insert("ShowChat", entry("Boolean", Llsd::Boolean(true)))
+                .unwrap(),
+        );
+        write(
+            "user.xml",
+            &Llsd::map()
+                .insert("FarClip", Llsd::map().insert("Value", 128.0).unwrap())
+                .unwrap(),
+        );
+        write(
+            "cycle.xml",
+            &Llsd::map()
+                .insert(
+                    INCLUDE,
+                    Llsd::map().insert("Value", vec!["cycle.xml"]).unwrap(),
+                )
+                .unwrap(),
+        );
+
+        let settings = Settings::load([dir.join("defaults.xml"), dir.join("user.xml")]).unwrap();
+        assert_eq!(settings.len(), 2);
+        assert_eq!(settings.get_f32("FarClip").unwrap(), 128.0);
+        assert!(settings.get_bool("ShowChat").unwrap());
+        let err = Settings::load([dir.join("cycle.xml")]).unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
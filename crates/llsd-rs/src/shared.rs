@@ -0,0 +1,548 @@
+//! Structural sharing for parsed documents.
+//!
+//! [`SharedLlsd`] mirrors [`Llsd`] with reference-counted strings, binaries and
+//! containers. A [`Deduplicator`] converts values into it bottom-up, handing out
+//! the same allocation for every subtree it has seen before, so documents that
+//! repeat blocks (object updates carrying identical permission and flag maps for
+//! most entries, say) keep one copy of each. Deduplication is opt-in: parse as
+//! usual, then pass the result through [`dedup`] or a [`Deduplicator`] that is
+//! kept around to share subtrees across documents too.
+//!
+//! A [`KeyInterner`] goes further for long-lived services: one table of map
+//! keys behind an `Arc`, shared by any number of deduplicators across
+//! threads, so the same few hundred keys repeated by thousands of event
+//! bodies are allocated once, with hit-rate statistics to show it pays.
+//!
+//! ```
+//! use llsd_rs::{Llsd, shared};
+//!
+//! let perms = Llsd::map().insert("owner_mask", 0x7fffffff).unwrap();
+//! let llsd = Llsd::Array(vec![perms.clone(), perms]);
+//! let shared = shared::dedup(&llsd);
+//! let items = shared.as_array().unwrap();
+//! assert!(items[0].ptr_eq(&items[1]));
+//! assert_eq!(shared.to_llsd(), llsd);
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::Uuid;
+use enum_as_inner::EnumAsInner;
+
+use crate::{Date, Llsd, LlsdMap, Uri};
+
+/// A value whose strings, binaries and containers are reference counted, so
+/// identical subtrees can share one allocation.
+#[derive(Debug, Clone, PartialEq, EnumAsInner)]
+pub enum SharedLlsd {
+    Undefined,
+    Boolean(bool),
+    Integer(i32),
+    Real(f64),
+    String(Arc<str>),
+    Uri(Arc<str>),
+    Uuid(Uuid),
+    Date(Date),
+    Binary(Arc<[u8]>),
+    Array(Arc<[SharedLlsd]>),
+    /// Entries sorted by key, so equal maps have one representation whatever
+    /// order they were read in.
+    Map(Arc<[(Arc<str>, SharedLlsd)]>),
+}
+
+impl SharedLlsd {
+    /// Same names as [`Llsd::type_name`].
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SharedLlsd::Undefined => "undef",
+            SharedLlsd::Boolean(_) => "boolean",
+            SharedLlsd::Integer(_) => "integer",
+            SharedLlsd::Real(_) => "real",
+            SharedLlsd::String(_) => "string",
+            SharedLlsd::Uri(_) => "uri",
+            SharedLlsd::Uuid(_) => "uuid",
+            SharedLlsd::Date(_) => "date",
+            SharedLlsd::Binary(_) => "binary",
+            SharedLlsd::Array(_) => "array",
+            SharedLlsd::Map(_) => "map",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            SharedLlsd::Array(a) => a.len(),
+            SharedLlsd::Map(m) => m.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SharedLlsd> {
+        match self {
+            SharedLlsd::Map(entries) => entries
+                .binary_search_by(|(k, _)| (**k).cmp(key))
+                .ok()
+                .map(|i| &entries[i].1),
+            _ => None,
+        }
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&SharedLlsd> {
+        match self {
+            SharedLlsd::Array(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    /// Whether both values are the same allocation. Always false for scalars
+    /// stored inline (numbers, booleans, UUIDs, dates and undefined).
+    pub fn ptr_eq(&self, other: &SharedLlsd) -> bool {
+        match (self, other) {
+            (SharedLlsd::String(a), SharedLlsd::String(b))
+            | (SharedLlsd::Uri(a), SharedLlsd::Uri(b)) => Arc::ptr_eq(a, b),
+            (SharedLlsd::Binary(a), SharedLlsd::Binary(b)) => Arc::ptr_eq(a, b),
+            (SharedLlsd::Array(a), SharedLlsd::Array(b)) => Arc::ptr_eq(a, b),
+            (SharedLlsd::Map(a), SharedLlsd::Map(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Copy into an owned [`Llsd`], expanding every shared subtree.
+    pub fn to_llsd(&self) -> Llsd {
+        match self {
+            SharedLlsd::Undefined => Llsd::Undefined,
+            SharedLlsd::Boolean(v) => Llsd::Boolean(*v),
+            SharedLlsd::Integer(v) => Llsd::Integer(*v),
+            SharedLlsd::Real(v) => Llsd::Real(*v),
+            SharedLlsd::String(v) => Llsd::String(v.to_string()),
+            SharedLlsd::Uri(v) => Llsd::Uri(Uri::parse(v)),
+            SharedLlsd::Uuid(v) => Llsd::Uuid(*v),
+            SharedLlsd::Date(v) => Llsd::Date(*v),
+            SharedLlsd::Binary(v) => Llsd::Binary(v.to_vec()),
+            SharedLlsd::Array(items) => {
+                Llsd::Array(items.iter().map(SharedLlsd::to_llsd).collect())
+            }
+            SharedLlsd::Map(entries) => Llsd::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_llsd()))
+                    .collect::<LlsdMap>(),
+            ),
+        }
+    }
+}
+
+impl From<&SharedLlsd> for Llsd {
+    fn from(llsd: &SharedLlsd) -> Self {
+        llsd.to_llsd()
+    }
+}
+
+impl From<&Llsd> for SharedLlsd {
+    fn from(llsd: &Llsd) -> Self {
+        dedup(llsd)
+    }
+}
+
+/// Identity of a value for lookup in the container table. Children are
+/// converted first, so a container's children are already canonical and can
+/// be compared by address instead of by content.
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Undefined,
+    Boolean(bool),
+    Integer(i32),
+    Real(u64),
+    String(Arc<str>),
+    Uri(Arc<str>),
+    Uuid(Uuid),
+    Date(Date),
+    Binary(Arc<[u8]>),
+    Array(Vec<Key>),
+    Map(Vec<(Arc<str>, Key)>),
+    Container(usize),
+}
+
+fn child_key(value: &SharedLlsd) -> Key {
+    match value {
+        SharedLlsd::Undefined => Key::Undefined,
+        SharedLlsd::Boolean(v) => Key::Boolean(*v),
+        SharedLlsd::Integer(v) => Key::Integer(*v),
+        // Bitwise, so `0.0` and `-0.0` stay distinct and NaNs can be shared.
+        SharedLlsd::Real(v) => Key::Real(v.to_bits()),
+        SharedLlsd::String(v) => Key::String(v.clone()),
+        SharedLlsd::Uri(v) => Key::Uri(v.clone()),
+        SharedLlsd::Uuid(v) => Key::Uuid(*v),
+        SharedLlsd::Date(v) => Key::Date(*v),
+        SharedLlsd::Binary(v) => Key::Binary(v.clone()),
+        SharedLlsd::Array(v) => Key::Container(v.as_ptr() as usize),
+        SharedLlsd::Map(v) => Key::Container(v.as_ptr() as usize),
+    }
+}
+
+/// Converts [`Llsd`] values into [`SharedLlsd`], sharing every string, binary
+/// and container that is equal to one it has already produced.
+///
+/// The table holds a reference to everything it has handed out, so subtrees
+/// are shared across all values passed to the same deduplicator until it is
+/// cleared or dropped.
+#[derive(Default)]
+pub struct Deduplicator {
+    strings: HashSet<Arc<str>>,
+    binaries: HashSet<Arc<[u8]>>,
+    containers: HashMap<Key, SharedLlsd>,
+    keys: Option<Arc<KeyInterner>>,
+}
+
+impl Deduplicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take map keys from `keys` instead of this deduplicator's own table,
+    /// sharing them with every other user of the interner.
+    pub fn with_key_interner(mut self, keys: Arc<KeyInterner>) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    pub fn dedup(&mut self, llsd: &Llsd) -> SharedLlsd {
+        match llsd {
+            Llsd::Undefined => SharedLlsd::Undefined,
+            Llsd::Boolean(v) => SharedLlsd::Boolean(*v),
+            Llsd::Integer(v) => SharedLlsd::Integer(*v),
+            Llsd::Real(v) => SharedLlsd::Real(*v),
+            Llsd::String(v) => SharedLlsd::String(self.string(v)),
+            Llsd::Uri(v) => SharedLlsd::Uri(self.string(v.as_str())),
+            Llsd::Uuid(v) => SharedLlsd::Uuid(*v),
+            Llsd::Date(v) => SharedLlsd::Date(*v),
+            Llsd::Binary(v) => SharedLlsd::Binary(self.binary(v)),
+            Llsd::Array(items) => {
+                let items: Vec<SharedLlsd> = items.iter().map(|v| self.dedup(v)).collect();
+                let key = Key::Array(items.iter().map(child_key).collect());
+                self.containers
+                    .entry(key)
+                    .or_insert_with(|| SharedLlsd::Array(items.into()))
+                    .clone()
+            }
+            Llsd::Map(map) => {
+                let mut entries: Vec<(Arc<str>, SharedLlsd)> = map
+                    .iter()
+                    .map(|(k, v)| (self.key(k), self.dedup(v)))
+                    .collect();
+                entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                let key = Key::Map(
+                    entries
+                        .iter()
+                        .map(|(k, v)| (k.clone(), child_key(v)))
+                        .collect(),
+                );
+                self.containers
+                    .entry(key)
+                    .or_insert_with(|| SharedLlsd::Map(entries.into()))
+                    .clone()
+            }
+        }
+    }
+
+    /// Number of distinct strings, binaries and containers held.
+    pub fn len(&self) -> usize {
+        self.strings.len() + self.binaries.len() + self.containers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget everything handed out so far. Values already returned keep
+    /// their allocations; later ones no longer share with them.
+    pub fn clear(&mut self) {
+        self.strings.clear();
+        self.binaries.clear();
+        self.containers.clear();
+    }
+
+    fn string(&mut self, s: &str) -> Arc<str> {
+        if let Some(v) = self.strings.get(s) {
+            return v.clone();
+        }
+        let v: Arc<str> = Arc::from(s);
+        self.strings.insert(v.clone());
+        v
+    }
+
+    fn key(&mut self, k: &str) -> Arc<str> {
+        match &self.keys {
+            Some(keys) => keys.intern(k),
+            None => self.string(k),
+        }
+    }
+
+    fn binary(&mut self, b: &[u8]) -> Arc<[u8]> {
+        if let Some(v) = self.binaries.get(b) {
+            return v.clone();
+        }
+        let v: Arc<[u8]> = Arc::from(b);
+        self.binaries.insert(v.clone());
+        v
+    }
+}
+
+/// A thread-safe table of map keys, shared through an `Arc` by the
+/// [`Deduplicator`]s of many documents and threads.
+///
+/// Keys are kept until [`clear`](Self::clear); once
+/// [`max_keys`](Self::with_max_keys) are held, new keys are handed out
+/// without being stored, so a stream of unique keys can't grow it without
+/// bound.
+#[derive(Debug)]
+pub struct KeyInterner {
+    keys: Mutex<HashSet<Arc<str>>>,
+    max_keys: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// How well a [`KeyInterner`] is doing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InternStats {
+    /// Lookups answered with an existing key.
+    pub hits: u64,
+    /// Lookups that allocated.
+    pub misses: u64,
+    /// Keys held.
+    pub keys: usize,
+}
+
+impl InternStats {
+    /// Fraction of lookups that were hits, 0 before any lookup.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl KeyInterner {
+    /// Holds up to 65536 keys.
+    pub fn new() -> Self {
+        Self {
+            keys: Mutex::default(),
+            max_keys: 65536,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys;
+        self
+    }
+
+    /// The shared copy of `key`.
+    pub fn intern(&self, key: &str) -> Arc<str> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(v) = keys.get(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return v.clone();
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let v: Arc<str> = Arc::from(key);
+        if keys.len() < self.max_keys {
+            keys.insert(v.clone());
+        }
+        v
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> InternStats {
+        InternStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            keys: self.len(),
+        }
+    }
+
+    /// Forget every key and reset the statistics. Keys already handed out
+    /// stay valid.
+    pub fn clear(&self) {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for KeyInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert `llsd` with a fresh [`Deduplicator`], sharing repeated subtrees
+/// within this one value.
+pub fn dedup(llsd: &Llsd) -> SharedLlsd {
+    Deduplicator::new().dedup(llsd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permissions(mask: i32) -> Llsd {
+        let mut map = LlsdMap::new();
+        map.insert("base_mask".into(), Llsd::Integer(mask));
+        map.insert("owner_mask".into(), Llsd::Integer(mask));
+        map.insert("flags".into(), Llsd::Array(vec![Llsd::Boolean(true); 3]));
+        Llsd::Map(map)
+    }
+
+    fn object(id: i32, mask: i32) -> Llsd {
+        let mut map = LlsdMap::new();
+        map.insert("local_id".into(), Llsd::Integer(id));
+        map.insert("name".into(), Llsd::String("Object".into()));
+        map.insert("permissions".into(), permissions(mask));
+        Llsd::Map(map)
+    }
+
+    #[test]
+    fn identical_subtrees_share_an_allocation() {
+        let llsd = Llsd::Array(vec![object(1, 7), object(2, 7), object(3, 5)]);
+        let shared = dedup(&llsd);
+        assert_eq!(shared.to_llsd(), llsd);
+
+        let perms: Vec<_> = (0..3)
+            .map(|i| shared.get_index(i).unwrap().get("permissions").unwrap())
+            .collect();
+        assert!(perms[0].ptr_eq(perms[1]));
+        assert!(!perms[0].ptr_eq(perms[2]));
+        assert_eq!(perms[0], perms[1]);
+        // The flag array is shared between the two different permission maps.
+        assert!(
+            perms[0]
+                .get("flags")
+                .unwrap()
+                .ptr_eq(perms[2].get("flags").unwrap())
+        );
+        let names: Vec<_> = (0..3)
+            .map(|i| shared.get_index(i).unwrap().get("name").unwrap())
+            .collect();
+        assert!(names[0].ptr_eq(names[2]));
+    }
+
+    #[test]
+    fn map_order_does_not_matter() {
+        let a: LlsdMap = [
+            ("x".to_string(), Llsd::Integer(1)),
+            ("y".to_string(), Llsd::Integer(2)),
+        ]
+        .into();
+        let b: LlsdMap = [
+            ("y".to_string(), Llsd::Integer(2)),
+            ("x".to_string(), Llsd::Integer(1)),
+        ]
+        .into();
+        let shared = dedup(&Llsd::Array(vec![Llsd::Map(a), Llsd::Map(b)]));
+        let items = shared.as_array().unwrap();
+        assert!(items[0].ptr_eq(&items[1]));
+        assert_eq!(items[0].get("y"), Some(&SharedLlsd::Integer(2)));
+        assert_eq!(items[0].get("z"), None);
+    }
+
+    #[test]
+    fn reals_are_compared_bitwise() {
+        let llsd = Llsd::Array(vec![
+            Llsd::Array(vec![Llsd::Real(0.0)]),
+            Llsd::Array(vec![Llsd::Real(-0.0)]),
+            Llsd::Array(vec![Llsd::Real(f64::NAN)]),
+            Llsd::Array(vec![Llsd::Real(f64::NAN)]),
+        ]);
+        let shared = dedup(&llsd);
+        let items = shared.as_array().unwrap();
+        assert!(!items[0].ptr_eq(&items[1]));
+        assert!(items[2].ptr_eq(&items[3]));
+    }
+
+    #[test]
+    fn sharing_spans_documents_until_cleared() {
+        let mut dedup = Deduplicator::new();
+        let first = dedup.dedup(&object(1, 7));
+        let second = dedup.dedup(&object(2, 7));
+        assert!(
+            first
+                .get("permissions")
+                .unwrap()
+                .ptr_eq(second.get("permissions").unwrap())
+        );
+        assert!(!dedup.is_empty());
+
+        dedup.clear();
+        assert!(dedup.is_empty());
+        let third = dedup.dedup(&object(3, 7));
+        assert!(
+            !first
+                .get("permissions")
+                .unwrap()
+                .ptr_eq(third.get("permissions").unwrap())
+        );
+    }
+
+    #[test]
+    fn key_interner_shares_keys_across_deduplicators() {
+        let keys = Arc::new(KeyInterner::new().with_max_keys(4));
+        let documents: Vec<SharedLlsd> = (0..2)
+            .map(|id| {
+                let keys = keys.clone();
+                std::thread::spawn(move || {
+                    Deduplicator::new()
+                        .with_key_interner(keys)
+                        .dedup(&object(id, id))
+                })
+                .join()
+                .unwrap()
+            })
+            .collect();
+        let key = |doc: &SharedLlsd, name: &str| {
+            let entries = doc.as_map().unwrap();
+            entries
+                .iter()
+                .find(|(k, _)| &**k == name)
+                .unwrap()
+                .0
+                .clone()
+        };
+        assert!(Arc::ptr_eq(
+            &key(&documents[0], "local_id"),
+            &key(&documents[1], "local_id")
+        ));
+
+        // Three top-level and three permission keys: the first four are
+        // stored, the rest miss every time.
+        let stats = keys.stats();
+        assert_eq!(stats.keys, 4);
+        assert_eq!((stats.hits, stats.misses), (4, 8));
+        assert!((stats.hit_rate() - 1.0 / 3.0).abs() < 1e-9);
+
+        keys.clear();
+        assert!(keys.is_empty());
+        assert_eq!(keys.stats(), InternStats::default());
+    }
+}
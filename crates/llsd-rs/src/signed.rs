@@ -0,0 +1,146 @@
+//! HMAC-signed envelopes.
+//!
+//! An envelope is a map carrying a value in the canonical binary encoding
+//! ([`binary::to_canonical_vec`]) together with its HMAC:
+//!
+//! ```text
+//! { "payload": <binary llsd>, "sig": <binary>, "alg": "hmac-sha256" }
+//! ```
+//!
+//! The envelope itself is ordinary LLSD and can be sent in any encoding; only
+//! the payload bytes are signed, so relays may re-encode the envelope freely.
+//!
+//! ```
+//! use llsd_rs::{Llsd, signed};
+//!
+//! let grant = Llsd::map().insert("cap", "seed").unwrap();
+//! let envelope = signed::sign(&grant, b"shared secret").unwrap();
+//! assert_eq!(signed::verify(&envelope, b"shared secret").unwrap(), grant);
+//! assert!(signed::verify(&envelope, b"wrong secret").is_err());
+//! ```
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{Llsd, LlsdError, LlsdMap, binary};
+
+/// The `alg` value of envelopes signed with HMAC-SHA-256.
+pub const HMAC_SHA256: &str = "hmac-sha256";
+
+/// Wrap `llsd` in an envelope signed with HMAC-SHA-256 under `key`.
+pub fn sign(llsd: &Llsd, key: &[u8]) -> Result<Llsd, anyhow::Error> {
+    let payload = binary::to_canonical_vec(llsd)?;
+    let sig = mac(key, &payload).finalize().into_bytes();
+    let mut envelope = LlsdMap::with_capacity(3);
+    envelope.insert("payload".into(), Llsd::Binary(payload));
+    envelope.insert("sig".into(), Llsd::Binary(sig.to_vec()));
+    envelope.insert("alg".into(), Llsd::String(HMAC_SHA256.into()));
+    Ok(Llsd::Map(envelope))
+}
+
+/// Check the signature of an envelope made by [`sign`] and return the value
+/// it carries. Fails if a field is missing, the algorithm is not
+/// [`HMAC_SHA256`], the signature does not match, or the payload is not
+/// canonical.
+pub fn verify(envelope: &Llsd, key: &[u8]) -> Result<Llsd, anyhow::Error> {
+    let map = envelope
+        .as_map()
+        .ok_or_else(|| LlsdError::invalid_type("map", envelope))?;
+    let field = |name: &str| map.get(name).ok_or_else(|| LlsdError::missing_field(name));
+    let alg = field("alg")?;
+    let alg = alg
+        .as_string()
+        .ok_or_else(|| LlsdError::invalid_type("string", alg).with_key("alg"))?;
+    if alg != HMAC_SHA256 {
+        return Err(
+            LlsdError::validation(format!("unsupported signature algorithm {alg}"))
+                .with_key("alg")
+                .into(),
+        );
+    }
+    let binary_field = |name: &str| -> Result<&[u8], LlsdError> {
+        let value = field(name)?;
+        value
+            .as_binary()
+            .map(Vec::as_slice)
+            .ok_or_else(|| LlsdError::invalid_type("binary", value).with_key(name))
+    };
+    let payload = binary_field("payload")?;
+    let sig = binary_field("sig")?;
+    // `verify_slice` compares in constant time.
+    if mac(key, payload).verify_slice(sig).is_err() {
+        return Err(LlsdError::validation("signature mismatch")
+            .with_key("sig")
+            .into());
+    }
+    binary::verify_canonical(payload)
+}
+
+/// HMAC-SHA-256 of `payload` under `key`, ready to finalize or verify.
+fn mac(key: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(payload);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+        mac(key, message).finalize().into_bytes().to_vec()
+    }
+
+    #[test]
+    fn hmac_sha256_rfc4231() {
+        assert_eq!(
+            hex(&hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // A key longer than the block size is hashed first.
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let mut grant = LlsdMap::new();
+        grant.insert("agent".into(), Llsd::Integer(42));
+        grant.insert("expires".into(), Llsd::Real(1.5));
+        let grant = Llsd::Map(grant);
+        let key = b"0123456789abcdef";
+        let envelope = sign(&grant, key).unwrap();
+
+        // The envelope survives a trip through another encoding.
+        let relayed = crate::xml::from_str(&crate::xml::to_string(&envelope).unwrap()).unwrap();
+        assert_eq!(verify(&relayed, key).unwrap(), grant);
+
+        let mut forged = envelope.clone();
+        let payload = forged.get_mut("payload").unwrap().as_binary_mut().unwrap();
+        *payload.last_mut().unwrap() ^= 1;
+        assert!(verify(&forged, key).is_err());
+
+        let mut other_alg = envelope.clone();
+        *other_alg.get_mut("alg").unwrap() = Llsd::String("none".into());
+        assert!(verify(&other_alg, key).is_err());
+
+        let mut missing = envelope.clone();
+        missing.as_map_mut().unwrap().remove("sig");
+        let err = verify(&missing, key).unwrap_err();
+        let err = err.downcast_ref::<LlsdError>().unwrap();
+        assert_eq!(err.path_string(), "sig");
+    }
+}
@@ -0,0 +1,254 @@
+//! Source locations of parsed values.
+//!
+//! The `*_spanned` parse functions in [`crate::notation`] and [`crate::xml`]
+//! return a [`Spanned`] value that records where in the input every value,
+//! down to each array element and map entry, was read from. Errors found
+//! after parsing, such as a failed conversion to a derived type, carry the
+//! path of the offending value, which [`Spanned::span_of`] turns back into a
+//! location in the source.
+//!
+//! ```
+//! use llsd_rs::{FromLlsd, notation};
+//!
+//! let text = "[i1,\n i2,\n {'x':i3}]";
+//! let doc = notation::from_str_spanned(text, 8).unwrap();
+//! let err = Vec::<i32>::from_llsd(&doc.value).unwrap_err();
+//! let span = doc.span_of(&err);
+//! assert_eq!(span.to_string(), "3:2");
+//! assert_eq!(&text[span.start..span.end], "{'x':i3}");
+//! ```
+
+use std::fmt;
+
+use crate::{LlsdError, PathSegment};
+
+/// Where a value was read from: its byte range and the line and column
+/// (both 1-based, columns counting characters) it starts at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl fmt::Display for Span {
+    /// `line:column`, the form editors accept.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Spans of the values in a document, as a tree shaped like the value.
+///
+/// For a map, the span of each entry's value keyed by its key; for an array,
+/// the span of each element. No children for scalars.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpanTree {
+    span: Span,
+    keys: Vec<String>,
+    children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    pub(crate) fn new(span: Span) -> Self {
+        Self {
+            span,
+            ..Self::default()
+        }
+    }
+
+    /// The span of this value.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The spans of map entry `key` (its last occurrence).
+    pub fn get(&self, key: &str) -> Option<&SpanTree> {
+        self.keys
+            .iter()
+            .rposition(|k| k == key)
+            .map(|i| &self.children[i])
+    }
+
+    /// The spans of array element `index`.
+    pub fn get_index(&self, index: usize) -> Option<&SpanTree> {
+        self.keys
+            .is_empty()
+            .then(|| self.children.get(index))
+            .flatten()
+    }
+
+    /// The spans of the value at `path`.
+    pub fn get_path(&self, path: &[PathSegment]) -> Option<&SpanTree> {
+        path.iter().try_fold(self, |tree, segment| match segment {
+            PathSegment::Key(key) => tree.get(key),
+            PathSegment::Index(index) => tree.get_index(*index),
+        })
+    }
+
+    pub(crate) fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
+    pub(crate) fn push_entry(&mut self, key: String, child: SpanTree) {
+        self.keys.push(key);
+        self.children.push(child);
+    }
+
+    pub(crate) fn push_item(&mut self, child: SpanTree) {
+        self.children.push(child);
+    }
+}
+
+/// A decoded value together with where each part of it was read from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub spans: SpanTree,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, spans: SpanTree) -> Self {
+        Self { value, spans }
+    }
+
+    /// The span of the whole value.
+    pub fn span(&self) -> Span {
+        self.spans.span
+    }
+
+    /// The span of the value at `path`, if the document has one there.
+    pub fn span_at(&self, path: &[PathSegment]) -> Option<Span> {
+        self.spans.get_path(path).map(SpanTree::span)
+    }
+
+    /// The span of the value `err` is about or, when it names a value the
+    /// document lacks (a missing field), of the closest enclosing one.
+    pub fn span_of(&self, err: &LlsdError) -> Span {
+        let mut tree = &self.spans;
+        for segment in err.path() {
+            let child = match segment {
+                PathSegment::Key(key) => tree.get(key),
+                PathSegment::Index(index) => tree.get_index(*index),
+            };
+            match child {
+                Some(child) => tree = child,
+                None => break,
+            }
+        }
+        tree.span
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// Builds a [`SpanTree`] for decoders that see containers open and close as
+/// events rather than by recursion.
+#[derive(Debug, Default)]
+pub(crate) struct SpanStack {
+    open: Vec<SpanTree>,
+    root: Option<SpanTree>,
+}
+
+impl SpanStack {
+    /// A value starting at `start`.
+    pub(crate) fn open(&mut self, start: Span) {
+        self.open.push(SpanTree::new(start));
+    }
+
+    /// Close the innermost value at byte `end`, as entry `key` of the
+    /// enclosing map, an element of the enclosing array, or the root.
+    pub(crate) fn close(&mut self, key: Option<String>, end: usize) {
+        let Some(mut tree) = self.open.pop() else {
+            return;
+        };
+        tree.span.end = end;
+        match (self.open.last_mut(), key) {
+            (Some(parent), Some(key)) => parent.push_entry(key, tree),
+            (Some(parent), None) => parent.push_item(tree),
+            (None, _) => self.root = Some(tree),
+        }
+    }
+
+    pub(crate) fn finish(self) -> SpanTree {
+        self.root.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Llsd, notation, xml};
+
+    fn text(source: &str, span: Option<Span>) -> &str {
+        let span = span.unwrap();
+        &source[span.start..span.end]
+    }
+
+    #[test]
+    fn notation_spans() {
+        let source = "{ 'a' : [ i1 , 'two' ],\n  'b':{'c':r1.5}, 'a':[!] }";
+        let doc = notation::from_str_spanned(source, 8).unwrap();
+        assert_eq!(text(source, Some(doc.span())), source);
+        // A repeated key resolves to its last value, as in the map.
+        assert_eq!(
+            text(source, doc.span_at(&[PathSegment::Key("a".into())])),
+            "[!]"
+        );
+        let c = [PathSegment::Key("b".into()), PathSegment::Key("c".into())];
+        let span = doc.span_at(&c).unwrap();
+        assert_eq!(text(source, Some(span)), "r1.5");
+        assert_eq!((span.line, span.column), (2, 12));
+        assert_eq!(doc.span_at(&[PathSegment::Index(0)]), None);
+
+        let doc = notation::from_str_spanned("  ", 8).unwrap();
+        assert_eq!(doc.value, Llsd::Undefined);
+    }
+
+    #[test]
+    fn xml_spans() {
+        let source = "<?xml version=\"1.0\"?>\n<llsd><map>\n  <key>list</key>\n  <array><integer>1</integer><undef/><string>é</string></array>\n</map></llsd>";
+        let doc = xml::from_str_spanned(source).unwrap();
+        let list = PathSegment::Key("list".into());
+        let span = doc.span_at(std::slice::from_ref(&list)).unwrap();
+        assert!(text(source, Some(span)).starts_with("<array><integer>"));
+        assert!(text(source, Some(span)).ends_with("</array>"));
+        assert_eq!((span.line, span.column), (4, 3));
+        let item = |i| doc.span_at(&[list.clone(), PathSegment::Index(i)]);
+        assert_eq!(text(source, item(0)), "<integer>1</integer>");
+        assert_eq!(text(source, item(1)), "<undef/>");
+        assert_eq!(text(source, item(2)), "<string>é</string>");
+        assert_eq!(doc.value, xml::from_str(source).unwrap());
+    }
+
+    #[test]
+    fn conversion_errors_point_into_the_source() {
+        let source = "{'items':[{'id':i1},\n{'id':'x'},\n{}]}";
+        let doc = notation::from_str_spanned(source, 8).unwrap();
+
+        let err = LlsdError::invalid_type("integer", &doc.value["items"][1]["id"])
+            .with_key("id")
+            .with_index(1)
+            .with_key("items");
+        assert_eq!(doc.span_of(&err).to_string(), "2:7");
+
+        // A missing field points at the map that lacks it.
+        let err = LlsdError::missing_field("id")
+            .with_index(2)
+            .with_key("items");
+        assert_eq!(text(source, Some(doc.span_of(&err))), "{}");
+    }
+}
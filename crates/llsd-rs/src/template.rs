@@ -0,0 +1,152 @@
+//! `${var}` placeholders, filled in by [`Llsd::substitute`].
+
+use std::collections::HashMap;
+
+use crate::{Llsd, LlsdError, LlsdMap, Uri};
+
+pub(crate) fn substitute(value: &Llsd, vars: &HashMap<String, Llsd>) -> Result<Llsd, LlsdError> {
+    match value {
+        Llsd::String(text) => match whole(text) {
+            Some(name) => vars.get(name).cloned().ok_or_else(|| undefined(name)),
+            None => splice(text, vars).map(Llsd::String),
+        },
+        Llsd::Uri(uri) => splice(uri.as_str(), vars).map(|text| Llsd::Uri(Uri::parse(&text))),
+        Llsd::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| substitute(item, vars).map_err(|e| e.with_index(i)))
+            .collect::<Result<_, _>>()
+            .map(Llsd::Array),
+        Llsd::Map(map) => {
+            let mut out = LlsdMap::with_capacity(map.len());
+            for (key, value) in map.iter() {
+                out.insert(
+                    key.clone(),
+                    substitute(value, vars).map_err(|e| e.with_key(key))?,
+                );
+            }
+            Ok(Llsd::Map(out))
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+/// The variable name when `text` is a single placeholder and nothing else.
+fn whole(text: &str) -> Option<&str> {
+    let name = text.strip_prefix("${")?.strip_suffix('}')?;
+    (!name.contains('}')).then_some(name)
+}
+
+fn splice(text: &str, vars: &HashMap<String, Llsd>) -> Result<String, LlsdError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix("${") {
+            out.push_str("${");
+            rest = after;
+        } else if let Some(after) = after.strip_prefix('{') {
+            let Some(end) = after.find('}') else {
+                return Err(LlsdError::custom(format_args!(
+                    "unterminated placeholder in {text:?}"
+                )));
+            };
+            let name = &after[..end];
+            push_text(
+                &mut out,
+                name,
+                vars.get(name).ok_or_else(|| undefined(name))?,
+            )?;
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Append the text form of a scalar.
+fn push_text(out: &mut String, name: &str, value: &Llsd) -> Result<(), LlsdError> {
+    use std::fmt::Write;
+
+    match value {
+        Llsd::String(s) => out.push_str(s),
+        Llsd::Uri(uri) => out.push_str(uri.as_str()),
+        Llsd::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        Llsd::Integer(i) => out.push_str(itoa::Buffer::new().format(*i)),
+        Llsd::Real(r) => write!(out, "{r}").expect("writing to a String"),
+        Llsd::Uuid(uuid) => write!(out, "{uuid}").expect("writing to a String"),
+        Llsd::Date(date) => out.push_str(&date.to_rfc3339()),
+        other => {
+            return Err(LlsdError::custom(format_args!(
+                "${{{name}}} is {}, which can only replace a whole string",
+                other.type_name()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn undefined(name: &str) -> LlsdError {
+    LlsdError::custom(format_args!("undefined variable ${{{name}}}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uuid;
+    use crate::{llsd_array, llsd_map};
+
+    fn vars() -> HashMap<String, Llsd> {
+        [
+            ("host", Llsd::from("sim1.example.com")),
+            ("port", Llsd::Integer(12043)),
+            ("agent", Llsd::Uuid(Uuid::nil())),
+            ("flags", llsd_array![1, 2]),
+            ("scale", Llsd::Real(0.5)),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let template = llsd_map! {
+            "url" => Llsd::Uri(Uri::parse("https://${host}:${port}/cap")),
+            "agent" => "${agent}",
+            "label" => "agent ${agent} at ${scale}x, $5 and $${literal}",
+            "flags" => "${flags}",
+            "${host}" => llsd_array!["${port}", 3, "plain"],
+        };
+        let filled = template.substitute(&vars()).unwrap();
+        assert_eq!(
+            filled,
+            llsd_map! {
+                "url" => Llsd::Uri(Uri::parse("https://sim1.example.com:12043/cap")),
+                "agent" => Uuid::nil(),
+                "label" => "agent 00000000-0000-0000-0000-000000000000 at 0.5x, $5 and ${literal}",
+                "flags" => llsd_array![1, 2],
+                "${host}" => llsd_array![12043, 3, "plain"],
+            }
+        );
+        assert!(filled["url"].as_uri().unwrap().is_url());
+    }
+
+    #[test]
+    fn errors_name_the_path() {
+        let vars = vars();
+        let err = llsd_map! { "a" => llsd_array!["x", "${missing}"] }
+            .substitute(&vars)
+            .unwrap_err();
+        assert_eq!(err.path_string(), "a[1]");
+        assert!(err.to_string().contains("undefined variable ${missing}"));
+
+        let err = Llsd::from("flags: ${flags}").substitute(&vars).unwrap_err();
+        assert!(err.to_string().contains("${flags} is array"), "{err}");
+        assert!(Llsd::from("${host").substitute(&vars).is_err());
+    }
+}
@@ -0,0 +1,457 @@
+//! A tiny in-process HTTP server for integration-testing LLSD clients.
+//!
+//! [`MockServer`] listens on a local port, answers each path from canned
+//! [`MockResponse`]s and records what it was sent as [`ReceivedRequest`]s
+//! with the body already decoded, so a test can check the LLSD its client
+//! posted instead of comparing XML by hand:
+//!
+//! ```
+//! use std::io::{Read, Write};
+//! use llsd_rs::{Llsd, test_server::{MockResponse, MockServer}};
+//!
+//! let server = MockServer::start().unwrap();
+//! server.respond("/cap/seed", MockResponse::llsd(&Llsd::Integer(1)).unwrap());
+//!
+//! let body = "<llsd><string>hello</string></llsd>";
+//! let mut stream = std::net::TcpStream::connect(server.addr()).unwrap();
+//! write!(
+//!     stream,
+//!     "POST /cap/seed HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+//!     body.len()
+//! )
+//! .unwrap();
+//! let mut reply = String::new();
+//! stream.read_to_string(&mut reply).unwrap();
+//! assert!(reply.starts_with("HTTP/1.1 200 OK"));
+//!
+//! let requests = server.requests();
+//! assert_eq!(requests[0].llsd, Some(Llsd::String("hello".into())));
+//! ```
+//!
+//! The server speaks just enough HTTP/1.1 for test clients: one request per
+//! connection, bodies sized by `Content-Length`, and `Connection: close` on
+//! every reply.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{
+    Llsd,
+    fingerprint::etag_listed,
+    rpc::{self, XmlRpc},
+};
+
+/// How long a connection may stall before the server gives up on it.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A canned reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// A 200 with `llsd` as LLSD XML, tagged with its
+    /// [cache key](Llsd::cache_key) as the `ETag`.
+    pub fn llsd(llsd: &Llsd) -> Result<Self, anyhow::Error> {
+        Ok(Self::new(200, crate::xml::to_string(llsd)?)
+            .with_header("Content-Type", "application/llsd+xml")
+            .with_header("ETag", llsd.cache_key()?.etag()))
+    }
+
+    /// A 200 with `rpc` as XML-RPC.
+    pub fn rpc(rpc: &XmlRpc) -> Result<Self, anyhow::Error> {
+        Ok(Self::new(200, rpc::to_string(rpc)?).with_header("Content-Type", "text/xml"))
+    }
+
+    /// An empty reply with `status`, such as a 503 to exercise retries.
+    pub fn status(status: u16) -> Self {
+        Self::new(status, Vec::new())
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// This response as a bodyless 304 when it is a 2xx whose `ETag` the
+    /// request's `If-None-Match` lists.
+    fn conditional(self, request: &ReceivedRequest) -> Self {
+        let etag = self.header("ETag");
+        let unchanged = (200..300).contains(&self.status)
+            && etag
+                .zip(request.header("If-None-Match"))
+                .is_some_and(|(etag, header)| etag_listed(header, etag));
+        match etag {
+            Some(etag) if unchanged => Self::status(304).with_header("ETag", etag),
+            _ => self,
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A request the server received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedRequest {
+    pub method: String,
+    /// The request target, query string included.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The body as LLSD in any format, or the parameters of an XML-RPC call;
+    /// `None` when it is neither.
+    pub llsd: Option<Llsd>,
+    /// The body as an XML-RPC call, when it is one.
+    pub rpc: Option<XmlRpc>,
+}
+
+impl ReceivedRequest {
+    /// The first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: HashMap<String, VecDeque<MockResponse>>,
+    requests: Vec<ReceivedRequest>,
+}
+
+impl State {
+    /// The next response queued for `path`; the last one queued keeps
+    /// answering once the others are used up.
+    fn response(&mut self, path: &str) -> MockResponse {
+        let route = path.split('?').next().unwrap_or(path);
+        match self.routes.get_mut(route) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap_or_else(not_found),
+            Some(queue) => queue.front().cloned().unwrap_or_else(not_found),
+            None => not_found(),
+        }
+    }
+}
+
+fn not_found() -> MockResponse {
+    MockResponse::new(404, "no response configured for this path")
+}
+
+/// An HTTP server on a local port, running until dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Listen on a free port of 127.0.0.1.
+    pub fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            move || serve(listener, &state, &stop)
+        });
+        Ok(Self {
+            addr,
+            state,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// `http://127.0.0.1:port`, without a trailing slash.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The URL of `path`, which should start with `/`.
+    pub fn url_for(&self, path: &str) -> String {
+        format!("{}{path}", self.url())
+    }
+
+    /// Queue `response` for requests to `path` (any method, query ignored).
+    /// Responses are used in order, the last one repeating; paths with none
+    /// get a 404.
+    pub fn respond(&self, path: &str, response: MockResponse) {
+        lock(&self.state)
+            .routes
+            .entry(path.to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// The requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        lock(&self.state).requests.clone()
+    }
+
+    /// Forget the received requests, keeping the routes.
+    pub fn clear_requests(&self) {
+        lock(&self.state).requests.clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A panicking test thread must not wedge the server for the others.
+fn lock(state: &Mutex<State>) -> std::sync::MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn serve(listener: TcpListener, state: &Mutex<State>, stop: &AtomicBool) {
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(stream) = stream {
+            // A client that hangs up mid-request only loses its own reply.
+            let _ = handle(stream, state);
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream, state: &Mutex<State>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let request = read_request(&mut BufReader::new(&stream))?;
+    let response = {
+        let mut state = lock(state);
+        let response = state.response(&request.path).conditional(&request);
+        state.requests.push(request);
+        response
+    };
+    write_response(&mut stream, &response)
+}
+
+fn read_request(reader: &mut impl BufRead) -> std::io::Result<ReceivedRequest> {
+    let invalid = |what: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, what);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("end of input in headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid("bad header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    let length = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .map(|(_, v)| v.parse::<usize>())
+        .transpose()
+        .map_err(|_| invalid("bad content length"))?
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let rpc = rpc::from_slice(&body)
+        .ok()
+        .filter(|rpc| matches!(rpc, XmlRpc::MethodCall(..)));
+    let llsd = match &rpc {
+        Some(rpc) => Some(rpc.llsd().clone()),
+        None if body.is_empty() => None,
+        None => crate::autodetect::from_slice(&body).ok(),
+    };
+    Ok(ReceivedRequest {
+        method,
+        path,
+        headers,
+        body,
+        llsd,
+        rpc,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &MockResponse) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    /// Send a request and return the status line and body of the reply.
+    fn send(server: &MockServer, method: &str, path: &str, body: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: test\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        let (head, body) = reply.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[test]
+    fn queued_responses_then_the_last_repeats() {
+        let server = MockServer::start().unwrap();
+        server.respond(
+            "/cap",
+            MockResponse::status(503).with_header("Retry-After", "1"),
+        );
+        server.respond("/cap", MockResponse::llsd(&Llsd::Integer(2)).unwrap());
+
+        assert_eq!(
+            send(&server, "GET", "/cap", "").0,
+            "HTTP/1.1 503 Service Unavailable"
+        );
+        for _ in 0..2 {
+            let (status, body) = send(&server, "GET", "/cap?x=1", "");
+            assert_eq!(status, "HTTP/1.1 200 OK");
+            assert_eq!(crate::xml::from_str(&body).unwrap(), Llsd::Integer(2));
+        }
+        assert_eq!(
+            send(&server, "GET", "/other", "").0,
+            "HTTP/1.1 404 Not Found"
+        );
+        assert_eq!(server.requests().len(), 4);
+        server.clear_requests();
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn honors_if_none_match() {
+        let server = MockServer::start().unwrap();
+        let value = Llsd::String("unchanged".into());
+        let etag = value.cache_key().unwrap().etag();
+        server.respond("/events", MockResponse::llsd(&value).unwrap());
+
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        write!(
+            stream,
+            "GET /events HTTP/1.1\r\nIf-None-Match: W/{etag}\r\n\r\n"
+        )
+        .unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 304 Not Modified"));
+        assert!(reply.contains(&format!("ETag: {etag}")));
+        assert!(reply.ends_with("\r\n\r\n"));
+
+        let (status, body) = send(&server, "GET", "/events", "");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(crate::xml::from_str(&body).unwrap(), value);
+    }
+
+    #[test]
+    fn records_decoded_bodies() {
+        let server = MockServer::start().unwrap();
+        let reply = XmlRpc::MethodResponse(Llsd::Boolean(true));
+        server.respond("/login", MockResponse::rpc(&reply).unwrap());
+
+        let call = XmlRpc::MethodCall("login_to_simulator".into(), Llsd::Integer(1));
+        let (status, body) = send(&server, "POST", "/login", &rpc::to_string(&call).unwrap());
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(rpc::from_str(&body).unwrap(), reply);
+        send(&server, "POST", "/cap", "[i1,'two']");
+        send(&server, "POST", "/cap", "not llsd");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].header("content-type"), Some("text/xml"));
+        assert_eq!(requests[0].rpc, Some(call));
+        assert_eq!(requests[0].llsd, Some(Llsd::Integer(1)));
+        assert_eq!(
+            requests[1].llsd,
+            Some(Llsd::Array(vec![
+                Llsd::Integer(1),
+                Llsd::String("two".into())
+            ]))
+        );
+        assert_eq!(requests[1].rpc, None);
+        assert_eq!(requests[2].llsd, None);
+        assert_eq!(requests[2].body, b"not llsd");
+    }
+}
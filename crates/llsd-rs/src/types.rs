@@ -0,0 +1,832 @@
+//! Typed wrappers for the composite values Second Life messages carry.
+//!
+//! Vectors, rotations and colors travel as arrays of reals and 64-bit handles
+//! as 8-byte big-endian binaries, the encodings the viewer's `ll_sd_from_*`
+//! helpers produce. Each type implements [`FromLlsd`] / [`IntoLlsd`] (so it
+//! can be a field of a derived struct) and converts to and from plain arrays,
+//! and, with the `glam` and `nalgebra` features, those crates' vector and
+//! quaternion types:
+//!
+//! ```
+//! use llsd_rs::{FromLlsd, IntoLlsd, Llsd, types::Vector3};
+//!
+//! let llsd = Llsd::Array(vec![Llsd::Real(128.0), Llsd::Real(64.5), Llsd::Real(22.0)]);
+//! let position = Vector3::from_llsd(&llsd).unwrap();
+//! assert_eq!(<[f32; 3]>::from(position), [128.0, 64.5, 22.0]);
+//! assert_eq!(position.to_llsd(), llsd);
+//! ```
+
+use crate::{Date, FromLlsd, IntoLlsd, Llsd, LlsdError, escape::hex_value};
+
+/// Read an array of exactly `N` numbers.
+fn reals<T: FromLlsd + Copy + Default, const N: usize>(llsd: &Llsd) -> Result<[T; N], LlsdError> {
+    match llsd {
+        Llsd::Array(items) if items.len() == N => {
+            let mut out = [T::default(); N];
+            for (i, (slot, item)) in out.iter_mut().zip(items).enumerate() {
+                *slot = T::from_llsd(item).map_err(|e| e.with_index(i))?;
+            }
+            Ok(out)
+        }
+        Llsd::Array(items) => Err(LlsdError::invalid_length(N, items.len())),
+        _ => Err(LlsdError::invalid_type("array", llsd)),
+    }
+}
+
+/// `From`/`TryFrom` bridges matching what the derives generate.
+macro_rules! bridge {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Llsd {
+                fn from(v: $t) -> Self {
+                    v.to_llsd()
+                }
+            }
+
+            impl From<&$t> for Llsd {
+                fn from(v: &$t) -> Self {
+                    v.to_llsd()
+                }
+            }
+
+            impl TryFrom<&Llsd> for $t {
+                type Error = anyhow::Error;
+
+                fn try_from(llsd: &Llsd) -> Result<Self, Self::Error> {
+                    Ok(<$t>::from_llsd(llsd)?)
+                }
+            }
+
+            impl TryFrom<Llsd> for $t {
+                type Error = anyhow::Error;
+
+                fn try_from(llsd: Llsd) -> Result<Self, Self::Error> {
+                    Ok(<$t>::from_llsd(&llsd)?)
+                }
+            }
+        )*
+    };
+}
+
+/// A single-precision vector (`LLVector3`): region positions, velocities,
+/// scales. Encoded as `[x, y, z]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3 {
+    pub const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<[f32; 3]> for Vector3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vector3> for [f32; 3] {
+    fn from(v: Vector3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl FromLlsd for Vector3 {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        reals::<f32, 3>(llsd).map(Self::from)
+    }
+}
+
+impl IntoLlsd for Vector3 {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Array(vec![
+            Llsd::Real(self.x.into()),
+            Llsd::Real(self.y.into()),
+            Llsd::Real(self.z.into()),
+        ])
+    }
+}
+
+/// A double-precision vector (`LLVector3d`): global (grid) positions.
+/// Encoded as `[x, y, z]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vector3d {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vector3d {
+    pub const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<[f64; 3]> for Vector3d {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vector3d> for [f64; 3] {
+    fn from(v: Vector3d) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl FromLlsd for Vector3d {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        reals::<f64, 3>(llsd).map(Self::from)
+    }
+}
+
+impl IntoLlsd for Vector3d {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Array(vec![
+            Llsd::Real(self.x),
+            Llsd::Real(self.y),
+            Llsd::Real(self.z),
+        ])
+    }
+}
+
+/// A rotation (`LLQuaternion`). Encoded as `[x, y, z, w]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// `[x, y, z, w]`, the component order of `glam::Quat::from_array` and of the
+/// wire format. nalgebra stores the same order but its `Quaternion::new`
+/// takes `w` first.
+impl From<[f32; 4]> for Quaternion {
+    fn from([x, y, z, w]: [f32; 4]) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+impl From<Quaternion> for [f32; 4] {
+    fn from(q: Quaternion) -> Self {
+        [q.x, q.y, q.z, q.w]
+    }
+}
+
+impl FromLlsd for Quaternion {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        reals::<f32, 4>(llsd).map(Self::from)
+    }
+}
+
+impl IntoLlsd for Quaternion {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Array(
+            <[f32; 4]>::from(*self)
+                .map(|v| Llsd::Real(v.into()))
+                .to_vec(),
+        )
+    }
+}
+
+/// A color with alpha (`LLColor4`), components nominally in `0.0..=1.0`.
+/// Encoded as `[r, g, b, a]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Color4 {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color4 {
+    pub const WHITE: Self = Self::new(1.0, 1.0, 1.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl From<[f32; 4]> for Color4 {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl From<Color4> for [f32; 4] {
+    fn from(c: Color4) -> Self {
+        [c.r, c.g, c.b, c.a]
+    }
+}
+
+impl FromLlsd for Color4 {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        reals::<f32, 4>(llsd).map(Self::from)
+    }
+}
+
+impl IntoLlsd for Color4 {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Array(
+            <[f32; 4]>::from(*self)
+                .map(|v| Llsd::Real(v.into()))
+                .to_vec(),
+        )
+    }
+}
+
+/// A 64-bit unsigned value (region handles, 64-bit flags). LLSD integers are
+/// 32-bit, so these travel as an 8-byte big-endian binary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct U64(pub u64);
+
+impl From<u64> for U64 {
+    fn from(v: u64) -> Self {
+        Self(v)
+    }
+}
+
+impl From<U64> for u64 {
+    fn from(v: U64) -> Self {
+        v.0
+    }
+}
+
+impl FromLlsd for U64 {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Binary(bytes) => <[u8; 8]>::try_from(bytes.as_slice())
+                .map(|bytes| Self(u64::from_be_bytes(bytes)))
+                .map_err(|_| LlsdError::invalid_length(8, bytes.len())),
+            _ => Err(LlsdError::invalid_type("binary", llsd)),
+        }
+    }
+}
+
+impl IntoLlsd for U64 {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Binary(self.0.to_be_bytes().to_vec())
+    }
+}
+
+/// A date as whole seconds since the Unix epoch, for code that keeps its
+/// own time types rather than [`Date`]. Written as an LLSD date; reads dates,
+/// RFC 3339 strings and numbers of seconds (as the viewer's `asDate` does),
+/// dropping any fraction of a second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UnixTime(pub i64);
+
+impl From<i64> for UnixTime {
+    fn from(v: i64) -> Self {
+        Self(v)
+    }
+}
+
+impl From<UnixTime> for i64 {
+    fn from(v: UnixTime) -> Self {
+        v.0
+    }
+}
+
+impl FromLlsd for UnixTime {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Integer(seconds) => Ok(Self((*seconds).into())),
+            Llsd::Real(seconds) if seconds.is_finite() => Ok(Self(seconds.floor() as i64)),
+            _ => Date::from_llsd(llsd).map(|d| Self(d.timestamp())),
+        }
+    }
+}
+
+/// Seconds past [`Date`]'s range (about 262,000 years either way) are
+/// written as the epoch.
+impl IntoLlsd for UnixTime {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Date(Date::from_timestamp(self.0, 0).unwrap_or_default())
+    }
+}
+
+/// The value of an LLSD UUID: the `uuid` crate's with the default `uuid`
+/// feature, [`UuidBytes`] without it.
+#[cfg(feature = "uuid")]
+pub type Uuid = uuid::Uuid;
+/// The value of an LLSD UUID: the `uuid` crate's with the default `uuid`
+/// feature, [`UuidBytes`] without it.
+#[cfg(not(feature = "uuid"))]
+pub type Uuid = UuidBytes;
+
+/// Why a UUID didn't parse.
+#[cfg(feature = "uuid")]
+pub type UuidError = uuid::Error;
+/// Why a UUID didn't parse.
+#[cfg(not(feature = "uuid"))]
+pub type UuidError = InvalidUuid;
+
+/// A UUID as its 16 bytes, for code that doesn't use the `uuid` crate's
+/// type, and the [`Uuid`] of builds without the `uuid` feature. It has the
+/// subset of `uuid::Uuid`'s methods the codecs use (`nil`, `from_bytes`,
+/// `parse_str`, `from_u128`...) and displays as lowercase hyphenated hex.
+/// Written as an LLSD UUID; reads UUIDs and UUID strings, and with the `uuid`
+/// feature also 16-byte binaries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UuidBytes(pub [u8; 16]);
+
+impl UuidBytes {
+    pub const fn nil() -> Self {
+        Self([0; 16])
+    }
+
+    pub const fn is_nil(&self) -> bool {
+        u128::from_be_bytes(self.0) == 0
+    }
+
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, InvalidUuid> {
+        <[u8; 16]>::try_from(bytes)
+            .map(Self)
+            .map_err(|_| InvalidUuid::Length(bytes.len()))
+    }
+
+    pub const fn from_u128(v: u128) -> Self {
+        Self(v.to_be_bytes())
+    }
+
+    pub const fn from_u64_pair(high: u64, low: u64) -> Self {
+        Self::from_u128(((high as u128) << 64) | low as u128)
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    pub const fn into_bytes(self) -> [u8; 16] {
+        self.0
+    }
+
+    pub const fn as_u128(&self) -> u128 {
+        u128::from_be_bytes(self.0)
+    }
+
+    /// Read the hyphenated, simple (32 digits), braced or `urn:uuid:` form,
+    /// like `uuid::Uuid::parse_str`; the last two wrap the hyphenated form.
+    pub fn parse_str(text: &str) -> Result<Self, InvalidUuid> {
+        let wrapped = text
+            .strip_prefix("urn:uuid:")
+            .or_else(|| text.strip_prefix('{').and_then(|t| t.strip_suffix('}')));
+        let digits: Vec<u8> = match wrapped {
+            None if text.len() == 32 => text.bytes().collect(),
+            _ => {
+                let bytes = wrapped.unwrap_or(text).as_bytes();
+                if bytes.len() != 36 || [8, 13, 18, 23].iter().any(|&i| bytes[i] != b'-') {
+                    return Err(InvalidUuid::Text);
+                }
+                bytes
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| ![8, 13, 18, 23].contains(i))
+                    .map(|(_, b)| *b)
+                    .collect()
+            }
+        };
+        let mut out = [0; 16];
+        for (byte, pair) in out.iter_mut().zip(digits.chunks_exact(2)) {
+            let (Some(high), Some(low)) = (hex_value(pair[0]), hex_value(pair[1])) else {
+                return Err(InvalidUuid::Text);
+            };
+            *byte = high << 4 | low;
+        }
+        Ok(Self(out))
+    }
+
+    pub fn try_parse(text: &str) -> Result<Self, InvalidUuid> {
+        Self::parse_str(text)
+    }
+}
+
+impl std::fmt::Display for UuidBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for UuidBytes {
+    type Err = InvalidUuid;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse_str(text)
+    }
+}
+
+/// A UUID [`UuidBytes`] couldn't read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidUuid {
+    #[error("invalid UUID text")]
+    Text,
+    #[error("invalid UUID length: expected 16 bytes, found {0}")]
+    Length(usize),
+}
+
+/// `id` as lowercase hyphenated text, written into `buf`.
+pub(crate) fn uuid_str<'a>(id: &Uuid, buf: &'a mut [u8; 36]) -> &'a str {
+    #[cfg(feature = "uuid")]
+    return id.hyphenated().encode_lower(buf);
+    #[cfg(not(feature = "uuid"))]
+    {
+        use std::io::Write;
+        write!(&mut buf[..], "{id}").expect("a UUID is 36 characters");
+        std::str::from_utf8(buf).expect("hex digits and hyphens")
+    }
+}
+
+impl From<[u8; 16]> for UuidBytes {
+    fn from(v: [u8; 16]) -> Self {
+        Self(v)
+    }
+}
+
+impl From<UuidBytes> for [u8; 16] {
+    fn from(v: UuidBytes) -> Self {
+        v.0
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for UuidBytes {
+    fn from(v: uuid::Uuid) -> Self {
+        Self(v.into_bytes())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<UuidBytes> for uuid::Uuid {
+    fn from(v: UuidBytes) -> Self {
+        uuid::Uuid::from_bytes(v.0)
+    }
+}
+
+/// Without the `uuid` feature, the [`Uuid`] conversions apply instead.
+#[cfg(feature = "uuid")]
+impl FromLlsd for UuidBytes {
+    fn from_llsd(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::Binary(bytes) => <[u8; 16]>::try_from(bytes.as_slice())
+                .map(Self)
+                .map_err(|_| LlsdError::invalid_length(16, bytes.len())),
+            _ => uuid::Uuid::from_llsd(llsd).map(Self::from),
+        }
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl IntoLlsd for UuidBytes {
+    fn to_llsd(&self) -> Llsd {
+        Llsd::Uuid(uuid::Uuid::from_bytes(self.0))
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Vec3> for Vector3 {
+    fn from(v: glam::Vec3) -> Self {
+        v.to_array().into()
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Vector3> for glam::Vec3 {
+    fn from(v: Vector3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::DVec3> for Vector3d {
+    fn from(v: glam::DVec3) -> Self {
+        v.to_array().into()
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Vector3d> for glam::DVec3 {
+    fn from(v: Vector3d) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Quat> for Quaternion {
+    fn from(q: glam::Quat) -> Self {
+        q.to_array().into()
+    }
+}
+
+/// Taken as is: glam expects a unit quaternion, so normalize values read from
+/// the network first if they might not be.
+#[cfg(feature = "glam")]
+impl From<Quaternion> for glam::Quat {
+    fn from(q: Quaternion) -> Self {
+        Self::from_xyzw(q.x, q.y, q.z, q.w)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Vec4> for Color4 {
+    fn from(c: glam::Vec4) -> Self {
+        c.to_array().into()
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Color4> for glam::Vec4 {
+    fn from(c: Color4) -> Self {
+        Self::new(c.r, c.g, c.b, c.a)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Vector3<f32>> for Vector3 {
+    fn from(v: nalgebra::Vector3<f32>) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Vector3> for nalgebra::Vector3<f32> {
+    fn from(v: Vector3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Vector3<f64>> for Vector3d {
+    fn from(v: nalgebra::Vector3<f64>) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Vector3d> for nalgebra::Vector3<f64> {
+    fn from(v: Vector3d) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Quaternion<f32>> for Quaternion {
+    fn from(q: nalgebra::Quaternion<f32>) -> Self {
+        Self::new(q.i, q.j, q.k, q.w)
+    }
+}
+
+/// nalgebra's constructor takes `w` first; the components keep their names.
+#[cfg(feature = "nalgebra")]
+impl From<Quaternion> for nalgebra::Quaternion<f32> {
+    fn from(q: Quaternion) -> Self {
+        Self::new(q.w, q.x, q.y, q.z)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::UnitQuaternion<f32>> for Quaternion {
+    fn from(q: nalgebra::UnitQuaternion<f32>) -> Self {
+        q.into_inner().into()
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Vector4<f32>> for Color4 {
+    fn from(c: nalgebra::Vector4<f32>) -> Self {
+        Self::new(c.x, c.y, c.z, c.w)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Color4> for nalgebra::Vector4<f32> {
+    fn from(c: Color4) -> Self {
+        Self::new(c.r, c.g, c.b, c.a)
+    }
+}
+
+bridge!(Vector3, Vector3d, Quaternion, Color4, U64, UnixTime);
+#[cfg(feature = "uuid")]
+bridge!(UuidBytes);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: FromLlsd + IntoLlsd + PartialEq + std::fmt::Debug>(value: T) {
+        let llsd = value.to_llsd();
+        let encoded = crate::binary::to_vec(&llsd).unwrap();
+        let decoded = crate::binary::from_slice(&encoded).unwrap();
+        assert_eq!(T::from_llsd(&decoded).unwrap(), value);
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(Vector3::new(128.0, 64.5, 22.25));
+        round_trip(Vector3d::new(256_000.5, 512_000.25, 4096.0));
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        round_trip(Quaternion::new(0.0, 0.0, half, half));
+        round_trip(Color4::new(1.0, 0.5, 0.25, 1.0));
+        round_trip(U64(0x0003_e800_0003_e800));
+        round_trip(UnixTime(1_700_000_000));
+        round_trip(UnixTime(-86_400));
+        round_trip(UuidBytes(*b"0123456789abcdef"));
+    }
+
+    #[test]
+    fn unix_time_reads_dates_strings_and_numbers() {
+        let read = |llsd: Llsd| UnixTime::from_llsd(&llsd).unwrap();
+        assert_eq!(
+            read(Llsd::String("2001-09-09T01:46:40.5Z".into())),
+            UnixTime(1_000_000_000)
+        );
+        assert_eq!(read(Llsd::Real(12.75)), UnixTime(12));
+        assert_eq!(read(Llsd::Integer(-3)), UnixTime(-3));
+        assert_eq!(UnixTime(0).to_llsd(), Llsd::Date(Default::default()));
+        assert!(UnixTime::from_llsd(&Llsd::Real(f64::NAN)).is_err());
+        assert!(UnixTime::from_llsd(&Llsd::Boolean(true)).is_err());
+    }
+
+    #[test]
+    fn uuid_bytes_parse_and_format_like_uuid() {
+        let id = UuidBytes::from_u64_pair(0x0102_0304_0506_0708, 0x090a_0b0c_0d0e_0f10);
+        let text = "01020304-0506-0708-090a-0b0c0d0e0f10";
+        assert_eq!(id.to_string(), text);
+        assert_eq!(id.as_u128(), 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        assert_eq!(UuidBytes::parse_str(text), Ok(id));
+        assert_eq!(UuidBytes::parse_str(&text.to_uppercase()), Ok(id));
+        assert_eq!(UuidBytes::parse_str(&text.replace('-', "")), Ok(id));
+        assert_eq!(UuidBytes::parse_str(&format!("{{{text}}}")), Ok(id));
+        assert_eq!(format!("urn:uuid:{text}").parse(), Ok(id));
+        assert_eq!(uuid_str(&Uuid::from_bytes(id.0), &mut [0; 36]), text);
+        for bad in [
+            "+0000000-0000-0000-0000-000000000000",
+            "+0000000000000000000000000000000",
+            "{0102030405060708090a0b0c0d0e0f10}",
+            "",
+            "nope",
+            "01020304-0506-0708-090a-0b0c0d0e0f1g",
+            "0102030405-06-0708-090a-0b0c0d0e0f10",
+        ] {
+            assert_eq!(UuidBytes::parse_str(bad), Err(InvalidUuid::Text));
+        }
+        assert_eq!(
+            UuidBytes::from_slice(&[0; 15]),
+            Err(InvalidUuid::Length(15))
+        );
+        assert!(UuidBytes::nil().is_nil() && !id.is_nil());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_bytes_read_uuids_strings_and_binaries() {
+        let id = uuid::Uuid::from_u128(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        let bytes = UuidBytes::from(id);
+        assert_eq!(UuidBytes::from_llsd(&Llsd::Uuid(id)).unwrap(), bytes);
+        assert_eq!(
+            UuidBytes::from_llsd(&Llsd::String(id.to_string())).unwrap(),
+            bytes
+        );
+        assert_eq!(
+            UuidBytes::from_llsd(&Llsd::Binary(bytes.0.to_vec())).unwrap(),
+            bytes
+        );
+        assert_eq!(uuid::Uuid::from(bytes), id);
+        assert!(UuidBytes::from_llsd(&Llsd::Binary(vec![0; 15])).is_err());
+        assert!(UuidBytes::from_llsd(&Llsd::String("nope".into())).is_err());
+        for text in [
+            "01020304-0506-0708-090A-0b0c0d0e0f10",
+            "0102030405060708090a0b0c0d0e0f10",
+            "{01020304-0506-0708-090a-0b0c0d0e0f10}",
+            "urn:uuid:01020304-0506-0708-090a-0b0c0d0e0f10",
+            "+0000000-0000-0000-0000-000000000000",
+            "{0102030405060708090a0b0c0d0e0f10}",
+            "01020304-0506-0708-090a-0b0c0d0e0f1",
+        ] {
+            assert_eq!(
+                UuidBytes::parse_str(text).ok(),
+                uuid::Uuid::parse_str(text).ok().map(UuidBytes::from),
+                "{text}"
+            );
+        }
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn glam_round_trips() {
+        let position = Vector3::new(128.0, 64.5, 22.25);
+        assert_eq!(
+            glam::Vec3::from(position),
+            glam::Vec3::new(128.0, 64.5, 22.25)
+        );
+        assert_eq!(Vector3::from(glam::Vec3::from(position)), position);
+        let global = Vector3d::new(256_000.5, 512_000.25, 4096.0);
+        assert_eq!(Vector3d::from(glam::DVec3::from(global)), global);
+        let turn = glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        let rotation = Quaternion::from(turn);
+        assert_eq!(<[f32; 4]>::from(rotation), turn.to_array());
+        assert_eq!(glam::Quat::from(rotation), turn);
+        let color = Color4::new(1.0, 0.5, 0.25, 1.0);
+        assert_eq!(
+            glam::Vec4::from(color),
+            glam::Vec4::new(1.0, 0.5, 0.25, 1.0)
+        );
+        assert_eq!(Color4::from(glam::Vec4::from(color)), color);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn nalgebra_round_trips() {
+        let position = Vector3::new(128.0, 64.5, 22.25);
+        assert_eq!(
+            nalgebra::Vector3::from(position),
+            nalgebra::Vector3::new(128.0, 64.5, 22.25)
+        );
+        assert_eq!(Vector3::from(nalgebra::Vector3::from(position)), position);
+        let global = Vector3d::new(256_000.5, 512_000.25, 4096.0);
+        assert_eq!(Vector3d::from(nalgebra::Vector3::from(global)), global);
+        let turn = nalgebra::UnitQuaternion::from_axis_angle(
+            &nalgebra::Vector3::z_axis(),
+            std::f32::consts::FRAC_PI_2,
+        );
+        let rotation = Quaternion::from(turn);
+        assert_eq!(rotation.w, turn.w);
+        assert_eq!(rotation.z, turn.k);
+        assert_eq!(nalgebra::Quaternion::from(rotation), turn.into_inner());
+        let color = Color4::new(1.0, 0.5, 0.25, 1.0);
+        assert_eq!(
+            nalgebra::Vector4::from(color),
+            nalgebra::Vector4::new(1.0, 0.5, 0.25, 1.0)
+        );
+        assert_eq!(Color4::from(nalgebra::Vector4::from(color)), color);
+    }
+
+    #[test]
+    fn encodings() {
+        assert_eq!(
+            Quaternion::IDENTITY.to_llsd(),
+            Llsd::Array(vec![
+                Llsd::Real(0.0),
+                Llsd::Real(0.0),
+                Llsd::Real(0.0),
+                Llsd::Real(1.0)
+            ])
+        );
+        assert_eq!(U64(1).to_llsd(), Llsd::Binary(vec![0, 0, 0, 0, 0, 0, 0, 1]));
+        // Integers are accepted where reals are expected.
+        let llsd = Llsd::Array(vec![Llsd::Integer(1), Llsd::Real(2.0), Llsd::Integer(3)]);
+        assert_eq!(
+            Vector3::try_from(&llsd).unwrap(),
+            Vector3::new(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn errors_carry_the_path() {
+        let short = Llsd::Array(vec![Llsd::Real(1.0); 2]);
+        assert!(matches!(
+            Vector3::from_llsd(&short).unwrap_err().kind(),
+            crate::LlsdErrorKind::InvalidLength {
+                expected: 3,
+                found: 2
+            }
+        ));
+        let bad = Llsd::Array(vec![Llsd::Real(1.0), Llsd::Undefined, Llsd::Real(1.0)]);
+        assert_eq!(Vector3::from_llsd(&bad).unwrap_err().path_string(), "[1]");
+        assert!(U64::from_llsd(&Llsd::Binary(vec![0; 4])).is_err());
+        assert!(U64::from_llsd(&Llsd::Integer(1)).is_err());
+    }
+}
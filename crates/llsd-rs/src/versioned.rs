@@ -0,0 +1,276 @@
+//! Schema versions for stored documents.
+//!
+//! A versioned document is a map with an integer `version` key. [`Migrations`]
+//! holds the functions that take a document from one version to the next and
+//! applies them in order, so code that loads settings written by any older
+//! release only ever sees the latest shape:
+//!
+//! ```
+//! use llsd_rs::{Llsd, llsd_map, versioned::Migrations};
+//!
+//! let mut migrations = Migrations::new();
+//! migrations
+//!     .register(1, 2, |doc| {
+//!         // Version 2 renamed `far_clip` to `draw_distance`.
+//!         let map = doc.as_map_mut().unwrap();
+//!         if let Some(value) = map.remove("far_clip") {
+//!             map.insert("draw_distance".into(), value);
+//!         }
+//!         Ok(())
+//!     })
+//!     .register(2, 3, |doc| {
+//!         doc.as_map_mut().unwrap().insert("vsync".into(), Llsd::Boolean(true));
+//!         Ok(())
+//!     });
+//!
+//! let mut doc = llsd_map! { "version" => 1, "far_clip" => 128.0 };
+//! assert_eq!(migrations.migrate_to_latest(&mut doc).unwrap(), 3);
+//! assert_eq!(
+//!     doc,
+//!     llsd_map! { "version" => 3, "draw_distance" => 128.0, "vsync" => true }
+//! );
+//! ```
+//!
+//! A migration may skip versions (`register(2, 4, ..)`); when several start
+//! at the same version the one reaching furthest is used. Migrations only
+//! change the document's content: the version key is updated after each step.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+
+use crate::{Llsd, LlsdError};
+
+/// The key holding a document's version, unless
+/// [`Migrations::with_version_key`] names another.
+pub const VERSION_KEY: &str = "version";
+
+type Migration = Box<dyn Fn(&mut Llsd) -> Result<(), anyhow::Error> + Send + Sync>;
+
+/// A set of migrations between document versions.
+pub struct Migrations {
+    key: String,
+    /// Migrations by the version they start from, then the one they reach.
+    steps: BTreeMap<u32, BTreeMap<u32, Migration>>,
+    latest: u32,
+    unversioned: Option<u32>,
+}
+
+impl std::fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let steps: Vec<(u32, u32)> = self
+            .steps
+            .iter()
+            .flat_map(|(from, to)| to.keys().map(|to| (*from, *to)))
+            .collect();
+        f.debug_struct("Migrations")
+            .field("key", &self.key)
+            .field("steps", &steps)
+            .field("latest", &self.latest)
+            .field("unversioned", &self.unversioned)
+            .finish()
+    }
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self {
+            key: VERSION_KEY.to_string(),
+            steps: BTreeMap::new(),
+            latest: 0,
+            unversioned: None,
+        }
+    }
+
+    pub fn with_version_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Treat documents without a version key as `version`, such as the
+    /// settings written before versions were introduced. By default they
+    /// fail to migrate.
+    pub fn with_unversioned(mut self, version: u32) -> Self {
+        self.unversioned = Some(version);
+        self
+    }
+
+    /// Raise the latest version above the last one a migration reaches, for
+    /// a version bump that needs no migration.
+    pub fn with_latest(mut self, version: u32) -> Self {
+        self.latest = self.latest.max(version);
+        self
+    }
+
+    /// Add the migration from version `from` to version `to`, replacing any
+    /// registered for the same pair.
+    ///
+    /// # Panics
+    ///
+    /// If `to` is not greater than `from`.
+    pub fn register<F>(&mut self, from: u32, to: u32, migration: F) -> &mut Self
+    where
+        F: Fn(&mut Llsd) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        assert!(
+            to > from,
+            "migration from version {from} to {to} goes backwards"
+        );
+        self.steps
+            .entry(from)
+            .or_default()
+            .insert(to, Box::new(migration));
+        self.latest = self.latest.max(to);
+        self
+    }
+
+    pub fn version_key(&self) -> &str {
+        &self.key
+    }
+
+    /// The highest version migrations reach.
+    pub fn latest(&self) -> u32 {
+        self.latest
+    }
+
+    /// The version `doc` is at: its version key, or the
+    /// [unversioned](Self::with_unversioned) version when it has none.
+    pub fn version_of(&self, doc: &Llsd) -> Result<u32, LlsdError> {
+        match doc.as_map().and_then(|map| map.get(&self.key)) {
+            Some(version) => u32::try_from(version).map_err(|_| {
+                LlsdError::invalid_type("version number", version).with_key(&self.key)
+            }),
+            None if doc.is_map() => self
+                .unversioned
+                .ok_or_else(|| LlsdError::missing_field(&self.key)),
+            None => Err(LlsdError::invalid_type("map", doc)),
+        }
+    }
+
+    /// Migrate `doc` to the latest version and return that version.
+    pub fn migrate_to_latest(&self, doc: &mut Llsd) -> Result<u32, anyhow::Error> {
+        self.migrate_to(doc, self.latest)
+    }
+
+    /// Migrate `doc` up to version `target` and return it. Documents already
+    /// there are left alone; on failure `doc` is left as it was.
+    pub fn migrate_to(&self, doc: &mut Llsd, target: u32) -> Result<u32, anyhow::Error> {
+        let mut version = self.version_of(doc)?;
+        if version == target {
+            return Ok(version);
+        }
+        if version > target {
+            return Err(LlsdError::custom(format!(
+                "document version {version} is newer than version {target}"
+            ))
+            .into());
+        }
+        let mut migrated = doc.clone();
+        while version < target {
+            let (next, migration) = self
+                .steps
+                .get(&version)
+                .and_then(|steps| steps.range(..=target).next_back())
+                .ok_or_else(|| {
+                    LlsdError::custom(format!(
+                        "no migration from version {version} towards {target}"
+                    ))
+                })?;
+            migration(&mut migrated)
+                .with_context(|| format!("migrating from version {version} to {next}"))?;
+            let map = migrated.as_map_mut().ok_or_else(|| {
+                LlsdError::custom(format!(
+                    "migrating from version {version} to {next} left a non-map document"
+                ))
+            })?;
+            map.insert(self.key.clone(), Llsd::Integer(*next as i32));
+            version = *next;
+        }
+        *doc = migrated;
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llsd_map;
+
+    fn migrations() -> Migrations {
+        let mut migrations = Migrations::new().with_unversioned(1);
+        let add = |key: &'static str| {
+            move |doc: &mut Llsd| {
+                doc.as_map_mut()
+                    .unwrap()
+                    .insert(key.into(), Llsd::Boolean(true));
+                Ok(())
+            }
+        };
+        migrations
+            .register(1, 2, add("two"))
+            .register(2, 3, add("three"))
+            .register(2, 4, add("skipped_to_four"))
+            .register(3, 4, add("four"));
+        migrations
+    }
+
+    #[test]
+    fn applies_steps_in_order() {
+        let migrations = migrations();
+        assert_eq!(migrations.latest(), 4);
+
+        let mut doc = llsd_map! { "name" => "a" };
+        assert_eq!(migrations.migrate_to(&mut doc, 3).unwrap(), 3);
+        assert_eq!(
+            doc,
+            llsd_map! { "name" => "a", "two" => true, "three" => true, "version" => 3 }
+        );
+        assert_eq!(migrations.migrate_to_latest(&mut doc).unwrap(), 4);
+        assert!(doc.get("four").is_some());
+
+        // The longest step that doesn't overshoot is taken.
+        let mut doc = llsd_map! { "version" => 2 };
+        migrations.migrate_to_latest(&mut doc).unwrap();
+        assert_eq!(doc, llsd_map! { "version" => 4, "skipped_to_four" => true });
+        let unchanged = doc.clone();
+        assert_eq!(migrations.migrate_to_latest(&mut doc).unwrap(), 4);
+        assert_eq!(doc, unchanged);
+    }
+
+    #[test]
+    fn failures_leave_the_document_alone() {
+        let mut migrations = Migrations::new().with_version_key("schema");
+        migrations
+            .register(1, 2, |doc| {
+                doc.as_map_mut().unwrap().clear();
+                Ok(())
+            })
+            .register(2, 3, |_| Err(anyhow::anyhow!("bad value")));
+
+        let mut doc = llsd_map! { "schema" => 1, "keep" => 1 };
+        let original = doc.clone();
+        let err = migrations.migrate_to_latest(&mut doc).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "migrating from version 2 to 3: bad value"
+        );
+        assert_eq!(doc, original);
+
+        let mut newer = llsd_map! { "schema" => 5 };
+        let err = migrations.migrate_to_latest(&mut newer).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value: document version 5 is newer than version 3"
+        );
+        let err = migrations.migrate_to_latest(&mut llsd_map! {}).unwrap_err();
+        assert_eq!(err.to_string(), "missing field at schema");
+        let mut gap = llsd_map! { "schema" => 0 };
+        assert!(migrations.migrate_to_latest(&mut gap).is_err());
+    }
+}
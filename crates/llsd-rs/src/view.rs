@@ -0,0 +1,257 @@
+//! [`LlsdView`], panic-free extraction from loosely structured documents.
+//!
+//! For messages not worth modelling as a derived struct, a view walks keys
+//! and indices without checking each step, and the typed getter at the end
+//! reports the first thing that went wrong as an [`LlsdError`] naming the
+//! full path. Numeric, boolean and UUID getters coerce the way the viewer
+//! does (`"5"` reads as 5, an integer as a boolean).
+//!
+//! ```
+//! use llsd_rs::{Llsd, view::LlsdView};
+//! use llsd_rs::Uuid;
+//!
+//! let id = Uuid::from_u128(7);
+//! let body = Llsd::map()
+//!     .insert("agent", Llsd::array().push(Llsd::map().insert("id", id).unwrap()).unwrap())
+//!     .unwrap();
+//! let view = LlsdView::new(&body);
+//! assert_eq!(view.key("agent").index(0).key("id").uuid().unwrap(), id);
+//!
+//! let err = view.key("agent").index(3).key("id").uuid().unwrap_err();
+//! assert_eq!(err.to_string(), "missing field at agent[3]");
+//! ```
+
+use crate::Uuid;
+
+use crate::{Date, FromLlsd, Llsd, LlsdError, LlsdErrorKind, PathSegment, Uri};
+
+/// A position in a document, or the first failure on the way to it.
+#[derive(Debug, Clone)]
+pub struct LlsdView<'a> {
+    value: Result<&'a Llsd, LlsdError>,
+    path: Vec<PathSegment>,
+}
+
+impl<'a> LlsdView<'a> {
+    pub fn new(llsd: &'a Llsd) -> Self {
+        Self {
+            value: Ok(llsd),
+            path: Vec::new(),
+        }
+    }
+
+    /// The path walked so far (up to the failing step, if any).
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// `error` with the path walked so far prepended.
+    fn at(&self, error: LlsdError) -> LlsdError {
+        self.path
+            .iter()
+            .rev()
+            .fold(error, |error, segment| match segment {
+                PathSegment::Key(key) => error.with_key(key),
+                PathSegment::Index(index) => error.with_index(*index),
+            })
+    }
+
+    /// The value under `key` of this map.
+    pub fn key(&self, key: &str) -> Self {
+        let value = match self.value {
+            Ok(Llsd::Map(map)) => map
+                .get(key)
+                .ok_or_else(|| self.at(LlsdError::missing_field(key))),
+            Ok(other) => Err(self.at(LlsdError::invalid_type("map", other))),
+            Err(ref e) => Err(e.clone()),
+        };
+        self.step(value, PathSegment::Key(key.to_string()))
+    }
+
+    /// Element `index` of this array.
+    pub fn index(&self, index: usize) -> Self {
+        let value = match self.value {
+            Ok(Llsd::Array(items)) => items.get(index).ok_or_else(|| {
+                self.at(LlsdError::new(LlsdErrorKind::MissingField).with_index(index))
+            }),
+            Ok(other) => Err(self.at(LlsdError::invalid_type("array", other))),
+            Err(ref e) => Err(e.clone()),
+        };
+        self.step(value, PathSegment::Index(index))
+    }
+
+    fn step(&self, value: Result<&'a Llsd, LlsdError>, segment: PathSegment) -> Self {
+        let mut path = self.path.clone();
+        if value.is_ok() {
+            path.push(segment);
+        }
+        Self { value, path }
+    }
+
+    /// The value, or the failure that prevented reaching it.
+    pub fn llsd(&self) -> Result<&'a Llsd, LlsdError> {
+        self.value.clone()
+    }
+
+    /// Whether the walk reached a value other than `Undefined`.
+    pub fn is_defined(&self) -> bool {
+        self.value.as_ref().is_ok_and(|llsd| llsd.is_defined())
+    }
+
+    /// `None` when the walk failed or reached `Undefined`, for optional
+    /// fields; `view.key("x").optional().map(|x| x.i32()).transpose()?`.
+    pub fn optional(&self) -> Option<Self> {
+        self.is_defined().then(|| self.clone())
+    }
+
+    fn read<T>(
+        &self,
+        expected: &'static str,
+        f: impl FnOnce(&'a Llsd) -> Option<T>,
+    ) -> Result<T, LlsdError> {
+        let llsd = self.llsd()?;
+        f(llsd).ok_or_else(|| self.at(LlsdError::invalid_type(expected, llsd)))
+    }
+
+    pub fn bool(&self) -> Result<bool, LlsdError> {
+        self.read("boolean", Llsd::try_coerce_bool)
+    }
+
+    pub fn i32(&self) -> Result<i32, LlsdError> {
+        self.read("integer", Llsd::try_coerce_i32)
+    }
+
+    pub fn f64(&self) -> Result<f64, LlsdError> {
+        self.read("real", Llsd::try_coerce_f64)
+    }
+
+    pub fn uuid(&self) -> Result<Uuid, LlsdError> {
+        self.read("uuid", Llsd::try_coerce_uuid)
+    }
+
+    /// A string, or the text of a URI.
+    pub fn str(&self) -> Result<&'a str, LlsdError> {
+        self.read("string", |llsd| match llsd {
+            Llsd::String(s) => Some(s.as_str()),
+            Llsd::Uri(uri) => Some(uri.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn uri(&self) -> Result<&'a Uri, LlsdError> {
+        self.read("uri", Llsd::as_uri)
+    }
+
+    pub fn date(&self) -> Result<Date, LlsdError> {
+        self.read("date", |llsd| llsd.as_date().copied())
+    }
+
+    pub fn binary(&self) -> Result<&'a [u8], LlsdError> {
+        self.read("binary", |llsd| llsd.as_binary().map(Vec::as_slice))
+    }
+
+    /// Views of the elements of this array.
+    pub fn items(&self) -> Result<impl Iterator<Item = LlsdView<'a>> + '_, LlsdError> {
+        let len = self.read("array", |llsd| llsd.as_array().map(Vec::len))?;
+        Ok((0..len).map(|i| self.index(i)))
+    }
+
+    /// Keys and views of the entries of this map, in the map's order.
+    pub fn entries(&self) -> Result<impl Iterator<Item = (&'a str, LlsdView<'a>)> + '_, LlsdError> {
+        let map = self.read("map", Llsd::as_map)?;
+        Ok(map.keys().map(|key| (key.as_str(), self.key(key))))
+    }
+
+    /// Convert through [`FromLlsd`], with the path prepended to its error.
+    pub fn get<T: FromLlsd>(&self) -> Result<T, LlsdError> {
+        T::from_llsd(self.llsd()?).map_err(|e| self.at(e))
+    }
+}
+
+impl<'a> From<&'a Llsd> for LlsdView<'a> {
+    fn from(llsd: &'a Llsd) -> Self {
+        Self::new(llsd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory() -> Llsd {
+        let folder = |name: &str, id: u128| {
+            Llsd::map()
+                .insert("name", name)
+                .unwrap()
+                .insert("folder_id", Uuid::from_u128(id))
+                .unwrap()
+                .insert("version", "12")
+                .unwrap()
+        };
+        Llsd::map()
+            .insert(
+                "folders",
+                Llsd::Array(vec![folder("Objects", 1), folder("Scripts", 2)]),
+            )
+            .unwrap()
+            .insert("owner", Llsd::Undefined)
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_and_coerces() {
+        let llsd = inventory();
+        let view = LlsdView::new(&llsd);
+        let folders = view.key("folders");
+        assert_eq!(folders.index(1).key("name").str().unwrap(), "Scripts");
+        assert_eq!(folders.index(0).key("version").i32().unwrap(), 12);
+        assert_eq!(
+            folders.index(0).key("folder_id").uuid().unwrap(),
+            Uuid::from_u128(1)
+        );
+        let names: Vec<_> = folders
+            .items()
+            .unwrap()
+            .map(|f| f.key("name").get::<String>().unwrap())
+            .collect();
+        assert_eq!(names, ["Objects", "Scripts"]);
+        assert_eq!(view.entries().unwrap().count(), 2);
+        assert!(view.key("owner").optional().is_none());
+        assert!(view.key("missing").optional().is_none());
+        assert!(folders.optional().is_some());
+    }
+
+    #[test]
+    fn errors_name_the_path() {
+        let llsd = inventory();
+        let view = LlsdView::new(&llsd);
+        let message = |e: LlsdError| e.to_string();
+
+        assert_eq!(
+            message(view.key("folders").index(1).key("name").uuid().unwrap_err()),
+            "invalid type at folders[1].name: expected uuid, found string"
+        );
+        assert_eq!(
+            message(view.key("folders").index(0).key("size").i32().unwrap_err()),
+            "missing field at folders[0].size"
+        );
+        assert_eq!(
+            message(view.key("folders").index(2).str().unwrap_err()),
+            "missing field at folders[2]"
+        );
+        let missing = view.key("nope").key("deeper").index(2);
+        assert_eq!(missing.path(), [] as [PathSegment; 0]);
+        assert_eq!(
+            message(missing.bool().unwrap_err()),
+            "missing field at nope"
+        );
+        assert_eq!(
+            message(view.key("folders").key("name").str().unwrap_err()),
+            "invalid type at folders: expected map, found array"
+        );
+        assert_eq!(
+            message(view.key("folders").index(0).get::<Vec<i32>>().unwrap_err()),
+            "invalid type at folders[0]: expected array, found map"
+        );
+    }
+}
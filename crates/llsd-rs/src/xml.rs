@@ -1,145 +1,295 @@
-use std::io::Write;
+use std::{cell::Cell, io::Write};
 
-use base64::prelude::*;
-use chrono::DateTime;
-use uuid::Uuid;
-use xml::{EventReader, EventWriter};
+use crate::Uuid;
+use xml::{
+    EventReader, EventWriter, attribute::OwnedAttribute, common::TextPosition, namespace::Namespace,
+};
 
-use crate::Uri;
+use crate::{
+    PathSegment, UndefinedEntries, Uri, UriPolicy,
+    autodetect::LlsdEncoding,
+    codec::Base64Decoder,
+    compare::{Token, TokenSource},
+    date, escape,
+    metrics::{self, Limit, Operation},
+    order::{Entries, KeyOrder, OrderedLlsd, XmlAttributes},
+    parse::{ParseFailure, ParseOptions, ParseWarningKind, ParseWarnings, Warn, locate, unlocate},
+    spanned::{Span, SpanStack, Spanned},
+};
 
 use super::Llsd;
 
+/// Decoded size past which a `<binary>` element is rejected, as for
+/// unbounded lengths in the binary format.
+const MAX_BINARY_LENGTH: usize = 64 * 1024 * 1024;
+
 pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<Llsd, anyhow::Error> {
+    from_events(parser)
+}
+
+fn from_events(
+    events: impl IntoIterator<Item = xml::reader::Result<xml::reader::XmlEvent>>,
+) -> Result<Llsd, anyhow::Error> {
+    from_events_inner(events, None)
+}
+
+/// Decode a document from `events`, recording the key order of its maps and
+/// the attributes of its elements into `doc` when given.
+fn from_events_inner(
+    events: impl IntoIterator<Item = xml::reader::Result<xml::reader::XmlEvent>>,
+    doc: Option<&mut OrderedLlsd>,
+) -> Result<Llsd, anyhow::Error> {
+    from_events_located(events, doc, None, None, false).map_err(unlocate)
+}
+
+/// Like [`from_events_inner`], keeping the path of the failing value on the
+/// error for [`ParseFailure`].
+fn from_events_located(
+    events: impl IntoIterator<Item = xml::reader::Result<xml::reader::XmlEvent>>,
+    doc: Option<&mut OrderedLlsd>,
+    mut warnings: Option<&mut dyn Warn>,
+    mut spans: Option<&mut SpanRecorder<'_>>,
+    lenient: bool,
+) -> Result<Llsd, anyhow::Error> {
     use xml::reader::XmlEvent;
     let mut stack: Vec<Llsd> = Vec::new();
+    // Parallel to `stack` while recording.
+    let mut orders: Vec<KeyOrder> = Vec::new();
+    let recording = doc.is_some();
+    // While recording, the namespace in scope of each open element, to tell
+    // the declarations an element makes; and the attributes of the `<key>`
+    // before the next value.
+    let mut namespaces: Vec<Namespace> = Vec::new();
+    let mut key_attributes = XmlAttributes::new();
+    let mut root_attributes = XmlAttributes::new();
     let mut name_stack: Vec<String> = Vec::new();
     let mut key_stack: Vec<Option<String>> = Vec::new();
+    // The open <binary> element's decoder; they do not nest.
+    let mut binary: Option<Base64Decoder> = None;
+    // Text of the open scalar element other than a string or binary, decoded
+    // at its end tag; they do not nest either.
+    let mut text = String::new();
     let mut start = false;
     let mut end = false;
 
-    for event in parser {
-        match event {
-            Ok(XmlEvent::StartElement { name, .. }) => {
-                name_stack.push(name.local_name.clone());
-                if !start {
-                    if name.local_name.as_str() != "llsd" {
-                        return Err(anyhow::anyhow!(
-                            "Error parsing LLSD: expected <llsd> root element, got {}",
-                            name.local_name
-                        ));
-                    }
-                    start = true;
-                    continue;
-                }
-                match name.local_name.as_str() {
-                    "llsd" => {
-                        return Err(anyhow::anyhow!(
-                            "Error parsing LLSD: unexpected <llsd> element"
-                        ));
+    let result = (|| -> Result<(), anyhow::Error> {
+        for event in events {
+            match event {
+                Ok(XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                }) => {
+                    if let Some(warnings) = warnings.as_deref_mut() {
+                        for attribute in &attributes {
+                            let known = name.local_name == "binary"
+                                && attribute.name.local_name == "encoding"
+                                && attribute.value == "base64";
+                            if !known {
+                                warnings.warn_at(
+                                    xml_path(&stack, &key_stack),
+                                    ParseWarningKind::IgnoredAttribute {
+                                        element: name.local_name.clone(),
+                                        attribute: attribute.name.local_name.clone(),
+                                    },
+                                );
+                            }
+                        }
                     }
-                    "undef" => stack.push(Llsd::Undefined),
-                    "boolean" => stack.push(Llsd::Boolean(false)),
-                    "string" => stack.push(Llsd::String(String::new())),
-                    "uuid" => stack.push(Llsd::Uuid(Default::default())),
-                    "uri" => stack.push(Llsd::Uri(Uri::Empty)),
-                    "date" => stack.push(Llsd::Date(Default::default())),
-                    "binary" => stack.push(Llsd::Binary(Vec::new())),
-                    "integer" => stack.push(Llsd::Integer(0)),
-                    "real" => stack.push(Llsd::Real(0.0)),
-                    "array" => stack.push(Llsd::Array(Vec::new())),
-                    "map" => stack.push(Llsd::Map(Default::default())),
-                    "key" => {
-                        key_stack.push(None);
+                    name_stack.push(name.local_name.clone());
+                    let mut recorded = XmlAttributes::new();
+                    if recording {
+                        recorded = record_attributes(&attributes, &namespace, namespaces.last());
+                        namespaces.push(namespace);
                     }
-                    _ => {
-                        return Err(anyhow::anyhow!(
-                            "Error parsing LLSD: unexpected element {}",
-                            name.local_name
-                        ));
+                    if !start {
+                        if name.local_name.as_str() != "llsd" {
+                            return Err(anyhow::anyhow!(
+                                "Error parsing LLSD: expected <llsd> root element, got {}",
+                                name.local_name
+                            ));
+                        }
+                        start = true;
+                        root_attributes = recorded;
+                        continue;
                     }
-                }
-            }
-            Ok(XmlEvent::Characters(data)) => {
-                if key_stack.last() == Some(&None) {
-                    key_stack.pop();
-                    key_stack.push(Some(data.clone()));
-                } else if let Some(llsd) = stack.last_mut() {
-                    match llsd {
-                        Llsd::Boolean(_) => match data.as_str() {
-                            "true" => *llsd = Llsd::Boolean(true),
-                            "false" => *llsd = Llsd::Boolean(false),
-                            "1" => *llsd = Llsd::Boolean(true),
-                            "0" => *llsd = Llsd::Boolean(false),
-                            _ => {
-                                return Err(anyhow::anyhow!(
-                                    "Error parsing LLSD: expected boolean, got {}",
-                                    data
-                                ));
-                            }
-                        },
-                        &mut Llsd::String(ref mut s) => s.push_str(data.as_str()),
-                        &mut Llsd::Uuid(ref mut u) => *u = Uuid::parse_str(data.as_str())?,
-                        &mut Llsd::Uri(ref mut u) => *u = Uri::parse(data.as_str()),
-                        &mut Llsd::Date(ref mut d) => {
-                            *d = DateTime::parse_from_rfc3339(data.as_str())?.into()
+                    match name.local_name.as_str() {
+                        "llsd" => {
+                            return Err(anyhow::anyhow!(
+                                "Error parsing LLSD: unexpected <llsd> element"
+                            ));
                         }
-                        &mut Llsd::Binary(ref mut b) => {
-                            *b = BASE64_STANDARD.decode(data.as_bytes())?
+                        "undef" => stack.push(Llsd::Undefined),
+                        "boolean" => stack.push(Llsd::Boolean(false)),
+                        "string" => stack.push(Llsd::String(String::new())),
+                        "uuid" => stack.push(Llsd::Uuid(Default::default())),
+                        "uri" => stack.push(Llsd::Uri(Uri::Empty)),
+                        "date" => stack.push(Llsd::Date(Default::default())),
+                        "binary" => {
+                            binary = Some(Base64Decoder::new(MAX_BINARY_LENGTH));
+                            stack.push(Llsd::Binary(Vec::new()))
                         }
-                        &mut Llsd::Integer(ref mut i) => {
-                            *i = crate::parse_i32_decimal_wrapping(data.as_str())?
+                        "integer" => stack.push(Llsd::Integer(0)),
+                        "real" => stack.push(Llsd::Real(0.0)),
+                        "array" => stack.push(Llsd::Array(Vec::new())),
+                        "map" => stack.push(Llsd::Map(Default::default())),
+                        "key" => {
+                            key_stack.push(None);
+                            key_attributes = recorded;
+                            continue;
                         }
-                        &mut Llsd::Real(ref mut r) => match data.as_str() {
-                            "nan" => *r = f64::NAN,
-                            "inf" => *r = f64::INFINITY,
-                            "-inf" => *r = f64::NEG_INFINITY,
-                            _ => *r = data.parse()?,
-                        },
                         _ => {
                             return Err(anyhow::anyhow!(
-                                "Error parsing LLSD: unexpected characters {}",
-                                data
+                                "Error parsing LLSD: unexpected element {}",
+                                name.local_name
                             ));
                         }
                     }
+                    if recording {
+                        let mut node = KeyOrder::new();
+                        node.set_attributes(recorded);
+                        if matches!(stack.iter().nth_back(1), Some(Llsd::Map(_))) {
+                            node.set_key_attributes(std::mem::take(&mut key_attributes));
+                        }
+                        orders.push(node);
+                    }
+                    if let Some(spans) = spans.as_deref_mut() {
+                        spans.open();
+                    }
                 }
-            }
-            Ok(XmlEvent::EndElement { name }) => {
-                if name_stack.pop().as_ref() != Some(&name.local_name) {
-                    return Err(anyhow::anyhow!(
-                        "Error parsing LLSD: unexpected end element {}",
-                        name.local_name
-                    ));
+                // Whitespace-only text is only significant in keys and
+                // strings; elsewhere it is formatting between elements.
+                Ok(XmlEvent::Whitespace(data)) => {
+                    if key_stack.last() == Some(&None) {
+                        key_stack.pop();
+                        key_stack.push(Some(data));
+                    } else {
+                        match stack.last_mut() {
+                            Some(Llsd::String(s)) => s.push_str(&data),
+                            Some(llsd) if has_text(llsd) => text.push_str(&data),
+                            _ => {}
+                        }
+                    }
                 }
-                if name.local_name.as_str() == "key" {
-                    if key_stack.last().is_none() {
-                        return Err(anyhow::anyhow!("Error parsing LLSD: missing key"));
+                Ok(XmlEvent::Characters(data)) => {
+                    if key_stack.last() == Some(&None) {
+                        key_stack.pop();
+                        key_stack.push(Some(data.clone()));
+                    } else if let Some((last, parents)) = stack.split_last_mut() {
+                        match last {
+                            &mut Llsd::String(ref mut s) => s.push_str(data.as_str()),
+                            &mut Llsd::Binary(ref mut b) => {
+                                if let Some(decoder) = binary.as_mut() {
+                                    let pushed = decoder.push(data.as_bytes(), b);
+                                    report_binary_limit(decoder);
+                                    pushed?;
+                                }
+                            }
+                            // Decoded as the text arrives, so a failure points at it;
+                            // text split by a comment is decoded again in full.
+                            llsd if has_text(llsd) => {
+                                text.push_str(&data);
+                                decode_text(llsd, &text, lenient, |kind| {
+                                    if let Some(warnings) = warnings.as_deref_mut() {
+                                        warnings.warn_at(xml_path(parents, &key_stack), kind);
+                                    }
+                                })?;
+                            }
+                            _ => {
+                                return Err(anyhow::anyhow!(
+                                    "Error parsing LLSD: unexpected characters {}",
+                                    data
+                                ));
+                            }
+                        }
                     }
-                } else if name.local_name.as_str() == "llsd" {
-                    end = true;
-                    break;
-                } else if let Some(last) = stack.pop() {
-                    match stack.last_mut() {
-                        Some(Llsd::Array(parent)) => parent.push(last),
-                        Some(Llsd::Map(parent)) => {
-                            if let Some(Some(key)) = key_stack.pop() {
-                                parent.insert(key.to_string(), last);
-                            } else {
-                                return Err(anyhow::anyhow!("Error parsing LLSD: missing key"));
+                }
+                Ok(XmlEvent::EndElement { name }) => {
+                    if name_stack.pop().as_ref() != Some(&name.local_name) {
+                        return Err(anyhow::anyhow!(
+                            "Error parsing LLSD: unexpected end element {}",
+                            name.local_name
+                        ));
+                    }
+                    namespaces.pop();
+                    if name.local_name.as_str() == "key" {
+                        match key_stack.last_mut() {
+                            None => return Err(anyhow::anyhow!("Error parsing LLSD: missing key")),
+                            // `<key />`: no characters came.
+                            Some(key @ None) => *key = Some(String::new()),
+                            Some(Some(_)) => {}
+                        }
+                    } else if name.local_name.as_str() == "llsd" {
+                        end = true;
+                        break;
+                    } else if let Some(mut last) = stack.pop() {
+                        text.clear();
+                        if let (Llsd::Binary(b), Some(mut decoder)) = (&mut last, binary.take())
+                            && let Err(e) = decoder.finish(b).inspect_err(|_| {
+                                report_binary_limit(&decoder);
+                            })
+                        {
+                            stack.push(last);
+                            return Err(e);
+                        }
+                        let last_order = orders.pop();
+                        if let Some(spans) = spans.as_deref_mut() {
+                            let key = match stack.last() {
+                                Some(Llsd::Map(_)) => key_stack.last().cloned().flatten(),
+                                _ => None,
+                            };
+                            spans.close(key);
+                        }
+                        if let Some(warnings) = warnings.as_deref_mut()
+                            && let (Some(Llsd::Map(parent)), Some(Some(key))) =
+                                (stack.last(), key_stack.last())
+                            && parent.contains_key(key.as_str())
+                        {
+                            warnings.warn_at(
+                                xml_path(&stack[..stack.len() - 1], &key_stack),
+                                ParseWarningKind::DuplicateKey(key.clone()),
+                            );
+                        }
+                        match stack.last_mut() {
+                            Some(Llsd::Array(parent)) => {
+                                parent.push(last);
+                                if let (Some(child), Some(parent)) = (last_order, orders.last_mut())
+                                {
+                                    parent.push_item(child);
+                                }
+                            }
+                            Some(Llsd::Map(parent)) => {
+                                if let Some(Some(key)) = key_stack.pop() {
+                                    if let (Some(child), Some(parent)) =
+                                        (last_order, orders.last_mut())
+                                    {
+                                        parent.push_entry(key.clone(), child);
+                                    }
+                                    parent.insert(key, last);
+                                } else {
+                                    return Err(anyhow::anyhow!("Error parsing LLSD: missing key"));
+                                }
+                            }
+                            _ => {
+                                stack.push(last);
+                                orders.extend(last_order);
                             }
                         }
-                        _ => stack.push(last),
+                    } else {
+                        return Err(anyhow::anyhow!(
+                            "Error parsing LLSD: unexpected end element {}",
+                            name.local_name
+                        ));
                     }
-                } else {
-                    return Err(anyhow::anyhow!(
-                        "Error parsing LLSD: unexpected end element {}",
-                        name.local_name
-                    ));
                 }
+                Err(e) => return Err(anyhow::anyhow!("Error parsing LLSD: {}", e)),
+                _ => {}
             }
-            Err(e) => return Err(anyhow::anyhow!("Error parsing LLSD: {}", e)),
-            _ => {}
         }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        return Err(with_xml_path(e, &stack, &key_stack));
     }
     if !end {
         Err(anyhow::anyhow!(
@@ -153,12 +303,388 @@ pub fn from_parser<R: std::io::Read>(parser: EventReader<R>) -> Result<Llsd, any
             stack.len()
         ))
     } else {
+        if let (Some(doc), Some(root)) = (doc, orders.pop()) {
+            doc.order = root;
+            doc.root_attributes = root_attributes;
+        }
         Ok(stack.pop().unwrap_or(Llsd::Undefined))
     }
 }
 
+/// The namespace declarations and attributes of an element, in that order,
+/// with qualified names. Declarations are the mappings in `namespace` that
+/// differ from the `parent` element's.
+fn record_attributes(
+    attributes: &[OwnedAttribute],
+    namespace: &Namespace,
+    parent: Option<&Namespace>,
+) -> XmlAttributes {
+    let mut recorded = XmlAttributes::new();
+    for (prefix, uri) in namespace {
+        let inherited = match parent {
+            Some(parent) => parent.get(prefix) == Some(uri),
+            // Mappings every document has.
+            None => matches!(prefix, "xml" | "xmlns") || (prefix.is_empty() && uri.is_empty()),
+        };
+        if !inherited {
+            let name = match prefix {
+                "" => "xmlns".to_string(),
+                prefix => format!("xmlns:{prefix}"),
+            };
+            recorded.push((name, uri.to_string()));
+        }
+    }
+    for attribute in attributes {
+        let name = match &attribute.name.prefix {
+            Some(prefix) => format!("{prefix}:{}", attribute.name.local_name),
+            None => attribute.name.local_name.clone(),
+        };
+        recorded.push((name, attribute.value.clone()));
+    }
+    recorded
+}
+
+/// Whether `llsd` is a scalar decoded from its element's text as a whole,
+/// unlike strings and binaries, which are appended to.
+fn has_text(llsd: &Llsd) -> bool {
+    matches!(
+        llsd,
+        Llsd::Boolean(_)
+            | Llsd::Uuid(_)
+            | Llsd::Uri(_)
+            | Llsd::Date(_)
+            | Llsd::Integer(_)
+            | Llsd::Real(_)
+    )
+}
+
+/// Decode the text of `llsd`'s element into it. Empty or whitespace-only
+/// text (`<integer />`, `<real> </real>`) leaves the default value the
+/// start tag gave it; otherwise surrounding whitespace is dropped, except
+/// for URIs. `lenient` also accepts MSVC special reals.
+fn decode_text(
+    llsd: &mut Llsd,
+    text: &str,
+    lenient: bool,
+    mut warn: impl FnMut(ParseWarningKind),
+) -> Result<(), anyhow::Error> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Ok(());
+    }
+    if trimmed.len() != text.len() && !llsd.is_uri() {
+        warn(ParseWarningKind::TrimmedWhitespace);
+    }
+    match llsd {
+        Llsd::Boolean(b) => match trimmed {
+            "true" | "1" => *b = true,
+            "false" | "0" => *b = false,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Error parsing LLSD: expected boolean, got {}",
+                    text
+                ));
+            }
+        },
+        Llsd::Uuid(u) => *u = Uuid::parse_str(trimmed)?,
+        Llsd::Uri(u) => *u = Uri::parse(text),
+        Llsd::Date(d) => *d = date::parse_rfc3339(trimmed)?,
+        Llsd::Integer(i) => {
+            *i = crate::parse_i32_decimal_wrapping(trimmed)?;
+            if trimmed.parse::<i32>().is_err() {
+                warn(ParseWarningKind::OutOfRange {
+                    text: trimmed.to_string(),
+                    value: *i,
+                });
+            }
+        }
+        Llsd::Real(r) => match trimmed {
+            "nan" => *r = f64::NAN,
+            "inf" => *r = f64::INFINITY,
+            "-inf" => *r = f64::NEG_INFINITY,
+            _ => match trimmed.parse() {
+                Ok(value) => *r = value,
+                Err(e) => {
+                    *r = lenient
+                        .then(|| crate::parse_msvc_real(trimmed))
+                        .flatten()
+                        .ok_or(e)?
+                }
+            },
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+fn report_binary_limit(decoder: &Base64Decoder) {
+    if decoder.over_limit() {
+        metrics::limit_hit(LlsdEncoding::Xml, Operation::Parse, Limit::Length);
+    }
+}
+
+/// Attach the path of the value being read, from the decoder's container and
+/// key stacks, to `err`.
+fn with_xml_path(
+    err: anyhow::Error,
+    stack: &[Llsd],
+    key_stack: &[Option<String>],
+) -> anyhow::Error {
+    let path = xml_path(&stack[..stack.len().saturating_sub(1)], key_stack);
+    path.into_iter().rev().fold(err, locate)
+}
+
+/// The path of the next value inside the innermost of `containers`.
+fn xml_path(containers: &[Llsd], key_stack: &[Option<String>]) -> Vec<PathSegment> {
+    let mut keys = key_stack.iter();
+    let mut path = Vec::new();
+    for frame in containers {
+        match frame {
+            Llsd::Array(items) => path.push(PathSegment::Index(items.len())),
+            Llsd::Map(_) => {
+                if let Some(Some(key)) = keys.next() {
+                    path.push(PathSegment::Key(key.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+    path
+}
+
+/// The next child element of the open element, or `None` at its end tag.
+fn next_child<R: std::io::Read>(
+    events: &mut xml::reader::Events<R>,
+) -> Result<Option<xml::reader::XmlEvent>, anyhow::Error> {
+    use xml::reader::XmlEvent;
+    for event in events {
+        match event {
+            Ok(event @ XmlEvent::StartElement { .. }) => return Ok(Some(event)),
+            Ok(XmlEvent::EndElement { .. }) => return Ok(None),
+            Ok(_) => {}
+            Err(e) => return Err(anyhow::anyhow!("Error parsing LLSD: {}", e)),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "Error parsing LLSD: unexpected end of input"
+    ))
+}
+
+/// Consume events up to the end tag of the element just opened, returning
+/// its text.
+fn skip_element<R: std::io::Read>(
+    events: &mut xml::reader::Events<R>,
+) -> Result<String, anyhow::Error> {
+    use xml::reader::XmlEvent;
+    let mut text = String::new();
+    let mut depth = 1usize;
+    for event in events {
+        match event {
+            Ok(XmlEvent::StartElement { .. }) => depth += 1,
+            Ok(XmlEvent::EndElement { .. }) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(text);
+                }
+            }
+            Ok(XmlEvent::Characters(data) | XmlEvent::Whitespace(data)) if depth == 1 => {
+                text.push_str(&data)
+            }
+            Ok(_) => {}
+            Err(e) => return Err(anyhow::anyhow!("Error parsing LLSD: {}", e)),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "Error parsing LLSD: unexpected end of input"
+    ))
+}
+
+/// Decode the element `current` has just opened, feeding it through the
+/// regular parser as a document of its own and stopping at its end tag.
+fn element_value<R: std::io::Read>(
+    events: &mut xml::reader::Events<R>,
+    root: xml::name::OwnedName,
+    current: xml::reader::XmlEvent,
+) -> Result<Llsd, anyhow::Error> {
+    use xml::{name::OwnedName, namespace::Namespace, reader::XmlEvent};
+    let mut depth = 1usize;
+    let subtree = std::iter::from_fn(|| {
+        if depth == 0 {
+            return None;
+        }
+        let event = events.next()?;
+        match &event {
+            Ok(XmlEvent::StartElement { .. }) => depth += 1,
+            Ok(XmlEvent::EndElement { .. }) => depth -= 1,
+            Err(_) => depth = 0,
+            _ => {}
+        }
+        Some(event)
+    });
+    let document = std::iter::once(Ok(XmlEvent::StartElement {
+        name: root,
+        attributes: Vec::new(),
+        namespace: Namespace::empty(),
+    }))
+    .chain(std::iter::once(Ok(current)))
+    .chain(subtree)
+    .chain(std::iter::once(Ok(XmlEvent::EndElement {
+        name: OwnedName::local("llsd"),
+    })));
+    from_events(document)
+}
+
+/// Open the `<llsd>` root, returning its name.
+fn read_root<R: std::io::Read>(
+    events: &mut xml::reader::Events<R>,
+) -> Result<xml::name::OwnedName, anyhow::Error> {
+    use xml::reader::XmlEvent;
+    match next_child(events)? {
+        Some(XmlEvent::StartElement { name, .. }) if name.local_name == "llsd" => Ok(name),
+        Some(XmlEvent::StartElement { name, .. }) => Err(anyhow::anyhow!(
+            "Error parsing LLSD: expected <llsd> root element, got {}",
+            name.local_name
+        )),
+        _ => Err(anyhow::anyhow!("Error parsing LLSD: missing <llsd>")),
+    }
+}
+
+/// A document as [`Token`]s, for [`crate::compare`]. Containers are walked
+/// element by element; each scalar goes through the regular parser.
+pub(crate) struct Tokens<R: std::io::Read> {
+    events: xml::reader::Events<R>,
+    root: Option<xml::name::OwnedName>,
+    /// Open containers, `true` for maps.
+    stack: Vec<bool>,
+}
+
+impl<R: std::io::Read> Tokens<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            events: EventReader::new(reader).into_iter(),
+            root: None,
+            stack: Vec::new(),
+        }
+    }
+
+    fn value(&mut self, element: xml::reader::XmlEvent) -> Result<Token, anyhow::Error> {
+        use xml::reader::XmlEvent;
+        let XmlEvent::StartElement { name, .. } = &element else {
+            unreachable!("next_child only returns start elements");
+        };
+        match name.local_name.as_str() {
+            "array" => {
+                self.stack.push(false);
+                Ok(Token::ArrayStart)
+            }
+            "map" => {
+                self.stack.push(true);
+                Ok(Token::MapStart)
+            }
+            _ => {
+                let root =
+                    (self.root.clone()).unwrap_or_else(|| xml::name::OwnedName::local("llsd"));
+                element_value(&mut self.events, root, element).map(Token::Scalar)
+            }
+        }
+    }
+}
+
+impl<R: std::io::Read> TokenSource for Tokens<R> {
+    fn next_token(&mut self) -> Result<Option<Token>, anyhow::Error> {
+        use xml::reader::XmlEvent;
+        if self.root.is_none() {
+            self.root = Some(read_root(&mut self.events)?);
+            return match next_child(&mut self.events)? {
+                Some(element) => self.value(element).map(Some),
+                None => Ok(Some(Token::Scalar(Llsd::Undefined))),
+            };
+        }
+        let Some(&map) = self.stack.last() else {
+            return Ok(None);
+        };
+        match next_child(&mut self.events)? {
+            None => {
+                self.stack.pop();
+                Ok(Some(Token::End))
+            }
+            Some(XmlEvent::StartElement { name, .. }) if map && name.local_name == "key" => {
+                skip_element(&mut self.events).map(|key| Some(Token::Key(key)))
+            }
+            Some(element) => self.value(element).map(Some),
+        }
+    }
+}
+
+/// Decode only the value at `pointer` (the syntax of [`Llsd::pointer`]),
+/// stepping over the elements before it. Reading stops once that value is
+/// decoded. Returns `None` when the path does not exist; unlike a full
+/// decode, a repeated map key resolves to its first occurrence.
+pub fn extract_pointer<R: std::io::Read>(
+    reader: R,
+    pointer: &str,
+) -> Result<Option<Llsd>, anyhow::Error> {
+    use xml::reader::XmlEvent;
+    let Some(tokens) = crate::pointer_tokens(pointer) else {
+        return Ok(None);
+    };
+    let mut events = EventReader::new(reader).into_iter();
+    let root = read_root(&mut events)?;
+    let Some(mut current) = next_child(&mut events)? else {
+        return Ok(tokens.is_empty().then_some(Llsd::Undefined));
+    };
+    for token in &tokens {
+        let XmlEvent::StartElement { name, .. } = &current else {
+            unreachable!("next_child only returns start elements");
+        };
+        let found = match name.local_name.as_str() {
+            "map" => loop {
+                match next_child(&mut events)? {
+                    Some(XmlEvent::StartElement { name, .. }) if name.local_name == "key" => {
+                        let key = skip_element(&mut events)?;
+                        let Some(value) = next_child(&mut events)? else {
+                            return Err(anyhow::anyhow!("Error parsing LLSD: missing value"));
+                        };
+                        if key == *token {
+                            break Some(value);
+                        }
+                        skip_element(&mut events)?;
+                    }
+                    Some(_) => return Err(anyhow::anyhow!("Error parsing LLSD: missing key")),
+                    None => break None,
+                }
+            },
+            "array" => match token.parse::<usize>() {
+                Ok(index) => {
+                    let mut i = 0;
+                    loop {
+                        match next_child(&mut events)? {
+                            Some(value) if i == index => break Some(value),
+                            Some(_) => {
+                                skip_element(&mut events)?;
+                                i += 1;
+                            }
+                            None => break None,
+                        }
+                    }
+                }
+                Err(_) => None,
+            },
+            _ => None,
+        };
+        match found {
+            Some(value) => current = value,
+            None => return Ok(None),
+        }
+    }
+
+    element_value(&mut events, root, current).map(Some)
+}
+
 pub fn from_str(data: &str) -> Result<Llsd, anyhow::Error> {
-    from_parser(EventReader::from_str(data))
+    metrics::parse(LlsdEncoding::Xml, data.len(), || {
+        from_parser(EventReader::from_str(data))
+    })
 }
 
 pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Llsd, anyhow::Error> {
@@ -166,66 +692,578 @@ pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Llsd, anyhow::Error> {
 }
 
 pub fn from_slice(data: &[u8]) -> Result<Llsd, anyhow::Error> {
-    from_parser(EventReader::new(std::io::Cursor::new(data)))
+    metrics::parse(LlsdEncoding::Xml, data.len(), || {
+        from_parser(EventReader::new(std::io::Cursor::new(data)))
+    })
+}
+
+/// Like [`from_slice`], reporting failures as a [`ParseFailure`] with the
+/// offset, element path and (per `options`) surrounding bytes.
+pub fn from_slice_with_options(data: &[u8], options: &ParseOptions) -> Result<Llsd, anyhow::Error> {
+    from_slice_reporting(data, options, None)
+}
+
+/// Like [`from_slice_with_options`], reporting duplicate map keys, attributes
+/// other than `encoding="base64"`, whitespace around non-string scalars and
+/// integers outside the `i32` range to `warnings`.
+pub fn from_slice_with_warnings(
+    data: &[u8],
+    options: &ParseOptions,
+    warnings: &mut ParseWarnings<'_>,
+) -> Result<Llsd, anyhow::Error> {
+    from_slice_reporting(data, options, Some(warnings.begin()))
+}
+
+fn from_slice_reporting(
+    data: &[u8],
+    options: &ParseOptions,
+    warnings: Option<&mut dyn Warn>,
+) -> Result<Llsd, anyhow::Error> {
+    let position = Cell::new(TextPosition::new());
+    let events = positioned_events(data, &position);
+    from_events_located(events, None, warnings, None, options.lenient()).map_err(|e| {
+        let offset = LineIndex::new(data).offset(position.get());
+        ParseFailure::new(LlsdEncoding::Xml, data, offset, e, options).into()
+    })
+}
+
+/// Like [`from_slice`], also recording where each value was read from.
+pub fn from_slice_spanned(data: &[u8]) -> Result<Spanned<Llsd>, anyhow::Error> {
+    let position = Cell::new(TextPosition::new());
+    let events = positioned_events(data, &position);
+    let mut spans = SpanRecorder {
+        lines: LineIndex::new(data),
+        position: &position,
+        stack: SpanStack::default(),
+    };
+    let llsd =
+        from_events_located(events, None, None, Some(&mut spans), false).map_err(unlocate)?;
+    Ok(Spanned::new(llsd, spans.stack.finish()))
+}
+
+pub fn from_str_spanned(data: &str) -> Result<Spanned<Llsd>, anyhow::Error> {
+    from_slice_spanned(data.as_bytes())
+}
+
+/// The events of `data`, keeping `position` at the start of the latest one.
+fn positioned_events<'a>(
+    data: &'a [u8],
+    position: &'a Cell<TextPosition>,
+) -> impl Iterator<Item = xml::reader::Result<xml::reader::XmlEvent>> + 'a {
+    use xml::common::Position;
+    let mut reader = EventReader::new(std::io::Cursor::new(data));
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let event = reader.next();
+        position.set(reader.position());
+        done = matches!(event, Ok(xml::reader::XmlEvent::EndDocument) | Err(_));
+        Some(event)
+    })
+}
+
+/// Maps the (zero-based) rows and character columns xml-rs reports to byte
+/// offsets.
+struct LineIndex<'a> {
+    data: &'a [u8],
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let newlines = data.iter().enumerate().filter(|&(_, &b)| b == b'\n');
+        let starts = std::iter::once(0)
+            .chain(newlines.map(|(i, _)| i + 1))
+            .collect();
+        Self { data, starts }
+    }
+
+    fn offset(&self, position: TextPosition) -> Option<usize> {
+        let start = *self.starts.get(usize::try_from(position.row).ok()?)?;
+        let line = self.data[start..].split(|&b| b == b'\n').next()?;
+        let line = String::from_utf8_lossy(line);
+        let column = usize::try_from(position.column).ok()?;
+        Some(match line.char_indices().nth(column) {
+            Some((offset, _)) => start + offset,
+            None => start + line.len(),
+        })
+    }
+}
+
+/// Records the span of each value element as the decoder opens and closes
+/// it.
+struct SpanRecorder<'a> {
+    lines: LineIndex<'a>,
+    position: &'a Cell<TextPosition>,
+    stack: SpanStack,
+}
+
+impl SpanRecorder<'_> {
+    /// A value element starting at the current event.
+    fn open(&mut self) {
+        let position = self.position.get();
+        let start = self.lines.offset(position).unwrap_or(self.lines.data.len());
+        self.stack.open(Span {
+            start,
+            end: start,
+            line: position.row as usize + 1,
+            column: position.column as usize + 1,
+        });
+    }
+
+    /// The value element ending with the current event (its end tag).
+    fn close(&mut self, key: Option<String>) {
+        let data = self.lines.data;
+        let tag = self.lines.offset(self.position.get()).unwrap_or(data.len());
+        let end = data[tag..]
+            .iter()
+            .position(|&b| b == b'>')
+            .map_or(data.len(), |i| tag + i + 1);
+        self.stack.close(key, end);
+    }
+}
+
+/// Like [`from_parser`], also recording the order of every map's keys and
+/// the attributes of every element for [`write_ordered`].
+pub fn from_parser_ordered<R: std::io::Read>(
+    parser: EventReader<R>,
+) -> Result<OrderedLlsd, anyhow::Error> {
+    let mut doc = OrderedLlsd::default();
+    doc.llsd = from_events_inner(parser, Some(&mut doc))?;
+    Ok(doc)
+}
+
+pub fn from_str_ordered(data: &str) -> Result<OrderedLlsd, anyhow::Error> {
+    from_parser_ordered(EventReader::from_str(data))
+}
+
+pub fn from_reader_ordered<R: std::io::Read>(reader: R) -> Result<OrderedLlsd, anyhow::Error> {
+    from_parser_ordered(EventReader::new(reader))
+}
+
+pub fn from_slice_ordered(data: &[u8]) -> Result<OrderedLlsd, anyhow::Error> {
+    from_parser_ordered(EventReader::new(std::io::Cursor::new(data)))
+}
+
+const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Where the writer's markup goes: an [`EventWriter`], or straight into a
+/// buffer for the compact output of [`to_string`] and friends.
+trait Sink {
+    /// A start tag with recorded `attributes` (see [`XmlAttributes`]).
+    fn start(
+        &mut self,
+        name: &'static str,
+        attributes: &[(String, String)],
+    ) -> Result<(), anyhow::Error>;
+    /// `<binary encoding="base64">`.
+    fn start_base64(&mut self) -> Result<(), anyhow::Error>;
+    fn text(&mut self, text: &str) -> Result<(), anyhow::Error>;
+    fn end(&mut self, name: &'static str) -> Result<(), anyhow::Error>;
+}
+
+impl<W: Write> Sink for EventWriter<W> {
+    fn start(
+        &mut self,
+        name: &'static str,
+        attributes: &[(String, String)],
+    ) -> Result<(), anyhow::Error> {
+        let mut event = xml::writer::XmlEvent::start_element(name);
+        for (name, value) in attributes {
+            event = match name.strip_prefix("xmlns") {
+                Some("") => event.default_ns(value),
+                Some(prefix) if prefix.starts_with(':') => event.ns(&prefix[1..], value),
+                _ => event.attr(name.as_str(), value),
+            };
+        }
+        Ok(self.write(event)?)
+    }
+
+    fn start_base64(&mut self) -> Result<(), anyhow::Error> {
+        Ok(self.write(xml::writer::XmlEvent::start_element("binary").attr("encoding", "base64"))?)
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), anyhow::Error> {
+        Ok(self.write(xml::writer::XmlEvent::characters(text))?)
+    }
+
+    fn end(&mut self, _name: &'static str) -> Result<(), anyhow::Error> {
+        Ok(self.write(xml::writer::XmlEvent::end_element())?)
+    }
+}
+
+/// Writes the markup an `EventWriter` with the default configuration would,
+/// without building events: `<llsd>`, `<key />` for empty elements, and only
+/// `<`, `>` and `&` escaped in text.
+struct Direct<'a> {
+    out: &'a mut Vec<u8>,
+    /// A start tag is waiting for its `>` or ` />`.
+    open: bool,
 }
 
-fn write_inner<W: Write>(llsd: &Llsd, w: &mut EventWriter<W>) -> Result<(), anyhow::Error> {
-    use xml::writer::XmlEvent;
-    let tag = |w: &mut EventWriter<W>, tag, text: &str| -> Result<(), anyhow::Error> {
-        w.write(XmlEvent::start_element(tag))?;
-        if !text.is_empty() {
-            w.write(XmlEvent::characters(text))?;
+impl<'a> Direct<'a> {
+    fn new(out: &'a mut Vec<u8>, declaration: bool) -> Self {
+        if declaration {
+            out.extend_from_slice(br#"<?xml version="1.0" encoding="UTF-8"?>"#);
         }
-        w.write(XmlEvent::end_element())?;
+        Self { out, open: false }
+    }
+
+    fn close_start(&mut self) {
+        if std::mem::take(&mut self.open) {
+            self.out.push(b'>');
+        }
+    }
+}
+
+impl Sink for Direct<'_> {
+    fn start(
+        &mut self,
+        name: &'static str,
+        attributes: &[(String, String)],
+    ) -> Result<(), anyhow::Error> {
+        self.close_start();
+        self.out.push(b'<');
+        self.out.extend_from_slice(name.as_bytes());
+        for (name, value) in attributes {
+            self.out.push(b' ');
+            self.out.extend_from_slice(name.as_bytes());
+            self.out.extend_from_slice(b"=\"");
+            escape_attribute(value, self.out);
+            self.out.push(b'"');
+        }
+        self.open = true;
+        Ok(())
+    }
+
+    fn start_base64(&mut self) -> Result<(), anyhow::Error> {
+        self.close_start();
+        self.out.extend_from_slice(br#"<binary encoding="base64""#);
+        self.open = true;
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), anyhow::Error> {
+        self.close_start();
+        escape::escape_xml_text(text, self.out);
         Ok(())
-    };
-    fn f64_to_xml(v: f64) -> String {
-        let ss = v.to_string();
-        if ss == "NaN" { "nan".to_string() } else { ss }
+    }
+
+    fn end(&mut self, name: &'static str) -> Result<(), anyhow::Error> {
+        if std::mem::take(&mut self.open) {
+            self.out.extend_from_slice(b" />");
+        } else {
+            self.out.extend_from_slice(b"</");
+            self.out.extend_from_slice(name.as_bytes());
+            self.out.push(b'>');
+        }
+        Ok(())
+    }
+}
+
+/// Append `value` escaped for a double-quoted attribute, as `EventWriter`
+/// does.
+fn escape_attribute(value: &str, out: &mut Vec<u8>) {
+    for c in value.chars() {
+        match c {
+            '<' => out.extend_from_slice(b"&lt;"),
+            '>' => out.extend_from_slice(b"&gt;"),
+            '&' => out.extend_from_slice(b"&amp;"),
+            '"' => out.extend_from_slice(b"&quot;"),
+            '\'' => out.extend_from_slice(b"&apos;"),
+            '\n' => out.extend_from_slice(b"&#xA;"),
+            '\r' => out.extend_from_slice(b"&#xD;"),
+            '\t' => out.extend_from_slice(b"&#x9;"),
+            c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+}
+
+fn tag(
+    w: &mut impl Sink,
+    tag: &'static str,
+    attributes: &[(String, String)],
+    text: &str,
+) -> Result<(), anyhow::Error> {
+    w.start(tag, attributes)?;
+    if !text.is_empty() {
+        w.text(text)?;
+    }
+    w.end(tag)
+}
+
+/// Write a scalar, formatting into `scratch` where the value isn't text
+/// already. Recorded `attributes` replace the writer's own.
+fn write_scalar(
+    llsd: &Llsd,
+    w: &mut impl Sink,
+    scratch: &mut String,
+    settings: Settings,
+    attributes: &[(String, String)],
+) -> Result<(), anyhow::Error> {
+    use std::fmt::Write;
+    fn f64_to_xml(v: f64, buf: &mut ryu::Buffer) -> &str {
+        if v.is_nan() { "nan" } else { buf.format(v) }
     }
     match llsd {
-        Llsd::Undefined => tag(w, "undef", "")?,
-        Llsd::Boolean(b) => tag(w, "boolean", if *b { "1" } else { "0" })?,
-        Llsd::String(s) => tag(w, "string", s)?,
-        Llsd::Uuid(u) => tag(w, "uuid", u.to_string().as_str())?,
-        Llsd::Uri(u) => tag(w, "uri", u.as_str())?,
-        Llsd::Date(d) => tag(w, "date", d.to_rfc3339().as_str())?,
+        Llsd::Undefined => tag(w, "undef", attributes, "")?,
+        Llsd::Boolean(b) => tag(w, "boolean", attributes, settings.booleans.text(*b))?,
+        Llsd::String(s) => tag(w, "string", attributes, s)?,
+        Llsd::Uuid(u) => tag(
+            w,
+            "uuid",
+            attributes,
+            crate::types::uuid_str(u, &mut [0; 36]),
+        )?,
+        Llsd::Uri(u) => {
+            let text = u
+                .text_for(settings.uris)
+                .ok_or_else(|| anyhow::anyhow!("invalid URI {:?}", u.as_str()))?;
+            tag(w, "uri", attributes, &text)?
+        }
+        Llsd::Date(d) => {
+            scratch.clear();
+            write!(scratch, "{}", date::Rfc3339(d))?;
+            tag(w, "date", attributes, scratch)?
+        }
         Llsd::Binary(b) => {
             if b.is_empty() {
-                tag(w, "binary", "")?;
+                tag(w, "binary", attributes, "")?;
             } else {
-                w.write(XmlEvent::start_element("binary").attr("encoding", "base64"))?;
-                w.write(XmlEvent::characters(&BASE64_STANDARD.encode(b)))?;
-                w.write(XmlEvent::end_element())?;
+                if attributes.is_empty() {
+                    w.start_base64()?;
+                } else {
+                    w.start("binary", attributes)?;
+                }
+                crate::codec::base64_encode_chunks(b, scratch, |chunk| w.text(chunk))?;
+                w.end("binary")?;
             }
         }
-        Llsd::Integer(i) => tag(w, "integer", &i.to_string())?,
-        Llsd::Real(r) => tag(w, "real", f64_to_xml(*r).as_str())?,
-        Llsd::Array(a) => {
-            w.write(XmlEvent::start_element("array"))?;
-            for v in a {
-                write_inner(v, w)?;
-            }
-            w.write(XmlEvent::end_element())?;
+        Llsd::Integer(i) => tag(w, "integer", attributes, itoa::Buffer::new().format(*i))?,
+        Llsd::Real(r) => tag(
+            w,
+            "real",
+            attributes,
+            f64_to_xml(*r, &mut ryu::Buffer::new()),
+        )?,
+        Llsd::Array(_) | Llsd::Map(_) => unreachable!("containers are written by write_inner"),
+    }
+    Ok(())
+}
+
+/// How [`XmlEncoder`] writes booleans. The parser reads both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BooleanText {
+    /// `1` / `0`, as the viewer writes them.
+    #[default]
+    Digits,
+    /// `true` / `false`, as in the specification's examples.
+    Words,
+}
+
+impl BooleanText {
+    fn text(self, value: bool) -> &'static str {
+        match (self, value) {
+            (BooleanText::Digits, true) => "1",
+            (BooleanText::Digits, false) => "0",
+            (BooleanText::Words, true) => "true",
+            (BooleanText::Words, false) => "false",
         }
-        Llsd::Map(m) => {
-            w.write(XmlEvent::start_element("map"))?;
-            for (k, v) in m {
-                tag(w, "key", k)?;
-                write_inner(v, w)?;
+    }
+}
+
+/// Writer settings an [`XmlEncoder`] can change.
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    max_depth: usize,
+    sorted_keys: bool,
+    uris: UriPolicy,
+    undefined: UndefinedEntries,
+    booleans: BooleanText,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            sorted_keys: false,
+            uris: UriPolicy::Verbatim,
+            undefined: UndefinedEntries::Keep,
+            booleans: BooleanText::Digits,
+        }
+    }
+}
+
+/// Open containers while writing, innermost last.
+enum Frame<'a> {
+    Array {
+        items: std::slice::Iter<'a, Llsd>,
+        orders: std::slice::Iter<'a, KeyOrder>,
+    },
+    Map(Entries<'a>),
+}
+
+fn write_inner<'a>(
+    llsd: &'a Llsd,
+    w: &mut impl Sink,
+    settings: Settings,
+    stack: &mut Vec<Frame<'a>>,
+    scratch: &mut String,
+    order: Option<&'a KeyOrder>,
+) -> Result<(), anyhow::Error> {
+    let attributes = |order: Option<&'a KeyOrder>| order.map_or(&[][..], KeyOrder::attributes);
+    stack.clear();
+    let mut next = Some((llsd, order));
+    loop {
+        if let Some((value, order)) = next.take() {
+            if stack.len() >= settings.max_depth {
+                metrics::limit_hit(LlsdEncoding::Xml, Operation::Write, Limit::Depth);
+                return Err(anyhow::anyhow!("LLSD XML maximum depth exceeded"));
             }
-            w.write(XmlEvent::end_element())?;
+            match value {
+                Llsd::Array(a) => {
+                    w.start("array", attributes(order))?;
+                    stack.push(Frame::Array {
+                        items: a.iter(),
+                        orders: crate::order::items(order),
+                    });
+                }
+                Llsd::Map(m) => {
+                    w.start("map", attributes(order))?;
+                    stack.push(Frame::Map(crate::order::write_entries(
+                        m,
+                        order,
+                        settings.sorted_keys,
+                        settings.undefined,
+                    )));
+                }
+                scalar => write_scalar(scalar, w, scratch, settings, attributes(order))?,
+            }
+        }
+        match stack.last_mut() {
+            None => return Ok(()),
+            Some(Frame::Array { items, orders }) => match items.next() {
+                Some(v) => next = Some((v, orders.next())),
+                None => {
+                    w.end("array")?;
+                    stack.pop();
+                }
+            },
+            Some(Frame::Map(entries)) => match entries.next() {
+                Some((k, v, order)) => {
+                    let key_attributes = order.map_or(&[][..], KeyOrder::key_attributes);
+                    tag(w, "key", key_attributes, k)?;
+                    next = Some((v, order));
+                }
+                None => {
+                    w.end("map")?;
+                    stack.pop();
+                }
+            },
         }
     }
-    Ok(())
 }
 
 pub fn write<W: Write>(llsd: &Llsd, w: &mut EventWriter<W>) -> Result<(), anyhow::Error> {
-    use xml::writer::XmlEvent;
-    w.write(XmlEvent::start_element("llsd"))?;
-    write_inner(llsd, w)?;
-    w.write(XmlEvent::end_element())?;
-    Ok(())
+    write_with_depth(llsd, w, DEFAULT_MAX_DEPTH)
+}
+
+/// Like [`write`], failing on values nested deeper than `max_depth` levels.
+pub fn write_with_depth<W: Write>(
+    llsd: &Llsd,
+    w: &mut EventWriter<W>,
+    max_depth: usize,
+) -> Result<(), anyhow::Error> {
+    write_document(
+        llsd,
+        w,
+        Settings {
+            max_depth,
+            ..Settings::default()
+        },
+        &mut Vec::new(),
+        &mut String::new(),
+        None,
+        &[],
+    )
+}
+
+fn write_document<'a>(
+    llsd: &'a Llsd,
+    w: &mut impl Sink,
+    settings: Settings,
+    stack: &mut Vec<Frame<'a>>,
+    scratch: &mut String,
+    order: Option<&'a KeyOrder>,
+    root_attributes: &[(String, String)],
+) -> Result<(), anyhow::Error> {
+    w.start("llsd", root_attributes)?;
+    write_inner(llsd, w, settings, stack, scratch, order)?;
+    w.end("llsd")
+}
+
+/// The compact document [`write`] would produce with a default
+/// `EventWriter`, formatted straight into `buf`.
+fn write_direct<'a>(
+    llsd: &'a Llsd,
+    buf: &mut Vec<u8>,
+    declaration: bool,
+    order: Option<&'a KeyOrder>,
+    root_attributes: &[(String, String)],
+) -> Result<(), anyhow::Error> {
+    write_document(
+        llsd,
+        &mut Direct::new(buf, declaration),
+        Settings::default(),
+        &mut Vec::new(),
+        &mut String::new(),
+        order,
+        root_attributes,
+    )
+}
+
+/// Like [`write`], listing map keys in the order recorded in `doc` and
+/// giving elements the attributes recorded there.
+pub fn write_ordered<W: Write>(
+    doc: &OrderedLlsd,
+    w: &mut EventWriter<W>,
+) -> Result<(), anyhow::Error> {
+    let order = Some(&doc.order);
+    write_document(
+        &doc.llsd,
+        w,
+        Settings::default(),
+        &mut Vec::new(),
+        &mut String::new(),
+        order,
+        &doc.root_attributes,
+    )
+}
+
+pub fn to_string_ordered(doc: &OrderedLlsd) -> Result<String, anyhow::Error> {
+    let mut buf = Vec::new();
+    write_direct(
+        &doc.llsd,
+        &mut buf,
+        true,
+        Some(&doc.order),
+        &doc.root_attributes,
+    )?;
+    Ok(String::from_utf8(buf)?)
+}
+
+pub fn to_pretty_string_ordered(doc: &OrderedLlsd) -> Result<String, anyhow::Error> {
+    let mut buf = Vec::new();
+    write_ordered(
+        doc,
+        &mut EventWriter::new_with_config(
+            &mut buf,
+            xml::writer::EmitterConfig::new().perform_indent(true),
+        ),
+    )?;
+    Ok(String::from_utf8(buf)?)
 }
 
 pub fn to_pretty_string(llsd: &Llsd) -> Result<String, anyhow::Error> {
@@ -241,28 +1279,125 @@ pub fn to_pretty_string(llsd: &Llsd) -> Result<String, anyhow::Error> {
 }
 
 pub fn to_string(llsd: &Llsd) -> Result<String, anyhow::Error> {
-    let mut buf = Vec::new();
-    write(llsd, &mut EventWriter::new(&mut buf))?;
-    Ok(String::from_utf8(buf)?)
+    metrics::write(LlsdEncoding::Xml, llsd, || {
+        let mut buf = Vec::new();
+        write_direct(llsd, &mut buf, true, None, &[])?;
+        Ok(String::from_utf8(buf)?)
+    })
 }
 
 pub fn to_request(llsd: &Llsd) -> Result<Vec<u8>, anyhow::Error> {
     let mut buf = Vec::new();
-    write(
-        llsd,
-        &mut EventWriter::new_with_config(
-            &mut buf,
-            xml::writer::EmitterConfig::new().write_document_declaration(false),
-        ),
-    )?;
+    write_direct(llsd, &mut buf, false, None, &[])?;
     Ok(buf)
 }
 
+/// A reusable XML writer.
+///
+/// Keeps the container stack and the scratch buffer between calls. Each
+/// call writes a complete `<llsd>` document using the encoder's emitter
+/// settings; without any, the compact output of [`to_string`] is formatted
+/// straight into the output.
+pub struct XmlEncoder {
+    config: Option<xml::writer::EmitterConfig>,
+    settings: Settings,
+    stack: Vec<Frame<'static>>,
+    scratch: String,
+}
+
+impl XmlEncoder {
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            settings: Settings::default(),
+            stack: Vec::new(),
+            scratch: String::new(),
+        }
+    }
+
+    pub fn with_config(config: xml::writer::EmitterConfig) -> Self {
+        Self {
+            config: Some(config),
+            ..Self::new()
+        }
+    }
+
+    /// Fail instead of writing values nested deeper than `max_depth` levels.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.settings.max_depth = max_depth;
+        self
+    }
+
+    /// Write map entries sorted by key instead of in the map's iteration order.
+    pub fn with_sorted_keys(mut self, sorted_keys: bool) -> Self {
+        self.settings.sorted_keys = sorted_keys;
+        self
+    }
+
+    /// How to write URIs that didn't parse as URLs.
+    pub fn with_uri_policy(mut self, uris: UriPolicy) -> Self {
+        self.settings.uris = uris;
+        self
+    }
+
+    /// Keep (the default) or leave out map entries whose value is undefined.
+    pub fn with_undefined_entries(mut self, undefined: UndefinedEntries) -> Self {
+        self.settings.undefined = undefined;
+        self
+    }
+
+    /// Write booleans as `1`/`0` (the default) or `true`/`false`.
+    pub fn with_boolean_text(mut self, booleans: BooleanText) -> Self {
+        self.settings.booleans = booleans;
+        self
+    }
+
+    /// Append an XML document holding `llsd` to `out`.
+    ///
+    /// On error `out` may hold a partial document past its original length.
+    pub fn encode_into(&mut self, llsd: &Llsd, out: &mut Vec<u8>) -> Result<(), anyhow::Error> {
+        let mut stack = crate::output::recycle(std::mem::take(&mut self.stack));
+        let result = match &self.config {
+            Some(config) => write_document(
+                llsd,
+                &mut EventWriter::new_with_config(out, config.clone()),
+                self.settings,
+                &mut stack,
+                &mut self.scratch,
+                None,
+                &[],
+            ),
+            None => write_document(
+                llsd,
+                &mut Direct::new(out, true),
+                self.settings,
+                &mut stack,
+                &mut self.scratch,
+                None,
+                &[],
+            ),
+        };
+        self.stack = crate::output::recycle(stack);
+        result
+    }
+
+    pub fn encode(&mut self, llsd: &Llsd) -> Result<Vec<u8>, anyhow::Error> {
+        let mut out = Vec::new();
+        self.encode_into(llsd, &mut out)?;
+        Ok(out)
+    }
+}
+
+impl Default for XmlEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{TimeZone, Utc};
-    use std::collections::HashMap;
+    use crate::{Date, LlsdMap};
     use url::Url;
 
     fn round_trip(llsd: Llsd) {
@@ -327,7 +1462,7 @@ mod tests {
 
     #[test]
     fn date() {
-        let dt = Utc.timestamp_opt(1_620_000_000, 0).unwrap();
+        let dt = Date::from_timestamp(1_620_000_000, 0).unwrap();
         round_trip(Llsd::Date(dt));
     }
 
@@ -336,6 +1471,27 @@ mod tests {
         round_trip(Llsd::Binary(vec![0xde, 0xad, 0xbe, 0xef]));
     }
 
+    #[test]
+    fn large_binary_is_written_and_read_in_pieces() {
+        let data: Vec<u8> = (0..20_000).map(|i| (i * 13) as u8).collect();
+        round_trip(Llsd::Binary(data.clone()));
+        let encoded = crate::codec::base64_encode(&data);
+        let wrapped: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        let text = format!(
+            "<llsd><binary encoding=\"base64\">\n  {}\n</binary></llsd>",
+            wrapped.join("\n  ")
+        );
+        assert_eq!(from_str(&text).unwrap(), Llsd::Binary(data));
+
+        let err =
+            from_str("<llsd><map><key>a</key><binary>bGxzZA=</binary></map></llsd>").unwrap_err();
+        assert!(err.to_string().contains("partial group"), "{err}");
+    }
+
     #[test]
     fn array() {
         let arr = vec![
@@ -348,10 +1504,364 @@ mod tests {
 
     #[test]
     fn map() {
-        let mut map = HashMap::new();
+        let mut map = LlsdMap::new();
         map.insert("answer".into(), Llsd::Integer(42));
         map.insert("pi".into(), Llsd::Real(13.14));
         map.insert("greeting".into(), Llsd::String("hello".into()));
         round_trip(Llsd::Map(map));
     }
+
+    #[test]
+    fn write_rejects_excessive_depth() {
+        let mut llsd = Llsd::Integer(1);
+        for _ in 0..10_000 {
+            llsd = Llsd::Array(vec![llsd]);
+        }
+        assert!(to_string(&llsd).is_err());
+
+        let nested = Llsd::Array(vec![Llsd::Array(vec![Llsd::Integer(1)])]);
+        let mut buf = Vec::new();
+        assert!(write_with_depth(&nested, &mut EventWriter::new(&mut buf), 2).is_err());
+        let mut buf = Vec::new();
+        write_with_depth(&nested, &mut EventWriter::new(&mut buf), 3).expect("Failed to encode");
+        assert_eq!(from_slice(&buf).expect("Failed to decode"), nested);
+    }
+
+    #[test]
+    fn extract_pointer_skips_to_subtree() {
+        let mut body = LlsdMap::new();
+        body.insert("message".into(), Llsd::String("TeleportFinish".into()));
+        body.insert("blob".into(), Llsd::Binary(vec![1, 2, 3]));
+        let mut event = LlsdMap::new();
+        event.insert("skip".into(), Llsd::Array(vec![Llsd::Map(LlsdMap::new())]));
+        event.insert("body".into(), Llsd::Map(body));
+        let mut root = LlsdMap::new();
+        root.insert("id".into(), Llsd::Integer(3));
+        root.insert(
+            "events".into(),
+            Llsd::Array(vec![Llsd::Undefined, Llsd::Map(event)]),
+        );
+        let llsd = Llsd::Map(root);
+
+        for encoded in [to_string(&llsd).unwrap(), to_pretty_string(&llsd).unwrap()] {
+            for pointer in [
+                "",
+                "/id",
+                "/events/0",
+                "/events/1/body",
+                "/events/1/body/blob",
+            ] {
+                let extracted = extract_pointer(encoded.as_bytes(), pointer).unwrap();
+                assert_eq!(extracted.as_ref(), llsd.pointer(pointer), "{pointer}");
+            }
+            for pointer in ["/nope", "/events/2", "/events/x", "/id/0", "id"] {
+                assert_eq!(extract_pointer(encoded.as_bytes(), pointer).unwrap(), None);
+            }
+        }
+        let cut = to_string(&llsd).unwrap();
+        let cut = &cut.as_bytes()[..cut.len() - "</map></llsd>".len()];
+        assert!(extract_pointer(cut, "/events/1/body").unwrap().is_some());
+        assert!(extract_pointer(cut, "/missing").is_err());
+    }
+
+    #[test]
+    fn reals_round_trip_exactly() {
+        for v in [
+            0.1,
+            -0.0,
+            1.0 / 3.0,
+            1e21,
+            1e-7,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let decoded = from_str(&to_string(&Llsd::Real(v)).unwrap()).unwrap();
+            assert_eq!(decoded.as_real().map(|r| r.to_bits()), Some(v.to_bits()));
+        }
+        let decoded = from_str(&to_string(&Llsd::Real(f64::NAN)).unwrap()).unwrap();
+        assert!(decoded.as_real().is_some_and(|r| r.is_nan()));
+    }
+
+    #[test]
+    fn lenient_reals() {
+        let doc = b"<llsd><array><real>-1.#INF</real><real> 1.#QNAN </real>\
+            <real>NaN</real><real>Inf</real></array></llsd>";
+        let lenient = ParseOptions::new().with_lenient(true);
+        let reals: Vec<f64> = from_slice_with_options(doc, &lenient)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| *r.as_real().unwrap())
+            .collect();
+        assert_eq!(reals[0], f64::NEG_INFINITY);
+        assert!(reals[1].is_nan() && reals[2].is_nan());
+        assert_eq!(reals[3], f64::INFINITY);
+        assert!(from_slice_with_options(doc, &ParseOptions::new()).is_err());
+        assert!(from_slice(doc).is_err());
+    }
+
+    #[test]
+    fn encoder_matches_writers_across_calls() {
+        let mut map = LlsdMap::new();
+        map.insert("blob".into(), Llsd::Binary(vec![0x5a; 100]));
+        map.insert("list".into(), Llsd::Array(vec![Llsd::Real(0.5); 3]));
+        let docs = [Llsd::Map(map), Llsd::Binary(vec![1]), Llsd::Undefined];
+
+        let mut encoder = XmlEncoder::new();
+        let mut request = XmlEncoder::with_config(
+            xml::writer::EmitterConfig::new().write_document_declaration(false),
+        );
+        let mut out = Vec::new();
+        for llsd in &docs {
+            out.clear();
+            encoder.encode_into(llsd, &mut out).unwrap();
+            assert_eq!(
+                String::from_utf8(out.clone()).unwrap(),
+                to_string(llsd).unwrap()
+            );
+            assert_eq!(request.encode(llsd).unwrap(), to_request(llsd).unwrap());
+        }
+
+        let mut shallow = XmlEncoder::new().with_max_depth(1);
+        assert!(shallow.encode(&docs[0]).is_err());
+        assert!(shallow.encode(&docs[1]).is_ok());
+    }
+
+    #[test]
+    fn direct_output_matches_event_writer() {
+        let through_events = |llsd: &Llsd, config: xml::writer::EmitterConfig| {
+            let mut buf = Vec::new();
+            write(llsd, &mut EventWriter::new_with_config(&mut buf, config)).unwrap();
+            buf
+        };
+        let date = Date::from_timestamp(1_700_000_000, 123_456_789).unwrap();
+        let mut docs: Vec<Llsd> = vec![
+            Llsd::Date(date),
+            Llsd::Date(Date::from_timestamp(0, 0).unwrap()),
+            Llsd::String("a<b>&c]]>\"'".into()),
+            Llsd::Array(vec![Llsd::Binary(vec![]), Llsd::String(String::new())]),
+        ];
+        let options = crate::corpus::CorpusOptions::new(1445).with_max_depth(5);
+        docs.extend(crate::corpus::Generator::new(options).take(50));
+        for llsd in &docs {
+            let config = xml::writer::EmitterConfig::new();
+            assert_eq!(
+                to_string(llsd).unwrap().into_bytes(),
+                through_events(llsd, config.clone())
+            );
+            assert_eq!(
+                to_request(llsd).unwrap(),
+                through_events(llsd, config.write_document_declaration(false))
+            );
+        }
+    }
+
+    #[test]
+    fn ordered_round_trip_keeps_attributes() {
+        let text = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <llsd xmlns:ext=\"urn:example:ext\" version=\"1.0\"><map>\
+            <key ext:note=\"a &amp; &quot;b&quot;\">blob</key><binary encoding=\"base64\" />\
+            <key>list</key><array ext:sum=\"d41d8c\"><string>x</string>\
+            <binary encoding=\"base64\">AAE=</binary></array></map></llsd>";
+        let doc = from_str_ordered(text).unwrap();
+        assert_eq!(doc.llsd, from_str(text).unwrap());
+        assert_eq!(
+            doc.root_attributes,
+            [
+                ("xmlns:ext".to_string(), "urn:example:ext".to_string()),
+                ("version".to_string(), "1.0".to_string()),
+            ]
+        );
+        let blob = doc.order.get("blob").unwrap();
+        assert_eq!(
+            blob.key_attributes(),
+            [("ext:note".to_string(), "a & \"b\"".to_string())]
+        );
+        assert_eq!(
+            blob.attributes(),
+            [("encoding".to_string(), "base64".to_string())]
+        );
+        assert_eq!(to_string_ordered(&doc).unwrap(), text);
+
+        // The event writer declares the namespace and writes the same
+        // attributes.
+        let pretty = to_pretty_string_ordered(&doc).unwrap();
+        let reread = from_str_ordered(&pretty).unwrap();
+        assert_eq!(reread.llsd, doc.llsd);
+        assert_eq!(reread.root_attributes, doc.root_attributes);
+        assert_eq!(reread.order.get("list"), doc.order.get("list"));
+
+        // Without a recorded order, the plain writer's attributes.
+        assert!(
+            to_string(&doc.llsd)
+                .unwrap()
+                .starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?><llsd><map>")
+        );
+    }
+
+    #[test]
+    fn ordered_round_trip_keeps_key_order() {
+        let keys: Vec<String> = (0..20).map(|i| format!("k{:02}", (i * 7) % 20)).collect();
+        let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?><llsd><array><map>");
+        for key in &keys {
+            text.push_str(&format!(
+                "<key>{key}</key><map><key>z</key><undef /><key>a</key><integer>1</integer></map>"
+            ));
+        }
+        text.push_str("</map><integer>2</integer></array></llsd>");
+
+        let doc = from_str_ordered(&text).unwrap();
+        assert_eq!(doc.llsd, from_str(&text).unwrap());
+        assert_eq!(doc.order.get_index(0).unwrap().keys(), keys.as_slice());
+        assert_eq!(to_string_ordered(&doc).unwrap(), text);
+
+        // Keys added after parsing follow the recorded ones; removed ones are skipped.
+        let mut edited = doc.clone();
+        let map = edited.llsd.as_array_mut().unwrap()[0].as_map_mut().unwrap();
+        map.remove("k07");
+        map.insert("new".into(), Llsd::Boolean(true));
+        let reparsed = from_str_ordered(&to_string_ordered(&edited).unwrap()).unwrap();
+        let mut expected: Vec<&str> = keys.iter().map(String::as_str).collect();
+        expected.retain(|k| *k != "k07");
+        expected.push("new");
+        assert_eq!(
+            reparsed.order.get_index(0).unwrap().keys(),
+            expected.as_slice()
+        );
+        assert_eq!(reparsed.llsd, edited.llsd);
+    }
+
+    #[test]
+    fn empty_key() {
+        let mut map = LlsdMap::new();
+        map.insert(String::new(), Llsd::Real(1.5));
+        map.insert("b".to_string(), Llsd::Integer(2));
+        let xml = to_string(&Llsd::Map(map.clone())).unwrap();
+        assert!(xml.contains("<key />"));
+        assert_eq!(from_str(&xml).unwrap(), Llsd::Map(map));
+    }
+
+    #[test]
+    fn whitespace_only_keys_and_strings() {
+        let mut map = LlsdMap::new();
+        map.insert("\n\t".to_string(), Llsd::String("  ".into()));
+        let llsd = Llsd::Array(vec![Llsd::Map(map), Llsd::String(" ".into())]);
+        let xml = to_string(&llsd).unwrap();
+        assert_eq!(from_str(&xml).unwrap(), llsd);
+        let pretty = "<llsd>\n  <array>\n    <integer>1</integer>\n  </array>\n</llsd>";
+        assert_eq!(
+            from_str(pretty).unwrap(),
+            Llsd::Array(vec![Llsd::Integer(1)])
+        );
+    }
+
+    #[test]
+    fn empty_scalar_elements_read_as_defaults() {
+        let defaults = [
+            ("undef", Llsd::Undefined),
+            ("boolean", Llsd::Boolean(false)),
+            ("integer", Llsd::Integer(0)),
+            ("real", Llsd::Real(0.0)),
+            ("string", Llsd::String(String::new())),
+            ("uuid", Llsd::Uuid(Uuid::nil())),
+            ("date", Llsd::Date(Date::default())),
+            ("uri", Llsd::Uri(Uri::Empty)),
+            ("binary", Llsd::Binary(Vec::new())),
+        ];
+        for (name, expected) in defaults {
+            for element in [
+                format!("<{name}/>"),
+                format!("<{name}></{name}>"),
+                format!("<{name}> \n\t</{name}>"),
+                format!("<{name}><!-- empty --></{name}>"),
+            ] {
+                let text = format!("<llsd><array>{element}</array></llsd>");
+                let expected = match (&expected, element.contains('\t')) {
+                    (Llsd::String(_), true) => Llsd::String(" \n\t".into()),
+                    _ => expected.clone(),
+                };
+                assert_eq!(
+                    from_str(&text).unwrap(),
+                    Llsd::Array(vec![expected]),
+                    "{element}"
+                );
+                assert_eq!(
+                    extract_pointer(text.as_bytes(), "/0").unwrap(),
+                    Some(from_str(&text).unwrap()[0].clone()),
+                    "{element}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn padded_scalar_elements() {
+        let id = Uuid::from_u128(0x1234);
+        let date = Date::from_timestamp(1_714_979_289, 0).unwrap();
+        let text = format!(
+            "<llsd><array>
+                <boolean>\n  true\n</boolean>
+                <integer>\n  -12 </integer>
+                <real>\t2.5\n</real>
+                <uuid>
+                    {id}
+                </uuid>
+                <date> {} </date>
+                <integer>1<!-- split -->2</integer>
+                <string> kept </string>
+            </array></llsd>",
+            date.to_rfc3339()
+        );
+        assert_eq!(
+            from_str(&text).unwrap(),
+            Llsd::Array(vec![
+                Llsd::Boolean(true),
+                Llsd::Integer(-12),
+                Llsd::Real(2.5),
+                Llsd::Uuid(id),
+                Llsd::Date(date),
+                Llsd::Integer(12),
+                Llsd::String(" kept ".into()),
+            ])
+        );
+        assert!(from_str("<llsd><integer> 1 2 </integer></llsd>").is_err());
+    }
+
+    #[test]
+    fn encoder_boolean_text() {
+        let llsd = Llsd::Array(vec![Llsd::Boolean(true), Llsd::Boolean(false)]);
+        let digits = XmlEncoder::new().encode(&llsd).unwrap();
+        assert!(
+            String::from_utf8(digits)
+                .unwrap()
+                .contains("<boolean>1</boolean><boolean>0</boolean>")
+        );
+        for encoder in [
+            XmlEncoder::new(),
+            XmlEncoder::with_config(xml::writer::EmitterConfig::new().perform_indent(true)),
+        ] {
+            let mut encoder = encoder.with_boolean_text(BooleanText::Words);
+            let text = String::from_utf8(encoder.encode(&llsd).unwrap()).unwrap();
+            assert!(text.contains("<boolean>true</boolean>"), "{text}");
+            assert!(text.contains("<boolean>false</boolean>"), "{text}");
+            assert_eq!(from_str(&text).unwrap(), llsd);
+        }
+    }
+
+    #[test]
+    fn encoder_sorts_keys() {
+        let map: LlsdMap = (0..12)
+            .rev()
+            .map(|i| (format!("k{i:02}"), Llsd::Integer(i)))
+            .collect();
+        let mut encoder = XmlEncoder::new().with_sorted_keys(true);
+        let text = String::from_utf8(encoder.encode(&Llsd::Map(map)).unwrap()).unwrap();
+        let doc = from_str_ordered(&text).unwrap();
+        let expected: Vec<String> = (0..12).map(|i| format!("k{i:02}")).collect();
+        assert_eq!(doc.order.keys(), expected.as_slice());
+    }
 }
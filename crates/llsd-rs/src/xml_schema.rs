@@ -0,0 +1,243 @@
+//! The LLSD XML document type, as a DTD and as an XML Schema, for
+//! toolchains that validate stored XML.
+//!
+//! Both describe structure only, as the LLSD specification's DTD does:
+//! scalar elements hold text, whose syntax the parsers check (and accept
+//! empty, as the default value). With the `validate` feature, [`validate`]
+//! checks a document against the same rules without an external validator.
+//!
+//! ```
+//! use llsd_rs::xml_schema;
+//!
+//! let doctype = format!("<!DOCTYPE llsd [\n{}]>", xml_schema::DTD);
+//! assert!(doctype.contains("<!ELEMENT undef EMPTY>"));
+//! assert!(xml_schema::XSD.starts_with("<?xml"));
+//! ```
+
+/// The LLSD XML DTD. It declares no parameter entities, so it can go in a
+/// document's internal subset as well as in an external `.dtd` file.
+pub const DTD: &str = r#"<!ELEMENT llsd (undef | boolean | integer | real | uuid | string | date | uri | binary | map | array)>
+<!ELEMENT undef EMPTY>
+<!ELEMENT boolean (#PCDATA)>
+<!ELEMENT integer (#PCDATA)>
+<!ELEMENT real (#PCDATA)>
+<!ELEMENT uuid (#PCDATA)>
+<!ELEMENT string (#PCDATA)>
+<!ELEMENT date (#PCDATA)>
+<!ELEMENT uri (#PCDATA)>
+<!ELEMENT binary (#PCDATA)>
+<!ATTLIST binary encoding CDATA "base64">
+<!ELEMENT key (#PCDATA)>
+<!ELEMENT map (key, (undef | boolean | integer | real | uuid | string | date | uri | binary | map | array))*>
+<!ELEMENT array (undef | boolean | integer | real | uuid | string | date | uri | binary | map | array)*>
+"#;
+
+/// The LLSD XML document type as an XML Schema (no target namespace).
+pub const XSD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" elementFormDefault="qualified">
+  <xs:group name="data">
+    <xs:choice>
+      <xs:element name="undef">
+        <xs:complexType/>
+      </xs:element>
+      <xs:element name="boolean" type="xs:string"/>
+      <xs:element name="integer" type="xs:string"/>
+      <xs:element name="real" type="xs:string"/>
+      <xs:element name="uuid" type="xs:string"/>
+      <xs:element name="string" type="xs:string"/>
+      <xs:element name="date" type="xs:string"/>
+      <xs:element name="uri" type="xs:string"/>
+      <xs:element name="binary">
+        <xs:complexType>
+          <xs:simpleContent>
+            <xs:extension base="xs:string">
+              <xs:attribute name="encoding" type="xs:string" default="base64"/>
+            </xs:extension>
+          </xs:simpleContent>
+        </xs:complexType>
+      </xs:element>
+      <xs:element name="map" type="map"/>
+      <xs:element name="array" type="array"/>
+    </xs:choice>
+  </xs:group>
+  <xs:complexType name="map">
+    <xs:sequence minOccurs="0" maxOccurs="unbounded">
+      <xs:element name="key" type="xs:string"/>
+      <xs:group ref="data"/>
+    </xs:sequence>
+  </xs:complexType>
+  <xs:complexType name="array">
+    <xs:group ref="data" minOccurs="0" maxOccurs="unbounded"/>
+  </xs:complexType>
+  <xs:element name="llsd">
+    <xs:complexType>
+      <xs:group ref="data"/>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>
+"#;
+
+#[cfg(feature = "validate")]
+const DATA: &[&str] = &[
+    "undef", "boolean", "integer", "real", "uuid", "string", "date", "uri", "binary", "map",
+    "array",
+];
+
+/// An element being validated, by what it may contain.
+#[cfg(feature = "validate")]
+enum Open {
+    /// `<llsd>`, with whether its value was seen.
+    Root(bool),
+    /// `<undef>`: nothing at all.
+    Empty,
+    /// Scalars and `<key>`: text only.
+    Text,
+    Array,
+    /// With whether a key is expected next.
+    Map(bool),
+}
+
+/// Check `data` against [`DTD`]: one value in `<llsd>`, maps alternating
+/// `<key>` and a value, text only in scalars and keys, nothing in
+/// `<undef>`, and no attributes but `encoding` on `<binary>`. Errors give
+/// the line and column (from 1) the parser was at after the offending node.
+#[cfg(feature = "validate")]
+pub fn validate(data: &[u8]) -> Result<(), anyhow::Error> {
+    use xml::{EventReader, common::Position, reader::XmlEvent};
+
+    let mut reader = EventReader::new(std::io::Cursor::new(data));
+    let mut stack: Vec<Open> = Vec::new();
+    let mut seen_root = false;
+    loop {
+        let event = reader.next()?;
+        let invalid = |message: String| {
+            let pos = reader.position();
+            anyhow::anyhow!(
+                "invalid LLSD XML at {}:{}: {message}",
+                pos.row + 1,
+                pos.column + 1
+            )
+        };
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let name = name.local_name;
+                if let Some(attribute) = attributes
+                    .iter()
+                    .find(|a| !(name == "binary" && a.name.local_name == "encoding"))
+                {
+                    return Err(invalid(format!(
+                        "undeclared attribute {} on <{name}>",
+                        attribute.name.local_name
+                    )));
+                }
+                let is_data = DATA.contains(&name.as_str());
+                let allowed = match stack.last_mut() {
+                    None => !seen_root && name == "llsd",
+                    Some(Open::Root(full)) => is_data && !std::mem::replace(full, true),
+                    Some(Open::Array) => is_data,
+                    Some(Open::Map(expect_key)) => {
+                        let ok = if *expect_key { name == "key" } else { is_data };
+                        *expect_key = !*expect_key;
+                        ok
+                    }
+                    Some(Open::Empty | Open::Text) => false,
+                };
+                if !allowed {
+                    return Err(invalid(format!("unexpected <{name}>")));
+                }
+                seen_root = true;
+                stack.push(match name.as_str() {
+                    "llsd" => Open::Root(false),
+                    "undef" => Open::Empty,
+                    "array" => Open::Array,
+                    "map" => Open::Map(true),
+                    _ => Open::Text,
+                });
+            }
+            XmlEvent::EndElement { .. } => match stack.pop() {
+                Some(Open::Root(false)) => return Err(invalid("<llsd> holds no value".into())),
+                Some(Open::Map(false)) => return Err(invalid("<key> without a value".into())),
+                _ => {}
+            },
+            XmlEvent::Characters(_) | XmlEvent::CData(_)
+                if !matches!(stack.last(), Some(Open::Text)) =>
+            {
+                return Err(invalid("text outside a scalar or key".into()));
+            }
+            XmlEvent::Whitespace(_) if matches!(stack.last(), Some(Open::Empty)) => {
+                return Err(invalid("<undef> must be empty".into()));
+            }
+            XmlEvent::EndDocument => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_are_well_formed() {
+        let doc = format!("<?xml version=\"1.0\"?><!DOCTYPE llsd [\n{DTD}]><llsd><undef/></llsd>");
+        assert_eq!(crate::xml::from_str(&doc).unwrap(), crate::Llsd::Undefined);
+        for event in xml::EventReader::new(XSD.as_bytes()) {
+            event.unwrap();
+        }
+        for element in [
+            "llsd", "undef", "boolean", "integer", "real", "uuid", "string", "date", "uri",
+            "binary", "key", "map", "array",
+        ] {
+            assert!(DTD.contains(&format!("<!ELEMENT {element} ")), "{element}");
+            assert!(XSD.contains(&format!("name=\"{element}\"")), "{element}");
+        }
+    }
+
+    #[cfg(feature = "validate")]
+    #[test]
+    fn validates_structure() {
+        use crate::corpus::{CorpusOptions, Generator};
+        for llsd in Generator::new(CorpusOptions::new(1457)).take(50) {
+            let xml = crate::xml::to_string(&llsd).unwrap();
+            validate(xml.as_bytes()).unwrap_or_else(|e| panic!("{e}\n{xml}"));
+        }
+        let pretty = "<?xml version=\"1.0\"?>\n<!-- note -->\n<llsd>\n  <map>\n    <key>a</key>\n    \
+                      <binary encoding=\"base64\">AA==</binary>\n  </map>\n</llsd>\n";
+        validate(pretty.as_bytes()).unwrap();
+
+        let invalid = [
+            ("<llsd/>", "<llsd> holds no value"),
+            ("<llsd><integer/><integer/></llsd>", "unexpected <integer>"),
+            (
+                "<llsd><map><integer>1</integer></map></llsd>",
+                "unexpected <integer>",
+            ),
+            (
+                "<llsd><map><key>a</key></map></llsd>",
+                "<key> without a value",
+            ),
+            (
+                "<llsd><map><key>a</key><key>b</key></map></llsd>",
+                "unexpected <key>",
+            ),
+            (
+                "<llsd>\n<array>x</array></llsd>",
+                "at 2:8: text outside a scalar or key",
+            ),
+            ("<llsd><string><b/></string></llsd>", "unexpected <b>"),
+            ("<llsd><undef> </undef></llsd>", "<undef> must be empty"),
+            (
+                "<llsd><real kind=\"f\">1</real></llsd>",
+                "undeclared attribute kind on <real>",
+            ),
+            ("<map/>", "unexpected <map>"),
+            ("<llsd><string>", "Unexpected end of stream"),
+        ];
+        for (doc, message) in invalid {
+            let err = validate(doc.as_bytes()).unwrap_err().to_string();
+            assert!(err.contains(message), "{doc}: {err}");
+        }
+    }
+}
@@ -1,5 +1,5 @@
 #![cfg(feature = "derive")]
-use llsd_rs::{Llsd, LlsdFrom, LlsdFromTo};
+use llsd_rs::{FromLlsd, IntoLlsd, Llsd, LlsdError, LlsdFrom, LlsdFromTo, Uuid};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, LlsdFromTo)]
@@ -105,6 +105,52 @@ fn flatten_merge() {
     assert_eq!(o, back);
 }
 
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(deny_unknown_fields)]
+struct FlattenOptional {
+    id: u32,
+    #[llsd(flatten)]
+    extra: Option<FlattenMiddle>,
+}
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct FlattenMiddle {
+    c: i32,
+    #[llsd(flatten)]
+    inner: FlattenInner,
+}
+
+#[test]
+fn flatten_option_and_nested() {
+    let some = FlattenOptional {
+        id: 1,
+        extra: Some(FlattenMiddle {
+            c: 4,
+            inner: FlattenInner { a: 2, b: 3 },
+        }),
+    };
+    let l: Llsd = (&some).into();
+    let mut keys: Vec<_> = l.as_map().unwrap().keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, ["a", "b", "c", "id"]);
+    assert_eq!(FlattenOptional::try_from(&l).unwrap(), some);
+    assert_eq!(FlattenOptional::try_from(l).unwrap(), some);
+
+    let none = FlattenOptional { id: 1, extra: None };
+    let l: Llsd = none.clone().into();
+    assert_eq!(l.as_map().unwrap().len(), 1);
+    assert_eq!(FlattenOptional::try_from(&l).unwrap(), none);
+    assert_eq!(FlattenOptional::try_from(l).unwrap(), none);
+
+    // One inner key present selects `Some`, so the missing ones are reported
+    let partial = Llsd::map().insert("id", 1).unwrap().insert("a", 2).unwrap();
+    let err = FlattenOptional::try_from(&partial).unwrap_err();
+    assert_eq!(err.to_string(), "missing field at c");
+
+    let unknown = Llsd::map().insert("id", 1).unwrap().insert("z", 2).unwrap();
+    let err = FlattenOptional::try_from(&unknown).unwrap_err();
+    assert_eq!(err.to_string(), "unknown field at z");
+}
+
 #[test]
 fn tuple_try_from() {
     let l = Llsd::Array(vec![1i32.into(), "hi".into()]);
@@ -142,3 +188,820 @@ fn with_attribute_round_trip() {
     let back: WithDemo = WithDemo::try_from(&l).unwrap();
     assert_eq!(w, back);
 }
+
+#[test]
+fn owned_try_from_moves_out_of_map() {
+    let mut map = HashMap::new();
+    map.insert("first".to_string(), Inner { value: 10 });
+    let c = Collections {
+        numbers: vec![1, 2, 3],
+        data: map,
+        tuple: (5, "hi".into()),
+    };
+    let l: Llsd = c.clone().into();
+    let back = Collections::try_from(l).unwrap();
+    assert_eq!(c, back);
+}
+
+#[test]
+fn owned_try_from_flatten_uses_remaining_keys() {
+    let o = FlattenOuter {
+        id: 1,
+        inner: FlattenInner { a: 2, b: 3 },
+    };
+    let l: Llsd = o.clone().into();
+    let back = FlattenOuter::try_from(l).unwrap();
+    assert_eq!(o, back);
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct SharedKeyInner {
+    id: i32,
+}
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct SharedKeyOuter {
+    id: i32,
+    #[llsd(flatten)]
+    inner: SharedKeyInner,
+}
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct SharedKeyOptional {
+    id: i32,
+    #[llsd(flatten)]
+    inner: Option<SharedKeyInner>,
+}
+
+#[test]
+fn owned_and_borrowed_agree_on_shared_flatten_keys() {
+    let l = Llsd::map().insert("id", 5).unwrap();
+    let borrowed = SharedKeyOuter::try_from(&l).unwrap();
+    assert_eq!(borrowed.inner, SharedKeyInner { id: 5 });
+    assert_eq!(SharedKeyOuter::try_from(l.clone()).unwrap(), borrowed);
+
+    let borrowed = SharedKeyOptional::try_from(&l).unwrap();
+    assert_eq!(borrowed.inner, Some(SharedKeyInner { id: 5 }));
+    assert_eq!(SharedKeyOptional::try_from(l).unwrap(), borrowed);
+}
+
+#[test]
+fn owned_try_from_reports_missing_field() {
+    let l = Llsd::map().insert("name", "x").unwrap();
+    let err = Simple::try_from(l).unwrap_err();
+    assert_eq!(err.to_string(), "missing field at id");
+}
+
+#[test]
+fn borrowed_into_matches_owned() {
+    let o = FlattenOuter {
+        id: 4,
+        inner: FlattenInner { a: 5, b: 6 },
+    };
+    let borrowed = Llsd::from(&o);
+    let owned: Llsd = o.clone().into();
+    assert_eq!(borrowed, owned);
+
+    let mut data = HashMap::new();
+    data.insert("k".to_string(), Inner { value: 1 });
+    let c = Collections {
+        numbers: vec![7, 8],
+        data,
+        tuple: (1, "one".into()),
+    };
+    let borrowed = Llsd::from(&c);
+    assert_eq!(Collections::try_from(&borrowed).unwrap(), c);
+}
+
+#[test]
+fn borrowed_into_with_attribute() {
+    let w = WithDemo { id: 1, code: 77 };
+    let l = Llsd::from(&w);
+    assert_eq!(l["code"].as_string().unwrap(), "77");
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(rename_all(serialize = "camelCase", deserialize = "PascalCase"))]
+struct SplitRenames {
+    agent_id: u32,
+    #[llsd(rename(serialize = "region", deserialize = "RegionName"))]
+    region_name: String,
+    #[llsd(rename(deserialize = "Flags"))]
+    flag_bits: u32,
+}
+
+#[test]
+fn split_renames_read_and_write_different_keys() {
+    let l = Llsd::map()
+        .insert("AgentId", 1u32)
+        .unwrap()
+        .insert("RegionName", "Ahern")
+        .unwrap()
+        .insert("Flags", 4u32)
+        .unwrap();
+    let parsed = SplitRenames::try_from(&l).unwrap();
+    assert_eq!(parsed.agent_id, 1);
+    assert_eq!(parsed.region_name, "Ahern");
+    assert_eq!(parsed.flag_bits, 4);
+
+    let out: Llsd = parsed.into();
+    let map = out.as_map().unwrap();
+    assert_eq!(map.get("agentId"), Some(&Llsd::Integer(1)));
+    assert_eq!(map.get("region"), Some(&Llsd::String("Ahern".into())));
+    assert_eq!(map.get("flagBits"), Some(&Llsd::Integer(4)));
+    assert!(!map.contains_key("RegionName"));
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(deny_unknown_fields)]
+struct ServerRecord {
+    #[llsd(deserialize_only)]
+    id: u32,
+    name: String,
+    #[llsd(serialize_only, default)]
+    revision: u32,
+}
+
+#[test]
+fn serialize_only_and_deserialize_only() {
+    let record = ServerRecord {
+        id: 7,
+        name: "a".into(),
+        revision: 3,
+    };
+    let l: Llsd = (&record).into();
+    let map = l.as_map().unwrap();
+    assert!(!map.contains_key("id"));
+    assert_eq!(map.get("revision"), Some(&Llsd::Integer(3)));
+
+    let input = Llsd::map()
+        .insert("id", 7)
+        .unwrap()
+        .insert("name", "a")
+        .unwrap();
+    let parsed = ServerRecord::try_from(&input).unwrap();
+    assert_eq!((parsed.id, parsed.revision), (7, 0));
+
+    let err = ServerRecord::try_from(input.insert("revision", 3).unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown field at revision");
+}
+
+/// Wire form: region corners in meters under the viewer's key names.
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct WireRegion {
+    #[llsd(rename = "RegionX")]
+    region_x: u32,
+    #[llsd(rename = "RegionY")]
+    region_y: u32,
+}
+
+/// Domain form: grid coordinates, converted through `WireRegion`.
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(from = "WireRegion", into = "WireRegion")]
+enum GridPos {
+    Origin,
+    At(u32, u32),
+}
+
+impl From<WireRegion> for GridPos {
+    fn from(wire: WireRegion) -> Self {
+        match (wire.region_x / 256, wire.region_y / 256) {
+            (0, 0) => GridPos::Origin,
+            (x, y) => GridPos::At(x, y),
+        }
+    }
+}
+
+impl From<GridPos> for WireRegion {
+    fn from(pos: GridPos) -> Self {
+        let (x, y) = match pos {
+            GridPos::Origin => (0, 0),
+            GridPos::At(x, y) => (x, y),
+        };
+        WireRegion {
+            region_x: x * 256,
+            region_y: y * 256,
+        }
+    }
+}
+
+#[test]
+fn container_from_into_go_through_wire_type() {
+    let pos = GridPos::At(1000, 1001);
+    let l: Llsd = (&pos).into();
+    assert_eq!(l.get("RegionX"), Some(&Llsd::Integer(256_000)));
+    assert_eq!(GridPos::try_from(&l).unwrap(), pos);
+    assert_eq!(
+        GridPos::try_from(Llsd::from(GridPos::Origin)).unwrap(),
+        GridPos::Origin
+    );
+
+    let err = GridPos::try_from(&Llsd::map().insert("RegionX", 1).unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "missing field at RegionY");
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(as_array)]
+struct Vector3 {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+#[test]
+fn as_array_packs_fields_in_declaration_order() {
+    let v = Vector3 {
+        x: 1.0,
+        y: 2.0,
+        z: 3.0,
+    };
+    let l: Llsd = (&v).into();
+    assert_eq!(
+        l,
+        Llsd::Array(vec![Llsd::Real(1.0), Llsd::Real(2.0), Llsd::Real(3.0)])
+    );
+    assert_eq!(Vector3::try_from(&l).unwrap(), v);
+    assert_eq!(Vector3::try_from(l).unwrap(), v);
+
+    let err = Vector3::try_from(&Llsd::Array(vec![Llsd::Real(1.0)])).unwrap_err();
+    assert_eq!(err.to_string(), "invalid length: expected 3, found 1");
+    let bad = Llsd::Array(vec![Llsd::Real(1.0), Llsd::Real(2.0), Llsd::map()]);
+    let err = Vector3::try_from(bad).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type at [2]: expected real, found map"
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct CapsSummary {
+    #[llsd(path = "/agent/info/id")]
+    agent_id: u32,
+    #[llsd(path = "/agent/info/name")]
+    agent_name: Option<String>,
+    region: String,
+}
+
+#[test]
+fn path_fields_map_to_nested_locations() {
+    let summary = CapsSummary {
+        agent_id: 7,
+        agent_name: Some("Ann".into()),
+        region: "Ahern".into(),
+    };
+    let l: Llsd = (&summary).into();
+    assert_eq!(l.pointer("/agent/info/id"), Some(&Llsd::Integer(7)));
+    assert_eq!(l.pointer("/agent/info/name"), Some(&Llsd::from("Ann")));
+    assert_eq!(CapsSummary::try_from(&l).unwrap(), summary);
+    assert_eq!(CapsSummary::try_from(l).unwrap(), summary);
+
+    let missing = Llsd::map().insert("region", "Ahern").unwrap();
+    let err = CapsSummary::try_from(&missing).unwrap_err();
+    assert_eq!(err.to_string(), "missing field at agent.info.id");
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct AgentInfo {
+    #[llsd(empty_as_none)]
+    group_name: Option<String>,
+    #[llsd(empty_as_none)]
+    group_id: Option<Uuid>,
+    #[llsd(empty_as_none)]
+    home_uri: Option<llsd_rs::Uri>,
+}
+
+#[test]
+fn empty_as_none_treats_viewer_placeholders_as_absent() {
+    let l = Llsd::map()
+        .insert("group_name", "")
+        .unwrap()
+        .insert("group_id", Uuid::nil())
+        .unwrap()
+        .insert("home_uri", Llsd::Uri(llsd_rs::Uri::new()))
+        .unwrap();
+    let empty = AgentInfo {
+        group_name: None,
+        group_id: None,
+        home_uri: None,
+    };
+    assert_eq!(AgentInfo::try_from(&l).unwrap(), empty);
+    assert_eq!(AgentInfo::try_from(l).unwrap(), empty);
+
+    let id = Uuid::from_u128(7);
+    let mut info = AgentInfo {
+        group_name: Some("Builders".into()),
+        group_id: Some(id),
+        home_uri: None,
+    };
+    assert_eq!(AgentInfo::try_from(Llsd::from(&info)).unwrap(), info);
+
+    let delta = Llsd::map()
+        .insert("group_name", "")
+        .unwrap()
+        .insert("group_id", "")
+        .unwrap();
+    info.update_from_llsd(&delta).unwrap();
+    assert_eq!(info, empty);
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(rename_all = "camelCase", deny_unknown_fields)]
+struct ObjectUpdate {
+    r#type: u32,
+    r#ref_id: u32,
+    #[llsd(rename = "god_level")]
+    r#override: u8,
+    #[llsd(rename = "look at \"here\"!\n")]
+    look_at: String,
+    #[llsd(path = "/r#box/a b")]
+    nested: i32,
+}
+
+#[test]
+fn raw_identifiers_and_arbitrary_keys() {
+    let update = ObjectUpdate {
+        r#type: 1,
+        r#ref_id: 2,
+        r#override: 3,
+        look_at: "north".into(),
+        nested: 4,
+    };
+    let l = Llsd::from(&update);
+    let map = l.as_map().unwrap();
+    let mut keys: Vec<_> = map.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        ["god_level", "look at \"here\"!\n", "r#box", "refId", "type"]
+    );
+    assert_eq!(
+        l.get("r#box").and_then(|b| b.get("a b")),
+        Some(&Llsd::Integer(4))
+    );
+    assert_eq!(ObjectUpdate::try_from(&l).unwrap(), update);
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct InventoryNode {
+    name: std::borrow::Cow<'static, str>,
+    owner: std::sync::Arc<str>,
+    shared: std::rc::Rc<Simple>,
+    #[llsd(default)]
+    children: Vec<InventoryNode>,
+    parent: Option<Box<InventoryNode>>,
+}
+
+#[test]
+fn smart_pointer_and_recursive_fields() {
+    let root = InventoryNode {
+        name: "My Inventory".into(),
+        owner: "Ann".into(),
+        shared: std::rc::Rc::new(Simple { id: 1, name: None }),
+        children: Vec::new(),
+        parent: None,
+    };
+    let node = InventoryNode {
+        name: std::borrow::Cow::Owned("Objects".to_string()),
+        owner: "Ann".into(),
+        shared: std::rc::Rc::new(Simple {
+            id: 2,
+            name: Some("x".into()),
+        }),
+        children: vec![root.clone()],
+        parent: Some(Box::new(root)),
+    };
+    let l = Llsd::from(&node);
+    assert_eq!(
+        l.get("parent").and_then(|p| p.get("name")),
+        Some(&Llsd::from("My Inventory"))
+    );
+    assert_eq!(l.get("owner"), Some(&Llsd::from("Ann")));
+    assert_eq!(InventoryNode::try_from(&l).unwrap(), node);
+    assert_eq!(InventoryNode::try_from(l).unwrap(), node);
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(deny_unknown_fields)]
+struct SessionInfo {
+    session_id: u32,
+    #[llsd(skip_deserializing)]
+    circuit_code: u32,
+}
+
+#[test]
+fn deny_unknown_fields_matches_own_and_flattened_keys() {
+    let input = Llsd::map()
+        .insert("session_id", 1)
+        .unwrap()
+        .insert("circuit_code", 2)
+        .unwrap();
+    let parsed = SessionInfo::try_from(input.clone()).unwrap();
+    assert_eq!((parsed.session_id, parsed.circuit_code), (1, 0));
+    let err = SessionInfo::try_from(&input.insert("agent_id", 3).unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown field at agent_id");
+
+    let nested = Llsd::map()
+        .insert("id", 1)
+        .unwrap()
+        .insert("a", 2)
+        .unwrap()
+        .insert("z", 3)
+        .unwrap();
+    let err = FlattenOptional::try_from(&nested).unwrap_err();
+    assert_eq!(err.to_string(), "unknown field at z");
+}
+
+#[test]
+fn integer_fields_reject_out_of_range_values() {
+    let err = Simple::try_from(&Llsd::map().insert("id", -1).unwrap()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "out of range at id: -1 does not fit in u32"
+    );
+    let err = ObjectUpdate::try_from(
+        &Llsd::map()
+            .insert("type", 1)
+            .unwrap()
+            .insert("refId", 2)
+            .unwrap()
+            .insert("god_level", 256)
+            .unwrap(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "out of range at god_level: 256 does not fit in u8"
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct AssetInfo {
+    #[llsd(int_as = "string")]
+    size: u64,
+    #[llsd(int_as = "real")]
+    created: i64,
+    #[llsd(int_as = "binary", default)]
+    region_handle: Option<u64>,
+}
+
+#[test]
+fn int_as_writes_wide_integers_losslessly() {
+    let info = AssetInfo {
+        size: 6_000_000_000,
+        created: -4_000_000_000,
+        region_handle: Some(0x0003_e800_0003_e800),
+    };
+    let l = Llsd::from(&info);
+    assert_eq!(l["size"], Llsd::String("6000000000".into()));
+    assert_eq!(l["created"], Llsd::Real(-4_000_000_000.0));
+    assert_eq!(
+        l["region_handle"],
+        Llsd::Binary(vec![0, 3, 0xe8, 0, 0, 3, 0xe8, 0])
+    );
+    assert_eq!(AssetInfo::try_from(&l).unwrap(), info);
+    assert_eq!(AssetInfo::try_from(l).unwrap(), info);
+}
+
+#[test]
+fn int_as_reads_any_encoding() {
+    let l = Llsd::map()
+        .insert(
+            "size",
+            Llsd::Binary(6_000_000_000u64.to_be_bytes().to_vec()),
+        )
+        .unwrap()
+        .insert("created", "-4000000000")
+        .unwrap()
+        .insert("region_handle", 12)
+        .unwrap();
+    let parsed = AssetInfo::try_from(&l).unwrap();
+    assert_eq!(parsed.size, 6_000_000_000);
+    assert_eq!(parsed.created, -4_000_000_000);
+    assert_eq!(parsed.region_handle, Some(12));
+
+    let short = l.insert("size", Llsd::Binary(vec![1, 2])).unwrap();
+    let err = AssetInfo::try_from(&short).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid length at size: expected 8, found 2"
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct Asset {
+    data: Vec<u8>,
+    hash: [u8; 4],
+    #[llsd(default)]
+    thumbnail: Option<Vec<u8>>,
+    #[llsd(binary = false)]
+    palette: Vec<u8>,
+}
+
+#[test]
+fn byte_containers_default_to_binary() {
+    let asset = Asset {
+        data: vec![1, 2, 3],
+        hash: [0xde, 0xad, 0xbe, 0xef],
+        thumbnail: Some(vec![9]),
+        palette: vec![7, 8],
+    };
+    let l = Llsd::from(&asset);
+    assert_eq!(l["data"], Llsd::Binary(vec![1, 2, 3]));
+    assert_eq!(l["hash"], Llsd::Binary(vec![0xde, 0xad, 0xbe, 0xef]));
+    assert_eq!(l["thumbnail"], Llsd::Binary(vec![9]));
+    assert_eq!(
+        l["palette"],
+        Llsd::Array(vec![Llsd::Integer(7), Llsd::Integer(8)])
+    );
+    assert_eq!(Asset::try_from(&l).unwrap(), asset);
+    let xml = llsd_rs::xml::to_string(&l).unwrap();
+    assert_eq!(
+        Asset::try_from(llsd_rs::xml::from_str(&xml).unwrap()).unwrap(),
+        asset
+    );
+}
+
+#[test]
+fn byte_containers_accept_integer_arrays() {
+    let l = Llsd::map()
+        .insert("data", vec![1u8, 2, 3])
+        .unwrap()
+        .insert("hash", Llsd::Binary(vec![0xde, 0xad, 0xbe, 0xef]))
+        .unwrap()
+        .insert("palette", vec![7u8, 8])
+        .unwrap();
+    let parsed = Asset::try_from(&l).unwrap();
+    assert_eq!(parsed.data, vec![1, 2, 3]);
+    assert_eq!(parsed.thumbnail, None);
+
+    let short = l.insert("hash", Llsd::Binary(vec![1, 2])).unwrap();
+    let err = Asset::try_from(&short).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid length at hash: expected 4, found 2"
+    );
+}
+
+#[cfg(feature = "bytes")]
+#[derive(Debug, PartialEq, LlsdFromTo)]
+struct Packet {
+    body: bytes::Bytes,
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_default_to_binary() {
+    let packet = Packet {
+        body: bytes::Bytes::from_static(b"hi"),
+    };
+    let l = Llsd::from(&packet);
+    assert_eq!(l["body"], Llsd::Binary(b"hi".to_vec()));
+    assert_eq!(Packet::try_from(l).unwrap(), packet);
+}
+
+/// A type with hand-written std conversions, bridged into the derive traits.
+#[derive(Debug, Clone, PartialEq)]
+struct Color(u8, u8, u8);
+
+impl TryFrom<&Llsd> for Color {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: &Llsd) -> anyhow::Result<Self> {
+        let (r, g, b) = <(u8, u8, u8)>::try_from(llsd)?;
+        Ok(Color(r, g, b))
+    }
+}
+
+impl TryFrom<Llsd> for Color {
+    type Error = anyhow::Error;
+
+    fn try_from(llsd: Llsd) -> anyhow::Result<Self> {
+        Color::try_from(&llsd)
+    }
+}
+
+impl From<&Color> for Llsd {
+    fn from(color: &Color) -> Self {
+        Llsd::from((color.0, color.1, color.2))
+    }
+}
+
+impl From<Color> for Llsd {
+    fn from(color: Color) -> Self {
+        Llsd::from(&color)
+    }
+}
+
+llsd_rs::from_llsd_via_try_from!(Color);
+llsd_rs::into_llsd_via_from!(Color);
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct Theme {
+    name: String,
+    accent: Color,
+    palette: Vec<Color>,
+}
+
+#[test]
+fn bridged_field_types_round_trip() {
+    let theme = Theme {
+        name: "dusk".into(),
+        accent: Color(1, 2, 3),
+        palette: vec![Color(4, 5, 6)],
+    };
+    let l = theme.to_llsd();
+    assert_eq!(l.get("accent"), Some(&Llsd::from((1u8, 2u8, 3u8))));
+    assert_eq!(Theme::from_llsd(&l).unwrap(), theme);
+    assert_eq!(Theme::from_llsd_owned(l.clone()).unwrap(), theme);
+    assert_eq!(Theme::try_from(&l).unwrap(), theme);
+
+    let l = l
+        .insert("palette", Llsd::Array(vec![Llsd::Integer(1)]))
+        .unwrap();
+    let err = Theme::from_llsd(&l).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type at palette[0]: expected array, found integer"
+    );
+}
+
+/// Keeps its own `TryFrom<&Llsd>`, which accepts a bare string as well.
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(traits_only)]
+struct Label {
+    text: String,
+}
+
+impl TryFrom<&Llsd> for Label {
+    type Error = LlsdError;
+
+    fn try_from(llsd: &Llsd) -> Result<Self, LlsdError> {
+        match llsd {
+            Llsd::String(text) => Ok(Label { text: text.clone() }),
+            other => Label::from_llsd(other),
+        }
+    }
+}
+
+#[test]
+fn traits_only_leaves_std_impls_to_the_user() {
+    let label = Label { text: "hi".into() };
+    assert_eq!(Label::try_from(&Llsd::String("hi".into())).unwrap(), label);
+    assert_eq!(Label::try_from(&label.to_llsd()).unwrap(), label);
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct Graphics {
+    quality: u32,
+    #[llsd(validate(range = "0.0..=1.0"))]
+    gamma: f64,
+    shadows: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct Settings {
+    name: String,
+    nickname: Option<String>,
+    graphics: Graphics,
+    #[llsd(default)]
+    tags: Vec<String>,
+}
+
+#[test]
+fn update_from_llsd_keeps_unspecified_fields() {
+    let settings = Settings {
+        name: "default".into(),
+        nickname: None,
+        graphics: Graphics {
+            quality: 2,
+            gamma: 0.5,
+            shadows: false,
+        },
+        tags: vec!["a".into()],
+    };
+    let delta = Llsd::map()
+        .insert("nickname", "nick")
+        .unwrap()
+        .insert("graphics", Llsd::map().insert("shadows", true).unwrap())
+        .unwrap();
+    let mut current = settings.clone();
+    current.update_from_llsd(&delta).unwrap();
+    let mut expected = settings.clone();
+    expected.nickname = Some("nick".into());
+    expected.graphics.shadows = true;
+    assert_eq!(current, expected);
+
+    let delta = Llsd::map()
+        .insert("graphics", Llsd::map().insert("gamma", 2.0).unwrap())
+        .unwrap();
+    let err = current.update_from_llsd(&delta).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "validation failed at graphics.gamma: must be in range 0.0..=1.0"
+    );
+    assert!(current.update_from_llsd(&Llsd::Integer(1)).is_err());
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct Session {
+    #[llsd(date_format = "epoch_seconds")]
+    started: chrono::DateTime<chrono::Utc>,
+    #[llsd(date_format = "epoch_millis")]
+    last_seen: Option<chrono::DateTime<chrono::Utc>>,
+    #[llsd(date_format = "rfc3339")]
+    expires: chrono::DateTime<chrono::Utc>,
+    #[llsd(date_format = "%Y-%m-%d %H:%M:%S")]
+    logged: chrono::NaiveDateTime,
+    #[llsd(date_format = "seconds")]
+    idle: chrono::TimeDelta,
+    #[llsd(date_format = "millis")]
+    timeout: std::time::Duration,
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn date_formats_round_trip() {
+    use chrono::{DateTime, TimeDelta};
+
+    let started = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let session = Session {
+        started,
+        last_seen: Some(started + TimeDelta::milliseconds(1500)),
+        expires: started + TimeDelta::hours(1),
+        logged: started.naive_utc(),
+        idle: TimeDelta::seconds(90),
+        timeout: std::time::Duration::from_millis(250),
+    };
+    let l = Llsd::from(&session);
+    assert_eq!(l["started"], Llsd::Integer(1_700_000_000));
+    assert_eq!(l["last_seen"], Llsd::Real(1_700_000_001_500.0));
+    assert_eq!(l["expires"], Llsd::from("2023-11-14T23:13:20+00:00"));
+    assert_eq!(l["logged"], Llsd::from("2023-11-14 22:13:20"));
+    assert_eq!(l["idle"], Llsd::Integer(90));
+    assert_eq!(l["timeout"], Llsd::Integer(250));
+    assert_eq!(Session::try_from(&l).unwrap(), session);
+
+    let l = l.insert("logged", "yesterday").unwrap();
+    let err = Session::try_from(l).unwrap_err();
+    assert!(
+        err.to_string().starts_with("invalid value at logged: "),
+        "{err}"
+    );
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+struct CompactUpdate {
+    #[llsd(uuid_as_binary)]
+    id: Uuid,
+    #[llsd(uuid_as_binary, default)]
+    owner: Option<Uuid>,
+    #[llsd(compact_date)]
+    updated: chrono::DateTime<chrono::Utc>,
+    #[llsd(compact_date, default)]
+    expires: Option<chrono::DateTime<chrono::Utc>>,
+    #[llsd(compact_date)]
+    ttl: chrono::TimeDelta,
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn compact_encodings_round_trip() {
+    let update = CompactUpdate {
+        id: Uuid::from_u128(0x6c1f_5d2e_9a7b_4c3d_8e0f_1a2b_3c4d_5e6f),
+        owner: Some(Uuid::from_u128(7)),
+        updated: chrono::DateTime::from_timestamp(1_700_000_000, 125_000_000).unwrap(),
+        expires: None,
+        ttl: chrono::TimeDelta::seconds(30),
+    };
+    let l = Llsd::from(&update);
+    assert_eq!(l["id"], Llsd::Binary(update.id.as_bytes().to_vec()));
+    assert_eq!(
+        l["owner"],
+        Llsd::Binary(Uuid::from_u128(7).as_bytes().to_vec())
+    );
+    assert_eq!(l["updated"], Llsd::Real(1_700_000_000.125));
+    assert_eq!(l["ttl"], Llsd::Real(30.0));
+    assert_eq!(l.get("expires"), None);
+    let encoded = llsd_rs::binary::to_vec(&l).unwrap();
+    let decoded = llsd_rs::binary::from_slice(&encoded).unwrap();
+    assert_eq!(CompactUpdate::try_from(decoded).unwrap(), update);
+
+    // The natural encodings are read as well.
+    let natural = Llsd::map()
+        .insert("id", Llsd::Uuid(update.id))
+        .unwrap()
+        .insert("owner", Uuid::from_u128(7).to_string())
+        .unwrap()
+        .insert("updated", Llsd::Date(update.updated))
+        .unwrap()
+        .insert("ttl", 30)
+        .unwrap();
+    assert_eq!(CompactUpdate::try_from(&natural).unwrap(), update);
+
+    let short = natural.insert("id", Llsd::Binary(vec![1, 2, 3])).unwrap();
+    let err = CompactUpdate::try_from(&short).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid length at id: expected 16, found 3"
+    );
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "derive")]
+use llsd_rs::{Llsd, LlsdFromTo};
+
+fn default_region() -> String {
+    "Ahern".into()
+}
+
+#[derive(Debug, Clone, PartialEq, LlsdFromTo)]
+#[llsd(builder)]
+pub struct LoginRequest {
+    first_name: String,
+    last_name: String,
+    #[llsd(rename = "passwd")]
+    password: String,
+    #[llsd(default = default_region)]
+    start: String,
+    channel: Option<String>,
+    #[llsd(default)]
+    options: Vec<String>,
+    #[llsd(skip)]
+    attempt: u32,
+}
+
+#[test]
+fn builder_fills_optional_and_default_fields() {
+    let request = LoginRequest::builder()
+        .first_name("Ann")
+        .last_name("Resident")
+        .password("$1$abc")
+        .channel("test")
+        .build()
+        .unwrap();
+    assert_eq!(
+        request,
+        LoginRequest {
+            first_name: "Ann".into(),
+            last_name: "Resident".into(),
+            password: "$1$abc".into(),
+            start: "Ahern".into(),
+            channel: Some("test".into()),
+            options: Vec::new(),
+            attempt: 0,
+        }
+    );
+    let llsd: Llsd = request.into();
+    assert_eq!(llsd.get("passwd"), Some(&Llsd::from("$1$abc")));
+}
+
+#[test]
+fn builder_reports_missing_required_fields() {
+    let err = LoginRequestBuilder::new()
+        .first_name("Ann")
+        .password("x")
+        .build()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "missing field at last_name");
+}
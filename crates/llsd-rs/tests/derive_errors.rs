@@ -0,0 +1,72 @@
+#![cfg(feature = "derive")]
+use llsd_rs::Uuid;
+use llsd_rs::{Llsd, LlsdError, LlsdErrorKind, LlsdFrom, PathSegment};
+
+#[allow(dead_code)]
+#[derive(Debug, LlsdFrom)]
+struct LoginEnvelope {
+    login_response: LoginResponse,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, LlsdFrom)]
+struct LoginResponse {
+    inventory: Vec<Folder>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, LlsdFrom)]
+struct Folder {
+    folder_id: Uuid,
+}
+
+fn folder(id: Llsd) -> Llsd {
+    Llsd::map().insert("folder_id", id).unwrap()
+}
+
+#[test]
+fn nested_type_error_reports_full_path() {
+    let mut folders = vec![folder(Uuid::nil().into()); 3];
+    folders.push(folder("not-a-uuid".into()));
+    let llsd = Llsd::map()
+        .insert(
+            "login_response",
+            Llsd::map().insert("inventory", folders).unwrap(),
+        )
+        .unwrap();
+
+    let err = LoginEnvelope::try_from(&llsd).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type at login_response.inventory[3].folder_id: expected uuid, found string"
+    );
+    let err = LlsdError::from_anyhow(err);
+    assert_eq!(
+        err.path(),
+        &[
+            PathSegment::Key("login_response".into()),
+            PathSegment::Key("inventory".into()),
+            PathSegment::Index(3),
+            PathSegment::Key("folder_id".into()),
+        ]
+    );
+}
+
+#[test]
+fn missing_nested_field_reports_path() {
+    let llsd = Llsd::map()
+        .insert(
+            "login_response",
+            Llsd::map().insert("inventory", vec![Llsd::map()]).unwrap(),
+        )
+        .unwrap();
+    let err = LlsdError::from_anyhow(LoginEnvelope::try_from(&llsd).unwrap_err());
+    assert_eq!(err.kind(), &LlsdErrorKind::MissingField);
+    assert_eq!(err.path_string(), "login_response.inventory[0].folder_id");
+}
+
+#[test]
+fn non_map_input_reports_found_type() {
+    let err = Folder::try_from(&Llsd::Integer(1)).unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: expected map, found integer");
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "messages")]
+use llsd_rs::Uuid;
+use llsd_rs::messages::*;
+use llsd_rs::{FromLlsd, IntoLlsd, Llsd, xml};
+
+const OBJECT_MEDIA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<llsd><map>
+  <key>object_id</key><uuid>8e6e0c44-6d3b-4cd5-a5ab-2b0e0bd7f1a4</uuid>
+  <key>object_media_version</key><string>x-mv:0000000003/a2a7e0c4-3d2d-4cda-8a1b-6d1f3e8b9c0d</string>
+  <key>object_media_data</key>
+  <array>
+    <map>
+      <key>alt_image_enable</key><boolean>0</boolean>
+      <key>auto_loop</key><boolean>1</boolean>
+      <key>auto_play</key><boolean>1</boolean>
+      <key>auto_scale</key><boolean>1</boolean>
+      <key>auto_zoom</key><boolean>0</boolean>
+      <key>controls</key><integer>0</integer>
+      <key>current_url</key><string>https://example.com/</string>
+      <key>first_click_interact</key><boolean>0</boolean>
+      <key>height_pixels</key><integer>512</integer>
+      <key>home_url</key><string>https://example.com/</string>
+      <key>perms_control</key><integer>7</integer>
+      <key>perms_interact</key><integer>7</integer>
+      <key>whitelist</key><array><string>example.com</string></array>
+      <key>whitelist_enable</key><boolean>1</boolean>
+      <key>width_pixels</key><integer>1024</integer>
+    </map>
+    <undef />
+  </array>
+</map></llsd>"#;
+
+#[test]
+fn object_media_faces() {
+    let llsd = xml::from_str(OBJECT_MEDIA).unwrap();
+    let media = ObjectMediaResponse::from_llsd(&llsd).unwrap();
+    assert_eq!(media.object_media_data.len(), 2);
+    let face = media.object_media_data[0].as_ref().unwrap();
+    assert_eq!(face.width_pixels, 1024);
+    assert_eq!(face.whitelist, ["example.com"]);
+    assert!(media.object_media_data[1].is_none());
+    assert_eq!(
+        ObjectMediaResponse::from_llsd(&media.to_llsd()).unwrap(),
+        media
+    );
+}
+
+#[test]
+fn display_names_dates_and_missing_lists() {
+    let llsd = llsd_rs::notation::from_str(
+        "{'agents':[{'id':u3c115e51-04f4-523c-9fa6-98aff1034730,'username':'avatar.resident',\
+         'display_name':'Avatar','legacy_first_name':'Avatar','legacy_last_name':'Resident',\
+         'is_display_name_default':true,'display_name_next_update':d\"2024-05-01T12:00:00Z\"}]}",
+        8,
+    )
+    .unwrap();
+    let names = DisplayNamesResponse::from_llsd(&llsd).unwrap();
+    let agent = &names.agents[0];
+    assert_eq!(agent.username, "avatar.resident");
+    assert!(agent.display_name_next_update.is_some());
+    assert!(agent.display_name_expires.is_none());
+    assert!(names.bad_ids.is_empty());
+}
+
+#[test]
+fn inventory_item_errors_name_the_path() {
+    let owner = Uuid::from_u128(1);
+    let request = FetchInventoryRequest {
+        agent_id: owner,
+        items: vec![InventoryItemRef {
+            owner_id: owner,
+            item_id: Uuid::from_u128(2),
+        }],
+    };
+    let llsd = request.to_llsd();
+    assert_eq!(llsd["items"][0]["item_id"], Llsd::Uuid(Uuid::from_u128(2)));
+
+    let permissions = Permissions {
+        creator_id: owner,
+        owner_id: owner,
+        last_owner_id: owner,
+        group_id: Uuid::nil(),
+        is_owner_group: false,
+        base_mask: i32::MAX,
+        owner_mask: i32::MAX,
+        group_mask: 0,
+        everyone_mask: 0,
+        next_owner_mask: 0x82000,
+    };
+    let item = InventoryItem {
+        item_id: Uuid::from_u128(2),
+        parent_id: Uuid::from_u128(3),
+        asset_id: Some(Uuid::from_u128(4)),
+        name: "Box".into(),
+        desc: String::new(),
+        r#type: 6,
+        inv_type: 6,
+        flags: 0,
+        created_at: 1_700_000_000,
+        sale_info: SaleInfo {
+            sale_price: 10,
+            sale_type: 0,
+        },
+        permissions,
+    };
+    let mut response = FetchInventoryResponse {
+        agent_id: owner,
+        items: vec![item],
+    }
+    .to_llsd();
+    assert_eq!(response["items"][0]["type"], Llsd::Integer(6));
+    assert!(FetchInventoryResponse::from_llsd(&response).is_ok());
+
+    response["items"][0]["permissions"]["owner_mask"] = Llsd::map();
+    let err = FetchInventoryResponse::from_llsd(&response).unwrap_err();
+    assert_eq!(err.path_string(), "items[0].permissions.owner_mask");
+}
+
+#[test]
+fn event_queue_dispatch() {
+    let finish = TeleportFinish {
+        info: vec![TeleportFinishInfo {
+            agent_id: Uuid::from_u128(1),
+            location_id: 4,
+            sim_ip: [192, 0, 2, 10],
+            sim_port: 13005,
+            region_handle: (256_000 << 32) | 256_256,
+            seed_capability: "https://sim.example/seed".into(),
+            sim_access: 13,
+            teleport_flags: [0, 0, 0, 16],
+        }],
+    };
+    let reply = EventQueueResponse {
+        id: 7,
+        events: vec![Event::new(&finish)],
+    };
+    let llsd = reply.to_llsd();
+    assert_eq!(llsd["events"][0]["message"], Llsd::from("TeleportFinish"));
+    assert_eq!(
+        llsd["events"][0]["body"]["Info"][0]["RegionHandle"],
+        Llsd::Binary(((256_000u64 << 32) | 256_256).to_be_bytes().to_vec())
+    );
+
+    let decoded = EventQueueResponse::from_llsd(&llsd).unwrap();
+    let event = &decoded.events[0];
+    assert!(event.decode::<EstablishAgentCommunication>().is_none());
+    assert_eq!(event.decode::<TeleportFinish>().unwrap().unwrap(), finish);
+
+    let bad = Event {
+        message: "TeleportFinish".into(),
+        body: Llsd::map(),
+    };
+    let err = bad.decode::<TeleportFinish>().unwrap().unwrap_err();
+    assert_eq!(err.path_string(), "body.Info");
+}
+
+#[test]
+fn registry_lists_message_types() {
+    assert!(MESSAGES.contains(&("FetchInventory2", "FetchInventoryResponse")));
+    assert!(MESSAGES.iter().all(|(_, ty)| *ty != "MediaEntry"));
+    assert_eq!(EventQueueRequest::NAME, "EventQueueGet");
+}
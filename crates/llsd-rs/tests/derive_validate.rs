@@ -0,0 +1,104 @@
+#![cfg(feature = "derive")]
+use llsd_rs::{Llsd, LlsdError, LlsdErrorKind, LlsdFrom};
+
+fn check_even(v: &i32) -> Result<(), String> {
+    if v % 2 == 0 {
+        Ok(())
+    } else {
+        Err(format!("{v} is not even"))
+    }
+}
+
+#[derive(Debug, PartialEq, LlsdFrom)]
+struct Limits {
+    #[llsd(validate(range = "1..=100"))]
+    percent: u32,
+    #[llsd(validate(min_len = 3))]
+    name: String,
+    #[llsd(validate(non_empty))]
+    tags: Vec<String>,
+    #[llsd(validate = "check_even")]
+    even: i32,
+    #[llsd(default, validate(range = "0.0..1.0"))]
+    ratio: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, LlsdFrom)]
+struct Outer {
+    limits: Vec<Limits>,
+}
+
+fn valid() -> Llsd {
+    Llsd::map()
+        .insert("percent", 50u32)
+        .unwrap()
+        .insert("name", "abc")
+        .unwrap()
+        .insert("tags", vec!["t"])
+        .unwrap()
+        .insert("even", 4)
+        .unwrap()
+}
+
+fn set(llsd: Llsd, key: &str, value: impl Into<Llsd>) -> Llsd {
+    llsd.insert(key, value).unwrap()
+}
+
+fn validation_error(llsd: &Llsd) -> LlsdError {
+    LlsdError::from_anyhow(Limits::try_from(llsd).unwrap_err())
+}
+
+#[test]
+fn valid_input_passes() {
+    let parsed = Limits::try_from(valid()).unwrap();
+    assert_eq!(parsed.percent, 50);
+    assert_eq!(parsed.ratio, None);
+}
+
+#[test]
+fn range_violation_names_field() {
+    let err = validation_error(&set(valid(), "percent", 0u32));
+    assert_eq!(
+        err.kind(),
+        &LlsdErrorKind::Validation("must be in range 1..=100".into())
+    );
+    assert_eq!(
+        err.to_string(),
+        "validation failed at percent: must be in range 1..=100"
+    );
+}
+
+#[test]
+fn length_and_emptiness_checks() {
+    let err = validation_error(&set(valid(), "name", "ab"));
+    assert_eq!(
+        err.to_string(),
+        "validation failed at name: length must be at least 3"
+    );
+    let err = validation_error(&set(valid(), "tags", Llsd::array()));
+    assert_eq!(
+        err.to_string(),
+        "validation failed at tags: must not be empty"
+    );
+}
+
+#[test]
+fn custom_validator_and_option_inner_value() {
+    let err = validation_error(&set(valid(), "even", 3));
+    assert_eq!(err.to_string(), "validation failed at even: 3 is not even");
+    let err = validation_error(&set(valid(), "ratio", 1.5));
+    assert_eq!(err.path_string(), "ratio");
+    assert!(Limits::try_from(&set(valid(), "ratio", 0.5)).is_ok());
+}
+
+#[test]
+fn nested_validation_is_path_qualified() {
+    let outer = Llsd::map()
+        .insert("limits", vec![valid(), set(valid(), "percent", 101u32)])
+        .unwrap();
+    let err = Outer::try_from(&outer).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "validation failed at limits[1].percent: must be in range 1..=100"
+    );
+}